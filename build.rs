@@ -0,0 +1,34 @@
+//! Compiles the commit and the contributors into the credits screen
+
+use std::process::Command;
+
+fn git(args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+fn main() {
+    let hash = git(&["rev-parse", "--short", "HEAD"]).unwrap_or_else(|| "unknown".to_string());
+
+    // Authors in the order they first committed
+    let mut contributors: Vec<String> = Vec::new();
+    for author in git(&["log", "--reverse", "--format=%an"])
+        .unwrap_or_default()
+        .lines()
+    {
+        if !contributors.iter().any(|known| known == author) {
+            contributors.push(author.to_string());
+        }
+    }
+
+    println!("cargo:rustc-env=GIT_HASH={hash}");
+    println!(
+        "cargo:rustc-env=GIT_CONTRIBUTORS={}",
+        contributors.join(";")
+    );
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs");
+}
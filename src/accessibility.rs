@@ -0,0 +1,242 @@
+use crate::settings::Settings;
+use crate::{AppState, GameSet, GameState, SonarDetections, Submarine};
+use bevy::prelude::*;
+#[cfg(not(target_arch = "wasm32"))]
+use std::process::Command;
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::mpsc::{self, Receiver, Sender};
+#[cfg(not(target_arch = "wasm32"))]
+use std::thread;
+
+const DEPTH_MILESTONE_INTERVAL: f32 = 5.0; // Announce every 5 m of depth
+const OXYGEN_WARNING_LEVELS: [f32; 3] = [50.0, 25.0, 10.0];
+const CONTACT_CONFIDENCE: f32 = 0.5; // Weaker contacts are not announced
+#[cfg(not(target_arch = "wasm32"))]
+const MAX_QUEUED_ANNOUNCEMENTS: usize = 3; // Older messages are dropped when speech falls behind
+
+/// A short spoken message describing a critical state change
+#[derive(Event, Clone)]
+pub struct Announcement(pub String);
+
+/// Speech turned on by `--tts` for this run only, leaving the saved setting alone
+#[derive(Resource, Default)]
+pub struct TtsOverride(pub bool);
+
+/// Handle to the background speech thread, which exists only if the platform
+/// has a speech command
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Resource)]
+pub struct TtsBackend {
+    sender: Sender<String>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl TtsBackend {
+    /// Queues a message to be spoken after any already waiting
+    pub fn say(&self, message: &str) {
+        let _ = self.sender.send(message.to_string());
+    }
+}
+
+/// Browsers speak through the Web Speech API, which queues utterances itself
+#[cfg(target_arch = "wasm32")]
+#[derive(Resource)]
+pub struct TtsBackend;
+
+#[cfg(target_arch = "wasm32")]
+impl TtsBackend {
+    pub fn say(&self, message: &str) {
+        let synthesis = web_sys::window().and_then(|window| window.speech_synthesis().ok());
+        let utterance = web_sys::SpeechSynthesisUtterance::new_with_text(message).ok();
+        if let (Some(synthesis), Some(utterance)) = (synthesis, utterance) {
+            synthesis.speak(&utterance);
+        }
+    }
+}
+
+/// Last announced values, used to detect threshold crossings
+#[derive(Resource, Default)]
+struct AnnouncementTracker {
+    depth_milestone: i32,
+    oxygen_level: usize, // Number of oxygen warning thresholds already crossed
+    had_contact: bool,
+}
+
+pub struct AccessibilityPlugin;
+
+impl Plugin for AccessibilityPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<Announcement>()
+            .init_resource::<AnnouncementTracker>()
+            .init_resource::<TtsOverride>()
+            .add_systems(Startup, start_tts_backend)
+            .add_systems(OnEnter(AppState::InGame), reset_announcement_tracker)
+            .add_systems(
+                Update,
+                (announcement_watch_system, speak_announcements_system)
+                    .chain()
+                    .in_set(GameSet::Presentation),
+            );
+    }
+}
+
+fn reset_announcement_tracker(mut commands: Commands) {
+    commands.insert_resource(AnnouncementTracker::default());
+}
+
+#[cfg(target_arch = "wasm32")]
+fn start_tts_backend(mut commands: Commands) {
+    commands.insert_resource(TtsBackend);
+}
+
+/// Looks for the speech command once, so each message doesn't probe for it again
+#[cfg(not(target_arch = "wasm32"))]
+fn start_tts_backend(mut commands: Commands) {
+    let program = match speech_program() {
+        Ok(program) => program,
+        Err(err) => {
+            warn!(
+                "No text-to-speech backend found ({}), announcements disabled",
+                err
+            );
+            return;
+        }
+    };
+    let (sender, receiver) = mpsc::channel();
+    let spawned = thread::Builder::new()
+        .name("tts".to_string())
+        .spawn(move || speech_worker(program, receiver));
+    match spawned {
+        Ok(_) => commands.insert_resource(TtsBackend { sender }),
+        Err(err) => warn!("Text-to-speech unavailable: {}", err),
+    }
+}
+
+/// Speaks queued messages one at a time using the platform's speech command
+#[cfg(not(target_arch = "wasm32"))]
+fn speech_worker(program: &'static str, receiver: Receiver<String>) {
+    while let Ok(first) = receiver.recv() {
+        // Skip to the most recent messages if several piled up while speaking
+        let queue: Vec<String> = std::iter::once(first).chain(receiver.try_iter()).collect();
+        let skip = queue.len().saturating_sub(MAX_QUEUED_ANNOUNCEMENTS);
+        for message in queue.into_iter().skip(skip) {
+            if let Err(err) = speech_command(program, &message).status() {
+                warn!("Text-to-speech failed: {}", err);
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn speech_program() -> std::io::Result<&'static str> {
+    Ok("say")
+}
+
+#[cfg(target_os = "windows")]
+fn speech_program() -> std::io::Result<&'static str> {
+    Ok("powershell")
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows", target_arch = "wasm32")))]
+fn speech_program() -> std::io::Result<&'static str> {
+    // Prefer speech-dispatcher, fall back to espeak-ng/espeak
+    ["spd-say", "espeak-ng", "espeak"]
+        .into_iter()
+        .find(|program| Command::new(program).arg("--version").output().is_ok())
+        .ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "install speech-dispatcher or espeak-ng",
+            )
+        })
+}
+
+#[cfg(target_os = "windows")]
+fn speech_command(program: &str, message: &str) -> Command {
+    let script = format!(
+        "Add-Type -AssemblyName System.Speech; (New-Object System.Speech.Synthesis.SpeechSynthesizer).Speak('{}')",
+        message.replace('\'', "''")
+    );
+    let mut command = Command::new(program);
+    command.args(["-NoProfile", "-Command", &script]);
+    command
+}
+
+#[cfg(not(any(target_os = "windows", target_arch = "wasm32")))]
+fn speech_command(program: &str, message: &str) -> Command {
+    let mut command = Command::new(program);
+    if program == "spd-say" {
+        command.arg("--wait");
+    }
+    command.arg(message);
+    command
+}
+
+/// Watches game state for depth milestones, oxygen warnings and new sonar contacts
+fn announcement_watch_system(
+    submarine_query: Query<&Transform, With<Submarine>>,
+    game_state: Res<GameState>,
+    sonar_detections: Res<SonarDetections>,
+    mut tracker: ResMut<AnnouncementTracker>,
+    mut announcements: EventWriter<Announcement>,
+) {
+    if let Ok(transform) = submarine_query.single() {
+        let depth = (-transform.translation.y).max(0.0);
+        let milestone = (depth / DEPTH_MILESTONE_INTERVAL).floor() as i32;
+        if milestone != tracker.depth_milestone {
+            let meters = milestone.max(tracker.depth_milestone) as f32 * DEPTH_MILESTONE_INTERVAL;
+            if milestone == 0 {
+                announcements.write(Announcement("Surfaced".to_string()));
+            } else {
+                announcements.write(Announcement(format!("Passing {:.0} meters", meters)));
+            }
+            tracker.depth_milestone = milestone;
+        }
+    }
+
+    // Warn once per threshold as oxygen drops, re-arm when it recovers
+    let level = OXYGEN_WARNING_LEVELS
+        .iter()
+        .filter(|&&threshold| game_state.oxygen <= threshold)
+        .count();
+    if level > tracker.oxygen_level {
+        let threshold = OXYGEN_WARNING_LEVELS[level - 1];
+        announcements.write(Announcement(format!(
+            "Oxygen below {:.0} percent",
+            threshold
+        )));
+    }
+    tracker.oxygen_level = level;
+
+    // Faint returns under jamming aren't worth calling out
+    let contact = sonar_detections
+        .contacts
+        .iter()
+        .find(|contact| contact.confidence >= CONTACT_CONFIDENCE);
+    let has_contact = contact.is_some();
+    if let (Some(contact), false) = (contact, tracker.had_contact) {
+        // Sonar display angles put the bow at the top; convert to a clockwise relative bearing
+        let bearing = (90.0 - contact.angle.to_degrees()).rem_euclid(360.0);
+        announcements.write(Announcement(format!(
+            "Contact detected, bearing {:03.0}",
+            bearing
+        )));
+    }
+    tracker.had_contact = has_contact;
+}
+
+fn speak_announcements_system(
+    settings: Res<Settings>,
+    tts_override: Res<TtsOverride>,
+    backend: Option<Res<TtsBackend>>,
+    mut announcements: EventReader<Announcement>,
+) {
+    let enabled = settings.accessibility.tts_enabled || tts_override.0;
+    let Some(backend) = backend.filter(|_| enabled) else {
+        announcements.clear();
+        return;
+    };
+    for Announcement(message) in announcements.read() {
+        backend.say(message);
+    }
+}
@@ -0,0 +1,180 @@
+//! How sound carries through the lake: detonations are heard only once their
+//! sound has travelled to the listener, and distance strips out their highs,
+//! so far-off blasts arrive late as a dull rumble.
+
+use crate::settings::Settings;
+use crate::{AppState, GameSet};
+use bevy::audio::{
+    AddAudioSource, Decodable, DefaultSpatialScale, Source, SpatialListener, SpatialScale, Volume,
+};
+use bevy::prelude::*;
+use std::f32::consts::TAU;
+use std::time::Duration;
+
+// Real sound in water travels at about 1500 m/s, which would make delays and
+// Doppler shifts across the lake too small to notice. A much slower speed
+// exaggerates both into cues.
+pub const SOUND_SPEED: f32 = 120.0; // m/s
+const SPATIAL_SCALE: f32 = 0.05; // Meters to spatial audio units, so sounds carry tens of meters
+const SAMPLE_RATE: u32 = 44_100;
+const NEAR_CUTOFF: f32 = 4000.0; // Hz, low-pass cutoff right next to a blast
+const FAR_CUTOFF: f32 = 120.0; // Hz, the cutoff never drops below this
+const CUTOFF_FALLOFF: f32 = 60.0; // Meters over which the cutoff falls by a factor of e
+const BOOM_LENGTH: f32 = 1.5; // Seconds, before the extra rumble of distance
+const RUMBLE_PER_METER: f32 = 0.01; // Seconds of extra rumble per meter away
+
+/// An explosion or eruption at `position`; its sound reaches the listener after
+/// the propagation delay
+#[derive(Event, Clone, Copy)]
+pub struct Detonation {
+    pub position: Vec3,
+    pub loudness: f32, // 0..1
+}
+
+/// Blast sounds still on their way to the listener
+#[derive(Resource, Default)]
+struct SoundsInTransit(Vec<(f32, Vec3, Boom)>); // Seconds left, position, sound
+
+/// Low-passed noise burst over a low thump
+#[derive(Asset, TypePath, Clone, Copy)]
+struct Boom {
+    cutoff: f32, // Hz
+    length: f32, // Seconds
+    volume: f32, // 0..1
+}
+
+impl Boom {
+    /// How a blast of `loudness` sounds from `distance` meters away
+    fn heard_from(distance: f32, loudness: f32) -> Self {
+        Self {
+            cutoff: (NEAR_CUTOFF * (-distance / CUTOFF_FALLOFF).exp()).max(FAR_CUTOFF),
+            length: BOOM_LENGTH + distance * RUMBLE_PER_METER,
+            volume: loudness,
+        }
+    }
+}
+
+struct BoomDecoder {
+    boom: Boom,
+    sample: u32,
+    samples: u32,
+    noise: u32,    // Xorshift state
+    filtered: f32, // One-pole low-pass output
+}
+
+impl Iterator for BoomDecoder {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.sample >= self.samples {
+            return None;
+        }
+        let t = self.sample as f32 / SAMPLE_RATE as f32;
+        self.sample += 1;
+
+        self.noise ^= self.noise << 13;
+        self.noise ^= self.noise >> 17;
+        self.noise ^= self.noise << 5;
+        let white = self.noise as f32 / u32::MAX as f32 * 2.0 - 1.0;
+        let alpha = 1.0 - (-TAU * self.boom.cutoff / SAMPLE_RATE as f32).exp();
+        self.filtered += alpha * (white - self.filtered);
+        // Filtering removes power; make it back up so distant booms aren't just quieter
+        let noise = self.filtered * ((2.0 - alpha) / alpha).sqrt().min(8.0);
+
+        let envelope = (t / 0.01).min(1.0) * (-4.0 * t / self.boom.length).exp();
+        let thump = (TAU * 40.0 * t).sin() * (-6.0 * t).exp();
+        Some((self.boom.volume * envelope * (0.5 * noise + thump)).clamp(-1.0, 1.0))
+    }
+}
+
+impl Source for BoomDecoder {
+    fn current_frame_len(&self) -> Option<usize> {
+        Some((self.samples - self.sample) as usize)
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        Some(Duration::from_secs_f32(self.boom.length))
+    }
+}
+
+impl Decodable for Boom {
+    type DecoderItem = f32;
+    type Decoder = BoomDecoder;
+
+    fn decoder(&self) -> BoomDecoder {
+        BoomDecoder {
+            boom: *self,
+            sample: 0,
+            samples: (self.length * SAMPLE_RATE as f32) as u32,
+            noise: 0x9E37_79B9,
+            filtered: 0.0,
+        }
+    }
+}
+
+pub struct AcousticsPlugin;
+
+impl Plugin for AcousticsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_audio_source::<Boom>()
+            .add_event::<Detonation>()
+            .insert_resource(DefaultSpatialScale(SpatialScale::new(SPATIAL_SCALE)))
+            .init_resource::<SoundsInTransit>()
+            .add_systems(OnEnter(AppState::InGame), reset_sounds_in_transit)
+            .add_systems(Update, propagation_system.in_set(GameSet::Presentation));
+    }
+}
+
+fn reset_sounds_in_transit(mut commands: Commands) {
+    commands.insert_resource(SoundsInTransit::default());
+}
+
+/// Sends each detonation's sound on its way and plays those that have arrived
+fn propagation_system(
+    mut commands: Commands,
+    mut detonations: EventReader<Detonation>,
+    mut in_transit: ResMut<SoundsInTransit>,
+    mut booms: ResMut<Assets<Boom>>,
+    listener_query: Query<&GlobalTransform, With<SpatialListener>>,
+    settings: Res<Settings>,
+    time: Res<Time>,
+) {
+    let listener = listener_query
+        .single()
+        .map(|transform| transform.translation())
+        .unwrap_or(Vec3::ZERO);
+    for detonation in detonations.read() {
+        let distance = detonation.position.distance(listener);
+        in_transit.0.push((
+            distance / SOUND_SPEED,
+            detonation.position,
+            Boom::heard_from(distance, detonation.loudness),
+        ));
+    }
+
+    for sound in in_transit.0.iter_mut() {
+        sound.0 -= time.delta_secs();
+    }
+    let (arrived, travelling): (Vec<_>, Vec<_>) = std::mem::take(&mut in_transit.0)
+        .into_iter()
+        .partition(|sound| sound.0 <= 0.0);
+    in_transit.0 = travelling;
+    for (_, position, boom) in arrived {
+        commands.spawn((
+            AudioPlayer(booms.add(boom)),
+            PlaybackSettings::DESPAWN
+                .with_spatial(true)
+                .with_volume(Volume::Linear(settings.audio.effects_volume)),
+            Transform::from_translation(position),
+            StateScoped(AppState::InGame),
+        ));
+    }
+}
@@ -0,0 +1,223 @@
+//! Sounds of life aboard: ventilation hum, distant machinery, the sonar room's
+//! pings and now and then the crew talking. They play in full from the control
+//! room and are ducked to a murmur while the camera is outside the hull.
+
+use crate::settings::Settings;
+use crate::{AppState, CameraMode, PauseState};
+use bevy::audio::{AddAudioSource, Decodable, Source, Volume};
+use bevy::prelude::*;
+use std::f32::consts::{PI, TAU};
+use std::time::Duration;
+
+const SAMPLE_RATE: u32 = 44_100;
+const LOOP_LENGTH: u32 = 20; // Seconds; every layer repeats after this long
+const EXTERIOR_DUCKING: f32 = 0.15; // Share of the interior level heard from outside
+const FADE_RATE: f32 = 1.0; // Gain change per second when the view changes
+const PING_INTERVAL: f32 = 5.0; // Seconds between sonar room pings
+/// Start and length in seconds, and voice pitch in Hz, of each exchange in the loop
+const CHATTER: [(f32, f32, f32); 3] = [(3.0, 1.4, 140.0), (9.5, 0.9, 190.0), (15.0, 1.8, 120.0)];
+
+/// One layer of the interior soundscape
+#[derive(Component, Clone, Copy, PartialEq, Debug)]
+enum AmbienceLayer {
+    Ventilation,
+    Machinery,
+    SonarRoom,
+    Chatter,
+}
+
+impl AmbienceLayer {
+    const ALL: [AmbienceLayer; 4] = [
+        AmbienceLayer::Ventilation,
+        AmbienceLayer::Machinery,
+        AmbienceLayer::SonarRoom,
+        AmbienceLayer::Chatter,
+    ];
+
+    /// Level of the layer in the control room
+    fn level(self) -> f32 {
+        match self {
+            AmbienceLayer::Ventilation => 0.25,
+            AmbienceLayer::Machinery => 0.3,
+            AmbienceLayer::SonarRoom => 0.2,
+            AmbienceLayer::Chatter => 0.15,
+        }
+    }
+}
+
+/// Endless synthesized audio for one layer; tones are whole numbers of Hz so
+/// the loop repeats without a click
+#[derive(Asset, TypePath, Clone, Copy)]
+struct AmbienceLoop(AmbienceLayer);
+
+struct AmbienceDecoder {
+    layer: AmbienceLayer,
+    sample: u32,
+    noise: u32,    // Xorshift state
+    filtered: f32, // Low-passed noise for the ventilation
+}
+
+impl AmbienceDecoder {
+    fn white_noise(&mut self) -> f32 {
+        self.noise ^= self.noise << 13;
+        self.noise ^= self.noise >> 17;
+        self.noise ^= self.noise << 5;
+        self.noise as f32 / u32::MAX as f32 * 2.0 - 1.0
+    }
+}
+
+impl Iterator for AmbienceDecoder {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let t = self.sample as f32 / SAMPLE_RATE as f32;
+        self.sample = (self.sample + 1) % (LOOP_LENGTH * SAMPLE_RATE);
+        let value = match self.layer {
+            AmbienceLayer::Ventilation => {
+                // Rushing air over the mains hum of the fans
+                let white = self.white_noise();
+                self.filtered += 0.05 * (white - self.filtered);
+                2.5 * self.filtered + 0.3 * (TAU * 60.0 * t).sin()
+            }
+            AmbienceLayer::Machinery => {
+                // A pump throbbing somewhere aft, with a clank every few seconds
+                let throb = (0.6 + 0.4 * (TAU * 0.5 * t).sin()) * (TAU * 47.0 * t).sin();
+                let clank_time = t % 2.5;
+                let clank = (-clank_time * 30.0).exp()
+                    * ((TAU * 310.0 * t).sin() + 0.6 * (TAU * 523.0 * t).sin());
+                0.7 * throb + 0.3 * clank
+            }
+            AmbienceLayer::SonarRoom => {
+                // An active ping and its fainter return
+                let since_ping = t % PING_INTERVAL;
+                let ping = |start: f32, volume: f32| {
+                    let local = since_ping - start;
+                    if local < 0.0 {
+                        0.0
+                    } else {
+                        volume * (-local * 3.0).exp() * (TAU * 1400.0 * t).sin()
+                    }
+                };
+                ping(0.0, 1.0) + ping(1.2, 0.25)
+            }
+            AmbienceLayer::Chatter => {
+                // Muffled voices: a buzzy tone chopped into syllables
+                CHATTER
+                    .iter()
+                    .filter(|(start, length, _)| t >= *start && t < start + length)
+                    .map(|(start, length, pitch)| {
+                        let local = t - start;
+                        let phrase = (PI * local / length).sin();
+                        let syllables = (PI * 5.0 * local).sin().abs();
+                        let phase = TAU * pitch * t;
+                        let voice =
+                            phase.sin() + 0.5 * (2.0 * phase).sin() + 0.3 * (3.0 * phase).sin();
+                        phrase * syllables * voice / 1.8
+                    })
+                    .sum()
+            }
+        };
+        Some(value.clamp(-1.0, 1.0))
+    }
+}
+
+impl Source for AmbienceDecoder {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+impl Decodable for AmbienceLoop {
+    type DecoderItem = f32;
+    type Decoder = AmbienceDecoder;
+
+    fn decoder(&self) -> AmbienceDecoder {
+        AmbienceDecoder {
+            layer: self.0,
+            sample: 0,
+            noise: 0x6C07_8965,
+            filtered: 0.0,
+        }
+    }
+}
+
+/// How far the ambience is brought up, from ducked (exterior) to full (control room)
+#[derive(Resource)]
+struct AmbienceLevel(f32);
+
+impl Default for AmbienceLevel {
+    fn default() -> Self {
+        Self(EXTERIOR_DUCKING)
+    }
+}
+
+pub struct AmbiencePlugin;
+
+impl Plugin for AmbiencePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_audio_source::<AmbienceLoop>()
+            .init_resource::<AmbienceLevel>()
+            .add_systems(OnEnter(AppState::InGame), start_ambience)
+            .add_systems(OnEnter(PauseState::Paused), pause_ambience)
+            .add_systems(OnExit(PauseState::Paused), resume_ambience)
+            .add_systems(Update, ambience_system.run_if(in_state(AppState::InGame)));
+    }
+}
+
+fn start_ambience(mut commands: Commands, mut sources: ResMut<Assets<AmbienceLoop>>) {
+    commands.insert_resource(AmbienceLevel::default());
+    for layer in AmbienceLayer::ALL {
+        commands.spawn((
+            AudioPlayer(sources.add(AmbienceLoop(layer))),
+            PlaybackSettings::LOOP.with_volume(Volume::Linear(0.0)),
+            layer,
+            StateScoped(AppState::InGame),
+        ));
+    }
+}
+
+fn pause_ambience(sink_query: Query<&AudioSink, With<AmbienceLayer>>) {
+    for sink in sink_query.iter() {
+        sink.pause();
+    }
+}
+
+fn resume_ambience(sink_query: Query<&AudioSink, With<AmbienceLayer>>) {
+    for sink in sink_query.iter() {
+        sink.play();
+    }
+}
+
+/// Fades the ambience up in the control room and ducks it outside
+fn ambience_system(
+    camera_mode: Res<State<CameraMode>>,
+    settings: Res<Settings>,
+    mut level: ResMut<AmbienceLevel>,
+    mut sink_query: Query<(&AmbienceLayer, &mut AudioSink)>,
+    time: Res<Time>,
+) {
+    let target = match camera_mode.get() {
+        CameraMode::ControlRoom | CameraMode::Periscope | CameraMode::Tactical => 1.0,
+        CameraMode::Exterior | CameraMode::Bridge | CameraMode::Cutscene => EXTERIOR_DUCKING,
+    };
+    let step = FADE_RATE * time.delta_secs();
+    level.0 += (target - level.0).clamp(-step, step);
+
+    for (layer, mut sink) in sink_query.iter_mut() {
+        sink.set_volume(Volume::Linear(
+            layer.level() * level.0 * settings.audio.effects_volume,
+        ));
+    }
+}
@@ -0,0 +1,159 @@
+//! Handles to the font, meshes and materials the core world is built from.
+//! They are made before the first frame and again on each load, while the
+//! loading screen is up, and every spawner shares them instead of adding its
+//! own copies. Features keep their own handles in their modules.
+
+use crate::tables::Tables;
+use crate::HULL_END_OFFSET;
+use bevy::prelude::*;
+use std::collections::HashMap;
+
+/// Shared handles for the submarine, terrain, water, fish and bubbles. Shapes
+/// that come in many sizes are unit meshes scaled by their transform.
+#[derive(Resource, Clone)]
+pub struct GameAssets {
+    pub font: Handle<Font>,
+    pub hull_mesh: Handle<Mesh>,
+    pub hull_end_mesh: Handle<Mesh>,
+    pub wing_mesh: Handle<Mesh>,
+    pub rudder_mesh: Handle<Mesh>,
+    pub hull_material: Handle<StandardMaterial>, // Repainted by hull paint loot
+    pub wing_material: Handle<StandardMaterial>,
+    pub seabed_mesh: Handle<Mesh>,
+    pub seabed_material: Handle<StandardMaterial>,
+    pub water_mesh: Handle<Mesh>, // Displaced by the waves every frame
+    pub water_material: Handle<StandardMaterial>,
+    pub cone_mesh: Handle<Mesh>, // Unit cone for mountains, peaks and foothills
+    pub mountain_material: Handle<StandardMaterial>,
+    pub foothill_material: Handle<StandardMaterial>,
+    pub bubble_mesh: Handle<Mesh>, // Unit sphere
+    pub bubble_material: Handle<StandardMaterial>,
+    pub fish: HashMap<String, (Handle<Mesh>, Handle<StandardMaterial>)>, // By species name
+    pub encounter_mesh: Handle<Mesh>, // Unit across, scaled to each species
+    pub encounters: HashMap<String, Handle<StandardMaterial>>, // By species name
+}
+
+/// The menus, console and overlays spawned at startup share the assets too,
+/// before any dive has loaded
+impl FromWorld for GameAssets {
+    fn from_world(world: &mut World) -> Self {
+        world.resource_scope(|world, mut meshes: Mut<Assets<Mesh>>| {
+            world.resource_scope(|world, mut materials: Mut<Assets<StandardMaterial>>| {
+                GameAssets::new(
+                    world.resource::<AssetServer>(),
+                    &mut meshes,
+                    &mut materials,
+                    world.resource::<Tables>(),
+                )
+            })
+        })
+    }
+}
+
+impl GameAssets {
+    pub fn new(
+        asset_server: &AssetServer,
+        meshes: &mut Assets<Mesh>,
+        materials: &mut Assets<StandardMaterial>,
+        tables: &Tables,
+    ) -> Self {
+        let fish = tables
+            .fish
+            .iter()
+            .map(|species| {
+                let (red, green, blue) = species.color;
+                let mesh = meshes.add(Sphere::new(species.radius));
+                let material = materials.add(StandardMaterial {
+                    base_color: Color::srgb(red, green, blue),
+                    ..default()
+                });
+                (species.name.clone(), (mesh, material))
+            })
+            .collect();
+        let encounters = tables
+            .encounters
+            .iter()
+            .map(|species| {
+                let (red, green, blue) = species.color;
+                let material = materials.add(StandardMaterial {
+                    base_color: Color::srgb(red, green, blue),
+                    perceptual_roughness: 0.8,
+                    ..default()
+                });
+                (species.name.clone(), material)
+            })
+            .collect();
+        Self {
+            font: asset_server.load("fonts/NotoSans-Regular.ttf"),
+            hull_mesh: meshes.add(Cylinder::new(0.7, 2.0 * HULL_END_OFFSET)),
+            hull_end_mesh: meshes.add(Sphere::new(0.7)),
+            wing_mesh: meshes.add(Cuboid::new(0.8, 0.2, 0.4)),
+            rudder_mesh: meshes.add(Cuboid::new(0.2, 0.6, 0.4)),
+            hull_material: materials.add(StandardMaterial {
+                base_color: Color::srgb(0.3, 0.3, 0.5),
+                ..default()
+            }),
+            wing_material: materials.add(StandardMaterial {
+                base_color: Color::srgb(0.8, 0.2, 0.2),
+                ..default()
+            }),
+            // Ocean floor - exactly same size as water surface
+            seabed_mesh: meshes.add(Plane3d::default().mesh().size(1800.0, 1800.0)),
+            seabed_material: materials.add(StandardMaterial {
+                base_color: Color::srgb(0.6, 0.5, 0.3),
+                perceptual_roughness: 0.9,
+                metallic: 0.0,
+                reflectance: 0.02,
+                ..default()
+            }),
+            water_mesh: meshes.add(
+                Plane3d::default()
+                    .mesh()
+                    .size(2000.0, 2000.0)
+                    .subdivisions(120),
+            ),
+            water_material: materials.add(StandardMaterial {
+                base_color: Color::srgba(0.05, 0.2, 0.4, 0.85),
+                alpha_mode: AlphaMode::Blend,
+                metallic: 0.1,
+                perceptual_roughness: 0.1,
+                reflectance: 0.08,
+                ior: 1.33, // Water's index of refraction
+                specular_transmission: if crate::supports_specular_transmission() {
+                    0.6
+                } else {
+                    0.0
+                },
+                thickness: 3.0,
+                cull_mode: None, // Make water surface visible from both sides
+                ..default()
+            }),
+            cone_mesh: meshes.add(Cone::new(1.0, 1.0)),
+            mountain_material: materials.add(StandardMaterial {
+                base_color: Color::srgb(0.5, 0.4, 0.3),
+                perceptual_roughness: 0.9,
+                metallic: 0.0,
+                reflectance: 0.02,
+                ..default()
+            }),
+            foothill_material: materials.add(StandardMaterial {
+                base_color: Color::srgb(0.35, 0.3, 0.2),
+                perceptual_roughness: 0.95,
+                metallic: 0.0,
+                reflectance: 0.02,
+                ..default()
+            }),
+            bubble_mesh: meshes.add(Sphere::new(1.0)),
+            bubble_material: materials.add(StandardMaterial {
+                base_color: Color::srgba(0.8, 0.9, 1.0, 0.45),
+                alpha_mode: AlphaMode::Blend,
+                perceptual_roughness: 0.3,
+                reflectance: 0.1,
+                ..default()
+            }),
+            fish,
+            encounter_mesh: meshes.add(Sphere::new(0.5)),
+            encounters,
+        }
+    }
+}
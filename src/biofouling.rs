@@ -0,0 +1,311 @@
+//! Biofouling: weed and barnacles slowly take hold on the hull over a long
+//! patrol. A foul hull drags, costing top speed, and the rough water over it
+//! makes the boat louder. The yard at the pier by the start scrubs it clean
+//! for a fee in points while the boat lies alongside, surfaced and stopped;
+//! or the boat can drift with its screw stopped through a tide race and let
+//! the current scour it for free, if more slowly.
+
+use crate::callouts::{CalloutKind, CrewCallout};
+use crate::classes::SubmarineClass;
+use crate::controls::{HelmInput, HelmState, Telegraph};
+use crate::currents::{flow_at, TideRace};
+use crate::{
+    AppState, GameAssets, GameMode, GameSet, GameState, Submarine, Surfaced, HULL_END_OFFSET,
+};
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+const GROWTH_RATE: f32 = 1.0 / 1800.0; // Growth per second; half an hour fouls the hull fully
+const DRAG: f32 = 0.3; // Share of its speed a fully fouled hull loses
+const WARN_GROWTH: f32 = 0.5; // The crew mention the hull once growth passes this
+const SCOUR_RATE: f32 = 0.02; // Growth scoured off per second drifting in a race
+const YARD_RATE: f32 = 0.1; // Growth scrubbed off per second by the yard
+const YARD_PRICE: f32 = 200.0; // Points the yard charges to clean a fully fouled hull
+const PIER_POSITION: Vec3 = Vec3::new(6.0, 0.0, 0.0); // Middle of the pier, beside the start
+const PIER_SIZE: Vec3 = Vec3::new(2.0, 1.0, 14.0);
+const BERTH_RANGE: f32 = 9.0; // Meters from the pier the yard can reach the boat
+const STOPPED_SPEED: f32 = 0.5; // Meters per second that still counts as lying stopped
+const COAT_OPACITY: f32 = 0.85; // Opacity of the growth coat on a fully fouled hull
+const COAT_RADIUS: f32 = 0.73; // Just outside the hull
+
+/// How much has grown on the hull, from 0 (clean) to 1 (fully fouled)
+#[derive(Resource, Default)]
+pub struct Biofouling {
+    pub growth: f32,
+    pub cleaning: Option<Cleaning>,
+    warned: bool,
+    bill: f32, // Points owed to the yard not yet charged
+}
+
+/// What is cleaning the hull this frame
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Cleaning {
+    Yard,
+    Current,
+    Unpaid, // Alongside the pier, but with no points to pay the yard
+}
+
+impl Biofouling {
+    /// Share of its speed the boat keeps through the growth
+    pub fn speed_factor(&self) -> f32 {
+        1.0 - DRAG * self.growth
+    }
+}
+
+/// The pier, and the translucent shell over the hull that thickens as growth
+/// takes hold. Made once; there is only ever one boat, so it has the coat
+/// material to itself.
+#[derive(Resource)]
+struct BiofoulingAssets {
+    pier_mesh: Handle<Mesh>,
+    pier_material: Handle<StandardMaterial>,
+    coat: Handle<StandardMaterial>,
+    coat_body_mesh: Handle<Mesh>,
+    coat_end_mesh: Handle<Mesh>,
+}
+
+impl FromWorld for BiofoulingAssets {
+    fn from_world(world: &mut World) -> Self {
+        let mut meshes = world.resource_mut::<Assets<Mesh>>();
+        let pier_mesh = meshes.add(Cuboid::from_size(PIER_SIZE));
+        let coat_body_mesh = meshes.add(Cylinder::new(COAT_RADIUS, 2.0 * HULL_END_OFFSET));
+        let coat_end_mesh = meshes.add(Sphere::new(COAT_RADIUS));
+        let mut materials = world.resource_mut::<Assets<StandardMaterial>>();
+        Self {
+            pier_mesh,
+            pier_material: materials.add(StandardMaterial {
+                base_color: Color::srgb(0.45, 0.35, 0.25),
+                perceptual_roughness: 0.9,
+                ..default()
+            }),
+            coat: materials.add(StandardMaterial {
+                base_color: Color::srgba(0.25, 0.35, 0.15, 0.0),
+                alpha_mode: AlphaMode::Blend,
+                perceptual_roughness: 1.0,
+                ..default()
+            }),
+            coat_body_mesh,
+            coat_end_mesh,
+        }
+    }
+}
+
+#[derive(Component)]
+struct BiofoulingText;
+
+pub struct BiofoulingPlugin;
+
+impl Plugin for BiofoulingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Biofouling>()
+            .add_systems(
+                OnEnter(AppState::InGame),
+                (
+                    reset_biofouling.before(crate::save::apply_pending_load),
+                    (spawn_pier, spawn_growth_coat)
+                        .after(crate::setup)
+                        .run_if(not_time_trial),
+                    spawn_biofouling_text,
+                ),
+            )
+            .add_systems(
+                Update,
+                (
+                    (growth_system, drag_system)
+                        .chain()
+                        .after(crate::submarine_movement)
+                        .in_set(GameSet::Simulation)
+                        .run_if(not_time_trial),
+                    (growth_coat_system, biofouling_text_system).in_set(GameSet::Presentation),
+                ),
+            );
+    }
+
+    fn finish(&self, app: &mut App) {
+        app.init_resource::<BiofoulingAssets>();
+    }
+}
+
+/// Time trials are too short for growth to matter, and race from a clear start
+fn not_time_trial(mode: Res<GameMode>) -> bool {
+    *mode != GameMode::TimeTrial
+}
+
+/// New dives start with a clean hull; a loaded save puts its growth back after
+fn reset_biofouling(mut biofouling: ResMut<Biofouling>) {
+    *biofouling = Biofouling::default();
+}
+
+fn spawn_pier(mut commands: Commands, assets: Res<BiofoulingAssets>) {
+    commands.spawn((
+        Mesh3d(assets.pier_mesh.clone()),
+        MeshMaterial3d(assets.pier_material.clone()),
+        Transform::from_translation(PIER_POSITION),
+        RigidBody::Fixed,
+        Collider::cuboid(PIER_SIZE.x / 2.0, PIER_SIZE.y / 2.0, PIER_SIZE.z / 2.0),
+        StateScoped(AppState::InGame),
+    ));
+}
+
+/// Gives the submarine a shell of growth just outside its hull, clear to start
+fn spawn_growth_coat(
+    mut commands: Commands,
+    assets: Res<BiofoulingAssets>,
+    class: Res<SubmarineClass>,
+    submarine_query: Query<Entity, With<Submarine>>,
+) {
+    let Ok(submarine) = submarine_query.single() else {
+        return;
+    };
+    // Sized for the patrol boat's hull, then scaled with it
+    let size = Vec3::splat(class.hull);
+    commands.entity(submarine).with_children(|parent| {
+        parent.spawn((
+            Mesh3d(assets.coat_body_mesh.clone()),
+            MeshMaterial3d(assets.coat.clone()),
+            Transform::from_rotation(Quat::from_rotation_x(std::f32::consts::FRAC_PI_2))
+                .with_scale(size),
+        ));
+        for z in [HULL_END_OFFSET, -HULL_END_OFFSET] {
+            parent.spawn((
+                Mesh3d(assets.coat_end_mesh.clone()),
+                MeshMaterial3d(assets.coat.clone()),
+                Transform::from_xyz(0.0, 0.0, z * class.hull).with_scale(size),
+            ));
+        }
+    });
+}
+
+fn spawn_biofouling_text(mut commands: Commands, assets: Res<GameAssets>) {
+    commands.spawn((
+        Text::new(""),
+        TextFont {
+            font_size: 16.0,
+            font: assets.font.clone(),
+            ..default()
+        },
+        TextColor(Color::srgb(0.6, 0.85, 0.4)),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Percent(59.0),
+            left: Val::Px(16.0),
+            ..default()
+        },
+        BiofoulingText,
+        StateScoped(AppState::InGame),
+    ));
+}
+
+/// Grows the fouling, and cleans it off at the pier or in a race
+#[allow(clippy::too_many_arguments)]
+fn growth_system(
+    mut biofouling: ResMut<Biofouling>,
+    mut game_state: ResMut<GameState>,
+    mut callouts: EventWriter<CrewCallout>,
+    surfaced: Res<Surfaced>,
+    helm_input: Res<HelmInput>,
+    helm_state: Res<HelmState>,
+    submarine_query: Query<(&Transform, &Velocity), With<Submarine>>,
+    race_query: Query<(&Transform, &TideRace), Without<Submarine>>,
+    time: Res<Time>,
+) {
+    let Ok((transform, velocity)) = submarine_query.single() else {
+        return;
+    };
+    let delta = time.delta_secs();
+    let position = transform.translation;
+    let screw_stopped = helm_input.throttle == 0.0 && helm_state.telegraph == Telegraph::Stop;
+    let alongside = surfaced.0
+        && Vec2::new(velocity.linvel.x, velocity.linvel.z).length() < STOPPED_SPEED
+        && Vec2::new(position.x - PIER_POSITION.x, position.z - PIER_POSITION.z).length()
+            < BERTH_RANGE;
+    let in_race = flow_at(race_query.iter(), position) != Vec3::ZERO;
+
+    biofouling.cleaning = None;
+    if biofouling.growth > 0.0 && alongside {
+        if game_state.score == 0 {
+            biofouling.cleaning = Some(Cleaning::Unpaid);
+        } else {
+            // The yard bills by the growth it scrubs off, in whole points as it goes
+            let scrubbed = (YARD_RATE * delta).min(biofouling.growth);
+            biofouling.growth -= scrubbed;
+            biofouling.bill += scrubbed * YARD_PRICE;
+            let charged = biofouling.bill.floor();
+            biofouling.bill -= charged;
+            game_state.score = game_state.score.saturating_sub(charged as u32);
+            biofouling.cleaning = Some(Cleaning::Yard);
+        }
+    } else if biofouling.growth > 0.0 && in_race && screw_stopped {
+        biofouling.growth = (biofouling.growth - SCOUR_RATE * delta).max(0.0);
+        biofouling.cleaning = Some(Cleaning::Current);
+    } else {
+        biofouling.growth = (biofouling.growth + GROWTH_RATE * delta).min(1.0);
+    }
+
+    if biofouling.growth >= WARN_GROWTH && !biofouling.warned {
+        biofouling.warned = true;
+        callouts.write(CrewCallout {
+            kind: CalloutKind::Failure,
+            text: "Hull's fouling up, she's losing speed. The yard at the pier can scrub her"
+                .to_string(),
+        });
+    } else if biofouling.growth < WARN_GROWTH {
+        biofouling.warned = false;
+    }
+}
+
+/// The growth holds back the boat's headway through the water
+fn drag_system(
+    biofouling: Res<Biofouling>,
+    mut submarine_query: Query<&mut Velocity, With<Submarine>>,
+) {
+    let Ok(mut velocity) = submarine_query.single_mut() else {
+        return;
+    };
+    let factor = biofouling.speed_factor();
+    velocity.linvel.x *= factor;
+    velocity.linvel.z *= factor;
+}
+
+fn growth_coat_system(
+    biofouling: Res<Biofouling>,
+    assets: Res<BiofoulingAssets>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let alpha = biofouling.growth * COAT_OPACITY;
+    if let Some(material) = materials.get_mut(&assets.coat) {
+        if material.base_color.alpha() != alpha {
+            material.base_color.set_alpha(alpha);
+        }
+    }
+}
+
+fn biofouling_text_system(
+    biofouling: Res<Biofouling>,
+    mut text_query: Query<&mut Text, With<BiofoulingText>>,
+) {
+    let Ok(mut text) = text_query.single_mut() else {
+        return;
+    };
+    let status = match biofouling.cleaning {
+        Some(Cleaning::Yard) => " - yard scrubbing the hull",
+        Some(Cleaning::Current) => " - current scouring the hull",
+        Some(Cleaning::Unpaid) => " - no points to pay the yard",
+        None => "",
+    };
+    let line = if biofouling.growth >= 0.1 || biofouling.cleaning.is_some() {
+        let filled = ((biofouling.growth * 20.0) as usize).min(20);
+        format!(
+            "Hull growth [{}{}] -{:.0}% speed{}",
+            "#".repeat(filled),
+            "-".repeat(20 - filled),
+            (1.0 - biofouling.speed_factor()) * 100.0,
+            status
+        )
+    } else {
+        String::new()
+    };
+    if **text != line {
+        **text = line;
+    }
+}
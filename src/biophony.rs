@@ -0,0 +1,378 @@
+//! Biophony: noisy wildlife. Beds of snapping shrimp crackle on the floor all
+//! the time, and schools of croakers drum in choruses that come and go. Their
+//! racket clutters the sonar with false echoes and smears real returns near
+//! them, and it drowns out the boat: a submarine quieter than the wildlife
+//! around it can't be heard by hunting predators, which lose it unless it is
+//! close enough to see.
+
+use crate::stealth::SubmarineNoise;
+use crate::tables::Tables;
+use crate::{
+    calculate_fish_angle, calculate_sonar_position, AppState, GameAssets, GameMode, GameSet,
+    SonarContact, SonarDetections, Submarine, Tuning, WorldSeed,
+};
+use bevy::prelude::*;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::f32::consts::TAU;
+
+const SHRIMP_RADIUS: f32 = 12.0; // Meters the crackle of a bed carries
+const SHRIMP_LOUDNESS: f32 = 0.5;
+const CROAKER_RADIUS: f32 = 10.0;
+const CROAKER_LOUDNESS: f32 = 0.8; // While the school is drumming
+const CHORUS_PERIOD: f32 = 30.0; // Seconds from the start of one chorus to the next
+const CHORUS_TIME: f32 = 18.0; // Seconds each chorus lasts
+const CROAKER_DRIFT: f32 = 0.6; // Meters per second a school wanders at
+const CROAKER_ROAM: f32 = 15.0; // Meters a school wanders from its home
+const SHRIMP_PER_BED: usize = 30;
+const CROAKERS_PER_SCHOOL: usize = 12;
+const CLUTTER_ECHOES: f32 = 6.0; // False echoes from a source at full loudness
+const CLUTTER_WANDER: f32 = 0.2; // Radians real returns near a source wander by
+pub const SIGHT_RANGE: f32 = 8.0; // Predators still see a masked boat this close
+const SEA_FLOOR: f32 = -20.5;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum NoiseKind {
+    ShrimpBed,
+    Croakers,
+}
+
+impl NoiseKind {
+    fn label(self) -> &'static str {
+        match self {
+            NoiseKind::ShrimpBed => "snapping shrimp",
+            NoiseKind::Croakers => "croaker chorus",
+        }
+    }
+}
+
+/// Wildlife making a racket around its position
+#[derive(Component)]
+pub struct NoiseSource {
+    pub kind: NoiseKind,
+    pub radius: f32,
+    phase: f32, // Seconds into the chorus cycle at the start of the dive
+}
+
+impl NoiseSource {
+    /// How loud the source is at time `t`, from 0 to 1
+    pub fn loudness(&self, t: f32) -> f32 {
+        match self.kind {
+            NoiseKind::ShrimpBed => SHRIMP_LOUDNESS,
+            NoiseKind::Croakers if (t + self.phase) % CHORUS_PERIOD < CHORUS_TIME => {
+                CROAKER_LOUDNESS
+            }
+            NoiseKind::Croakers => 0.0,
+        }
+    }
+
+    /// How much of the sound at `distance` meters away is this source's
+    fn level(&self, distance: f32, t: f32) -> f32 {
+        self.loudness(t) * (1.0 - distance / self.radius).max(0.0)
+    }
+}
+
+/// How loud the wildlife is at a point, and what is loudest there
+pub fn masking_at<'a>(
+    sources: impl IntoIterator<Item = (&'a Transform, &'a NoiseSource)>,
+    point: Vec3,
+    t: f32,
+) -> (f32, Option<NoiseKind>) {
+    sources
+        .into_iter()
+        .map(|(transform, source)| {
+            (
+                source.level(transform.translation.distance(point), t),
+                source.kind,
+            )
+        })
+        .filter(|(level, _)| *level > 0.0)
+        .max_by(|a, b| a.0.total_cmp(&b.0))
+        .map_or((0.0, None), |(level, kind)| (level, Some(kind)))
+}
+
+/// A croaker school's wandering round its home
+#[derive(Component)]
+struct CroakerSchool {
+    home: Vec3,
+    heading: f32,
+}
+
+#[derive(Component)]
+struct MaskingText;
+
+/// Shrimp and croakers, made once and shared by every bed and school
+#[derive(Resource)]
+struct WildlifeAssets {
+    shrimp_mesh: Handle<Mesh>,
+    shrimp_material: Handle<StandardMaterial>,
+    croaker_mesh: Handle<Mesh>,
+    croaker_material: Handle<StandardMaterial>,
+}
+
+impl FromWorld for WildlifeAssets {
+    fn from_world(world: &mut World) -> Self {
+        let mut meshes = world.resource_mut::<Assets<Mesh>>();
+        let shrimp_mesh = meshes.add(Sphere::new(0.08));
+        let croaker_mesh = meshes.add(Capsule3d::new(0.12, 0.4));
+        let mut materials = world.resource_mut::<Assets<StandardMaterial>>();
+        Self {
+            shrimp_mesh,
+            shrimp_material: materials.add(StandardMaterial {
+                base_color: Color::srgb(0.9, 0.5, 0.45),
+                perceptual_roughness: 0.7,
+                ..default()
+            }),
+            croaker_mesh,
+            croaker_material: materials.add(StandardMaterial {
+                base_color: Color::srgb(0.55, 0.5, 0.35),
+                metallic: 0.3,
+                ..default()
+            }),
+        }
+    }
+}
+
+pub struct BiophonyPlugin;
+
+impl Plugin for BiophonyPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            OnEnter(AppState::InGame),
+            (
+                spawn_wildlife
+                    .after(crate::setup)
+                    .run_if(|mode: Res<GameMode>| *mode != GameMode::TimeTrial),
+                spawn_masking_text,
+            ),
+        )
+        .add_systems(
+            Update,
+            (
+                croaker_system.in_set(GameSet::Simulation),
+                clutter_system
+                    .after(crate::sonar_detection_system)
+                    .in_set(GameSet::Detection),
+                masking_text_system.in_set(GameSet::Presentation),
+            ),
+        );
+    }
+
+    fn finish(&self, app: &mut App) {
+        app.init_resource::<WildlifeAssets>();
+    }
+}
+
+fn spawn_wildlife(
+    mut commands: Commands,
+    assets: Res<WildlifeAssets>,
+    seed: Res<WorldSeed>,
+    tables: Res<Tables>,
+) {
+    let mut rng = StdRng::seed_from_u64(seed.0 ^ 0x4e4f_4953);
+    let scatter = |rng: &mut StdRng| {
+        let angle = rng.gen::<f32>() * TAU;
+        let distance = rng.gen_range(40.0..220.0);
+        Vec3::new(angle.cos() * distance, 0.0, angle.sin() * distance)
+    };
+
+    for _ in 0..tables.spawns.shrimp_beds {
+        let center = scatter(&mut rng).with_y(SEA_FLOOR);
+        commands
+            .spawn((
+                Transform::from_translation(center),
+                Visibility::default(),
+                NoiseSource {
+                    kind: NoiseKind::ShrimpBed,
+                    radius: SHRIMP_RADIUS,
+                    phase: 0.0,
+                },
+                StateScoped(AppState::InGame),
+            ))
+            .with_children(|bed| {
+                for _ in 0..SHRIMP_PER_BED {
+                    let spot = Vec2::from_angle(rng.gen::<f32>() * TAU) * rng.gen::<f32>() * 3.0;
+                    bed.spawn((
+                        Mesh3d(assets.shrimp_mesh.clone()),
+                        MeshMaterial3d(assets.shrimp_material.clone()),
+                        Transform::from_xyz(spot.x, 0.05, spot.y),
+                    ));
+                }
+            });
+    }
+
+    for _ in 0..tables.spawns.croaker_schools {
+        let home = scatter(&mut rng).with_y(rng.gen_range(-16.0..-6.0));
+        commands
+            .spawn((
+                Transform::from_translation(home),
+                Visibility::default(),
+                NoiseSource {
+                    kind: NoiseKind::Croakers,
+                    radius: CROAKER_RADIUS,
+                    phase: rng.gen_range(0.0..CHORUS_PERIOD),
+                },
+                CroakerSchool {
+                    home,
+                    heading: rng.gen::<f32>() * TAU,
+                },
+                StateScoped(AppState::InGame),
+            ))
+            .with_children(|school| {
+                for _ in 0..CROAKERS_PER_SCHOOL {
+                    let offset = Vec3::new(
+                        rng.gen_range(-1.5..1.5),
+                        rng.gen_range(-0.6..0.6),
+                        rng.gen_range(-1.5..1.5),
+                    );
+                    school.spawn((
+                        Mesh3d(assets.croaker_mesh.clone()),
+                        MeshMaterial3d(assets.croaker_material.clone()),
+                        Transform::from_translation(offset)
+                            .with_rotation(Quat::from_rotation_x(std::f32::consts::FRAC_PI_2)),
+                    ));
+                }
+            });
+    }
+}
+
+fn spawn_masking_text(mut commands: Commands, assets: Res<GameAssets>) {
+    commands.spawn((
+        Text::new(""),
+        TextFont {
+            font_size: 16.0,
+            font: assets.font.clone(),
+            ..default()
+        },
+        TextColor(Color::srgb(0.7, 0.6, 1.0)),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Percent(68.0),
+            left: Val::Px(16.0),
+            ..default()
+        },
+        MaskingText,
+        StateScoped(AppState::InGame),
+    ));
+}
+
+/// Croakers wander slowly, turning back towards home when they stray
+fn croaker_system(mut school_query: Query<(&mut CroakerSchool, &mut Transform)>, time: Res<Time>) {
+    let delta = time.delta_secs();
+    for (mut school, mut transform) in school_query.iter_mut() {
+        let away = transform.translation - school.home;
+        let turn = if away.length() > CROAKER_ROAM {
+            0.8
+        } else {
+            0.15 * (time.elapsed_secs() * 0.3 + school.home.x).sin()
+        };
+        school.heading += turn * delta;
+        let direction = Vec3::new(school.heading.cos(), 0.0, school.heading.sin());
+        transform.translation += direction * CROAKER_DRIFT * delta;
+        transform.look_to(direction, Vec3::Y);
+    }
+}
+
+/// The racket smears returns from near a source and throws false echoes
+/// round it, more the louder it is
+fn clutter_system(
+    mut detections: ResMut<SonarDetections>,
+    tuning: Res<Tuning>,
+    seed: Res<WorldSeed>,
+    submarine_query: Query<&Transform, With<Submarine>>,
+    source_query: Query<(Entity, &Transform, &NoiseSource)>,
+    target_query: Query<&Transform>,
+    time: Res<Time>,
+) {
+    let Ok(submarine) = submarine_query.single() else {
+        return;
+    };
+    let t = time.elapsed_secs();
+    let to_display = |position: Vec3, wander: f32| {
+        let rel = position - submarine.translation;
+        let angle = calculate_fish_angle(submarine.rotation.inverse() * rel) + wander;
+        let (x, y) = calculate_sonar_position(angle, rel.length(), tuning.sonar_range);
+        (angle, x, y)
+    };
+
+    for contact in detections.contacts.iter_mut() {
+        let Some(position) = contact
+            .target
+            .and_then(|target| target_query.get(target).ok())
+            .map(|transform| transform.translation)
+        else {
+            continue;
+        };
+        let (level, _) = masking_at(
+            source_query
+                .iter()
+                .map(|(_, transform, source)| (transform, source)),
+            position,
+            t,
+        );
+        if level > 0.0 {
+            let wander = (t * 5.0 + position.z).sin() * CLUTTER_WANDER * level;
+            (contact.angle, contact.x, contact.y) = to_display(position, wander);
+            contact.confidence *= 1.0 - 0.6 * level;
+        }
+    }
+
+    for (entity, transform, source) in source_query.iter() {
+        let loudness = source.loudness(t);
+        if loudness <= 0.0
+            || transform.translation.distance(submarine.translation)
+                > tuning.sonar_range + source.radius
+        {
+            continue;
+        }
+        let mut rng = StdRng::seed_from_u64(seed.0 ^ entity.to_bits() ^ (t * 4.0) as u64);
+        for _ in 0..(CLUTTER_ECHOES * loudness).round() as usize {
+            let spot = Vec2::from_angle(rng.gen::<f32>() * TAU) * rng.gen::<f32>() * source.radius;
+            let position = transform.translation + Vec3::new(spot.x, 0.0, spot.y);
+            if position.distance(submarine.translation) > tuning.sonar_range {
+                continue;
+            }
+            let (angle, x, y) = to_display(position, 0.0);
+            detections.contacts.push(SonarContact {
+                x,
+                y,
+                angle,
+                confidence: rng.gen_range(0.1..0.4),
+                target: None,
+                depth: -position.y,
+            });
+        }
+    }
+}
+
+fn masking_text_system(
+    noise: Res<SubmarineNoise>,
+    submarine_query: Query<&Transform, With<Submarine>>,
+    source_query: Query<(&Transform, &NoiseSource)>,
+    mut text_query: Query<&mut Text, With<MaskingText>>,
+    time: Res<Time>,
+) {
+    let Ok(mut text) = text_query.single_mut() else {
+        return;
+    };
+    let line = match submarine_query.single().map(|submarine| {
+        masking_at(
+            source_query.iter(),
+            submarine.translation,
+            time.elapsed_secs(),
+        )
+    }) {
+        Ok((level, Some(kind))) if level >= noise.0 => {
+            format!("MASKED - the {} drowns out the boat", kind.label())
+        }
+        Ok((level, Some(kind))) => format!(
+            "Wildlife noise ({}) masks {:.0}%; the boat makes {:.0}%",
+            kind.label(),
+            level * 100.0,
+            noise.0 * 100.0
+        ),
+        _ => String::new(),
+    };
+    if **text != line {
+        **text = line;
+    }
+}
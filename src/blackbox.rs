@@ -0,0 +1,311 @@
+//! Black box: a rolling record of the last minute of a dive. When the hull
+//! gives out or takes a heavy blow, the record is written to `incidents/` and a
+//! post-mortem panel charts depth and speed up to the moment it happened.
+
+use crate::{storage, AppState, BallastState, GameAssets, GameSet, GameState, Submarine};
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::Velocity;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+const INCIDENT_DIR: &str = "incidents";
+const SAMPLE_INTERVAL: f32 = 0.25;
+const RECORD_LENGTH: f32 = 60.0; // Seconds kept in the buffer
+const SEVERE_DAMAGE: f32 = 25.0; // Hull percent lost within DAMAGE_WINDOW that counts as an incident
+const DAMAGE_WINDOW: f32 = 5.0;
+const CHART_WIDTH: f32 = 480.0;
+const CHART_HEIGHT: f32 = 60.0;
+
+/// The submarine's state at one moment
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct BlackBoxSample {
+    pub time: f32, // Seconds into the dive
+    pub depth: f32,
+    pub speed: f32,
+    pub health: f32,
+    pub oxygen: f32,
+    pub ballast_fill: f32,
+    pub compressed_air: f32,
+    pub electricity: f32,
+    pub position: [f32; 3],
+}
+
+/// What went wrong, and the minute leading up to it
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Incident {
+    pub cause: String,
+    pub timestamp: u64, // Unix time the incident was recorded
+    pub samples: Vec<BlackBoxSample>,
+}
+
+#[derive(Resource, Default)]
+struct BlackBox {
+    samples: VecDeque<BlackBoxSample>,
+    elapsed: f32,
+    next_sample: f32,
+    last_incident: Option<f32>, // Dive time of the last severe damage incident
+    lost: bool,                 // The hull has given out; nothing more to record
+}
+
+/// The most recent incident, kept for the post-mortem panel
+#[derive(Resource, Default)]
+struct LastIncident(Option<Incident>);
+
+#[derive(Component)]
+struct PostMortemPanel;
+
+pub struct BlackBoxPlugin;
+
+impl Plugin for BlackBoxPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BlackBox>()
+            .init_resource::<LastIncident>()
+            .add_systems(OnEnter(AppState::InGame), reset_black_box)
+            .add_systems(
+                Update,
+                (
+                    black_box_system.in_set(GameSet::Detection),
+                    post_mortem_toggle_system.in_set(GameSet::Input),
+                ),
+            );
+    }
+}
+
+fn reset_black_box(mut commands: Commands) {
+    commands.insert_resource(BlackBox::default());
+    commands.insert_resource(LastIncident::default());
+}
+
+#[allow(clippy::too_many_arguments)]
+fn black_box_system(
+    mut commands: Commands,
+    mut black_box: ResMut<BlackBox>,
+    mut last_incident: ResMut<LastIncident>,
+    submarine_query: Query<(&Transform, &Velocity), With<Submarine>>,
+    panel_query: Query<Entity, With<PostMortemPanel>>,
+    game_state: Res<GameState>,
+    ballast_state: Res<BallastState>,
+    assets: Res<GameAssets>,
+    time: Res<Time>,
+) {
+    if black_box.lost {
+        return;
+    }
+    black_box.elapsed += time.delta_secs();
+    if black_box.elapsed < black_box.next_sample {
+        return;
+    }
+    black_box.next_sample += SAMPLE_INTERVAL;
+    let Ok((transform, velocity)) = submarine_query.single() else {
+        return;
+    };
+
+    let now = black_box.elapsed;
+    let position = transform.translation;
+    black_box.samples.push_back(BlackBoxSample {
+        time: now,
+        depth: -position.y,
+        speed: velocity.linvel.length(),
+        health: game_state.health,
+        oxygen: game_state.oxygen,
+        ballast_fill: ballast_state.fill_level,
+        compressed_air: ballast_state.compressed_air,
+        electricity: ballast_state.electricity,
+        position: position.to_array(),
+    });
+    while black_box
+        .samples
+        .front()
+        .is_some_and(|sample| sample.time < now - RECORD_LENGTH)
+    {
+        black_box.samples.pop_front();
+    }
+
+    let cause = if game_state.health <= 0.0 {
+        black_box.lost = true;
+        Some(if game_state.oxygen <= 0.0 {
+            "Suffocated: oxygen ran out"
+        } else {
+            "Hull lost"
+        })
+    } else {
+        let recent_health = black_box
+            .samples
+            .iter()
+            .filter(|sample| sample.time >= now - DAMAGE_WINDOW)
+            .map(|sample| sample.health)
+            .fold(game_state.health, f32::max);
+        let cooled_down = black_box
+            .last_incident
+            .is_none_or(|last| now - last > DAMAGE_WINDOW);
+        (recent_health - game_state.health >= SEVERE_DAMAGE && cooled_down)
+            .then_some("Severe hull damage")
+    };
+    let Some(cause) = cause else {
+        return;
+    };
+    black_box.last_incident = Some(now);
+
+    let incident = Incident {
+        cause: cause.to_string(),
+        timestamp: storage::unix_time(),
+        samples: black_box.samples.iter().copied().collect(),
+    };
+    // Dive time tells apart incidents recorded within the same second
+    let path = format!(
+        "{}/incident-{}-{}.ron",
+        INCIDENT_DIR, incident.timestamp, now as u32
+    );
+    let pretty = ron::ser::PrettyConfig::default().compact_arrays(true);
+    match ron::ser::to_string_pretty(&incident, pretty) {
+        Ok(contents) => match storage::write(&path, &contents) {
+            Ok(()) => info!("{}; black box written to {}", cause, path),
+            Err(err) => warn!("Failed to write black box: {}", err),
+        },
+        Err(err) => warn!("Failed to write black box: {}", err),
+    }
+
+    for panel in panel_query.iter() {
+        commands.entity(panel).despawn();
+    }
+    spawn_post_mortem(&mut commands, assets.font.clone(), &incident);
+    last_incident.0 = Some(incident);
+}
+
+/// F4 closes the post-mortem, or opens it again for the last incident
+fn post_mortem_toggle_system(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    last_incident: Res<LastIncident>,
+    panel_query: Query<Entity, With<PostMortemPanel>>,
+    assets: Res<GameAssets>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::F4) {
+        return;
+    }
+    if !panel_query.is_empty() {
+        for panel in panel_query.iter() {
+            commands.entity(panel).despawn();
+        }
+    } else if let Some(incident) = &last_incident.0 {
+        spawn_post_mortem(&mut commands, assets.font.clone(), incident);
+    }
+}
+
+/// Depth and speed charts for the recorded minute, along the bottom of the screen
+fn spawn_post_mortem(commands: &mut Commands, font: Handle<Font>, incident: &Incident) {
+    let samples = &incident.samples;
+    let max_depth = samples
+        .iter()
+        .map(|sample| sample.depth)
+        .fold(1.0, f32::max);
+    let max_speed = samples
+        .iter()
+        .map(|sample| sample.speed)
+        .fold(1.0, f32::max);
+    let last = samples.last();
+    let summary = match last {
+        Some(sample) => format!(
+            "Black box: {}\nLast reading: {:.1} m deep at {:.1} m/s, hull {:.0}%, oxygen {:.0}%",
+            incident.cause, sample.depth, sample.speed, sample.health, sample.oxygen
+        ),
+        None => format!("Black box: {}", incident.cause),
+    };
+    let text_font = TextFont {
+        font_size: 15.0,
+        font,
+        ..default()
+    };
+
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                bottom: Val::Px(20.0),
+                left: Val::Percent(50.0),
+                margin: UiRect::left(Val::Px(-CHART_WIDTH / 2.0 - 12.0)),
+                padding: UiRect::all(Val::Px(12.0)),
+                flex_direction: FlexDirection::Column,
+                row_gap: Val::Px(6.0),
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.75)),
+            GlobalZIndex(6),
+            PostMortemPanel,
+            StateScoped(AppState::InGame),
+        ))
+        .with_children(|panel| {
+            panel.spawn((
+                Text::new(summary),
+                text_font.clone(),
+                TextColor(Color::srgb(1.0, 0.6, 0.5)),
+            ));
+            let charts = [
+                (
+                    format!("Depth (0 to {:.0} m, down is deeper)", max_depth),
+                    Color::srgb(0.3, 0.7, 1.0),
+                    samples
+                        .iter()
+                        .map(|sample| (sample.depth / max_depth).clamp(0.0, 1.0))
+                        .collect::<Vec<_>>(),
+                    true,
+                ),
+                (
+                    format!("Speed (0 to {:.1} m/s)", max_speed),
+                    Color::srgb(0.4, 1.0, 0.5),
+                    samples
+                        .iter()
+                        .map(|sample| (sample.speed / max_speed).clamp(0.0, 1.0))
+                        .collect::<Vec<_>>(),
+                    false,
+                ),
+            ];
+            for (label, color, values, hanging) in charts {
+                panel.spawn((
+                    Text::new(label),
+                    text_font.clone(),
+                    TextColor(Color::srgb(0.8, 0.8, 0.8)),
+                ));
+                spawn_chart(panel, &values, color, hanging);
+            }
+            panel.spawn((
+                Text::new(format!(
+                    "Last {:.0} s before the incident. F4 closes",
+                    samples.len() as f32 * SAMPLE_INTERVAL
+                )),
+                text_font,
+                TextColor(Color::srgb(0.6, 0.6, 0.6)),
+            ));
+        });
+}
+
+/// One bar per sample; depth bars hang from the top like the water column
+fn spawn_chart(panel: &mut ChildSpawnerCommands, values: &[f32], color: Color, hanging: bool) {
+    let bar_width = CHART_WIDTH / (RECORD_LENGTH / SAMPLE_INTERVAL);
+    panel
+        .spawn((
+            Node {
+                width: Val::Px(CHART_WIDTH),
+                height: Val::Px(CHART_HEIGHT),
+                align_items: if hanging {
+                    AlignItems::FlexStart
+                } else {
+                    AlignItems::FlexEnd
+                },
+                ..default()
+            },
+            BackgroundColor(Color::srgba(1.0, 1.0, 1.0, 0.05)),
+        ))
+        .with_children(|chart| {
+            for value in values {
+                chart.spawn((
+                    Node {
+                        width: Val::Px(bar_width),
+                        height: Val::Px((value * CHART_HEIGHT).max(1.0)),
+                        ..default()
+                    },
+                    BackgroundColor(color),
+                ));
+            }
+        });
+}
@@ -0,0 +1,199 @@
+//! Bottom-contour navigation: the fathometer sounds the floor under the keel.
+//! While the position is known well the soundings are charted; once dead
+//! reckoning has drifted, the last stretch of soundings is slid around the
+//! chart until it lines up with the charted contours, and where it matches
+//! closely the estimate is corrected without surfacing. Flat bottom gives
+//! nothing to match against, so fixes come from charted slopes and hills.
+
+use crate::callouts::{CalloutKind, CrewCallout};
+use crate::magnetics::Navigation;
+use crate::{AppState, GameSet, Submarine};
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use std::collections::HashMap;
+
+const CHART_CELL: f32 = 4.0; // Meters each charted sounding covers
+const CHART_DRIFT: f32 = 3.0; // Soundings are only charted while the estimate is this good
+const SOUNDING_SPACING: f32 = 4.0; // Meters travelled between soundings on the track
+const TRACK_LENGTH: usize = 12; // Soundings compared against the chart
+const MIN_CHARTED: usize = 9; // Track soundings that must fall on charted cells to compare
+const MIN_RELIEF: f32 = 1.5; // Meters the floor must rise and fall along the track
+const FIX_DRIFT: f32 = 5.0; // Estimates worse than this are worth correcting
+const FIX_INTERVAL: f32 = 2.0; // Seconds between attempts at a fix
+const SEARCH_RADIUS: f32 = 30.0; // Most meters a fix can move the estimate
+const SEARCH_STEP: f32 = 1.0; // Meters between the offsets tried
+const MATCH_TOLERANCE: f32 = 0.6; // Meters of RMS depth error a match may have
+const FIX_ACCURACY: f32 = 2.0; // Meters the estimate may be off by after a fix
+const FATHOMETER_RANGE: f32 = 60.0;
+
+/// Floor depths sounded so far this dive, by chart cell
+#[derive(Resource, Default)]
+pub struct BottomChart {
+    pub cells: HashMap<IVec2, f32>,
+}
+
+impl BottomChart {
+    fn cell(x: f32, z: f32) -> IVec2 {
+        IVec2::new(
+            (x / CHART_CELL).floor() as i32,
+            (z / CHART_CELL).floor() as i32,
+        )
+    }
+
+    /// Charted floor depth at a point, if it has been sounded
+    pub fn depth(&self, x: f32, z: f32) -> Option<f32> {
+        self.cells.get(&Self::cell(x, z)).copied()
+    }
+}
+
+/// Recent soundings, each at the dead reckoned position it was taken at
+#[derive(Resource, Default)]
+struct SoundingTrack {
+    soundings: Vec<(Vec2, f32)>,
+    travelled: f32, // Meters since the last sounding
+    fix_timer: f32,
+}
+
+pub struct BottomContourPlugin;
+
+impl Plugin for BottomContourPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BottomChart>()
+            .init_resource::<SoundingTrack>()
+            .add_systems(OnEnter(AppState::InGame), reset_chart)
+            .add_systems(
+                Update,
+                (fathometer_system, contour_fix_system)
+                    .chain()
+                    .after(crate::magnetics::dead_reckoning_system)
+                    .in_set(GameSet::Simulation),
+            );
+    }
+}
+
+fn reset_chart(mut commands: Commands) {
+    commands.insert_resource(BottomChart::default());
+    commands.insert_resource(SoundingTrack::default());
+}
+
+/// Sounds the floor every few meters, adding it to the track and, while the
+/// position is trusted, to the chart
+fn fathometer_system(
+    rapier_context: ReadRapierContext,
+    mut chart: ResMut<BottomChart>,
+    mut track: ResMut<SoundingTrack>,
+    navigation: Res<Navigation>,
+    submarine_query: Query<(&Transform, &Velocity), With<Submarine>>,
+    time: Res<Time>,
+) {
+    let (Ok((transform, velocity)), Ok(context)) =
+        (submarine_query.single(), rapier_context.single())
+    else {
+        return;
+    };
+    track.travelled += Vec2::new(velocity.linvel.x, velocity.linvel.z).length() * time.delta_secs();
+    if track.travelled < SOUNDING_SPACING {
+        return;
+    }
+    track.travelled = 0.0;
+
+    let filter = QueryFilter::only_fixed().exclude_sensors();
+    let Some((_, toi)) = context.cast_ray(
+        transform.translation,
+        Vec3::NEG_Y,
+        FATHOMETER_RANGE,
+        true,
+        filter,
+    ) else {
+        return;
+    };
+    let floor = toi - transform.translation.y;
+    let position = Vec2::new(navigation.estimate.x, navigation.estimate.z);
+    if navigation.drift < CHART_DRIFT {
+        chart
+            .cells
+            .insert(BottomChart::cell(position.x, position.y), floor);
+    }
+    track.soundings.push((position, floor));
+    if track.soundings.len() > TRACK_LENGTH {
+        track.soundings.remove(0);
+    }
+}
+
+/// Slides the track over the chart within the estimate's error, and takes
+/// the offset where the contours match best as a fix
+fn contour_fix_system(
+    mut navigation: ResMut<Navigation>,
+    mut track: ResMut<SoundingTrack>,
+    mut callouts: EventWriter<CrewCallout>,
+    chart: Res<BottomChart>,
+    time: Res<Time>,
+) {
+    track.fix_timer += time.delta_secs();
+    if track.fix_timer < FIX_INTERVAL
+        || navigation.drift < FIX_DRIFT
+        || track.soundings.len() < TRACK_LENGTH
+    {
+        return;
+    }
+    track.fix_timer = 0.0;
+    let (low, high) = track
+        .soundings
+        .iter()
+        .fold((f32::MAX, f32::MIN), |(low, high), &(_, floor)| {
+            (low.min(floor), high.max(floor))
+        });
+    if high - low < MIN_RELIEF {
+        return;
+    }
+
+    let reach = (navigation.drift.min(SEARCH_RADIUS) / SEARCH_STEP) as i32;
+    let mut best: Option<(Vec2, f32)> = None;
+    for i in -reach..=reach {
+        for j in -reach..=reach {
+            let offset = Vec2::new(i as f32, j as f32) * SEARCH_STEP;
+            let errors: Vec<f32> = track
+                .soundings
+                .iter()
+                .filter_map(|&(position, floor)| {
+                    let charted = chart.depth(position.x + offset.x, position.y + offset.y)?;
+                    Some((charted - floor).powi(2))
+                })
+                .collect();
+            if errors.len() < MIN_CHARTED {
+                continue;
+            }
+            let rms = (errors.iter().sum::<f32>() / errors.len() as f32).sqrt();
+            // Ties go to the smaller correction
+            let better = best.is_none_or(|(best_offset, best_rms)| {
+                rms < best_rms || (rms == best_rms && offset.length() < best_offset.length())
+            });
+            if better {
+                best = Some((offset, rms));
+            }
+        }
+    }
+    let Some((offset, rms)) = best else {
+        return;
+    };
+    if rms > MATCH_TOLERANCE {
+        return;
+    }
+
+    navigation.estimate.x += offset.x;
+    navigation.estimate.z += offset.y;
+    navigation.drift = FIX_ACCURACY;
+    track.soundings.clear();
+    info!(
+        "Bottom contour fix moved the estimate {:.1} m (RMS {:.2} m)",
+        offset.length(),
+        rms
+    );
+    callouts.write(CrewCallout {
+        kind: CalloutKind::Navigation,
+        text: format!(
+            "Contour fix, position corrected {:.0} meters",
+            offset.length()
+        ),
+    });
+}
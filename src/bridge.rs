@@ -0,0 +1,233 @@
+//! The bridge: once the boat is fully surfaced, a lookout can climb the
+//! conning tower and sweep the horizon with binoculars. Ships held in view are
+//! reported with a bearing and a rough range, so surfacing is a chance to get
+//! a picture of the traffic as well as to recharge.
+
+use crate::controls::{Action, HelmInput, InputMap};
+use crate::magnetics::Navigation;
+use crate::periscope::{ContactLog, ContactReport, SurfaceShip};
+use crate::{
+    AppState, CameraFollow, CameraMode, GameAssets, GameSet, Submarine, ViewCameraQuery, WorldSeed,
+};
+use bevy::prelude::*;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::collections::HashMap;
+use std::f32::consts::{FRAC_PI_4, TAU};
+
+pub const SURFACED_DEPTH: f32 = 0.5; // Shallower than this the bridge is clear of the water
+const BRIDGE_HEIGHT: f32 = 1.8; // Eye level on the conning tower above the hull's centre line
+const MAGNIFICATIONS: [f32; 2] = [1.0, 7.0];
+const LOOK_SPEED: f32 = 1.5; // Radians per second at 1x; slower through the binoculars
+const MAX_PITCH: f32 = 0.6; // Radians the lookout can look up or down
+const SPOT_RANGE: f32 = 120.0; // Meters a ship is made out at with the naked eye; times the magnification
+const SPOT_TIME: f32 = 1.0; // Seconds a ship has to stay in view to be reported
+const RANGE_ERROR: f32 = 0.25; // Share a lookout's range estimate may be off by
+const RESPOT_TIME: f32 = 60.0; // Seconds before the same ship is reported again
+
+/// Where the lookout is looking and through what
+#[derive(Resource, Default)]
+struct Lookout {
+    yaw: f32,   // Radians to port of the bow
+    pitch: f32, // Radians above the horizon
+    magnification: usize,
+    in_view: HashMap<Entity, f32>, // Seconds each ship has been held in view
+    reported: HashMap<Entity, f32>, // Clock time each ship was last reported
+    clock: f32,
+}
+
+/// Random source for range estimates, seeded from the world so replays match
+#[derive(Resource)]
+struct LookoutRng(StdRng);
+
+#[derive(Component)]
+struct BridgeOverlay;
+
+#[derive(Component)]
+struct BridgeText;
+
+pub struct BridgePlugin;
+
+impl Plugin for BridgePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Lookout>()
+            .insert_resource(LookoutRng(StdRng::seed_from_u64(0)))
+            .add_systems(OnEnter(AppState::InGame), reset_lookout)
+            .add_systems(OnEnter(CameraMode::Bridge), spawn_overlay)
+            .add_systems(OnExit(CameraMode::Bridge), leave_bridge)
+            .add_systems(
+                Update,
+                (
+                    lookout_system.in_set(GameSet::Input),
+                    (bridge_camera_system, bridge_overlay_system)
+                        .chain()
+                        .after(crate::camera_follow)
+                        .in_set(GameSet::Presentation),
+                )
+                    .run_if(in_state(CameraMode::Bridge)),
+            );
+    }
+}
+
+fn reset_lookout(mut commands: Commands, seed: Res<WorldSeed>) {
+    commands.insert_resource(Lookout::default());
+    commands.insert_resource(LookoutRng(StdRng::seed_from_u64(seed.0 ^ 0x4C4F_4F4B)));
+}
+
+fn spawn_overlay(mut commands: Commands, assets: Res<GameAssets>) {
+    commands.spawn((
+        Text::new(""),
+        TextFont {
+            font_size: 16.0,
+            font: assets.font.clone(),
+            ..default()
+        },
+        TextColor(Color::srgb(0.9, 0.95, 0.8)),
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(130.0),
+            left: Val::Px(16.0),
+            ..default()
+        },
+        BridgeText,
+        BridgeOverlay,
+        StateScoped(AppState::InGame),
+    ));
+}
+
+fn leave_bridge(
+    mut commands: Commands,
+    overlay_query: Query<Entity, With<BridgeOverlay>>,
+    mut projection_query: Query<&mut Projection, With<CameraFollow>>,
+) {
+    for overlay in overlay_query.iter() {
+        commands.entity(overlay).despawn();
+    }
+    for mut projection in projection_query.iter_mut() {
+        if let Projection::Perspective(perspective) = projection.as_mut() {
+            perspective.fov = FRAC_PI_4;
+        }
+    }
+}
+
+/// Where the lookout stands on the conning tower
+fn bridge_eye(submarine: &Transform) -> Vec3 {
+    submarine.translation + submarine.rotation * Vec3::new(0.0, BRIDGE_HEIGHT, -0.3)
+}
+
+/// Free look with the camera keys, binoculars on the zoom key, and reports of
+/// ships held in view; everyone goes below if the boat dives
+#[allow(clippy::too_many_arguments)]
+fn lookout_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    input_map: Res<InputMap>,
+    helm_input: Res<HelmInput>,
+    navigation: Res<Navigation>,
+    mut lookout: ResMut<Lookout>,
+    mut log: ResMut<ContactLog>,
+    mut rng: ResMut<LookoutRng>,
+    mut next_camera_mode: ResMut<NextState<CameraMode>>,
+    submarine_query: Query<&Transform, With<Submarine>>,
+    ship_query: Query<(Entity, &SurfaceShip, &Transform), Without<Submarine>>,
+    time: Res<Time>,
+) {
+    let Ok(submarine) = submarine_query.single() else {
+        return;
+    };
+    if -submarine.translation.y > SURFACED_DEPTH {
+        next_camera_mode.set(CameraMode::ControlRoom);
+        return;
+    }
+
+    let delta = time.delta_secs();
+    lookout.clock += delta;
+    if input_map.just_pressed(&keyboard_input, Action::PeriscopeZoom) {
+        lookout.magnification = (lookout.magnification + 1) % MAGNIFICATIONS.len();
+    }
+    let magnification = MAGNIFICATIONS[lookout.magnification];
+    let step = LOOK_SPEED / magnification * delta;
+    lookout.yaw = (lookout.yaw - helm_input.camera_yaw * step).rem_euclid(TAU);
+    lookout.pitch = (lookout.pitch + helm_input.camera_pitch * step).clamp(-MAX_PITCH, MAX_PITCH);
+
+    // Ships inside the field of view and close enough to make out
+    let eye = bridge_eye(submarine);
+    let yaw = submarine.rotation.to_euler(EulerRot::YXZ).0 + lookout.yaw;
+    let half_fov = FRAC_PI_4 / magnification / 2.0;
+    let mut held = HashMap::new();
+    for (entity, ship, transform) in ship_query.iter() {
+        let offset = transform.translation - eye;
+        let distance = Vec2::new(offset.x, offset.z).length();
+        let ship_yaw = (-offset.x).atan2(-offset.z);
+        let off_axis =
+            (ship_yaw - yaw + std::f32::consts::PI).rem_euclid(TAU) - std::f32::consts::PI;
+        if off_axis.abs() > half_fov || distance > SPOT_RANGE * magnification {
+            continue;
+        }
+        let seen = lookout.in_view.get(&entity).copied().unwrap_or(0.0) + delta;
+        held.insert(entity, seen);
+        let recently = lookout
+            .reported
+            .get(&entity)
+            .is_some_and(|last| lookout.clock - last < RESPOT_TIME);
+        if seen < SPOT_TIME || recently {
+            continue;
+        }
+        let report = ContactReport {
+            name: ship.name,
+            bearing: navigation.compass_heading(ship_yaw),
+            range: distance * (1.0 + rng.0.gen_range(-RANGE_ERROR..RANGE_ERROR)),
+        };
+        info!(
+            "Lookout: {} bearing {:03.0}, about {:.0} m",
+            report.name, report.bearing, report.range
+        );
+        log.entries.push(report);
+        let clock = lookout.clock;
+        lookout.reported.insert(entity, clock);
+    }
+    lookout.in_view = held;
+}
+
+/// Looks out from the bridge where the lookout is looking
+fn bridge_camera_system(
+    lookout: Res<Lookout>,
+    submarine_query: Query<&Transform, With<Submarine>>,
+    mut camera_query: ViewCameraQuery,
+) {
+    let Ok(submarine) = submarine_query.single() else {
+        return;
+    };
+    let yaw = submarine.rotation.to_euler(EulerRot::YXZ).0 + lookout.yaw;
+    for (mut transform, mut projection) in camera_query.iter_mut() {
+        *transform = Transform::from_translation(bridge_eye(submarine))
+            .with_rotation(Quat::from_euler(EulerRot::YXZ, yaw, lookout.pitch, 0.0));
+        if let Projection::Perspective(perspective) = projection.as_mut() {
+            perspective.fov = FRAC_PI_4 / MAGNIFICATIONS[lookout.magnification];
+        }
+    }
+}
+
+fn bridge_overlay_system(
+    lookout: Res<Lookout>,
+    log: Res<ContactLog>,
+    mut text_query: Query<&mut Text, With<BridgeText>>,
+) {
+    let magnification = MAGNIFICATIONS[lookout.magnification];
+    let mut lines = vec![format!(
+        "{}  Relative bearing {:03.0}\nZ: Binoculars  Arrows: Look around",
+        if magnification > 1.0 {
+            format!("Binoculars {:.0}x", magnification)
+        } else {
+            "Bridge".to_string()
+        },
+        (-lookout.yaw).rem_euclid(TAU).to_degrees()
+    )];
+    if let Some(report) = log.entries.last() {
+        lines.push(format!(
+            "Last contact: {}, bearing {:03.0}, range {:.0} m",
+            report.name, report.bearing, report.range
+        ));
+    }
+    for mut text in text_query.iter_mut() {
+        text.0 = lines.join("\n");
+    }
+}
@@ -0,0 +1,284 @@
+//! Crew callouts: the crew reports depth, contacts, damage, ballast orders and
+//! oxygen as they happen. Each callout is captioned and, with the crew voice
+//! setting on, spoken. A limiter spaces them out and keeps any one kind from
+//! repeating too often.
+
+use crate::accessibility::TtsBackend;
+use crate::combat::Hostile;
+use crate::settings::Settings;
+use crate::{AppState, BallastState, GameAssets, GameSet, GameState, Submarine};
+use bevy::prelude::*;
+use std::collections::{HashMap, HashSet};
+
+const DEPTH_INTERVAL: f32 = 10.0; // Meters between depth callouts on the way down
+const CONTACT_RANGE: f32 = 50.0; // Hostiles closer than this are reported
+const DAMAGE_STEP: f32 = 5.0; // Hull percent lost before damage is reported
+const OXYGEN_LEVELS: [f32; 3] = [50.0, 25.0, 10.0];
+const MIN_GAP: f32 = 2.5; // Seconds between any two callouts
+const KIND_COOLDOWN: f32 = 8.0; // Seconds before a kind of callout is repeated
+const MAX_WAIT: f32 = 6.0; // Callouts still waiting after this long are stale and dropped
+const CAPTION_TIME: f32 = 3.5; // Seconds a caption stays on screen
+
+/// What a callout is about; the limiter paces each kind separately
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum CalloutKind {
+    Depth,
+    Contact,
+    Damage,
+    Ballast,
+    Oxygen,
+    Casualty,
+    Watch,
+    Failure,
+    Leak,
+    Navigation,
+}
+
+/// A report from the crew, in their words
+#[derive(Event, Clone)]
+pub struct CrewCallout {
+    pub kind: CalloutKind,
+    pub text: String,
+}
+
+/// Values last reported, so the watch only calls out changes
+#[derive(Resource, Default)]
+struct CalloutWatch {
+    depth_milestone: Option<i32>, // None until the first frame of the dive
+    contacts: HashSet<Entity>,    // Hostiles already reported
+    reported_health: Option<f32>,
+    oxygen_level: usize, // Number of oxygen levels already crossed
+    vents_open: bool,
+    air_valve_open: bool,
+}
+
+/// Callouts waiting their turn and when each kind was last heard
+#[derive(Resource, Default)]
+struct CalloutLimiter {
+    clock: f32,
+    last_callout: Option<f32>,
+    last_by_kind: HashMap<CalloutKind, f32>,
+    waiting: Vec<(f32, CrewCallout)>, // Time queued and the callout
+    caption_timer: f32,
+}
+
+#[derive(Component)]
+struct CalloutCaption;
+
+pub struct CalloutsPlugin;
+
+impl Plugin for CalloutsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<CrewCallout>()
+            .init_resource::<CalloutWatch>()
+            .init_resource::<CalloutLimiter>()
+            .add_systems(OnEnter(AppState::InGame), (reset_callouts, spawn_caption))
+            .add_systems(
+                Update,
+                (callout_watch_system, callout_limiter_system)
+                    .chain()
+                    .in_set(GameSet::Presentation),
+            );
+    }
+}
+
+fn reset_callouts(mut commands: Commands) {
+    commands.insert_resource(CalloutWatch::default());
+    commands.insert_resource(CalloutLimiter::default());
+}
+
+fn spawn_caption(mut commands: Commands, assets: Res<GameAssets>) {
+    commands.spawn((
+        Text::new(""),
+        TextFont {
+            font_size: 20.0,
+            font: assets.font.clone(),
+            ..default()
+        },
+        TextColor(Color::srgb(0.75, 0.95, 1.0)),
+        TextLayout::new_with_justify(JustifyText::Center),
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(90.0),
+            width: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        CalloutCaption,
+        StateScoped(AppState::InGame),
+    ));
+}
+
+/// Digits read out one at a time, the way bearings are called: 270 is "two-seven-zero"
+fn spoken_digits(value: u32, width: usize) -> String {
+    const NAMES: [&str; 10] = [
+        "zero", "one", "two", "three", "four", "five", "six", "seven", "eight", "niner",
+    ];
+    format!("{:0width$}", value, width = width)
+        .chars()
+        .filter_map(|digit| digit.to_digit(10))
+        .map(|digit| NAMES[digit as usize])
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Turns changes in the submarine's situation into crew callouts
+fn callout_watch_system(
+    mut watch: ResMut<CalloutWatch>,
+    mut callouts: EventWriter<CrewCallout>,
+    game_state: Res<GameState>,
+    ballast_state: Res<BallastState>,
+    submarine_query: Query<&Transform, With<Submarine>>,
+    hostile_query: Query<(Entity, &Transform), With<Hostile>>,
+) {
+    let Ok(submarine) = submarine_query.single() else {
+        return;
+    };
+    let mut call = |kind: CalloutKind, text: String| {
+        callouts.write(CrewCallout { kind, text });
+    };
+
+    // Depth is called on the way down only; coming up, the next milestone re-arms
+    let depth = (-submarine.translation.y).max(0.0);
+    let milestone = (depth / DEPTH_INTERVAL).floor() as i32;
+    if watch.depth_milestone.is_some_and(|last| milestone > last) {
+        call(
+            CalloutKind::Depth,
+            format!("Passing {:.0} meters", milestone as f32 * DEPTH_INTERVAL),
+        );
+    }
+    watch.depth_milestone = Some(milestone);
+
+    let mut in_range = HashSet::new();
+    for (entity, hostile) in hostile_query.iter() {
+        let offset = hostile.translation - submarine.translation;
+        if offset.length() >= CONTACT_RANGE {
+            continue;
+        }
+        in_range.insert(entity);
+        if watch.contacts.contains(&entity) {
+            continue;
+        }
+        // Clockwise from the bow, which faces local -Z
+        let local = submarine.rotation.inverse() * offset;
+        let bearing = local
+            .x
+            .atan2(-local.z)
+            .to_degrees()
+            .rem_euclid(360.0)
+            .round() as u32
+            % 360;
+        call(
+            CalloutKind::Contact,
+            format!("Contact, bearing {}", spoken_digits(bearing, 3)),
+        );
+    }
+    watch.contacts = in_range;
+
+    let reported = *watch.reported_health.get_or_insert(game_state.health);
+    if game_state.health <= reported - DAMAGE_STEP {
+        call(
+            CalloutKind::Damage,
+            format!("Taking damage! Hull at {:.0} percent", game_state.health),
+        );
+        watch.reported_health = Some(game_state.health);
+    } else if game_state.health > reported {
+        // Repairs move the baseline up
+        watch.reported_health = Some(game_state.health);
+    }
+
+    if ballast_state.vents_open && !watch.vents_open {
+        call(CalloutKind::Ballast, "Flooding main ballast".to_string());
+    }
+    if ballast_state.air_valve_open && !watch.air_valve_open {
+        call(CalloutKind::Ballast, "Blowing main ballast".to_string());
+    }
+    watch.vents_open = ballast_state.vents_open;
+    watch.air_valve_open = ballast_state.air_valve_open;
+
+    let level = OXYGEN_LEVELS
+        .iter()
+        .filter(|&&threshold| game_state.oxygen <= threshold)
+        .count();
+    if level > watch.oxygen_level {
+        call(
+            CalloutKind::Oxygen,
+            format!("Oxygen at {:.0} percent", OXYGEN_LEVELS[level - 1]),
+        );
+    }
+    watch.oxygen_level = level;
+}
+
+/// Lets callouts through one at a time, dropping repeats and stale reports,
+/// and captions and voices each one
+fn callout_limiter_system(
+    mut limiter: ResMut<CalloutLimiter>,
+    mut callouts: EventReader<CrewCallout>,
+    mut caption_query: Query<&mut Text, With<CalloutCaption>>,
+    settings: Res<Settings>,
+    backend: Option<Res<TtsBackend>>,
+    time: Res<Time>,
+) {
+    let delta = time.delta_secs();
+    limiter.clock += delta;
+    let now = limiter.clock;
+
+    for callout in callouts.read() {
+        // A newer report of the same kind supersedes one still waiting
+        limiter
+            .waiting
+            .retain(|(_, waiting)| waiting.kind != callout.kind);
+        limiter.waiting.push((now, callout.clone()));
+    }
+    limiter
+        .waiting
+        .retain(|(queued, _)| now - queued <= MAX_WAIT);
+
+    let gap_clear = limiter
+        .last_callout
+        .is_none_or(|last| now - last >= MIN_GAP);
+    let ready = limiter.waiting.iter().position(|(_, callout)| {
+        limiter
+            .last_by_kind
+            .get(&callout.kind)
+            .is_none_or(|last| now - last >= KIND_COOLDOWN)
+    });
+    if let (true, Some(index)) = (gap_clear, ready) {
+        let (_, callout) = limiter.waiting.remove(index);
+        limiter.last_callout = Some(now);
+        limiter.last_by_kind.insert(callout.kind, now);
+        limiter.caption_timer = CAPTION_TIME;
+        for mut text in caption_query.iter_mut() {
+            text.0 = callout.text.clone();
+        }
+        if let Some(backend) = backend.filter(|_| settings.audio.crew_voice) {
+            backend.say(&callout.text);
+        }
+        return;
+    }
+
+    if limiter.caption_timer > 0.0 {
+        limiter.caption_timer -= delta;
+        if limiter.caption_timer <= 0.0 {
+            for mut text in caption_query.iter_mut() {
+                text.0.clear();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn digits_are_read_one_at_a_time() {
+        assert_eq!(spoken_digits(270, 3), "two-seven-zero");
+        assert_eq!(spoken_digits(9, 3), "zero-zero-niner");
+    }
+
+    #[test]
+    fn values_wider_than_the_padding_keep_every_digit() {
+        assert_eq!(spoken_digits(1045, 3), "one-zero-four-five");
+    }
+}
@@ -0,0 +1,222 @@
+use crate::cvars::{CvarDef, Cvars, RegisterCvar};
+use crate::settings::Settings;
+use crate::{camera_follow, AppState, CameraFollow, Fish, GameSet, Submarine};
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+const TRAUMA_DECAY: CvarDef = CvarDef {
+    name: "shake.trauma_decay",
+    default: 0.8,
+    range: 0.0..=10.0,
+    description: "Trauma removed per second",
+};
+
+const MAX_SHAKE_OFFSET: CvarDef = CvarDef {
+    name: "shake.max_offset",
+    default: 0.6,
+    range: 0.0..=5.0,
+    description: "Camera offset in world units at full trauma",
+};
+
+const MAX_SHAKE_ANGLE: CvarDef = CvarDef {
+    name: "shake.max_angle",
+    default: 0.06,
+    range: 0.0..=0.5,
+    description: "Camera rotation in radians at full trauma",
+};
+
+const SHAKE_FREQUENCY: CvarDef = CvarDef {
+    name: "shake.frequency",
+    default: 18.0,
+    range: 1.0..=60.0,
+    description: "How fast the camera shakes",
+};
+
+const SHAKE_FALLOFF_DISTANCE: CvarDef = CvarDef {
+    name: "shake.falloff_distance",
+    default: 60.0,
+    range: 1.0..=500.0,
+    description: "Shakes from explosions fade out over this distance",
+};
+
+const COLLISION_TRAUMA_SPEED: CvarDef = CvarDef {
+    name: "shake.collision_speed",
+    default: 12.0,
+    range: 1.0..=50.0,
+    description: "Impact speed that produces full trauma",
+};
+
+const HULL_GROAN_DEPTH: CvarDef = CvarDef {
+    name: "shake.groan_depth",
+    default: 12.0,
+    range: 0.0..=50.0,
+    description: "The hull starts groaning below this depth",
+};
+
+const HULL_GROAN_TRAUMA: CvarDef = CvarDef {
+    name: "shake.groan_trauma",
+    default: 0.25,
+    range: 0.0..=1.0,
+    description: "Trauma added by each hull groan",
+};
+
+/// Adds trauma to the follow camera. Positional shakes (detonations) are attenuated
+/// by their distance from the camera.
+#[derive(Event, Clone, Copy)]
+pub struct CameraShake {
+    pub trauma: f32,
+    pub origin: Option<Vec3>,
+}
+
+impl CameraShake {
+    pub fn new(trauma: f32) -> Self {
+        Self {
+            trauma,
+            origin: None,
+        }
+    }
+}
+
+/// Current camera trauma; the visible shake is proportional to its square
+#[derive(Resource, Default)]
+pub struct CameraTrauma {
+    pub trauma: f32,
+    elapsed: f32,
+}
+
+pub struct CameraShakePlugin;
+
+impl Plugin for CameraShakePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<CameraShake>()
+            .init_resource::<CameraTrauma>()
+            .register_cvar(&TRAUMA_DECAY)
+            .register_cvar(&MAX_SHAKE_OFFSET)
+            .register_cvar(&MAX_SHAKE_ANGLE)
+            .register_cvar(&SHAKE_FREQUENCY)
+            .register_cvar(&SHAKE_FALLOFF_DISTANCE)
+            .register_cvar(&COLLISION_TRAUMA_SPEED)
+            .register_cvar(&HULL_GROAN_DEPTH)
+            .register_cvar(&HULL_GROAN_TRAUMA)
+            .add_systems(OnEnter(AppState::InGame), reset_camera_trauma)
+            .add_systems(
+                Update,
+                (
+                    collision_shake_system,
+                    hull_groan_system,
+                    accumulate_trauma_system,
+                    apply_camera_shake_system,
+                )
+                    .chain()
+                    .in_set(GameSet::Presentation)
+                    .after(camera_follow),
+            );
+    }
+}
+
+fn reset_camera_trauma(mut commands: Commands) {
+    commands.insert_resource(CameraTrauma::default());
+}
+
+/// Shakes the camera when the submarine hits terrain (not fish), scaled by impact speed
+fn collision_shake_system(
+    mut collision_events: EventReader<CollisionEvent>,
+    submarine_query: Query<(Entity, &Velocity), With<Submarine>>,
+    fish_query: Query<(), With<Fish>>,
+    cvars: Res<Cvars>,
+    mut shakes: EventWriter<CameraShake>,
+) {
+    let Ok((submarine, velocity)) = submarine_query.single() else {
+        collision_events.clear();
+        return;
+    };
+    for event in collision_events.read() {
+        if let CollisionEvent::Started(a, b, _) = event {
+            let other = if *a == submarine { *b } else { *a };
+            if (*a == submarine || *b == submarine) && !fish_query.contains(other) {
+                let impact =
+                    (velocity.linvel.length() / cvars.get(&COLLISION_TRAUMA_SPEED)).clamp(0.2, 1.0);
+                shakes.write(CameraShake::new(impact));
+            }
+        }
+    }
+}
+
+/// Occasional low rumbles when the hull is under pressure at depth
+fn hull_groan_system(
+    submarine_query: Query<&Transform, With<Submarine>>,
+    time: Res<Time>,
+    cvars: Res<Cvars>,
+    mut next_groan: Local<f32>,
+    mut shakes: EventWriter<CameraShake>,
+) {
+    let Ok(transform) = submarine_query.single() else {
+        return;
+    };
+    let depth = -transform.translation.y;
+    if depth < cvars.get(&HULL_GROAN_DEPTH) {
+        *next_groan = 0.0;
+        return;
+    }
+    if *next_groan <= 0.0 {
+        *next_groan = 8.0 + rand::random::<f32>() * 12.0;
+    }
+    *next_groan -= time.delta_secs();
+    if *next_groan <= 0.0 {
+        shakes.write(CameraShake::new(cvars.get(&HULL_GROAN_TRAUMA)));
+    }
+}
+
+fn accumulate_trauma_system(
+    mut shakes: EventReader<CameraShake>,
+    mut camera_trauma: ResMut<CameraTrauma>,
+    camera_query: Query<&Transform, With<CameraFollow>>,
+    cvars: Res<Cvars>,
+    time: Res<Time>,
+) {
+    let camera_position = camera_query.single().map(|t| t.translation).ok();
+    for shake in shakes.read() {
+        let falloff = match (shake.origin, camera_position) {
+            (Some(origin), Some(camera)) => {
+                (1.0 - camera.distance(origin) / cvars.get(&SHAKE_FALLOFF_DISTANCE)).max(0.0)
+            }
+            _ => 1.0,
+        };
+        camera_trauma.trauma = (camera_trauma.trauma + shake.trauma * falloff).min(1.0);
+    }
+
+    camera_trauma.trauma =
+        (camera_trauma.trauma - cvars.get(&TRAUMA_DECAY) * time.delta_secs()).max(0.0);
+    camera_trauma.elapsed += time.delta_secs();
+}
+
+/// Offsets the camera after `camera_follow` has placed it for this frame
+fn apply_camera_shake_system(
+    camera_trauma: Res<CameraTrauma>,
+    settings: Res<Settings>,
+    cvars: Res<Cvars>,
+    mut camera_query: Query<&mut Transform, With<CameraFollow>>,
+) {
+    let intensity = settings.accessibility.camera_shake.clamp(0.0, 1.0);
+    let shake = camera_trauma.trauma * camera_trauma.trauma * intensity;
+    if shake <= 0.0 {
+        return;
+    }
+    let Ok(mut transform) = camera_query.single_mut() else {
+        return;
+    };
+
+    // Sum of incommensurate sines gives a cheap smooth noise per axis
+    let t = camera_trauma.elapsed * cvars.get(&SHAKE_FREQUENCY);
+    let max_offset = cvars.get(&MAX_SHAKE_OFFSET);
+    let max_angle = cvars.get(&MAX_SHAKE_ANGLE);
+    let noise = |seed: f32| ((t + seed).sin() + (t * 1.7 + seed * 3.1).sin() * 0.5) / 1.5;
+
+    let offset = Vec3::new(noise(0.0), noise(11.0), noise(23.0)) * max_offset * shake;
+    let yaw = noise(37.0) * max_angle * shake;
+    let pitch = noise(51.0) * max_angle * shake;
+    let roll = noise(67.0) * max_angle * shake;
+
+    transform.translation += offset;
+    transform.rotate_local(Quat::from_euler(EulerRot::YXZ, yaw, pitch, roll));
+}
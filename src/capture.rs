@@ -0,0 +1,169 @@
+//! Offline video capture: the game steps at a fixed frame time instead of the
+//! wall clock and screenshots every frame, so recordings come out smooth however
+//! slowly the machine renders them. Frames are written as numbered PNGs and/or
+//! piped to ffmpeg. Best combined with `--play-replay` to film a recorded dive.
+
+use crate::replay::ReplayPlayback;
+use bevy::prelude::*;
+use bevy::render::view::screenshot::{save_to_disk, Screenshot, ScreenshotCaptured};
+use bevy::time::TimeUpdateStrategy;
+use std::io::Write;
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// What to capture, from the command line
+#[derive(Resource, Clone)]
+pub struct CaptureSettings {
+    pub dir: Option<String>,    // Numbered PNGs go here
+    pub ffmpeg: Option<String>, // Video file ffmpeg encodes the frames into
+    pub fps: u32,
+    pub frames: Option<u32>, // Quit after this many frames
+}
+
+/// A running ffmpeg reading raw RGBA frames from its standard input
+struct Encoder {
+    child: Child,
+    stdin: Option<ChildStdin>,
+}
+
+#[derive(Resource)]
+struct CaptureState {
+    frame: u32,
+    encoder: Arc<Mutex<Option<Encoder>>>, // Started once the first frame's size is known
+    failed: Arc<Mutex<bool>>,             // ffmpeg couldn't start or stopped reading
+}
+
+pub struct CapturePlugin(pub CaptureSettings);
+
+impl Plugin for CapturePlugin {
+    fn build(&self, app: &mut App) {
+        let settings = self.0.clone();
+        if let Some(dir) = &settings.dir {
+            if let Err(err) = std::fs::create_dir_all(dir) {
+                eprintln!("Cannot create capture folder {}: {}", dir, err);
+                std::process::exit(1);
+            }
+        }
+        app.insert_resource(TimeUpdateStrategy::ManualDuration(frame_time(&settings)))
+            .insert_resource(CaptureState {
+                frame: 0,
+                encoder: Arc::new(Mutex::new(None)),
+                failed: Arc::new(Mutex::new(false)),
+            })
+            .insert_resource(settings)
+            .add_systems(Last, capture_frame_system)
+            .add_systems(Last, finish_capture.run_if(on_event::<AppExit>));
+    }
+}
+
+fn frame_time(settings: &CaptureSettings) -> Duration {
+    Duration::from_secs_f64(1.0 / settings.fps.max(1) as f64)
+}
+
+/// Screenshots the frame being rendered and advances the clock by one frame time
+fn capture_frame_system(
+    mut commands: Commands,
+    settings: Res<CaptureSettings>,
+    mut state: ResMut<CaptureState>,
+    playback: Option<Res<ReplayPlayback>>,
+    mut exit: EventWriter<AppExit>,
+) {
+    // Replays step by their recorded frame times until they finish
+    if playback.is_none() {
+        commands.insert_resource(TimeUpdateStrategy::ManualDuration(frame_time(&settings)));
+    }
+
+    let mut screenshot = commands.spawn(Screenshot::primary_window());
+    if let Some(dir) = &settings.dir {
+        screenshot.observe(save_to_disk(format!(
+            "{}/frame-{:06}.png",
+            dir, state.frame
+        )));
+    }
+    if let Some(output) = settings.ffmpeg.clone() {
+        let encoder = state.encoder.clone();
+        let failed = state.failed.clone();
+        let fps = settings.fps;
+        screenshot.observe(move |trigger: Trigger<ScreenshotCaptured>| {
+            let mut failed = failed.lock().unwrap();
+            if *failed {
+                return;
+            }
+            if let Err(err) = encode_frame(&encoder, &trigger.0, &output, fps) {
+                error!("Video capture stopped: {}", err);
+                *failed = true;
+            }
+        });
+    }
+
+    state.frame += 1;
+    if settings.frames.is_some_and(|frames| state.frame >= frames) {
+        info!("Captured {} frames", state.frame);
+        exit.write(AppExit::Success);
+    }
+}
+
+fn encode_frame(
+    encoder: &Mutex<Option<Encoder>>,
+    image: &Image,
+    output: &str,
+    fps: u32,
+) -> Result<(), String> {
+    let pixels = image
+        .clone()
+        .try_into_dynamic()
+        .map_err(|err| err.to_string())?
+        .to_rgba8();
+    let mut encoder = encoder.lock().unwrap();
+    if encoder.is_none() {
+        *encoder = Some(start_ffmpeg(output, pixels.width(), pixels.height(), fps)?);
+    }
+    let stdin = encoder
+        .as_mut()
+        .and_then(|encoder| encoder.stdin.as_mut())
+        .ok_or("ffmpeg has already finished")?;
+    stdin
+        .write_all(pixels.as_raw())
+        .map_err(|err| format!("ffmpeg stopped reading frames: {}", err))
+}
+
+fn start_ffmpeg(output: &str, width: u32, height: u32, fps: u32) -> Result<Encoder, String> {
+    let mut child = Command::new("ffmpeg")
+        .args([
+            "-y",
+            "-loglevel",
+            "error",
+            "-f",
+            "rawvideo",
+            "-pix_fmt",
+            "rgba",
+        ])
+        .args(["-s", &format!("{}x{}", width, height)])
+        .args(["-r", &fps.to_string(), "-i", "-"])
+        .args(["-pix_fmt", "yuv420p", output])
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|err| format!("cannot run ffmpeg: {}", err))?;
+    info!(
+        "Encoding {}x{} video at {} fps to {}",
+        width, height, fps, output
+    );
+    Ok(Encoder {
+        stdin: child.stdin.take(),
+        child,
+    })
+}
+
+/// Closes ffmpeg's input so it finishes writing the video before the game exits
+fn finish_capture(state: Res<CaptureState>) {
+    let Some(mut encoder) = state.encoder.lock().unwrap().take() else {
+        return;
+    };
+    drop(encoder.stdin.take());
+    match encoder.child.wait() {
+        Ok(status) if status.success() => info!("Video capture finished"),
+        Ok(status) => warn!("ffmpeg exited with {}", status),
+        Err(err) => warn!("Cannot wait for ffmpeg: {}", err),
+    }
+}
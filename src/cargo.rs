@@ -0,0 +1,392 @@
+//! Cargo: heavy salvage lies beside the wrecks, and the boat can strap it
+//! under the hull to bring it up. A load is carried where it was picked up,
+//! so one strapped near the bow weighs the bow down and one aft the stern.
+//! Its weight comes off the boat's buoyancy like water in the tanks, and the
+//! drag of it under the keel costs speed and turning. Surfacing with a load
+//! recovers it for points; it can also be let go, and sinks back to the floor.
+
+use crate::controls::ControlCommand;
+use crate::magnetics::Wreck;
+use crate::{AppState, GameAssets, GameMode, GameSet, GameState, Submarine, Surfaced};
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+const SEA_FLOOR: f32 = -20.5;
+const STRAP_REACH: f32 = 5.0; // Meters from the submarine's center a load can be strapped
+const STRAP_SPEED: f32 = 1.5; // Faster than this and the load can't be made fast
+const MAX_LOADS: usize = 2;
+const MOUNT_LENGTH: f32 = 1.8; // Furthest fore or aft of the middle a load can hang
+const MOUNT_DEPTH: f32 = 1.0; // Meters below the centerline a load hangs
+const HANDLING_LOSS: f32 = 1.5; // Speed and turning lost per unit of weight carried
+const TRIM_GAIN: f32 = 1.2; // Radians of pitch per unit of weight hung at the hull's end
+const MAX_TRIM: f32 = 0.35; // Steepest angle a load can pitch the boat to
+const SINK_SPEED: f32 = 1.5; // Meters per second a load let go sinks at
+const MESSAGE_TIME: f32 = 3.0;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CargoKind {
+    Generator,
+    Crate,
+}
+
+impl CargoKind {
+    fn label(self) -> &'static str {
+        match self {
+            CargoKind::Generator => "generator",
+            CargoKind::Crate => "cargo crate",
+        }
+    }
+
+    /// Weight as a share of full ballast tanks
+    pub fn weight(self) -> f32 {
+        match self {
+            CargoKind::Generator => 0.3,
+            CargoKind::Crate => 0.12,
+        }
+    }
+
+    fn points(self) -> u32 {
+        match self {
+            CargoKind::Generator => 200,
+            CargoKind::Crate => 60,
+        }
+    }
+
+    fn size(self) -> Vec3 {
+        match self {
+            CargoKind::Generator => Vec3::new(1.2, 0.9, 1.6),
+            CargoKind::Crate => Vec3::new(0.8, 0.6, 0.8),
+        }
+    }
+}
+
+/// A load of salvage, lying on the floor or strapped under the hull
+#[derive(Component)]
+pub struct Cargo {
+    pub kind: CargoKind,
+    pub strapped: bool,
+}
+
+/// Weight the boat is carrying and where it hangs
+#[derive(Resource, Default)]
+pub struct CargoLoad {
+    pub weight: f32, // Share of full ballast tanks
+    pub moment: f32, // Weight times meters aft of the middle over half the hull; negative is bow-heavy
+    placed: bool,
+    message: String,
+    message_timer: f32,
+}
+
+impl CargoLoad {
+    /// Share of its speed and turning the boat keeps under the load
+    pub fn handling(&self) -> f32 {
+        1.0 / (1.0 + HANDLING_LOSS * self.weight)
+    }
+
+    /// Pitch the load settles the boat to submerged; a bow-heavy load
+    /// (forward is -Z) puts the bow down
+    pub fn trim(&self) -> f32 {
+        (TRIM_GAIN * self.moment).clamp(-MAX_TRIM, MAX_TRIM)
+    }
+}
+
+#[derive(Component)]
+struct CargoText;
+
+/// Generators and crates, made once
+#[derive(Resource)]
+struct CargoAssets {
+    generator_mesh: Handle<Mesh>,
+    generator_material: Handle<StandardMaterial>,
+    crate_mesh: Handle<Mesh>,
+    crate_material: Handle<StandardMaterial>,
+}
+
+impl FromWorld for CargoAssets {
+    fn from_world(world: &mut World) -> Self {
+        let mut meshes = world.resource_mut::<Assets<Mesh>>();
+        let generator_mesh = meshes.add(Cuboid::from_size(CargoKind::Generator.size()));
+        let crate_mesh = meshes.add(Cuboid::from_size(CargoKind::Crate.size()));
+        let mut materials = world.resource_mut::<Assets<StandardMaterial>>();
+        Self {
+            generator_mesh,
+            generator_material: materials.add(StandardMaterial {
+                base_color: Color::srgb(0.35, 0.4, 0.3),
+                metallic: 0.8,
+                perceptual_roughness: 0.6,
+                ..default()
+            }),
+            crate_mesh,
+            crate_material: materials.add(StandardMaterial {
+                base_color: Color::srgb(0.5, 0.4, 0.25),
+                perceptual_roughness: 0.9,
+                ..default()
+            }),
+        }
+    }
+}
+
+pub struct CargoPlugin;
+
+impl Plugin for CargoPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CargoLoad>()
+            .add_systems(OnEnter(AppState::InGame), (reset_cargo, spawn_cargo_text))
+            .add_systems(
+                Update,
+                (
+                    (place_cargo_system, strap_system)
+                        .chain()
+                        .before(crate::submarine_movement)
+                        .in_set(GameSet::Simulation),
+                    (handling_system, recover_system)
+                        .chain()
+                        .after(crate::submarine_movement)
+                        .in_set(GameSet::Simulation),
+                    (sink_system, cargo_text_system).in_set(GameSet::Presentation),
+                )
+                    .run_if(|mode: Res<GameMode>| *mode != GameMode::TimeTrial),
+            );
+    }
+
+    fn finish(&self, app: &mut App) {
+        app.init_resource::<CargoAssets>();
+    }
+}
+
+fn reset_cargo(mut commands: Commands) {
+    commands.insert_resource(CargoLoad::default());
+}
+
+fn spawn_cargo_text(mut commands: Commands, assets: Res<GameAssets>) {
+    commands.spawn((
+        Text::new(""),
+        TextFont {
+            font_size: 18.0,
+            font: assets.font.clone(),
+            ..default()
+        },
+        TextColor(Color::srgb(0.9, 0.8, 0.5)),
+        TextLayout::new_with_justify(JustifyText::Center),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Percent(65.0),
+            width: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        CargoText,
+        StateScoped(AppState::InGame),
+    ));
+}
+
+/// A generator astern of each wreck and a crate off its bow, once the wrecks are down
+fn place_cargo_system(
+    mut commands: Commands,
+    mut load: ResMut<CargoLoad>,
+    assets: Res<CargoAssets>,
+    wreck_query: Query<(&Wreck, &Transform)>,
+) {
+    if load.placed {
+        return;
+    }
+    load.placed = true;
+    for (wreck, transform) in wreck_query.iter() {
+        for (kind, spot, mesh, material) in [
+            (
+                CargoKind::Generator,
+                wreck.beside(transform.translation, -8.0, -1.0),
+                &assets.generator_mesh,
+                &assets.generator_material,
+            ),
+            (
+                CargoKind::Crate,
+                wreck.beside(transform.translation, 8.0, 1.0),
+                &assets.crate_mesh,
+                &assets.crate_material,
+            ),
+        ] {
+            let size = kind.size();
+            commands.spawn((
+                Mesh3d(mesh.clone()),
+                MeshMaterial3d(material.clone()),
+                Transform::from_xyz(spot.x, SEA_FLOOR + size.y / 2.0, spot.z)
+                    .with_rotation(Quat::from_rotation_y(wreck.heading)),
+                Cargo {
+                    kind,
+                    strapped: false,
+                },
+                StateScoped(AppState::InGame),
+            ));
+        }
+    }
+}
+
+/// Straps the nearest load in reach under the hull where it lies, or with
+/// nothing in reach lets one go
+fn strap_system(
+    mut commands: Commands,
+    mut load: ResMut<CargoLoad>,
+    mut control_commands: EventReader<ControlCommand>,
+    submarine_query: Query<(Entity, &Transform, &Velocity), With<Submarine>>,
+    mut cargo_query: Query<(Entity, &mut Cargo, &mut Transform), Without<Submarine>>,
+    time: Res<Time>,
+) {
+    load.message_timer = (load.message_timer - time.delta_secs()).max(0.0);
+    if !control_commands
+        .read()
+        .any(|command| *command == ControlCommand::StrapCargo)
+    {
+        return;
+    }
+    let Ok((submarine_entity, submarine, velocity)) = submarine_query.single() else {
+        return;
+    };
+
+    let strapped: Vec<Entity> = cargo_query
+        .iter()
+        .filter(|(_, cargo, _)| cargo.strapped)
+        .map(|(entity, _, _)| entity)
+        .collect();
+    let nearest = cargo_query
+        .iter()
+        .filter(|(_, cargo, _)| !cargo.strapped)
+        .map(|(entity, _, transform)| {
+            (
+                entity,
+                transform.translation.distance(submarine.translation),
+            )
+        })
+        .filter(|(_, distance)| *distance < STRAP_REACH)
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(entity, _)| entity);
+
+    let message = match nearest {
+        Some(_) if velocity.linvel.length() > STRAP_SPEED => {
+            "Too fast to make a load fast; slow down".to_string()
+        }
+        Some(_) if strapped.len() >= MAX_LOADS => "No room under the hull for more".to_string(),
+        Some(entity) => {
+            let Ok((_, mut cargo, mut transform)) = cargo_query.get_mut(entity) else {
+                return;
+            };
+            // Hung under the keel at the point along the hull it was picked up
+            let local =
+                submarine.rotation.inverse() * (transform.translation - submarine.translation);
+            let along = local.z.clamp(-MOUNT_LENGTH, MOUNT_LENGTH);
+            *transform = Transform::from_xyz(0.0, -MOUNT_DEPTH, along);
+            cargo.strapped = true;
+            let size = cargo.kind.size();
+            commands.entity(entity).insert((
+                ChildOf(submarine_entity),
+                Collider::cuboid(size.x / 2.0, size.y / 2.0, size.z / 2.0),
+            ));
+            load.weight += cargo.kind.weight();
+            load.moment += cargo.kind.weight() * along / MOUNT_LENGTH;
+            format!("Strapped the {} {}", cargo.kind.label(), mount_name(along))
+        }
+        None => match strapped.last() {
+            Some(&entity) => {
+                let Ok((_, mut cargo, mut transform)) = cargo_query.get_mut(entity) else {
+                    return;
+                };
+                let along = transform.translation.z;
+                *transform = submarine.mul_transform(*transform);
+                cargo.strapped = false;
+                commands.entity(entity).remove::<(ChildOf, Collider)>();
+                load.weight = (load.weight - cargo.kind.weight()).max(0.0);
+                load.moment -= cargo.kind.weight() * along / MOUNT_LENGTH;
+                format!("Let go of the {}", cargo.kind.label())
+            }
+            None => "No load within reach".to_string(),
+        },
+    };
+    load.message = message;
+    load.message_timer = MESSAGE_TIME;
+}
+
+fn mount_name(along: f32) -> &'static str {
+    if along < -MOUNT_LENGTH / 3.0 {
+        "under the bow"
+    } else if along > MOUNT_LENGTH / 3.0 {
+        "under the stern"
+    } else {
+        "amidships"
+    }
+}
+
+/// The load drags on the boat's headway
+fn handling_system(
+    load: Res<CargoLoad>,
+    mut submarine_query: Query<&mut Velocity, With<Submarine>>,
+) {
+    let Ok(mut velocity) = submarine_query.single_mut() else {
+        return;
+    };
+    let handling = load.handling();
+    velocity.linvel.x *= handling;
+    velocity.linvel.z *= handling;
+}
+
+/// Surfacing with loads strapped recovers them
+fn recover_system(
+    mut commands: Commands,
+    mut load: ResMut<CargoLoad>,
+    mut game_state: ResMut<GameState>,
+    surfaced: Res<Surfaced>,
+    cargo_query: Query<(Entity, &Cargo)>,
+) {
+    if !surfaced.0 || load.weight <= 0.0 {
+        return;
+    }
+    let mut recovered = Vec::new();
+    for (entity, cargo) in cargo_query.iter().filter(|(_, cargo)| cargo.strapped) {
+        commands.entity(entity).despawn();
+        game_state.score += cargo.kind.points();
+        recovered.push(format!("{} +{}", cargo.kind.label(), cargo.kind.points()));
+    }
+    load.weight = 0.0;
+    load.moment = 0.0;
+    load.message = format!("Recovered {}", recovered.join(", "));
+    load.message_timer = MESSAGE_TIME;
+    info!("{}", load.message);
+}
+
+/// Loads let go sink back to the floor
+fn sink_system(mut cargo_query: Query<(&Cargo, &mut Transform)>, time: Res<Time>) {
+    for (cargo, mut transform) in cargo_query.iter_mut() {
+        let resting = SEA_FLOOR + cargo.kind.size().y / 2.0;
+        if cargo.strapped || transform.translation.y <= resting {
+            continue;
+        }
+        transform.translation.y =
+            (transform.translation.y - SINK_SPEED * time.delta_secs()).max(resting);
+    }
+}
+
+fn cargo_text_system(load: Res<CargoLoad>, mut text_query: Query<&mut Text, With<CargoText>>) {
+    let Ok(mut text) = text_query.single_mut() else {
+        return;
+    };
+    let line = if load.message_timer > 0.0 {
+        load.message.clone()
+    } else if load.weight > 0.0 {
+        let trim = if load.moment < -0.05 {
+            ", bow heavy"
+        } else if load.moment > 0.05 {
+            ", stern heavy"
+        } else {
+            ""
+        };
+        format!(
+            "Carrying {:.0}% of a ballast load{} - surface to recover it",
+            load.weight * 100.0,
+            trim
+        )
+    } else {
+        String::new()
+    };
+    if **text != line {
+        **text = line;
+    }
+}
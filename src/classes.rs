@@ -0,0 +1,136 @@
+//! Submarine classes: the boats a new game can be started in, listed in
+//! `assets/tables.ron`. Each class scales the boat as tuned, its hull and
+//! collider, ballast tanks, air flasks, batteries, crush depth and rudder,
+//! so a small scout turns sharply but runs short of air and power, and a
+//! heavy hauler carries plenty of both but comes about slowly.
+
+use crate::tables::Tables;
+use crate::tuning::Tuning;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// The boat the dive is played in. Every value is a multiple of the boat as
+/// tuned, so a class of all ones plays exactly as `assets/tuning.ron` says
+#[derive(Resource, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SubmarineClass {
+    pub name: String,
+    pub description: String,
+    pub hull: f32,        // Length and beam of the hull and its collider
+    pub ballast: f32,     // Weight of the tanks when full
+    pub air: f32,         // Compressed air the flasks hold
+    pub battery: f32,     // Charge the batteries hold
+    pub crush_depth: f32, // Depth the hull is rated to
+    pub turn_rate: f32,
+}
+
+impl Default for SubmarineClass {
+    /// The boat as tuned, played before there were classes to pick from
+    fn default() -> Self {
+        Self {
+            name: "Patrol Boat".to_string(),
+            description: String::new(),
+            hull: 1.0,
+            ballast: 1.0,
+            air: 1.0,
+            battery: 1.0,
+            crush_depth: 1.0,
+            turn_rate: 1.0,
+        }
+    }
+}
+
+impl SubmarineClass {
+    /// Scales the tuned boat to this class. A bigger hull displaces more water,
+    /// and bigger flasks and batteries fill and drain more slowly for their size
+    pub fn apply(&self, tuning: &mut Tuning) {
+        tuning.turn_speed *= self.turn_rate;
+        tuning.base_buoyancy_force *= self.hull;
+        tuning.ballast_buoyancy_force *= self.ballast;
+        tuning.compressed_air_rate /= self.air;
+        tuning.compressor_power_drain /= self.battery;
+        tuning.floodlight_power_drain /= self.battery;
+        tuning.power_recharge_rate /= self.battery;
+        tuning.crush_depth *= self.crush_depth;
+    }
+
+    /// The class listed after this one in the tables, wrapping around
+    pub fn next(&self, tables: &Tables) -> Option<SubmarineClass> {
+        let index = tables
+            .classes
+            .iter()
+            .position(|class| class.name == self.name)
+            .map_or(0, |index| index + 1);
+        tables
+            .classes
+            .get(index % tables.classes.len().max(1))
+            .cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(tables: &Tables, start: &SubmarineClass, steps: usize) -> Vec<String> {
+        let mut class = start.clone();
+        (0..steps)
+            .map(|_| {
+                class = class.next(tables).expect("tables list classes");
+                class.name.clone()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn next_cycles_through_the_tables() {
+        let tables = Tables::default();
+        let first = &tables.classes[0];
+        let cycle = names(&tables, first, tables.classes.len());
+        assert_eq!(cycle.last(), Some(&first.name));
+        let mut listed: Vec<String> = tables.classes.iter().map(|c| c.name.clone()).collect();
+        listed.rotate_left(1);
+        assert_eq!(cycle, listed);
+    }
+
+    #[test]
+    fn unknown_class_moves_to_the_first() {
+        let tables = Tables::default();
+        let unknown = SubmarineClass {
+            name: "Removed By A Mod".to_string(),
+            ..default()
+        };
+        assert_eq!(unknown.next(&tables), Some(tables.classes[0].clone()));
+    }
+
+    #[test]
+    fn next_without_classes_is_none() {
+        let mut tables = Tables::default();
+        tables.classes.clear();
+        assert_eq!(SubmarineClass::default().next(&tables), None);
+    }
+
+    #[test]
+    fn default_class_plays_as_tuned() {
+        let mut tuning = Tuning::default();
+        SubmarineClass::default().apply(&mut tuning);
+        let tuned = Tuning::default();
+        assert_eq!(tuning.turn_speed, tuned.turn_speed);
+        assert_eq!(tuning.base_buoyancy_force, tuned.base_buoyancy_force);
+        assert_eq!(tuning.compressed_air_rate, tuned.compressed_air_rate);
+        assert_eq!(tuning.crush_depth, tuned.crush_depth);
+    }
+
+    #[test]
+    fn bigger_flasks_drain_more_slowly() {
+        let class = SubmarineClass {
+            air: 2.0,
+            turn_rate: 0.5,
+            ..default()
+        };
+        let mut tuning = Tuning::default();
+        class.apply(&mut tuning);
+        let tuned = Tuning::default();
+        assert_eq!(tuning.compressed_air_rate, tuned.compressed_air_rate / 2.0);
+        assert_eq!(tuning.turn_speed, tuned.turn_speed * 0.5);
+    }
+}
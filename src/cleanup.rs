@@ -0,0 +1,445 @@
+//! Ocean cleanup: barrels and clumps of plastic litter the sea floor and ghost
+//! nets hang snagged on the rocks. The manipulator arm hauls debris aboard for
+//! points and reputation while the boat holds still beside it. Running into a
+//! ghost net fouls the boat and slows it to a crawl until the arm cuts it free.
+
+use crate::controls::ControlCommand;
+use crate::tables::Tables;
+use crate::{
+    AppState, GameAssets, GameMode, GameSet, GameState, Submarine, UnderwaterRock, WorldSeed,
+};
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use rand::{rngs::StdRng, seq::IteratorRandom, Rng, SeedableRng};
+
+const SEA_FLOOR: f32 = -20.5;
+const ARM_REACH: f32 = 5.0; // Meters from the submarine's center the arm can grab
+const ARM_SPEED: f32 = 1.5; // Faster than this and the arm loses its grip
+const HAUL_TIME: f32 = 2.0; // Seconds to haul a piece of debris aboard
+const CUT_TIME: f32 = 4.0; // Seconds to cut a fouled net away
+const NET_WIDTH: f32 = 6.0;
+const NET_HEIGHT: f32 = 3.0;
+const NET_SNAG_SPEED: f32 = 0.5; // Drifting into a net slower than this doesn't foul the boat
+const ENTANGLED_SPEED: f32 = 0.3; // Share of its speed a fouled boat can make
+const MESSAGE_TIME: f32 = 3.0;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DebrisKind {
+    Barrel,
+    Plastic,
+    GhostNet,
+}
+
+impl DebrisKind {
+    fn label(self) -> &'static str {
+        match self {
+            DebrisKind::Barrel => "barrel",
+            DebrisKind::Plastic => "plastic",
+            DebrisKind::GhostNet => "ghost net",
+        }
+    }
+
+    fn points(self) -> u32 {
+        match self {
+            DebrisKind::Barrel => 30,
+            DebrisKind::Plastic => 15,
+            DebrisKind::GhostNet => 50,
+        }
+    }
+
+    fn reputation(self) -> u32 {
+        match self {
+            DebrisKind::Barrel | DebrisKind::Plastic => 1,
+            DebrisKind::GhostNet => 3, // Nets go on killing until someone clears them
+        }
+    }
+}
+
+/// Whether a point is caught in a hanging ghost net, allowing for the hull's width
+pub fn in_net(net: &Transform, point: Vec3) -> bool {
+    let local = net.rotation.inverse() * (point - net.translation);
+    local.x.abs() < NET_WIDTH / 2.0 && local.y.abs() < NET_HEIGHT / 2.0 && local.z.abs() < 1.5
+}
+
+/// Litter the manipulator arm can haul aboard
+#[derive(Component)]
+pub struct Debris {
+    pub kind: DebrisKind,
+}
+
+/// The arm at work on one piece of debris
+#[derive(Clone, Copy)]
+struct ArmTask {
+    target: Entity,
+    progress: f32, // Seconds spent so far
+}
+
+/// How much has been cleared this dive, and what the arm is doing
+#[derive(Resource, Default)]
+pub struct Cleanup {
+    pub collected: u32,
+    pub reputation: u32,
+    pub entangled: Option<Entity>, // Ghost net fouling the submarine
+    arm: Option<ArmTask>,
+    message: String,
+    message_timer: f32,
+}
+
+#[derive(Component)]
+struct CleanupText;
+
+/// Barrels, plastic scrap and ghost nets, made once
+#[derive(Resource)]
+struct DebrisAssets {
+    barrel_mesh: Handle<Mesh>,
+    barrel_material: Handle<StandardMaterial>,
+    scrap_mesh: Handle<Mesh>,
+    plastic_materials: [Handle<StandardMaterial>; 3],
+    net_mesh: Handle<Mesh>,
+    net_material: Handle<StandardMaterial>,
+}
+
+impl FromWorld for DebrisAssets {
+    fn from_world(world: &mut World) -> Self {
+        let mut meshes = world.resource_mut::<Assets<Mesh>>();
+        let barrel_mesh = meshes.add(Cylinder::new(0.4, 1.1));
+        let scrap_mesh = meshes.add(Cuboid::new(0.5, 0.15, 0.35));
+        let net_mesh = meshes.add(Cuboid::new(NET_WIDTH, NET_HEIGHT, 0.05));
+        let mut materials = world.resource_mut::<Assets<StandardMaterial>>();
+        Self {
+            barrel_mesh,
+            barrel_material: materials.add(StandardMaterial {
+                base_color: Color::srgb(0.55, 0.25, 0.1),
+                metallic: 0.6,
+                perceptual_roughness: 0.8,
+                ..default()
+            }),
+            scrap_mesh,
+            plastic_materials: [
+                Color::srgb(0.2, 0.5, 0.9),
+                Color::srgb(0.9, 0.9, 0.85),
+                Color::srgb(0.9, 0.3, 0.3),
+            ]
+            .map(|color| materials.add(color)),
+            net_mesh,
+            net_material: materials.add(StandardMaterial {
+                base_color: Color::srgba(0.4, 0.5, 0.35, 0.5),
+                alpha_mode: AlphaMode::Blend,
+                double_sided: true,
+                cull_mode: None,
+                ..default()
+            }),
+        }
+    }
+}
+
+pub struct CleanupPlugin;
+
+impl Plugin for CleanupPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Cleanup>()
+            .add_systems(
+                OnEnter(AppState::InGame),
+                (
+                    reset_cleanup,
+                    spawn_debris
+                        .after(crate::setup)
+                        .run_if(|mode: Res<GameMode>| *mode != GameMode::TimeTrial),
+                    spawn_cleanup_text,
+                ),
+            )
+            .add_systems(
+                Update,
+                (
+                    (
+                        arm_command_system,
+                        arm_system,
+                        entanglement_system.after(crate::submarine_movement),
+                    )
+                        .chain()
+                        .in_set(GameSet::Simulation),
+                    cleanup_text_system.in_set(GameSet::Presentation),
+                ),
+            );
+    }
+
+    fn finish(&self, app: &mut App) {
+        app.init_resource::<DebrisAssets>();
+    }
+}
+
+fn reset_cleanup(mut commands: Commands) {
+    commands.insert_resource(Cleanup::default());
+}
+
+/// Barrels and plastic on the open floor, and nets snagged on a few of the rocks
+fn spawn_debris(
+    mut commands: Commands,
+    assets: Res<DebrisAssets>,
+    seed: Res<WorldSeed>,
+    tables: Res<Tables>,
+    rock_query: Query<&Transform, With<UnderwaterRock>>,
+) {
+    let mut rng = StdRng::seed_from_u64(seed.0 ^ 0x4A55_4E4B);
+
+    for index in 0..tables.spawns.debris {
+        let angle = rng.gen::<f32>() * std::f32::consts::TAU;
+        let distance = rng.gen_range(20.0..300.0);
+        let position = Vec3::new(
+            angle.cos() * distance,
+            SEA_FLOOR + 0.5,
+            angle.sin() * distance,
+        );
+        let tilt = Quat::from_rotation_z(rng.gen_range(0.0..1.5))
+            * Quat::from_rotation_y(rng.gen::<f32>() * std::f32::consts::TAU);
+        if index % 2 == 0 {
+            commands.spawn((
+                Mesh3d(assets.barrel_mesh.clone()),
+                MeshMaterial3d(assets.barrel_material.clone()),
+                Transform::from_translation(position).with_rotation(tilt),
+                Debris {
+                    kind: DebrisKind::Barrel,
+                },
+                StateScoped(AppState::InGame),
+            ));
+        } else {
+            commands
+                .spawn((
+                    Transform::from_translation(position),
+                    Visibility::default(),
+                    Debris {
+                        kind: DebrisKind::Plastic,
+                    },
+                    StateScoped(AppState::InGame),
+                ))
+                .with_children(|parent| {
+                    for material in assets.plastic_materials.iter() {
+                        let offset = Vec3::new(
+                            rng.gen_range(-0.6..0.6),
+                            rng.gen_range(-0.2..0.2),
+                            rng.gen_range(-0.6..0.6),
+                        );
+                        parent.spawn((
+                            Mesh3d(assets.scrap_mesh.clone()),
+                            MeshMaterial3d(material.clone()),
+                            Transform::from_translation(offset).with_rotation(tilt),
+                        ));
+                    }
+                });
+        }
+    }
+
+    let rocks = rock_query
+        .iter()
+        .choose_multiple(&mut rng, tables.spawns.ghost_nets);
+    for rock in rocks {
+        let yaw = Quat::from_rotation_y(rng.gen::<f32>() * std::f32::consts::TAU);
+        commands.spawn((
+            Mesh3d(assets.net_mesh.clone()),
+            MeshMaterial3d(assets.net_material.clone()),
+            Transform::from_translation(rock.translation + Vec3::Y * (NET_HEIGHT / 2.0 + 1.0))
+                .with_rotation(yaw),
+            Debris {
+                kind: DebrisKind::GhostNet,
+            },
+            StateScoped(AppState::InGame),
+        ));
+    }
+}
+
+fn spawn_cleanup_text(mut commands: Commands, assets: Res<GameAssets>) {
+    commands.spawn((
+        Text::new(""),
+        TextFont {
+            font_size: 20.0,
+            font: assets.font.clone(),
+            ..default()
+        },
+        TextColor(Color::srgb(0.6, 1.0, 0.8)),
+        TextLayout::new_with_justify(JustifyText::Center),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Percent(70.0),
+            width: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        CleanupText,
+        StateScoped(AppState::InGame),
+    ));
+}
+
+/// The arm goes for the net the boat is fouled in, or else the nearest debris
+/// within reach
+fn arm_command_system(
+    mut control_commands: EventReader<ControlCommand>,
+    mut cleanup: ResMut<Cleanup>,
+    submarine_query: Query<(&Transform, &Velocity), With<Submarine>>,
+    debris_query: Query<(Entity, &Transform), With<Debris>>,
+) {
+    if !control_commands
+        .read()
+        .any(|command| *command == ControlCommand::OperateArm)
+    {
+        return;
+    }
+    let Ok((submarine, velocity)) = submarine_query.single() else {
+        return;
+    };
+    if cleanup.arm.is_some() {
+        return;
+    }
+    let target = cleanup.entangled.or_else(|| {
+        debris_query
+            .iter()
+            .map(|(entity, transform)| {
+                (
+                    entity,
+                    transform.translation.distance(submarine.translation),
+                )
+            })
+            .filter(|(_, distance)| *distance < ARM_REACH)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(entity, _)| entity)
+    });
+    let message = match target {
+        None => "Nothing within reach of the arm",
+        Some(_) if velocity.linvel.length() > ARM_SPEED && cleanup.entangled.is_none() => {
+            "Too fast to use the arm; slow down"
+        }
+        Some(target) => {
+            cleanup.arm = Some(ArmTask {
+                target,
+                progress: 0.0,
+            });
+            return;
+        }
+    };
+    cleanup.message = message.to_string();
+    cleanup.message_timer = MESSAGE_TIME;
+}
+
+/// Hauls the debris in towards the hull, or saws at a fouled net, and scores
+/// it once it is aboard; the grip slips if the boat moves off
+fn arm_system(
+    mut commands: Commands,
+    mut cleanup: ResMut<Cleanup>,
+    mut game_state: ResMut<GameState>,
+    submarine_query: Query<(&Transform, &Velocity), With<Submarine>>,
+    mut debris_query: Query<(&Debris, &mut Transform), Without<Submarine>>,
+    time: Res<Time>,
+) {
+    cleanup.message_timer = (cleanup.message_timer - time.delta_secs()).max(0.0);
+    let Some(mut task) = cleanup.arm else {
+        return;
+    };
+    let (Ok((submarine, velocity)), Ok((debris, mut transform))) =
+        (submarine_query.single(), debris_query.get_mut(task.target))
+    else {
+        cleanup.arm = None;
+        return;
+    };
+    let fouled = cleanup.entangled == Some(task.target);
+    let slipped = velocity.linvel.length() > ARM_SPEED
+        || transform.translation.distance(submarine.translation) > ARM_REACH * 1.5;
+    if slipped && !fouled {
+        cleanup.arm = None;
+        cleanup.message = "The arm lost its grip".to_string();
+        cleanup.message_timer = MESSAGE_TIME;
+        return;
+    }
+
+    task.progress += time.delta_secs();
+    let duration = if fouled { CUT_TIME } else { HAUL_TIME };
+    if !fouled {
+        // Drawn in under the hull as the arm folds back
+        let stowed = submarine.translation - Vec3::Y;
+        let step = time.delta_secs() / (duration - task.progress).max(time.delta_secs());
+        transform.translation = transform.translation.lerp(stowed, step.min(1.0));
+    }
+    if task.progress < duration {
+        cleanup.arm = Some(task);
+        return;
+    }
+
+    commands.entity(task.target).despawn();
+    let kind = debris.kind;
+    game_state.score += kind.points();
+    cleanup.collected += 1;
+    cleanup.reputation += kind.reputation();
+    cleanup.arm = None;
+    if fouled {
+        cleanup.entangled = None;
+        cleanup.message = format!("Cut free of the ghost net  +{}", kind.points());
+    } else {
+        cleanup.message = format!("Recovered {}  +{}", kind.label(), kind.points());
+    }
+    cleanup.message_timer = MESSAGE_TIME;
+    info!("{}", cleanup.message);
+}
+
+/// Driving into a ghost net wraps it round the boat, which then crawls along
+/// dragging it until the arm cuts it away
+fn entanglement_system(
+    mut cleanup: ResMut<Cleanup>,
+    mut submarine_query: Query<(&Transform, &mut Velocity), With<Submarine>>,
+    mut net_query: Query<(Entity, &Debris, &mut Transform), Without<Submarine>>,
+) {
+    let Ok((submarine, mut velocity)) = submarine_query.single_mut() else {
+        return;
+    };
+    match cleanup.entangled {
+        Some(net) => {
+            let Ok((_, _, mut transform)) = net_query.get_mut(net) else {
+                cleanup.entangled = None;
+                return;
+            };
+            transform.translation = submarine.translation;
+            transform.rotation = submarine.rotation;
+            velocity.linvel.x *= ENTANGLED_SPEED;
+            velocity.linvel.z *= ENTANGLED_SPEED;
+        }
+        None => {
+            if velocity.linvel.length() < NET_SNAG_SPEED {
+                return;
+            }
+            let snagged = net_query.iter().find(|(_, debris, transform)| {
+                debris.kind == DebrisKind::GhostNet && in_net(transform, submarine.translation)
+            });
+            if let Some((net, _, _)) = snagged {
+                cleanup.entangled = Some(net);
+                cleanup.message = "Fouled in a ghost net! Use the arm to cut free".to_string();
+                cleanup.message_timer = MESSAGE_TIME;
+                warn!("Submarine fouled in a ghost net");
+            }
+        }
+    }
+}
+
+fn cleanup_text_system(cleanup: Res<Cleanup>, mut text_query: Query<&mut Text, With<CleanupText>>) {
+    let Ok(mut text) = text_query.single_mut() else {
+        return;
+    };
+    let line = if cleanup.message_timer > 0.0 {
+        cleanup.message.clone()
+    } else if let Some(task) = cleanup.arm {
+        let duration = if cleanup.entangled == Some(task.target) {
+            CUT_TIME
+        } else {
+            HAUL_TIME
+        };
+        let filled = (task.progress / duration * 20.0) as usize;
+        format!(
+            "Manipulator arm at work\n[{}{}]",
+            "#".repeat(filled),
+            "-".repeat(20 - filled.min(20))
+        )
+    } else if cleanup.entangled.is_some() {
+        "Fouled in a ghost net".to_string()
+    } else {
+        String::new()
+    };
+    if **text != line {
+        **text = line;
+    }
+}
@@ -0,0 +1,521 @@
+use crate::acoustics::Detonation;
+use crate::biophony::{masking_at, NoiseSource, SIGHT_RANGE};
+use crate::controls::ControlCommand;
+use crate::difficulty::Difficulty;
+use crate::doppler::Doppler;
+use crate::enemy_submarine::EnemySubmarine;
+use crate::game_over::hull_intact;
+use crate::new_game_plus::{DECOY_SUSPICION, LEAD_TIME};
+use crate::settings::Settings;
+use crate::sfx::Hum;
+use crate::splash::Breaching;
+use crate::stealth::SubmarineNoise;
+use crate::{AppState, Fish, GameSet, GameState, NewGamePlus, Submarine};
+use bevy::audio::Volume;
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+const TORPEDO_SPEED: f32 = 25.0;
+const TORPEDO_LIFETIME: f32 = 4.0;
+const TORPEDO_DAMAGE: f32 = 50.0;
+const TORPEDO_BLAST_RADIUS: f32 = 4.0; // Hostiles this close to the impact are hit too
+const TORPEDO_RELOAD: f32 = 1.0; // Seconds between launches
+const TORPEDO_SPLASH_SIZE: f32 = 0.8; // Radius of the foam ring a torpedo throws breaking the surface
+const DECOY_LIFETIME: f32 = 12.0;
+const DECOY_RANGE: f32 = 30.0; // Hostiles within this distance chase the decoy instead
+const DECOY_SPEED: f32 = 0.5; // Decoys drift slowly towards the surface
+const BAIT_LIFETIME: f32 = 20.0;
+pub const BAIT_RANGE: f32 = 20.0; // Fish within this distance swim to the bait
+const BAIT_SCENT_RANGE: f32 = 40.0; // Hostiles smell it from further off than decoys are heard
+const BAIT_SINK_SPEED: f32 = 0.3; // Bait settles slowly towards the sea floor
+const SEA_FLOOR: f32 = -20.5;
+const HUNT_RANGE: f32 = 10.0; // Hostiles turn on fish this close instead of their prey
+const HIT_DISTANCE: f32 = 2.0;
+const STARTING_TORPEDOES: u32 = 6;
+const STARTING_DECOYS: u32 = 3;
+const STARTING_BAIT: u32 = 3;
+const TORPEDO_HUM: Hum = Hum {
+    frequency: 330.0,
+    volume: 0.5,
+};
+const HOSTILE_HUM: Hum = Hum {
+    frequency: 70.0,
+    volume: 0.7,
+};
+
+/// A creature that hunts `prey` and damages it on contact, then swims off
+#[derive(Component)]
+pub struct Hostile {
+    pub health: f32,
+    pub speed: f32,
+    pub damage: f32,
+    pub prey: Entity,
+    pub reach: f32, // Distance from the prey's center at which the hostile strikes
+}
+
+/// Sent when a hostile reaches its prey
+#[derive(Event)]
+pub struct HostileAttack {
+    pub prey: Entity,
+    pub damage: f32,
+    pub position: Vec3, // Where the hostile struck from
+}
+
+/// Sent when a hostile catches and eats a fish at `position`
+#[derive(Event)]
+pub struct FishEaten {
+    pub position: Vec3,
+}
+
+/// Torpedoes, decoys and bait canisters left aboard
+#[derive(Resource)]
+pub struct Armament {
+    pub torpedoes: u32,
+    pub decoys: u32,
+    pub bait: u32,
+    reload: f32,
+}
+
+impl Default for Armament {
+    fn default() -> Self {
+        Self {
+            torpedoes: STARTING_TORPEDOES,
+            decoys: STARTING_DECOYS,
+            bait: STARTING_BAIT,
+            reload: 0.0,
+        }
+    }
+}
+
+#[derive(Component)]
+struct Torpedo {
+    velocity: Vec3,
+    life: f32,
+}
+
+/// Noisemaker that draws nearby hostiles away from their prey
+#[derive(Component)]
+struct Decoy {
+    life: f32,
+}
+
+/// Chum canister that draws fish in while it lasts, and hostiles with them
+#[derive(Component)]
+pub struct Bait {
+    life: f32,
+}
+
+/// Hostiles and what the boat launches at them, made once and shared by
+/// every spawn
+#[derive(Resource)]
+pub struct CombatAssets {
+    hostile_mesh: Handle<Mesh>, // Shark-like body along local Z
+    hostile_fin_mesh: Handle<Mesh>,
+    hostile_material: Handle<StandardMaterial>,
+    torpedo_mesh: Handle<Mesh>,
+    torpedo_material: Handle<StandardMaterial>,
+    decoy_mesh: Handle<Mesh>,
+    decoy_material: Handle<StandardMaterial>,
+    bait_mesh: Handle<Mesh>,
+    bait_material: Handle<StandardMaterial>,
+}
+
+impl FromWorld for CombatAssets {
+    fn from_world(world: &mut World) -> Self {
+        let mut meshes = world.resource_mut::<Assets<Mesh>>();
+        let hostile_mesh = meshes.add(Capsule3d::new(0.6, 2.5));
+        let hostile_fin_mesh = meshes.add(Cone::new(0.4, 1.0));
+        let torpedo_mesh = meshes.add(Capsule3d::new(0.15, 1.0));
+        let decoy_mesh = meshes.add(Sphere::new(0.3));
+        let bait_mesh = meshes.add(Sphere::new(0.25));
+        let mut materials = world.resource_mut::<Assets<StandardMaterial>>();
+        Self {
+            hostile_mesh,
+            hostile_fin_mesh,
+            hostile_material: materials.add(StandardMaterial {
+                base_color: Color::srgb(0.25, 0.27, 0.3),
+                perceptual_roughness: 0.6,
+                ..default()
+            }),
+            torpedo_mesh,
+            torpedo_material: materials.add(StandardMaterial {
+                base_color: Color::srgb(0.2, 0.2, 0.2),
+                emissive: LinearRgba::rgb(1.0, 0.4, 0.1),
+                ..default()
+            }),
+            decoy_mesh,
+            decoy_material: materials.add(StandardMaterial {
+                base_color: Color::srgb(1.0, 0.9, 0.3),
+                emissive: LinearRgba::rgb(2.0, 1.6, 0.4),
+                ..default()
+            }),
+            bait_mesh,
+            bait_material: materials.add(StandardMaterial {
+                base_color: Color::srgb(0.6, 0.15, 0.1),
+                emissive: LinearRgba::rgb(0.4, 0.05, 0.02),
+                ..default()
+            }),
+        }
+    }
+}
+
+pub struct CombatPlugin;
+
+impl Plugin for CombatPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<HostileAttack>()
+            .add_event::<FishEaten>()
+            .init_resource::<Armament>()
+            .add_systems(OnEnter(AppState::InGame), reset_armament)
+            .add_systems(
+                Update,
+                (
+                    weapon_command_system.run_if(hull_intact),
+                    torpedo_system,
+                    decoy_system,
+                    bait_system,
+                    hostile_system,
+                    submarine_attack_system,
+                )
+                    .chain()
+                    .in_set(GameSet::Simulation),
+            )
+            .add_systems(Update, travel_sound_system.in_set(GameSet::Presentation));
+    }
+
+    fn finish(&self, app: &mut App) {
+        app.init_resource::<CombatAssets>();
+    }
+}
+
+fn reset_armament(mut commands: Commands) {
+    commands.insert_resource(Armament::default());
+}
+
+/// Spawns a shark-like hostile; its body lies along the local Z axis like the submarine's
+pub fn spawn_hostile(
+    commands: &mut Commands,
+    assets: &CombatAssets,
+    position: Vec3,
+    hostile: Hostile,
+) {
+    commands
+        .spawn((
+            Transform::from_translation(position),
+            Visibility::default(),
+            hostile,
+            StateScoped(AppState::InGame),
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Mesh3d(assets.hostile_mesh.clone()),
+                MeshMaterial3d(assets.hostile_material.clone()),
+                Transform::from_rotation(Quat::from_rotation_x(std::f32::consts::FRAC_PI_2)),
+            ));
+            // Dorsal fin
+            parent.spawn((
+                Mesh3d(assets.hostile_fin_mesh.clone()),
+                MeshMaterial3d(assets.hostile_material.clone()),
+                Transform::from_xyz(0.0, 0.8, 0.2),
+            ));
+        });
+}
+
+/// Launches torpedoes from the bow and releases decoys and bait from the stern
+fn weapon_command_system(
+    mut commands: Commands,
+    mut control_commands: EventReader<ControlCommand>,
+    mut armament: ResMut<Armament>,
+    assets: Res<CombatAssets>,
+    time: Res<Time>,
+    submarine_query: Query<&Transform, With<Submarine>>,
+) {
+    armament.reload = (armament.reload - time.delta_secs()).max(0.0);
+    let Ok(submarine) = submarine_query.single() else {
+        control_commands.clear();
+        return;
+    };
+    let forward = submarine.rotation * Vec3::NEG_Z;
+
+    for command in control_commands.read() {
+        match command {
+            ControlCommand::FireTorpedo if armament.torpedoes > 0 && armament.reload <= 0.0 => {
+                armament.torpedoes -= 1;
+                armament.reload = TORPEDO_RELOAD;
+                commands.spawn((
+                    Mesh3d(assets.torpedo_mesh.clone()),
+                    MeshMaterial3d(assets.torpedo_material.clone()),
+                    Transform::from_translation(submarine.translation + forward * 3.0)
+                        .with_rotation(
+                            submarine.rotation * Quat::from_rotation_x(std::f32::consts::FRAC_PI_2),
+                        ),
+                    Torpedo {
+                        velocity: forward * TORPEDO_SPEED,
+                        life: TORPEDO_LIFETIME,
+                    },
+                    Breaching::new(TORPEDO_SPLASH_SIZE),
+                    StateScoped(AppState::InGame),
+                ));
+            }
+            ControlCommand::DropDecoy if armament.decoys > 0 => {
+                armament.decoys -= 1;
+                commands.spawn((
+                    Mesh3d(assets.decoy_mesh.clone()),
+                    MeshMaterial3d(assets.decoy_material.clone()),
+                    Transform::from_translation(submarine.translation - forward * 3.0),
+                    Decoy {
+                        life: DECOY_LIFETIME,
+                    },
+                    StateScoped(AppState::InGame),
+                ));
+            }
+            ControlCommand::ReleaseBait if armament.bait > 0 => {
+                armament.bait -= 1;
+                commands.spawn((
+                    Mesh3d(assets.bait_mesh.clone()),
+                    MeshMaterial3d(assets.bait_material.clone()),
+                    Transform::from_translation(submarine.translation - forward * 3.0),
+                    Bait {
+                        life: BAIT_LIFETIME,
+                    },
+                    StateScoped(AppState::InGame),
+                ));
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Gives torpedoes a motor whine and hostiles a low thrum, placed in the world
+/// and Doppler shifted so they can be heard closing in or moving away
+fn travel_sound_system(
+    mut commands: Commands,
+    mut hums: ResMut<Assets<Hum>>,
+    settings: Res<Settings>,
+    torpedo_query: Query<Entity, Added<Torpedo>>,
+    hostile_query: Query<Entity, Added<Hostile>>,
+) {
+    let sounds = torpedo_query
+        .iter()
+        .map(|entity| (entity, TORPEDO_HUM))
+        .chain(hostile_query.iter().map(|entity| (entity, HOSTILE_HUM)));
+    for (entity, hum) in sounds {
+        commands.entity(entity).try_insert((
+            AudioPlayer(hums.add(hum)),
+            PlaybackSettings::LOOP
+                .with_spatial(true)
+                .with_volume(Volume::Linear(settings.audio.effects_volume)),
+            Doppler::default(),
+        ));
+    }
+}
+
+/// Runs torpedoes forward until they hit a hostile or enemy submarine or run
+/// out of fuel; the warhead damages every one of them within its blast radius
+fn torpedo_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut detonations: EventWriter<Detonation>,
+    mut torpedo_query: Query<(Entity, &mut Torpedo, &mut Transform)>,
+    mut hostile_query: Query<(Entity, &mut Hostile, &Transform), Without<Torpedo>>,
+    mut enemy_query: Query<(Entity, &mut EnemySubmarine, &Transform), Without<Torpedo>>,
+) {
+    for (entity, mut torpedo, mut transform) in torpedo_query.iter_mut() {
+        transform.translation += torpedo.velocity * time.delta_secs();
+        torpedo.life -= time.delta_secs();
+
+        let impact = hostile_query
+            .iter()
+            .map(|(_, _, target)| target)
+            .chain(enemy_query.iter().map(|(_, _, target)| target))
+            .any(|target| target.translation.distance(transform.translation) < HIT_DISTANCE);
+        if impact {
+            detonations.write(Detonation {
+                position: transform.translation,
+                loudness: 0.8,
+            });
+            for (hostile_entity, mut hostile, hostile_transform) in hostile_query.iter_mut() {
+                if hostile_transform
+                    .translation
+                    .distance(transform.translation)
+                    < TORPEDO_BLAST_RADIUS
+                {
+                    hostile.health -= TORPEDO_DAMAGE;
+                    if hostile.health <= 0.0 {
+                        commands.entity(hostile_entity).try_despawn();
+                    }
+                }
+            }
+            for (enemy_entity, mut enemy, enemy_transform) in enemy_query.iter_mut() {
+                if enemy_transform.translation.distance(transform.translation)
+                    < TORPEDO_BLAST_RADIUS
+                {
+                    enemy.health -= TORPEDO_DAMAGE;
+                    if enemy.health <= 0.0 {
+                        commands.entity(enemy_entity).try_despawn();
+                    }
+                }
+            }
+        }
+        if impact || torpedo.life <= 0.0 {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+fn decoy_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut decoy_query: Query<(Entity, &mut Decoy, &mut Transform)>,
+) {
+    for (entity, mut decoy, mut transform) in decoy_query.iter_mut() {
+        decoy.life -= time.delta_secs();
+        transform.translation.y =
+            (transform.translation.y + DECOY_SPEED * time.delta_secs()).min(-0.5);
+        if decoy.life <= 0.0 {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+/// Bait sinks slowly and dissolves
+fn bait_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut bait_query: Query<(Entity, &mut Bait, &mut Transform)>,
+) {
+    for (entity, mut bait, mut transform) in bait_query.iter_mut() {
+        bait.life -= time.delta_secs();
+        transform.translation.y =
+            (transform.translation.y - BAIT_SINK_SPEED * time.delta_secs()).max(SEA_FLOOR + 0.3);
+        if bait.life <= 0.0 {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+/// Decoys and bait, and whether each is bait
+type LureQuery<'w, 's> = Query<'w, 's, (Entity, Has<Bait>), Or<(With<Decoy>, With<Bait>)>>;
+
+/// Fish the hostiles can eat
+type PreyFishQuery<'w, 's> =
+    Query<'w, 's, (Entity, &'static Transform), (With<Fish>, Without<Hostile>)>;
+
+/// Hostiles chase the nearest decoy or bait in range, then any fish close by,
+/// and otherwise their prey. Reaching a decoy or bait destroys it and reaching
+/// a fish eats it; the prey is struck when reached, or when brushed past while
+/// feeding. A submarine quieter than the wildlife around it goes unheard, and
+/// hostiles that can't see it swim on past.
+#[allow(clippy::too_many_arguments)]
+fn hostile_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut attacks: EventWriter<HostileAttack>,
+    mut eaten: EventWriter<FishEaten>,
+    difficulty: Res<Difficulty>,
+    new_game_plus: Res<NewGamePlus>,
+    mut hostile_query: Query<(Entity, &Hostile, &mut Transform)>,
+    lure_query: LureQuery,
+    fish_query: PreyFishQuery,
+    target_query: Query<&Transform, Without<Hostile>>,
+    velocity_query: Query<&Velocity>,
+    submarine_query: Query<(), With<Submarine>>,
+    source_query: Query<(&Transform, &NoiseSource), Without<Hostile>>,
+    noise: Res<SubmarineNoise>,
+) {
+    for (entity, hostile, mut transform) in hostile_query.iter_mut() {
+        let position = transform.translation;
+        let lure = lure_query
+            .iter()
+            .filter_map(|(lure, bait)| {
+                let range = match (bait, new_game_plus.active()) {
+                    (true, _) => BAIT_SCENT_RANGE,
+                    // Seasoned predators only fall for a decoy right in front of them
+                    (false, true) => DECOY_RANGE * DECOY_SUSPICION,
+                    (false, false) => DECOY_RANGE,
+                };
+                let target = target_query.get(lure).ok()?;
+                let distance = target.translation.distance(position);
+                (distance < range).then_some((lure, distance, target))
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1));
+        let fish = fish_query
+            .iter()
+            .map(|(fish, target)| (fish, target.translation.distance(position), target))
+            .filter(|(_, distance, _)| *distance < HUNT_RANGE)
+            .min_by(|a, b| a.1.total_cmp(&b.1));
+
+        let goal = match (lure, fish) {
+            (Some((lure, distance, lure_transform)), _) => {
+                if distance < HIT_DISTANCE {
+                    // Several hostiles may reach the same lure in one frame
+                    commands.entity(lure).try_despawn();
+                }
+                lure_transform.translation
+            }
+            (None, Some((fish, distance, fish_transform))) => {
+                if distance < HIT_DISTANCE {
+                    commands.entity(fish).try_despawn();
+                    eaten.write(FishEaten {
+                        position: fish_transform.translation,
+                    });
+                }
+                fish_transform.translation
+            }
+            (None, None) => {
+                let Ok(prey) = target_query.get(hostile.prey) else {
+                    continue;
+                };
+                let masked = submarine_query.contains(hostile.prey)
+                    && prey.translation.distance(position) > SIGHT_RANGE
+                    && masking_at(source_query.iter(), prey.translation, time.elapsed_secs()).0
+                        >= noise.0;
+                match velocity_query.get(hostile.prey) {
+                    _ if masked => position + *transform.forward(),
+                    // In New Game Plus they cut the prey off instead of tailing it
+                    Ok(velocity) if new_game_plus.active() => {
+                        let lead = (prey.translation.distance(position) / hostile.speed.max(0.1))
+                            .min(LEAD_TIME);
+                        prey.translation + velocity.linvel * lead
+                    }
+                    _ => prey.translation,
+                }
+            }
+        };
+        if lure.is_none() {
+            if let Ok(prey) = target_query.get(hostile.prey) {
+                if prey.translation.distance(position) < hostile.reach {
+                    attacks.write(HostileAttack {
+                        prey: hostile.prey,
+                        damage: hostile.damage * difficulty.aggression(),
+                        position,
+                    });
+                    commands.entity(entity).despawn();
+                    continue;
+                }
+            }
+        }
+
+        let direction = (goal - position).normalize_or_zero();
+        transform.translation +=
+            direction * hostile.speed * difficulty.aggression() * time.delta_secs();
+        // Hostiles don't break the surface
+        transform.translation.y = transform.translation.y.min(-0.5);
+        if direction != Vec3::ZERO {
+            transform.look_to(direction, Vec3::Y);
+        }
+    }
+}
+
+/// Strikes on the submarine damage the hull
+fn submarine_attack_system(
+    mut attacks: EventReader<HostileAttack>,
+    mut game_state: ResMut<GameState>,
+    submarine_query: Query<Entity, With<Submarine>>,
+) {
+    for attack in attacks.read() {
+        if submarine_query.contains(attack.prey) {
+            game_state.health = (game_state.health - attack.damage).max(0.0);
+        }
+    }
+}
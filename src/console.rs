@@ -0,0 +1,202 @@
+//! Drop-down console, opened with the backquote key, for reading and changing cvars
+
+use crate::cvars::Cvars;
+use crate::{GameAssets, GameSet};
+use bevy::input::keyboard::{Key, KeyboardInput};
+use bevy::input::ButtonState;
+use bevy::prelude::*;
+
+const VISIBLE_LINES: usize = 14;
+const MAX_LINES: usize = 200; // Older output is dropped
+
+#[derive(Resource, Default)]
+pub struct ConsoleState {
+    pub open: bool,
+    input: String,
+    lines: Vec<String>,
+}
+
+impl ConsoleState {
+    fn print(&mut self, line: impl Into<String>) {
+        self.lines.push(line.into());
+        if self.lines.len() > MAX_LINES {
+            self.lines.remove(0);
+        }
+    }
+}
+
+#[derive(Component)]
+struct ConsolePanel;
+
+#[derive(Component)]
+struct ConsoleText;
+
+pub struct ConsolePlugin;
+
+impl Plugin for ConsolePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ConsoleState>()
+            // Typing into the console shouldn't also steer the submarine
+            .configure_sets(
+                Update,
+                GameSet::Input.run_if(|console: Res<ConsoleState>| !console.open),
+            )
+            .add_systems(Startup, spawn_console)
+            .add_systems(
+                Update,
+                (console_input_system, console_display_system)
+                    .chain()
+                    .before(GameSet::Input),
+            );
+    }
+}
+
+fn spawn_console(mut commands: Commands, assets: Res<GameAssets>) {
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                top: Val::Px(0.0),
+                width: Val::Percent(100.0),
+                padding: UiRect::all(Val::Px(8.0)),
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.8)),
+            GlobalZIndex(100),
+            Visibility::Hidden,
+            ConsolePanel,
+        ))
+        .with_children(|panel| {
+            panel.spawn((
+                Text::new(""),
+                TextFont {
+                    font_size: 15.0,
+                    font: assets.font.clone(),
+                    ..default()
+                },
+                TextColor(Color::srgb(0.8, 1.0, 0.8)),
+                ConsoleText,
+            ));
+        });
+}
+
+fn console_input_system(
+    mut keyboard_events: EventReader<KeyboardInput>,
+    mut console: ResMut<ConsoleState>,
+    mut cvars: ResMut<Cvars>,
+) {
+    for event in keyboard_events.read() {
+        if event.state != ButtonState::Pressed {
+            continue;
+        }
+        if event.key_code == KeyCode::Backquote {
+            console.open = !console.open;
+            continue;
+        }
+        if !console.open {
+            continue;
+        }
+        match &event.logical_key {
+            Key::Enter => {
+                let line = std::mem::take(&mut console.input);
+                console.print(format!("> {}", line));
+                run_command(line.trim(), &mut console, &mut cvars);
+            }
+            Key::Backspace => {
+                console.input.pop();
+            }
+            _ => {
+                if let Some(text) = &event.text {
+                    console
+                        .input
+                        .extend(text.chars().filter(|c| !c.is_control()));
+                }
+            }
+        }
+    }
+}
+
+fn run_command(line: &str, console: &mut ConsoleState, cvars: &mut Cvars) {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    match words.as_slice() {
+        [] => {}
+        ["help"] => {
+            console.print("list [prefix]    show cvars");
+            console.print("<name>           show one cvar");
+            console.print("<name> <value>   change a cvar");
+            console.print("reset <name>     restore the default");
+            console.print("save             write changed cvars to cvars.cfg");
+        }
+        ["list"] | ["list", _] => {
+            let prefix = words.get(1).copied().unwrap_or("");
+            let listing: Vec<String> = cvars
+                .iter()
+                .filter(|(name, _)| name.starts_with(prefix))
+                .map(|(name, cvar)| format!("{} = {}  ({})", name, cvar.value, cvar.description))
+                .collect();
+            for line in listing {
+                console.print(line);
+            }
+        }
+        ["reset", name] => {
+            let result = match cvars.lookup(name) {
+                Some(cvar) => cvars.set(name, cvar.default),
+                None => Err(format!("Unknown cvar {}", name)),
+            };
+            console.print(match result {
+                Ok(value) => format!("{} = {}", name, value),
+                Err(err) => err,
+            });
+        }
+        ["save"] => console.print(match cvars.save() {
+            Ok(count) => format!("Saved {} changed cvars", count),
+            Err(err) => format!("Cannot save: {}", err),
+        }),
+        [name] => match cvars.lookup(name) {
+            Some(cvar) => console.print(format!(
+                "{} = {}  (default {}, {} to {})  {}",
+                name,
+                cvar.value,
+                cvar.default,
+                cvar.range.start(),
+                cvar.range.end(),
+                cvar.description
+            )),
+            None => console.print(format!("Unknown command or cvar {}", name)),
+        },
+        [name, value] => match value.parse::<f32>() {
+            Ok(value) => console.print(match cvars.set(name, value) {
+                Ok(value) => format!("{} = {}", name, value),
+                Err(err) => err,
+            }),
+            Err(_) => console.print(format!("{} is not a number", value)),
+        },
+        _ => console.print("Type help for a list of commands"),
+    }
+}
+
+fn console_display_system(
+    console: Res<ConsoleState>,
+    mut panel_query: Query<&mut Visibility, With<ConsolePanel>>,
+    mut text_query: Query<&mut Text, With<ConsoleText>>,
+) {
+    if !console.is_changed() {
+        return;
+    }
+    if let Ok(mut visibility) = panel_query.single_mut() {
+        visibility.set_if_neq(if console.open {
+            Visibility::Visible
+        } else {
+            Visibility::Hidden
+        });
+    }
+    if let Ok(mut text) = text_query.single_mut() {
+        let start = console.lines.len().saturating_sub(VISIBLE_LINES);
+        let mut contents = console.lines[start..].join("\n");
+        if !contents.is_empty() {
+            contents.push('\n');
+        }
+        contents.push_str(&format!("> {}_", console.input));
+        **text = contents;
+    }
+}
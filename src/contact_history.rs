@@ -0,0 +1,262 @@
+//! Contact depth history: a strip chart under the sonar panel tracing how far
+//! above or below the boat the selected contact has been, so a school that is
+//! rising or diving shows before the ballast is trimmed to chase it. The
+//! select key steps through the contacts from the nearest out.
+
+use crate::controls::ControlCommand;
+use crate::hud::SonarPanel;
+use crate::{GameAssets, GameSet, SonarDetections, Submarine, SONAR_CENTER_X, SONAR_CENTER_Y};
+use bevy::prelude::*;
+use std::collections::VecDeque;
+
+pub const STRIP_HEIGHT: f32 = 56.0; // Pixels the strip takes up below the sonar panel
+const CHART_HEIGHT: f32 = 36.0;
+const CHART_RANGE: f32 = 10.0; // Meters above or below the boat at the chart's edges
+const SAMPLE_INTERVAL: f32 = 0.5; // Seconds between samples
+const HISTORY_LENGTH: usize = 60; // Samples kept, half a minute
+const TREND_SAMPLES: usize = 6; // Samples back the trend is measured over
+const TREND_THRESHOLD: f32 = 0.3; // Meters of change over the trend that count as moving
+
+/// The sonar contact being followed, by the entity that echoes back
+#[derive(Resource, Default)]
+pub struct SelectedContact(pub Option<Entity>);
+
+/// Depth of the selected contact relative to the boat, newest last; gaps
+/// where it dropped off the sonar are kept so time runs evenly
+#[derive(Resource, Default)]
+struct DepthHistory {
+    samples: VecDeque<Option<f32>>,
+    since_sample: f32,
+}
+
+impl DepthHistory {
+    /// Meters the contact has risen relative to the boat over the last few samples
+    fn trend(&self) -> Option<f32> {
+        let latest = self.samples.back().copied().flatten()?;
+        let earlier = self
+            .samples
+            .iter()
+            .rev()
+            .nth(TREND_SAMPLES)
+            .copied()
+            .flatten()?;
+        Some(earlier - latest)
+    }
+}
+
+#[derive(Component)]
+struct HistoryBar(usize);
+
+#[derive(Component)]
+struct HistoryText;
+
+pub struct ContactHistoryPlugin;
+
+impl Plugin for ContactHistoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DepthHistory>().add_systems(
+            Update,
+            (
+                spawn_strip,
+                select_contact_system.in_set(GameSet::Simulation),
+                record_history_system
+                    .after(crate::sonar_detection_system)
+                    .in_set(GameSet::Detection),
+                history_chart_system.in_set(GameSet::Presentation),
+            ),
+        );
+    }
+}
+
+/// Hangs the strip under each new sonar panel
+fn spawn_strip(
+    mut commands: Commands,
+    mut history: ResMut<DepthHistory>,
+    assets: Res<GameAssets>,
+    panel_query: Query<Entity, Added<SonarPanel>>,
+) {
+    for panel in panel_query.iter() {
+        *history = DepthHistory::default();
+        // As wide as the sonar panel, whatever size it is shown at
+        let bar_width = 100.0 / HISTORY_LENGTH as f32;
+        commands
+            .spawn((
+                Node {
+                    position_type: PositionType::Absolute,
+                    top: Val::Percent(100.0),
+                    left: Val::Px(0.0),
+                    width: Val::Percent(100.0),
+                    height: Val::Px(STRIP_HEIGHT),
+                    flex_direction: FlexDirection::Column,
+                    row_gap: Val::Px(2.0),
+                    ..default()
+                },
+                BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.5)),
+                ChildOf(panel),
+            ))
+            .with_children(|strip| {
+                strip.spawn((
+                    Text::new(""),
+                    TextFont {
+                        font_size: 11.0,
+                        font: assets.font.clone(),
+                        ..default()
+                    },
+                    TextColor(Color::srgb(0.6, 1.0, 0.6)),
+                    HistoryText,
+                ));
+                strip
+                    .spawn(Node {
+                        width: Val::Percent(100.0),
+                        height: Val::Px(CHART_HEIGHT),
+                        ..default()
+                    })
+                    .with_children(|chart| {
+                        // The boat's own depth across the middle
+                        chart.spawn((
+                            Node {
+                                position_type: PositionType::Absolute,
+                                top: Val::Px(CHART_HEIGHT / 2.0),
+                                width: Val::Percent(100.0),
+                                height: Val::Px(1.0),
+                                ..default()
+                            },
+                            BackgroundColor(Color::srgba(0.0, 1.0, 0.0, 0.4)),
+                        ));
+                        for index in 0..HISTORY_LENGTH {
+                            chart.spawn((
+                                Node {
+                                    position_type: PositionType::Absolute,
+                                    left: Val::Percent(index as f32 * bar_width),
+                                    width: Val::Percent(bar_width),
+                                    height: Val::Px(0.0),
+                                    ..default()
+                                },
+                                BackgroundColor(Color::srgb(1.0, 0.75, 0.1)),
+                                HistoryBar(index),
+                            ));
+                        }
+                    });
+            });
+    }
+}
+
+/// Steps to the next contact further out, and off the end back to none. The
+/// selection is an order, so a replay follows the same contacts
+fn select_contact_system(
+    mut control_commands: EventReader<ControlCommand>,
+    detections: Res<SonarDetections>,
+    mut selected: ResMut<SelectedContact>,
+    mut history: ResMut<DepthHistory>,
+) {
+    if !control_commands
+        .read()
+        .any(|command| *command == ControlCommand::SelectContact)
+    {
+        return;
+    }
+    let mut contacts: Vec<(Entity, f32)> = detections
+        .contacts
+        .iter()
+        .filter_map(|contact| {
+            let range = Vec2::new(contact.x - SONAR_CENTER_X, contact.y - SONAR_CENTER_Y).length();
+            contact.target.map(|target| (target, range))
+        })
+        .collect();
+    contacts.sort_by(|a, b| a.1.total_cmp(&b.1));
+    let next = match selected
+        .0
+        .and_then(|current| contacts.iter().position(|(target, _)| *target == current))
+    {
+        Some(index) => contacts.get(index + 1),
+        None => contacts.first(),
+    };
+    selected.0 = next.map(|(target, _)| *target);
+    *history = DepthHistory::default();
+}
+
+/// Samples the selected contact's depth against the boat's at a steady rate,
+/// and lets the selection go once the contact is gone for good
+fn record_history_system(
+    detections: Res<SonarDetections>,
+    mut selected: ResMut<SelectedContact>,
+    mut history: ResMut<DepthHistory>,
+    submarine_query: Query<&Transform, With<Submarine>>,
+    target_query: Query<(), With<Transform>>,
+    time: Res<Time>,
+) {
+    let Some(target) = selected.0 else {
+        return;
+    };
+    if !target_query.contains(target) {
+        selected.0 = None;
+        return;
+    }
+    let Ok(submarine) = submarine_query.single() else {
+        return;
+    };
+    history.since_sample += time.delta_secs();
+    if history.since_sample < SAMPLE_INTERVAL {
+        return;
+    }
+    history.since_sample -= SAMPLE_INTERVAL;
+    let difference = detections
+        .contacts
+        .iter()
+        .find(|contact| contact.target == Some(target))
+        .map(|contact| contact.depth + submarine.translation.y);
+    history.samples.push_back(difference);
+    if history.samples.len() > HISTORY_LENGTH {
+        history.samples.pop_front();
+    }
+}
+
+/// Bars reach up from the middle for a contact above the boat and down for
+/// one below, newest on the right
+fn history_chart_system(
+    selected: Res<SelectedContact>,
+    history: Res<DepthHistory>,
+    mut bar_query: Query<(&HistoryBar, &mut Node)>,
+    mut text_query: Query<&mut Text, With<HistoryText>>,
+) {
+    let offset = HISTORY_LENGTH - history.samples.len();
+    for (bar, mut node) in bar_query.iter_mut() {
+        let sample = bar
+            .0
+            .checked_sub(offset)
+            .and_then(|index| history.samples.get(index).copied().flatten());
+        let Some(difference) = sample else {
+            node.height = Val::Px(0.0);
+            continue;
+        };
+        let length = (difference.abs() / CHART_RANGE).min(1.0) * CHART_HEIGHT / 2.0;
+        node.height = Val::Px(length.max(1.0));
+        node.top = Val::Px(if difference < 0.0 {
+            CHART_HEIGHT / 2.0 - length
+        } else {
+            CHART_HEIGHT / 2.0
+        });
+    }
+
+    let label = match (selected.0, history.samples.back().copied().flatten()) {
+        (None, _) => "Depth history: X to select a contact".to_string(),
+        (Some(_), None) => "Contact lost".to_string(),
+        (Some(_), Some(difference)) => {
+            let trend = match history.trend() {
+                Some(rise) if rise > TREND_THRESHOLD => ", rising",
+                Some(rise) if rise < -TREND_THRESHOLD => ", diving",
+                Some(_) => ", level",
+                None => "",
+            };
+            format!(
+                "Contact {:.1} m {}{}",
+                difference.abs(),
+                if difference < 0.0 { "above" } else { "below" },
+                trend
+            )
+        }
+    };
+    for mut text in text_query.iter_mut() {
+        text.0 = label.clone();
+    }
+}
@@ -0,0 +1,361 @@
+use crate::controls::{Action, ControlCommand, HelmState, InputMap};
+use crate::trim_computer::TrimComputer;
+use crate::{AppState, BallastState, GameAssets, GameSet};
+use bevy::{prelude::*, window::PrimaryWindow};
+
+const BUTTON_OFF: Color = Color::srgba(0.1, 0.15, 0.2, 0.85);
+const BUTTON_ON: Color = Color::srgba(0.1, 0.5, 0.2, 0.9);
+const BUTTON_HOVER_TINT: f32 = 0.15;
+const TRIM_FILLED: Color = Color::srgba(0.1, 0.3, 0.5, 0.9); // Trim slider steps the tanks are filled past
+const TRIM_MARKS: [(u8, &str); 11] = [
+    (0, "0"),
+    (10, "10"),
+    (20, "20"),
+    (30, "30"),
+    (40, "40"),
+    (50, "50"),
+    (60, "60"),
+    (70, "70"),
+    (80, "80"),
+    (90, "90"),
+    (100, "100"),
+];
+
+/// Root node of the clickable control panel
+#[derive(Component)]
+struct ControlPanel;
+
+/// A clickable control bound to a command
+#[derive(Component)]
+struct ControlButton {
+    command: ControlCommand,
+    label: &'static str,
+}
+
+/// Hover help text for a control
+#[derive(Component)]
+struct Tooltip(&'static str);
+
+#[derive(Component)]
+struct TooltipText;
+
+/// Whether the control panel is shown (and the cursor released)
+#[derive(Resource, Default)]
+pub struct ControlPanelState {
+    pub open: bool,
+}
+
+pub struct ControlPanelPlugin;
+
+impl Plugin for ControlPanelPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ControlPanelState>()
+            .add_systems(OnEnter(AppState::InGame), spawn_control_panel)
+            .add_systems(
+                Update,
+                (
+                    (toggle_control_panel_system, control_button_click_system)
+                        .chain()
+                        .in_set(GameSet::Input),
+                    (control_button_style_system, tooltip_system).in_set(GameSet::Presentation),
+                ),
+            );
+    }
+}
+
+fn spawn_control_panel(mut commands: Commands, assets: Res<GameAssets>) {
+    commands.insert_resource(ControlPanelState::default());
+
+    let font = assets.font.clone();
+    let buttons = [
+        (
+            ControlCommand::ToggleVents,
+            "Vents",
+            "Flood the ballast tanks to dive (Q)",
+        ),
+        (
+            ControlCommand::ToggleAirValve,
+            "Air Valve",
+            "Blow ballast with compressed air to rise (E)",
+        ),
+        (
+            ControlCommand::ToggleCompressor,
+            "Compressor",
+            "Refill compressed air using electricity, surface only (R)",
+        ),
+        (
+            ControlCommand::TelegraphAstern,
+            "< Astern",
+            "Ring the engine telegraph one step astern (G)",
+        ),
+        (
+            ControlCommand::TelegraphAhead,
+            "Ahead >",
+            "Ring the engine telegraph one step ahead (T)",
+        ),
+        (
+            ControlCommand::ToggleAutopilot,
+            "Autopilot",
+            "Hold the current heading; steering adjusts the held course (P)",
+        ),
+        (
+            ControlCommand::ToggleLights,
+            "Lights",
+            "Switch the bow floodlights; they draw power and attract eels (L)",
+        ),
+    ];
+
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                bottom: Val::Px(20.0),
+                left: Val::Percent(50.0),
+                margin: UiRect::left(Val::Px(-385.0)),
+                width: Val::Px(770.0),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                row_gap: Val::Px(6.0),
+                display: Display::None,
+                ..default()
+            },
+            ControlPanel,
+            StateScoped(AppState::InGame),
+        ))
+        .with_children(|panel| {
+            panel.spawn((
+                Text::new(""),
+                TextFont {
+                    font_size: 14.0,
+                    font: font.clone(),
+                    ..default()
+                },
+                TextColor(Color::srgb(0.9, 0.9, 0.7)),
+                TooltipText,
+            ));
+
+            panel
+                .spawn(Node {
+                    flex_direction: FlexDirection::Row,
+                    column_gap: Val::Px(6.0),
+                    ..default()
+                })
+                .with_children(|row| {
+                    for (command, label, tooltip) in buttons {
+                        spawn_control_button(row, &font, command, label, tooltip, 104.0, 44.0);
+                    }
+                });
+
+            // The trim slider: each step holds the tanks at that percent full
+            panel
+                .spawn(Node {
+                    flex_direction: FlexDirection::Row,
+                    align_items: AlignItems::Center,
+                    column_gap: Val::Px(4.0),
+                    ..default()
+                })
+                .with_children(|row| {
+                    row.spawn((
+                        Text::new("Trim %"),
+                        TextFont {
+                            font_size: 14.0,
+                            font: font.clone(),
+                            ..default()
+                        },
+                        TextColor(Color::srgb(0.9, 0.9, 0.7)),
+                    ));
+                    for (percent, label) in TRIM_MARKS {
+                        spawn_control_button(
+                            row,
+                            &font,
+                            ControlCommand::SetBallastTarget(percent),
+                            label,
+                            "Work the valves to hold the ballast tanks this full (, and . step it)",
+                            40.0,
+                            28.0,
+                        );
+                    }
+                    spawn_control_button(
+                        row,
+                        &font,
+                        ControlCommand::ClearBallastTarget,
+                        "Off",
+                        "Stop holding a trim; the vents and air valve also cancel it",
+                        48.0,
+                        28.0,
+                    );
+                    spawn_control_button(
+                        row,
+                        &font,
+                        ControlCommand::ToggleTrimComputer,
+                        "Auto",
+                        "Trim computer (I): holds neutral buoyancy at the current depth",
+                        48.0,
+                        28.0,
+                    );
+                });
+        });
+}
+
+fn spawn_control_button(
+    row: &mut ChildSpawnerCommands,
+    font: &Handle<Font>,
+    command: ControlCommand,
+    label: &'static str,
+    tooltip: &'static str,
+    width: f32,
+    height: f32,
+) {
+    row.spawn((
+        Button,
+        Node {
+            width: Val::Px(width),
+            height: Val::Px(height),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            border: UiRect::all(Val::Px(1.0)),
+            ..default()
+        },
+        BorderColor(Color::srgb(0.4, 0.6, 0.7)),
+        BackgroundColor(BUTTON_OFF),
+        ControlButton { command, label },
+        Tooltip(tooltip),
+    ))
+    .with_children(|button| {
+        button.spawn((
+            Text::new(label),
+            TextFont {
+                font_size: 14.0,
+                font: font.clone(),
+                ..default()
+            },
+            TextColor(Color::WHITE),
+            TextLayout::new_with_justify(JustifyText::Center),
+        ));
+    });
+}
+
+/// Backslash (by default) opens the panel and frees the cursor for clicking
+fn toggle_control_panel_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    input_map: Res<InputMap>,
+    mut panel_state: ResMut<ControlPanelState>,
+    mut panel_query: Query<&mut Node, With<ControlPanel>>,
+    mut window_query: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    if input_map.just_pressed(&keyboard_input, Action::ControlPanel) {
+        panel_state.open = !panel_state.open;
+    }
+    if !panel_state.is_changed() {
+        return;
+    }
+
+    for mut node in panel_query.iter_mut() {
+        node.display = if panel_state.open {
+            Display::Flex
+        } else {
+            Display::None
+        };
+    }
+    if let Ok(mut window) = window_query.single_mut() {
+        window.cursor_options.visible = panel_state.open;
+    }
+}
+
+fn control_button_click_system(
+    button_query: Query<(&Interaction, &ControlButton), Changed<Interaction>>,
+    mut commands: EventWriter<ControlCommand>,
+) {
+    for (interaction, button) in button_query.iter() {
+        if *interaction == Interaction::Pressed {
+            commands.write(button.command);
+        }
+    }
+}
+
+/// Lights up buttons whose system is active and tints hovered buttons
+fn control_button_style_system(
+    ballast_state: Res<BallastState>,
+    helm_state: Res<HelmState>,
+    trim_computer: Res<TrimComputer>,
+    mut button_query: Query<(
+        &Interaction,
+        &ControlButton,
+        &mut BackgroundColor,
+        &Children,
+    )>,
+    mut text_query: Query<&mut Text>,
+) {
+    for (interaction, button, mut background, children) in button_query.iter_mut() {
+        let active = match button.command {
+            ControlCommand::ToggleVents => ballast_state.vents_open,
+            ControlCommand::ToggleAirValve => ballast_state.air_valve_open,
+            ControlCommand::ToggleCompressor => ballast_state.compressor_on,
+            ControlCommand::ToggleAutopilot => helm_state.autopilot,
+            ControlCommand::ToggleIntercept => helm_state.intercept,
+            ControlCommand::TelegraphAhead => helm_state.telegraph.throttle() > 0.0,
+            ControlCommand::TelegraphAstern => helm_state.telegraph.throttle() < 0.0,
+            ControlCommand::ToggleLights => helm_state.lights_on,
+            ControlCommand::ToggleTrimComputer => trim_computer.engaged,
+            ControlCommand::SetBallastTarget(percent) => ballast_state
+                .target_fill
+                .is_some_and(|target| (target * 100.0).round() as u8 == percent),
+            ControlCommand::BallastTargetUp
+            | ControlCommand::BallastTargetDown
+            | ControlCommand::ClearBallastTarget
+            | ControlCommand::FireTorpedo
+            | ControlCommand::DropDecoy
+            | ControlCommand::ReleaseBait
+            | ControlCommand::OperateArm
+            | ControlCommand::SendDiver
+            | ControlCommand::RepairHull
+            | ControlCommand::ReleaseDistressBuoy
+            | ControlCommand::StrapCargo
+            | ControlCommand::SelectContact => false,
+        };
+        let base = match button.command {
+            _ if active => BUTTON_ON,
+            ControlCommand::SetBallastTarget(percent)
+                if ballast_state.fill_level * 100.0 >= percent as f32 =>
+            {
+                TRIM_FILLED
+            }
+            _ => BUTTON_OFF,
+        };
+        *background = BackgroundColor(match interaction {
+            Interaction::None => base,
+            _ => base.lighter(BUTTON_HOVER_TINT),
+        });
+
+        // Telegraph buttons show the current order under their label
+        let label = match button.command {
+            ControlCommand::TelegraphAhead | ControlCommand::TelegraphAstern if active => {
+                format!("{}\n{}", button.label, helm_state.telegraph.label())
+            }
+            _ => button.label.to_string(),
+        };
+        for child in children.iter() {
+            if let Ok(mut text) = text_query.get_mut(child) {
+                if **text != label {
+                    **text = label.clone();
+                }
+            }
+        }
+    }
+}
+
+fn tooltip_system(
+    tooltip_query: Query<(&Interaction, &Tooltip)>,
+    mut text_query: Query<&mut Text, With<TooltipText>>,
+) {
+    let hovered = tooltip_query
+        .iter()
+        .find(|(interaction, _)| **interaction != Interaction::None)
+        .map(|(_, tooltip)| tooltip.0)
+        .unwrap_or("");
+    for mut text in text_query.iter_mut() {
+        if **text != hovered {
+            **text = hovered.to_string();
+        }
+    }
+}
@@ -0,0 +1,827 @@
+use crate::bridge::SURFACED_DEPTH;
+use crate::magnetics::Navigation;
+use crate::periscope::PERISCOPE_DEPTH;
+use crate::{submarine_movement, CameraMode, CameraState, ExteriorView, GameSet, Submarine};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// Discrete submarine orders, issued from the keyboard or the clickable control panel
+#[derive(Event, Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum ControlCommand {
+    ToggleVents,
+    ToggleAirValve,
+    ToggleCompressor,
+    SetBallastTarget(u8), // Percent full the trim pump holds the tanks at
+    BallastTargetUp,
+    BallastTargetDown,
+    ClearBallastTarget,
+    ToggleTrimComputer,
+    TelegraphAhead,
+    TelegraphAstern,
+    ToggleAutopilot,
+    FireTorpedo,
+    DropDecoy,
+    ReleaseBait,
+    OperateArm,
+    SendDiver,
+    ToggleLights,
+    RepairHull,
+    ReleaseDistressBuoy,
+    StrapCargo,
+    SelectContact,
+    ToggleIntercept,
+}
+
+/// Engine order telegraph positions, from full astern to full ahead
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum Telegraph {
+    FullAstern,
+    HalfAstern,
+    #[default]
+    Stop,
+    AheadSlow,
+    AheadHalf,
+    AheadFull,
+}
+
+impl Telegraph {
+    /// Fraction of maximum speed ordered (negative is astern)
+    pub fn throttle(self) -> f32 {
+        match self {
+            Telegraph::FullAstern => -0.5,
+            Telegraph::HalfAstern => -0.25,
+            Telegraph::Stop => 0.0,
+            Telegraph::AheadSlow => 0.25,
+            Telegraph::AheadHalf => 0.5,
+            Telegraph::AheadFull => 1.0,
+        }
+    }
+
+    pub fn ahead(self) -> Self {
+        match self {
+            Telegraph::FullAstern => Telegraph::HalfAstern,
+            Telegraph::HalfAstern => Telegraph::Stop,
+            Telegraph::Stop => Telegraph::AheadSlow,
+            Telegraph::AheadSlow => Telegraph::AheadHalf,
+            Telegraph::AheadHalf | Telegraph::AheadFull => Telegraph::AheadFull,
+        }
+    }
+
+    pub fn astern(self) -> Self {
+        match self {
+            Telegraph::FullAstern | Telegraph::HalfAstern => Telegraph::FullAstern,
+            Telegraph::Stop => Telegraph::HalfAstern,
+            Telegraph::AheadSlow => Telegraph::Stop,
+            Telegraph::AheadHalf => Telegraph::AheadSlow,
+            Telegraph::AheadFull => Telegraph::AheadHalf,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Telegraph::FullAstern => "Full Astern",
+            Telegraph::HalfAstern => "Half Astern",
+            Telegraph::Stop => "Stop",
+            Telegraph::AheadSlow => "Ahead Slow",
+            Telegraph::AheadHalf => "Ahead Half",
+            Telegraph::AheadFull => "Ahead Full",
+        }
+    }
+}
+
+/// Continuous steering input for the current frame, each axis in -1.0..=1.0
+#[derive(Resource, Default)]
+pub struct HelmInput {
+    pub throttle: f32,     // Positive is ahead
+    pub turn: f32,         // Positive turns to port (left)
+    pub camera_yaw: f32,   // Positive orbits the camera right
+    pub camera_pitch: f32, // Positive raises the camera
+    pub ballast: f32,      // Positive blows the tanks while held, negative floods them
+    pub planes: f32,       // Positive pitches the bow up while held
+}
+
+/// Standing helm orders that persist without holding keys
+#[derive(Resource)]
+pub struct HelmState {
+    pub telegraph: Telegraph,
+    pub autopilot: bool,
+    pub autopilot_heading: f32, // Yaw in radians held while the autopilot is engaged
+    pub intercept: bool,        // The autopilot steers to meet the selected contact
+    pub lights_on: bool,        // Bow floodlights
+}
+
+impl Default for HelmState {
+    fn default() -> Self {
+        Self {
+            telegraph: Telegraph::default(),
+            autopilot: false,
+            autopilot_heading: 0.0,
+            intercept: false,
+            lights_on: true,
+        }
+    }
+}
+
+/// Rebindable keyboard actions
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Serialize, Deserialize)]
+pub enum Action {
+    Forward,
+    Reverse,
+    TurnLeft,
+    TurnRight,
+    PlanesRise,
+    PlanesDive,
+    CameraLeft,
+    CameraRight,
+    CameraUp,
+    CameraDown,
+    ToggleVents,
+    ToggleAirValve,
+    ToggleCompressor,
+    HoldVent,
+    HoldBlow,
+    BallastTargetUp,
+    BallastTargetDown,
+    TrimComputer,
+    TelegraphAhead,
+    TelegraphAstern,
+    ToggleAutopilot,
+    Intercept,
+    FireTorpedo,
+    DropDecoy,
+    ReleaseBait,
+    ManipulatorArm,
+    SendDiver,
+    ToggleLights,
+    RepairHull,
+    DistressBuoy,
+    StrapCargo,
+    ControlPanel,
+    CameraView,
+    CameraStyle,
+    TacticalMap,
+    SelectContact,
+    PeriscopeZoom,
+    Stadimeter,
+    EventLog,
+}
+
+impl Action {
+    pub const ALL: [Action; 39] = [
+        Action::Forward,
+        Action::Reverse,
+        Action::TurnLeft,
+        Action::TurnRight,
+        Action::PlanesRise,
+        Action::PlanesDive,
+        Action::CameraLeft,
+        Action::CameraRight,
+        Action::CameraUp,
+        Action::CameraDown,
+        Action::ToggleVents,
+        Action::ToggleAirValve,
+        Action::ToggleCompressor,
+        Action::HoldVent,
+        Action::HoldBlow,
+        Action::BallastTargetUp,
+        Action::BallastTargetDown,
+        Action::TrimComputer,
+        Action::TelegraphAhead,
+        Action::TelegraphAstern,
+        Action::ToggleAutopilot,
+        Action::Intercept,
+        Action::FireTorpedo,
+        Action::DropDecoy,
+        Action::ReleaseBait,
+        Action::ManipulatorArm,
+        Action::SendDiver,
+        Action::ToggleLights,
+        Action::RepairHull,
+        Action::DistressBuoy,
+        Action::StrapCargo,
+        Action::ControlPanel,
+        Action::CameraView,
+        Action::CameraStyle,
+        Action::TacticalMap,
+        Action::SelectContact,
+        Action::PeriscopeZoom,
+        Action::Stadimeter,
+        Action::EventLog,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Action::Forward => "Ahead",
+            Action::Reverse => "Astern",
+            Action::TurnLeft => "Turn Port",
+            Action::TurnRight => "Turn Starboard",
+            Action::PlanesRise => "Planes Rise",
+            Action::PlanesDive => "Planes Dive",
+            Action::CameraLeft => "Camera Left",
+            Action::CameraRight => "Camera Right",
+            Action::CameraUp => "Camera Up",
+            Action::CameraDown => "Camera Down",
+            Action::ToggleVents => "Toggle Vents",
+            Action::ToggleAirValve => "Toggle Air Valve",
+            Action::ToggleCompressor => "Toggle Compressor",
+            Action::HoldVent => "Hold to Flood",
+            Action::HoldBlow => "Hold to Blow",
+            Action::BallastTargetUp => "Trim Target Up",
+            Action::BallastTargetDown => "Trim Target Down",
+            Action::TrimComputer => "Trim Computer",
+            Action::TelegraphAhead => "Telegraph Ahead",
+            Action::TelegraphAstern => "Telegraph Astern",
+            Action::ToggleAutopilot => "Autopilot",
+            Action::Intercept => "Intercept",
+            Action::FireTorpedo => "Fire Torpedo",
+            Action::DropDecoy => "Drop Decoy",
+            Action::ReleaseBait => "Release Bait",
+            Action::ManipulatorArm => "Manipulator Arm",
+            Action::SendDiver => "Send Diver",
+            Action::ToggleLights => "Floodlights",
+            Action::RepairHull => "Repair Hull",
+            Action::DistressBuoy => "Distress Buoy",
+            Action::StrapCargo => "Strap Cargo",
+            Action::ControlPanel => "Control Panel",
+            Action::CameraView => "Camera View",
+            Action::CameraStyle => "Camera Style",
+            Action::TacticalMap => "Tactical Map",
+            Action::SelectContact => "Select Contact",
+            Action::PeriscopeZoom => "Zoom",
+            Action::Stadimeter => "Stadimeter",
+            Action::EventLog => "Event Log",
+        }
+    }
+
+    fn default_binding(self) -> Binding {
+        let primary = match self {
+            Action::Forward => KeyCode::KeyW,
+            Action::Reverse => KeyCode::KeyS,
+            Action::TurnLeft => KeyCode::KeyA,
+            Action::TurnRight => KeyCode::KeyD,
+            Action::PlanesRise => KeyCode::PageUp,
+            Action::PlanesDive => KeyCode::PageDown,
+            Action::CameraLeft => KeyCode::ArrowLeft,
+            Action::CameraRight => KeyCode::ArrowRight,
+            Action::CameraUp => KeyCode::ArrowUp,
+            Action::CameraDown => KeyCode::ArrowDown,
+            Action::ToggleVents => KeyCode::KeyQ,
+            Action::ToggleAirValve => KeyCode::KeyE,
+            Action::ToggleCompressor => KeyCode::KeyR,
+            Action::HoldVent => KeyCode::KeyY,
+            Action::HoldBlow => KeyCode::KeyU,
+            Action::BallastTargetUp => KeyCode::Period,
+            Action::BallastTargetDown => KeyCode::Comma,
+            Action::TrimComputer => KeyCode::KeyI,
+            Action::TelegraphAhead => KeyCode::KeyT,
+            Action::TelegraphAstern => KeyCode::KeyG,
+            Action::ToggleAutopilot => KeyCode::KeyP,
+            Action::Intercept => KeyCode::Quote,
+            Action::FireTorpedo => KeyCode::Space,
+            Action::DropDecoy => KeyCode::KeyF,
+            Action::ReleaseBait => KeyCode::KeyB,
+            Action::ManipulatorArm => KeyCode::KeyK,
+            Action::SendDiver => KeyCode::KeyJ,
+            Action::ToggleLights => KeyCode::KeyL,
+            Action::RepairHull => KeyCode::KeyH,
+            Action::DistressBuoy => KeyCode::KeyO,
+            Action::StrapCargo => KeyCode::Semicolon,
+            Action::ControlPanel => KeyCode::Backslash,
+            Action::CameraView => KeyCode::KeyV,
+            Action::CameraStyle => KeyCode::KeyC,
+            Action::TacticalMap => KeyCode::KeyN,
+            Action::SelectContact => KeyCode::KeyX,
+            Action::PeriscopeZoom => KeyCode::KeyZ,
+            Action::Stadimeter => KeyCode::KeyM,
+            Action::EventLog => KeyCode::Tab,
+        };
+        Binding {
+            primary: Some(primary),
+            secondary: None,
+        }
+    }
+
+    /// The discrete order issued when this action's key is pressed, if any
+    fn command(self) -> Option<ControlCommand> {
+        match self {
+            Action::ToggleVents => Some(ControlCommand::ToggleVents),
+            Action::ToggleAirValve => Some(ControlCommand::ToggleAirValve),
+            Action::ToggleCompressor => Some(ControlCommand::ToggleCompressor),
+            Action::BallastTargetUp => Some(ControlCommand::BallastTargetUp),
+            Action::BallastTargetDown => Some(ControlCommand::BallastTargetDown),
+            Action::TrimComputer => Some(ControlCommand::ToggleTrimComputer),
+            Action::TelegraphAhead => Some(ControlCommand::TelegraphAhead),
+            Action::TelegraphAstern => Some(ControlCommand::TelegraphAstern),
+            Action::ToggleAutopilot => Some(ControlCommand::ToggleAutopilot),
+            Action::Intercept => Some(ControlCommand::ToggleIntercept),
+            Action::FireTorpedo => Some(ControlCommand::FireTorpedo),
+            Action::DropDecoy => Some(ControlCommand::DropDecoy),
+            Action::ReleaseBait => Some(ControlCommand::ReleaseBait),
+            Action::ManipulatorArm => Some(ControlCommand::OperateArm),
+            Action::SendDiver => Some(ControlCommand::SendDiver),
+            Action::ToggleLights => Some(ControlCommand::ToggleLights),
+            Action::RepairHull => Some(ControlCommand::RepairHull),
+            Action::DistressBuoy => Some(ControlCommand::ReleaseDistressBuoy),
+            Action::StrapCargo => Some(ControlCommand::StrapCargo),
+            Action::SelectContact => Some(ControlCommand::SelectContact),
+            _ => None,
+        }
+    }
+}
+
+/// Keys that are handled outside the input map (pause, console, saving, HUD,
+/// profiler and replay shortcuts) and therefore can't be bound to an action
+pub const RESERVED_KEYS: [KeyCode; 14] = [
+    KeyCode::Escape,
+    KeyCode::Backquote,
+    KeyCode::F2,
+    KeyCode::F3,
+    KeyCode::F4,
+    KeyCode::F5,
+    KeyCode::F6,
+    KeyCode::F7,
+    KeyCode::F8,
+    KeyCode::F9,
+    KeyCode::F10,
+    KeyCode::F12,
+    KeyCode::Minus,
+    KeyCode::Equal,
+];
+
+/// Primary and optional secondary key for an action
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Binding {
+    pub primary: Option<KeyCode>,
+    pub secondary: Option<KeyCode>,
+}
+
+impl Binding {
+    pub fn keys(self) -> impl Iterator<Item = KeyCode> {
+        self.primary.into_iter().chain(self.secondary)
+    }
+
+    pub fn get(self, slot: BindingSlot) -> Option<KeyCode> {
+        match slot {
+            BindingSlot::Primary => self.primary,
+            BindingSlot::Secondary => self.secondary,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BindingSlot {
+    Primary,
+    Secondary,
+}
+
+/// Keyboard bindings for every [`Action`], persisted in the settings file
+#[derive(Resource, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct InputMap {
+    bindings: BTreeMap<Action, Binding>,
+}
+
+impl Default for InputMap {
+    fn default() -> Self {
+        Self {
+            bindings: Action::ALL
+                .iter()
+                .map(|action| (*action, action.default_binding()))
+                .collect(),
+        }
+    }
+}
+
+impl InputMap {
+    pub fn binding(&self, action: Action) -> Binding {
+        self.bindings.get(&action).copied().unwrap_or_default()
+    }
+
+    /// Sets or clears one slot; clearing is refused if it would leave the action unbound
+    pub fn bind(&mut self, action: Action, slot: BindingSlot, key: Option<KeyCode>) {
+        let mut binding = self.binding(action);
+        match slot {
+            BindingSlot::Primary => binding.primary = key,
+            BindingSlot::Secondary => binding.secondary = key,
+        }
+        if binding.primary.is_none() {
+            binding.primary = binding.secondary.take();
+        }
+        if binding.primary.is_some() {
+            self.bindings.insert(action, binding);
+        }
+    }
+
+    /// Gives the control panel and event log today's default keys if they still
+    /// hold the old ones, the other way round; keys the player chose are kept
+    pub fn swap_panel_and_log_keys(&mut self) {
+        let old_default = |key| Binding {
+            primary: Some(key),
+            secondary: None,
+        };
+        let log = self.bindings.get(&Action::EventLog).copied();
+        if self.binding(Action::ControlPanel) == old_default(KeyCode::Tab)
+            && log.is_none_or(|log| log == old_default(KeyCode::Backslash))
+        {
+            for action in [Action::ControlPanel, Action::EventLog] {
+                self.bindings.insert(action, action.default_binding());
+            }
+        }
+    }
+
+    pub fn pressed(&self, keyboard_input: &ButtonInput<KeyCode>, action: Action) -> bool {
+        self.binding(action)
+            .keys()
+            .any(|key| keyboard_input.pressed(key))
+    }
+
+    pub fn just_pressed(&self, keyboard_input: &ButtonInput<KeyCode>, action: Action) -> bool {
+        self.binding(action)
+            .keys()
+            .any(|key| keyboard_input.just_pressed(key))
+    }
+
+    /// Other actions sharing at least one key with `action`
+    pub fn conflicts(&self, action: Action) -> Vec<Action> {
+        let keys: Vec<KeyCode> = self.binding(action).keys().collect();
+        Action::ALL
+            .into_iter()
+            .filter(|other| *other != action)
+            .filter(|other| self.binding(*other).keys().any(|key| keys.contains(&key)))
+            .collect()
+    }
+
+    /// Repairs bindings so every action stays reachable: reserved keys, keys
+    /// already taken by an earlier action and duplicate slots are dropped, and
+    /// unbound actions get their default key back. A default taken by another
+    /// action's custom binding is taken back, and that action then falls back
+    /// to its own default. Returns a description of each fix for logging.
+    pub fn validate(&mut self) -> Vec<String> {
+        let mut fixes = Vec::new();
+        let mut owners: HashMap<KeyCode, Action> = HashMap::new();
+        for action in Action::ALL {
+            let mut binding = self.binding(action);
+            for slot in [&mut binding.primary, &mut binding.secondary] {
+                let Some(key) = *slot else {
+                    continue;
+                };
+                if RESERVED_KEYS.contains(&key) {
+                    fixes.push(format!(
+                        "{} cannot use reserved key {:?}",
+                        action.label(),
+                        key
+                    ));
+                    *slot = None;
+                } else if let Some(owner) = owners.get(&key).filter(|owner| **owner != action) {
+                    fixes.push(format!(
+                        "{} cannot share {:?} with {}",
+                        action.label(),
+                        key,
+                        owner.label()
+                    ));
+                    *slot = None;
+                } else {
+                    owners.insert(key, action);
+                }
+            }
+            if binding.secondary.is_some() && binding.secondary == binding.primary {
+                binding.secondary = None;
+            }
+            if binding.primary.is_none() {
+                binding.primary = binding.secondary.take();
+            }
+            self.bindings.insert(action, binding);
+        }
+
+        // Defaults are all different keys, so this ends once every action
+        // robbed of a key is back on its own default
+        let mut unbound: Vec<Action> = Action::ALL
+            .into_iter()
+            .filter(|action| self.binding(*action).primary.is_none())
+            .collect();
+        while let Some(action) = unbound.pop() {
+            fixes.push(format!("{} was unbound; restored default", action.label()));
+            let binding = action.default_binding();
+            for key in binding.keys() {
+                let Some(owner) = owners.insert(key, action).filter(|owner| *owner != action)
+                else {
+                    continue;
+                };
+                let mut taken = self.binding(owner);
+                if taken.secondary == Some(key) {
+                    taken.secondary = None;
+                } else {
+                    taken.primary = taken.secondary.take();
+                }
+                fixes.push(format!(
+                    "{} gave up {:?} to {}",
+                    owner.label(),
+                    key,
+                    action.label()
+                ));
+                if taken.primary.is_none() {
+                    unbound.push(owner);
+                }
+                self.bindings.insert(owner, taken);
+            }
+            self.bindings.insert(action, binding);
+        }
+        fixes
+    }
+}
+
+/// Human-readable name for a key, e.g. `KeyW` becomes `W`
+pub fn key_name(key: KeyCode) -> String {
+    let name = format!("{:?}", key);
+    for prefix in ["Key", "Digit", "Arrow"] {
+        if let Some(rest) = name.strip_prefix(prefix) {
+            return rest.to_string();
+        }
+    }
+    name
+}
+
+pub struct ControlsPlugin;
+
+impl Plugin for ControlsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ControlCommand>()
+            .init_resource::<HelmState>()
+            .init_resource::<HelmInput>()
+            .init_resource::<InputMap>()
+            .add_systems(
+                Update,
+                (
+                    (keyboard_helm_input_system, keyboard_command_system)
+                        .chain()
+                        .in_set(GameSet::Input),
+                    (camera_view_system, camera_style_system).in_set(GameSet::Input),
+                    // Orders are applied with the rest of the simulation so that
+                    // replayed commands go through the same path as live ones
+                    helm_command_system
+                        .in_set(GameSet::Simulation)
+                        .before(submarine_movement),
+                ),
+            );
+    }
+}
+
+/// Reads held movement and camera keys into the helm axes
+pub fn keyboard_helm_input_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    input_map: Res<InputMap>,
+    mut helm_input: ResMut<HelmInput>,
+) {
+    let axis = |positive: Action, negative: Action| {
+        let mut value = 0.0;
+        if input_map.pressed(&keyboard_input, positive) {
+            value += 1.0;
+        }
+        if input_map.pressed(&keyboard_input, negative) {
+            value -= 1.0;
+        }
+        value
+    };
+    *helm_input = HelmInput {
+        throttle: axis(Action::Forward, Action::Reverse),
+        turn: axis(Action::TurnLeft, Action::TurnRight),
+        camera_yaw: axis(Action::CameraRight, Action::CameraLeft),
+        camera_pitch: axis(Action::CameraUp, Action::CameraDown),
+        ballast: axis(Action::HoldBlow, Action::HoldVent),
+        planes: axis(Action::PlanesRise, Action::PlanesDive),
+    };
+}
+
+/// Translates toggle keys into control commands
+fn keyboard_command_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    input_map: Res<InputMap>,
+    mut commands: EventWriter<ControlCommand>,
+) {
+    for action in Action::ALL {
+        if let Some(command) = action.command() {
+            if input_map.just_pressed(&keyboard_input, action) {
+                commands.write(command);
+            }
+        }
+    }
+}
+
+/// Cycles through the exterior and control room views, the periscope at
+/// periscope depth and the bridge when surfaced
+fn camera_view_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    input_map: Res<InputMap>,
+    camera_mode: Res<State<CameraMode>>,
+    mut next_camera_mode: ResMut<NextState<CameraMode>>,
+    submarine_query: Query<&Transform, With<Submarine>>,
+) {
+    if input_map.just_pressed(&keyboard_input, Action::CameraView) {
+        let depth = submarine_query
+            .single()
+            .map_or(f32::MAX, |transform| -transform.translation.y);
+        next_camera_mode.set(match camera_mode.get() {
+            CameraMode::Exterior => CameraMode::ControlRoom,
+            CameraMode::ControlRoom if depth <= PERISCOPE_DEPTH => CameraMode::Periscope,
+            CameraMode::Periscope if depth <= SURFACED_DEPTH => CameraMode::Bridge,
+            CameraMode::ControlRoom
+            | CameraMode::Periscope
+            | CameraMode::Bridge
+            | CameraMode::Tactical
+            | CameraMode::Cutscene => CameraMode::Exterior,
+        });
+    }
+}
+
+/// Steps the exterior camera through its styles, then up the periscope when
+/// at periscope depth, and from any other view back to the chase camera
+fn camera_style_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    input_map: Res<InputMap>,
+    camera_mode: Res<State<CameraMode>>,
+    mut next_camera_mode: ResMut<NextState<CameraMode>>,
+    mut camera_state: ResMut<CameraState>,
+    submarine_query: Query<&Transform, With<Submarine>>,
+) {
+    if !input_map.just_pressed(&keyboard_input, Action::CameraStyle) {
+        return;
+    }
+    if *camera_mode.get() != CameraMode::Exterior {
+        camera_state.view = ExteriorView::Chase;
+        next_camera_mode.set(CameraMode::Exterior);
+        return;
+    }
+    let depth = submarine_query
+        .single()
+        .map_or(f32::MAX, |transform| -transform.translation.y);
+    let index = ExteriorView::ALL
+        .iter()
+        .position(|view| *view == camera_state.view)
+        .unwrap_or(0);
+    match ExteriorView::ALL.get(index + 1) {
+        Some(view) => camera_state.view = *view,
+        None => {
+            camera_state.view = ExteriorView::Chase;
+            if depth <= PERISCOPE_DEPTH {
+                next_camera_mode.set(CameraMode::Periscope);
+            }
+        }
+    }
+    info!("Camera: {}", camera_state.view.label());
+}
+
+/// Applies telegraph, autopilot and floodlight orders to the helm
+pub fn helm_command_system(
+    mut commands: EventReader<ControlCommand>,
+    mut helm_state: ResMut<HelmState>,
+    navigation: Res<Navigation>,
+    submarine_query: Query<&Transform, With<Submarine>>,
+) {
+    for command in commands.read() {
+        match command {
+            ControlCommand::TelegraphAhead => helm_state.telegraph = helm_state.telegraph.ahead(),
+            ControlCommand::TelegraphAstern => helm_state.telegraph = helm_state.telegraph.astern(),
+            ControlCommand::ToggleAutopilot => {
+                helm_state.autopilot = !helm_state.autopilot;
+                helm_state.intercept = false;
+                // Hold the compass heading the submarine had when the autopilot was engaged
+                if let Ok(transform) = submarine_query.single() {
+                    helm_state.autopilot_heading =
+                        transform.rotation.to_euler(EulerRot::YXZ).0 + navigation.compass_error;
+                }
+            }
+            // Steering an intercept engages the autopilot, which then takes its
+            // heading from the plot; breaking off holds the last course it steered
+            ControlCommand::ToggleIntercept => {
+                helm_state.intercept = !helm_state.intercept;
+                helm_state.autopilot |= helm_state.intercept;
+            }
+            ControlCommand::ToggleLights => helm_state.lights_on = !helm_state.lights_on,
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn defaults_except(action: Action) -> InputMap {
+        let mut map = InputMap::default();
+        map.bindings.remove(&action);
+        map
+    }
+
+    #[test]
+    fn defaults_are_distinct_and_unreserved() {
+        let mut map = InputMap::default();
+        assert!(map.validate().is_empty());
+        let mut keys: Vec<KeyCode> = Action::ALL
+            .iter()
+            .flat_map(|action| action.default_binding().keys())
+            .collect();
+        assert!(keys.iter().all(|key| !RESERVED_KEYS.contains(key)));
+        let count = keys.len();
+        keys.sort_by_key(|key| format!("{:?}", key));
+        keys.dedup();
+        assert_eq!(keys.len(), count);
+    }
+
+    #[test]
+    fn reserved_key_is_replaced_by_default() {
+        let mut map = InputMap::default();
+        map.bindings.insert(
+            Action::FireTorpedo,
+            Binding {
+                primary: Some(KeyCode::F5),
+                secondary: None,
+            },
+        );
+        let fixes = map.validate();
+        assert!(fixes.iter().any(|fix| fix.contains("reserved")));
+        assert_eq!(
+            map.binding(Action::FireTorpedo).primary,
+            Some(KeyCode::Space)
+        );
+    }
+
+    #[test]
+    fn duplicate_keys_keep_the_first_action() {
+        let mut map = InputMap::default();
+        for action in Action::ALL {
+            map.bindings.insert(
+                action,
+                Binding {
+                    primary: Some(KeyCode::KeyW),
+                    secondary: None,
+                },
+            );
+        }
+        map.validate();
+        assert_eq!(map, InputMap::default());
+    }
+
+    #[test]
+    fn restored_default_is_taken_back() {
+        let mut map = defaults_except(Action::TurnRight);
+        map.bindings.insert(
+            Action::Reverse,
+            Binding {
+                primary: Some(KeyCode::KeyD),
+                secondary: None,
+            },
+        );
+        map.validate();
+        assert_eq!(map.binding(Action::TurnRight).primary, Some(KeyCode::KeyD));
+        assert_eq!(map.binding(Action::Reverse).primary, Some(KeyCode::KeyS));
+        assert!(Action::ALL
+            .into_iter()
+            .all(|action| map.conflicts(action).is_empty()));
+    }
+
+    #[test]
+    fn missing_action_gets_default() {
+        let mut map = defaults_except(Action::EventLog);
+        let fixes = map.validate();
+        assert_eq!(fixes.len(), 1);
+        assert_eq!(map, InputMap::default());
+    }
+
+    #[test]
+    fn old_panel_and_log_keys_are_swapped() {
+        let mut map = InputMap::default();
+        map.bind(
+            Action::EventLog,
+            BindingSlot::Primary,
+            Some(KeyCode::Backslash),
+        );
+        map.bind(
+            Action::ControlPanel,
+            BindingSlot::Primary,
+            Some(KeyCode::Tab),
+        );
+        map.swap_panel_and_log_keys();
+        assert_eq!(map, InputMap::default());
+
+        let mut chosen = InputMap::default();
+        chosen.bind(Action::EventLog, BindingSlot::Primary, Some(KeyCode::KeyU));
+        chosen.bind(
+            Action::ControlPanel,
+            BindingSlot::Primary,
+            Some(KeyCode::Tab),
+        );
+        let before = chosen.clone();
+        chosen.swap_panel_and_log_keys();
+        assert_eq!(chosen, before);
+    }
+
+    #[test]
+    fn binding_fields_default_when_missing() {
+        let binding: Binding = ron::from_str("(primary: Some(KeyW))").unwrap();
+        assert_eq!(binding.primary, Some(KeyCode::KeyW));
+        assert_eq!(binding.secondary, None);
+        let empty: Binding = ron::from_str("()").unwrap();
+        assert_eq!(empty, Binding::default());
+    }
+}
@@ -0,0 +1,522 @@
+//! The crew: who stands which station, how tired and how hurt they are. Each
+//! station's bonus depends on the hands at it, hull damage injures crew, and
+//! the sickbay patches them up. Hands tire on watch and recover in quarters,
+//! and with automatic watches on, rested crew relieve tired ones. The roster
+//! opens with the control panel and crew are reassigned by dragging their card
+//! onto another station.
+
+use crate::callouts::{CalloutKind, CrewCallout};
+use crate::control_panel::ControlPanelState;
+use crate::{AppState, GameAssets, GameSet, GameState, WorldSeed};
+use bevy::prelude::*;
+use rand::{rngs::StdRng, seq::IteratorRandom, Rng, SeedableRng};
+
+const UNMANNED_EFFICIENCY: f32 = 0.5; // Share of a station's bonus with nobody on it
+const INJURY_PENALTY: f32 = 0.5; // Effectiveness lost by a fully injured crew member
+const INJURY_DAMAGE: f32 = 10.0; // Hull percent lost per crew injury
+const HEAL_RATE: f32 = 0.02; // Injury treated per second in the sickbay
+const FATIGUE_PENALTY: f32 = 0.4; // Effectiveness lost by a fully exhausted crew member
+const DUTY_FATIGUE_RATE: f32 = 1.0 / 600.0; // Ten minutes on watch exhausts a hand
+const REST_RATE: f32 = 1.0 / 200.0; // Fatigue recovered per second in quarters
+const SICKBAY_REST_RATE: f32 = 1.0 / 400.0;
+const RELIEVE_AT: f32 = 0.6; // Automatic watches relieve hands this tired
+const RESTED_BELOW: f32 = 0.2; // and send in someone this fresh
+const CARD_NORMAL: Color = Color::srgba(0.1, 0.15, 0.2, 0.9);
+const CARD_DRAGGED: Color = Color::srgba(0.2, 0.35, 0.45, 0.95);
+
+/// A post aboard; the first four give the submarine a bonus when well manned
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Station {
+    Helm,        // Turn rate
+    Sonar,       // Sonar range
+    Diving,      // Ballast flood and blow rates
+    Engineering, // Compressor and battery recharge rates
+    Sickbay,
+    Quarters,
+}
+
+impl Station {
+    /// Stations that tire the crew standing them
+    pub fn on_watch(self) -> bool {
+        !matches!(self, Station::Sickbay | Station::Quarters)
+    }
+
+    pub const ALL: [Station; 6] = [
+        Station::Helm,
+        Station::Sonar,
+        Station::Diving,
+        Station::Engineering,
+        Station::Sickbay,
+        Station::Quarters,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Station::Helm => "Helm",
+            Station::Sonar => "Sonar",
+            Station::Diving => "Diving",
+            Station::Engineering => "Engineering",
+            Station::Sickbay => "Sickbay",
+            Station::Quarters => "Quarters",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct CrewMember {
+    pub name: &'static str,
+    pub station: Station,
+    pub fatigue: f32, // 0..1
+    pub injury: f32,  // 0..1, treated in the sickbay
+    pub held: bool,   // Placed by hand; automatic watches leave them be
+}
+
+impl CrewMember {
+    fn new(name: &'static str, station: Station) -> Self {
+        Self {
+            name,
+            station,
+            fatigue: 0.0,
+            injury: 0.0,
+            held: false,
+        }
+    }
+
+    /// How much of a station's bonus this member provides, 0..1
+    pub fn effectiveness(&self) -> f32 {
+        (1.0 - INJURY_PENALTY * self.injury) * (1.0 - FATIGUE_PENALTY * self.fatigue)
+    }
+}
+
+/// Everyone aboard and where they stand
+#[derive(Resource, Clone)]
+pub struct Crew {
+    pub members: Vec<CrewMember>,
+    pub auto_watch: bool, // Rotate tired hands off watch automatically
+}
+
+impl Default for Crew {
+    fn default() -> Self {
+        Self {
+            members: vec![
+                CrewMember::new("Okafor", Station::Helm),
+                CrewMember::new("Lindqvist", Station::Sonar),
+                CrewMember::new("Moreau", Station::Diving),
+                CrewMember::new("Tanaka", Station::Engineering),
+                CrewMember::new("Reyes", Station::Quarters),
+                CrewMember::new("Novak", Station::Quarters),
+            ],
+            auto_watch: true,
+        }
+    }
+}
+
+impl Crew {
+    /// Multiplier for a station's bonus from the best hand on it; a healthy
+    /// crew member gives 1.0
+    pub fn efficiency(&self, station: Station) -> f32 {
+        self.members
+            .iter()
+            .filter(|member| member.station == station)
+            .map(CrewMember::effectiveness)
+            .fold(UNMANNED_EFFICIENCY, f32::max)
+    }
+}
+
+/// Random source for picking who gets hurt, seeded from the world so replays match
+#[derive(Resource)]
+struct CrewRng(StdRng);
+
+/// Hull damage taken since the last injury
+#[derive(Resource, Default)]
+struct CrewDamage {
+    last_health: Option<f32>,
+    accumulated: f32,
+}
+
+#[derive(Component)]
+struct CrewRoster;
+
+/// A station's column on the roster; crew cards dropped on it are reassigned there
+#[derive(Component)]
+struct StationColumn(Station);
+
+/// A crew member's card on the roster, by index into `Crew::members`
+#[derive(Component)]
+struct CrewCard(usize);
+
+#[derive(Component)]
+struct CrewCardText;
+
+/// Button switching automatic watches on and off
+#[derive(Component)]
+struct AutoWatchButton;
+
+pub struct CrewPlugin;
+
+impl Plugin for CrewPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Crew>()
+            .init_resource::<CrewDamage>()
+            .insert_resource(CrewRng(StdRng::seed_from_u64(0)))
+            .add_systems(
+                OnEnter(AppState::InGame),
+                (reset_crew, spawn_roster).chain(),
+            )
+            .add_systems(
+                Update,
+                (
+                    auto_watch_button_system.in_set(GameSet::Input),
+                    (
+                        crew_injury_system,
+                        sickbay_system,
+                        fatigue_system,
+                        watch_rotation_system,
+                    )
+                        .chain()
+                        .in_set(GameSet::Simulation),
+                    roster_system.in_set(GameSet::Presentation),
+                ),
+            );
+    }
+}
+
+fn reset_crew(mut commands: Commands, seed: Res<WorldSeed>) {
+    commands.insert_resource(Crew::default());
+    commands.insert_resource(CrewDamage::default());
+    commands.insert_resource(CrewRng(StdRng::seed_from_u64(seed.0 ^ 0x4352_4557)));
+}
+
+fn spawn_roster(mut commands: Commands, assets: Res<GameAssets>) {
+    let font = assets.font.clone();
+    let roster = commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                bottom: Val::Px(100.0),
+                left: Val::Percent(50.0),
+                margin: UiRect::left(Val::Px(-385.0)),
+                width: Val::Px(770.0),
+                flex_direction: FlexDirection::Column,
+                row_gap: Val::Px(6.0),
+                display: Display::None,
+                ..default()
+            },
+            CrewRoster,
+            StateScoped(AppState::InGame),
+        ))
+        .id();
+    commands
+        .spawn((
+            Button,
+            Node {
+                width: Val::Px(160.0),
+                height: Val::Px(28.0),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                border: UiRect::all(Val::Px(1.0)),
+                ..default()
+            },
+            BorderColor(Color::srgb(0.4, 0.6, 0.7)),
+            BackgroundColor(CARD_NORMAL),
+            AutoWatchButton,
+            ChildOf(roster),
+        ))
+        .with_child((
+            Text::new(""),
+            TextFont {
+                font_size: 14.0,
+                font: font.clone(),
+                ..default()
+            },
+            TextColor(Color::WHITE),
+        ));
+    let stations = commands
+        .spawn((
+            Node {
+                flex_direction: FlexDirection::Row,
+                column_gap: Val::Px(6.0),
+                ..default()
+            },
+            ChildOf(roster),
+        ))
+        .id();
+
+    let mut columns = Vec::new();
+    for station in Station::ALL {
+        let column = commands
+            .spawn((
+                Node {
+                    width: Val::Px(124.0),
+                    min_height: Val::Px(120.0),
+                    flex_direction: FlexDirection::Column,
+                    row_gap: Val::Px(4.0),
+                    padding: UiRect::all(Val::Px(4.0)),
+                    border: UiRect::all(Val::Px(1.0)),
+                    ..default()
+                },
+                BorderColor(Color::srgb(0.4, 0.6, 0.7)),
+                BackgroundColor(Color::srgba(0.0, 0.05, 0.1, 0.7)),
+                StationColumn(station),
+                ChildOf(stations),
+            ))
+            .observe(drop_on_station)
+            .with_child((
+                Text::new(station.name()),
+                TextFont {
+                    font_size: 14.0,
+                    font: font.clone(),
+                    ..default()
+                },
+                TextColor(Color::srgb(0.9, 0.9, 0.7)),
+                Pickable::IGNORE,
+            ))
+            .id();
+        columns.push((station, column));
+    }
+
+    // Cards start in their station's column; the roster system moves them on reassignment
+    for (index, member) in Crew::default().members.iter().enumerate() {
+        let column = columns
+            .iter()
+            .find(|(station, _)| *station == member.station)
+            .map(|(_, column)| *column)
+            .unwrap_or(stations);
+        commands
+            .spawn((
+                Node {
+                    padding: UiRect::all(Val::Px(4.0)),
+                    ..default()
+                },
+                BackgroundColor(CARD_NORMAL),
+                CrewCard(index),
+                ChildOf(column),
+            ))
+            .observe(start_drag)
+            .observe(end_drag)
+            .with_child((
+                Text::new(member.name),
+                TextFont {
+                    font_size: 12.0,
+                    font: font.clone(),
+                    ..default()
+                },
+                TextColor(Color::WHITE),
+                CrewCardText,
+                Pickable::IGNORE,
+            ));
+    }
+}
+
+fn start_drag(trigger: Trigger<Pointer<DragStart>>, mut card_query: Query<&mut BackgroundColor>) {
+    if let Ok(mut background) = card_query.get_mut(trigger.target()) {
+        *background = BackgroundColor(CARD_DRAGGED);
+    }
+}
+
+fn end_drag(trigger: Trigger<Pointer<DragEnd>>, mut card_query: Query<&mut BackgroundColor>) {
+    if let Ok(mut background) = card_query.get_mut(trigger.target()) {
+        *background = BackgroundColor(CARD_NORMAL);
+    }
+}
+
+/// Reassigns a crew member whose card is dropped anywhere on a station's column
+fn drop_on_station(
+    mut trigger: Trigger<Pointer<DragDrop>>,
+    column_query: Query<&StationColumn>,
+    card_query: Query<&CrewCard>,
+    mut crew: ResMut<Crew>,
+) {
+    let (Ok(column), Ok(card)) = (
+        column_query.get(trigger.target()),
+        card_query.get(trigger.event().dropped),
+    ) else {
+        return;
+    };
+    trigger.propagate(false);
+    if let Some(member) = crew.members.get_mut(card.0) {
+        // Hands put on watch by the player stay there until moved again
+        member.held = column.0.on_watch();
+        if member.station != column.0 {
+            info!("{} reassigned to {}", member.name, column.0.name());
+            member.station = column.0;
+        }
+    }
+}
+
+/// Every so much hull damage injures someone outside the sickbay
+fn crew_injury_system(
+    game_state: Res<GameState>,
+    mut damage: ResMut<CrewDamage>,
+    mut rng: ResMut<CrewRng>,
+    mut crew: ResMut<Crew>,
+    mut callouts: EventWriter<CrewCallout>,
+) {
+    let last_health = *damage.last_health.get_or_insert(game_state.health);
+    damage.accumulated += (last_health - game_state.health).max(0.0);
+    damage.last_health = Some(game_state.health);
+    if damage.accumulated < INJURY_DAMAGE {
+        return;
+    }
+    damage.accumulated -= INJURY_DAMAGE;
+
+    let Some(member) = crew
+        .members
+        .iter_mut()
+        .filter(|member| member.station != Station::Sickbay)
+        .choose(&mut rng.0)
+    else {
+        return;
+    };
+    member.injury = (member.injury + rng.0.gen_range(0.3..0.8)).min(1.0);
+    callouts.write(CrewCallout {
+        kind: CalloutKind::Casualty,
+        text: format!("{} is hurt at {}", member.name, member.station.name()),
+    });
+}
+
+/// Injuries heal only while the crew member rests in the sickbay
+fn sickbay_system(mut crew: ResMut<Crew>, time: Res<Time>) {
+    let treating = crew
+        .members
+        .iter()
+        .any(|member| member.station == Station::Sickbay && member.injury > 0.0);
+    if !treating {
+        return;
+    }
+    let heal = HEAL_RATE * time.delta_secs();
+    for member in crew.members.iter_mut() {
+        if member.station == Station::Sickbay {
+            member.injury = (member.injury - heal).max(0.0);
+        }
+    }
+}
+
+/// Hands tire on watch and recover in quarters, and more slowly in the sickbay
+fn fatigue_system(mut crew: ResMut<Crew>, time: Res<Time>) {
+    let delta = time.delta_secs();
+    for member in crew.members.iter_mut() {
+        let change = match member.station {
+            Station::Quarters => -REST_RATE,
+            Station::Sickbay => -SICKBAY_REST_RATE,
+            _ => DUTY_FATIGUE_RATE,
+        };
+        member.fatigue = (member.fatigue + change * delta).clamp(0.0, 1.0);
+    }
+}
+
+/// With automatic watches on, sends a rested hand from quarters to relieve
+/// the most tired one on watch, who goes below to rest
+fn watch_rotation_system(mut crew: ResMut<Crew>, mut callouts: EventWriter<CrewCallout>) {
+    if !crew.auto_watch {
+        return;
+    }
+    let tired = crew
+        .members
+        .iter()
+        .enumerate()
+        .filter(|(_, member)| member.station.on_watch() && !member.held)
+        .filter(|(_, member)| member.fatigue >= RELIEVE_AT)
+        .max_by(|(_, a), (_, b)| a.fatigue.total_cmp(&b.fatigue))
+        .map(|(index, _)| index);
+    let rested = crew
+        .members
+        .iter()
+        .enumerate()
+        .filter(|(_, member)| member.station == Station::Quarters)
+        .filter(|(_, member)| member.fatigue < RESTED_BELOW && member.injury == 0.0)
+        .min_by(|(_, a), (_, b)| a.fatigue.total_cmp(&b.fatigue))
+        .map(|(index, _)| index);
+    let (Some(tired), Some(rested)) = (tired, rested) else {
+        return;
+    };
+
+    let station = crew.members[tired].station;
+    crew.members[rested].station = station;
+    crew.members[tired].station = Station::Quarters;
+    callouts.write(CrewCallout {
+        kind: CalloutKind::Watch,
+        text: format!(
+            "{} relieves {} at {}",
+            crew.members[rested].name,
+            crew.members[tired].name,
+            station.name()
+        ),
+    });
+}
+
+fn auto_watch_button_system(
+    button_query: Query<&Interaction, (Changed<Interaction>, With<AutoWatchButton>)>,
+    mut crew: ResMut<Crew>,
+) {
+    for interaction in button_query.iter() {
+        if *interaction == Interaction::Pressed {
+            crew.auto_watch = !crew.auto_watch;
+        }
+    }
+}
+
+/// Shows the roster with the control panel and keeps the cards up to date
+#[allow(clippy::too_many_arguments)]
+fn roster_system(
+    mut commands: Commands,
+    crew: Res<Crew>,
+    panel_state: Res<ControlPanelState>,
+    mut roster_query: Query<&mut Node, With<CrewRoster>>,
+    column_query: Query<(Entity, &StationColumn)>,
+    card_query: Query<(Entity, &CrewCard, &ChildOf, &Children)>,
+    mut text_query: Query<(&mut Text, &mut TextColor), With<CrewCardText>>,
+    button_query: Query<&Children, With<AutoWatchButton>>,
+    mut label_query: Query<&mut Text, Without<CrewCardText>>,
+) {
+    if panel_state.is_changed() {
+        for mut node in roster_query.iter_mut() {
+            node.display = if panel_state.open {
+                Display::Flex
+            } else {
+                Display::None
+            };
+        }
+    }
+    if !crew.is_changed() {
+        return;
+    }
+
+    for children in button_query.iter() {
+        for child in children.iter() {
+            if let Ok(mut text) = label_query.get_mut(child) {
+                text.0 = format!("Auto Watch: {}", if crew.auto_watch { "On" } else { "Off" });
+            }
+        }
+    }
+
+    for (card_entity, card, parent, children) in card_query.iter() {
+        let Some(member) = crew.members.get(card.0) else {
+            continue;
+        };
+        if let Some((column, _)) = column_query
+            .iter()
+            .find(|(_, column)| column.0 == member.station)
+        {
+            if parent.parent() != column {
+                commands.entity(column).add_child(card_entity);
+            }
+        }
+        for child in children.iter() {
+            if let Ok((mut text, mut color)) = text_query.get_mut(child) {
+                text.0 = format!(
+                    "{}{}\nFatigue {:.0}%\nInjury {:.0}%",
+                    member.name,
+                    if member.held { " (held)" } else { "" },
+                    member.fatigue * 100.0,
+                    member.injury * 100.0
+                );
+                *color = TextColor(if member.injury > 0.0 {
+                    Color::srgb(1.0, 0.5, 0.4)
+                } else {
+                    Color::WHITE
+                });
+            }
+        }
+    }
+}
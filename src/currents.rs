@@ -0,0 +1,170 @@
+//! Tide races: patches of the lake where the water runs hard in one direction
+//! from surface to floor. The boat is carried along with the flow, which dead
+//! reckoning knows nothing about, and drifting silt shows which way it runs.
+
+use crate::tables::Tables;
+use crate::{AppState, GameMode, GameSet, Submarine, WorldSeed};
+use bevy::prelude::*;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+const RACE_RADIUS: f32 = 25.0; // Meters from the middle of a race to its edge
+const FLOW_SPEED: (f32, f32) = (1.0, 1.6); // Meters per second the water runs at
+const SILT_MOTES: usize = 40; // Drifting specks showing the flow in each race
+const SEA_FLOOR: f32 = -20.5;
+
+/// A patch of fast-running water, reaching from the surface to the floor
+#[derive(Component)]
+pub struct TideRace {
+    pub radius: f32,
+    pub flow: Vec3, // Meters per second, level
+}
+
+impl TideRace {
+    fn contains(&self, center: Vec3, point: Vec3) -> bool {
+        Vec2::new(point.x - center.x, point.z - center.z).length() < self.radius
+    }
+}
+
+/// Flow of the water around a point, or zero outside every race
+pub fn flow_at<'a>(
+    races: impl IntoIterator<Item = (&'a Transform, &'a TideRace)>,
+    point: Vec3,
+) -> Vec3 {
+    races
+        .into_iter()
+        .filter(|(transform, race)| race.contains(transform.translation, point))
+        .map(|(_, race)| race.flow)
+        .sum()
+}
+
+/// A speck of silt carried through a race, wrapping round to its upstream edge
+#[derive(Component)]
+struct SiltMote {
+    race: Entity,
+}
+
+/// The silt every race carries, made once
+#[derive(Resource)]
+struct MoteAssets {
+    mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+}
+
+impl FromWorld for MoteAssets {
+    fn from_world(world: &mut World) -> Self {
+        let mesh = world.resource_mut::<Assets<Mesh>>().add(Sphere::new(0.06));
+        let material = world
+            .resource_mut::<Assets<StandardMaterial>>()
+            .add(StandardMaterial {
+                base_color: Color::srgba(0.75, 0.7, 0.55, 0.6),
+                alpha_mode: AlphaMode::Blend,
+                unlit: true,
+                ..default()
+            });
+        Self { mesh, material }
+    }
+}
+
+pub struct CurrentsPlugin;
+
+impl Plugin for CurrentsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            OnEnter(AppState::InGame),
+            spawn_races
+                .after(crate::setup)
+                .run_if(|mode: Res<GameMode>| *mode != GameMode::TimeTrial),
+        )
+        .add_systems(
+            Update,
+            (
+                drift_system
+                    .after(crate::submarine_movement)
+                    .in_set(GameSet::Simulation),
+                silt_system.in_set(GameSet::Presentation),
+            ),
+        );
+    }
+
+    fn finish(&self, app: &mut App) {
+        app.init_resource::<MoteAssets>();
+    }
+}
+
+/// Races scattered away from the start, each running its own way
+fn spawn_races(
+    mut commands: Commands,
+    assets: Res<MoteAssets>,
+    seed: Res<WorldSeed>,
+    tables: Res<Tables>,
+) {
+    let mut rng = StdRng::seed_from_u64(seed.0 ^ 0x5449_4445);
+
+    for _ in 0..tables.spawns.tide_races {
+        let angle = rng.gen::<f32>() * std::f32::consts::TAU;
+        let distance = rng.gen_range(60.0..220.0);
+        let center = Vec3::new(angle.cos() * distance, 0.0, angle.sin() * distance);
+        let heading = rng.gen::<f32>() * std::f32::consts::TAU;
+        let speed = rng.gen_range(FLOW_SPEED.0..FLOW_SPEED.1);
+        let race = commands
+            .spawn((
+                Transform::from_translation(center),
+                TideRace {
+                    radius: RACE_RADIUS,
+                    flow: Vec3::new(heading.cos(), 0.0, heading.sin()) * speed,
+                },
+                StateScoped(AppState::InGame),
+            ))
+            .id();
+        for _ in 0..SILT_MOTES {
+            let spot = Vec2::from_angle(rng.gen::<f32>() * std::f32::consts::TAU)
+                * rng.gen::<f32>().sqrt()
+                * RACE_RADIUS;
+            let height = rng.gen_range(SEA_FLOOR..-0.5);
+            commands.spawn((
+                Mesh3d(assets.mesh.clone()),
+                MeshMaterial3d(assets.material.clone()),
+                Transform::from_xyz(center.x + spot.x, height, center.z + spot.y),
+                SiltMote { race },
+                StateScoped(AppState::InGame),
+            ));
+        }
+    }
+}
+
+/// Carries the boat along with the water it is in
+fn drift_system(
+    race_query: Query<(&Transform, &TideRace), Without<Submarine>>,
+    mut submarine_query: Query<&mut Transform, With<Submarine>>,
+    time: Res<Time>,
+) {
+    let Ok(mut transform) = submarine_query.single_mut() else {
+        return;
+    };
+    let flow = flow_at(race_query.iter(), transform.translation);
+    transform.translation += flow * time.delta_secs();
+}
+
+/// Moves silt with the flow, putting specks that leave back in at the far side
+fn silt_system(
+    race_query: Query<(&Transform, &TideRace), Without<SiltMote>>,
+    mut mote_query: Query<(&SiltMote, &mut Transform)>,
+    time: Res<Time>,
+) {
+    for (mote, mut transform) in mote_query.iter_mut() {
+        let Ok((race_transform, race)) = race_query.get(mote.race) else {
+            continue;
+        };
+        transform.translation += race.flow * time.delta_secs();
+        if !race.contains(race_transform.translation, transform.translation) {
+            // Mirror through the middle, which lands upstream of it
+            let center = race_transform.translation;
+            let offset = transform.translation - center;
+            transform.translation = Vec3::new(
+                center.x - offset.x * 0.95,
+                transform.translation.y,
+                center.z - offset.z * 0.95,
+            );
+        }
+    }
+}
@@ -0,0 +1,260 @@
+//! Cutscenes: the camera follows a keyframed path while the dive waits, then
+//! hands back to the chase camera. A new game opens with a flyover that sweeps
+//! over the mountain ring and comes down behind the submarine.
+
+use crate::{
+    chase_offset, AppState, CameraFollow, CameraMode, CameraState, GameAssets, GameSet, Submarine,
+};
+use bevy::prelude::*;
+
+const INTRO_RADIUS: f32 = 640.0; // Meters from the centre the flyover starts, just outside the mountains
+const INTRO_HEIGHT: f32 = 130.0; // Meters above the water the flyover starts
+const INTRO_SWEEP: f32 = 1.6; // Radians the flyover circles before coming down
+
+/// Where the camera is and what it looks at a given time into a cutscene
+#[derive(Clone, Copy, Debug)]
+pub struct CameraKeyframe {
+    pub time: f32,
+    pub position: Vec3,
+    pub look_at: Vec3,
+}
+
+/// A camera move through keyframes in time order, passing smoothly through
+/// each one
+#[derive(Clone, Debug, Default)]
+pub struct CameraPath {
+    pub keyframes: Vec<CameraKeyframe>,
+}
+
+impl CameraPath {
+    pub fn duration(&self) -> f32 {
+        self.keyframes.last().map_or(0.0, |keyframe| keyframe.time)
+    }
+
+    /// The camera transform `time` seconds into the path; positions follow a
+    /// Catmull-Rom spline and the view eases between targets
+    pub fn sample(&self, time: f32) -> Transform {
+        let Some(first) = self.keyframes.first() else {
+            return Transform::default();
+        };
+        let last = self.keyframes.len() - 1;
+        let segment = self
+            .keyframes
+            .iter()
+            .rposition(|keyframe| keyframe.time <= time)
+            .unwrap_or(0)
+            .min(last.saturating_sub(1));
+        let from = self.keyframes[segment];
+        let Some(to) = self.keyframes.get(segment + 1).copied() else {
+            return Transform::from_translation(first.position).looking_at(first.look_at, Vec3::Y);
+        };
+
+        let t = ((time - from.time) / (to.time - from.time).max(f32::EPSILON)).clamp(0.0, 1.0);
+        let before = self.keyframes[segment.saturating_sub(1)].position;
+        let after = self.keyframes[(segment + 2).min(last)].position;
+        let position = catmull_rom(before, from.position, to.position, after, t);
+        let eased = t * t * (3.0 - 2.0 * t);
+        let look_at = from.look_at.lerp(to.look_at, eased);
+        Transform::from_translation(position).looking_at(look_at, Vec3::Y)
+    }
+}
+
+fn catmull_rom(p0: Vec3, p1: Vec3, p2: Vec3, p3: Vec3, t: f32) -> Vec3 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    0.5 * (2.0 * p1
+        + (p2 - p0) * t
+        + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t2
+        + (3.0 * p1 - p0 - 3.0 * p2 + p3) * t3)
+}
+
+/// The cutscene playing, if any; set one with [`Cutscene::play`] and switch
+/// the camera to [`CameraMode::Cutscene`]
+#[derive(Resource, Default)]
+pub struct Cutscene {
+    pub path: Option<CameraPath>,
+    pub elapsed: f32,
+}
+
+impl Cutscene {
+    pub fn play(path: CameraPath) -> Self {
+        Self {
+            path: Some(path),
+            elapsed: 0.0,
+        }
+    }
+}
+
+/// Set by a new game from the menu so the dive opens with the flyover
+#[derive(Resource)]
+pub struct PlayIntro;
+
+#[derive(Component)]
+struct SkipHint;
+
+pub struct CutscenePlugin;
+
+impl Plugin for CutscenePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Cutscene>()
+            .add_systems(OnEnter(CameraMode::Cutscene), spawn_skip_hint)
+            .add_systems(OnExit(CameraMode::Cutscene), end_cutscene)
+            .add_systems(
+                Update,
+                (
+                    start_intro
+                        .run_if(resource_exists::<PlayIntro>)
+                        .run_if(in_state(AppState::InGame)),
+                    (skip_system, cutscene_camera_system)
+                        .chain()
+                        .after(crate::camera_follow)
+                        .in_set(GameSet::Presentation)
+                        .run_if(in_state(CameraMode::Cutscene)),
+                ),
+            );
+    }
+}
+
+/// Once the submarine is in the water, flies in from beyond the mountains to
+/// the chase camera's place behind it
+fn start_intro(
+    mut commands: Commands,
+    mut next_camera_mode: ResMut<NextState<CameraMode>>,
+    camera_state: Res<CameraState>,
+    submarine_query: Query<&Transform, With<Submarine>>,
+) {
+    let Ok(submarine) = submarine_query.single() else {
+        return;
+    };
+    commands.remove_resource::<PlayIntro>();
+
+    let yaw = submarine.rotation.to_euler(EulerRot::YXZ).0;
+    let chase = submarine.translation
+        + chase_offset(
+            camera_state.distance,
+            camera_state.height,
+            yaw,
+            camera_state.pitch,
+        );
+    let approach = chase - submarine.translation;
+    let end_angle = approach.x.atan2(approach.z);
+    let ring = |angle: f32, radius: f32, height: f32| {
+        Vec3::new(radius * angle.sin(), height, radius * angle.cos())
+    };
+    let keyframe = |time: f32, position: Vec3, look_at: Vec3| CameraKeyframe {
+        time,
+        position,
+        look_at,
+    };
+    let path = CameraPath {
+        keyframes: vec![
+            keyframe(
+                0.0,
+                ring(end_angle - INTRO_SWEEP, INTRO_RADIUS, INTRO_HEIGHT),
+                Vec3::ZERO,
+            ),
+            keyframe(
+                4.0,
+                ring(
+                    end_angle - INTRO_SWEEP / 2.0,
+                    INTRO_RADIUS - 40.0,
+                    INTRO_HEIGHT - 20.0,
+                ),
+                Vec3::ZERO,
+            ),
+            keyframe(
+                8.0,
+                ring(end_angle, INTRO_RADIUS - 150.0, INTRO_HEIGHT - 40.0),
+                submarine.translation,
+            ),
+            keyframe(
+                11.0,
+                submarine.translation + approach * 3.0 + Vec3::Y * 20.0,
+                submarine.translation,
+            ),
+            keyframe(14.0, chase, submarine.translation),
+        ],
+    };
+    info!("Playing intro flyover ({:.0} s)", path.duration());
+    commands.insert_resource(Cutscene::play(path));
+    next_camera_mode.set(CameraMode::Cutscene);
+}
+
+fn spawn_skip_hint(mut commands: Commands, assets: Res<GameAssets>) {
+    commands.spawn((
+        Text::new("Press any key to skip"),
+        TextFont {
+            font_size: 16.0,
+            font: assets.font.clone(),
+            ..default()
+        },
+        TextColor(Color::srgba(1.0, 1.0, 1.0, 0.7)),
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(24.0),
+            right: Val::Px(24.0),
+            ..default()
+        },
+        SkipHint,
+        StateScoped(AppState::InGame),
+    ));
+}
+
+/// Any key but Escape (which still pauses) or a click skips to the end
+fn skip_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    mut cutscene: ResMut<Cutscene>,
+) {
+    let key = keyboard_input
+        .get_just_pressed()
+        .any(|key| *key != KeyCode::Escape);
+    if key || mouse_input.get_just_pressed().next().is_some() {
+        cutscene.elapsed = f32::MAX;
+    }
+}
+
+/// Moves the camera along the path and hands back to the chase camera at the end
+fn cutscene_camera_system(
+    mut cutscene: ResMut<Cutscene>,
+    mut next_camera_mode: ResMut<NextState<CameraMode>>,
+    mut camera_query: Query<&mut Transform, With<CameraFollow>>,
+    time: Res<Time>,
+) {
+    let Some(path) = cutscene.path.as_ref() else {
+        next_camera_mode.set(CameraMode::Exterior);
+        return;
+    };
+    let elapsed = cutscene.elapsed.min(path.duration());
+    for mut transform in camera_query.iter_mut() {
+        *transform = path.sample(elapsed);
+    }
+    if cutscene.elapsed >= path.duration() {
+        next_camera_mode.set(CameraMode::Exterior);
+    } else {
+        cutscene.elapsed += time.delta_secs();
+    }
+}
+
+/// Clears the cutscene and lets the chase camera take over from where it ended
+fn end_cutscene(
+    mut commands: Commands,
+    mut cutscene: ResMut<Cutscene>,
+    mut camera_state: ResMut<CameraState>,
+    hint_query: Query<Entity, With<SkipHint>>,
+    camera_query: Query<&Transform, With<CameraFollow>>,
+    submarine_query: Query<&Transform, (With<Submarine>, Without<CameraFollow>)>,
+) {
+    *cutscene = Cutscene::default();
+    for hint in hint_query.iter() {
+        commands.entity(hint).despawn();
+    }
+    if let Ok(camera) = camera_query.single() {
+        camera_state.position = camera.translation;
+    }
+    if let Ok(submarine) = submarine_query.single() {
+        let yaw = submarine.rotation.to_euler(EulerRot::YXZ).0;
+        camera_state.yaw = yaw;
+        camera_state.target_yaw = yaw;
+    }
+}
@@ -0,0 +1,163 @@
+//! Console variables: named, bounded numbers that plugins register with a default
+//! and read back through the `Cvars` resource. Values can be changed from the
+//! in-game console or set in `cvars.cfg`, one `name value` pair per line.
+//! Cvars are for how the game's systems behave: ranges, rates and timings of
+//! creatures, hunters, encounters, failures and leaks. The boat's handling
+//! is in `tuning.ron` and what spawns where is in `tables.ron`.
+
+use crate::storage;
+use bevy::prelude::*;
+use std::collections::{BTreeMap, HashMap};
+use std::ops::RangeInclusive;
+
+/// Config storage key (a file relative to the working directory on native builds)
+const CVARS_PATH: &str = "cvars.cfg";
+
+/// A cvar as its plugin declares it, kept as a constant next to the code that
+/// reads it
+pub struct CvarDef {
+    pub name: &'static str,
+    pub default: f32,
+    pub range: RangeInclusive<f32>,
+    pub description: &'static str,
+}
+
+pub struct Cvar {
+    pub value: f32,
+    pub default: f32,
+    pub range: RangeInclusive<f32>,
+    pub description: &'static str,
+}
+
+/// Every registered cvar, by name
+#[derive(Resource)]
+pub struct Cvars {
+    vars: BTreeMap<String, Cvar>,
+    config: HashMap<String, f32>, // Values from cvars.cfg, applied as their cvars register
+}
+
+impl Default for Cvars {
+    /// Starts out with the values in `cvars.cfg`, waiting for their cvars to register
+    fn default() -> Self {
+        Self {
+            vars: BTreeMap::new(),
+            config: storage::read(CVARS_PATH)
+                .map(|contents| parse_config(&contents))
+                .unwrap_or_default(),
+        }
+    }
+}
+
+fn parse_config(contents: &str) -> HashMap<String, f32> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with("//"))
+        .filter_map(|line| {
+            let (name, value) = line.split_once(char::is_whitespace)?;
+            match value.trim().parse() {
+                Ok(value) => Some((name.to_string(), value)),
+                Err(_) => {
+                    warn!("Ignoring {} in {}: not a number", name, CVARS_PATH);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+impl Cvars {
+    /// Ignores `cvars.cfg`, so every cvar keeps its default
+    pub fn without_config() -> Self {
+        Self {
+            vars: BTreeMap::new(),
+            config: HashMap::new(),
+        }
+    }
+
+    pub fn register(&mut self, def: &CvarDef) {
+        let range = def.range.clone();
+        let value = self.config.remove(def.name).map_or(def.default, |value| {
+            value.clamp(*range.start(), *range.end())
+        });
+        self.vars.insert(
+            def.name.to_string(),
+            Cvar {
+                value,
+                default: def.default,
+                range,
+                description: def.description,
+            },
+        );
+    }
+
+    /// The current value, or the default while its plugin is left out of the app
+    pub fn get(&self, def: &CvarDef) -> f32 {
+        self.vars
+            .get(def.name)
+            .map_or(def.default, |cvar| cvar.value)
+    }
+
+    pub fn lookup(&self, name: &str) -> Option<&Cvar> {
+        self.vars.get(name)
+    }
+
+    /// Sets a cvar, clamped to its bounds, and returns the value it ended up with
+    pub fn set(&mut self, name: &str, value: f32) -> Result<f32, String> {
+        let cvar = self
+            .vars
+            .get_mut(name)
+            .ok_or_else(|| format!("Unknown cvar {}", name))?;
+        cvar.value = value.clamp(*cvar.range.start(), *cvar.range.end());
+        Ok(cvar.value)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Cvar)> {
+        self.vars.iter()
+    }
+
+    /// Writes every changed cvar to `cvars.cfg`
+    pub fn save(&self) -> Result<usize, String> {
+        let changed: Vec<String> = self
+            .vars
+            .iter()
+            .filter(|(_, cvar)| cvar.value != cvar.default)
+            .map(|(name, cvar)| format!("{} {}", name, cvar.value))
+            .collect();
+        let contents = format!(
+            "// Console variables that differ from their defaults\n{}\n",
+            changed.join("\n")
+        );
+        storage::write(CVARS_PATH, &contents).map(|_| changed.len())
+    }
+}
+
+pub trait RegisterCvar {
+    /// Registers a cvar on the app, for plugins to call while building
+    fn register_cvar(&mut self, def: &CvarDef) -> &mut Self;
+}
+
+impl RegisterCvar for App {
+    fn register_cvar(&mut self, def: &CvarDef) -> &mut Self {
+        self.world_mut()
+            .get_resource_or_init::<Cvars>()
+            .register(def);
+        self
+    }
+}
+
+pub struct CvarsPlugin;
+
+impl Plugin for CvarsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Cvars>()
+            .add_systems(PostStartup, report_unknown_config);
+    }
+}
+
+/// Everything has registered by now, so leftover config entries are typos
+fn report_unknown_config(cvars: Res<Cvars>) {
+    for name in cvars.config.keys() {
+        warn!("{} sets unknown cvar {}", CVARS_PATH, name);
+    }
+}
@@ -0,0 +1,300 @@
+//! Mission debrief: while a mission runs its time, catches, damage, resource use
+//! and route are logged, and when it ends a debrief screen sums them up with the
+//! route traced on a chart. The figures also go out as a `MissionDebrief` event.
+
+use crate::save::format_play_time;
+use crate::scoring::FishCollected;
+use crate::scripting::MissionEnded;
+use crate::{AppState, BallastState, GameAssets, GameSet, GameState, Submarine};
+use bevy::prelude::*;
+
+const ROUTE_INTERVAL: f32 = 1.0; // Seconds between route points
+const CHART_SIZE: f32 = 220.0;
+const CHART_MARGIN: f32 = 10.0;
+
+/// What a finished mission took, for statistics and achievements
+#[derive(Event, Clone)]
+pub struct MissionDebrief {
+    pub mission: String,
+    pub completed: bool,
+    pub time: f32, // Seconds
+    pub fish: u32,
+    pub damage: f32,      // Hull percent lost
+    pub oxygen_used: f32, // Percent
+    pub air_used: f32,    // Share of a full air bank
+    pub electricity_used: f32,
+}
+
+/// Running totals for the mission in progress
+#[derive(Resource, Default)]
+struct MissionLog {
+    elapsed: f32,
+    fish: u32,
+    damage: f32,
+    oxygen_used: f32,
+    air_used: f32,
+    electricity_used: f32,
+    route: Vec<Vec2>, // X and Z, seen from above
+    next_route_point: f32,
+    previous: Option<[f32; 4]>, // Health, oxygen, air and electricity last frame
+}
+
+impl MissionLog {
+    /// Starts the next mission's totals from where the submarine is now
+    fn restart(&mut self) {
+        *self = MissionLog {
+            previous: self.previous,
+            ..default()
+        };
+    }
+}
+
+#[derive(Component)]
+struct DebriefScreen {
+    completed: bool,
+}
+
+pub struct DebriefPlugin;
+
+impl Plugin for DebriefPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<MissionDebrief>()
+            .init_resource::<MissionLog>()
+            .add_systems(OnEnter(AppState::InGame), reset_mission_log)
+            .add_systems(
+                Update,
+                (
+                    (mission_log_system, debrief_system)
+                        .chain()
+                        .in_set(GameSet::Detection),
+                    close_debrief_system.run_if(any_with_component::<DebriefScreen>),
+                ),
+            );
+    }
+}
+
+fn reset_mission_log(mut commands: Commands) {
+    commands.insert_resource(MissionLog::default());
+}
+
+fn mission_log_system(
+    mut log: ResMut<MissionLog>,
+    mut fish_collected: EventReader<FishCollected>,
+    game_state: Res<GameState>,
+    ballast_state: Res<BallastState>,
+    submarine_query: Query<&Transform, With<Submarine>>,
+    time: Res<Time>,
+) {
+    log.elapsed += time.delta_secs();
+    log.fish += fish_collected.read().count() as u32;
+
+    // Only losses count; refills at the surface and repairs aren't subtracted
+    let current = [
+        game_state.health,
+        game_state.oxygen,
+        ballast_state.compressed_air,
+        ballast_state.electricity,
+    ];
+    if let Some(previous) = log.previous {
+        let lost = |index: usize| (previous[index] - current[index]).max(0.0);
+        log.damage += lost(0);
+        log.oxygen_used += lost(1);
+        log.air_used += lost(2);
+        log.electricity_used += lost(3);
+    }
+    log.previous = Some(current);
+
+    if log.elapsed >= log.next_route_point {
+        log.next_route_point += ROUTE_INTERVAL;
+        if let Ok(transform) = submarine_query.single() {
+            log.route.push(transform.translation.xz());
+        }
+    }
+}
+
+fn debrief_system(
+    mut commands: Commands,
+    mut log: ResMut<MissionLog>,
+    mut mission_ended: EventReader<MissionEnded>,
+    mut debriefs: EventWriter<MissionDebrief>,
+    screen_query: Query<Entity, With<DebriefScreen>>,
+    assets: Res<GameAssets>,
+) {
+    for ended in mission_ended.read() {
+        let debrief = MissionDebrief {
+            mission: ended.mission.clone(),
+            completed: ended.completed,
+            time: log.elapsed,
+            fish: log.fish,
+            damage: log.damage,
+            oxygen_used: log.oxygen_used,
+            air_used: log.air_used,
+            electricity_used: log.electricity_used,
+        };
+        for screen in screen_query.iter() {
+            commands.entity(screen).despawn();
+        }
+        spawn_debrief_screen(
+            &mut commands,
+            assets.font.clone(),
+            ended,
+            &debrief,
+            &log.route,
+        );
+        debriefs.write(debrief);
+        log.restart();
+    }
+}
+
+fn spawn_debrief_screen(
+    commands: &mut Commands,
+    font: Handle<Font>,
+    ended: &MissionEnded,
+    debrief: &MissionDebrief,
+    route: &[Vec2],
+) {
+    let mut lines = vec![if ended.completed {
+        format!("Mission complete: {}", ended.mission)
+    } else {
+        format!("Mission failed: {}\n{}", ended.mission, ended.reason)
+    }];
+    lines.push(String::new());
+    if !ended.objectives.is_empty() {
+        lines.push("Objectives".to_string());
+        for (index, objective) in ended.objectives.iter().enumerate() {
+            let last = index + 1 == ended.objectives.len();
+            let status = if last && !ended.completed {
+                "failed"
+            } else {
+                "done"
+            };
+            lines.push(format!("  {} ({})", objective, status));
+        }
+        lines.push(String::new());
+    }
+    lines.push(format!("Time: {}", format_play_time(debrief.time)));
+    lines.push(format!("Fish caught: {}", debrief.fish));
+    lines.push(format!("Damage taken: {:.0}%", debrief.damage));
+    lines.push(format!(
+        "Used: {:.0}% oxygen, {:.0}% compressed air, {:.1} electricity",
+        debrief.oxygen_used,
+        debrief.air_used * 100.0,
+        debrief.electricity_used
+    ));
+    lines.push(String::new());
+    lines.push(
+        if ended.completed {
+            "Press Enter to continue"
+        } else {
+            "Press Enter to return to the main menu, or R to try again"
+        }
+        .to_string(),
+    );
+
+    let mut screen = commands.spawn((
+        Node {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        GlobalZIndex(7),
+        DebriefScreen {
+            completed: ended.completed,
+        },
+        StateScoped(AppState::InGame),
+    ));
+    if !ended.completed {
+        // A failed mission ends the run like any other game over
+        screen.insert(crate::hud::GameOverScreen);
+    }
+    screen.with_children(|screen| {
+        screen
+            .spawn((
+                Node {
+                    padding: UiRect::all(Val::Px(20.0)),
+                    column_gap: Val::Px(24.0),
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                BackgroundColor(Color::srgba(0.0, 0.05, 0.1, 0.85)),
+            ))
+            .with_children(|panel| {
+                panel.spawn((
+                    Text::new(lines.join("\n")),
+                    TextFont {
+                        font_size: 18.0,
+                        font,
+                        ..default()
+                    },
+                    TextColor(Color::WHITE),
+                ));
+                spawn_route_chart(panel, route);
+            });
+    });
+}
+
+/// The mission's route from above, scaled to fit; green where it started and
+/// red where it ended
+fn spawn_route_chart(panel: &mut ChildSpawnerCommands, route: &[Vec2]) {
+    let (min, max) = route.iter().fold(
+        (Vec2::splat(f32::MAX), Vec2::splat(f32::MIN)),
+        |(min, max), point| (min.min(*point), max.max(*point)),
+    );
+    // Keep the aspect ratio, and don't zoom in on a submarine that barely moved
+    let extent = (max - min).max_element().max(20.0);
+    let center = (min + max) / 2.0;
+    let scale = (CHART_SIZE - 2.0 * CHART_MARGIN) / extent;
+
+    panel
+        .spawn((
+            Node {
+                width: Val::Px(CHART_SIZE),
+                height: Val::Px(CHART_SIZE),
+                ..default()
+            },
+            BackgroundColor(Color::srgb(0.05, 0.15, 0.25)),
+        ))
+        .with_children(|chart| {
+            for (index, point) in route.iter().enumerate() {
+                let offset = (*point - center) * scale + Vec2::splat(CHART_SIZE / 2.0);
+                let (size, color) = if index == 0 {
+                    (6.0, Color::srgb(0.3, 1.0, 0.4))
+                } else if index + 1 == route.len() {
+                    (6.0, Color::srgb(1.0, 0.3, 0.3))
+                } else {
+                    (3.0, Color::srgb(0.9, 0.9, 0.6))
+                };
+                chart.spawn((
+                    Node {
+                        position_type: PositionType::Absolute,
+                        left: Val::Px(offset.x - size / 2.0),
+                        top: Val::Px(offset.y - size / 2.0),
+                        width: Val::Px(size),
+                        height: Val::Px(size),
+                        ..default()
+                    },
+                    BackgroundColor(color),
+                ));
+            }
+        });
+}
+
+/// Enter dismisses the debrief of a completed mission; failed missions are
+/// handled by the game over screen
+fn close_debrief_system(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    screen_query: Query<(Entity, &DebriefScreen)>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::Enter) {
+        return;
+    }
+    for (entity, screen) in screen_query.iter() {
+        if screen.completed {
+            commands.entity(entity).despawn();
+        }
+    }
+}
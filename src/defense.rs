@@ -0,0 +1,356 @@
+use crate::combat::{spawn_hostile, Armament, CombatAssets, Hostile, HostileAttack};
+use crate::hud::spawn_game_over_screen;
+use crate::{AppState, GameAssets, GameMode, GameSet, Submarine, WorldSeed};
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+const HABITAT_POSITION: Vec3 = Vec3::new(0.0, -20.5, -30.0); // Center of the dome on the sea floor
+const HABITAT_RADIUS: f32 = 4.0;
+const HABITAT_INTEGRITY: f32 = 100.0;
+const INTERMISSION: f32 = 20.0; // Seconds of repair and resupply between waves
+const DOCK_RANGE: f32 = 12.0; // The submarine repairs the habitat while this close to it
+const REPAIR_RATE: f32 = 4.0; // Integrity restored per second while docked
+const WAVE_TORPEDOES: u32 = 12;
+const WAVE_DECOYS: u32 = 4;
+const SPAWN_DISTANCE: f32 = 90.0;
+const SPAWN_INTERVAL: f32 = 2.0; // Seconds between attackers within a wave
+
+/// The undersea station that has to be kept standing
+#[derive(Component)]
+struct Habitat;
+
+#[derive(Component)]
+struct DefenseText;
+
+/// Random source for attacker placement, seeded from the world so replays match
+#[derive(Resource)]
+struct DefenseRng(StdRng);
+
+#[derive(Clone, Copy, PartialEq)]
+enum Phase {
+    Intermission { remaining: f32 },
+    Wave { to_spawn: u32, spawn_timer: f32 },
+    Lost,
+}
+
+/// Progress of the current defense
+#[derive(Resource)]
+struct DefenseState {
+    wave: u32, // Number of the current or next wave, from 1
+    phase: Phase,
+    integrity: f32,
+}
+
+impl Default for DefenseState {
+    fn default() -> Self {
+        Self {
+            wave: 1,
+            phase: Phase::Intermission {
+                remaining: INTERMISSION,
+            },
+            integrity: HABITAT_INTEGRITY,
+        }
+    }
+}
+
+/// The habitat's dome, modules and windows, made once
+#[derive(Resource)]
+struct HabitatAssets {
+    dome_mesh: Handle<Mesh>,
+    tube_mesh: Handle<Mesh>,
+    module_mesh: Handle<Mesh>,
+    window_mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+    window_material: Handle<StandardMaterial>,
+}
+
+impl FromWorld for HabitatAssets {
+    fn from_world(world: &mut World) -> Self {
+        let mut meshes = world.resource_mut::<Assets<Mesh>>();
+        let dome_mesh = meshes.add(Sphere::new(HABITAT_RADIUS));
+        let tube_mesh = meshes.add(Cylinder::new(0.8, 5.0));
+        let module_mesh = meshes.add(Sphere::new(2.0));
+        let window_mesh = meshes.add(Sphere::new(0.35));
+        let mut materials = world.resource_mut::<Assets<StandardMaterial>>();
+        Self {
+            dome_mesh,
+            tube_mesh,
+            module_mesh,
+            window_mesh,
+            material: materials.add(StandardMaterial {
+                base_color: Color::srgb(0.75, 0.78, 0.8),
+                metallic: 0.6,
+                perceptual_roughness: 0.4,
+                ..default()
+            }),
+            window_material: materials.add(StandardMaterial {
+                base_color: Color::srgb(1.0, 0.9, 0.6),
+                emissive: LinearRgba::rgb(3.0, 2.5, 1.2),
+                ..default()
+            }),
+        }
+    }
+}
+
+pub struct DefensePlugin;
+
+impl Plugin for DefensePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DefenseState>()
+            .add_systems(
+                OnEnter(AppState::InGame),
+                spawn_habitat.run_if(resource_equals(GameMode::Defense)),
+            )
+            .add_systems(
+                Update,
+                (
+                    (habitat_attack_system, wave_system)
+                        .chain()
+                        .in_set(GameSet::Simulation),
+                    defense_text_system.in_set(GameSet::Presentation),
+                )
+                    .run_if(resource_equals(GameMode::Defense)),
+            );
+    }
+
+    fn finish(&self, app: &mut App) {
+        app.init_resource::<HabitatAssets>();
+    }
+}
+
+/// Waves grow by two attackers each and get tougher and faster
+fn wave_size(wave: u32) -> u32 {
+    3 + 2 * (wave - 1)
+}
+
+fn spawn_habitat(
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+    habitat: Res<HabitatAssets>,
+    seed: Res<WorldSeed>,
+) {
+    commands.insert_resource(DefenseState::default());
+    commands.insert_resource(DefenseRng(StdRng::seed_from_u64(seed.0)));
+
+    commands
+        .spawn((
+            Mesh3d(habitat.dome_mesh.clone()),
+            MeshMaterial3d(habitat.material.clone()),
+            Transform::from_translation(HABITAT_POSITION),
+            RigidBody::Fixed,
+            Collider::ball(HABITAT_RADIUS),
+            Habitat,
+            StateScoped(AppState::InGame),
+        ))
+        .with_children(|parent| {
+            // Connecting tubes to two smaller modules
+            for side in [-1.0, 1.0] {
+                parent.spawn((
+                    Mesh3d(habitat.tube_mesh.clone()),
+                    MeshMaterial3d(habitat.material.clone()),
+                    Transform::from_xyz(side * 5.5, 1.0, 0.0)
+                        .with_rotation(Quat::from_rotation_z(std::f32::consts::FRAC_PI_2)),
+                ));
+                parent.spawn((
+                    Mesh3d(habitat.module_mesh.clone()),
+                    MeshMaterial3d(habitat.material.clone()),
+                    Transform::from_xyz(side * 9.0, 1.0, 0.0),
+                ));
+            }
+            // Ring of lit windows around the dome
+            for i in 0..8 {
+                let angle = i as f32 * std::f32::consts::TAU / 8.0;
+                parent.spawn((
+                    Mesh3d(habitat.window_mesh.clone()),
+                    MeshMaterial3d(habitat.window_material.clone()),
+                    Transform::from_xyz(
+                        angle.cos() * HABITAT_RADIUS * 0.9,
+                        1.5,
+                        angle.sin() * HABITAT_RADIUS * 0.9,
+                    ),
+                ));
+            }
+            parent.spawn((
+                PointLight {
+                    color: Color::srgb(1.0, 0.9, 0.7),
+                    intensity: 200_000.0,
+                    range: 30.0,
+                    ..default()
+                },
+                Transform::from_xyz(0.0, HABITAT_RADIUS + 2.0, 0.0),
+            ));
+        });
+
+    commands.spawn((
+        Text::new(""),
+        TextFont {
+            font_size: 24.0,
+            font: assets.font.clone(),
+            ..default()
+        },
+        TextColor(Color::WHITE),
+        TextLayout::new_with_justify(JustifyText::Center),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(16.0),
+            width: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        DefenseText,
+        StateScoped(AppState::InGame),
+    ));
+}
+
+/// Strikes on the habitat wear down its integrity; losing it ends the game
+fn habitat_attack_system(
+    mut commands: Commands,
+    mut attacks: EventReader<HostileAttack>,
+    mut defense: ResMut<DefenseState>,
+    habitat_query: Query<Entity, With<Habitat>>,
+    assets: Res<GameAssets>,
+) {
+    for attack in attacks.read() {
+        if habitat_query.contains(attack.prey) {
+            defense.integrity = (defense.integrity - attack.damage).max(0.0);
+        }
+    }
+    if defense.integrity > 0.0 || defense.phase == Phase::Lost {
+        return;
+    }
+    defense.phase = Phase::Lost;
+    let held = defense.wave - 1;
+    info!("Habitat lost during wave {}", defense.wave);
+    spawn_game_over_screen(
+        &mut commands,
+        assets.font.clone(),
+        format!(
+            "Habitat lost\nHeld out for {} wave{}",
+            held,
+            if held == 1 { "" } else { "s" }
+        ),
+    );
+}
+
+/// Alternates repair/resupply intermissions with waves of attackers
+#[allow(clippy::too_many_arguments)]
+fn wave_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut defense: ResMut<DefenseState>,
+    mut armament: ResMut<Armament>,
+    mut rng: ResMut<DefenseRng>,
+    assets: Res<CombatAssets>,
+    submarine_query: Query<(Entity, &Transform), With<Submarine>>,
+    habitat_query: Query<Entity, With<Habitat>>,
+    hostile_query: Query<(), With<Hostile>>,
+) {
+    let (Ok((submarine_entity, submarine)), Ok(habitat)) =
+        (submarine_query.single(), habitat_query.single())
+    else {
+        return;
+    };
+    let delta = time.delta_secs();
+    match defense.phase {
+        Phase::Intermission { remaining } => {
+            if submarine.translation.distance(HABITAT_POSITION) < DOCK_RANGE {
+                defense.integrity =
+                    (defense.integrity + REPAIR_RATE * delta).min(HABITAT_INTEGRITY);
+            }
+            let remaining = remaining - delta;
+            defense.phase = if remaining > 0.0 {
+                Phase::Intermission { remaining }
+            } else {
+                info!("Wave {} incoming", defense.wave);
+                Phase::Wave {
+                    to_spawn: wave_size(defense.wave),
+                    spawn_timer: 0.0,
+                }
+            };
+        }
+        Phase::Wave {
+            to_spawn,
+            spawn_timer,
+        } => {
+            if to_spawn == 0 {
+                if hostile_query.is_empty() {
+                    // Wave cleared: resupply and give the player time to repair
+                    defense.wave += 1;
+                    armament.torpedoes = armament.torpedoes.max(WAVE_TORPEDOES);
+                    armament.decoys = armament.decoys.max(WAVE_DECOYS);
+                    defense.phase = Phase::Intermission {
+                        remaining: INTERMISSION,
+                    };
+                }
+                return;
+            }
+            let spawn_timer = spawn_timer - delta;
+            if spawn_timer > 0.0 {
+                defense.phase = Phase::Wave {
+                    to_spawn,
+                    spawn_timer,
+                };
+                return;
+            }
+
+            let bearing = rng.0.gen::<f32>() * std::f32::consts::TAU;
+            let position = Vec3::new(
+                HABITAT_POSITION.x + bearing.cos() * SPAWN_DISTANCE,
+                -3.0 - rng.0.gen::<f32>() * 15.0,
+                HABITAT_POSITION.z + bearing.sin() * SPAWN_DISTANCE,
+            );
+            let wave = defense.wave as f32;
+            // Every fourth attacker hunts the submarine instead of the habitat
+            let (prey, reach) = if to_spawn % 4 == 0 {
+                (submarine_entity, 2.5)
+            } else {
+                (habitat, HABITAT_RADIUS + 1.5)
+            };
+            spawn_hostile(
+                &mut commands,
+                &assets,
+                position,
+                Hostile {
+                    health: 40.0 + 15.0 * wave,
+                    speed: (3.0 + 0.4 * wave).min(8.0),
+                    damage: 10.0,
+                    prey,
+                    reach,
+                },
+            );
+            defense.phase = Phase::Wave {
+                to_spawn: to_spawn - 1,
+                spawn_timer: SPAWN_INTERVAL,
+            };
+        }
+        Phase::Lost => {}
+    }
+}
+
+fn defense_text_system(
+    defense: Res<DefenseState>,
+    armament: Res<Armament>,
+    hostile_query: Query<(), With<Hostile>>,
+    mut text_query: Query<&mut Text, With<DefenseText>>,
+) {
+    let Ok(mut text) = text_query.single_mut() else {
+        return;
+    };
+    let status = match defense.phase {
+        Phase::Intermission { remaining } => format!(
+            "Wave {} in {:.0} s - stay close to the habitat to repair it",
+            defense.wave, remaining
+        ),
+        Phase::Wave { to_spawn, .. } => format!(
+            "Wave {}   Attackers left {}",
+            defense.wave,
+            to_spawn as usize + hostile_query.iter().count()
+        ),
+        Phase::Lost => "Habitat lost".to_string(),
+    };
+    **text = format!(
+        "{}\nHabitat {:.0}%   Torpedoes {}   Decoys {}",
+        status, defense.integrity, armament.torpedoes, armament.decoys
+    );
+}
@@ -0,0 +1,244 @@
+//! The depth profile: a side view of the water ahead, the vertical partner of
+//! the sonar. Rays dropped along the heading trace the sea floor, and the
+//! submarine's depth sits against the thermoclines and the crush depth, so a
+//! rising slope or a dive past the hull's limit is easy to see coming.
+
+use crate::settings::Settings;
+use crate::{AppState, GameAssets, GameSet, Submarine, Tuning};
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+const PROFILE_WIDTH: f32 = 240.0;
+const PROFILE_HEIGHT: f32 = 110.0;
+const PROFILE_RANGE: f32 = 60.0; // Meters ahead the profile reaches
+const PROFILE_DEPTH: f32 = 24.0; // Meters from the top of the profile to the bottom
+const COLUMNS: usize = 48; // Rays dropped along the heading, one per column
+const SUBMARINE_X: f32 = 8.0; // Pixels from the left edge the submarine is drawn at
+
+const FLOOR_COLOR: Color = Color::srgb(0.45, 0.35, 0.2);
+const CRUSH_COLOR: Color = Color::srgb(1.0, 0.25, 0.2);
+const THERMOCLINE_COLOR: Color = Color::srgba(0.4, 0.7, 1.0, 0.6);
+
+#[derive(Component)]
+struct DepthProfilePanel;
+
+/// One slice of sea floor, filled up from the bottom of the profile
+#[derive(Component)]
+struct FloorColumn(usize);
+
+#[derive(Component)]
+struct ProfileSubmarine;
+
+/// A depth marked across the profile, kept in step with the tuning
+#[derive(Component)]
+enum ProfileLine {
+    Crush,
+    Thermocline(usize),
+}
+
+#[derive(Component)]
+struct ProfileText;
+
+pub struct DepthProfilePlugin;
+
+impl Plugin for DepthProfilePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(AppState::InGame), spawn_profile)
+            .add_systems(
+                Update,
+                (depth_profile_system, profile_lines_system).in_set(GameSet::Presentation),
+            );
+    }
+}
+
+/// Pixels from the top of the profile a depth is drawn at
+fn depth_to_y(depth: f32) -> f32 {
+    (depth / PROFILE_DEPTH).clamp(0.0, 1.0) * PROFILE_HEIGHT
+}
+
+fn spawn_profile(mut commands: Commands, assets: Res<GameAssets>, tuning: Res<Tuning>) {
+    let font = assets.font.clone();
+    let column_width = PROFILE_WIDTH / COLUMNS as f32;
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                top: Val::Percent(30.0),
+                right: Val::Px(16.0),
+                flex_direction: FlexDirection::Column,
+                row_gap: Val::Px(4.0),
+                ..default()
+            },
+            DepthProfilePanel,
+            StateScoped(AppState::InGame),
+        ))
+        .with_children(|panel| {
+            panel.spawn((
+                Text::new(""),
+                TextFont {
+                    font_size: 12.0,
+                    font,
+                    ..default()
+                },
+                TextColor(Color::srgb(0.7, 0.9, 1.0)),
+                ProfileText,
+            ));
+            panel
+                .spawn((
+                    Node {
+                        width: Val::Px(PROFILE_WIDTH),
+                        height: Val::Px(PROFILE_HEIGHT),
+                        border: UiRect::all(Val::Px(1.0)),
+                        overflow: Overflow::clip(),
+                        ..default()
+                    },
+                    BorderColor(Color::srgb(0.4, 0.6, 0.7)),
+                    BackgroundColor(Color::srgba(0.0, 0.05, 0.1, 0.7)),
+                ))
+                .with_children(|profile| {
+                    for index in 0..COLUMNS {
+                        profile.spawn((
+                            Node {
+                                position_type: PositionType::Absolute,
+                                left: Val::Px(index as f32 * column_width),
+                                bottom: Val::Px(0.0),
+                                width: Val::Px(column_width),
+                                height: Val::Px(0.0),
+                                ..default()
+                            },
+                            BackgroundColor(FLOOR_COLOR),
+                            FloorColumn(index),
+                        ));
+                    }
+                    let line = |depth: f32, color: Color| {
+                        (
+                            Node {
+                                position_type: PositionType::Absolute,
+                                left: Val::Px(0.0),
+                                top: Val::Px(depth_to_y(depth)),
+                                width: Val::Percent(100.0),
+                                height: Val::Px(1.0),
+                                ..default()
+                            },
+                            BackgroundColor(color),
+                        )
+                    };
+                    for index in 0..tuning.thermoclines.len() {
+                        profile.spawn((
+                            line(tuning.thermoclines[index], THERMOCLINE_COLOR),
+                            ProfileLine::Thermocline(index),
+                        ));
+                    }
+                    profile.spawn((line(tuning.crush_depth, CRUSH_COLOR), ProfileLine::Crush));
+                    profile.spawn((
+                        Node {
+                            position_type: PositionType::Absolute,
+                            left: Val::Px(SUBMARINE_X - 4.0),
+                            top: Val::Px(0.0),
+                            width: Val::Px(10.0),
+                            height: Val::Px(4.0),
+                            ..default()
+                        },
+                        BackgroundColor(Color::srgb(0.3, 1.0, 0.5)),
+                        ProfileSubmarine,
+                    ));
+                });
+        });
+}
+
+/// The submarine's marker on the profile
+type MarkerQuery<'w, 's> = Query<
+    'w,
+    's,
+    &'static mut Node,
+    (
+        With<ProfileSubmarine>,
+        Without<DepthProfilePanel>,
+        Without<FloorColumn>,
+    ),
+>;
+
+/// The nodes the depth profile draws into
+#[derive(SystemParam)]
+struct ProfileNodes<'w, 's> {
+    panel: Query<'w, 's, &'static mut Node, With<DepthProfilePanel>>,
+    columns: Query<'w, 's, (&'static FloorColumn, &'static mut Node), Without<DepthProfilePanel>>,
+    submarine_marker: MarkerQuery<'w, 's>,
+    text: Query<'w, 's, &'static mut Text, With<ProfileText>>,
+}
+
+/// Drops a ray from the surface every column's width along the heading and
+/// fills the profile up to where it met the floor, a mountain or a rock
+fn depth_profile_system(
+    rapier_context: ReadRapierContext,
+    settings: Res<Settings>,
+    tuning: Res<Tuning>,
+    submarine_query: Query<&Transform, With<Submarine>>,
+    mut nodes: ProfileNodes,
+) {
+    // Shown and hidden along with the sonar panel
+    for mut node in nodes.panel.iter_mut() {
+        node.display = if settings.hud.show_sonar {
+            Display::Flex
+        } else {
+            Display::None
+        };
+    }
+    let (Ok(submarine), Ok(context)) = (submarine_query.single(), rapier_context.single()) else {
+        return;
+    };
+
+    let heading = (submarine.rotation * Vec3::NEG_Z)
+        .with_y(0.0)
+        .try_normalize()
+        .unwrap_or(Vec3::NEG_Z);
+    let filter = QueryFilter::only_fixed().exclude_sensors();
+    let floor_at = |column: usize| {
+        let ahead = (column as f32 + 0.5) / COLUMNS as f32 * PROFILE_RANGE;
+        let origin = submarine.translation.with_y(0.0) + heading * ahead;
+        context
+            .cast_ray(origin, Vec3::NEG_Y, PROFILE_DEPTH, true, filter)
+            .map_or(PROFILE_DEPTH, |(_, toi)| toi)
+    };
+    for (column, mut node) in nodes.columns.iter_mut() {
+        node.height = Val::Px(PROFILE_HEIGHT - depth_to_y(floor_at(column.0)));
+    }
+
+    let depth = -submarine.translation.y;
+    for mut node in nodes.submarine_marker.iter_mut() {
+        node.top = Val::Px(depth_to_y(depth) - 2.0);
+    }
+    let clearance = floor_at(0) - depth;
+    let warning = if depth > tuning.crush_depth {
+        "  PAST CRUSH DEPTH"
+    } else {
+        ""
+    };
+    for mut text in nodes.text.iter_mut() {
+        text.0 = format!(
+            "Depth Profile, {:.0} m ahead\nDepth {:.1} m  Under keel {:.1} m  Crush {:.0} m{}",
+            PROFILE_RANGE, depth, clearance, tuning.crush_depth, warning
+        );
+    }
+}
+
+/// Moves the crush depth and thermocline lines when the tuning is reloaded
+fn profile_lines_system(tuning: Res<Tuning>, mut line_query: Query<(&ProfileLine, &mut Node)>) {
+    if !tuning.is_changed() {
+        return;
+    }
+    for (line, mut node) in line_query.iter_mut() {
+        let depth = match line {
+            ProfileLine::Crush => Some(tuning.crush_depth),
+            ProfileLine::Thermocline(index) => tuning.thermoclines.get(*index).copied(),
+        };
+        match depth {
+            Some(depth) => {
+                node.display = Display::Flex;
+                node.top = Val::Px(depth_to_y(depth));
+            }
+            None => node.display = Display::None,
+        }
+    }
+}
@@ -0,0 +1,187 @@
+//! Adaptive difficulty: with the gameplay option on, each free dive is
+//! reviewed as it ends. Dying, taking heavy damage or running short of oxygen
+//! or power eases the next dive a notch; a dive that goes smoothly tightens it.
+//! The level scales how fast and hard predators strike, how often equipment
+//! fails and, inversely, how many fish there are, and stays within the range
+//! set in `assets/tuning.ron`.
+
+use crate::replay::ReplayPlayback;
+use crate::settings::Settings;
+use crate::tuning::Tuning;
+use crate::{storage, AppState, BallastState, GameMode, GameSet, GameState};
+use bevy::prelude::*;
+
+/// Storage key the controller's level is kept under between sessions
+const LEVEL_PATH: &str = "difficulty.ron";
+const EASE_STEP: f32 = 0.05; // Level dropped after a rough dive
+const DEATH_STEP: f32 = 0.1; // Level dropped after losing the boat
+const HARDEN_STEP: f32 = 0.05; // Level raised after a smooth dive
+const MIN_DIVE_TIME: f32 = 60.0; // Dives shorter than this that survive say too little to judge
+const HEAVY_DAMAGE: f32 = 10.0; // Hull percent lost per minute that counts as a rough dive
+const LIGHT_DAMAGE: f32 = 2.0; // Hull percent lost per minute under which a dive went smoothly
+const STARVED_SHARE: f32 = 0.2; // Share of the dive short of oxygen or power that counts as rough
+const LOW_OXYGEN: f32 = 20.0; // Percent
+const LOW_POWER: f32 = 10.0; // Percent
+
+/// Difficulty in force for the current dive; 1 is the game as tuned, lower
+/// is kinder
+#[derive(Resource, Clone, Copy)]
+pub struct Difficulty(pub f32);
+
+impl Default for Difficulty {
+    fn default() -> Self {
+        Self(1.0)
+    }
+}
+
+impl Difficulty {
+    /// Multiplier on predator speed and bite
+    pub fn aggression(self) -> f32 {
+        self.0
+    }
+
+    /// Multiplier on the chance of equipment failures
+    pub fn failure_rate(self) -> f32 {
+        self.0
+    }
+
+    /// Fish released into the lake for a tuned count
+    pub fn fish_count(self, count: usize) -> usize {
+        (count as f32 / self.0).round() as usize
+    }
+}
+
+/// Where the controller has settled across dives, and how the current dive
+/// is going
+#[derive(Resource)]
+struct AdaptiveDifficulty {
+    level: f32,
+    elapsed: f32,
+    damage: f32,  // Hull percent lost this dive
+    starved: f32, // Seconds short of oxygen or power
+    last_health: Option<f32>,
+}
+
+impl Default for AdaptiveDifficulty {
+    fn default() -> Self {
+        Self {
+            level: 1.0,
+            elapsed: 0.0,
+            damage: 0.0,
+            starved: 0.0,
+            last_health: None,
+        }
+    }
+}
+
+pub struct DifficultyPlugin;
+
+impl Plugin for DifficultyPlugin {
+    fn build(&self, app: &mut App) {
+        let level = storage::read(LEVEL_PATH)
+            .and_then(|contents| ron::from_str(&contents).ok())
+            .unwrap_or(1.0);
+        app.init_resource::<Difficulty>()
+            .insert_resource(AdaptiveDifficulty { level, ..default() })
+            .add_systems(OnEnter(AppState::Loading), choose_difficulty)
+            .add_systems(OnEnter(AppState::InGame), reset_strain)
+            .add_systems(OnExit(AppState::InGame), review_dive)
+            .add_systems(
+                Update,
+                strain_system
+                    .in_set(GameSet::Simulation)
+                    .run_if(resource_equals(GameMode::Dive)),
+            );
+    }
+}
+
+/// Fixes the level for the dive about to be built; replays bring their own
+fn choose_difficulty(
+    mut difficulty: ResMut<Difficulty>,
+    adaptive: Res<AdaptiveDifficulty>,
+    settings: Res<Settings>,
+    tuning: Res<Tuning>,
+    mode: Res<GameMode>,
+    playback: Option<Res<ReplayPlayback>>,
+) {
+    if playback.is_some() {
+        return;
+    }
+    let (low, high) = tuning.difficulty_range;
+    *difficulty = if settings.gameplay.adaptive_difficulty && *mode == GameMode::Dive {
+        Difficulty(adaptive.level.clamp(low, high))
+    } else {
+        Difficulty::default()
+    };
+}
+
+fn reset_strain(mut adaptive: ResMut<AdaptiveDifficulty>) {
+    *adaptive = AdaptiveDifficulty {
+        level: adaptive.level,
+        ..default()
+    };
+}
+
+/// Adds up the damage taken and the time spent short of oxygen or power
+fn strain_system(
+    mut adaptive: ResMut<AdaptiveDifficulty>,
+    game_state: Res<GameState>,
+    ballast_state: Res<BallastState>,
+    time: Res<Time>,
+) {
+    let delta = time.delta_secs();
+    adaptive.elapsed += delta;
+    if let Some(last) = adaptive.last_health {
+        adaptive.damage += (last - game_state.health).max(0.0);
+    }
+    adaptive.last_health = Some(game_state.health);
+    if game_state.oxygen < LOW_OXYGEN || ballast_state.electricity < LOW_POWER {
+        adaptive.starved += delta;
+    }
+}
+
+/// Eases or tightens the level once a free dive is over, and keeps it for the
+/// next session
+fn review_dive(
+    mut adaptive: ResMut<AdaptiveDifficulty>,
+    settings: Res<Settings>,
+    tuning: Res<Tuning>,
+    game_state: Res<GameState>,
+    mode: Res<GameMode>,
+    playback: Option<Res<ReplayPlayback>>,
+) {
+    if !settings.gameplay.adaptive_difficulty || *mode != GameMode::Dive || playback.is_some() {
+        return;
+    }
+    let died = game_state.health <= 0.0;
+    if !died && adaptive.elapsed < MIN_DIVE_TIME {
+        return;
+    }
+    let minutes = adaptive.elapsed.max(MIN_DIVE_TIME) / 60.0;
+    let damage_rate = adaptive.damage / minutes;
+    let starved = adaptive.starved / adaptive.elapsed.max(1.0);
+    let step = if died {
+        -DEATH_STEP
+    } else if damage_rate > HEAVY_DAMAGE || starved > STARVED_SHARE {
+        -EASE_STEP
+    } else if damage_rate < LIGHT_DAMAGE && adaptive.starved == 0.0 {
+        HARDEN_STEP
+    } else {
+        0.0
+    };
+    let (low, high) = tuning.difficulty_range;
+    adaptive.level = (adaptive.level + step).clamp(low, high);
+    info!(
+        "Adaptive difficulty {:+.2} to {:.2} ({:.1}% hull per minute, {:.0}% starved)",
+        step,
+        adaptive.level,
+        damage_rate,
+        starved * 100.0
+    );
+    let result = ron::to_string(&adaptive.level)
+        .map_err(|err| err.to_string())
+        .and_then(|contents| storage::write(LEVEL_PATH, &contents));
+    if let Err(err) = result {
+        warn!("Failed to save the difficulty level: {}", err);
+    }
+}
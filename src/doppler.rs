@@ -0,0 +1,62 @@
+use crate::acoustics::SOUND_SPEED;
+use crate::{camera_follow, GameSet};
+use bevy::audio::SpatialListener;
+use bevy::prelude::*;
+
+const MIN_PITCH: f32 = 0.5;
+const MAX_PITCH: f32 = 2.0;
+
+/// Shifts the pitch of a spatial sound with how fast its entity closes on or
+/// opens from the listener; velocities come from the change in position
+#[derive(Component, Default)]
+pub struct Doppler {
+    previous: Option<Vec3>,
+}
+
+pub struct DopplerPlugin;
+
+impl Plugin for DopplerPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            doppler_system
+                .in_set(GameSet::Presentation)
+                .after(camera_follow),
+        );
+    }
+}
+
+fn doppler_system(
+    time: Res<Time>,
+    listener_query: Query<&Transform, With<SpatialListener>>,
+    mut source_query: Query<(&mut Doppler, &GlobalTransform, Option<&SpatialAudioSink>)>,
+    mut previous_listener: Local<Option<Vec3>>,
+) {
+    let dt = time.delta_secs();
+    let Ok(listener) = listener_query.single() else {
+        return;
+    };
+    let listener_position = listener.translation;
+    let listener_velocity = match *previous_listener {
+        Some(previous) if dt > 0.0 => (listener_position - previous) / dt,
+        _ => Vec3::ZERO,
+    };
+    *previous_listener = Some(listener_position);
+
+    for (mut doppler, transform, sink) in source_query.iter_mut() {
+        let position = transform.translation();
+        let previous = doppler.previous.replace(position);
+        let (Some(previous), Some(sink)) = (previous, sink) else {
+            continue;
+        };
+        if dt <= 0.0 {
+            continue;
+        }
+        let source_velocity = (position - previous) / dt;
+        let toward_source = (position - listener_position).normalize_or_zero();
+        // f' = f (c + listener speed towards the source) / (c + source speed away from the listener)
+        let pitch = (SOUND_SPEED + listener_velocity.dot(toward_source))
+            / (SOUND_SPEED + source_velocity.dot(toward_source));
+        sink.set_speed(pitch.clamp(MIN_PITCH, MAX_PITCH));
+    }
+}
@@ -0,0 +1,235 @@
+//! Fish ecology: the lake is split into a grid of regions, each able to carry
+//! as many fish as it held when the dive began. Schools breed back towards
+//! that over time, but a region fished down below a quarter of its stock
+//! collapses and won't breed again for a while. The tactical map shades each
+//! region by how healthy its stock is.
+
+use crate::tables::Tables;
+use crate::tactical::TacticalGizmos;
+use crate::{
+    spawn_fish, AppState, CameraMode, Fish, GameAssets, GameMode, GameSet, NewGamePlus, WorldSeed,
+};
+use bevy::prelude::*;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::f32::consts::FRAC_PI_2;
+
+const REGION_SIZE: f32 = 80.0; // Meters along each side of a region
+const GRID: usize = 6; // Regions along each side, centred on the middle of the lake
+const BREED_INTERVAL: f32 = 15.0; // Seconds between breeding rounds
+const BREED_RATE: f32 = 0.15; // Chance each fish spawns per round in an empty habitat, falling to 0 at capacity
+const COLLAPSE_SHARE: f32 = 0.25; // Stock below this share of capacity collapses
+const RECOVERY_TIME: f32 = 180.0; // Seconds a collapsed region waits before it breeds again
+const SPAWN_SPREAD: f32 = 3.0; // Meters from the parent young fish appear
+
+const HEALTHY_COLOR: Color = Color::srgba(0.3, 0.9, 0.4, 0.5);
+const DEPLETED_COLOR: Color = Color::srgba(1.0, 0.3, 0.2, 0.5);
+
+/// One square of the lake and the fish it holds
+#[derive(Clone, Copy, Default)]
+pub struct Region {
+    pub capacity: usize, // Fish it held at the start of the dive; 0 where none live
+    pub stock: usize,
+    pub recovering: f32, // Seconds left before a collapsed region breeds again
+    collapsed: bool,
+}
+
+impl Region {
+    /// Stock as a share of what the habitat carries, if it carries any
+    pub fn health(&self) -> Option<f32> {
+        (self.capacity > 0).then(|| (self.stock as f32 / self.capacity as f32).min(1.0))
+    }
+}
+
+/// Fish stocks across the lake, surveyed every frame of a free dive
+#[derive(Resource)]
+pub struct Ecosystem {
+    pub regions: [Region; GRID * GRID],
+    surveyed: bool, // Set once the capacities have been taken from the first school
+    breed_timer: f32,
+}
+
+impl Default for Ecosystem {
+    fn default() -> Self {
+        Self {
+            regions: [Region::default(); GRID * GRID],
+            surveyed: false,
+            breed_timer: BREED_INTERVAL,
+        }
+    }
+}
+
+impl Ecosystem {
+    /// Index of the region a position falls in; beyond the grid it counts
+    /// towards the nearest edge
+    pub fn region_at(position: Vec3) -> usize {
+        let cell = |coordinate: f32| {
+            ((coordinate / REGION_SIZE + GRID as f32 / 2.0).floor() as isize)
+                .clamp(0, GRID as isize - 1) as usize
+        };
+        cell(position.z) * GRID + cell(position.x)
+    }
+
+    /// Centre of a region on the water's surface
+    pub fn region_center(index: usize) -> Vec3 {
+        let offset = |cell: usize| (cell as f32 + 0.5 - GRID as f32 / 2.0) * REGION_SIZE;
+        Vec3::new(offset(index % GRID), 0.0, offset(index / GRID))
+    }
+
+    /// Stock against capacity over the whole lake
+    pub fn overall_health(&self) -> Option<f32> {
+        let capacity: usize = self.regions.iter().map(|region| region.capacity).sum();
+        let stock: usize = self
+            .regions
+            .iter()
+            .filter(|region| region.capacity > 0)
+            .map(|region| region.stock.min(region.capacity))
+            .sum();
+        (capacity > 0).then(|| stock as f32 / capacity as f32)
+    }
+}
+
+/// Random source for breeding, seeded from the world so replays match
+#[derive(Resource)]
+struct EcosystemRng(StdRng);
+
+pub struct EcosystemPlugin;
+
+impl Plugin for EcosystemPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Ecosystem>()
+            .insert_resource(EcosystemRng(StdRng::seed_from_u64(0)))
+            .add_systems(OnEnter(AppState::InGame), reset_ecosystem)
+            .add_systems(
+                Update,
+                (
+                    (survey_system, breed_system)
+                        .chain()
+                        .after(crate::collect_fish)
+                        .in_set(GameSet::Simulation),
+                    ecosystem_chart_system
+                        .in_set(GameSet::Presentation)
+                        .run_if(in_state(CameraMode::Tactical)),
+                )
+                    .run_if(resource_equals(GameMode::Dive)),
+            );
+    }
+}
+
+fn reset_ecosystem(mut commands: Commands, seed: Res<WorldSeed>) {
+    commands.insert_resource(Ecosystem::default());
+    commands.insert_resource(EcosystemRng(StdRng::seed_from_u64(seed.0 ^ 0x4543_4F53)));
+}
+
+/// Counts the fish in each region, takes the first count as what each habitat
+/// can carry, and collapses regions that have been fished out
+fn survey_system(
+    mut ecosystem: ResMut<Ecosystem>,
+    fish_query: Query<&Transform, With<Fish>>,
+    time: Res<Time>,
+) {
+    let mut stock = [0; GRID * GRID];
+    for fish in fish_query.iter() {
+        stock[Ecosystem::region_at(fish.translation)] += 1;
+    }
+    if !ecosystem.surveyed {
+        if stock.iter().all(|count| *count == 0) {
+            return; // The school hasn't been released yet
+        }
+        ecosystem.surveyed = true;
+        for (region, count) in ecosystem.regions.iter_mut().zip(stock) {
+            region.capacity = count;
+        }
+    }
+
+    for (region, count) in ecosystem.regions.iter_mut().zip(stock) {
+        region.stock = count;
+        region.recovering = (region.recovering - time.delta_secs()).max(0.0);
+        let depleted = (count as f32) < region.capacity as f32 * COLLAPSE_SHARE;
+        if depleted && !region.collapsed {
+            region.collapsed = true;
+            region.recovering = RECOVERY_TIME;
+        } else if !depleted {
+            region.collapsed = false;
+        }
+    }
+}
+
+/// Every so often each fish in a healthy region may spawn a young one of its
+/// species nearby, less often the closer the region is to capacity. It takes
+/// two to breed, so a region fished empty waits for fish to wander back in.
+#[allow(clippy::too_many_arguments)]
+fn breed_system(
+    mut commands: Commands,
+    mut ecosystem: ResMut<Ecosystem>,
+    mut rng: ResMut<EcosystemRng>,
+    assets: Res<GameAssets>,
+    tables: Res<Tables>,
+    new_game_plus: Res<NewGamePlus>,
+    fish_query: Query<(&Transform, &Name), With<Fish>>,
+    time: Res<Time>,
+) {
+    ecosystem.breed_timer -= time.delta_secs();
+    if ecosystem.breed_timer > 0.0 {
+        return;
+    }
+    ecosystem.breed_timer += BREED_INTERVAL;
+
+    let mut births = [0; GRID * GRID];
+    for (transform, name) in fish_query.iter() {
+        let index = Ecosystem::region_at(transform.translation);
+        let region = ecosystem.regions[index];
+        if region.recovering > 0.0 || region.stock < 2 {
+            continue;
+        }
+        let room = region.capacity.saturating_sub(region.stock + births[index]);
+        let chance = BREED_RATE * room as f32 / region.capacity.max(1) as f32;
+        if room == 0 || !rng.0.gen_bool(chance as f64) {
+            continue;
+        }
+        let Some(species) = tables
+            .fish
+            .iter()
+            .find(|species| species.name == name.as_str())
+        else {
+            continue;
+        };
+        let offset = Vec3::new(
+            rng.0.gen_range(-1.0..=1.0),
+            rng.0.gen_range(-0.3..=0.3),
+            rng.0.gen_range(-1.0..=1.0),
+        ) * SPAWN_SPREAD;
+        let mut position = transform.translation + offset;
+        let depth = new_game_plus.fish_depth(species);
+        position.y = position.y.clamp(-depth.1, -depth.0);
+        spawn_fish(&mut commands, &assets, &mut rng.0, species, position);
+        births[index] += 1;
+    }
+}
+
+/// Shades each inhabited region on the tactical plot from red when fished
+/// out to green at full stock, with a cross through collapsed ones
+fn ecosystem_chart_system(mut gizmos: Gizmos<TacticalGizmos>, ecosystem: Res<Ecosystem>) {
+    let flat = Quat::from_rotation_x(FRAC_PI_2);
+    let size = Vec2::splat(REGION_SIZE - 4.0);
+    for (index, region) in ecosystem.regions.iter().enumerate() {
+        let Some(health) = region.health() else {
+            continue;
+        };
+        let center = Ecosystem::region_center(index);
+        let color = DEPLETED_COLOR.mix(&HEALTHY_COLOR, health);
+        gizmos.rect(Isometry3d::new(center, flat), size, color);
+        if region.recovering > 0.0 {
+            let half = size.x / 2.0;
+            gizmos.line(
+                center + Vec3::new(-half, 0.0, -half),
+                center + Vec3::new(half, 0.0, half),
+                color,
+            );
+            gizmos.line(
+                center + Vec3::new(-half, 0.0, half),
+                center + Vec3::new(half, 0.0, -half),
+                color,
+            );
+        }
+    }
+}
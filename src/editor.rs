@@ -0,0 +1,597 @@
+//! Scenario editor: a free camera over an empty lake where props, fish regions
+//! and the player start are placed with the mouse, then saved in the scenario
+//! format for `--scenario`.
+
+use crate::props::{Placement, PropLibrary};
+use crate::scenario::{FishRegion, Scenario, ScenarioPath};
+use crate::{AppState, CameraFollow, GameAssets};
+use bevy::input::mouse::{AccumulatedMouseMotion, AccumulatedMouseScroll};
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use rand::{rngs::StdRng, SeedableRng};
+use std::f32::consts::FRAC_PI_2;
+
+const SEA_FLOOR: f32 = -20.5;
+const MAX_START_DEPTH: f32 = 19.0;
+const GRID_SIZE: f32 = 2.0; // Meters between snapped positions
+const CAMERA_SPEED: f32 = 20.0; // m/s, tripled while Shift is held
+const LOOK_SENSITIVITY: f32 = 0.004; // Radians per pixel while the right button is held
+const PICK_RADIUS: f32 = 6.0; // How close to a prop a click has to land
+const UNDO_LIMIT: usize = 100;
+const DEFAULT_PATH: &str = "scenarios/untitled.ron";
+
+/// What a left click does
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Tool {
+    Place,
+    Move,
+    Delete,
+    FishRegion,
+    Start,
+}
+
+impl Tool {
+    fn label(self) -> &'static str {
+        match self {
+            Tool::Place => "Place prop",
+            Tool::Move => "Move prop",
+            Tool::Delete => "Delete prop",
+            Tool::FishRegion => "Paint fish region",
+            Tool::Start => "Set player start",
+        }
+    }
+}
+
+/// The scenario being edited, with undo history and tool settings
+#[derive(Resource)]
+struct EditorScene {
+    scenario: Scenario,
+    path: String,
+    undo: Vec<Scenario>,
+    tool: Tool,
+    prop: usize,             // Index into the library's prop names
+    yaw: f32,                // Degrees, for newly placed props
+    carrying: Option<usize>, // Placement following the cursor in the Move tool
+    region_radius: f32,
+    start_depth: f32,
+    snap: bool,
+    rebuild: bool, // Previews are out of date
+    status: String,
+}
+
+impl EditorScene {
+    /// Remembers the scenario as it is, before an edit
+    fn checkpoint(&mut self) {
+        self.undo.push(self.scenario.clone());
+        if self.undo.len() > UNDO_LIMIT {
+            self.undo.remove(0);
+        }
+    }
+
+    fn snapped(&self, point: Vec3) -> Vec3 {
+        if self.snap {
+            Vec3::new(
+                (point.x / GRID_SIZE).round() * GRID_SIZE,
+                point.y,
+                (point.z / GRID_SIZE).round() * GRID_SIZE,
+            )
+        } else {
+            point
+        }
+    }
+
+    /// Index of the placement closest to `point`, if one is within reach
+    fn placement_at(&self, point: Vec3) -> Option<usize> {
+        nearest(
+            self.scenario
+                .placements
+                .iter()
+                .map(|placement| (placement.translation.0, placement.translation.2)),
+            point,
+            |_| PICK_RADIUS,
+        )
+    }
+
+    fn region_at(&self, point: Vec3) -> Option<usize> {
+        nearest(
+            self.scenario
+                .fish_regions
+                .iter()
+                .map(|region| region.center),
+            point,
+            |index| self.scenario.fish_regions[index].radius,
+        )
+    }
+}
+
+/// Closest of `centers` to `point` on the horizontal plane, within `reach` of it
+fn nearest(
+    centers: impl Iterator<Item = (f32, f32)>,
+    point: Vec3,
+    reach: impl Fn(usize) -> f32,
+) -> Option<usize> {
+    centers
+        .enumerate()
+        .map(|(index, (x, z))| (index, Vec2::new(x, z).distance(point.xz())))
+        .filter(|&(index, distance)| distance <= reach(index))
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(index, _)| index)
+}
+
+/// Free camera orientation
+#[derive(Resource, Default)]
+struct EditorCamera {
+    yaw: f32,
+    pitch: f32,
+}
+
+/// Where the mouse points at the sea floor
+#[derive(Resource, Default)]
+struct EditorCursor(Option<Vec3>);
+
+/// A spawned copy of the placement at this index
+#[derive(Component)]
+struct EditorPreview(usize);
+
+#[derive(Component)]
+struct EditorText;
+
+pub struct EditorPlugin;
+
+impl Plugin for EditorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<EditorCamera>()
+            .init_resource::<EditorCursor>()
+            .add_systems(OnEnter(AppState::Editor), enter_editor)
+            .add_systems(
+                Update,
+                (
+                    editor_camera_system,
+                    editor_cursor_system,
+                    editor_input_system,
+                    editor_preview_system,
+                    (editor_gizmo_system, editor_text_system),
+                )
+                    .chain()
+                    .run_if(in_state(AppState::Editor)),
+            );
+    }
+}
+
+/// Opens the scenario given with `--scenario`, or starts a new one
+fn enter_editor(
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+    scenario: Option<Res<Scenario>>,
+    path: Option<Res<ScenarioPath>>,
+    mut editor_camera: ResMut<EditorCamera>,
+    mut camera_query: Query<&mut Transform, With<CameraFollow>>,
+) {
+    commands.insert_resource(EditorScene {
+        scenario: scenario
+            .map(|scenario| scenario.clone())
+            .unwrap_or_else(|| Scenario::new("Untitled")),
+        path: path
+            .map(|path| path.0.clone())
+            .unwrap_or_else(|| DEFAULT_PATH.to_string()),
+        undo: Vec::new(),
+        tool: Tool::Place,
+        prop: 0,
+        yaw: 0.0,
+        carrying: None,
+        region_radius: 20.0,
+        start_depth: 0.0,
+        snap: false,
+        rebuild: true,
+        status: String::new(),
+    });
+
+    *editor_camera = EditorCamera {
+        yaw: 0.0,
+        pitch: -0.6,
+    };
+    if let Ok(mut transform) = camera_query.single_mut() {
+        *transform = Transform::from_xyz(0.0, 10.0, 50.0).with_rotation(Quat::from_euler(
+            EulerRot::YXZ,
+            0.0,
+            -0.6,
+            0.0,
+        ));
+    }
+
+    commands.insert_resource(AmbientLight {
+        color: Color::srgb(0.3, 0.5, 0.7),
+        brightness: 800.0,
+        affects_lightmapped_meshes: false,
+    });
+    commands.spawn((
+        DirectionalLight {
+            illuminance: 12000.0,
+            color: Color::srgb(0.7, 0.8, 0.9),
+            ..default()
+        },
+        Transform::from_xyz(4.0, 15.0, 4.4).looking_at(Vec3::ZERO, Vec3::Y),
+        StateScoped(AppState::Editor),
+    ));
+    commands.spawn((
+        Mesh3d(assets.seabed_mesh.clone()),
+        MeshMaterial3d(assets.seabed_material.clone()),
+        Transform::from_xyz(0.0, SEA_FLOOR, 0.0),
+        StateScoped(AppState::Editor),
+    ));
+
+    commands.spawn((
+        Text::new(""),
+        TextFont {
+            font_size: 16.0,
+            font: assets.font.clone(),
+            ..default()
+        },
+        TextColor(Color::WHITE),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(16.0),
+            left: Val::Px(16.0),
+            ..default()
+        },
+        EditorText,
+        StateScoped(AppState::Editor),
+    ));
+}
+
+/// WASD flies, Q and E sink and rise, and dragging with the right button looks around
+fn editor_camera_system(
+    time: Res<Time>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mouse_buttons: Res<ButtonInput<MouseButton>>,
+    mouse_motion: Res<AccumulatedMouseMotion>,
+    mut editor_camera: ResMut<EditorCamera>,
+    mut camera_query: Query<&mut Transform, With<CameraFollow>>,
+) {
+    let Ok(mut transform) = camera_query.single_mut() else {
+        return;
+    };
+    if mouse_buttons.pressed(MouseButton::Right) {
+        editor_camera.yaw -= mouse_motion.delta.x * LOOK_SENSITIVITY;
+        editor_camera.pitch = (editor_camera.pitch - mouse_motion.delta.y * LOOK_SENSITIVITY)
+            .clamp(-FRAC_PI_2 + 0.05, FRAC_PI_2 - 0.05);
+    }
+    transform.rotation =
+        Quat::from_euler(EulerRot::YXZ, editor_camera.yaw, editor_camera.pitch, 0.0);
+
+    let forward = Quat::from_rotation_y(editor_camera.yaw) * Vec3::NEG_Z;
+    let right = Quat::from_rotation_y(editor_camera.yaw) * Vec3::X;
+    let mut direction = Vec3::ZERO;
+    for (key, step) in [
+        (KeyCode::KeyW, forward),
+        (KeyCode::KeyS, -forward),
+        (KeyCode::KeyD, right),
+        (KeyCode::KeyA, -right),
+        (KeyCode::KeyE, Vec3::Y),
+        (KeyCode::KeyQ, Vec3::NEG_Y),
+    ] {
+        if keyboard_input.pressed(key) {
+            direction += step;
+        }
+    }
+    let speed = if keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+        CAMERA_SPEED * 3.0
+    } else {
+        CAMERA_SPEED
+    };
+    transform.translation += direction.normalize_or_zero() * speed * time.delta_secs();
+    transform.translation.y = transform.translation.y.max(SEA_FLOOR + 1.0);
+}
+
+fn editor_cursor_system(
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<CameraFollow>>,
+    mut cursor: ResMut<EditorCursor>,
+) {
+    cursor.0 = None;
+    let (Ok(window), Ok((camera, camera_transform))) =
+        (window_query.single(), camera_query.single())
+    else {
+        return;
+    };
+    let Some(ray) = window
+        .cursor_position()
+        .and_then(|position| camera.viewport_to_world(camera_transform, position).ok())
+    else {
+        return;
+    };
+    cursor.0 = ray
+        .intersect_plane(
+            Vec3::new(0.0, SEA_FLOOR, 0.0),
+            InfinitePlane3d::new(Vec3::Y),
+        )
+        .map(|distance| ray.get_point(distance));
+}
+
+/// Tool keys, clicks, undo and save
+fn editor_input_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mouse_buttons: Res<ButtonInput<MouseButton>>,
+    mouse_scroll: Res<AccumulatedMouseScroll>,
+    cursor: Res<EditorCursor>,
+    library: Res<PropLibrary>,
+    mut scene: ResMut<EditorScene>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    let ctrl = keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    let shift = keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    let names = library.names();
+
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        next_state.set(AppState::MainMenu);
+        return;
+    }
+    if ctrl && keyboard_input.just_pressed(KeyCode::KeyZ) {
+        if let Some(previous) = scene.undo.pop() {
+            scene.scenario = previous;
+            scene.carrying = None;
+            scene.rebuild = true;
+            scene.status = "Undone".to_string();
+        }
+        return;
+    }
+    if ctrl && keyboard_input.just_pressed(KeyCode::KeyS) {
+        scene.status = match scene.scenario.save(&scene.path) {
+            Ok(()) => format!("Saved {}", scene.path),
+            Err(err) => format!("Cannot save {}: {}", scene.path, err),
+        };
+        return;
+    }
+
+    for (key, tool) in [
+        (KeyCode::Digit1, Tool::Place),
+        (KeyCode::Digit2, Tool::Move),
+        (KeyCode::Digit3, Tool::Delete),
+        (KeyCode::Digit4, Tool::FishRegion),
+        (KeyCode::Digit5, Tool::Start),
+    ] {
+        if keyboard_input.just_pressed(key) {
+            scene.tool = tool;
+            scene.carrying = None;
+        }
+    }
+    if keyboard_input.just_pressed(KeyCode::KeyG) {
+        scene.snap = !scene.snap;
+    }
+    if keyboard_input.just_pressed(KeyCode::Tab) && !names.is_empty() {
+        scene.prop = if shift {
+            (scene.prop + names.len() - 1) % names.len()
+        } else {
+            (scene.prop + 1) % names.len()
+        };
+    }
+
+    // The wheel adjusts whatever the current tool places
+    let scroll = mouse_scroll.delta.y;
+    if scroll != 0.0 {
+        match (scene.tool, scene.carrying) {
+            (Tool::Place, _) => scene.yaw = (scene.yaw + scroll * 15.0).rem_euclid(360.0),
+            (Tool::Move, Some(index)) => {
+                let placement = &mut scene.scenario.placements[index];
+                placement.yaw = (placement.yaw + scroll * 15.0).rem_euclid(360.0);
+            }
+            (Tool::FishRegion, _) => {
+                scene.region_radius = (scene.region_radius + scroll * 2.0).clamp(4.0, 200.0)
+            }
+            (Tool::Start, _) => {
+                scene.start_depth = (scene.start_depth - scroll).clamp(0.0, MAX_START_DEPTH)
+            }
+            _ => {}
+        }
+    }
+
+    let Some(point) = cursor.0 else {
+        return;
+    };
+    let point = scene.snapped(point);
+
+    // A carried prop follows the cursor; Page Up and Page Down lift and lower it
+    if let Some(index) = scene.carrying {
+        let placement = &mut scene.scenario.placements[index];
+        let mut height = placement.translation.1;
+        if keyboard_input.just_pressed(KeyCode::PageUp) {
+            height += 1.0;
+        }
+        if keyboard_input.just_pressed(KeyCode::PageDown) {
+            height = (height - 1.0).max(SEA_FLOOR);
+        }
+        placement.translation = (point.x, height, point.z);
+    }
+
+    if mouse_buttons.just_pressed(MouseButton::Right) && scene.tool == Tool::FishRegion {
+        if let Some(index) = scene.region_at(point) {
+            scene.checkpoint();
+            scene.scenario.fish_regions.remove(index);
+        }
+    }
+    if !mouse_buttons.just_pressed(MouseButton::Left) {
+        return;
+    }
+    match scene.tool {
+        Tool::Place => {
+            let Some(prop) = names.get(scene.prop) else {
+                return;
+            };
+            let placement = Placement {
+                prop: prop.clone(),
+                translation: (point.x, SEA_FLOOR, point.z),
+                yaw: scene.yaw,
+                scale: (1.0, 1.0, 1.0),
+            };
+            scene.checkpoint();
+            scene.scenario.placements.push(placement);
+            scene.rebuild = true;
+        }
+        Tool::Move => match scene.carrying {
+            Some(_) => scene.carrying = None,
+            None => {
+                if let Some(index) = scene.placement_at(point) {
+                    scene.checkpoint();
+                    scene.carrying = Some(index);
+                }
+            }
+        },
+        Tool::Delete => {
+            if let Some(index) = scene.placement_at(point) {
+                scene.checkpoint();
+                scene.scenario.placements.remove(index);
+                scene.rebuild = true;
+            }
+        }
+        Tool::FishRegion => {
+            let region = FishRegion {
+                center: (point.x, point.z),
+                radius: scene.region_radius,
+            };
+            scene.checkpoint();
+            scene.scenario.fish_regions.push(region);
+        }
+        Tool::Start => {
+            scene.checkpoint();
+            scene.scenario.start = Some((point.x, -scene.start_depth, point.z));
+        }
+    }
+}
+
+/// Respawns the props after placements are added or removed, and keeps a
+/// carried prop's preview under the cursor
+fn editor_preview_system(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut library: ResMut<PropLibrary>,
+    mut scene: ResMut<EditorScene>,
+    mut preview_query: Query<(Entity, &EditorPreview, &mut Transform)>,
+) {
+    if scene.rebuild {
+        scene.rebuild = false;
+        for (entity, _, _) in preview_query.iter() {
+            commands.entity(entity).despawn();
+        }
+        let mut rng = StdRng::seed_from_u64(0);
+        for (index, placement) in scene.scenario.placements.iter().enumerate() {
+            if let Some(entity) = library.spawn(
+                &mut commands,
+                &mut meshes,
+                &mut materials,
+                &mut rng,
+                &placement.prop,
+                placement.transform(),
+            ) {
+                commands
+                    .entity(entity)
+                    .insert((EditorPreview(index), StateScoped(AppState::Editor)));
+            }
+        }
+        return;
+    }
+    let Some(carried) = scene.carrying else {
+        return;
+    };
+    for (_, preview, mut transform) in preview_query.iter_mut() {
+        if preview.0 == carried {
+            *transform = scene.scenario.placements[carried].transform();
+        }
+    }
+}
+
+fn editor_gizmo_system(mut gizmos: Gizmos, scene: Res<EditorScene>, cursor: Res<EditorCursor>) {
+    // Gizmo circles lie in the XY plane; turn them flat onto the sea floor
+    let flat = |center: Vec3| Isometry3d::new(center, Quat::from_rotation_x(FRAC_PI_2));
+    let floor = |x: f32, z: f32| Vec3::new(x, SEA_FLOOR + 0.2, z);
+
+    for region in &scene.scenario.fish_regions {
+        gizmos.circle(
+            flat(floor(region.center.0, region.center.1)),
+            region.radius,
+            Color::srgb(0.3, 0.8, 1.0),
+        );
+    }
+    if let Some((x, y, z)) = scene.scenario.start {
+        gizmos.sphere(
+            Isometry3d::from_translation(Vec3::new(x, y, z)),
+            1.5,
+            Color::srgb(0.3, 1.0, 0.4),
+        );
+        gizmos.line(Vec3::new(x, y, z), floor(x, z), Color::srgb(0.3, 1.0, 0.4));
+    }
+
+    let Some(point) = cursor.0 else {
+        return;
+    };
+    let point = scene.snapped(point);
+    if scene.snap {
+        gizmos.grid(
+            flat(floor(point.x, point.z)),
+            UVec2::splat(16),
+            Vec2::splat(GRID_SIZE),
+            Color::srgba(1.0, 1.0, 1.0, 0.15),
+        );
+    }
+    let (radius, color) = match scene.tool {
+        Tool::FishRegion => (scene.region_radius, Color::srgb(0.3, 0.8, 1.0)),
+        Tool::Delete => (PICK_RADIUS, Color::srgb(1.0, 0.3, 0.3)),
+        Tool::Move => (PICK_RADIUS, Color::srgb(1.0, 0.9, 0.3)),
+        Tool::Place | Tool::Start => (1.0, Color::WHITE),
+    };
+    gizmos.circle(flat(floor(point.x, point.z)), radius, color);
+
+    // Outline the prop a click would pick up or delete
+    if matches!(scene.tool, Tool::Move | Tool::Delete) && scene.carrying.is_none() {
+        if let Some(index) = scene.placement_at(point) {
+            let (x, _, z) = scene.scenario.placements[index].translation;
+            gizmos.circle(flat(floor(x, z)), 2.0, color);
+        }
+    }
+}
+
+fn editor_text_system(
+    scene: Res<EditorScene>,
+    library: Res<PropLibrary>,
+    mut text_query: Query<&mut Text, With<EditorText>>,
+) {
+    let Ok(mut text) = text_query.single_mut() else {
+        return;
+    };
+    let names = library.names();
+    let detail = match scene.tool {
+        Tool::Place => format!(
+            "{}, heading {:.0}°  (Tab: next prop, wheel: turn)",
+            names.get(scene.prop).map_or("no props", String::as_str),
+            scene.yaw
+        ),
+        Tool::Move if scene.carrying.is_some() => {
+            "Click to drop  (wheel: turn, Page Up/Down: height)".to_string()
+        }
+        Tool::Move => "Click a prop to pick it up".to_string(),
+        Tool::Delete => "Click a prop to delete it".to_string(),
+        Tool::FishRegion => format!(
+            "Radius {:.0} m  (wheel: resize, right click: remove)",
+            scene.region_radius
+        ),
+        Tool::Start => format!("Depth {:.0} m  (wheel: change)", scene.start_depth),
+    };
+    let line = format!(
+        "Scenario editor: {}  ·  {}\n\
+         Tool: {}  ·  {}\n\
+         Grid snap {}  ·  {} props, {} fish regions\n\
+         1-5 tools, G snap, Ctrl+Z undo, Ctrl+S save, WASD/Q/E fly, right drag look, Esc menu\n\
+         {}",
+        scene.scenario.name,
+        scene.path,
+        scene.tool.label(),
+        detail,
+        if scene.snap { "on" } else { "off" },
+        scene.scenario.placements.len(),
+        scene.scenario.fish_regions.len(),
+        scene.status
+    );
+    if **text != line {
+        **text = line;
+    }
+}
@@ -0,0 +1,321 @@
+use crate::controls::HelmState;
+use crate::cvars::{CvarDef, Cvars, RegisterCvar};
+use crate::hud::{HudTextPanel, SonarPanel};
+use crate::tables::Tables;
+use crate::{AppState, BallastState, GameSet, Submarine, UnderwaterRock, WorldSeed};
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use rand::{rngs::StdRng, seq::IteratorRandom, Rng, SeedableRng};
+
+const CIRCLE_RADIUS: CvarDef = CvarDef {
+    name: "eels.circle_radius",
+    default: 4.0,
+    range: 0.5..=20.0,
+    description: "Radius eels loop around their rock while lurking",
+};
+
+const SENSE_RANGE: CvarDef = CvarDef {
+    name: "eels.sense_range",
+    default: 20.0,
+    range: 0.0..=100.0,
+    description: "Eels notice a lit submarine this close",
+};
+
+const LATCH_DISTANCE: CvarDef = CvarDef {
+    name: "eels.latch_distance",
+    default: 2.0,
+    range: 0.5..=10.0,
+    description: "Distance at which a hunting eel latches on",
+};
+
+const EEL_SPEED: CvarDef = CvarDef {
+    name: "eels.speed",
+    default: 6.0,
+    range: 0.0..=30.0,
+    description: "Eel swimming speed in m/s",
+};
+
+const EEL_DRAIN: CvarDef = CvarDef {
+    name: "eels.drain",
+    default: 1.5,
+    range: 0.0..=20.0,
+    description: "Electricity drained per second by each attached eel",
+};
+
+const SHAKE_SPEED: CvarDef = CvarDef {
+    name: "eels.shake_speed",
+    default: 9.0,
+    range: 0.0..=30.0,
+    description: "Speed the submarine has to hold to shake eels off",
+};
+
+const SHAKE_TIME: CvarDef = CvarDef {
+    name: "eels.shake_time",
+    default: 1.0,
+    range: 0.0..=10.0,
+    description: "Seconds at shake speed before eels let go",
+};
+
+const FLEE_TIME: CvarDef = CvarDef {
+    name: "eels.flee_time",
+    default: 10.0,
+    range: 0.0..=60.0,
+    description: "Seconds a shaken-off eel stays away",
+};
+
+#[derive(Clone, Copy, PartialEq)]
+enum EelState {
+    Lurking,
+    Hunting,
+    Attached { offset: Vec3 }, // Position on the hull, in submarine space
+    Fleeing { timer: f32 },
+}
+
+#[derive(Component)]
+struct Eel {
+    home: Vec3,
+    phase: f32,
+    state: EelState,
+}
+
+/// Static over the HUD while eels are shorting out the electrics
+#[derive(Component)]
+struct Interference;
+
+/// How long the submarine has been going fast enough to shake eels off
+#[derive(Resource, Default)]
+struct SpeedBurst(f32);
+
+/// An eel's body, made once and shared by every swarm
+#[derive(Resource)]
+struct EelAssets {
+    mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+}
+
+impl FromWorld for EelAssets {
+    fn from_world(world: &mut World) -> Self {
+        let mesh = world
+            .resource_mut::<Assets<Mesh>>()
+            .add(Capsule3d::new(0.15, 2.0));
+        let material = world
+            .resource_mut::<Assets<StandardMaterial>>()
+            .add(StandardMaterial {
+                base_color: Color::srgb(0.3, 0.4, 0.2),
+                emissive: LinearRgba::rgb(0.0, 0.2, 0.6),
+                ..default()
+            });
+        Self { mesh, material }
+    }
+}
+
+pub struct EelPlugin;
+
+impl Plugin for EelPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SpeedBurst>()
+            .register_cvar(&CIRCLE_RADIUS)
+            .register_cvar(&SENSE_RANGE)
+            .register_cvar(&LATCH_DISTANCE)
+            .register_cvar(&EEL_SPEED)
+            .register_cvar(&EEL_DRAIN)
+            .register_cvar(&SHAKE_SPEED)
+            .register_cvar(&SHAKE_TIME)
+            .register_cvar(&FLEE_TIME)
+            .add_systems(OnEnter(AppState::InGame), spawn_eels.after(crate::setup))
+            .add_systems(
+                Update,
+                (
+                    eel_system.in_set(GameSet::Simulation),
+                    interference_system.in_set(GameSet::Presentation),
+                ),
+            );
+    }
+
+    fn finish(&self, app: &mut App) {
+        app.init_resource::<EelAssets>();
+    }
+}
+
+/// Swarms settle around a few of the underwater rocks
+fn spawn_eels(
+    mut commands: Commands,
+    assets: Res<EelAssets>,
+    seed: Res<WorldSeed>,
+    tables: Res<Tables>,
+    rock_query: Query<&Transform, With<UnderwaterRock>>,
+) {
+    commands.insert_resource(SpeedBurst::default());
+    let mut rng = StdRng::seed_from_u64(seed.0 ^ 0x45454C53);
+
+    let rocks = rock_query
+        .iter()
+        .choose_multiple(&mut rng, tables.spawns.eel_swarms);
+    for rock in rocks {
+        let home = rock.translation + Vec3::Y * 2.0;
+        for _ in 0..tables.spawns.eels_per_swarm {
+            commands
+                .spawn((
+                    Transform::from_translation(home),
+                    Visibility::default(),
+                    Eel {
+                        home,
+                        phase: rng.gen::<f32>() * std::f32::consts::TAU,
+                        state: EelState::Lurking,
+                    },
+                    StateScoped(AppState::InGame),
+                ))
+                .with_children(|parent| {
+                    // Body along the local Z axis
+                    parent.spawn((
+                        Mesh3d(assets.mesh.clone()),
+                        MeshMaterial3d(assets.material.clone()),
+                        Transform::from_rotation(Quat::from_rotation_x(
+                            std::f32::consts::FRAC_PI_2,
+                        )),
+                    ));
+                });
+        }
+    }
+
+    commands.spawn((
+        Node {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            ..default()
+        },
+        BackgroundColor(Color::NONE),
+        Pickable::IGNORE,
+        Interference,
+        StateScoped(AppState::InGame),
+    ));
+}
+
+/// Eels are drawn to the floodlights, latch on and drain the batteries until the
+/// submarine sprints away or goes dark
+fn eel_system(
+    time: Res<Time>,
+    mut burst: ResMut<SpeedBurst>,
+    mut ballast_state: ResMut<BallastState>,
+    helm_state: Res<HelmState>,
+    cvars: Res<Cvars>,
+    submarine_query: Query<(&Transform, &Velocity), With<Submarine>>,
+    mut eel_query: Query<(&mut Eel, &mut Transform), Without<Submarine>>,
+) {
+    let Ok((submarine, velocity)) = submarine_query.single() else {
+        return;
+    };
+    let sense_range = cvars.get(&SENSE_RANGE);
+    let delta = time.delta_secs();
+    burst.0 = if velocity.linvel.length() > cvars.get(&SHAKE_SPEED) {
+        burst.0 + delta
+    } else {
+        0.0
+    };
+    let shaken = burst.0 >= cvars.get(&SHAKE_TIME);
+
+    for (mut eel, mut transform) in eel_query.iter_mut() {
+        let position = transform.translation;
+        let distance = position.distance(submarine.translation);
+        let (goal, next_state) = match eel.state {
+            EelState::Lurking => {
+                eel.phase += delta;
+                let circle = Vec3::new(
+                    eel.phase.cos(),
+                    0.3 * (eel.phase * 2.0).sin(),
+                    eel.phase.sin(),
+                );
+                let hunting = helm_state.lights_on && distance < sense_range;
+                (
+                    eel.home + circle * cvars.get(&CIRCLE_RADIUS),
+                    if hunting {
+                        EelState::Hunting
+                    } else {
+                        EelState::Lurking
+                    },
+                )
+            }
+            EelState::Hunting => {
+                let next_state = if !helm_state.lights_on || distance > sense_range * 1.5 {
+                    EelState::Lurking
+                } else if distance < cvars.get(&LATCH_DISTANCE) {
+                    // Cling to the hull surface where the eel touched it
+                    let local = submarine.rotation.inverse() * (position - submarine.translation);
+                    EelState::Attached {
+                        offset: local.normalize_or_zero() * 0.8,
+                    }
+                } else {
+                    EelState::Hunting
+                };
+                (submarine.translation, next_state)
+            }
+            EelState::Attached { offset } => {
+                ballast_state.electricity =
+                    (ballast_state.electricity - cvars.get(&EEL_DRAIN) * delta).max(0.0);
+                transform.translation = submarine.translation + submarine.rotation * offset;
+                transform.rotation = submarine.rotation;
+                if shaken || !helm_state.lights_on {
+                    eel.state = EelState::Fleeing {
+                        timer: cvars.get(&FLEE_TIME),
+                    };
+                }
+                continue;
+            }
+            EelState::Fleeing { timer } => {
+                let timer = timer - delta;
+                (
+                    eel.home,
+                    if timer > 0.0 {
+                        EelState::Fleeing { timer }
+                    } else {
+                        EelState::Lurking
+                    },
+                )
+            }
+        };
+        eel.state = next_state;
+
+        let offset = goal - position;
+        let direction = offset.normalize_or_zero();
+        transform.translation += offset.clamp_length_max(cvars.get(&EEL_SPEED) * delta);
+        if direction != Vec3::ZERO {
+            transform.look_to(direction, Vec3::Y);
+        }
+    }
+}
+
+/// Attached eels make the HUD flicker and fill the screen with static
+fn interference_system(
+    eel_query: Query<&Eel>,
+    mut overlay_query: Query<&mut BackgroundColor, With<Interference>>,
+    mut text_panel_query: Query<&mut Visibility, With<HudTextPanel>>,
+    mut sonar_panel_query: Query<&mut Visibility, (With<SonarPanel>, Without<HudTextPanel>)>,
+) {
+    let attached = eel_query
+        .iter()
+        .filter(|eel| matches!(eel.state, EelState::Attached { .. }))
+        .count();
+    let mut rng = rand::thread_rng();
+    let strength = (attached as f32 * 0.25).min(1.0);
+
+    if let Ok(mut overlay) = overlay_query.single_mut() {
+        let alpha = if attached > 0 {
+            rng.gen::<f32>() * 0.2 * strength
+        } else {
+            0.0
+        };
+        overlay.0 = Color::srgba(0.6, 0.8, 1.0, alpha);
+    }
+    for mut visibility in text_panel_query
+        .iter_mut()
+        .chain(sonar_panel_query.iter_mut())
+    {
+        let flicker = attached > 0 && rng.gen::<f32>() < 0.2 * strength;
+        visibility.set_if_neq(if flicker {
+            Visibility::Hidden
+        } else {
+            Visibility::Inherited
+        });
+    }
+}
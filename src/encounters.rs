@@ -0,0 +1,245 @@
+//! Encounters: now and then rare wildlife passes the boat, a squadron of
+//! manta rays, a pod of dolphins or, very seldom, a blue whale. A director
+//! rolls for one every so often and brings it in off to the side or astern,
+//! outside what the camera shows, swimming across the boat's path. It is
+//! heard calling and shows on the sonar well before it can be seen, so only
+//! a crew keeping watch will turn to look. What can come along, how often
+//! and in what numbers is read from `assets/tables.ron`.
+
+use crate::cvars::{CvarDef, Cvars, RegisterCvar};
+use crate::doppler::Doppler;
+use crate::settings::Settings;
+use crate::sfx::Hum;
+use crate::tables::{EncounterSpecies, Rarity, Tables};
+use crate::{AppState, CameraFollow, GameAssets, GameMode, GameSet, Submarine, WorldSeed};
+use bevy::audio::Volume;
+use bevy::prelude::*;
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use std::f32::consts::PI;
+
+const VIEW_HALF_ANGLE: f32 = 1.0; // Radians either side of the camera's heading kept clear
+const APPROACH_RANGE: (f32, f32) = (30.0, 45.0); // Meters off an encounter starts, inside sonar range
+const SWAY: f32 = 0.4; // Meters each animal rises and falls as it swims
+const CALL_VOLUME: f32 = 0.6;
+const SEA_FLOOR: f32 = -20.5;
+
+const ROLL_INTERVAL: CvarDef = CvarDef {
+    name: "encounters.roll_interval",
+    default: 45.0,
+    range: 1.0..=600.0,
+    description: "Seconds between the encounter director's rolls",
+};
+
+const LEAVE_RANGE: CvarDef = CvarDef {
+    name: "encounters.leave_range",
+    default: 80.0,
+    range: 20.0..=300.0,
+    description: "Meters off a passing encounter is gone",
+};
+
+const MAX_TIME: CvarDef = CvarDef {
+    name: "encounters.max_time",
+    default: 120.0,
+    range: 10.0..=600.0,
+    description: "Seconds before an encounter leaves regardless",
+};
+
+impl Rarity {
+    /// Chance that a roll of the director brings an encounter of this rarity
+    fn chance(self) -> f32 {
+        match self {
+            Rarity::Uncommon => 0.4,
+            Rarity::Rare => 0.15,
+            Rarity::Legendary => 0.03,
+        }
+    }
+}
+
+/// An animal of a passing encounter; the first of each group calls for all of them
+#[derive(Component)]
+pub struct Encounter {
+    pub echo: f32, // Strength of its sonar return
+    velocity: Vec3,
+    phase: f32, // Where in its sway it started
+    age: f32,
+}
+
+/// How long since the director last rolled, and the random source it rolls
+/// with, seeded from the world so replays match
+#[derive(Resource)]
+struct EncounterDirector {
+    rng: StdRng,
+    since_roll: f32,
+}
+
+impl EncounterDirector {
+    fn new(seed: u64) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed ^ 0x454E_434F),
+            since_roll: 0.0,
+        }
+    }
+}
+
+pub struct EncounterPlugin;
+
+impl Plugin for EncounterPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(EncounterDirector::new(0))
+            .register_cvar(&ROLL_INTERVAL)
+            .register_cvar(&LEAVE_RANGE)
+            .register_cvar(&MAX_TIME)
+            .add_systems(OnEnter(AppState::InGame), reset_director)
+            .add_systems(
+                Update,
+                (
+                    director_system.run_if(|mode: Res<GameMode>| *mode != GameMode::TimeTrial),
+                    encounter_motion_system,
+                )
+                    .chain()
+                    .in_set(GameSet::Simulation),
+            );
+    }
+}
+
+fn reset_director(mut commands: Commands, seed: Res<WorldSeed>) {
+    commands.insert_resource(EncounterDirector::new(seed.0));
+}
+
+/// Rolls for an encounter every so often while none is passing, trying the
+/// rarest first
+#[allow(clippy::too_many_arguments)]
+fn director_system(
+    mut commands: Commands,
+    mut director: ResMut<EncounterDirector>,
+    assets: Res<GameAssets>,
+    mut hums: ResMut<Assets<Hum>>,
+    tables: Res<Tables>,
+    settings: Res<Settings>,
+    submarine_query: Query<&Transform, With<Submarine>>,
+    camera_query: Query<&Transform, (With<CameraFollow>, Without<Submarine>)>,
+    encounter_query: Query<(), With<Encounter>>,
+    cvars: Res<Cvars>,
+    time: Res<Time>,
+) {
+    if !encounter_query.is_empty() {
+        return;
+    }
+    director.since_roll += time.delta_secs();
+    if director.since_roll < cvars.get(&ROLL_INTERVAL) {
+        return;
+    }
+    director.since_roll = 0.0;
+    let (Ok(submarine), Ok(camera)) = (submarine_query.single(), camera_query.single()) else {
+        return;
+    };
+
+    let rng = &mut director.rng;
+    let mut picked = None;
+    for rarity in [Rarity::Legendary, Rarity::Rare, Rarity::Uncommon] {
+        if rng.gen::<f32>() >= rarity.chance() {
+            continue;
+        }
+        let candidates: Vec<&EncounterSpecies> = tables
+            .encounters
+            .iter()
+            .filter(|species| species.rarity == rarity)
+            .collect();
+        if let Ok(species) = candidates.choose_weighted(rng, |species| species.weight) {
+            picked = Some(*species);
+            break;
+        }
+    }
+    let Some(species) = picked else {
+        return;
+    };
+
+    // Off to one side of the camera's view or behind it, never in front
+    let view = camera.forward();
+    let view_yaw = (-view.x).atan2(-view.z);
+    let off_view = rng.gen_range(VIEW_HALF_ANGLE..PI) * if rng.gen() { 1.0 } else { -1.0 };
+    let bearing = Quat::from_rotation_y(view_yaw + off_view) * Vec3::NEG_Z;
+    let range = rng.gen_range(APPROACH_RANGE.0..APPROACH_RANGE.1);
+    let depth = rng.gen_range(species.depth.0..=species.depth.1);
+    let center = Vec3::new(
+        submarine.translation.x + bearing.x * range,
+        (-depth).max(SEA_FLOOR + species.size.1),
+        submarine.translation.z + bearing.z * range,
+    );
+    // Crossing the boat's path, a little towards it so it passes close by
+    let across = bearing.cross(Vec3::Y) * if rng.gen() { 1.0 } else { -1.0 };
+    let heading = (across - bearing * 0.3).normalize();
+    let rotation = Transform::default().looking_to(heading, Vec3::Y).rotation;
+
+    info!("{} passing", species.name);
+    let Some(material) = assets.encounters.get(&species.name) else {
+        warn!("No assets for encounter species {:?}", species.name);
+        return;
+    };
+    let count = rng.gen_range(species.group.0..=species.group.1);
+    for index in 0..count {
+        let offset = if index == 0 {
+            Vec3::ZERO
+        } else {
+            Vec3::new(
+                rng.gen_range(-1.0..1.0),
+                rng.gen_range(-0.3..0.3),
+                rng.gen_range(-1.0..1.0),
+            ) * species.spread
+        };
+        let mut animal = commands.spawn((
+            Mesh3d(assets.encounter_mesh.clone()),
+            MeshMaterial3d(material.clone()),
+            Transform::from_translation(center + rotation * offset)
+                .with_rotation(rotation)
+                .with_scale(Vec3::new(species.size.0, species.size.1, species.size.2)),
+            Encounter {
+                echo: species.echo,
+                velocity: heading * species.speed,
+                phase: rng.gen::<f32>() * std::f32::consts::TAU,
+                age: 0.0,
+            },
+            StateScoped(AppState::InGame),
+        ));
+        if let (0, Some(call)) = (index, species.call) {
+            animal.insert((
+                AudioPlayer(hums.add(Hum {
+                    frequency: call,
+                    volume: CALL_VOLUME,
+                })),
+                PlaybackSettings::LOOP
+                    .with_spatial(true)
+                    .with_volume(Volume::Linear(settings.audio.effects_volume)),
+                Doppler::default(),
+            ));
+        }
+    }
+}
+
+/// Swims each animal on across the boat's path, swaying as it goes, until the
+/// encounter has passed well clear or outstayed its time
+fn encounter_motion_system(
+    mut commands: Commands,
+    mut encounter_query: Query<(Entity, &mut Encounter, &mut Transform)>,
+    submarine_query: Query<&Transform, (With<Submarine>, Without<Encounter>)>,
+    cvars: Res<Cvars>,
+    time: Res<Time>,
+) {
+    let delta = time.delta_secs();
+    let submarine = submarine_query.single().ok().map(|t| t.translation);
+    let (leave_range, max_time) = (cvars.get(&LEAVE_RANGE), cvars.get(&MAX_TIME));
+    for (entity, mut encounter, mut transform) in encounter_query.iter_mut() {
+        let sway = |age: f32| SWAY * (age * 0.8 + encounter.phase).sin();
+        let rise = sway(encounter.age + delta) - sway(encounter.age);
+        transform.translation += encounter.velocity * delta + Vec3::Y * rise;
+        encounter.age += delta;
+
+        let passed = submarine.is_some_and(|submarine| {
+            let offset = transform.translation - submarine;
+            offset.length() > leave_range && offset.dot(encounter.velocity) > 0.0
+        });
+        if passed || encounter.age > max_time {
+            commands.entity(entity).despawn();
+        }
+    }
+}
@@ -0,0 +1,360 @@
+//! Enemy submarines: hunter boats that patrol stations out in the bay,
+//! listening for the player. A boat that runs loud is heard from far off and
+//! chased; once close the hunter runs in to ram, then sheers off to come
+//! round again. Out of earshot for long enough it gives up and returns to its
+//! patrol. Hunters show on the sonar, and torpedoes sink them.
+
+use crate::biophony::SIGHT_RANGE;
+use crate::combat::HostileAttack;
+use crate::cvars::{CvarDef, Cvars, RegisterCvar};
+use crate::difficulty::Difficulty;
+use crate::stealth::SubmarineNoise;
+use crate::{AppState, GameMode, GameSet, Submarine, WorldSeed};
+use bevy::prelude::*;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+const ENEMY_COUNT: usize = 2;
+pub const ENEMY_ECHO: f32 = 1.5; // Sonar return strength; a steel hull echoes louder than fish
+const PATROL_RADIUS: f32 = 30.0; // Meters from its station a hunter wanders
+const WAYPOINT_REACHED: f32 = 3.0;
+const MIN_DEPTH: f32 = 3.0;
+const MAX_DEPTH: f32 = 17.0; // Clear of the sea floor
+
+const ENEMY_HEALTH: CvarDef = CvarDef {
+    name: "enemy_sub.health",
+    default: 100.0,
+    range: 1.0..=1000.0,
+    description: "Hunter hull strength; a torpedo hit takes 50",
+};
+
+const HEARING_RANGE: CvarDef = CvarDef {
+    name: "enemy_sub.hearing_range",
+    default: 70.0,
+    range: 0.0..=300.0,
+    description: "Meters a hunter hears the boat at its loudest",
+};
+
+const LOST_CONTACT_TIME: CvarDef = CvarDef {
+    name: "enemy_sub.lost_contact_time",
+    default: 10.0,
+    range: 0.0..=120.0,
+    description: "Seconds unheard before a hunter gives up the chase",
+};
+
+const ATTACK_RANGE: CvarDef = CvarDef {
+    name: "enemy_sub.attack_range",
+    default: 15.0,
+    range: 0.0..=100.0,
+    description: "A hunter this close runs in to ram",
+};
+
+const BREAK_OFF_RANGE: CvarDef = CvarDef {
+    name: "enemy_sub.break_off_range",
+    default: 25.0,
+    range: 0.0..=150.0,
+    description: "An attack that opens out past this goes back to the chase",
+};
+
+const RAM_DISTANCE: CvarDef = CvarDef {
+    name: "enemy_sub.ram_distance",
+    default: 3.5,
+    range: 0.5..=20.0,
+    description: "Hull to hull distance at which a hunter rams",
+};
+
+const RAM_DAMAGE: CvarDef = CvarDef {
+    name: "enemy_sub.ram_damage",
+    default: 15.0,
+    range: 0.0..=100.0,
+    description: "Hull percent lost to a ram, before difficulty",
+};
+
+const SHEER_OFF_TIME: CvarDef = CvarDef {
+    name: "enemy_sub.sheer_off_time",
+    default: 5.0,
+    range: 0.0..=60.0,
+    description: "Seconds a hunter runs clear after ramming",
+};
+
+const PATROL_SPEED: CvarDef = CvarDef {
+    name: "enemy_sub.patrol_speed",
+    default: 2.5,
+    range: 0.0..=20.0,
+    description: "Hunter patrol speed in m/s",
+};
+
+const CHASE_SPEED: CvarDef = CvarDef {
+    name: "enemy_sub.chase_speed",
+    default: 5.0,
+    range: 0.0..=20.0,
+    description: "Hunter chase speed in m/s",
+};
+
+const ATTACK_SPEED: CvarDef = CvarDef {
+    name: "enemy_sub.attack_speed",
+    default: 7.0,
+    range: 0.0..=30.0,
+    description: "Hunter ramming speed in m/s",
+};
+
+const TURN_RATE: CvarDef = CvarDef {
+    name: "enemy_sub.turn_rate",
+    default: 0.8,
+    range: 0.0..=10.0,
+    description: "Share of the way round to its new heading a hunter turns per second",
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum EnemyState {
+    Patrol, // Wandering its station
+    Chase,  // Heading for where it last heard the player
+    Attack, // Running in to ram
+}
+
+/// A hunter boat; its hull lies along the local Z axis like the player's
+#[derive(Component)]
+pub struct EnemySubmarine {
+    pub state: EnemyState,
+    pub health: f32,
+    station: Vec3,
+    waypoint: Vec3,
+    last_heard: Vec3, // Where the player was when last heard
+    unheard: f32,     // Seconds since the player was last heard
+    sheer_off: f32,   // Seconds left running clear after a ram
+}
+
+/// Random source for hunter patrols, seeded from the world so replays match
+#[derive(Resource)]
+struct EnemyRng(StdRng);
+
+/// A hunter's hull and sail, made once and shared by every hunter
+#[derive(Resource)]
+struct HunterAssets {
+    hull_mesh: Handle<Mesh>,
+    sail_mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+}
+
+impl FromWorld for HunterAssets {
+    fn from_world(world: &mut World) -> Self {
+        let mut meshes = world.resource_mut::<Assets<Mesh>>();
+        let hull_mesh = meshes.add(Capsule3d::new(0.7, 4.0));
+        let sail_mesh = meshes.add(Cuboid::new(0.4, 0.9, 1.2));
+        let material = world
+            .resource_mut::<Assets<StandardMaterial>>()
+            .add(StandardMaterial {
+                base_color: Color::srgb(0.12, 0.13, 0.15),
+                perceptual_roughness: 0.5,
+                metallic: 0.6,
+                ..default()
+            });
+        Self {
+            hull_mesh,
+            sail_mesh,
+            material,
+        }
+    }
+}
+
+pub struct EnemySubmarinePlugin;
+
+impl Plugin for EnemySubmarinePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(EnemyRng(StdRng::seed_from_u64(0)))
+            .register_cvar(&ENEMY_HEALTH)
+            .register_cvar(&HEARING_RANGE)
+            .register_cvar(&LOST_CONTACT_TIME)
+            .register_cvar(&ATTACK_RANGE)
+            .register_cvar(&BREAK_OFF_RANGE)
+            .register_cvar(&RAM_DISTANCE)
+            .register_cvar(&RAM_DAMAGE)
+            .register_cvar(&SHEER_OFF_TIME)
+            .register_cvar(&PATROL_SPEED)
+            .register_cvar(&CHASE_SPEED)
+            .register_cvar(&ATTACK_SPEED)
+            .register_cvar(&TURN_RATE)
+            .add_systems(
+                OnEnter(AppState::InGame),
+                (
+                    reset_enemy_rng,
+                    spawn_enemy_submarines
+                        .after(reset_enemy_rng)
+                        .run_if(|mode: Res<GameMode>| *mode != GameMode::TimeTrial),
+                ),
+            )
+            .add_systems(
+                Update,
+                (enemy_sonar_system, enemy_motion_system)
+                    .chain()
+                    .in_set(GameSet::Simulation),
+            );
+    }
+
+    fn finish(&self, app: &mut App) {
+        app.init_resource::<HunterAssets>();
+    }
+}
+
+fn reset_enemy_rng(mut commands: Commands, seed: Res<WorldSeed>) {
+    commands.insert_resource(EnemyRng(StdRng::seed_from_u64(seed.0 ^ 0x454E_454D)));
+}
+
+/// A point on a hunter's patrol around `station`, at a depth it can run at
+fn patrol_point(rng: &mut StdRng, station: Vec3) -> Vec3 {
+    let angle = rng.gen::<f32>() * std::f32::consts::TAU;
+    let radius = rng.gen::<f32>() * PATROL_RADIUS;
+    Vec3::new(
+        station.x + angle.cos() * radius,
+        -rng.gen_range(MIN_DEPTH..MAX_DEPTH),
+        station.z + angle.sin() * radius,
+    )
+}
+
+/// Hunters stay out of the time trial along with the krakens
+fn spawn_enemy_submarines(
+    mut commands: Commands,
+    assets: Res<HunterAssets>,
+    cvars: Res<Cvars>,
+    seed: Res<WorldSeed>,
+) {
+    // A separate stream from the patrols, so a hunter's station doesn't
+    // depend on how the last dive went
+    let mut rng = StdRng::seed_from_u64(seed.0 ^ 0x4855_4E54);
+
+    for _ in 0..ENEMY_COUNT {
+        let angle = rng.gen::<f32>() * std::f32::consts::TAU;
+        let radius = 100.0 + rng.gen::<f32>() * 150.0;
+        let station = Vec3::new(angle.cos() * radius, 0.0, angle.sin() * radius);
+        let position = patrol_point(&mut rng, station);
+        commands
+            .spawn((
+                Transform::from_translation(position),
+                Visibility::default(),
+                EnemySubmarine {
+                    state: EnemyState::Patrol,
+                    health: cvars.get(&ENEMY_HEALTH),
+                    station,
+                    waypoint: patrol_point(&mut rng, station),
+                    last_heard: position,
+                    unheard: 0.0,
+                    sheer_off: 0.0,
+                },
+                StateScoped(AppState::InGame),
+            ))
+            .with_children(|parent| {
+                parent.spawn((
+                    Mesh3d(assets.hull_mesh.clone()),
+                    MeshMaterial3d(assets.material.clone()),
+                    Transform::from_rotation(Quat::from_rotation_x(std::f32::consts::FRAC_PI_2)),
+                ));
+                parent.spawn((
+                    Mesh3d(assets.sail_mesh.clone()),
+                    MeshMaterial3d(assets.material.clone()),
+                    Transform::from_xyz(0.0, 0.9, -0.6),
+                ));
+            });
+    }
+}
+
+/// Hunters listen for the player: the louder the boat runs the further off it
+/// is heard, and it is always noticed close alongside. Hearing it starts a
+/// chase, closing on it starts an attack, and losing it for long enough sends
+/// the hunter back to its patrol
+fn enemy_sonar_system(
+    mut enemy_query: Query<(&mut EnemySubmarine, &Transform)>,
+    submarine_query: Query<&Transform, (With<Submarine>, Without<EnemySubmarine>)>,
+    noise: Res<SubmarineNoise>,
+    cvars: Res<Cvars>,
+    time: Res<Time>,
+) {
+    let player = submarine_query.single().ok().map(|t| t.translation);
+    let hearing = (cvars.get(&HEARING_RANGE) * noise.0).max(SIGHT_RANGE);
+    let attack_range = cvars.get(&ATTACK_RANGE);
+    let lost_contact_time = cvars.get(&LOST_CONTACT_TIME);
+    let break_off_range = cvars.get(&BREAK_OFF_RANGE);
+    for (mut enemy, transform) in enemy_query.iter_mut() {
+        let heard = player.filter(|player| player.distance(transform.translation) < hearing);
+        enemy.sheer_off = (enemy.sheer_off - time.delta_secs()).max(0.0);
+        match heard {
+            Some(player) => {
+                enemy.last_heard = player;
+                enemy.unheard = 0.0;
+            }
+            None => enemy.unheard += time.delta_secs(),
+        }
+        let range = heard.map(|player| player.distance(transform.translation));
+        let state = match (enemy.state, range) {
+            (EnemyState::Patrol, Some(_)) => EnemyState::Chase,
+            (EnemyState::Chase, Some(range)) if range < attack_range && enemy.sheer_off <= 0.0 => {
+                EnemyState::Attack
+            }
+            (EnemyState::Chase, None) if enemy.unheard > lost_contact_time => EnemyState::Patrol,
+            (EnemyState::Attack, None) => EnemyState::Chase,
+            (EnemyState::Attack, Some(range)) if range > break_off_range => EnemyState::Chase,
+            (state, _) => state,
+        };
+        if state != enemy.state {
+            debug!("Enemy submarine {:?} -> {:?}", enemy.state, state);
+            enemy.state = state;
+        }
+    }
+}
+
+/// The player's submarine, as the hunters see it
+type PlayerQuery<'w, 's> =
+    Query<'w, 's, (Entity, &'static Transform), (With<Submarine>, Without<EnemySubmarine>)>;
+
+/// Steers each hunter towards its goal for the state it is in, turning no
+/// faster than a boat can. A hunter that reaches the player rams it and then
+/// runs clear before it comes round again
+fn enemy_motion_system(
+    mut attacks: EventWriter<HostileAttack>,
+    mut rng: ResMut<EnemyRng>,
+    mut enemy_query: Query<(&mut EnemySubmarine, &mut Transform)>,
+    submarine_query: PlayerQuery,
+    difficulty: Res<Difficulty>,
+    cvars: Res<Cvars>,
+    time: Res<Time>,
+) {
+    let player = submarine_query.single().ok();
+    let delta = time.delta_secs();
+    let (patrol_speed, chase_speed) = (cvars.get(&PATROL_SPEED), cvars.get(&CHASE_SPEED));
+    for (mut enemy, mut transform) in enemy_query.iter_mut() {
+        let position = transform.translation;
+        let (goal, speed) = match (enemy.state, player) {
+            (EnemyState::Attack, Some((submarine, player))) => {
+                if player.translation.distance(position) < cvars.get(&RAM_DISTANCE) {
+                    attacks.write(HostileAttack {
+                        prey: submarine,
+                        damage: cvars.get(&RAM_DAMAGE) * difficulty.aggression(),
+                        position,
+                    });
+                    enemy.sheer_off = cvars.get(&SHEER_OFF_TIME);
+                    enemy.state = EnemyState::Chase;
+                }
+                (player.translation, cvars.get(&ATTACK_SPEED))
+            }
+            (EnemyState::Chase, Some((_, player))) if enemy.sheer_off > 0.0 => {
+                (position + (position - player.translation), chase_speed)
+            }
+            (EnemyState::Patrol, _) => {
+                if enemy.waypoint.distance(position) < WAYPOINT_REACHED {
+                    enemy.waypoint = patrol_point(&mut rng.0, enemy.station);
+                }
+                (enemy.waypoint, patrol_speed)
+            }
+            _ => (enemy.last_heard, chase_speed),
+        };
+
+        let direction = (goal - position).normalize_or_zero();
+        if direction != Vec3::ZERO {
+            let heading = Transform::default().looking_to(direction, Vec3::Y).rotation;
+            transform.rotation = transform
+                .rotation
+                .slerp(heading, (cvars.get(&TURN_RATE) * delta).min(1.0));
+        }
+        let forward = *transform.forward();
+        transform.translation += forward * speed * difficulty.aggression() * delta;
+        transform.translation.y = transform.translation.y.clamp(-MAX_DEPTH, -MIN_DEPTH);
+    }
+}
@@ -0,0 +1,236 @@
+//! The submarine's own engine noise: a diesel rumble on the surface and an
+//! electric whine below it, spinning up and down with the ordered speed and
+//! labouring under electrical load, with a crackle of cavitation when the
+//! screw is driven hard near the surface.
+
+use crate::controls::{HelmInput, HelmState};
+use crate::settings::Settings;
+use crate::{AppState, BallastState, GameSet, PauseState, Submarine};
+use bevy::audio::{AddAudioSource, Decodable, Source, Volume};
+use bevy::prelude::*;
+use std::f32::consts::TAU;
+use std::time::Duration;
+
+const SAMPLE_RATE: u32 = 44_100;
+const SURFACE_DEPTH: f32 = 1.0; // The diesels run while the hull is shallower than this
+const CAVITATION_THROTTLE: f32 = 0.5; // Throttle above which the screw starts to cavitate
+const CAVITATION_DEPTH: f32 = 15.0; // Water pressure suppresses cavitation below this depth
+const SPIN_RATE: f32 = 0.8; // Change in engine speed per second
+const FADE_RATE: f32 = 1.5; // Layer gain change per second
+
+/// One layer of engine noise
+#[derive(Component, Clone, Copy, PartialEq, Debug)]
+enum EngineLayer {
+    Diesel,
+    Electric,
+    Cavitation,
+}
+
+impl EngineLayer {
+    const ALL: [EngineLayer; 3] = [
+        EngineLayer::Diesel,
+        EngineLayer::Electric,
+        EngineLayer::Cavitation,
+    ];
+}
+
+/// Endless synthesized audio for one layer; every frequency is a whole number
+/// of Hz so the loop repeats each second without a click
+#[derive(Asset, TypePath, Clone, Copy)]
+struct EngineLoop(EngineLayer);
+
+struct EngineDecoder {
+    layer: EngineLayer,
+    sample: u32,
+    noise: u32, // Xorshift state
+}
+
+impl EngineDecoder {
+    fn white_noise(&mut self) -> f32 {
+        self.noise ^= self.noise << 13;
+        self.noise ^= self.noise >> 17;
+        self.noise ^= self.noise << 5;
+        self.noise as f32 / u32::MAX as f32 * 2.0 - 1.0
+    }
+}
+
+impl Iterator for EngineDecoder {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let t = self.sample as f32 / SAMPLE_RATE as f32;
+        self.sample = (self.sample + 1) % SAMPLE_RATE;
+        let value = match self.layer {
+            EngineLayer::Diesel => {
+                // Cylinder firings at 8 Hz knocking a rough low fundamental
+                let firing = (t * 8.0).fract();
+                let knock = (-firing * 12.0).exp();
+                let body = (TAU * 32.0 * t).sin() + 0.5 * (TAU * 64.0 * t).sin();
+                (0.4 + 0.6 * knock) * body / 1.5 + 0.1 * knock * self.white_noise()
+            }
+            EngineLayer::Electric => {
+                // Motor whine with a faint high gear tone
+                ((TAU * 120.0 * t).sin()
+                    + 0.4 * (TAU * 240.0 * t).sin()
+                    + 0.15 * (TAU * 960.0 * t).sin())
+                    / 1.55
+            }
+            EngineLayer::Cavitation => {
+                // Collapsing bubbles: sparse bursts of hiss
+                let noise = self.white_noise();
+                if self.white_noise() > 0.7 {
+                    noise
+                } else {
+                    0.2 * noise
+                }
+            }
+        };
+        Some(value.clamp(-1.0, 1.0))
+    }
+}
+
+impl Source for EngineDecoder {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+impl Decodable for EngineLoop {
+    type DecoderItem = f32;
+    type Decoder = EngineDecoder;
+
+    fn decoder(&self) -> EngineDecoder {
+        EngineDecoder {
+            layer: self.0,
+            sample: 0,
+            noise: 0x2545_F491,
+        }
+    }
+}
+
+/// Engine speed and layer gains, eased towards what the helm and load call for
+#[derive(Resource, Default)]
+struct EngineMixer {
+    revs: f32,       // 0..1, follows the ordered throttle
+    gains: [f32; 3], // In the order of EngineLayer::ALL
+}
+
+pub struct EngineSoundPlugin;
+
+impl Plugin for EngineSoundPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_audio_source::<EngineLoop>()
+            .init_resource::<EngineMixer>()
+            .add_systems(OnEnter(AppState::InGame), start_engine_sound)
+            .add_systems(OnEnter(PauseState::Paused), pause_engine_sound)
+            .add_systems(OnExit(PauseState::Paused), resume_engine_sound)
+            .add_systems(Update, engine_sound_system.in_set(GameSet::Presentation));
+    }
+}
+
+fn start_engine_sound(mut commands: Commands, mut sources: ResMut<Assets<EngineLoop>>) {
+    commands.insert_resource(EngineMixer::default());
+    for layer in EngineLayer::ALL {
+        commands.spawn((
+            AudioPlayer(sources.add(EngineLoop(layer))),
+            PlaybackSettings::LOOP.with_volume(Volume::Linear(0.0)),
+            layer,
+            StateScoped(AppState::InGame),
+        ));
+    }
+}
+
+fn pause_engine_sound(sink_query: Query<&AudioSink, With<EngineLayer>>) {
+    for sink in sink_query.iter() {
+        sink.pause();
+    }
+}
+
+fn resume_engine_sound(sink_query: Query<&AudioSink, With<EngineLayer>>) {
+    for sink in sink_query.iter() {
+        sink.play();
+    }
+}
+
+/// Sets pitch from the engine speed and loudness from speed, electrical load
+/// and cavitation
+#[allow(clippy::too_many_arguments)]
+fn engine_sound_system(
+    mut mixer: ResMut<EngineMixer>,
+    helm_input: Res<HelmInput>,
+    helm_state: Res<HelmState>,
+    ballast_state: Res<BallastState>,
+    settings: Res<Settings>,
+    submarine_query: Query<&Transform, With<Submarine>>,
+    mut sink_query: Query<(&EngineLayer, &mut AudioSink)>,
+    time: Res<Time>,
+) {
+    let Ok(submarine) = submarine_query.single() else {
+        return;
+    };
+    let delta = time.delta_secs();
+
+    // W/S override the telegraph, as they do for the helm
+    let throttle = if helm_input.throttle != 0.0 {
+        helm_input.throttle
+    } else {
+        helm_state.telegraph.throttle()
+    }
+    .abs();
+    let step = SPIN_RATE * delta;
+    mixer.revs += (throttle - mixer.revs).clamp(-step, step);
+    let revs = mixer.revs;
+
+    // The compressor and floodlights draw on the same batteries as the motor
+    let mut load = 0.0;
+    if ballast_state.compressor_on {
+        load += 0.6;
+    }
+    if helm_state.lights_on {
+        load += 0.3;
+    }
+    let depth = -submarine.translation.y;
+    let surfaced = depth < SURFACE_DEPTH;
+    let cavitation = ((revs - CAVITATION_THROTTLE) / (1.0 - CAVITATION_THROTTLE)).max(0.0)
+        * (1.0 - depth / CAVITATION_DEPTH).clamp(0.0, 1.0);
+
+    let running = 0.15 + 0.45 * revs;
+    let targets = [
+        if surfaced { running + 0.1 * load } else { 0.0 },
+        if surfaced { 0.0 } else { running + 0.2 * load },
+        0.5 * cavitation,
+    ];
+    let fade = FADE_RATE * delta;
+    for (gain, target) in mixer.gains.iter_mut().zip(targets) {
+        *gain += (target - *gain).clamp(-fade, fade);
+    }
+
+    for (layer, mut sink) in sink_query.iter_mut() {
+        let index = EngineLayer::ALL
+            .iter()
+            .position(|l| l == layer)
+            .unwrap_or(0);
+        // A loaded motor turns a little slower for the same order
+        let speed = match layer {
+            EngineLayer::Diesel => 0.7 + 0.6 * revs,
+            EngineLayer::Electric => (0.5 + 1.0 * revs) * (1.0 - 0.1 * load),
+            EngineLayer::Cavitation => 1.0,
+        };
+        sink.set_speed(speed);
+        sink.set_volume(Volume::Linear(
+            mixer.gains[index] * settings.audio.effects_volume,
+        ));
+    }
+}
@@ -0,0 +1,299 @@
+//! Event log: a history of the dive, kept so nothing is lost once a caption
+//! fades or a crew report finishes playing. Catches, crew reports of damage,
+//! contacts and alarms, radio traffic from the mission, periscope ranges and
+//! the ends of missions are each stamped with the sea clock time. The log key
+//! (Tab by default) shows it, and the mouse wheel scrolls back through it.
+
+use crate::callouts::{CalloutKind, CrewCallout};
+use crate::controls::{Action, InputMap};
+use crate::migration::SeaClock;
+use crate::scoring::FishCollected;
+use crate::scripting::MissionEnded;
+use crate::{AppState, GameAssets, GameSet};
+use bevy::input::mouse::{MouseScrollUnit, MouseWheel};
+use bevy::prelude::*;
+
+const MAX_ENTRIES: usize = 200; // Oldest entries are dropped past this
+const PANEL_WIDTH: f32 = 440.0;
+const LIST_HEIGHT: f32 = 260.0;
+const SCROLL_LINE: f32 = 20.0; // Pixels scrolled for each line of the mouse wheel
+
+/// What a log entry is about, which sets its colour
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LogKind {
+    Catch,
+    Damage,
+    Contact,
+    Radio,
+    Alarm,
+    Crew,
+    Mission,
+}
+
+impl LogKind {
+    fn color(self) -> Color {
+        match self {
+            LogKind::Catch => Color::srgb(0.6, 1.0, 0.6),
+            LogKind::Damage => Color::srgb(1.0, 0.5, 0.4),
+            LogKind::Contact => Color::srgb(0.5, 0.9, 1.0),
+            LogKind::Radio => Color::srgb(1.0, 0.9, 0.5),
+            LogKind::Alarm => Color::srgb(1.0, 0.7, 0.2),
+            LogKind::Crew => Color::srgb(0.85, 0.85, 0.85),
+            LogKind::Mission => Color::srgb(0.9, 0.7, 1.0),
+        }
+    }
+}
+
+/// A line for the event log, for events the log does not already hear of
+#[derive(Event, Clone)]
+pub struct LogMessage {
+    pub kind: LogKind,
+    pub text: String,
+}
+
+pub struct LogEntry {
+    pub time: String, // Sea clock time, as HH:MM
+    pub kind: LogKind,
+    pub text: String,
+}
+
+/// Everything logged this dive, oldest first
+#[derive(Resource, Default)]
+pub struct EventLog {
+    pub entries: Vec<LogEntry>,
+}
+
+/// Whether the log is shown, and whether it keeps the newest entry in view
+#[derive(Resource, Default)]
+struct EventLogView {
+    open: bool,
+    following: bool,
+}
+
+#[derive(Component)]
+struct EventLogPanel;
+
+#[derive(Component)]
+struct EventLogList;
+
+pub struct EventLogPlugin;
+
+impl Plugin for EventLogPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<LogMessage>()
+            .init_resource::<EventLog>()
+            .init_resource::<EventLogView>()
+            .add_systems(
+                OnEnter(AppState::InGame),
+                (reset_event_log, spawn_event_log),
+            )
+            .add_systems(
+                Update,
+                (
+                    (toggle_event_log_system, scroll_event_log_system)
+                        .chain()
+                        .in_set(GameSet::Input),
+                    (record_events_system, event_log_ui_system)
+                        .chain()
+                        .in_set(GameSet::Presentation),
+                ),
+            );
+    }
+}
+
+fn reset_event_log(mut commands: Commands) {
+    commands.insert_resource(EventLog::default());
+    commands.insert_resource(EventLogView::default());
+}
+
+fn spawn_event_log(mut commands: Commands, assets: Res<GameAssets>) {
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                top: Val::Px(36.0),
+                width: Val::Percent(100.0),
+                justify_content: JustifyContent::Center,
+                display: Display::None,
+                ..default()
+            },
+            EventLogPanel,
+            StateScoped(AppState::InGame),
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn((
+                    Node {
+                        width: Val::Px(PANEL_WIDTH),
+                        flex_direction: FlexDirection::Column,
+                        padding: UiRect::all(Val::Px(8.0)),
+                        row_gap: Val::Px(6.0),
+                        ..default()
+                    },
+                    BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.75)),
+                ))
+                .with_children(|panel| {
+                    panel.spawn((
+                        Text::new("Event Log (mouse wheel to scroll)"),
+                        TextFont {
+                            font_size: 16.0,
+                            font: assets.font.clone(),
+                            ..default()
+                        },
+                        TextColor(Color::WHITE),
+                    ));
+                    panel.spawn((
+                        Node {
+                            height: Val::Px(LIST_HEIGHT),
+                            flex_direction: FlexDirection::Column,
+                            overflow: Overflow::scroll_y(),
+                            ..default()
+                        },
+                        ScrollPosition::default(),
+                        EventLogList,
+                    ));
+                });
+        });
+}
+
+/// Opens the log at its newest entry, or closes it
+fn toggle_event_log_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    input_map: Res<InputMap>,
+    mut view: ResMut<EventLogView>,
+    mut panel_query: Query<&mut Node, With<EventLogPanel>>,
+) {
+    if !input_map.just_pressed(&keyboard_input, Action::EventLog) {
+        return;
+    }
+    view.open = !view.open;
+    view.following = view.open;
+    for mut node in panel_query.iter_mut() {
+        node.display = if view.open {
+            Display::Flex
+        } else {
+            Display::None
+        };
+    }
+}
+
+/// Scrolling back stops the newest entry being kept in view until the log is
+/// opened again
+fn scroll_event_log_system(
+    mut wheel_events: EventReader<MouseWheel>,
+    mut view: ResMut<EventLogView>,
+    mut list_query: Query<&mut ScrollPosition, With<EventLogList>>,
+) {
+    let pixels: f32 = wheel_events
+        .read()
+        .map(|event| match event.unit {
+            MouseScrollUnit::Line => event.y * SCROLL_LINE,
+            MouseScrollUnit::Pixel => event.y,
+        })
+        .sum();
+    if !view.open || pixels == 0.0 {
+        return;
+    }
+    if pixels > 0.0 {
+        view.following = false;
+    }
+    for mut scroll in list_query.iter_mut() {
+        // The layout keeps the offset within the list
+        scroll.offset_y = (scroll.offset_y - pixels).max(0.0);
+    }
+}
+
+/// Logs the events the rest of the game already announces
+fn record_events_system(
+    mut log: ResMut<EventLog>,
+    clock: Res<SeaClock>,
+    mut messages: EventReader<LogMessage>,
+    mut callouts: EventReader<CrewCallout>,
+    mut catches: EventReader<FishCollected>,
+    mut missions: EventReader<MissionEnded>,
+) {
+    let mut lines: Vec<(LogKind, String)> = Vec::new();
+    for catch in catches.read() {
+        let text = if catch.undersized {
+            "Undersized fish collected".to_string()
+        } else {
+            format!("Fish collected, {:.1}x its usual size", catch.size)
+        };
+        lines.push((LogKind::Catch, text));
+    }
+    for callout in callouts.read() {
+        let kind = match callout.kind {
+            CalloutKind::Damage | CalloutKind::Casualty | CalloutKind::Leak => LogKind::Damage,
+            CalloutKind::Contact => LogKind::Contact,
+            CalloutKind::Oxygen | CalloutKind::Failure => LogKind::Alarm,
+            CalloutKind::Depth
+            | CalloutKind::Ballast
+            | CalloutKind::Watch
+            | CalloutKind::Navigation => LogKind::Crew,
+        };
+        lines.push((kind, callout.text.clone()));
+    }
+    for ended in missions.read() {
+        let text = if ended.completed {
+            format!("Mission complete: {}", ended.mission)
+        } else {
+            format!("Mission failed: {}", ended.reason)
+        };
+        lines.push((LogKind::Mission, text));
+    }
+    lines.extend(
+        messages
+            .read()
+            .map(|message| (message.kind, message.text.clone())),
+    );
+    if lines.is_empty() {
+        return;
+    }
+
+    let minutes = (clock.hour() * 60.0) as u32;
+    let time = format!("{:02}:{:02}", minutes / 60, minutes % 60);
+    for (kind, text) in lines {
+        log.entries.push(LogEntry {
+            time: time.clone(),
+            kind,
+            text,
+        });
+    }
+    let excess = log.entries.len().saturating_sub(MAX_ENTRIES);
+    log.entries.drain(..excess);
+}
+
+/// Lists the entries again whenever the log changes, and keeps the newest in
+/// view while following
+fn event_log_ui_system(
+    mut commands: Commands,
+    log: Res<EventLog>,
+    view: Res<EventLogView>,
+    assets: Res<GameAssets>,
+    mut list_query: Query<(Entity, &mut ScrollPosition), With<EventLogList>>,
+) {
+    let Ok((list, mut scroll)) = list_query.single_mut() else {
+        return;
+    };
+    if view.following && (view.is_changed() || log.is_changed()) {
+        scroll.offset_y = f32::MAX;
+    }
+    if !log.is_changed() {
+        return;
+    }
+    let font = assets.font.clone();
+    commands.entity(list).despawn_related::<Children>();
+    commands.entity(list).with_children(|list| {
+        for entry in log.entries.iter() {
+            list.spawn((
+                Text::new(format!("{}  {}", entry.time, entry.text)),
+                TextFont {
+                    font_size: 14.0,
+                    font: font.clone(),
+                    ..default()
+                },
+                TextColor(entry.kind.color()),
+            ));
+        }
+    });
+}
@@ -0,0 +1,364 @@
+//! Equipment failures: now and then a vent valve sticks, the compressor
+//! breaker trips, the sonar glitches or the gyro drifts. Failures grow more
+//! likely with hull damage, depth and neglect, and each one has its own
+//! symptom and its own fix.
+
+use crate::callouts::{CalloutKind, CrewCallout};
+use crate::controls::{ControlCommand, HelmState};
+use crate::crew::{Crew, Station};
+use crate::cvars::{CvarDef, Cvars, RegisterCvar};
+use crate::difficulty::Difficulty;
+use crate::{
+    calculate_sonar_position, AppState, BallastState, GameAssets, GameMode, GameSet, GameState,
+    SonarDetections, Submarine, WorldSeed, SONAR_CENTER_X, SONAR_CENTER_Y, SONAR_RADIUS,
+};
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use rand::{rngs::StdRng, seq::IteratorRandom, Rng, SeedableRng};
+
+const WORK_PRESSES: usize = 3; // Vent toggles needed to work a stuck valve free
+const WORK_WINDOW: f32 = 2.0; // Seconds those toggles must fall within
+const RECALIBRATE_SPEED: f32 = 0.5; // Slower than this counts as holding still
+const RECALIBRATE_TIME: f32 = 5.0; // Seconds of holding still to recalibrate the sonar
+const GYRO_FIX_DEPTH: f32 = 1.0; // Shallower than this the gyro is realigned by a surface fix
+pub const GYRO_DRIFT_RATE: f32 = 0.05; // Radians per second the compass creeps with a drifting gyro
+
+const MEAN_INTERVAL: CvarDef = CvarDef {
+    name: "failures.mean_interval",
+    default: 900.0,
+    range: 60.0..=36000.0,
+    description: "Mean seconds between failures aboard a sound, shallow, tended boat",
+};
+
+const GRACE_TIME: CvarDef = CvarDef {
+    name: "failures.grace_time",
+    default: 60.0,
+    range: 0.0..=3600.0,
+    description: "Seconds into a dive before anything fails",
+};
+
+const DEPTH_SCALE: CvarDef = CvarDef {
+    name: "failures.depth_scale",
+    default: 20.0,
+    range: 1.0..=200.0,
+    description: "Each this many meters of depth adds the base failure rate again",
+};
+
+const NEGLECT_TIME: CvarDef = CvarDef {
+    name: "failures.neglect_time",
+    default: 600.0,
+    range: 1.0..=3600.0,
+    description: "Seconds without repairs until the equipment counts as neglected",
+};
+
+/// A piece of equipment that has broken down
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Failure {
+    StuckVent,      // Vents jam open
+    CompressorTrip, // Compressor cuts out and won't restart
+    SonarGlitch,    // Sonar blanks out and bearings jump
+    GyroDrift,      // Compass creeps off the true heading
+}
+
+impl Failure {
+    const ALL: [Failure; 4] = [
+        Failure::StuckVent,
+        Failure::CompressorTrip,
+        Failure::SonarGlitch,
+        Failure::GyroDrift,
+    ];
+
+    fn report(self) -> &'static str {
+        match self {
+            Failure::StuckVent => "Main vent stuck open!",
+            Failure::CompressorTrip => "Compressor breaker tripped!",
+            Failure::SonarGlitch => "Sonar's playing up!",
+            Failure::GyroDrift => "Gyro is drifting!",
+        }
+    }
+
+    /// What it takes to fix
+    fn repair(self) -> &'static str {
+        match self {
+            Failure::StuckVent => "work the vents three times quickly",
+            Failure::CompressorTrip => "floodlights off, then reset with the compressor switch",
+            Failure::SonarGlitch => "hold still for 5 s to recalibrate",
+            Failure::GyroDrift => "surface to realign",
+        }
+    }
+}
+
+/// Equipment currently broken down
+#[derive(Resource, Default)]
+pub struct Failures {
+    pub active: Vec<Failure>,
+}
+
+impl Failures {
+    pub fn has(&self, failure: Failure) -> bool {
+        self.active.contains(&failure)
+    }
+}
+
+/// Random source for failures, seeded from the world so replays match
+#[derive(Resource)]
+struct FailureRng(StdRng);
+
+/// Progress towards the current failures' repairs
+#[derive(Resource, Default)]
+struct Maintenance {
+    clock: f32,
+    last_serviced: f32,
+    vent_presses: Vec<f32>, // Times the vents were worked while stuck
+    still_time: f32,        // Seconds held still while the sonar glitches
+}
+
+#[derive(Component)]
+struct FailureText;
+
+pub struct FailuresPlugin;
+
+impl Plugin for FailuresPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Failures>()
+            .init_resource::<Maintenance>()
+            .insert_resource(FailureRng(StdRng::seed_from_u64(0)))
+            .register_cvar(&MEAN_INTERVAL)
+            .register_cvar(&GRACE_TIME)
+            .register_cvar(&DEPTH_SCALE)
+            .register_cvar(&NEGLECT_TIME)
+            .add_systems(
+                OnEnter(AppState::InGame),
+                (reset_failures, spawn_failure_text),
+            )
+            .add_systems(
+                Update,
+                (
+                    (
+                        failure_system.run_if(|mode: Res<GameMode>| *mode != GameMode::TimeTrial),
+                        repair_system,
+                    )
+                        .chain()
+                        .before(crate::ballast_control_system)
+                        .in_set(GameSet::Simulation),
+                    stuck_equipment_system
+                        .after(crate::ballast_control_system)
+                        .before(crate::submarine_movement)
+                        .in_set(GameSet::Simulation),
+                    sonar_glitch_system
+                        .after(crate::sonar_detection_system)
+                        .in_set(GameSet::Detection),
+                    failure_text_system.in_set(GameSet::Presentation),
+                ),
+            );
+    }
+}
+
+fn reset_failures(mut commands: Commands, seed: Res<WorldSeed>) {
+    commands.insert_resource(Failures::default());
+    commands.insert_resource(Maintenance::default());
+    commands.insert_resource(FailureRng(StdRng::seed_from_u64(seed.0 ^ 0x4641_494C)));
+}
+
+fn spawn_failure_text(mut commands: Commands, assets: Res<GameAssets>) {
+    commands.spawn((
+        Text::new(""),
+        TextFont {
+            font_size: 16.0,
+            font: assets.font.clone(),
+            ..default()
+        },
+        TextColor(Color::srgb(1.0, 0.6, 0.3)),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Percent(45.0),
+            left: Val::Px(16.0),
+            ..default()
+        },
+        FailureText,
+        StateScoped(AppState::InGame),
+    ));
+}
+
+/// Rolls for a new failure; damage, depth, time since the last repair and a
+/// short-handed or tired engine room all make one more likely
+#[allow(clippy::too_many_arguments)]
+fn failure_system(
+    mut failures: ResMut<Failures>,
+    mut maintenance: ResMut<Maintenance>,
+    mut rng: ResMut<FailureRng>,
+    mut callouts: EventWriter<CrewCallout>,
+    game_state: Res<GameState>,
+    crew: Res<Crew>,
+    difficulty: Res<Difficulty>,
+    cvars: Res<Cvars>,
+    submarine_query: Query<&Transform, With<Submarine>>,
+    time: Res<Time>,
+) {
+    let delta = time.delta_secs();
+    maintenance.clock += delta;
+    if maintenance.clock < cvars.get(&GRACE_TIME) {
+        return;
+    }
+    let Ok(submarine) = submarine_query.single() else {
+        return;
+    };
+
+    let damage = 1.0 - game_state.health / 100.0;
+    let depth = (-submarine.translation.y).max(0.0);
+    let neglect = ((maintenance.clock - maintenance.last_serviced) / cvars.get(&NEGLECT_TIME))
+        .min(1.0)
+        + (1.0 - crew.efficiency(Station::Engineering));
+    let rate = (1.0 + 2.0 * damage)
+        * (1.0 + depth / cvars.get(&DEPTH_SCALE))
+        * (1.0 + neglect)
+        * difficulty.failure_rate()
+        / cvars.get(&MEAN_INTERVAL);
+    if rng.0.gen::<f32>() >= rate * delta {
+        return;
+    }
+
+    let Some(failure) = Failure::ALL
+        .into_iter()
+        .filter(|failure| !failures.has(*failure))
+        .choose(&mut rng.0)
+    else {
+        return;
+    };
+    info!("Equipment failure: {:?}", failure);
+    failures.active.push(failure);
+    callouts.write(CrewCallout {
+        kind: CalloutKind::Failure,
+        text: failure.report().to_string(),
+    });
+}
+
+/// Applies the fix for each active failure once the player carries it out
+fn repair_system(
+    mut failures: ResMut<Failures>,
+    mut maintenance: ResMut<Maintenance>,
+    mut control_commands: EventReader<ControlCommand>,
+    mut callouts: EventWriter<CrewCallout>,
+    helm_state: Res<HelmState>,
+    submarine_query: Query<(&Transform, &Velocity), With<Submarine>>,
+    time: Res<Time>,
+) {
+    if failures.active.is_empty() {
+        control_commands.clear();
+        return;
+    }
+    let Ok((submarine, velocity)) = submarine_query.single() else {
+        return;
+    };
+    let now = maintenance.clock;
+    let mut repaired = Vec::new();
+
+    for command in control_commands.read() {
+        match command {
+            ControlCommand::ToggleVents if failures.has(Failure::StuckVent) => {
+                maintenance.vent_presses.push(now);
+            }
+            // The breaker only holds with the floodlights' load off the batteries
+            ControlCommand::ToggleCompressor
+                if failures.has(Failure::CompressorTrip) && !helm_state.lights_on =>
+            {
+                repaired.push(Failure::CompressorTrip);
+            }
+            _ => {}
+        }
+    }
+    maintenance
+        .vent_presses
+        .retain(|pressed| now - pressed <= WORK_WINDOW);
+    if maintenance.vent_presses.len() >= WORK_PRESSES {
+        maintenance.vent_presses.clear();
+        repaired.push(Failure::StuckVent);
+    }
+
+    if failures.has(Failure::SonarGlitch) {
+        if velocity.linvel.length() < RECALIBRATE_SPEED {
+            maintenance.still_time += time.delta_secs();
+        } else {
+            maintenance.still_time = 0.0;
+        }
+        if maintenance.still_time >= RECALIBRATE_TIME {
+            maintenance.still_time = 0.0;
+            repaired.push(Failure::SonarGlitch);
+        }
+    }
+
+    if failures.has(Failure::GyroDrift) && -submarine.translation.y < GYRO_FIX_DEPTH {
+        repaired.push(Failure::GyroDrift);
+    }
+
+    for failure in repaired {
+        if !failures.has(failure) {
+            continue;
+        }
+        info!("Repaired: {:?}", failure);
+        failures.active.retain(|active| *active != failure);
+        maintenance.last_serviced = now;
+        callouts.write(CrewCallout {
+            kind: CalloutKind::Failure,
+            text: match failure {
+                Failure::StuckVent => "Vent's free",
+                Failure::CompressorTrip => "Breaker reset",
+                Failure::SonarGlitch => "Sonar recalibrated",
+                Failure::GyroDrift => "Gyro realigned",
+            }
+            .to_string(),
+        });
+    }
+}
+
+/// A stuck vent stays open whatever the controls say, and a tripped
+/// compressor stays off
+fn stuck_equipment_system(failures: Res<Failures>, mut ballast_state: ResMut<BallastState>) {
+    if failures.has(Failure::StuckVent) {
+        ballast_state.vents_open = true;
+        ballast_state.air_valve_open = false;
+    }
+    if failures.has(Failure::CompressorTrip) {
+        ballast_state.compressor_on = false;
+    }
+}
+
+/// A glitching sonar blanks out for half of every two seconds and throws its
+/// bearings about the rest of the time
+fn sonar_glitch_system(
+    failures: Res<Failures>,
+    mut sonar_detections: ResMut<SonarDetections>,
+    time: Res<Time>,
+) {
+    if !failures.has(Failure::SonarGlitch) {
+        return;
+    }
+    let t = time.elapsed_secs();
+    if (t * 0.5).fract() < 0.5 {
+        sonar_detections.contacts.clear();
+        return;
+    }
+    for contact in sonar_detections.contacts.iter_mut() {
+        let scaled_dist = (contact.x - SONAR_CENTER_X).hypot(contact.y - SONAR_CENTER_Y);
+        contact.angle += (t * 7.0 + contact.angle * 13.0).sin() * 0.5;
+        (contact.x, contact.y) = calculate_sonar_position(contact.angle, scaled_dist, SONAR_RADIUS);
+        contact.confidence *= 0.5;
+    }
+}
+
+fn failure_text_system(
+    failures: Res<Failures>,
+    mut text_query: Query<&mut Text, With<FailureText>>,
+) {
+    if !failures.is_changed() {
+        return;
+    }
+    for mut text in text_query.iter_mut() {
+        text.0 = failures
+            .active
+            .iter()
+            .map(|failure| format!("{} {}", failure.report(), failure.repair()))
+            .collect::<Vec<_>>()
+            .join("\n");
+    }
+}
@@ -0,0 +1,182 @@
+//! Feeding frenzies: a hostile that catches a fish sets off a frenzy where it
+//! fed. Fish nearby scatter in panic, and the thrashing fills the sonar with a
+//! cluster of wandering returns and false echoes. Predators busy in a frenzy
+//! are a chance to slip past, but one brushing by still bites.
+
+use crate::combat::FishEaten;
+use crate::{
+    calculate_fish_angle, calculate_sonar_position, AppState, Fish, FishMovement, GameSet,
+    SonarContact, SonarDetections, Submarine, Tuning, WorldSeed,
+};
+use bevy::prelude::*;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+const FRENZY_TIME: f32 = 8.0; // Seconds a frenzy lasts after the last fish eaten
+const FRENZY_RADIUS: f32 = 15.0; // Fish inside panic, and returns from inside wander
+const MERGE_RADIUS: f32 = 10.0; // A kill this close to a frenzy feeds it instead of starting another
+const MAX_KILLS: u32 = 5; // Kills beyond this don't make a frenzy any noisier
+const GHOSTS_PER_KILL: u32 = 2; // False echoes on the sonar for each fish eaten
+const BEARING_WANDER: f32 = 0.15; // Radians returns from inside a frenzy wander by
+
+/// Where predators are tearing into a school
+#[derive(Component)]
+pub struct FeedingFrenzy {
+    pub life: f32, // Seconds left
+    pub kills: u32,
+}
+
+pub struct FeedingPlugin;
+
+impl Plugin for FeedingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                (frenzy_system, panic_system)
+                    .chain()
+                    .before(crate::fish_movement)
+                    .in_set(GameSet::Simulation),
+                frenzy_sonar_system
+                    .after(crate::sonar_detection_system)
+                    .in_set(GameSet::Detection),
+            ),
+        );
+    }
+}
+
+/// Starts a frenzy where a fish was eaten, or feeds the one already there,
+/// and lets frenzies die down once the killing stops
+fn frenzy_system(
+    mut commands: Commands,
+    mut eaten: EventReader<FishEaten>,
+    mut frenzy_query: Query<(Entity, &mut FeedingFrenzy, &mut Transform)>,
+    time: Res<Time>,
+) {
+    for FishEaten { position } in eaten.read() {
+        let nearby = frenzy_query
+            .iter_mut()
+            .find(|(_, _, transform)| transform.translation.distance(*position) < MERGE_RADIUS);
+        match nearby {
+            Some((_, mut frenzy, mut transform)) => {
+                frenzy.life = FRENZY_TIME;
+                frenzy.kills += 1;
+                // The frenzy follows the feeding as it moves through the school
+                transform.translation = transform.translation.lerp(*position, 0.5);
+            }
+            None => {
+                info!("Feeding frenzy at {:.0}, {:.0}", position.x, position.z);
+                commands.spawn((
+                    Transform::from_translation(*position),
+                    FeedingFrenzy {
+                        life: FRENZY_TIME,
+                        kills: 1,
+                    },
+                    StateScoped(AppState::InGame),
+                ));
+            }
+        }
+    }
+    for (entity, mut frenzy, _) in frenzy_query.iter_mut() {
+        frenzy.life -= time.delta_secs();
+        if frenzy.life <= 0.0 {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+/// Fish outside the frenzies, and which way each is swimming
+type ScatterQuery<'w, 's> = Query<
+    'w,
+    's,
+    (&'static Transform, &'static mut FishMovement),
+    (With<Fish>, Without<FeedingFrenzy>),
+>;
+
+/// Fish inside a frenzy scatter outwards, staying mostly level
+fn panic_system(
+    frenzy_query: Query<&Transform, With<FeedingFrenzy>>,
+    mut fish_query: ScatterQuery,
+    tuning: Res<Tuning>,
+) {
+    for frenzy in frenzy_query.iter() {
+        for (transform, mut movement) in fish_query.iter_mut() {
+            let away = transform.translation - frenzy.translation;
+            if away.length() > FRENZY_RADIUS {
+                continue;
+            }
+            movement.direction = (away * Vec3::new(1.0, 0.3, 1.0))
+                .try_normalize()
+                .unwrap_or(Vec3::X);
+            movement.fleeing = tuning.fish_flee_time;
+            movement.change_direction_timer = 0.0;
+        }
+    }
+}
+
+/// Returns from inside a frenzy wander in bearing, and each kill adds false
+/// echoes around it; like jamming ghosts they hold still for a moment at a time
+fn frenzy_sonar_system(
+    mut detections: ResMut<SonarDetections>,
+    tuning: Res<Tuning>,
+    seed: Res<WorldSeed>,
+    submarine_query: Query<&Transform, With<Submarine>>,
+    frenzy_query: Query<(Entity, &Transform, &FeedingFrenzy)>,
+    target_query: Query<&Transform>,
+    time: Res<Time>,
+) {
+    let Ok(submarine) = submarine_query.single() else {
+        return;
+    };
+    if frenzy_query.is_empty() {
+        return;
+    }
+    let t = time.elapsed_secs();
+    let to_display = |position: Vec3, wander: f32| {
+        let rel = position - submarine.translation;
+        let angle = calculate_fish_angle(submarine.rotation.inverse() * rel) + wander;
+        let (x, y) = calculate_sonar_position(angle, rel.length(), tuning.sonar_range);
+        (angle, x, y)
+    };
+
+    for contact in detections.contacts.iter_mut() {
+        let Some(position) = contact
+            .target
+            .and_then(|target| target_query.get(target).ok())
+            .map(|transform| transform.translation)
+        else {
+            continue;
+        };
+        let inside = frenzy_query
+            .iter()
+            .any(|(_, frenzy, _)| frenzy.translation.distance(position) < FRENZY_RADIUS);
+        if inside {
+            let wander = (t * 7.0 + position.x).sin() * BEARING_WANDER;
+            (contact.angle, contact.x, contact.y) = to_display(position, wander);
+            contact.confidence *= 0.6;
+        }
+    }
+
+    for (entity, transform, frenzy) in frenzy_query.iter() {
+        let mut rng = StdRng::seed_from_u64(seed.0 ^ entity.to_bits() ^ (t * 4.0) as u64);
+        for _ in 0..frenzy.kills.min(MAX_KILLS) * GHOSTS_PER_KILL {
+            let offset = Vec3::new(
+                rng.gen_range(-1.0..=1.0),
+                rng.gen_range(-0.3..=0.3),
+                rng.gen_range(-1.0..=1.0),
+            ) * FRENZY_RADIUS;
+            let position = transform.translation + offset;
+            if position.distance(submarine.translation) > tuning.sonar_range {
+                continue;
+            }
+            let (angle, x, y) = to_display(position, 0.0);
+            detections.contacts.push(SonarContact {
+                x,
+                y,
+                angle,
+                confidence: rng.gen_range(0.1..0.5),
+                target: None,
+                depth: -position.y,
+            });
+        }
+    }
+}
@@ -0,0 +1,189 @@
+//! Fish finder: an optional overlay that marks sonar contacts in the 3D view.
+//! Each time the sweep line passes over a firm return, a faint diamond with
+//! the contact's range is drawn over it in the world for a few seconds, so
+//! the blips on the sonar panel can be matched to what is out in the water.
+
+use crate::settings::Settings;
+use crate::{AppState, CameraFollow, GameAssets, GameSet, SonarDetections, SonarState, Submarine};
+use bevy::prelude::*;
+use std::f32::consts::{PI, TAU};
+
+const MIN_CONFIDENCE: f32 = 0.3; // Weaker returns are too uncertain to mark
+const MARK_TIME: f32 = 4.0; // Seconds a mark lasts after the sweep passes
+const MARK_OPACITY: f32 = 0.45; // Opacity of a fresh mark
+const MARK_SIZE: f32 = 0.03; // Half-width of a diamond per meter from the camera
+const MAX_MARKS: usize = 12;
+
+/// Contacts marked by the last sweeps, and where the sweep line was last frame
+#[derive(Resource, Default)]
+pub struct FishFinder {
+    marks: Vec<Mark>,
+    last_sweep: Option<f32>,
+}
+
+struct Mark {
+    target: Entity,
+    age: f32,
+}
+
+/// Range readout beside a mark, one per mark slot
+#[derive(Component)]
+struct RangeLabel(usize);
+
+pub struct FishFinderPlugin;
+
+impl Plugin for FishFinderPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FishFinder>()
+            .add_systems(
+                OnEnter(AppState::InGame),
+                (reset_fish_finder, spawn_range_labels),
+            )
+            .add_systems(
+                Update,
+                (mark_system, draw_marks_system)
+                    .chain()
+                    .in_set(GameSet::Presentation),
+            );
+    }
+}
+
+fn reset_fish_finder(mut commands: Commands) {
+    commands.insert_resource(FishFinder::default());
+}
+
+fn spawn_range_labels(mut commands: Commands, assets: Res<GameAssets>) {
+    let font = assets.font.clone();
+    for slot in 0..MAX_MARKS {
+        commands.spawn((
+            Text::new(""),
+            TextFont {
+                font_size: 13.0,
+                font: font.clone(),
+                ..default()
+            },
+            TextColor(Color::srgba(0.3, 1.0, 0.4, 0.0)),
+            Node {
+                position_type: PositionType::Absolute,
+                ..default()
+            },
+            RangeLabel(slot),
+            StateScoped(AppState::InGame),
+        ));
+    }
+}
+
+/// Marks firm contacts as the sweep line passes over their bearing, and ages
+/// the marks already made
+fn mark_system(
+    mut finder: ResMut<FishFinder>,
+    sonar_state: Res<SonarState>,
+    detections: Res<SonarDetections>,
+    settings: Res<Settings>,
+    submarine_query: Query<&Transform, With<Submarine>>,
+    time: Res<Time>,
+) {
+    let delta = time.delta_secs();
+    finder.marks.retain_mut(|mark| {
+        mark.age += delta;
+        mark.age < MARK_TIME
+    });
+    let Ok(submarine) = submarine_query.single() else {
+        return;
+    };
+    // The sweep line is drawn turned by the boat's heading
+    let sweep = sonar_state.sweep_angle + submarine.rotation.to_euler(EulerRot::YXZ).0;
+    let Some(last_sweep) = finder.last_sweep.replace(sweep) else {
+        return;
+    };
+    if !settings.hud.fish_finder {
+        return;
+    }
+    // The sweep turns clockwise; a turn of the boat against it covers no new ground
+    let arc = last_sweep - sweep;
+    if arc <= 0.0 || arc > PI {
+        return;
+    }
+
+    for contact in detections.contacts.iter() {
+        let Some(target) = contact.target else {
+            continue;
+        };
+        if contact.confidence < MIN_CONFIDENCE || (last_sweep - contact.angle).rem_euclid(TAU) > arc
+        {
+            continue;
+        }
+        if let Some(mark) = finder.marks.iter_mut().find(|mark| mark.target == target) {
+            mark.age = 0.0;
+        } else if finder.marks.len() < MAX_MARKS {
+            finder.marks.push(Mark { target, age: 0.0 });
+        }
+    }
+}
+
+/// Draws a diamond over each marked contact, facing the camera, with its range
+/// from the boat beside it
+#[allow(clippy::too_many_arguments)]
+fn draw_marks_system(
+    mut gizmos: Gizmos,
+    finder: Res<FishFinder>,
+    settings: Res<Settings>,
+    ui_scale: Res<UiScale>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<CameraFollow>>,
+    submarine_query: Query<&Transform, With<Submarine>>,
+    target_query: Query<&GlobalTransform>,
+    mut label_query: Query<(&RangeLabel, &mut Text, &mut TextColor, &mut Node)>,
+) {
+    let mut labels = vec![None; MAX_MARKS];
+    if let (true, Ok((camera, camera_transform)), Ok(submarine)) = (
+        settings.hud.fish_finder,
+        camera_query.single(),
+        submarine_query.single(),
+    ) {
+        let right = camera_transform.right();
+        let up = camera_transform.up();
+        for (slot, mark) in finder.marks.iter().enumerate() {
+            let Ok(target) = target_query.get(mark.target) else {
+                continue;
+            };
+            let position = target.translation();
+            let opacity = MARK_OPACITY * (1.0 - mark.age / MARK_TIME);
+            let color = Color::srgba(0.3, 1.0, 0.4, opacity);
+            let size = MARK_SIZE * position.distance(camera_transform.translation());
+            let corners = [
+                position + up * size,
+                position + right * size,
+                position - up * size,
+                position - right * size,
+                position + up * size,
+            ];
+            gizmos.linestrip(corners, color);
+
+            let Ok(screen) = camera.world_to_viewport(camera_transform, position + right * size)
+            else {
+                continue;
+            };
+            labels[slot] = Some((
+                screen / ui_scale.0,
+                position.distance(submarine.translation),
+                opacity,
+            ));
+        }
+    }
+
+    for (label, mut text, mut color, mut node) in label_query.iter_mut() {
+        match labels.get(label.0).copied().flatten() {
+            Some((screen, range, opacity)) => {
+                let line = format!("{:.0} m", range);
+                if **text != line {
+                    **text = line;
+                }
+                color.0 = Color::srgba(0.3, 1.0, 0.4, opacity);
+                node.left = Val::Px(screen.x + 4.0);
+                node.top = Val::Px(screen.y - 8.0);
+                node.display = Display::Flex;
+            }
+            None => node.display = Display::None,
+        }
+    }
+}
@@ -0,0 +1,396 @@
+//! Fouled screw: driving hard through a kelp bed or a ghost net can wrap weed
+//! or line round the propeller, and the boat makes little headway until it is
+//! cleared. Backing down unwinds most of it, then the screw has to stand
+//! stopped a few seconds to shed the rest; or, at a standstill, the diver can
+//! go out and cut it free at the cost of some air.
+
+use crate::callouts::{CalloutKind, CrewCallout};
+use crate::cleanup::{in_net, Debris, DebrisKind};
+use crate::controls::{ControlCommand, HelmInput, HelmState, Telegraph};
+use crate::settings::Settings;
+use crate::sfx::{self, Note, Sfx};
+use crate::tables::Tables;
+use crate::{AppState, GameAssets, GameSet, GameState, Submarine, Tuning, WorldSeed};
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+const SEA_FLOOR: f32 = -20.5;
+const BED_RADIUS: f32 = 8.0; // Meters from the middle of a kelp bed to its edge
+const STALKS: usize = 25; // Fronds in each bed
+const KELP_TOP: f32 = -6.0; // Tallest fronds reach this far up
+const FOUL_SPEED: f32 = 0.6; // Share of full speed above which weed or line can foul the screw
+const KELP_FOUL_RATE: f32 = 0.5; // Chance per second of fouling while driving hard through kelp
+const FOULED_THRUST: f32 = 0.25; // Share of its speed a boat with a fouled screw can make
+const BACK_DOWN_TIME: f32 = 1.5; // Seconds running astern to unwind the worst of it
+const HOLD_TIME: f32 = 4.0; // Seconds stopped to shed the rest
+const HOLD_SPEED: f32 = 0.5; // Meters per second that still counts as holding still
+const DIVER_TIME: f32 = 6.0; // Seconds the diver takes to cut the screw free
+const DIVER_OXYGEN: f32 = 10.0; // Oxygen percent the diver's lockout costs
+
+/// How far the crew have got with clearing a fouled screw
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FoulStage {
+    BackDown(f32),  // Seconds spent running astern so far
+    HoldStill(f32), // Seconds held still so far
+    Diver(f32),     // Seconds the diver has been at work
+}
+
+/// Whether the screw is fouled, and what is being done about it
+#[derive(Resource, Default)]
+pub struct Fouling {
+    pub stage: Option<FoulStage>,
+}
+
+/// A patch of kelp rising off the floor
+#[derive(Component)]
+pub struct KelpBed {
+    pub radius: f32,
+}
+
+/// Random source for fouling in kelp, seeded from the world so replays match
+#[derive(Resource)]
+struct FoulingRng(StdRng);
+
+#[derive(Resource)]
+struct FoulingSounds {
+    fouled: Handle<Sfx>,
+    cleared: Handle<Sfx>,
+}
+
+#[derive(Component)]
+struct FoulingText;
+
+/// Kelp fronds, unit height and scaled to each stalk, made once
+#[derive(Resource)]
+struct KelpAssets {
+    frond_mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+}
+
+impl FromWorld for KelpAssets {
+    fn from_world(world: &mut World) -> Self {
+        let frond_mesh = world
+            .resource_mut::<Assets<Mesh>>()
+            .add(Cylinder::new(0.12, 1.0));
+        let material = world
+            .resource_mut::<Assets<StandardMaterial>>()
+            .add(StandardMaterial {
+                base_color: Color::srgb(0.25, 0.4, 0.12),
+                perceptual_roughness: 0.9,
+                ..default()
+            });
+        Self {
+            frond_mesh,
+            material,
+        }
+    }
+}
+
+pub struct FoulingPlugin;
+
+impl Plugin for FoulingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Fouling>()
+            .insert_resource(FoulingRng(StdRng::seed_from_u64(0)))
+            .add_systems(Startup, load_fouling_sounds)
+            .add_systems(
+                OnEnter(AppState::InGame),
+                (
+                    reset_fouling,
+                    spawn_kelp.after(crate::setup),
+                    spawn_fouling_text,
+                ),
+            )
+            .add_systems(
+                Update,
+                (
+                    (
+                        (foul_system, clearing_system).before(crate::submarine_movement),
+                        thrust_system.after(crate::submarine_movement),
+                    )
+                        .chain()
+                        .in_set(GameSet::Simulation),
+                    fouling_text_system.in_set(GameSet::Presentation),
+                ),
+            );
+    }
+
+    fn finish(&self, app: &mut App) {
+        app.init_resource::<KelpAssets>();
+    }
+}
+
+fn load_fouling_sounds(mut commands: Commands, mut sounds: ResMut<Assets<Sfx>>) {
+    // Low beating drone, like line grinding round the shaft
+    let fouled = [55.0, 58.0, 82.0]
+        .into_iter()
+        .map(|frequency| Note {
+            frequency,
+            start: 0.0,
+            duration: 1.2,
+            volume: 0.35,
+        })
+        .collect();
+    // Two rising notes once the screw turns clean again
+    let cleared = [659.25, 987.77]
+        .into_iter()
+        .enumerate()
+        .map(|(i, frequency)| Note {
+            frequency,
+            start: i as f32 * 0.12,
+            duration: 0.7,
+            volume: 0.25,
+        })
+        .collect();
+    commands.insert_resource(FoulingSounds {
+        fouled: sounds.add(Sfx::new(fouled)),
+        cleared: sounds.add(Sfx::new(cleared)),
+    });
+}
+
+fn reset_fouling(mut commands: Commands, seed: Res<WorldSeed>) {
+    commands.insert_resource(Fouling::default());
+    commands.insert_resource(FoulingRng(StdRng::seed_from_u64(seed.0 ^ 0x4B45_4C50)));
+}
+
+/// Beds of tall fronds scattered over the floor, clear of the start
+fn spawn_kelp(
+    mut commands: Commands,
+    assets: Res<KelpAssets>,
+    seed: Res<WorldSeed>,
+    tables: Res<Tables>,
+) {
+    let mut rng = StdRng::seed_from_u64(seed.0 ^ 0x4B45_4C42);
+
+    for _ in 0..tables.spawns.kelp_beds {
+        let angle = rng.gen::<f32>() * std::f32::consts::TAU;
+        let distance = rng.gen_range(30.0..250.0);
+        let center = Vec3::new(angle.cos() * distance, SEA_FLOOR, angle.sin() * distance);
+        commands
+            .spawn((
+                Transform::from_translation(center),
+                Visibility::default(),
+                KelpBed { radius: BED_RADIUS },
+                StateScoped(AppState::InGame),
+            ))
+            .with_children(|bed| {
+                for _ in 0..STALKS {
+                    let spot = Vec2::from_angle(rng.gen::<f32>() * std::f32::consts::TAU)
+                        * rng.gen::<f32>().sqrt()
+                        * BED_RADIUS;
+                    let height = rng.gen_range(0.5..1.0) * (KELP_TOP - SEA_FLOOR);
+                    let lean = Quat::from_rotation_x(rng.gen_range(-0.15..0.15))
+                        * Quat::from_rotation_z(rng.gen_range(-0.15..0.15));
+                    bed.spawn((
+                        Mesh3d(assets.frond_mesh.clone()),
+                        MeshMaterial3d(assets.material.clone()),
+                        Transform::from_xyz(spot.x, height / 2.0, spot.y)
+                            .with_rotation(lean)
+                            .with_scale(Vec3::new(1.0, height, 1.0)),
+                    ));
+                }
+            });
+    }
+}
+
+/// Running hard through a ghost net fouls the screw at once; through kelp it
+/// is a matter of luck, worse the longer the boat stays in it
+#[allow(clippy::too_many_arguments)]
+fn foul_system(
+    mut commands: Commands,
+    mut fouling: ResMut<Fouling>,
+    mut rng: ResMut<FoulingRng>,
+    mut callouts: EventWriter<CrewCallout>,
+    settings: Res<Settings>,
+    sounds: Res<FoulingSounds>,
+    tuning: Res<Tuning>,
+    submarine_query: Query<(&Transform, &Velocity), With<Submarine>>,
+    kelp_query: Query<(&Transform, &KelpBed)>,
+    net_query: Query<(&Transform, &Debris), Without<Submarine>>,
+    time: Res<Time>,
+) {
+    if fouling.stage.is_some() {
+        return;
+    }
+    let Ok((submarine, velocity)) = submarine_query.single() else {
+        return;
+    };
+    let speed = Vec2::new(velocity.linvel.x, velocity.linvel.z).length();
+    if speed < FOUL_SPEED * tuning.speed {
+        return;
+    }
+    let position = submarine.translation;
+    let in_kelp = position.y < KELP_TOP
+        && kelp_query.iter().any(|(transform, bed)| {
+            Vec2::new(
+                position.x - transform.translation.x,
+                position.z - transform.translation.z,
+            )
+            .length()
+                < bed.radius
+        });
+    let through_net = net_query.iter().any(|(transform, debris)| {
+        debris.kind == DebrisKind::GhostNet && in_net(transform, position)
+    });
+    let what = if through_net {
+        "line"
+    } else if in_kelp && rng.0.gen::<f32>() < KELP_FOUL_RATE * time.delta_secs() {
+        "kelp"
+    } else {
+        return;
+    };
+
+    fouling.stage = Some(FoulStage::BackDown(0.0));
+    warn!("Screw fouled with {}", what);
+    callouts.write(CrewCallout {
+        kind: CalloutKind::Failure,
+        text: format!(
+            "Screw's fouled with {}! Back her down and hold still, or send the diver",
+            what
+        ),
+    });
+    sfx::play(&mut commands, sounds.fouled.clone(), &settings);
+}
+
+/// Works through backing down and holding still, or the diver's cutting, and
+/// hands the screw back once it is clear
+#[allow(clippy::too_many_arguments)]
+fn clearing_system(
+    mut commands: Commands,
+    mut fouling: ResMut<Fouling>,
+    mut game_state: ResMut<GameState>,
+    mut control_commands: EventReader<ControlCommand>,
+    mut callouts: EventWriter<CrewCallout>,
+    settings: Res<Settings>,
+    sounds: Res<FoulingSounds>,
+    helm_input: Res<HelmInput>,
+    helm_state: Res<HelmState>,
+    submarine_query: Query<&Velocity, With<Submarine>>,
+    time: Res<Time>,
+) {
+    let diver_sent = control_commands
+        .read()
+        .any(|command| *command == ControlCommand::SendDiver);
+    let Some(stage) = fouling.stage else {
+        return;
+    };
+    let Ok(velocity) = submarine_query.single() else {
+        return;
+    };
+    let delta = time.delta_secs();
+    let astern = helm_input.throttle < 0.0 || helm_state.telegraph.throttle() < 0.0;
+    let stopped = velocity.linvel.length() < HOLD_SPEED
+        && helm_input.throttle == 0.0
+        && helm_state.telegraph == Telegraph::Stop;
+
+    let next = match stage {
+        FoulStage::Diver(_) if !stopped => {
+            callouts.write(CrewCallout {
+                kind: CalloutKind::Failure,
+                text: "Diver's back aboard, we moved off! Screw's still fouled".to_string(),
+            });
+            FoulStage::BackDown(0.0)
+        }
+        FoulStage::Diver(progress) => FoulStage::Diver(progress + delta),
+        _ if diver_sent && !stopped => {
+            callouts.write(CrewCallout {
+                kind: CalloutKind::Failure,
+                text: "Can't lock the diver out under way; stop the boat".to_string(),
+            });
+            stage
+        }
+        _ if diver_sent => {
+            game_state.oxygen = (game_state.oxygen - DIVER_OXYGEN).max(0.0);
+            callouts.write(CrewCallout {
+                kind: CalloutKind::Failure,
+                text: "Diver's going out to clear the screw".to_string(),
+            });
+            FoulStage::Diver(0.0)
+        }
+        FoulStage::BackDown(progress) if astern => FoulStage::BackDown(progress + delta),
+        FoulStage::BackDown(progress) if progress >= BACK_DOWN_TIME => FoulStage::HoldStill(0.0),
+        FoulStage::BackDown(_) => stage,
+        FoulStage::HoldStill(progress) if stopped => FoulStage::HoldStill(progress + delta),
+        FoulStage::HoldStill(_) => FoulStage::HoldStill(0.0),
+    };
+
+    let clear = match next {
+        FoulStage::BackDown(_) => false,
+        FoulStage::HoldStill(progress) => progress >= HOLD_TIME,
+        FoulStage::Diver(progress) => progress >= DIVER_TIME,
+    };
+    if clear {
+        fouling.stage = None;
+        info!("Screw cleared");
+        callouts.write(CrewCallout {
+            kind: CalloutKind::Failure,
+            text: "Screw's clear, answering bells".to_string(),
+        });
+        sfx::play(&mut commands, sounds.cleared.clone(), &settings);
+    } else {
+        fouling.stage = Some(next);
+    }
+}
+
+/// A fouled screw gives only a fraction of its thrust; diving and surfacing
+/// are left to the ballast
+fn thrust_system(
+    fouling: Res<Fouling>,
+    mut submarine_query: Query<&mut Velocity, With<Submarine>>,
+) {
+    if fouling.stage.is_none() {
+        return;
+    }
+    let Ok(mut velocity) = submarine_query.single_mut() else {
+        return;
+    };
+    velocity.linvel.x *= FOULED_THRUST;
+    velocity.linvel.z *= FOULED_THRUST;
+}
+
+fn spawn_fouling_text(mut commands: Commands, assets: Res<GameAssets>) {
+    commands.spawn((
+        Text::new(""),
+        TextFont {
+            font_size: 16.0,
+            font: assets.font.clone(),
+            ..default()
+        },
+        TextColor(Color::srgb(1.0, 0.6, 0.2)),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Percent(55.0),
+            left: Val::Px(16.0),
+            ..default()
+        },
+        FoulingText,
+        StateScoped(AppState::InGame),
+    ));
+}
+
+fn fouling_text_system(fouling: Res<Fouling>, mut text_query: Query<&mut Text, With<FoulingText>>) {
+    let Ok(mut text) = text_query.single_mut() else {
+        return;
+    };
+    let bar = |share: f32| {
+        let filled = ((share * 20.0) as usize).min(20);
+        format!("[{}{}]", "#".repeat(filled), "-".repeat(20 - filled))
+    };
+    let line = match fouling.stage {
+        None => String::new(),
+        Some(FoulStage::BackDown(progress)) => {
+            format!("SCREW FOULED: back down {}", bar(progress / BACK_DOWN_TIME))
+        }
+        Some(FoulStage::HoldStill(progress)) => format!(
+            "SCREW FOULED: stop and hold still {}",
+            bar(progress / HOLD_TIME)
+        ),
+        Some(FoulStage::Diver(progress)) => {
+            format!("Diver clearing the screw {}", bar(progress / DIVER_TIME))
+        }
+    };
+    if **text != line {
+        **text = line;
+    }
+}
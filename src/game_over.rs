@@ -0,0 +1,79 @@
+//! Losing the hull: when it gives out the boat goes dead in the water, frozen
+//! where it lies, deaf to the helm and unable to fire or take fish, and after
+//! a moment a Game Over screen shows the final score. Runs that end on screens
+//! of their own, survival, ironman and failed missions, keep those instead.
+
+use crate::hud::{spawn_game_over_screen, GameOverScreen};
+use crate::{AppState, GameAssets, GameSet, GameState, Submarine};
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+const SETTLE_TIME: f32 = 2.0; // Seconds the lost boat lies still before the screen shows
+
+/// How long ago the hull was lost, if it has been
+#[derive(Resource, Default)]
+struct HullLoss {
+    lost_for: Option<f32>,
+}
+
+pub struct GameOverPlugin;
+
+impl Plugin for GameOverPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<HullLoss>()
+            .add_systems(OnEnter(AppState::InGame), reset_hull_loss)
+            .add_systems(
+                Update,
+                hull_loss_system
+                    .after(crate::submarine_movement)
+                    .in_set(GameSet::Simulation),
+            );
+    }
+}
+
+/// Run condition for the boat's own systems: the helm, ballast, weapons and
+/// catching fish all stop once the hull is lost
+pub fn hull_intact(game_state: Res<GameState>) -> bool {
+    game_state.health > 0.0
+}
+
+fn reset_hull_loss(mut commands: Commands) {
+    commands.insert_resource(HullLoss::default());
+}
+
+/// Freezes the boat the moment the hull is lost, and once it has lain still a
+/// while shows the Game Over screen unless the run has ended on another
+fn hull_loss_system(
+    mut commands: Commands,
+    mut loss: ResMut<HullLoss>,
+    game_state: Res<GameState>,
+    mut submarine_query: Query<(Entity, &mut Velocity), With<Submarine>>,
+    screen_query: Query<(), With<GameOverScreen>>,
+    assets: Res<GameAssets>,
+    time: Res<Time>,
+) {
+    if game_state.health > 0.0 {
+        return;
+    }
+    let Some(lost_for) = loss.lost_for else {
+        info!("Hull lost with {} points", game_state.score);
+        if let Ok((submarine, mut velocity)) = submarine_query.single_mut() {
+            *velocity = Velocity::zero();
+            commands.entity(submarine).insert(RigidBody::Fixed);
+        }
+        loss.lost_for = Some(0.0);
+        return;
+    };
+    let now = lost_for + time.delta_secs();
+    loss.lost_for = Some(now);
+    if lost_for < SETTLE_TIME && now >= SETTLE_TIME && screen_query.is_empty() {
+        spawn_game_over_screen(
+            &mut commands,
+            assets.font.clone(),
+            format!(
+                "Game Over\nThe hull gave out\n\nFinal score: {}",
+                game_state.score
+            ),
+        );
+    }
+}
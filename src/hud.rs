@@ -0,0 +1,255 @@
+use crate::contact_history::STRIP_HEIGHT;
+use crate::settings::{HudCorner, HudSide, Settings, SonarSize};
+use crate::{
+    start_new_game, AppState, GameMode, GameSet, Ironman, NewGamePlus, PlayIntro, SONAR_SCOPE_SIZE,
+};
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+
+const HUD_MARGIN: f32 = 20.0;
+const UI_SCALE_STEP: f32 = 0.1;
+const UI_SCALE_MIN: f32 = 0.5;
+const UI_SCALE_MAX: f32 = 3.0;
+const SMALL_SONAR: f32 = 0.18; // Share of the window's height the small sonar panel takes
+const MEDIUM_SONAR: f32 = 0.28;
+const MIN_SONAR: f32 = 100.0; // Smallest the sonar panel is drawn, in pixels
+
+/// Status text block on the left or right side of the screen
+#[derive(Component)]
+pub struct HudTextPanel;
+
+/// Container holding the sonar ring, sweep and blips
+#[derive(Component)]
+pub struct SonarPanel;
+
+/// Pixels across the sonar panel is laid out at, before the UI scale
+#[derive(Resource)]
+pub struct SonarScope {
+    pub size: f32,
+}
+
+impl Default for SonarScope {
+    fn default() -> Self {
+        Self {
+            size: SONAR_SCOPE_SIZE,
+        }
+    }
+}
+
+impl SonarScope {
+    /// Panel pixels to each pixel of the nominal scope contacts are plotted on
+    pub fn scale(&self) -> f32 {
+        self.size / SONAR_SCOPE_SIZE
+    }
+
+    /// Thickness of the scope's lines; they thicken more slowly than the
+    /// scope grows so a large one stays crisp
+    pub fn line_width(&self) -> f32 {
+        2.0 * self.scale().sqrt()
+    }
+}
+
+/// Summary shown when a run ends; Enter returns to the main menu and R starts
+/// the same kind of run over
+#[derive(Component)]
+pub struct GameOverScreen;
+
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        // Layout is applied outside the game sets so menu changes take effect immediately
+        app.init_resource::<SonarScope>().add_systems(
+            Update,
+            (
+                hud_layout_input_system.in_set(GameSet::Input),
+                apply_hud_layout_system,
+                game_over_input_system.run_if(any_with_component::<GameOverScreen>),
+            ),
+        );
+    }
+}
+
+/// Keyboard shortcuts for HUD scale and placement
+fn hud_layout_input_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut settings: ResMut<Settings>,
+) {
+    let hud = &settings.hud;
+    let mut scale = hud.ui_scale;
+    if keyboard_input.just_pressed(KeyCode::Equal) {
+        scale += UI_SCALE_STEP;
+    }
+    if keyboard_input.just_pressed(KeyCode::Minus) {
+        scale -= UI_SCALE_STEP;
+    }
+    let scale = (scale * 10.0).round() / 10.0; // Avoid drift from repeated float steps
+    let scale = scale.clamp(UI_SCALE_MIN, UI_SCALE_MAX);
+    if scale != hud.ui_scale {
+        settings.hud.ui_scale = scale;
+    }
+
+    if keyboard_input.just_pressed(KeyCode::F2) {
+        settings.hud.sonar_size = settings.hud.sonar_size.next();
+    }
+    if keyboard_input.just_pressed(KeyCode::F6) {
+        settings.hud.sonar_corner = settings.hud.sonar_corner.next();
+    }
+    if keyboard_input.just_pressed(KeyCode::F7) {
+        settings.hud.text_side = settings.hud.text_side.flipped();
+    }
+    if keyboard_input.just_pressed(KeyCode::F8) {
+        settings.hud.show_text = !settings.hud.show_text;
+    }
+    if keyboard_input.just_pressed(KeyCode::F9) {
+        settings.hud.show_sonar = !settings.hud.show_sonar;
+    }
+}
+
+/// Applies the HUD settings to the UI scale and panel nodes; the sonar panel
+/// is sized to the window, so it is laid out again when that is resized
+#[allow(clippy::too_many_arguments)]
+fn apply_hud_layout_system(
+    settings: Res<Settings>,
+    mut ui_scale: ResMut<UiScale>,
+    mut scope: ResMut<SonarScope>,
+    mut window_size: Local<Vec2>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    mut text_query: Query<&mut Node, (With<HudTextPanel>, Without<SonarPanel>)>,
+    mut sonar_query: Query<&mut Node, (With<SonarPanel>, Without<HudTextPanel>)>,
+    spawned_panels: Query<(), Added<HudTextPanel>>,
+) {
+    // Window size in UI pixels, before the UI scale
+    let window = window_query
+        .single()
+        .map_or(Vec2::new(1280.0, 720.0), |window| window.size());
+    let resized = *window_size != window;
+    *window_size = window;
+    // Panels are respawned with every dive, so apply the layout to fresh ones too
+    if !settings.is_changed() && spawned_panels.is_empty() && !resized {
+        return;
+    }
+    let hud = &settings.hud;
+
+    if ui_scale.0 != hud.ui_scale {
+        ui_scale.0 = hud.ui_scale;
+    }
+
+    for mut node in text_query.iter_mut() {
+        node.display = if hud.show_text {
+            Display::Flex
+        } else {
+            Display::None
+        };
+        node.top = Val::Px(HUD_MARGIN);
+        match hud.text_side {
+            HudSide::Left => {
+                node.left = Val::Px(HUD_MARGIN);
+                node.right = Val::Auto;
+            }
+            HudSide::Right => {
+                node.left = Val::Auto;
+                node.right = Val::Px(HUD_MARGIN);
+            }
+        }
+    }
+
+    let available = window / hud.ui_scale;
+    let size = match hud.sonar_size {
+        SonarSize::Small => window.y * SMALL_SONAR,
+        SonarSize::Medium => window.y * MEDIUM_SONAR,
+        // Leave room for the contact depth history hanging underneath
+        SonarSize::Fullscreen => {
+            (available.y - 2.0 * HUD_MARGIN - STRIP_HEIGHT).min(available.x - 2.0 * HUD_MARGIN)
+        }
+    }
+    .max(MIN_SONAR);
+    if scope.size != size {
+        scope.size = size;
+    }
+
+    for mut node in sonar_query.iter_mut() {
+        node.display = if hud.show_sonar {
+            Display::Flex
+        } else {
+            Display::None
+        };
+        node.width = Val::Px(size);
+        node.height = Val::Px(size);
+        if hud.sonar_size == SonarSize::Fullscreen {
+            node.top = Val::Px(HUD_MARGIN);
+            node.bottom = Val::Auto;
+            node.left = Val::Px((available.x - size) / 2.0);
+            node.right = Val::Auto;
+            continue;
+        }
+        let (top, bottom) = match hud.sonar_corner {
+            HudCorner::TopLeft | HudCorner::TopRight => (Val::Px(HUD_MARGIN), Val::Auto),
+            // Leave room for the contact depth history hanging underneath
+            HudCorner::BottomLeft | HudCorner::BottomRight => {
+                (Val::Auto, Val::Px(HUD_MARGIN + STRIP_HEIGHT))
+            }
+        };
+        let (left, right) = match hud.sonar_corner {
+            HudCorner::TopLeft | HudCorner::BottomLeft => (Val::Px(HUD_MARGIN), Val::Auto),
+            HudCorner::TopRight | HudCorner::BottomRight => (Val::Auto, Val::Px(HUD_MARGIN)),
+        };
+        node.top = top;
+        node.bottom = bottom;
+        node.left = left;
+        node.right = right;
+    }
+}
+
+/// Dims the dive behind a centered summary of the run that just ended
+pub fn spawn_game_over_screen(commands: &mut Commands, font: Handle<Font>, message: String) {
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0.1, 0.0, 0.0, 0.6)),
+            GlobalZIndex(5),
+            GameOverScreen,
+            StateScoped(AppState::InGame),
+        ))
+        .with_children(|screen| {
+            screen.spawn((
+                Text::new(
+                    message + "\n\nPress Enter to return to the main menu, or R to dive again",
+                ),
+                TextFont {
+                    font_size: 24.0,
+                    font,
+                    ..default()
+                },
+                TextColor(Color::WHITE),
+                TextLayout::new_with_justify(JustifyText::Center),
+            ));
+        });
+}
+
+/// A restart keeps the mode, the New Game Plus cycle and ironman, and skips
+/// the intro flyover already seen
+fn game_over_input_system(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut next_state: ResMut<NextState<AppState>>,
+    mode: Res<GameMode>,
+    new_game_plus: Res<NewGamePlus>,
+    ironman: Res<Ironman>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Enter) {
+        next_state.set(AppState::MainMenu);
+    } else if keyboard_input.just_pressed(KeyCode::KeyR) {
+        start_new_game(&mut commands, &mut next_state, *mode);
+        commands.insert_resource(*new_game_plus);
+        commands.insert_resource(*ironman);
+        commands.remove_resource::<PlayIntro>();
+    }
+}
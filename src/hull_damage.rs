@@ -0,0 +1,346 @@
+//! Hull damage you can see: as the hull loses strength the boat shows it,
+//! scuffed to bare metal first, then dented where it was struck, and at last
+//! breached, with air jetting out in streams of bubbles from the latest hits.
+//! Strikes are placed from the contacts that caused them, so the marks sit
+//! where the boat actually met the rocks or was rammed.
+
+use crate::classes::SubmarineClass;
+use crate::combat::HostileAttack;
+use crate::{
+    emit_bubble, AppState, Bubble, BubblePool, Fish, GameAssets, GameSet, GameState, Submarine,
+    HULL_END_OFFSET,
+};
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use bevy_rapier3d::rapier::geometry::CollisionEventFlags;
+
+const SCRATCHED_HEALTH: f32 = 75.0; // Hull percent below which the paint is scuffed through
+const DENTED_HEALTH: f32 = 50.0; // Below this the strikes show as dents
+const BREACHED_HEALTH: f32 = 25.0; // Below this the worst of them leak air
+const MAX_IMPACTS: usize = 6; // Strikes remembered; older ones give way to new
+const JETTING_BREACHES: usize = 3; // The latest strikes leak air once breached
+const JET_INTERVAL: f32 = 0.1; // Seconds between bubbles from each breach
+const JET_BUBBLE_LIFE: f32 = 2.0; // Seconds a leaked bubble rises for
+const HULL_RADIUS: f32 = 0.7; // Of the patrol boat, scaled by the class
+const COAT_RADIUS: f32 = 0.715; // Just over the paint, and under any growth
+const DENT_SIZE: f32 = 0.22; // Radius of a dent mark
+
+/// How battered the hull looks, worst last
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Default)]
+pub enum HullCondition {
+    #[default]
+    Intact,
+    Scratched,
+    Dented,
+    Breached,
+}
+
+impl HullCondition {
+    pub fn from_health(health: f32) -> Self {
+        if health < BREACHED_HEALTH {
+            HullCondition::Breached
+        } else if health < DENTED_HEALTH {
+            HullCondition::Dented
+        } else if health < SCRATCHED_HEALTH {
+            HullCondition::Scratched
+        } else {
+            HullCondition::Intact
+        }
+    }
+
+    /// The wear laid over the paint: bare metal, then grime, then rust
+    fn wear(self) -> Color {
+        match self {
+            HullCondition::Intact => Color::srgba(0.7, 0.68, 0.62, 0.0),
+            HullCondition::Scratched => Color::srgba(0.7, 0.68, 0.62, 0.25),
+            HullCondition::Dented => Color::srgba(0.35, 0.32, 0.28, 0.45),
+            HullCondition::Breached => Color::srgba(0.35, 0.18, 0.08, 0.6),
+        }
+    }
+}
+
+/// The hull's condition and where it has been struck, in hull coordinates
+#[derive(Resource, Default)]
+pub struct HullDamage {
+    pub condition: HullCondition,
+    pub impacts: Vec<Vec3>, // Oldest first
+    marks: Vec<Entity>,     // The dent left by each impact
+    jet_timer: f32,
+}
+
+/// Shell over the hull the wear shows on, and the look of a dent. Made once;
+/// there is only ever one boat, so it has the coat material to itself.
+#[derive(Resource)]
+struct WearAssets {
+    coat: Handle<StandardMaterial>,
+    coat_body_mesh: Handle<Mesh>,
+    coat_end_mesh: Handle<Mesh>,
+    dent_mesh: Handle<Mesh>,
+    dent_material: Handle<StandardMaterial>,
+}
+
+impl FromWorld for WearAssets {
+    fn from_world(world: &mut World) -> Self {
+        let mut meshes = world.resource_mut::<Assets<Mesh>>();
+        let coat_body_mesh = meshes.add(Cylinder::new(COAT_RADIUS, 2.0 * HULL_END_OFFSET));
+        let coat_end_mesh = meshes.add(Sphere::new(COAT_RADIUS));
+        let dent_mesh = meshes.add(Sphere::new(1.0));
+        let mut materials = world.resource_mut::<Assets<StandardMaterial>>();
+        Self {
+            coat: materials.add(StandardMaterial {
+                base_color: HullCondition::Intact.wear(),
+                alpha_mode: AlphaMode::Blend,
+                perceptual_roughness: 0.9,
+                ..default()
+            }),
+            coat_body_mesh,
+            coat_end_mesh,
+            dent_mesh,
+            dent_material: materials.add(StandardMaterial {
+                base_color: Color::srgb(0.12, 0.11, 0.1),
+                perceptual_roughness: 1.0,
+                ..default()
+            }),
+        }
+    }
+}
+
+/// A strike's mark on the hull, shown once the hull is dented
+#[derive(Component)]
+struct DentMark;
+
+pub struct HullDamagePlugin;
+
+impl Plugin for HullDamagePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<HullDamage>()
+            .add_systems(
+                OnEnter(AppState::InGame),
+                (reset_hull_damage, spawn_wear_coat.after(crate::setup)),
+            )
+            .add_systems(
+                Update,
+                (
+                    (impact_system, breach_jet_system)
+                        .chain()
+                        .in_set(GameSet::Simulation),
+                    wear_system.in_set(GameSet::Presentation),
+                ),
+            );
+    }
+
+    fn finish(&self, app: &mut App) {
+        app.init_resource::<WearAssets>();
+    }
+}
+
+/// A new dive starts with clean paint
+fn reset_hull_damage(
+    mut commands: Commands,
+    wear: Res<WearAssets>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands.insert_resource(HullDamage::default());
+    if let Some(material) = materials.get_mut(&wear.coat) {
+        material.base_color = HullCondition::Intact.wear();
+    }
+}
+
+/// Gives the submarine a clear shell of wear just over its paint
+fn spawn_wear_coat(
+    mut commands: Commands,
+    wear: Res<WearAssets>,
+    class: Res<SubmarineClass>,
+    submarine_query: Query<Entity, With<Submarine>>,
+) {
+    let Ok(submarine) = submarine_query.single() else {
+        return;
+    };
+    let size = Vec3::splat(class.hull);
+    commands.entity(submarine).with_children(|parent| {
+        parent.spawn((
+            Mesh3d(wear.coat_body_mesh.clone()),
+            MeshMaterial3d(wear.coat.clone()),
+            Transform::from_rotation(Quat::from_rotation_x(std::f32::consts::FRAC_PI_2))
+                .with_scale(size),
+        ));
+        for z in [HULL_END_OFFSET, -HULL_END_OFFSET] {
+            parent.spawn((
+                Mesh3d(wear.coat_end_mesh.clone()),
+                MeshMaterial3d(wear.coat.clone()),
+                Transform::from_xyz(0.0, 0.0, z * class.hull).with_scale(size),
+            ));
+        }
+    });
+}
+
+/// The point on the hull's skin nearest to `local` and the way the skin faces
+/// there, in hull coordinates
+fn on_hull(local: Vec3, size: f32) -> (Vec3, Vec3) {
+    let reach = HULL_END_OFFSET * size;
+    let spine = Vec3::new(0.0, 0.0, local.z.clamp(-reach, reach));
+    let normal = (local - spine).normalize_or(Vec3::Y);
+    (spine + normal * HULL_RADIUS * size, normal)
+}
+
+fn dent_visibility(condition: HullCondition) -> Visibility {
+    if condition >= HullCondition::Dented {
+        Visibility::Inherited
+    } else {
+        Visibility::Hidden
+    }
+}
+
+/// Records where the hull is struck: the nearest point of the rocks it runs
+/// into, or the side a hostile rams it from. Each strike leaves a dent mark
+#[allow(clippy::too_many_arguments)]
+fn impact_system(
+    mut commands: Commands,
+    mut damage: ResMut<HullDamage>,
+    mut collision_events: EventReader<CollisionEvent>,
+    mut attacks: EventReader<HostileAttack>,
+    wear: Res<WearAssets>,
+    class: Res<SubmarineClass>,
+    rapier_context: ReadRapierContext,
+    submarine_query: Query<(Entity, &Transform), With<Submarine>>,
+    fish_query: Query<(), With<Fish>>,
+    other_query: Query<&GlobalTransform>,
+) {
+    let Ok((submarine, transform)) = submarine_query.single() else {
+        collision_events.clear();
+        attacks.clear();
+        return;
+    };
+    let mut struck = Vec::new();
+    for event in collision_events.read() {
+        let CollisionEvent::Started(a, b, flags) = *event else {
+            continue;
+        };
+        if flags.contains(CollisionEventFlags::SENSOR) {
+            continue;
+        }
+        let other = match (a == submarine, b == submarine) {
+            (true, _) => b,
+            (_, true) => a,
+            _ => continue,
+        };
+        if fish_query.contains(other) {
+            continue;
+        }
+        // Rocks can be far bigger than the boat, so the nearest point of what
+        // it hit says more than that thing's middle does
+        let struck_thing = |entity| entity == other;
+        let filter = QueryFilter::default().predicate(&struck_thing);
+        let nearest = rapier_context.single().ok().and_then(|context| {
+            context
+                .project_point(transform.translation, true, filter)
+                .map(|(_, projection)| projection.point)
+        });
+        if let Some(point) = nearest.or(other_query.get(other).ok().map(|o| o.translation())) {
+            struck.push(point);
+        }
+    }
+    for attack in attacks.read() {
+        if attack.prey == submarine {
+            struck.push(attack.position);
+        }
+    }
+
+    for point in struck {
+        let (local, normal) = on_hull(
+            transform.rotation.inverse() * (point - transform.translation),
+            class.hull,
+        );
+        // A dent lies flat along the skin
+        let radius = DENT_SIZE * class.hull;
+        let dent = commands
+            .spawn((
+                Mesh3d(wear.dent_mesh.clone()),
+                MeshMaterial3d(wear.dent_material.clone()),
+                Transform::from_translation(local)
+                    .with_rotation(Quat::from_rotation_arc(Vec3::Z, normal))
+                    .with_scale(Vec3::new(radius, radius, radius * 0.25)),
+                dent_visibility(damage.condition),
+                DentMark,
+            ))
+            .id();
+        commands.entity(submarine).add_child(dent);
+        damage.impacts.push(local);
+        damage.marks.push(dent);
+        if damage.impacts.len() > MAX_IMPACTS {
+            damage.impacts.remove(0);
+            commands.entity(damage.marks.remove(0)).try_despawn();
+        }
+    }
+}
+
+/// Breached, the latest strikes leak air in steady streams of bubbles
+#[allow(clippy::too_many_arguments)]
+fn breach_jet_system(
+    mut commands: Commands,
+    mut damage: ResMut<HullDamage>,
+    mut pool: ResMut<BubblePool>,
+    mut bubbles: Query<(&mut Transform, &mut Bubble, &mut Visibility), Without<Submarine>>,
+    assets: Res<GameAssets>,
+    class: Res<SubmarineClass>,
+    submarine_query: Query<&Transform, With<Submarine>>,
+    time: Res<Time>,
+) {
+    let Ok(transform) = submarine_query.single() else {
+        return;
+    };
+    if damage.condition < HullCondition::Breached || transform.translation.y >= 0.0 {
+        damage.jet_timer = 0.0;
+        return;
+    }
+    damage.jet_timer += time.delta_secs();
+    if damage.jet_timer < JET_INTERVAL {
+        return;
+    }
+    damage.jet_timer -= JET_INTERVAL;
+
+    // A dive loaded from a save doesn't know where it was struck; the air
+    // finds its way out at the top of the hull
+    let fallback = [Vec3::Y * HULL_RADIUS * class.hull];
+    let breaches = if damage.impacts.is_empty() {
+        &fallback[..]
+    } else {
+        let start = damage.impacts.len().saturating_sub(JETTING_BREACHES);
+        &damage.impacts[start..]
+    };
+    for breach in breaches {
+        let position = transform.transform_point(*breach);
+        let radius = 0.05 + rand::random::<f32>() * 0.05;
+        emit_bubble(
+            &mut commands,
+            &mut pool,
+            &mut bubbles,
+            &assets,
+            position,
+            radius,
+            JET_BUBBLE_LIFE,
+        );
+    }
+}
+
+/// Judges the hull's condition from its strength, lays the wear for it over
+/// the paint and shows the dents once it has taken enough to dent
+fn wear_system(
+    mut damage: ResMut<HullDamage>,
+    game_state: Res<GameState>,
+    wear: Res<WearAssets>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut dent_query: Query<&mut Visibility, With<DentMark>>,
+) {
+    let condition = HullCondition::from_health(game_state.health);
+    if condition == damage.condition {
+        return;
+    }
+    damage.condition = condition;
+    if let Some(material) = materials.get_mut(&wear.coat) {
+        material.base_color = condition.wear();
+    }
+    for mut dent in dent_query.iter_mut() {
+        dent.set_if_neq(dent_visibility(condition));
+    }
+}
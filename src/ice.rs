@@ -0,0 +1,121 @@
+//! Pack ice: fields of floes lying on the surface with leads and open pools
+//! between them. The floes are solid, so a boat under the ice can only come up
+//! for air where the upward sonar shows open water overhead.
+
+use crate::tables::Tables;
+use crate::{AppState, WorldSeed};
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+const FIELD_CELLS: usize = 7; // Floes along each side of a field
+const CELL_SIZE: f32 = 16.0; // Meters each floe's cell spans
+const OPEN_CHANCE: f32 = 0.2; // Chance a cell is left as an open pool
+const FREEBOARD: f32 = 0.3; // Meters a floe stands above the water
+const DRAFT: (f32, f32) = (1.0, 3.5); // Meters a floe reaches below the surface
+
+/// A slab of ice floating at the surface
+#[derive(Component)]
+pub struct IceFloe {
+    pub half_size: Vec2, // Meters from the middle to the edges, along x and z
+    pub draft: f32,      // Meters below the surface the underside reaches
+}
+
+/// Meters of ice below the surface over a point, or 0 in open water
+pub fn ice_draft<'a>(
+    floes: impl IntoIterator<Item = (&'a Transform, &'a IceFloe)>,
+    x: f32,
+    z: f32,
+) -> f32 {
+    floes
+        .into_iter()
+        .filter(|(transform, floe)| {
+            let offset = Vec2::new(x - transform.translation.x, z - transform.translation.z);
+            offset.x.abs() <= floe.half_size.x && offset.y.abs() <= floe.half_size.y
+        })
+        .map(|(_, floe)| floe.draft)
+        .fold(0.0, f32::max)
+}
+
+/// Floes are unit cubes scaled to size; made once
+#[derive(Resource)]
+struct IceAssets {
+    floe_mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+}
+
+impl FromWorld for IceAssets {
+    fn from_world(world: &mut World) -> Self {
+        let floe_mesh = world
+            .resource_mut::<Assets<Mesh>>()
+            .add(Cuboid::new(1.0, 1.0, 1.0));
+        let material = world
+            .resource_mut::<Assets<StandardMaterial>>()
+            .add(StandardMaterial {
+                base_color: Color::srgb(0.85, 0.92, 0.97),
+                perceptual_roughness: 0.6,
+                ..default()
+            });
+        Self {
+            floe_mesh,
+            material,
+        }
+    }
+}
+
+pub struct IcePlugin;
+
+impl Plugin for IcePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(AppState::InGame), spawn_ice.after(crate::setup));
+    }
+
+    fn finish(&self, app: &mut App) {
+        app.init_resource::<IceAssets>();
+    }
+}
+
+/// Square fields of floes away from the start, each with a few cells left open
+fn spawn_ice(
+    mut commands: Commands,
+    assets: Res<IceAssets>,
+    seed: Res<WorldSeed>,
+    tables: Res<Tables>,
+) {
+    let mut rng = StdRng::seed_from_u64(seed.0 ^ 0x4943_4546);
+    let field_size = FIELD_CELLS as f32 * CELL_SIZE;
+
+    for _ in 0..tables.spawns.ice_fields {
+        let angle = rng.gen::<f32>() * std::f32::consts::TAU;
+        let distance = rng.gen_range(100.0..220.0);
+        let corner = Vec2::from_angle(angle) * distance - Vec2::splat(field_size / 2.0);
+        for row in 0..FIELD_CELLS {
+            for column in 0..FIELD_CELLS {
+                if rng.gen::<f32>() < OPEN_CHANCE {
+                    continue;
+                }
+                // Each floe falls a little short of its cell, leaving narrow leads
+                let size = Vec2::new(
+                    CELL_SIZE - rng.gen_range(0.5..3.0),
+                    CELL_SIZE - rng.gen_range(0.5..3.0),
+                );
+                let middle = corner + (Vec2::new(column as f32, row as f32) + 0.5) * CELL_SIZE;
+                let draft = rng.gen_range(DRAFT.0..DRAFT.1);
+                let thickness = draft + FREEBOARD;
+                commands.spawn((
+                    Mesh3d(assets.floe_mesh.clone()),
+                    MeshMaterial3d(assets.material.clone()),
+                    Transform::from_xyz(middle.x, FREEBOARD - thickness / 2.0, middle.y)
+                        .with_scale(Vec3::new(size.x, thickness, size.y)),
+                    RigidBody::Fixed,
+                    Collider::cuboid(0.5, 0.5, 0.5),
+                    IceFloe {
+                        half_size: size / 2.0,
+                        draft,
+                    },
+                    StateScoped(AppState::InGame),
+                ));
+            }
+        }
+    }
+}
@@ -0,0 +1,222 @@
+//! Scripted keyboard input: a timed list of synthetic key presses and releases
+//! fed into the same `ButtonInput<KeyCode>` the real keyboard writes, so every
+//! input system downstream behaves exactly as if a player were at the keys.
+//! Combined with `--headless` this drives dives from tests and the command line.
+
+use crate::save::PendingLoad;
+use crate::{AppState, GameMode, PauseState, WorldSeed};
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// One synthetic key event, `time` seconds into the dive
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct ScriptedKey {
+    pub time: f32,
+    pub key: KeyCode,
+    pub pressed: bool, // False releases the key
+}
+
+/// Key events played back in time order once a dive starts
+#[derive(Resource, Clone, Default, Serialize, Deserialize)]
+pub struct InputScript {
+    #[serde(default)]
+    pub seed: Option<u64>, // Terrain seed; a random one is rolled when missing
+    pub keys: Vec<ScriptedKey>,
+    #[serde(default)]
+    pub quit_after: Option<f32>, // Exit the game this many seconds into the dive
+}
+
+impl InputScript {
+    /// Reads a script file
+    pub fn load(path: &str) -> Result<Self, String> {
+        let contents = crate::storage::read(path).ok_or_else(|| format!("cannot read {}", path))?;
+        let mut script: InputScript = ron::Options::default()
+            .with_default_extension(ron::extensions::Extensions::IMPLICIT_SOME)
+            .from_str(&contents)
+            .map_err(|err| format!("{}: {}", path, err))?;
+        script.sort();
+        Ok(script)
+    }
+
+    fn sort(&mut self) {
+        // Stable, so a press and release at the same time stay in that order
+        self.keys.sort_by(|a, b| a.time.total_cmp(&b.time));
+    }
+}
+
+/// Builders for scripts written in code
+impl InputScript {
+    /// Holds `key` down from `start` until `end` seconds into the dive
+    pub fn hold(mut self, key: KeyCode, start: f32, end: f32) -> Self {
+        self.keys.push(ScriptedKey {
+            time: start,
+            key,
+            pressed: true,
+        });
+        self.keys.push(ScriptedKey {
+            time: end,
+            key,
+            pressed: false,
+        });
+        self.sort();
+        self
+    }
+
+    /// A single press and release, for toggles and other one-shot actions
+    pub fn tap(self, key: KeyCode, time: f32) -> Self {
+        self.hold(key, time, time)
+    }
+}
+
+/// How far playback has got through the script in the current dive
+#[derive(Resource, Default)]
+struct InputScriptPlayback {
+    elapsed: f32,
+    next: usize,
+    released: Vec<KeyCode>, // Released at the start of the next frame, after being seen pressed
+}
+
+pub struct InputScriptPlugin;
+
+impl Plugin for InputScriptPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<InputScriptPlayback>()
+            .add_systems(OnEnter(AppState::InGame), reset_playback)
+            .add_systems(
+                PreUpdate,
+                play_input_script
+                    .after(InputSystem)
+                    .run_if(resource_exists::<InputScript>)
+                    .run_if(in_state(AppState::InGame))
+                    .run_if(in_state(PauseState::Running)),
+            );
+    }
+}
+
+/// Skips the main menu and starts a dive that the script will drive
+pub fn start(app: &mut App, script: InputScript) {
+    if let Some(seed) = script.seed {
+        app.insert_resource(WorldSeed(seed));
+    }
+    app.insert_resource(GameMode::Dive)
+        .insert_resource(PendingLoad(None))
+        .insert_resource(script);
+    app.world_mut()
+        .resource_mut::<NextState<AppState>>()
+        .set(AppState::Loading);
+}
+
+fn reset_playback(mut playback: ResMut<InputScriptPlayback>) {
+    *playback = InputScriptPlayback::default();
+}
+
+/// Presses and releases the keys that are due, right after the real keyboard
+/// has been read so the synthetic state isn't cleared before Update sees it
+fn play_input_script(
+    script: Res<InputScript>,
+    mut playback: ResMut<InputScriptPlayback>,
+    mut keyboard_input: ResMut<ButtonInput<KeyCode>>,
+    time: Res<Time>,
+    mut exit: EventWriter<AppExit>,
+) {
+    for key in std::mem::take(&mut playback.released) {
+        keyboard_input.release(key);
+    }
+    while let Some(event) = script.keys.get(playback.next) {
+        if event.time > playback.elapsed {
+            break;
+        }
+        if event.pressed {
+            keyboard_input.press(event.key);
+        } else if keyboard_input.just_pressed(event.key) {
+            // A tap still has to be visible for one frame
+            playback.released.push(event.key);
+        } else {
+            keyboard_input.release(event.key);
+        }
+        playback.next += 1;
+    }
+    if script
+        .quit_after
+        .is_some_and(|quit_after| playback.elapsed >= quit_after)
+    {
+        info!("Input script finished after {:.1} s", playback.elapsed);
+        exit.write(AppExit::Success);
+    }
+    playback.elapsed += time.delta_secs();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{build_app, Args, BallastState, Submarine};
+    use bevy::time::TimeUpdateStrategy;
+    use clap::Parser;
+    use std::time::Duration;
+
+    const FRAME_RATE: f32 = 60.0;
+
+    /// A headless game running `script`, loaded and at the start of its dive
+    fn scripted_app(script: InputScript) -> App {
+        let mut app = build_app(Args::parse_from(["submarine", "--headless"]));
+        app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(
+            1.0 / FRAME_RATE,
+        )));
+        start(&mut app, script);
+        app.finish();
+        app.cleanup();
+        while *app.world().resource::<State<AppState>>() != AppState::InGame {
+            app.update();
+        }
+        app
+    }
+
+    fn run_for(app: &mut App, seconds: f32) {
+        for _ in 0..(seconds * FRAME_RATE) as usize {
+            app.update();
+        }
+    }
+
+    fn depth(app: &mut App) -> f32 {
+        let mut query = app
+            .world_mut()
+            .query_filtered::<&Transform, With<Submarine>>();
+        -query.single(app.world()).unwrap().translation.y
+    }
+
+    #[test]
+    fn open_vents_dive() {
+        let mut app = scripted_app(InputScript::default().tap(KeyCode::KeyQ, 0.0));
+        run_for(&mut app, 3.0);
+        assert!(app.world().resource::<BallastState>().vents_open);
+        let depth = depth(&mut app);
+        assert!(depth > 1.0, "submarine only reached {:.2} m", depth);
+    }
+
+    #[test]
+    fn closed_vents_stay_surfaced() {
+        let mut app = scripted_app(InputScript::default());
+        run_for(&mut app, 3.0);
+        assert!(!app.world().resource::<BallastState>().vents_open);
+        let depth = depth(&mut app);
+        assert!(depth < 1.0, "submarine sank to {:.2} m", depth);
+    }
+
+    #[test]
+    fn held_throttle_moves_forward() {
+        let mut app = scripted_app(InputScript::default().hold(KeyCode::KeyW, 0.0, 2.0));
+        let mut query = app
+            .world_mut()
+            .query_filtered::<&Transform, With<Submarine>>();
+        run_for(&mut app, 0.1);
+        let start = query.single(app.world()).unwrap().translation;
+        run_for(&mut app, 2.0);
+        let moved = query.single(app.world()).unwrap().translation - start;
+        assert!(
+            moved.xz().length() > 1.0,
+            "submarine only moved {:.2} m",
+            moved.xz().length()
+        );
+    }
+}
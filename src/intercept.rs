@@ -0,0 +1,251 @@
+//! Intercept plot: with a sonar contact selected as the target, successive
+//! sonar fixes give its bearing, range and how fast the range is closing, and
+//! an estimate of its course and speed. From those the plot works out the
+//! heading that meets it at the boat's full speed, which the autopilot can
+//! be ordered to steer.
+
+use crate::contact_history::SelectedContact;
+use crate::controls::{key_name, Action, HelmInput, HelmState, InputMap};
+use crate::magnetics::Navigation;
+use crate::{
+    AppState, GameAssets, GameSet, SonarDetections, Submarine, Tuning, SONAR_CENTER_X,
+    SONAR_CENTER_Y, SONAR_RADIUS,
+};
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use std::f32::consts::FRAC_PI_2;
+
+const TRACK_SMOOTHING: f32 = 1.5; // How quickly the estimated target motion follows new fixes
+const MAX_INTERCEPT_TIME: f32 = 120.0; // Seconds beyond which the plot just points at the target
+
+/// The plot on the selected contact, while it is held on sonar
+#[derive(Resource, Default)]
+pub struct InterceptPlot {
+    pub solution: Option<Solution>,
+    last_fix: Option<(Entity, Vec3)>,
+    target_velocity: Vec3,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Solution {
+    pub bearing: f32,      // Yaw from the boat to the target, in radians
+    pub range: f32,        // Meters, level
+    pub closure: f32,      // Meters per second the range is closing; negative is opening
+    pub heading: f32,      // Yaw that meets the target, in radians
+    pub time: Option<f32>, // Seconds to the meeting, if the boat can catch it
+}
+
+#[derive(Component)]
+struct InterceptText;
+
+pub struct InterceptPlugin;
+
+impl Plugin for InterceptPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<InterceptPlot>()
+            .add_systems(
+                OnEnter(AppState::InGame),
+                (reset_intercept_plot, spawn_intercept_text),
+            )
+            .add_systems(
+                Update,
+                (
+                    intercept_steering_system
+                        .after(crate::controls::helm_command_system)
+                        .before(crate::submarine_movement)
+                        .in_set(GameSet::Simulation),
+                    plot_system
+                        .after(crate::sonar_detection_system)
+                        .in_set(GameSet::Detection),
+                    intercept_text_system.in_set(GameSet::Presentation),
+                ),
+            );
+    }
+}
+
+fn reset_intercept_plot(mut commands: Commands) {
+    commands.insert_resource(InterceptPlot::default());
+}
+
+fn spawn_intercept_text(mut commands: Commands, assets: Res<GameAssets>) {
+    commands.spawn((
+        Text::new(""),
+        TextFont {
+            font_size: 16.0,
+            font: assets.font.clone(),
+            ..default()
+        },
+        TextColor(Color::srgb(1.0, 0.75, 0.1)),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Percent(63.0),
+            left: Val::Px(16.0),
+            ..default()
+        },
+        InterceptText,
+        StateScoped(AppState::InGame),
+    ));
+}
+
+/// Time for a boat at `speed` to meet a target at `offset` moving at
+/// `velocity`, if it can
+fn intercept_time(offset: Vec2, velocity: Vec2, speed: f32) -> Option<f32> {
+    let a = velocity.length_squared() - speed * speed;
+    let b = 2.0 * offset.dot(velocity);
+    let c = offset.length_squared();
+    if a.abs() < 1e-4 {
+        return (b < 0.0).then(|| -c / b);
+    }
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let root = discriminant.sqrt();
+    [(-b - root) / (2.0 * a), (-b + root) / (2.0 * a)]
+        .into_iter()
+        .filter(|time| *time > 0.0)
+        .min_by(f32::total_cmp)
+}
+
+/// Yaw facing along a level offset, forward being -Z
+fn yaw_towards(offset: Vec2) -> f32 {
+    (-offset.x).atan2(-offset.y)
+}
+
+/// Places the selected contact from its sonar return and works the intercept
+fn plot_system(
+    mut plot: ResMut<InterceptPlot>,
+    selected: Res<SelectedContact>,
+    detections: Res<SonarDetections>,
+    tuning: Res<Tuning>,
+    submarine_query: Query<(&Transform, &Velocity), With<Submarine>>,
+    time: Res<Time>,
+) {
+    let contact = selected.0.and_then(|target| {
+        detections
+            .contacts
+            .iter()
+            .find(|contact| contact.target == Some(target))
+            .map(|contact| (target, contact))
+    });
+    let (Some((target, contact)), Ok((submarine, velocity))) = (contact, submarine_query.single())
+    else {
+        plot.solution = None;
+        plot.last_fix = None;
+        return;
+    };
+
+    // The return gives the bearing off the bow and the range as drawn on the display
+    let range = Vec2::new(contact.x - SONAR_CENTER_X, contact.y - SONAR_CENTER_Y).length()
+        / SONAR_RADIUS
+        * tuning.sonar_range;
+    let off_bow = contact.angle - FRAC_PI_2;
+    let yaw = submarine.rotation.to_euler(EulerRot::YXZ).0;
+    let position = submarine.translation
+        + Quat::from_rotation_y(yaw) * Vec3::new(-off_bow.sin(), 0.0, -off_bow.cos()) * range;
+
+    let delta = time.delta_secs();
+    match plot.last_fix {
+        Some((last_target, last_position)) if last_target == target && delta > 0.0 => {
+            let measured = (position - last_position) / delta;
+            let blend = (TRACK_SMOOTHING * delta).min(1.0);
+            plot.target_velocity = plot.target_velocity.lerp(measured, blend);
+        }
+        _ => plot.target_velocity = Vec3::ZERO,
+    }
+    plot.last_fix = Some((target, position));
+
+    let offset = Vec2::new(
+        position.x - submarine.translation.x,
+        position.z - submarine.translation.z,
+    );
+    let target_velocity = Vec2::new(plot.target_velocity.x, plot.target_velocity.z);
+    let own_velocity = Vec2::new(velocity.linvel.x, velocity.linvel.z);
+    let closure = if offset.length() > 0.0 {
+        -offset.normalize().dot(target_velocity - own_velocity)
+    } else {
+        0.0
+    };
+    let time = intercept_time(offset, target_velocity, tuning.speed)
+        .filter(|time| *time < MAX_INTERCEPT_TIME);
+    let aim = offset + target_velocity * time.unwrap_or(0.0);
+    plot.solution = Some(Solution {
+        bearing: yaw_towards(offset),
+        range: offset.length(),
+        closure,
+        heading: yaw_towards(aim),
+        time,
+    });
+}
+
+/// Points the autopilot along the intercept; the autopilot steers by the
+/// compass, so the course is set as the compass would read it. Putting the
+/// rudder over by hand breaks off the intercept
+fn intercept_steering_system(
+    plot: Res<InterceptPlot>,
+    navigation: Res<Navigation>,
+    helm_input: Res<HelmInput>,
+    mut helm_state: ResMut<HelmState>,
+) {
+    if !helm_state.intercept {
+        return;
+    }
+    if helm_input.turn != 0.0 || !helm_state.autopilot {
+        helm_state.intercept = false;
+        return;
+    }
+    if let Some(solution) = plot.solution {
+        helm_state.autopilot_heading = solution.heading + navigation.compass_error;
+    }
+}
+
+fn intercept_text_system(
+    plot: Res<InterceptPlot>,
+    selected: Res<SelectedContact>,
+    helm_state: Res<HelmState>,
+    navigation: Res<Navigation>,
+    input_map: Res<InputMap>,
+    mut text_query: Query<&mut Text, With<InterceptText>>,
+) {
+    let Ok(mut text) = text_query.single_mut() else {
+        return;
+    };
+    let line = match (selected.0, plot.solution) {
+        (None, _) => String::new(),
+        (Some(_), None) if helm_state.intercept => "Target lost - holding last course".to_string(),
+        (Some(_), None) => "Target lost".to_string(),
+        (Some(_), Some(solution)) => {
+            let meeting = match solution.time {
+                Some(time) => format!("in {:.0} s", time),
+                None => "(pursuit, too fast to catch)".to_string(),
+            };
+            let steering = if helm_state.intercept && helm_state.autopilot {
+                " [steering]".to_string()
+            } else {
+                let key = input_map
+                    .binding(Action::Intercept)
+                    .primary
+                    .map(key_name)
+                    .unwrap_or_default();
+                format!(" - {} to steer", key)
+            };
+            format!(
+                "Target {:03.0}° {:.0} m, {} {:.1} m/s\nIntercept {:03.0}° {}{}",
+                navigation.compass_heading(solution.bearing),
+                solution.range,
+                if solution.closure >= 0.0 {
+                    "closing"
+                } else {
+                    "opening"
+                },
+                solution.closure.abs(),
+                navigation.compass_heading(solution.heading),
+                meeting,
+                steering
+            )
+        }
+    };
+    if **text != line {
+        **text = line;
+    }
+}
@@ -0,0 +1,342 @@
+//! The control room console: valve wheels, the compressor switch, the engine
+//! telegraph and gauges for depth, air, battery and ballast, seen from the
+//! control room view. Each instrument follows the state it shows, and with the
+//! cursor free (backslash) the controls can be clicked to operate them.
+
+use crate::controls::{ControlCommand, HelmState};
+use crate::{BallastState, CameraFollow, CameraMode, GameSet, Submarine};
+use bevy::picking::mesh_picking::{MeshPickingCamera, MeshPickingPlugin, MeshPickingSettings};
+use bevy::picking::PickingPlugin;
+use bevy::prelude::*;
+use std::f32::consts::{FRAC_PI_2, PI};
+
+const CONSOLE_POSITION: Vec3 = Vec3::new(0.0, 0.0, -2.3); // In front of the control room eye point
+const GAUGE_RADIUS: f32 = 0.045;
+const WHEEL_RADIUS: f32 = 0.07;
+const WHEEL_TURN: f32 = 1.5 * PI; // Radians a valve wheel turns from shut to open
+const GAUGE_SWEEP: f32 = 1.5 * PI; // Radians a needle sweeps from empty to full
+const GAUGE_DEPTH: f32 = 30.0; // Meters at full scale on the depth gauge
+const LEVER_SWING: f32 = 0.8; // Radians the telegraph lever leans at full ahead or astern
+const SWITCH_THROW: f32 = 0.5; // Radians the compressor switch leans when on
+const EASE_RATE: f32 = 6.0; // How quickly moving parts catch up with the state they show
+
+/// A moving part of the console and what it shows
+#[derive(Component, Clone, Copy, PartialEq, Debug)]
+enum Instrument {
+    VentWheel,
+    AirValveWheel,
+    CompressorSwitch,
+    TelegraphLever,
+    DepthGauge,
+    AirGauge,
+    BatteryGauge,
+    BallastGauge,
+}
+
+impl Instrument {
+    /// Angle about the console's facing axis for the current state
+    fn target_angle(self, ballast_state: &BallastState, helm_state: &HelmState, depth: f32) -> f32 {
+        let gauge = |value: f32| GAUGE_SWEEP / 2.0 - GAUGE_SWEEP * value.clamp(0.0, 1.0);
+        let open = |open: bool| if open { WHEEL_TURN } else { 0.0 };
+        match self {
+            Instrument::VentWheel => open(ballast_state.vents_open),
+            Instrument::AirValveWheel => open(ballast_state.air_valve_open),
+            Instrument::CompressorSwitch => {
+                if ballast_state.compressor_on {
+                    -SWITCH_THROW
+                } else {
+                    SWITCH_THROW
+                }
+            }
+            Instrument::TelegraphLever => -LEVER_SWING * helm_state.telegraph.throttle(),
+            Instrument::DepthGauge => gauge(depth / GAUGE_DEPTH),
+            Instrument::AirGauge => gauge(ballast_state.compressed_air),
+            Instrument::BatteryGauge => gauge(ballast_state.electricity / 100.0),
+            Instrument::BallastGauge => gauge(ballast_state.fill_level),
+        }
+    }
+
+    /// The order a click on this instrument gives; the telegraph rings ahead
+    /// on the left button and astern on the right
+    fn command(self, button: PointerButton) -> Option<ControlCommand> {
+        match (self, button) {
+            (Instrument::VentWheel, _) => Some(ControlCommand::ToggleVents),
+            (Instrument::AirValveWheel, _) => Some(ControlCommand::ToggleAirValve),
+            (Instrument::CompressorSwitch, _) => Some(ControlCommand::ToggleCompressor),
+            (Instrument::TelegraphLever, PointerButton::Primary) => {
+                Some(ControlCommand::TelegraphAhead)
+            }
+            (Instrument::TelegraphLever, PointerButton::Secondary) => {
+                Some(ControlCommand::TelegraphAstern)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Current angle of a moving part, eased towards its target
+#[derive(Component, Default)]
+struct InstrumentAngle(f32);
+
+/// Root of the console; shown only from the control room
+#[derive(Component)]
+struct Console;
+
+/// The console's parts, made once; every material is unlit
+#[derive(Resource)]
+struct ConsoleAssets {
+    panel_mesh: Handle<Mesh>,
+    gauge_face_mesh: Handle<Mesh>,
+    gauge_needle_mesh: Handle<Mesh>,
+    wheel_rim_mesh: Handle<Mesh>,
+    wheel_spoke_mesh: Handle<Mesh>,
+    lever_mesh: Handle<Mesh>, // Unit length, scaled to each lever
+    lever_knob_mesh: Handle<Mesh>,
+    panel_material: Handle<StandardMaterial>,
+    gauge_face_material: Handle<StandardMaterial>,
+    gauge_needle_material: Handle<StandardMaterial>,
+    brass_material: Handle<StandardMaterial>,
+    red_material: Handle<StandardMaterial>,
+}
+
+impl FromWorld for ConsoleAssets {
+    fn from_world(world: &mut World) -> Self {
+        let mut meshes = world.resource_mut::<Assets<Mesh>>();
+        let panel_mesh = meshes.add(Cuboid::new(0.55, 0.14, 0.01));
+        let gauge_face_mesh = meshes.add(Cylinder::new(GAUGE_RADIUS, 0.005));
+        let gauge_needle_mesh = meshes.add(Cuboid::new(0.004, GAUGE_RADIUS * 0.8, 0.002));
+        let wheel_rim_mesh = meshes.add(Torus::new(WHEEL_RADIUS - 0.012, WHEEL_RADIUS));
+        let wheel_spoke_mesh = meshes.add(Cuboid::new(WHEEL_RADIUS * 2.0, 0.01, 0.01));
+        let lever_mesh = meshes.add(Cuboid::new(0.015, 1.0, 0.015));
+        let lever_knob_mesh = meshes.add(Sphere::new(0.015));
+        let mut materials = world.resource_mut::<Assets<StandardMaterial>>();
+        let mut unlit = |color: Color| {
+            materials.add(StandardMaterial {
+                base_color: color,
+                unlit: true,
+                ..default()
+            })
+        };
+        Self {
+            panel_mesh,
+            gauge_face_mesh,
+            gauge_needle_mesh,
+            wheel_rim_mesh,
+            wheel_spoke_mesh,
+            lever_mesh,
+            lever_knob_mesh,
+            panel_material: unlit(Color::srgb(0.2, 0.22, 0.2)),
+            gauge_face_material: unlit(Color::srgb(0.9, 0.88, 0.8)),
+            gauge_needle_material: unlit(Color::srgb(0.1, 0.1, 0.1)),
+            brass_material: unlit(Color::srgb(0.7, 0.55, 0.25)),
+            red_material: unlit(Color::srgb(0.7, 0.1, 0.1)),
+        }
+    }
+}
+
+pub struct InteriorPlugin;
+
+impl Plugin for InteriorPlugin {
+    fn build(&self, app: &mut App) {
+        // Headless apps have no pointer to pick with
+        if app.is_plugin_added::<PickingPlugin>() {
+            app.add_plugins(MeshPickingPlugin)
+                .insert_resource(MeshPickingSettings {
+                    require_markers: true,
+                    ..default()
+                });
+        }
+        app.add_systems(Update, spawn_console)
+            .add_systems(Update, instrument_system.in_set(GameSet::Presentation));
+    }
+
+    fn finish(&self, app: &mut App) {
+        app.init_resource::<ConsoleAssets>();
+    }
+}
+
+/// Builds the console inside each new submarine
+fn spawn_console(
+    mut commands: Commands,
+    assets: Res<ConsoleAssets>,
+    submarine_query: Query<Entity, Added<Submarine>>,
+    camera_query: Query<Entity, With<CameraFollow>>,
+) {
+    let Some(submarine) = submarine_query.iter().next() else {
+        return;
+    };
+    for camera in camera_query.iter() {
+        commands.entity(camera).insert(MeshPickingCamera);
+    }
+
+    let console = commands
+        .spawn((
+            Transform::from_translation(CONSOLE_POSITION),
+            Visibility::Hidden,
+            Console,
+            ChildOf(submarine),
+        ))
+        .id();
+
+    // Backing plate behind the gauges
+    commands.spawn((
+        Mesh3d(assets.panel_mesh.clone()),
+        MeshMaterial3d(assets.panel_material.clone()),
+        Transform::from_xyz(0.0, 0.12, -0.01),
+        ChildOf(console),
+    ));
+
+    // Gauges: a face with a needle turning about its centre
+    let gauges = [
+        (Instrument::AirGauge, -0.18),
+        (Instrument::DepthGauge, -0.06),
+        (Instrument::BallastGauge, 0.06),
+        (Instrument::BatteryGauge, 0.18),
+    ];
+    for (instrument, x) in gauges {
+        commands.spawn((
+            Mesh3d(assets.gauge_face_mesh.clone()),
+            MeshMaterial3d(assets.gauge_face_material.clone()),
+            Transform::from_xyz(x, 0.12, 0.0).with_rotation(Quat::from_rotation_x(FRAC_PI_2)),
+            ChildOf(console),
+        ));
+        commands
+            .spawn((
+                Transform::from_xyz(x, 0.12, 0.004),
+                Visibility::default(),
+                instrument,
+                InstrumentAngle::default(),
+                ChildOf(console),
+            ))
+            .with_child((
+                Mesh3d(assets.gauge_needle_mesh.clone()),
+                MeshMaterial3d(assets.gauge_needle_material.clone()),
+                Transform::from_xyz(0.0, GAUGE_RADIUS * 0.4, 0.0),
+            ));
+    }
+
+    // Valve wheels: a rim with crossed spokes, so turning shows
+    for (instrument, x, material) in [
+        (Instrument::VentWheel, -0.38, &assets.red_material),
+        (Instrument::AirValveWheel, 0.38, &assets.brass_material),
+    ] {
+        commands
+            .spawn((
+                Transform::from_xyz(x, 0.22, 0.0),
+                Visibility::default(),
+                instrument,
+                InstrumentAngle::default(),
+                ChildOf(console),
+            ))
+            .observe(operate_instrument)
+            .with_children(|wheel| {
+                wheel.spawn((
+                    Mesh3d(assets.wheel_rim_mesh.clone()),
+                    MeshMaterial3d(material.clone()),
+                    Transform::from_rotation(Quat::from_rotation_x(FRAC_PI_2)),
+                    Pickable::default(),
+                ));
+                for angle in [0.0, FRAC_PI_2] {
+                    wheel.spawn((
+                        Mesh3d(assets.wheel_spoke_mesh.clone()),
+                        MeshMaterial3d(material.clone()),
+                        Transform::from_rotation(Quat::from_rotation_z(angle)),
+                        Pickable::default(),
+                    ));
+                }
+            });
+    }
+
+    // Levers: the telegraph and the compressor switch, pivoting at their base
+    for (instrument, x, length, material) in [
+        (
+            Instrument::TelegraphLever,
+            -0.3,
+            0.12,
+            &assets.brass_material,
+        ),
+        (
+            Instrument::CompressorSwitch,
+            0.3,
+            0.07,
+            &assets.red_material,
+        ),
+    ] {
+        commands
+            .spawn((
+                Transform::from_xyz(x, 0.06, 0.0),
+                Visibility::default(),
+                instrument,
+                InstrumentAngle::default(),
+                ChildOf(console),
+            ))
+            .observe(operate_instrument)
+            .with_children(|lever| {
+                lever.spawn((
+                    Mesh3d(assets.lever_mesh.clone()),
+                    MeshMaterial3d(material.clone()),
+                    Transform::from_xyz(0.0, length / 2.0, 0.0)
+                        .with_scale(Vec3::new(1.0, length, 1.0)),
+                    Pickable::default(),
+                ));
+                lever.spawn((
+                    Mesh3d(assets.lever_knob_mesh.clone()),
+                    MeshMaterial3d(material.clone()),
+                    Transform::from_xyz(0.0, length, 0.0),
+                    Pickable::default(),
+                ));
+            });
+    }
+}
+
+/// Clicking a valve wheel, the compressor switch or the telegraph operates it
+fn operate_instrument(
+    mut trigger: Trigger<Pointer<Click>>,
+    instrument_query: Query<&Instrument>,
+    mut commands: EventWriter<ControlCommand>,
+) {
+    let Ok(instrument) = instrument_query.get(trigger.target()) else {
+        return;
+    };
+    trigger.propagate(false);
+    if let Some(command) = instrument.command(trigger.event().button) {
+        commands.write(command);
+    }
+}
+
+/// Shows the console from the control room and turns every moving part
+/// towards the state it reflects
+fn instrument_system(
+    camera_mode: Res<State<CameraMode>>,
+    ballast_state: Res<BallastState>,
+    helm_state: Res<HelmState>,
+    submarine_query: Query<&Transform, With<Submarine>>,
+    mut console_query: Query<&mut Visibility, With<Console>>,
+    mut instrument_query: Query<
+        (&Instrument, &mut InstrumentAngle, &mut Transform),
+        Without<Submarine>,
+    >,
+    time: Res<Time>,
+) {
+    let inside = *camera_mode.get() == CameraMode::ControlRoom;
+    for mut visibility in console_query.iter_mut() {
+        *visibility = if inside {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+    }
+    if !inside {
+        return;
+    }
+    let depth = submarine_query
+        .single()
+        .map(|transform| (-transform.translation.y).max(0.0))
+        .unwrap_or(0.0);
+    let ease = (EASE_RATE * time.delta_secs()).min(1.0);
+    for (instrument, mut angle, mut transform) in instrument_query.iter_mut() {
+        let target = instrument.target_angle(&ballast_state, &helm_state, depth);
+        angle.0 += (target - angle.0) * ease;
+        transform.rotation = Quat::from_rotation_z(angle.0);
+    }
+}
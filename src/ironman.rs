@@ -0,0 +1,186 @@
+//! Ironman: a free dive with a single save that can't be reloaded. The run
+//! saves itself every few seconds to its own slot, and losing the hull
+//! deletes the save and enters the score on the ironman leaderboard, as does
+//! being rescued after releasing the distress buoy. A red
+//! badge stays on the HUD for the whole run.
+
+use crate::hud::spawn_game_over_screen;
+use crate::rescue::DistressBeacon;
+use crate::save::{self, PlayTime, SaveGame, IRONMAN_SLOT};
+use crate::survival::LeaderboardEntry;
+use crate::{storage, AppState, GameAssets, GameSet, GameState};
+use bevy::prelude::*;
+
+const LEADERBOARD_PATH: &str = "ironman-leaderboard.ron";
+const LEADERBOARD_SIZE: usize = 10;
+const SAVE_INTERVAL: f32 = 15.0; // Seconds between the run's saves
+
+/// Whether the current dive is an ironman run
+#[derive(Resource, Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct Ironman(pub bool);
+
+/// Time until the next save, and whether the run has been lost
+#[derive(Resource, Default)]
+struct IronmanState {
+    save_timer: f32,
+    ended: bool,
+}
+
+#[derive(Component)]
+struct IronmanBadge;
+
+pub struct IronmanPlugin;
+
+impl Plugin for IronmanPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Ironman>()
+            .init_resource::<IronmanState>()
+            .add_systems(
+                OnEnter(AppState::InGame),
+                (reset_ironman, spawn_ironman_badge).run_if(resource_equals(Ironman(true))),
+            )
+            .add_systems(
+                Update,
+                (ironman_save_system, ironman_end_system)
+                    .chain()
+                    .after(crate::oxygen_system)
+                    .in_set(GameSet::Simulation)
+                    .run_if(resource_equals(Ironman(true))),
+            );
+    }
+}
+
+/// Best ironman runs, highest score first
+pub fn leaderboard() -> Vec<LeaderboardEntry> {
+    storage::read(LEADERBOARD_PATH)
+        .and_then(|contents| ron::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+/// Adds a run to the leaderboard and returns its place, if it was good enough to be kept
+fn submit_to_leaderboard(entry: LeaderboardEntry) -> Option<usize> {
+    let mut entries = leaderboard();
+    let rank = entries.partition_point(|other| other.score >= entry.score);
+    if rank >= LEADERBOARD_SIZE {
+        return None;
+    }
+    entries.insert(rank, entry);
+    entries.truncate(LEADERBOARD_SIZE);
+    let result = ron::ser::to_string_pretty(&entries, ron::ser::PrettyConfig::default())
+        .map_err(|err| err.to_string())
+        .and_then(|contents| storage::write(LEADERBOARD_PATH, &contents));
+    if let Err(err) = result {
+        warn!("Failed to save the ironman leaderboard: {}", err);
+    }
+    Some(rank)
+}
+
+fn reset_ironman(mut commands: Commands) {
+    commands.insert_resource(IronmanState::default());
+}
+
+fn spawn_ironman_badge(mut commands: Commands, assets: Res<GameAssets>) {
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                top: Val::Px(8.0),
+                width: Val::Percent(100.0),
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            IronmanBadge,
+            StateScoped(AppState::InGame),
+        ))
+        .with_children(|row| {
+            row.spawn((
+                Node {
+                    padding: UiRect::axes(Val::Px(12.0), Val::Px(2.0)),
+                    border: UiRect::all(Val::Px(2.0)),
+                    ..default()
+                },
+                BorderColor(Color::srgb(1.0, 0.85, 0.85)),
+                BackgroundColor(Color::srgb(0.7, 0.05, 0.05)),
+            ))
+            .with_children(|badge| {
+                badge.spawn((
+                    Text::new("IRONMAN"),
+                    TextFont {
+                        font_size: 20.0,
+                        font: assets.font.clone(),
+                        ..default()
+                    },
+                    TextColor(Color::WHITE),
+                ));
+            });
+        });
+}
+
+/// Overwrites the run's save every few seconds, whatever the autosave setting
+fn ironman_save_system(
+    mut state: ResMut<IronmanState>,
+    mut saves: EventWriter<SaveGame>,
+    time: Res<Time>,
+) {
+    if state.ended {
+        return;
+    }
+    state.save_timer += time.delta_secs();
+    if state.save_timer < SAVE_INTERVAL {
+        return;
+    }
+    state.save_timer = 0.0;
+    saves.write(SaveGame {
+        slot: IRONMAN_SLOT.to_string(),
+        name: "Ironman".to_string(),
+    });
+}
+
+/// Losing the hull or being rescued ends the run for good: the save goes and
+/// the score is entered on the leaderboard
+fn ironman_end_system(
+    mut commands: Commands,
+    mut state: ResMut<IronmanState>,
+    game_state: Res<GameState>,
+    beacon: Res<DistressBeacon>,
+    play_time: Res<PlayTime>,
+    assets: Res<GameAssets>,
+) {
+    if state.ended || (game_state.health > 0.0 && !beacon.rescued) {
+        return;
+    }
+    state.ended = true;
+    save::delete_save(IRONMAN_SLOT);
+    let rank = submit_to_leaderboard(LeaderboardEntry {
+        time: play_time.0,
+        score: game_state.score,
+        timestamp: storage::unix_time(),
+    });
+    info!("Ironman run ended with {} points", game_state.score);
+
+    let mut lines = vec![
+        if beacon.rescued {
+            "Rescued, with half the score - the ironman run is over"
+        } else {
+            "Hull lost - the ironman run is over"
+        }
+        .to_string(),
+        format!(
+            "{} points in {}",
+            game_state.score,
+            save::format_play_time(play_time.0)
+        ),
+        String::new(),
+    ];
+    for (place, entry) in leaderboard().iter().enumerate() {
+        let marker = if rank == Some(place) { "  <" } else { "" };
+        lines.push(format!(
+            "{:>2}. {} pts   {}{}",
+            place + 1,
+            entry.score,
+            save::format_play_time(entry.time),
+            marker
+        ));
+    }
+    spawn_game_over_screen(&mut commands, assets.font.clone(), lines.join("\n"));
+}
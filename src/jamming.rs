@@ -0,0 +1,147 @@
+use crate::acoustics::Detonation;
+use crate::GameSet;
+use bevy::prelude::*;
+
+const PLUME_RADIUS: f32 = 40.0;
+const PLUME_STRENGTH: f32 = 0.4;
+const ERUPTION_RADIUS: f32 = 80.0;
+const ERUPTION_STRENGTH: f32 = 1.0;
+const ERUPTION_TIME: f32 = 10.0;
+const ERUPTION_INTERVAL: f32 = 60.0; // Seconds between eruptions of the same vent
+const PLUME_SCALE: Vec3 = Vec3::new(4.0, 10.0, 4.0);
+const ERUPTION_SCALE: Vec3 = Vec3::new(10.0, 25.0, 10.0);
+
+/// Scrambles sonar contacts within `radius`; `strength` is the jamming at the center, 0..1
+#[derive(Component)]
+pub struct SonarJammer {
+    pub radius: f32,
+    pub strength: f32,
+}
+
+impl SonarJammer {
+    /// How badly this jammer affects sonar at `position`, 0..1
+    pub fn jamming_at(&self, center: Vec3, position: Vec3) -> f32 {
+        (self.strength * (1.0 - center.distance(position) / self.radius)).max(0.0)
+    }
+}
+
+/// A vent plume that erupts every so often, thickening into a much larger cloud
+#[derive(Component)]
+struct Eruptions {
+    timer: f32,     // Seconds until the next eruption
+    remaining: f32, // Seconds left of the current eruption
+}
+
+/// The cloud every plume is drawn with, made once
+#[derive(Resource)]
+struct PlumeAssets {
+    mesh: Handle<Mesh>, // Unit sphere, scaled by the plume
+    material: Handle<StandardMaterial>,
+}
+
+impl FromWorld for PlumeAssets {
+    fn from_world(world: &mut World) -> Self {
+        let mesh = world.resource_mut::<Assets<Mesh>>().add(Sphere::new(1.0));
+        let material = world
+            .resource_mut::<Assets<StandardMaterial>>()
+            .add(StandardMaterial {
+                base_color: Color::srgba(0.2, 0.2, 0.22, 0.35),
+                alpha_mode: AlphaMode::Blend,
+                unlit: true,
+                cull_mode: None,
+                double_sided: true,
+                ..default()
+            });
+        Self { mesh, material }
+    }
+}
+
+pub struct JammingPlugin;
+
+impl Plugin for JammingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                dress_plume_system,
+                eruption_system.in_set(GameSet::Simulation),
+            ),
+        );
+    }
+
+    fn finish(&self, app: &mut App) {
+        app.init_resource::<PlumeAssets>();
+    }
+}
+
+/// Cloud of silt and gas rising from a volcanic vent; spawn it as a child at the summit
+pub fn vent_plume(first_eruption: f32) -> impl Bundle {
+    (
+        Transform::from_xyz(0.0, PLUME_SCALE.y, 0.0).with_scale(PLUME_SCALE),
+        SonarJammer {
+            radius: PLUME_RADIUS,
+            strength: PLUME_STRENGTH,
+        },
+        Eruptions {
+            timer: first_eruption,
+            remaining: 0.0,
+        },
+    )
+}
+
+/// Gives new plumes the shared cloud mesh and material
+fn dress_plume_system(
+    mut commands: Commands,
+    assets: Res<PlumeAssets>,
+    plume_query: Query<Entity, Added<Eruptions>>,
+) {
+    for plume in plume_query.iter() {
+        commands.entity(plume).insert((
+            Mesh3d(assets.mesh.clone()),
+            MeshMaterial3d(assets.material.clone()),
+        ));
+    }
+}
+
+/// Eruptions swell the plume and its jamming, then die back down
+fn eruption_system(
+    time: Res<Time>,
+    mut detonations: EventWriter<Detonation>,
+    mut plume_query: Query<(
+        &mut Eruptions,
+        &mut SonarJammer,
+        &mut Transform,
+        &GlobalTransform,
+    )>,
+) {
+    let delta = time.delta_secs();
+    for (mut eruptions, mut jammer, mut transform, global) in plume_query.iter_mut() {
+        if eruptions.remaining > 0.0 {
+            eruptions.remaining -= delta;
+        } else {
+            eruptions.timer -= delta;
+            if eruptions.timer <= 0.0 {
+                info!("Vent eruption");
+                detonations.write(Detonation {
+                    position: global.translation(),
+                    loudness: 1.0,
+                });
+                eruptions.timer = ERUPTION_INTERVAL;
+                eruptions.remaining = ERUPTION_TIME;
+            }
+        }
+
+        let erupting = eruptions.remaining > 0.0;
+        let (radius, strength, scale) = if erupting {
+            (ERUPTION_RADIUS, ERUPTION_STRENGTH, ERUPTION_SCALE)
+        } else {
+            (PLUME_RADIUS, PLUME_STRENGTH, PLUME_SCALE)
+        };
+        jammer.radius = radius;
+        jammer.strength = strength;
+        // The cloud billows out quickly and settles slowly
+        let rate = if erupting { 2.0 } else { 0.3 };
+        transform.scale = transform.scale.lerp(scale, (rate * delta).min(1.0));
+        transform.translation.y = transform.scale.y;
+    }
+}
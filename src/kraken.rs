@@ -0,0 +1,325 @@
+use crate::controls::{key_name, Action, ControlCommand, HelmInput, InputMap};
+use crate::{AppState, GameAssets, GameMode, GameSet, GameState, Submarine, WorldSeed};
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+const KRAKEN_COUNT: usize = 3;
+const KRAKEN_DEPTH: f32 = 16.0; // Krakens lurk just above the sea floor
+const GRAB_RANGE: f32 = 8.0;
+const TETHER_LENGTH: f32 = 5.0; // Slack in the tentacle holding the submarine
+const CRUSH_DAMAGE: f32 = 3.0; // Health lost per second while held
+const PULL_SPEED: f32 = 0.5; // Meters per second the kraken drags the submarine down
+const RUDDER_STEP: f32 = 0.08; // Escape progress for each rudder swing to the prompted side
+const BLOW_BONUS: f32 = 0.3; // Escape progress for an emergency blow inside the window
+const BLOW_PENALTY: f32 = 0.1; // Progress lost for blowing outside the window
+const BLOW_INTERVAL: f32 = 3.0;
+const BLOW_WINDOW: f32 = 0.8; // Seconds the emergency blow prompt stays open
+const PROGRESS_DECAY: f32 = 0.05; // The grip tightens again if the player stops struggling
+const RELEASE_COOLDOWN: f32 = 20.0; // Seconds before a kraken that let go can grab again
+
+#[derive(Component)]
+struct Kraken {
+    home: Vec3,
+    cooldown: f32,
+}
+
+#[derive(Component)]
+struct GrappleText;
+
+/// The struggle against a kraken that has grabbed the submarine
+#[derive(Resource, Default)]
+struct Grapple {
+    kraken: Option<Entity>,
+    progress: f32,  // 0..1; the kraken lets go at 1
+    next_side: f32, // Rudder side to swing to next: positive is port
+    last_turn: f32,
+    blow_timer: f32, // Counts down to the next emergency blow window
+    blow_window: f32,
+}
+
+/// The kraken's body and tentacles, made once
+#[derive(Resource)]
+struct KrakenAssets {
+    body_mesh: Handle<Mesh>,
+    tentacle_mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+}
+
+impl FromWorld for KrakenAssets {
+    fn from_world(world: &mut World) -> Self {
+        let mut meshes = world.resource_mut::<Assets<Mesh>>();
+        let body_mesh = meshes.add(Sphere::new(2.5));
+        let tentacle_mesh = meshes.add(Capsule3d::new(0.4, 6.0));
+        let material = world
+            .resource_mut::<Assets<StandardMaterial>>()
+            .add(StandardMaterial {
+                base_color: Color::srgb(0.45, 0.12, 0.2),
+                perceptual_roughness: 0.7,
+                ..default()
+            });
+        Self {
+            body_mesh,
+            tentacle_mesh,
+            material,
+        }
+    }
+}
+
+pub struct KrakenPlugin;
+
+impl Plugin for KrakenPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Grapple>()
+            .add_systems(
+                OnEnter(AppState::InGame),
+                (
+                    reset_grapple,
+                    spawn_krakens.run_if(|mode: Res<GameMode>| *mode != GameMode::TimeTrial),
+                ),
+            )
+            .add_systems(
+                Update,
+                (
+                    (grab_system, struggle_system, kraken_motion_system)
+                        .chain()
+                        .in_set(GameSet::Simulation),
+                    grapple_prompt_system.in_set(GameSet::Presentation),
+                ),
+            );
+    }
+
+    fn finish(&self, app: &mut App) {
+        app.init_resource::<KrakenAssets>();
+    }
+}
+
+fn reset_grapple(mut commands: Commands) {
+    commands.insert_resource(Grapple::default());
+}
+
+/// Krakens stay out of the time trial, where a grab would ruin a run
+fn spawn_krakens(
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+    kraken: Res<KrakenAssets>,
+    seed: Res<WorldSeed>,
+) {
+    // A separate stream from the terrain so adding krakens didn't move anything else
+    let mut rng = StdRng::seed_from_u64(seed.0 ^ 0x4B52_414B);
+
+    for _ in 0..KRAKEN_COUNT {
+        let angle = rng.gen::<f32>() * std::f32::consts::TAU;
+        let radius = 60.0 + rng.gen::<f32>() * 240.0;
+        let home = Vec3::new(angle.cos() * radius, -KRAKEN_DEPTH, angle.sin() * radius);
+        commands
+            .spawn((
+                Mesh3d(kraken.body_mesh.clone()),
+                MeshMaterial3d(kraken.material.clone()),
+                Transform::from_translation(home),
+                RigidBody::KinematicPositionBased,
+                Kraken {
+                    home,
+                    cooldown: 0.0,
+                },
+                StateScoped(AppState::InGame),
+            ))
+            .with_children(|parent| {
+                for i in 0..8 {
+                    let angle = i as f32 * std::f32::consts::TAU / 8.0;
+                    let outward = Vec3::new(angle.cos(), 0.0, angle.sin());
+                    parent.spawn((
+                        Mesh3d(kraken.tentacle_mesh.clone()),
+                        MeshMaterial3d(kraken.material.clone()),
+                        // Splayed outwards and down from under the body
+                        Transform::from_translation(outward * 3.0 + Vec3::NEG_Y * 2.0)
+                            .with_rotation(Quat::from_rotation_arc(
+                                Vec3::Y,
+                                (outward - Vec3::Y).normalize(),
+                            )),
+                    ));
+                }
+            });
+    }
+
+    commands.spawn((
+        Text::new(""),
+        TextFont {
+            font_size: 32.0,
+            font: assets.font.clone(),
+            ..default()
+        },
+        TextColor(Color::srgb(1.0, 0.5, 0.4)),
+        TextLayout::new_with_justify(JustifyText::Center),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Percent(35.0),
+            width: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        GrappleText,
+        StateScoped(AppState::InGame),
+    ));
+}
+
+/// A kraken that gets close enough wraps a tentacle around the hull, tethering
+/// the submarine to it with a rope joint
+fn grab_system(
+    mut commands: Commands,
+    mut grapple: ResMut<Grapple>,
+    submarine_query: Query<(Entity, &Transform), With<Submarine>>,
+    kraken_query: Query<(Entity, &Kraken, &Transform)>,
+) {
+    if grapple.kraken.is_some() {
+        return;
+    }
+    let Ok((submarine, submarine_transform)) = submarine_query.single() else {
+        return;
+    };
+    let Some((kraken, _, _)) = kraken_query.iter().find(|(_, kraken, transform)| {
+        kraken.cooldown <= 0.0
+            && transform
+                .translation
+                .distance(submarine_transform.translation)
+                < GRAB_RANGE
+    }) else {
+        return;
+    };
+
+    info!("Grabbed by a kraken");
+    commands.entity(submarine).insert(ImpulseJoint::new(
+        kraken,
+        RopeJointBuilder::new(TETHER_LENGTH).local_anchor1(Vec3::Y * 2.5),
+    ));
+    *grapple = Grapple {
+        kraken: Some(kraken),
+        next_side: 1.0,
+        blow_timer: BLOW_INTERVAL,
+        ..default()
+    };
+}
+
+/// Alternating rudder swings and well-timed emergency blows loosen the grip;
+/// the hull is crushed for as long as it holds
+#[allow(clippy::too_many_arguments)]
+fn struggle_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut grapple: ResMut<Grapple>,
+    mut game_state: ResMut<GameState>,
+    helm_input: Res<HelmInput>,
+    mut control_commands: EventReader<ControlCommand>,
+    submarine_query: Query<Entity, With<Submarine>>,
+    mut kraken_query: Query<&mut Kraken>,
+) {
+    let Some(kraken) = grapple.kraken else {
+        control_commands.clear();
+        return;
+    };
+    let delta = time.delta_secs();
+    game_state.health = (game_state.health - CRUSH_DAMAGE * delta).max(0.0);
+
+    let turn = helm_input.turn;
+    if turn * grapple.next_side > 0.0 && grapple.last_turn * grapple.next_side <= 0.0 {
+        grapple.progress += RUDDER_STEP;
+        grapple.next_side = -grapple.next_side;
+    }
+    grapple.last_turn = turn;
+
+    if grapple.blow_window > 0.0 {
+        grapple.blow_window -= delta;
+    } else {
+        grapple.blow_timer -= delta;
+        if grapple.blow_timer <= 0.0 {
+            grapple.blow_timer = BLOW_INTERVAL;
+            grapple.blow_window = BLOW_WINDOW;
+        }
+    }
+    for command in control_commands.read() {
+        if *command != ControlCommand::ToggleAirValve {
+            continue;
+        }
+        if grapple.blow_window > 0.0 {
+            grapple.progress += BLOW_BONUS;
+            grapple.blow_window = 0.0;
+        } else {
+            grapple.progress -= BLOW_PENALTY;
+        }
+    }
+    grapple.progress = (grapple.progress - PROGRESS_DECAY * delta).max(0.0);
+
+    if grapple.progress < 1.0 {
+        return;
+    }
+    info!("Broke free of the kraken");
+    if let Ok(submarine) = submarine_query.single() {
+        commands.entity(submarine).remove::<ImpulseJoint>();
+    }
+    if let Ok(mut kraken) = kraken_query.get_mut(kraken) {
+        kraken.cooldown = RELEASE_COOLDOWN;
+    }
+    *grapple = Grapple::default();
+}
+
+/// A kraken holding the submarine drags it towards the sea floor; the others
+/// drift back to their lairs
+fn kraken_motion_system(
+    time: Res<Time>,
+    grapple: Res<Grapple>,
+    mut kraken_query: Query<(Entity, &mut Kraken, &mut Transform)>,
+) {
+    let delta = time.delta_secs();
+    for (entity, mut kraken, mut transform) in kraken_query.iter_mut() {
+        kraken.cooldown = (kraken.cooldown - delta).max(0.0);
+        if grapple.kraken == Some(entity) {
+            transform.translation.y =
+                (transform.translation.y - PULL_SPEED * delta).max(-KRAKEN_DEPTH - 2.0);
+        } else {
+            let offset = kraken.home - transform.translation;
+            transform.translation += offset.clamp_length_max(PULL_SPEED * delta);
+        }
+        // Slow menacing turn
+        transform.rotate_y(0.2 * delta);
+    }
+}
+
+fn grapple_prompt_system(
+    grapple: Res<Grapple>,
+    input_map: Res<InputMap>,
+    mut text_query: Query<&mut Text, With<GrappleText>>,
+) {
+    let Ok(mut text) = text_query.single_mut() else {
+        return;
+    };
+    if grapple.kraken.is_none() {
+        if !text.is_empty() {
+            text.clear();
+        }
+        return;
+    }
+    let key = |action: Action| {
+        input_map
+            .binding(action)
+            .primary
+            .map(key_name)
+            .unwrap_or_default()
+    };
+    let (left, right) = (key(Action::TurnLeft), key(Action::TurnRight));
+    let rudder = if grapple.next_side > 0.0 {
+        format!("<< [{}]      {}", left, right)
+    } else {
+        format!("{}      [{}] >>", left, right)
+    };
+    let filled = (grapple.progress.clamp(0.0, 1.0) * 20.0) as usize;
+    let mut prompt = format!(
+        "GRABBED BY A KRAKEN\nSwing the rudder: {}\n[{}{}]",
+        rudder,
+        "#".repeat(filled),
+        "-".repeat(20 - filled)
+    );
+    if grapple.blow_window > 0.0 {
+        prompt += &format!("\nEMERGENCY BLOW! Press {}", key(Action::ToggleAirValve));
+    }
+    **text = prompt;
+}
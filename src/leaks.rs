@@ -0,0 +1,463 @@
+//! Leaks: hull damage springs leaks in the bow, control room or engine room,
+//! and water pours in until someone patches them. Leaks show on the damage
+//! panel; with the control panel open, hold the mouse on one to patch it. The
+//! pumps clear a compartment once its leaks are stopped, and a hurried patch,
+//! or one made up to the waist in water, is more likely to burst again. Below
+//! crush depth the water pressure itself damages the hull.
+
+use crate::callouts::{CalloutKind, CrewCallout};
+use crate::controls::{key_name, Action, InputMap};
+use crate::cvars::{CvarDef, Cvars, RegisterCvar};
+use crate::{AppState, BallastState, GameAssets, GameSet, GameState, Submarine, Tuning, WorldSeed};
+use bevy::prelude::*;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+const FLOOD_WEIGHT: f32 = 0.6; // Weight of fully flooded compartments, as a share of full ballast tanks
+const PANEL_WIDTH: f32 = 300.0;
+const PANEL_HEIGHT: f32 = 90.0;
+const MARKER_SIZE: f32 = 14.0;
+
+const LEAK_DAMAGE: CvarDef = CvarDef {
+    name: "leaks.damage_per_leak",
+    default: 8.0,
+    range: 1.0..=100.0,
+    description: "Hull percent lost per new leak",
+};
+
+const LEAK_RATE: CvarDef = CvarDef {
+    name: "leaks.rate",
+    default: 0.01,
+    range: 0.0..=1.0,
+    description: "Share of a compartment flooded per second by one leak at the surface",
+};
+
+const PRESSURE_DEPTH: CvarDef = CvarDef {
+    name: "leaks.pressure_depth",
+    default: 20.0,
+    range: 1.0..=200.0,
+    description: "Each this many meters of depth adds the surface leak rate again",
+};
+
+const PUMP_RATE: CvarDef = CvarDef {
+    name: "leaks.pump_rate",
+    default: 0.02,
+    range: 0.0..=1.0,
+    description: "Share of a compartment pumped out per second once its leaks are stopped",
+};
+
+const PUMP_POWER: CvarDef = CvarDef {
+    name: "leaks.pump_power",
+    default: 0.5,
+    range: 0.0..=10.0,
+    description: "Electricity per second while pumping",
+};
+
+const PATCH_TIME: CvarDef = CvarDef {
+    name: "leaks.patch_time",
+    default: 3.0,
+    range: 0.1..=30.0,
+    description: "Seconds of holding to patch a leak",
+};
+
+const RELEAK_RATE: CvarDef = CvarDef {
+    name: "leaks.releak_rate",
+    default: 0.01,
+    range: 0.0..=1.0,
+    description: "Chance per second a worthless patch bursts",
+};
+
+/// A watertight section of the hull
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Compartment {
+    Bow,
+    ControlRoom,
+    EngineRoom,
+}
+
+impl Compartment {
+    pub const ALL: [Compartment; 3] = [
+        Compartment::Bow,
+        Compartment::ControlRoom,
+        Compartment::EngineRoom,
+    ];
+
+    fn index(self) -> usize {
+        match self {
+            Compartment::Bow => 0,
+            Compartment::ControlRoom => 1,
+            Compartment::EngineRoom => 2,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Compartment::Bow => "the bow",
+            Compartment::ControlRoom => "the control room",
+            Compartment::EngineRoom => "the engine room",
+        }
+    }
+}
+
+/// Water standing in each compartment, 0..1, in the order of `Compartment::ALL`
+#[derive(Resource, Default)]
+pub struct Flooding {
+    pub water: [f32; 3],
+}
+
+impl Flooding {
+    /// Share of the whole hull flooded, 0..1
+    pub fn total(&self) -> f32 {
+        self.water.iter().sum::<f32>() / self.water.len() as f32
+    }
+
+    /// Weight of the water aboard, as a share of full ballast tanks
+    pub fn weight(&self) -> f32 {
+        self.total() * FLOOD_WEIGHT
+    }
+}
+
+/// An open leak; its marker on the damage panel is held to patch it
+#[derive(Component)]
+struct Leak {
+    compartment: Compartment,
+    spot: Vec2,         // Position within the compartment on the panel, 0..1
+    progress: f32,      // 0..1 towards a patch
+    held: bool,         // Whether it was being patched last frame
+    interruptions: u32, // Times patching was let go before finishing
+}
+
+/// A patched leak; the worse the patch, the likelier it is to burst again
+struct Patch {
+    compartment: Compartment,
+    spot: Vec2,
+    quality: f32, // 0..1
+}
+
+/// Damage taken since the last leak, and the patches holding
+#[derive(Resource, Default)]
+struct HullLeaks {
+    last_health: Option<f32>,
+    accumulated: f32,
+    patches: Vec<Patch>,
+}
+
+/// Random source for where leaks spring, seeded from the world so replays match
+#[derive(Resource)]
+struct LeakRng(StdRng);
+
+#[derive(Component)]
+struct DamagePanel;
+
+/// The panel's heading, naming the key that opens the control panel
+#[derive(Component)]
+struct DamageTitle;
+
+/// A compartment's section of the damage panel; leak markers are its children
+#[derive(Component)]
+struct CompartmentNode(Compartment);
+
+/// Water level drawn in a compartment's section
+#[derive(Component)]
+struct WaterLevel(Compartment);
+
+pub struct LeaksPlugin;
+
+impl Plugin for LeaksPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Flooding>()
+            .init_resource::<HullLeaks>()
+            .insert_resource(LeakRng(StdRng::seed_from_u64(0)))
+            .register_cvar(&LEAK_DAMAGE)
+            .register_cvar(&LEAK_RATE)
+            .register_cvar(&PRESSURE_DEPTH)
+            .register_cvar(&PUMP_RATE)
+            .register_cvar(&PUMP_POWER)
+            .register_cvar(&PATCH_TIME)
+            .register_cvar(&RELEAK_RATE)
+            .add_systems(OnEnter(AppState::InGame), (reset_leaks, spawn_damage_panel))
+            .add_systems(
+                Update,
+                (
+                    (crush_system, leak_system, patch_system, flooding_system)
+                        .chain()
+                        .in_set(GameSet::Simulation),
+                    (damage_panel_system, damage_title_system).in_set(GameSet::Presentation),
+                ),
+            );
+    }
+}
+
+fn reset_leaks(mut commands: Commands, seed: Res<WorldSeed>) {
+    commands.insert_resource(Flooding::default());
+    commands.insert_resource(HullLeaks::default());
+    commands.insert_resource(LeakRng(StdRng::seed_from_u64(seed.0 ^ 0x4C45_414B)));
+}
+
+fn spawn_damage_panel(mut commands: Commands, assets: Res<GameAssets>) {
+    let font = assets.font.clone();
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                top: Val::Percent(55.0),
+                right: Val::Px(16.0),
+                flex_direction: FlexDirection::Column,
+                row_gap: Val::Px(4.0),
+                display: Display::None,
+                ..default()
+            },
+            DamagePanel,
+            StateScoped(AppState::InGame),
+        ))
+        .with_children(|panel| {
+            panel.spawn((
+                Text::default(),
+                TextFont {
+                    font_size: 12.0,
+                    font: font.clone(),
+                    ..default()
+                },
+                TextColor(Color::srgb(0.9, 0.9, 0.7)),
+                DamageTitle,
+            ));
+            panel
+                .spawn(Node {
+                    width: Val::Px(PANEL_WIDTH),
+                    height: Val::Px(PANEL_HEIGHT),
+                    flex_direction: FlexDirection::Row,
+                    ..default()
+                })
+                .with_children(|hull| {
+                    for compartment in Compartment::ALL {
+                        hull.spawn((
+                            Node {
+                                width: Val::Percent(100.0 / 3.0),
+                                height: Val::Percent(100.0),
+                                border: UiRect::all(Val::Px(1.0)),
+                                ..default()
+                            },
+                            BorderColor(Color::srgb(0.4, 0.6, 0.7)),
+                            BackgroundColor(Color::srgba(0.0, 0.05, 0.1, 0.7)),
+                            CompartmentNode(compartment),
+                        ))
+                        .with_child((
+                            Node {
+                                position_type: PositionType::Absolute,
+                                bottom: Val::Px(0.0),
+                                width: Val::Percent(100.0),
+                                height: Val::Percent(0.0),
+                                ..default()
+                            },
+                            BackgroundColor(Color::srgba(0.1, 0.3, 0.8, 0.6)),
+                            WaterLevel(compartment),
+                        ));
+                    }
+                });
+        });
+}
+
+/// Past crush depth the hull gives a little more for every meter deeper
+fn crush_system(
+    mut game_state: ResMut<GameState>,
+    tuning: Res<Tuning>,
+    submarine_query: Query<&Transform, With<Submarine>>,
+    time: Res<Time>,
+) {
+    let Ok(transform) = submarine_query.single() else {
+        return;
+    };
+    let excess = -transform.translation.y - tuning.crush_depth;
+    if excess > 0.0 {
+        game_state.health =
+            (game_state.health - excess * tuning.crush_damage_rate * time.delta_secs()).max(0.0);
+    }
+}
+
+/// Springs leaks as the hull takes damage, and bursts patches that don't hold
+#[allow(clippy::too_many_arguments)]
+fn leak_system(
+    mut commands: Commands,
+    mut hull: ResMut<HullLeaks>,
+    mut rng: ResMut<LeakRng>,
+    mut callouts: EventWriter<CrewCallout>,
+    game_state: Res<GameState>,
+    compartment_query: Query<(Entity, &CompartmentNode)>,
+    cvars: Res<Cvars>,
+    time: Res<Time>,
+) {
+    let last_health = *hull.last_health.get_or_insert(game_state.health);
+    hull.accumulated += (last_health - game_state.health).max(0.0);
+    hull.last_health = Some(game_state.health);
+
+    let mut new_leaks = Vec::new();
+    let leak_damage = cvars.get(&LEAK_DAMAGE);
+    while hull.accumulated >= leak_damage {
+        hull.accumulated -= leak_damage;
+        let compartment = Compartment::ALL[rng.0.gen_range(0..Compartment::ALL.len())];
+        new_leaks.push((compartment, Vec2::new(rng.0.gen(), rng.0.gen())));
+    }
+    let delta = time.delta_secs();
+    let releak_rate = cvars.get(&RELEAK_RATE);
+    let rng = &mut rng.0;
+    hull.patches.retain(|patch| {
+        if rng.gen::<f32>() < (1.0 - patch.quality) * releak_rate * delta {
+            new_leaks.push((patch.compartment, patch.spot));
+            false
+        } else {
+            true
+        }
+    });
+
+    for (compartment, spot) in new_leaks {
+        let Some((node, _)) = compartment_query
+            .iter()
+            .find(|(_, node)| node.0 == compartment)
+        else {
+            continue;
+        };
+        callouts.write(CrewCallout {
+            kind: CalloutKind::Leak,
+            text: format!("Leak in {}!", compartment.name()),
+        });
+        commands.spawn((
+            Button,
+            Node {
+                position_type: PositionType::Absolute,
+                left: Val::Percent(spot.x * 80.0),
+                top: Val::Px(spot.y * (PANEL_HEIGHT - MARKER_SIZE - 2.0)),
+                width: Val::Px(MARKER_SIZE),
+                height: Val::Px(MARKER_SIZE),
+                ..default()
+            },
+            BackgroundColor(Color::srgb(1.0, 0.2, 0.1)),
+            Leak {
+                compartment,
+                spot,
+                progress: 0.0,
+                held: false,
+                interruptions: 0,
+            },
+            ChildOf(node),
+        ));
+    }
+}
+
+/// Holding the mouse on a leak patches it; the patch is worse for every time
+/// the work was let go and the deeper the water it was made in
+fn patch_system(
+    mut commands: Commands,
+    mut hull: ResMut<HullLeaks>,
+    mut callouts: EventWriter<CrewCallout>,
+    flooding: Res<Flooding>,
+    mut leak_query: Query<(Entity, &mut Leak, &Interaction)>,
+    cvars: Res<Cvars>,
+    time: Res<Time>,
+) {
+    for (entity, mut leak, interaction) in leak_query.iter_mut() {
+        let held = *interaction == Interaction::Pressed;
+        if leak.held && !held {
+            leak.interruptions += 1;
+        }
+        leak.held = held;
+        if !held {
+            continue;
+        }
+        leak.progress += time.delta_secs() / cvars.get(&PATCH_TIME);
+        if leak.progress < 1.0 {
+            continue;
+        }
+
+        let water = flooding.water[leak.compartment.index()];
+        let quality = (1.0 - 0.6 * water - 0.15 * leak.interruptions as f32).clamp(0.1, 1.0);
+        hull.patches.push(Patch {
+            compartment: leak.compartment,
+            spot: leak.spot,
+            quality,
+        });
+        callouts.write(CrewCallout {
+            kind: CalloutKind::Leak,
+            text: format!("Leak in {} patched", leak.compartment.name()),
+        });
+        commands.entity(entity).despawn();
+    }
+}
+
+/// Open leaks flood their compartment, faster at depth; once they're stopped
+/// the pumps clear the water, drawing on the batteries
+fn flooding_system(
+    mut flooding: ResMut<Flooding>,
+    mut ballast_state: ResMut<BallastState>,
+    leak_query: Query<&Leak>,
+    submarine_query: Query<&Transform, With<Submarine>>,
+    cvars: Res<Cvars>,
+    time: Res<Time>,
+) {
+    let delta = time.delta_secs();
+    let depth = submarine_query
+        .single()
+        .map(|transform| (-transform.translation.y).max(0.0))
+        .unwrap_or(0.0);
+    let pressure = 1.0 + depth / cvars.get(&PRESSURE_DEPTH);
+
+    for compartment in Compartment::ALL {
+        let leaks = leak_query
+            .iter()
+            .filter(|leak| leak.compartment == compartment)
+            .count();
+        let water = flooding.water[compartment.index()];
+        if leaks > 0 {
+            flooding.water[compartment.index()] =
+                (water + leaks as f32 * cvars.get(&LEAK_RATE) * pressure * delta).min(1.0);
+        } else if water > 0.0 && ballast_state.electricity > 0.0 {
+            flooding.water[compartment.index()] = (water - cvars.get(&PUMP_RATE) * delta).max(0.0);
+            ballast_state.electricity =
+                (ballast_state.electricity - cvars.get(&PUMP_POWER) * delta).max(0.0);
+        }
+    }
+}
+
+/// Shows the damage panel while there is water aboard or a leak to patch,
+/// with water levels and how far along each patch is
+/// Follows the control panel's key through any rebinding
+fn damage_title_system(
+    input_map: Res<InputMap>,
+    mut title_query: Query<(Ref<DamageTitle>, &mut Text)>,
+) {
+    for (title, mut text) in title_query.iter_mut() {
+        if title.is_added() || input_map.is_changed() {
+            let key = input_map
+                .binding(Action::ControlPanel)
+                .primary
+                .map(key_name)
+                .unwrap_or_default();
+            text.0 = format!("Damage Control ({}, then hold a leak to patch it)", key);
+        }
+    }
+}
+
+fn damage_panel_system(
+    flooding: Res<Flooding>,
+    mut panel_query: Query<&mut Node, With<DamagePanel>>,
+    mut water_query: Query<(&WaterLevel, &mut Node), Without<DamagePanel>>,
+    mut leak_query: Query<(&Leak, &Interaction, &mut BackgroundColor)>,
+) {
+    let active = !leak_query.is_empty() || flooding.total() > 0.0;
+    for mut node in panel_query.iter_mut() {
+        node.display = if active { Display::Flex } else { Display::None };
+    }
+    for (level, mut node) in water_query.iter_mut() {
+        node.height = Val::Percent(flooding.water[level.0.index()] * 100.0);
+    }
+    for (leak, interaction, mut background) in leak_query.iter_mut() {
+        let hover = if *interaction == Interaction::None {
+            0.0
+        } else {
+            0.2
+        };
+        *background = BackgroundColor(Color::srgb(
+            1.0,
+            0.2 + 0.8 * leak.progress + hover,
+            0.1 + hover,
+        ));
+    }
+}
@@ -0,0 +1,2809 @@
+extern crate rand;
+use bevy::{
+    app::PluginGroupBuilder, prelude::*, render::mesh::VertexAttributeValues, window::PrimaryWindow,
+};
+use bevy_rapier3d::prelude::*;
+use clap::Parser;
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+mod accessibility;
+mod acoustics;
+mod ambience;
+mod assets;
+mod biofouling;
+mod biophony;
+mod blackbox;
+mod bottom_contour;
+mod bridge;
+mod callouts;
+mod camera_shake;
+#[cfg(not(target_arch = "wasm32"))]
+mod capture;
+mod cargo;
+mod classes;
+mod cleanup;
+mod combat;
+mod console;
+mod contact_history;
+mod control_panel;
+mod controls;
+mod crew;
+mod currents;
+mod cutscene;
+mod cvars;
+mod debrief;
+mod defense;
+mod depth_profile;
+mod difficulty;
+mod doppler;
+mod ecosystem;
+mod editor;
+mod eels;
+mod encounters;
+mod enemy_submarine;
+mod engine_sound;
+mod event_log;
+mod failures;
+mod feeding;
+mod fish_finder;
+mod fouling;
+mod game_over;
+mod hud;
+mod hull_damage;
+mod ice;
+mod input_script;
+mod intercept;
+mod interior;
+mod ironman;
+mod jamming;
+mod kraken;
+mod leaks;
+mod loading;
+mod magnetics;
+mod menu;
+mod migration;
+mod mods;
+mod music;
+mod new_game_plus;
+mod optics;
+mod periscope;
+mod pickups;
+mod profiling;
+mod props;
+mod race;
+mod repair;
+mod replay;
+mod rescue;
+mod save;
+mod scenario;
+mod scoring;
+mod scripting;
+mod settings;
+mod settings_menu;
+mod sfx;
+mod splash;
+mod stations;
+mod stealth;
+mod storage;
+mod survival;
+mod tables;
+mod tactical;
+#[cfg(not(target_arch = "wasm32"))]
+mod telemetry;
+mod testing;
+mod touch;
+mod treasure;
+mod trim_computer;
+mod tuning;
+mod upward_sonar;
+
+use combat::{Armament, Bait, BAIT_RANGE};
+use enemy_submarine::ENEMY_ECHO;
+use game_over::hull_intact;
+use hud::{HudTextPanel, SonarPanel, SonarScope};
+use jamming::SonarJammer;
+use magnetics::Navigation;
+use mods::LoadedMods;
+use pickups::Pickup;
+use profiling::SystemTimings;
+use props::PropLibrary;
+use repair::Inventory;
+use replay::{Replay, ReplayOutput};
+use save::PendingLoad;
+use scenario::ScenarioPath;
+use scripting::Missions;
+use serde::{Deserialize, Serialize};
+use settings::Settings;
+use tables::{FishSpecies, LightResponse, Tables};
+use tuning::depth_factor;
+
+// The public API for embedding the game in other Bevy apps. These names are kept
+// stable between versions; everything not exported here may change freely.
+pub use accessibility::AccessibilityPlugin;
+pub use acoustics::{AcousticsPlugin, Detonation};
+pub use ambience::AmbiencePlugin;
+pub use assets::GameAssets;
+pub use biofouling::{Biofouling, BiofoulingPlugin};
+pub use biophony::{BiophonyPlugin, NoiseKind, NoiseSource};
+pub use blackbox::BlackBoxPlugin;
+pub use bottom_contour::{BottomChart, BottomContourPlugin};
+pub use bridge::BridgePlugin;
+pub use callouts::{CalloutKind, CalloutsPlugin, CrewCallout};
+pub use camera_shake::CameraShakePlugin;
+pub use cargo::{Cargo, CargoKind, CargoLoad, CargoPlugin};
+pub use classes::SubmarineClass;
+pub use cleanup::{Cleanup, CleanupPlugin};
+pub use combat::CombatPlugin;
+pub use console::ConsolePlugin;
+pub use contact_history::{ContactHistoryPlugin, SelectedContact};
+pub use control_panel::ControlPanelPlugin;
+pub use controls::{ControlCommand, ControlsPlugin, HelmInput, HelmState, Telegraph};
+pub use crew::{Crew, CrewMember, CrewPlugin, Station};
+pub use currents::{CurrentsPlugin, TideRace};
+pub use cutscene::{CameraKeyframe, CameraPath, Cutscene, CutscenePlugin, PlayIntro};
+pub use cvars::{CvarDef, Cvars, CvarsPlugin, RegisterCvar};
+pub use debrief::{DebriefPlugin, MissionDebrief};
+pub use defense::DefensePlugin;
+pub use depth_profile::DepthProfilePlugin;
+pub use difficulty::{Difficulty, DifficultyPlugin};
+pub use doppler::DopplerPlugin;
+pub use ecosystem::{Ecosystem, EcosystemPlugin};
+pub use editor::EditorPlugin;
+pub use eels::EelPlugin;
+pub use encounters::{Encounter, EncounterPlugin};
+pub use enemy_submarine::{EnemyState, EnemySubmarine, EnemySubmarinePlugin};
+pub use engine_sound::EngineSoundPlugin;
+pub use event_log::{EventLog, EventLogPlugin, LogKind, LogMessage};
+pub use failures::{Failure, Failures, FailuresPlugin};
+pub use feeding::FeedingPlugin;
+pub use fish_finder::{FishFinder, FishFinderPlugin};
+pub use fouling::{Fouling, FoulingPlugin};
+pub use game_over::GameOverPlugin;
+pub use hud::HudPlugin;
+pub use hull_damage::{HullCondition, HullDamage, HullDamagePlugin};
+pub use ice::{IceFloe, IcePlugin};
+pub use input_script::{InputScript, InputScriptPlugin, ScriptedKey};
+pub use intercept::{InterceptPlot, InterceptPlugin};
+pub use interior::InteriorPlugin;
+pub use ironman::{Ironman, IronmanPlugin};
+pub use jamming::JammingPlugin;
+pub use kraken::KrakenPlugin;
+pub use leaks::{Compartment, Flooding, LeaksPlugin};
+pub use loading::LoadingPlugin;
+pub use magnetics::MagneticsPlugin;
+pub use menu::MenuPlugin;
+pub use migration::{MigrationPlugin, SeaClock};
+pub use music::MusicPlugin;
+pub use new_game_plus::{CampaignProgress, NewGamePlus, NewGamePlusPlugin};
+pub use optics::{OpticsPlugin, WaterBody, WaterOpticalProperties, Weather};
+pub use periscope::{ContactLog, ContactReport, PeriscopePlugin, SurfaceShip};
+pub use pickups::PickupsPlugin;
+pub use profiling::ProfilingPlugin;
+pub use props::PropsPlugin;
+pub use race::RacePlugin;
+pub use repair::RepairPlugin;
+pub use replay::ReplayPlugin;
+pub use rescue::{DistressBeacon, RescuePlugin};
+pub use save::SavePlugin;
+pub use scenario::{Scenario, ScenarioPlugin};
+pub use scoring::{FishCollected, ScoringPlugin};
+pub use scripting::{MissionEnded, ScriptingPlugin};
+pub use settings::SettingsPlugin;
+pub use settings_menu::SettingsMenuPlugin;
+pub use sfx::SfxPlugin;
+pub use splash::{Breaching, SplashPlugin, SurfaceBreak};
+pub use stations::StationsPlugin;
+pub use stealth::{StealthPlugin, SubmarineNoise};
+pub use survival::SurvivalPlugin;
+pub use tables::TablesPlugin;
+pub use tactical::TacticalPlugin;
+pub use testing::{test_app, TestApp, TEST_FRAME_RATE};
+pub use touch::TouchPlugin;
+pub use treasure::TreasurePlugin;
+pub use trim_computer::{TrimComputer, TrimComputerPlugin};
+pub use tuning::{Tuning, TuningOverrides, TuningPlugin};
+pub use upward_sonar::UpwardSonarPlugin;
+
+// Constants
+// Contacts are plotted on a nominal scope 200 px across; the panel scales it
+// to whatever size it is shown at
+const SONAR_SCOPE_SIZE: f32 = 200.0;
+const SONAR_CENTER_X: f32 = 100.0;
+const SONAR_CENTER_Y: f32 = 100.0;
+const SONAR_RADIUS: f32 = 75.0;
+const BLIP_FADE_TIME: f32 = 3.0; // Seconds a sonar blip takes to fade once the sweep has painted it
+const CAMERA_CLEARANCE: f32 = 0.5; // Meters the chase camera keeps off terrain in its way
+const CAMERA_MIN_ARM: f32 = 2.0; // Closest the chase camera is pulled in behind the submarine
+const SURFACE_CLEARANCE: f32 = 0.4; // Meters the chase camera keeps off the water surface
+const SURFACED_CAMERA_DEPTH: f32 = 1.5; // Shallower than this the chase camera stays above water
+const FLOODLIGHT_REACH: f32 = 25.0; // Meters along the beam fish notice the floodlights in the dark
+const FLOODLIGHT_ANGLE: f32 = 0.5; // Radians off the beam's axis, as wide as the spotlight's cone
+const FLOODLIGHT_DARK_DEPTH: f32 = 12.0; // Depth from which the beam is seen at its full reach
+const FOG_START: f32 = 0.2; // Share of the visibility that is seen clear before the fog sets in
+const SURFACE_TOLERANCE: f32 = 0.05; // Meters under the swell that still count as surfaced
+const HULL_HALF_LENGTH: f32 = 2.5; // Meters from amidships to the bow, where the swell is sampled for pitch
+const HULL_END_OFFSET: f32 = 2.0; // Meters from amidships to the center of each rounded end of the hull
+const HULL_HALF_BEAM: f32 = 0.7; // Meters from the keel line to the side, where it is sampled for roll
+
+/// Command line options of the game binary
+#[derive(Parser)]
+#[command(name = "submarine")]
+#[command(about = "A 3D submarine game")]
+pub struct Args {
+    /// Enable physics collider wireframes
+    #[arg(short, long)]
+    debug_colliders: bool,
+
+    /// Speak depth, oxygen and contact announcements (also configurable in settings.ron)
+    #[arg(long)]
+    tts: bool,
+
+    /// Number of fish spawned in the lake
+    #[arg(long, value_name = "COUNT")]
+    fish_count: Option<usize>,
+
+    /// Oxygen percent lost per second while submerged
+    #[arg(long, value_name = "PERCENT")]
+    oxygen_rate: Option<f32>,
+
+    /// Multiplier for buoyancy and ballast forces
+    #[arg(long, value_name = "SCALE")]
+    buoyancy_scale: Option<f32>,
+
+    /// Depth in meters the submarine starts a new game at
+    #[arg(long, value_name = "METERS")]
+    start_depth: Option<f32>,
+
+    /// Play back a recorded replay file instead of showing the main menu
+    #[arg(long, value_name = "FILE")]
+    play_replay: Option<String>,
+
+    /// Write the current dive's replay to FILE when it ends or the game exits
+    #[arg(long, value_name = "FILE")]
+    record_replay: Option<String>,
+
+    /// Run the mission script assets/scripts/NAME.rhai during every dive
+    #[arg(long, value_name = "NAME")]
+    script: Option<String>,
+
+    /// Play the map and missions described in a scenario file
+    #[arg(long, value_name = "FILE")]
+    scenario: Option<String>,
+
+    /// Step at a fixed frame time and save every frame as a numbered PNG in DIR
+    #[arg(long, value_name = "DIR")]
+    capture: Option<String>,
+
+    /// Step at a fixed frame time and encode every frame into FILE with ffmpeg
+    #[arg(long, value_name = "FILE")]
+    capture_video: Option<String>,
+
+    /// Frame rate of captured frames
+    #[arg(long, value_name = "FPS", default_value_t = 60)]
+    capture_fps: u32,
+
+    /// Quit after capturing this many frames
+    #[arg(long, value_name = "COUNT")]
+    capture_frames: Option<u32>,
+
+    /// Run the simulation without a window or GPU, stepping 60 times a second
+    #[arg(long)]
+    headless: bool,
+
+    /// Skip the menu and play the timed key presses in a RON input script
+    #[arg(long, value_name = "FILE")]
+    input_script: Option<String>,
+
+    /// Sample depth, speed, ballast, air, power, oxygen and position into FILE
+    /// (.csv, or .jsonl for JSON Lines)
+    #[arg(long, value_name = "FILE")]
+    telemetry: Option<String>,
+
+    /// Telemetry samples per second
+    #[arg(long, value_name = "HZ", default_value_t = 4.0)]
+    telemetry_rate: f32,
+}
+
+/// Ordered stages of the Update schedule. Systems within a stage may run in parallel;
+/// add explicit `before`/`after` constraints only where data actually flows between them.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum GameSet {
+    Input,        // Keyboard, touch and UI input turned into commands and helm axes
+    Replay,       // Final helm input and commands recorded, or substituted during playback
+    Simulation,   // Movement, ballast, fish, resources and effects
+    Detection,    // Sensors reading the simulated world
+    Presentation, // Camera, lighting, HUD and overlays
+}
+
+/// Top-level flow between the menus and a running dive
+#[derive(States, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum AppState {
+    #[default]
+    MainMenu,
+    Loading, // Loading screen; a new game or load always rebuilds the world here
+    InGame,
+    Editor, // Building a scenario with a free camera
+}
+
+/// Whether a running dive is frozen behind the pause menu
+#[derive(SubStates, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[source(AppState = AppState::InGame)]
+pub enum PauseState {
+    #[default]
+    Running,
+    Paused,
+}
+
+/// Where the camera watches a running dive from
+#[derive(SubStates, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[source(AppState = AppState::InGame)]
+pub enum CameraMode {
+    #[default]
+    Exterior, // Orbiting behind the submarine
+    ControlRoom, // Looking ahead from inside the hull
+    Periscope,   // Looking out over the surface from periscope depth
+    Bridge,      // On top of the conning tower while surfaced
+    Tactical,    // Overhead schematic of the submarine, contacts and terrain
+    Cutscene,    // Following a scripted camera path; the helm waits
+}
+
+/// Rules for the current dive
+#[derive(Resource, Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum GameMode {
+    #[default]
+    Dive, // Free exploration and fish collection
+    TimeTrial, // Race through the gate course against a ghost of the best run
+    Survival,  // Last as long as possible as conditions worsen and predators close in
+    Defense,   // Protect a habitat from waves of attackers
+}
+
+/// Seed for terrain and fish placement, kept so saves can rebuild the same world
+#[derive(Resource, Clone, Copy)]
+pub struct WorldSeed(pub u64);
+
+// Components
+
+/// The player's submarine, a dynamic rigid body moved by the helm and ballast
+#[derive(Component)]
+pub struct Submarine;
+
+/// A collectable fish; the submarine catches it by coming within
+/// `Tuning::fish_collection_distance`
+#[derive(Component)]
+pub struct Fish;
+
+/// How big a fish grew, as a multiple of its species' usual size, and whether
+/// that is below the species' catch limit
+#[derive(Component)]
+pub struct FishSize {
+    pub scale: f32,
+    pub undersized: bool,
+}
+
+#[derive(Component)]
+struct CameraFollow;
+
+/// The main camera, for the views that take it over from the chase camera
+type ViewCameraQuery<'w, 's> = Query<
+    'w,
+    's,
+    (&'static mut Transform, &'static mut Projection),
+    (With<CameraFollow>, Without<Submarine>),
+>;
+
+/// Component for bubble particles; spent bubbles are hidden and kept for reuse
+#[derive(Component)]
+struct Bubble {
+    timer: Timer,
+    active: bool,
+}
+
+/// Hidden bubbles ready to be reused, so venting doesn't spawn and despawn an
+/// entity for every bubble
+#[derive(Resource, Default)]
+struct BubblePool {
+    free: Vec<Entity>,
+}
+
+const BUBBLE_POOL_SIZE: usize = 64; // Made ready at the start of a dive; the pool grows as needed
+const BUBBLE_SPLASH_SIZE: f32 = 0.25; // Radius of the foam ring a bubble leaves as it pops at the surface
+
+/// The main status text in the HUD
+#[derive(Component)]
+struct StatusText;
+
+#[derive(Component)]
+struct SonarSweepLine;
+
+/// A spot on the sonar scope, painted where a contact was when the sweep last
+/// passed over it and fading from then on
+#[derive(Component, Default)]
+struct SonarBlip {
+    painted: Option<SonarContact>,
+    age: f32, // Seconds since it was painted
+}
+
+/// A fixed part of the sonar scope, placed to suit the panel's size
+#[derive(Component, Clone, Copy)]
+enum ScopeMark {
+    Ring(f32), // One dot of the range ring, at this angle
+    VerticalLine,
+    HorizontalLine,
+    CenterDot,
+}
+
+#[derive(Component)]
+struct WaterSurface;
+
+#[derive(Component)]
+struct Mountain;
+
+#[derive(Component)]
+struct Foothill;
+
+#[derive(Component)]
+struct UnderwaterRock;
+
+#[derive(Component)]
+struct DepthLighting;
+
+#[derive(Component)]
+struct Floodlight;
+
+/// Painted hull sections of the submarine
+#[derive(Component)]
+struct Hull;
+
+#[derive(Component)]
+struct FishMovement {
+    direction: Vec3,
+    speed: f32,
+    change_direction_timer: f32,
+    change_direction_interval: f32,
+    fleeing: f32, // Seconds left darting away from the submarine
+    light: LightResponse,
+}
+
+// Resources
+
+/// Score and the submarine's condition, both in percent
+#[derive(Resource)]
+pub struct GameState {
+    pub score: u32,
+    pub health: f32,
+    pub oxygen: f32,
+}
+
+/// How the exterior camera frames the submarine
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum ExteriorView {
+    #[default]
+    Chase, // Orbiting well behind and above
+    Close,   // Tucked in just behind the sail
+    TopDown, // Straight down from above, heading up
+    Profile, // Abeam at the same depth, for judging depth and trim
+}
+
+impl ExteriorView {
+    pub const ALL: [ExteriorView; 4] = [
+        ExteriorView::Chase,
+        ExteriorView::Close,
+        ExteriorView::TopDown,
+        ExteriorView::Profile,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ExteriorView::Chase => "Chase",
+            ExteriorView::Close => "Close Follow",
+            ExteriorView::TopDown => "Top Down",
+            ExteriorView::Profile => "Side Profile",
+        }
+    }
+}
+
+#[derive(Resource)]
+struct CameraState {
+    distance: f32,
+    height: f32, // Meters the chase camera rides above the orbit
+    yaw: f32,
+    pitch: f32,
+    target_yaw: f32, // Target yaw that follows submarine rotation
+    position: Vec3,  // Smoothed camera position before any shake is applied
+    view: ExteriorView,
+    close_distance: f32,   // Meters behind the submarine in the close follow view
+    close_height: f32,     // Meters above the orbit in the close follow view
+    top_down_height: f32,  // Meters above the submarine in the top-down view
+    profile_distance: f32, // Meters abeam in the side profile view
+}
+
+impl CameraState {
+    /// Where the exterior camera sits relative to the submarine in the
+    /// current view
+    fn offset(&self) -> Vec3 {
+        match self.view {
+            ExteriorView::Chase => chase_offset(self.distance, self.height, self.yaw, self.pitch),
+            ExteriorView::Close => {
+                chase_offset(self.close_distance, self.close_height, self.yaw, self.pitch)
+            }
+            ExteriorView::TopDown => Vec3::Y * self.top_down_height,
+            ExteriorView::Profile => {
+                Vec3::new(self.yaw.cos(), 0.0, -self.yaw.sin()) * self.profile_distance
+            }
+        }
+    }
+
+    /// Which way is up on screen: the submarine's heading from overhead,
+    /// otherwise the sky
+    fn up(&self) -> Vec3 {
+        match self.view {
+            ExteriorView::TopDown => Vec3::new(-self.yaw.sin(), 0.0, -self.yaw.cos()),
+            _ => Vec3::Y,
+        }
+    }
+}
+
+#[derive(Resource)]
+struct SonarState {
+    sweep_angle: f32,
+}
+
+/// A blip on the sonar display
+#[derive(Clone, Copy)]
+struct SonarContact {
+    x: f32, // Position on the nominal sonar scope
+    y: f32,
+    angle: f32,             // Detection angle
+    confidence: f32,        // 0..1; how much the return can be trusted, lowered by jamming
+    target: Option<Entity>, // What sent the echo back; none for false returns
+    depth: f32,             // Meters below the surface
+}
+
+#[derive(Resource, Default)]
+struct SonarDetections {
+    contacts: Vec<SonarContact>,
+}
+
+/// Ballast tanks, compressed air and battery, driven by `ControlCommand`s
+#[derive(Resource)]
+pub struct BallastState {
+    pub fill_level: f32,          // 0.0 = empty (buoyant), 1.0 = full (sinks)
+    pub vents_open: bool,         // Water flows in when open
+    pub air_valve_open: bool,     // Compressed air flows in when open
+    pub compressed_air: f32,      // Amount of compressed air available (0.0 to 1.0)
+    pub compressor_on: bool,      // Air compressor is running
+    pub electricity: f32,         // Available electricity (0.0 to 100.0)
+    pub target_fill: Option<f32>, // Fill level the valves are worked to hold
+}
+
+impl BallastState {
+    /// Holds the tanks at `fill`; the toggles are closed so they don't fight it
+    pub fn set_target(&mut self, fill: f32) {
+        self.target_fill = Some(fill.clamp(0.0, 1.0));
+        self.vents_open = false;
+        self.air_valve_open = false;
+    }
+}
+
+#[derive(Resource)]
+struct WaveTime {
+    elapsed: f32,
+}
+
+/// Whether the submarine is riding the swell at the surface, updated as it moves
+#[derive(Resource, Default)]
+pub struct Surfaced(pub bool);
+
+impl Default for GameState {
+    fn default() -> Self {
+        Self {
+            score: 0,
+            health: 100.0,
+            oxygen: 100.0,
+        }
+    }
+}
+
+impl Default for CameraState {
+    fn default() -> Self {
+        Self {
+            distance: 25.0,
+            height: 5.0,
+            yaw: 0.0,
+            pitch: 0.0,
+            target_yaw: 0.0,
+            position: Vec3::new(0.0, 8.0, 25.0),
+            view: ExteriorView::Chase,
+            close_distance: 9.0,
+            close_height: 2.0,
+            top_down_height: 40.0,
+            profile_distance: 18.0,
+        }
+    }
+}
+
+impl Default for SonarState {
+    fn default() -> Self {
+        Self { sweep_angle: 0.0 }
+    }
+}
+
+impl Default for BallastState {
+    fn default() -> Self {
+        Self {
+            fill_level: 0.0, // Start with empty ballast tanks (buoyant)
+            vents_open: false,
+            air_valve_open: false,
+            compressed_air: 1.0, // Start with full compressed air
+            compressor_on: false,
+            electricity: 100.0, // Start with full electricity
+            target_fill: None,
+        }
+    }
+}
+
+impl Default for WorldSeed {
+    fn default() -> Self {
+        Self(rand::random())
+    }
+}
+
+impl Default for WaveTime {
+    fn default() -> Self {
+        Self { elapsed: 0.0 }
+    }
+}
+
+/// Headless runs keep every plugin but never open a window or touch the GPU
+fn headless_plugins() -> PluginGroupBuilder {
+    DefaultPlugins
+        .set(WindowPlugin {
+            primary_window: None,
+            exit_condition: bevy::window::ExitCondition::DontExit,
+            close_when_requested: false,
+        })
+        .set(bevy::render::RenderPlugin {
+            render_creation: bevy::render::settings::WgpuSettings {
+                backends: None,
+                ..default()
+            }
+            .into(),
+            ..default()
+        })
+        .disable::<bevy::winit::WinitPlugin>()
+        .add(bevy::app::ScheduleRunnerPlugin::run_loop(
+            std::time::Duration::from_secs_f64(1.0 / 60.0),
+        ))
+}
+
+/// Everything that makes up the game, to add on top of `DefaultPlugins` (or the
+/// headless setup of `test_app`). Individual plugins can be swapped out or
+/// disabled like those of any plugin group:
+///
+/// ```no_run
+/// # use bevy::prelude::*;
+/// # use submarine::{MenuPlugin, SubmarinePlugins};
+/// App::new()
+///     .add_plugins((DefaultPlugins, SubmarinePlugins.build().disable::<MenuPlugin>()))
+///     .run();
+/// ```
+pub struct SubmarinePlugins;
+
+impl PluginGroup for SubmarinePlugins {
+    fn build(self) -> PluginGroupBuilder {
+        PluginGroupBuilder::start::<Self>()
+            .add(RapierPhysicsPlugin::<NoUserData>::default())
+            .add(SettingsPlugin)
+            .add(TablesPlugin)
+            .add(HudPlugin)
+            .add(GameOverPlugin)
+            .add(AccessibilityPlugin)
+            .add(CameraShakePlugin)
+            .add(ControlsPlugin)
+            .add(ControlPanelPlugin)
+            .add(TrimComputerPlugin)
+            .add(InteriorPlugin)
+            .add(PeriscopePlugin)
+            .add(BridgePlugin)
+            .add(TacticalPlugin)
+            .add(DepthProfilePlugin)
+            .add(UpwardSonarPlugin)
+            .add(DifficultyPlugin)
+            .add(ContactHistoryPlugin)
+            .add(FishFinderPlugin)
+            .add(InterceptPlugin)
+            .add(StealthPlugin)
+            .add(BiophonyPlugin)
+            .add(CutscenePlugin)
+            .add(TouchPlugin)
+            .add(ProfilingPlugin)
+            .add(SavePlugin)
+            .add(LoadingPlugin)
+            .add(MenuPlugin)
+            .add(SettingsMenuPlugin)
+            .add(ReplayPlugin)
+            .add(RacePlugin)
+            .add(SurvivalPlugin)
+            .add(IronmanPlugin)
+            .add(RescuePlugin)
+            .add(CombatPlugin)
+            .add(FeedingPlugin)
+            .add(FoulingPlugin)
+            .add(BiofoulingPlugin)
+            .add(HullDamagePlugin)
+            .add(CurrentsPlugin)
+            .add(CargoPlugin)
+            .add(IcePlugin)
+            .add(SplashPlugin)
+            .add(DefensePlugin)
+            .add(EelPlugin)
+            .add(JammingPlugin)
+            .add(KrakenPlugin)
+            .add(EnemySubmarinePlugin)
+            .add(EncounterPlugin)
+            .add(MigrationPlugin)
+            .add(OpticsPlugin)
+            .add(MagneticsPlugin)
+            .add(BottomContourPlugin)
+            .add(PickupsPlugin)
+            .add(CleanupPlugin)
+            .add(EcosystemPlugin)
+            .add(ScoringPlugin)
+            .add(StationsPlugin)
+            .add(RepairPlugin)
+            .add(SfxPlugin)
+            .add(MusicPlugin)
+            .add(NewGamePlusPlugin)
+            .add(AcousticsPlugin)
+            .add(DopplerPlugin)
+            .add(EngineSoundPlugin)
+            .add(AmbiencePlugin)
+            .add(CalloutsPlugin)
+            .add(EventLogPlugin)
+            .add(CrewPlugin)
+            .add(FailuresPlugin)
+            .add(LeaksPlugin)
+            .add(TreasurePlugin)
+            .add(PropsPlugin)
+            .add(ScriptingPlugin)
+            .add(CvarsPlugin)
+            .add(ConsolePlugin)
+            .add(TuningPlugin)
+            .add(ScenarioPlugin)
+            .add(EditorPlugin)
+            .add(InputScriptPlugin)
+            .add(BlackBoxPlugin)
+            .add(DebriefPlugin)
+            .add(CorePlugin)
+    }
+}
+
+/// App states, the shared resources and the submarine, lake, sonar and HUD
+/// systems the other plugins build on
+pub struct CorePlugin;
+
+impl Plugin for CorePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_state::<AppState>()
+            .add_sub_state::<PauseState>()
+            .add_sub_state::<CameraMode>()
+            .enable_state_scoped_entities::<AppState>()
+            .enable_state_scoped_entities::<PauseState>()
+            .init_resource::<WorldSeed>()
+            .init_resource::<GameMode>()
+            .init_resource::<GameState>()
+            .init_resource::<CameraState>()
+            .init_resource::<SonarState>()
+            .init_resource::<SonarDetections>()
+            .init_resource::<SelectedContact>()
+            .init_resource::<BubblePool>()
+            .init_resource::<BallastState>()
+            .init_resource::<WaveTime>()
+            .init_resource::<Surfaced>()
+            .init_resource::<LoadedMods>()
+            .configure_sets(
+                Update,
+                (
+                    GameSet::Input.run_if(not(in_state(CameraMode::Cutscene))),
+                    GameSet::Replay,
+                    GameSet::Simulation,
+                    GameSet::Detection,
+                    GameSet::Presentation,
+                )
+                    .chain()
+                    .run_if(in_state(PauseState::Running)),
+            )
+            .add_systems(Startup, spawn_camera)
+            .add_systems(
+                OnEnter(AppState::InGame),
+                (
+                    (reset_game_resources, setup, save::apply_pending_load).chain(),
+                    warm_bubble_pool.after(reset_game_resources),
+                ),
+            )
+            .add_systems(
+                Update,
+                (
+                    // Ballast commands are applied before movement reads the fill level
+                    // A lost hull no longer answers the helm or takes catches
+                    (ballast_control_system, submarine_movement)
+                        .chain()
+                        .run_if(hull_intact),
+                    (fish_movement, collect_fish.run_if(hull_intact)).chain(),
+                    oxygen_system.after(submarine_movement),
+                    floodlight_system,
+                    sonar_sweep_system,
+                    wave_system,
+                    bubble_spawner_system,
+                    bubble_animation_system,
+                )
+                    .in_set(GameSet::Simulation),
+            )
+            .add_systems(Update, sonar_detection_system.in_set(GameSet::Detection))
+            .add_systems(
+                Update,
+                (
+                    (camera_follow, depth_lighting_system).chain(),
+                    ui_system,
+                    sonar_scope_layout_system,
+                    sonar_sweep_update_system,
+                    sonar_blip_system,
+                )
+                    .in_set(GameSet::Presentation),
+            );
+    }
+}
+
+/// The whole game as configured by the command line, ready to run
+pub fn build_app(args: Args) -> App {
+    let mut app = App::new();
+    let mut mods = mods::discover();
+    let scenario = args.scenario.as_deref().map(|path| {
+        Scenario::load(path).unwrap_or_else(|err| {
+            eprintln!("Cannot load scenario: {}", err);
+            std::process::exit(1);
+        })
+    });
+    if let Some(scenario) = &scenario {
+        println!("Loaded scenario {}", scenario.name);
+        mods.0.push(scenario.content());
+    }
+    mods::register_asset_overrides(&mut app, &mods);
+    let tuning_overrides = TuningOverrides {
+        fish_count: args
+            .fish_count
+            .or(scenario.as_ref().and_then(|scenario| scenario.fish_count)),
+        oxygen_rate: args.oxygen_rate,
+        buoyancy_scale: args.buoyancy_scale,
+        start_depth: args.start_depth,
+    };
+
+    let default_plugins = if args.headless {
+        headless_plugins()
+    } else {
+        DefaultPlugins.set(WindowPlugin {
+            primary_window: Some(Window {
+                title: "Submarine".to_string(),
+                // Web builds render into the <canvas id="bevy"> element of index.html
+                canvas: Some("#bevy".to_string()),
+                fit_canvas_to_parent: true,
+                ..default()
+            }),
+            ..default()
+        })
+    };
+
+    app.add_plugins(default_plugins)
+        .add_plugins(SubmarinePlugins)
+        .insert_resource(tuning_overrides);
+
+    // Conditionally add debug render plugin based on command line argument
+    if args.debug_colliders {
+        app.add_plugins(RapierDebugRenderPlugin::default());
+        println!("Physics collider wireframes enabled");
+    } else {
+        println!("Physics collider wireframes disabled (use --debug-colliders to enable)");
+    }
+
+    app.insert_resource(ReplayOutput(args.record_replay.clone()))
+        .insert_resource(Missions(match (&args.script, &scenario) {
+            (Some(script), _) => vec![script.clone()],
+            (None, Some(scenario)) if !scenario.missions.is_empty() => scenario.missions.clone(),
+            _ => mods.script().into_iter().collect(),
+        }))
+        .insert_resource(mods);
+    if let (Some(scenario), Some(path)) = (scenario, &args.scenario) {
+        app.insert_resource(scenario)
+            .insert_resource(ScenarioPath(path.clone()));
+    }
+    if let Some(path) = &args.play_replay {
+        match Replay::load(path) {
+            Ok(replay) => replay::start_playback(&mut app, replay),
+            Err(err) => {
+                eprintln!("Cannot play replay: {}", err);
+                std::process::exit(1);
+            }
+        }
+    }
+    if let Some(path) = &args.input_script {
+        match InputScript::load(path) {
+            Ok(script) => input_script::start(&mut app, script),
+            Err(err) => {
+                eprintln!("Cannot load input script: {}", err);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    if args.capture.is_some() || args.capture_video.is_some() {
+        app.add_plugins(capture::CapturePlugin(capture::CaptureSettings {
+            dir: args.capture.clone(),
+            ffmpeg: args.capture_video.clone(),
+            fps: args.capture_fps,
+            frames: args.capture_frames,
+        }));
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(path) = &args.telemetry {
+        app.add_plugins(telemetry::TelemetryPlugin(telemetry::TelemetrySettings {
+            path: path.clone(),
+            rate: args.telemetry_rate,
+        }));
+    }
+
+    if args.tts {
+        app.insert_resource(accessibility::TtsOverride(true));
+    }
+
+    app
+}
+
+// Helper functions
+
+/// WebGL2 cannot sample the transmission texture, so web builds without WebGPU
+/// fall back to plain alpha blending for the water surface
+fn supports_specular_transmission() -> bool {
+    !cfg!(all(target_arch = "wasm32", not(feature = "webgpu")))
+}
+
+fn normalize_angle(angle: f32) -> f32 {
+    (angle + 2.0 * std::f32::consts::PI) % (2.0 * std::f32::consts::PI)
+}
+
+/// Spawns bubbles near the submarine when air is vented (air_valve_open)
+#[allow(clippy::too_many_arguments)]
+fn bubble_spawner_system(
+    mut commands: Commands,
+    mut pool: ResMut<BubblePool>,
+    mut bubbles: Query<(&mut Transform, &mut Bubble, &mut Visibility), Without<Submarine>>,
+    assets: Res<GameAssets>,
+    ballast_state: Res<BallastState>,
+    query: Query<&Transform, With<Submarine>>,
+    time: Res<Time>,
+    mut timer: Local<f32>,
+    timings: Res<SystemTimings>,
+) {
+    let _timing = timings.scope("bubble spawner");
+    // Only spawn bubbles if vents are open and submarine is underwater
+    if ballast_state.vents_open {
+        if let Ok(sub_transform) = query.single() {
+            // Only spawn bubbles if submarine is underwater (y < 0) and ballast is not full
+            if sub_transform.translation.y < 0.0 && ballast_state.fill_level < 1.0 {
+                // Use a timer to control bubble spawn rate
+                *timer += time.delta_secs();
+                let spawn_interval = 0.08; // seconds between bubbles
+                while *timer > spawn_interval {
+                    *timer -= spawn_interval;
+
+                    // Spawn bubble at a random offset near the bottom of the sub
+                    let rng = rand::random::<f32>();
+                    let offset_x = (rand::random::<f32>() - 0.5) * 0.5;
+                    let offset_z = (rand::random::<f32>() - 0.5) * 0.5;
+                    let bubble_pos =
+                        sub_transform.translation + Vec3::new(offset_x, -0.7, offset_z); // slightly below sub
+
+                    let bubble_radius = 0.08 + rng * 0.06;
+
+                    emit_bubble(
+                        &mut commands,
+                        &mut pool,
+                        &mut bubbles,
+                        &assets,
+                        bubble_pos,
+                        bubble_radius,
+                        1.0 + rng * 0.5,
+                    );
+                }
+            } else {
+                *timer = 0.0;
+            }
+        }
+    } else {
+        *timer = 0.0;
+    }
+}
+
+/// Animates bubbles as they rise, and returns them to the pool once they
+/// reach the surface or burst
+fn bubble_animation_system(
+    mut pool: ResMut<BubblePool>,
+    mut splashes: EventWriter<SurfaceBreak>,
+    tuning: Res<Tuning>,
+    wave_time: Res<WaveTime>,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut Transform, &mut Bubble, &mut Visibility)>,
+    timings: Res<SystemTimings>,
+) {
+    let _timing = timings.scope("bubble animation");
+    let delta = time.delta_secs();
+    for (entity, mut transform, mut bubble, mut visibility) in query.iter_mut() {
+        if !bubble.active {
+            continue;
+        }
+        // Move bubble upward, slower where the water pressure squeezes it smaller
+        let depth = -transform.translation.y;
+        transform.translation.y +=
+            tuning.bubble_rise_speed * depth_factor(tuning.bubble_depth_loss, depth) * delta;
+
+        // Spent once it breaks the swell, with a little ring of foam, or its
+        // timer runs out
+        bubble.timer.tick(time.delta());
+        let position = transform.translation;
+        let surfaced = position.y >= wave_height(position.x, position.z, wave_time.elapsed);
+        if surfaced {
+            splashes.write(SurfaceBreak {
+                position,
+                size: BUBBLE_SPLASH_SIZE,
+            });
+        }
+        if surfaced || bubble.timer.finished() {
+            bubble.active = false;
+            *visibility = Visibility::Hidden;
+            pool.free.push(entity);
+        }
+    }
+}
+
+fn calculate_fish_angle(local_rel: Vec3) -> f32 {
+    // Calculate angle relative to submarine's forward direction
+    // Forward is negative Z in submarine's local space
+    // Add 90 degrees (π/2) to make forward point to the top of the sonar
+    // Negate local_rel.x to fix left/right inversion
+    normalize_angle((-local_rel.x).atan2(-local_rel.z) + std::f32::consts::FRAC_PI_2)
+}
+
+fn calculate_sonar_position(fish_angle: f32, distance: f32, sonar_range: f32) -> (f32, f32) {
+    let scaled_dist = (distance / sonar_range) * SONAR_RADIUS;
+    let blip_x = SONAR_CENTER_X + scaled_dist * fish_angle.cos();
+    let blip_y = SONAR_CENTER_Y - scaled_dist * fish_angle.sin(); // Negative to flip Y axis
+    (blip_x, blip_y)
+}
+
+/// The camera outlives individual dives so the menus have something to render with
+fn spawn_camera(mut commands: Commands) {
+    commands.spawn((
+        Camera3d::default(),
+        Transform::from_xyz(0.0, 8.0, 25.0).looking_at(Vec3::ZERO, Vec3::Y),
+        DistanceFog::default(),
+        CameraFollow,
+        SpatialListener::new(0.3),
+    ));
+}
+
+/// Clears per-dive state so a new game or a load starts from a clean slate
+fn reset_game_resources(mut commands: Commands) {
+    commands.insert_resource(GameState::default());
+    commands.insert_resource(CameraState::default());
+    commands.insert_resource(SonarState::default());
+    commands.insert_resource(SonarDetections::default());
+    commands.insert_resource(SelectedContact::default());
+    commands.insert_resource(BallastState::default());
+    commands.insert_resource(WaveTime::default());
+    commands.insert_resource(Surfaced::default());
+    commands.insert_resource(HelmState::default());
+}
+
+/// Fills the bubble pool ahead of the first vent
+fn warm_bubble_pool(mut commands: Commands, assets: Res<GameAssets>) {
+    let free = (0..BUBBLE_POOL_SIZE)
+        .map(|_| {
+            commands
+                .spawn((
+                    Mesh3d(assets.bubble_mesh.clone()),
+                    MeshMaterial3d(assets.bubble_material.clone()),
+                    Transform::default(),
+                    Visibility::Hidden,
+                    Bubble {
+                        timer: Timer::default(),
+                        active: false,
+                    },
+                    StateScoped(AppState::InGame),
+                ))
+                .id()
+        })
+        .collect();
+    commands.insert_resource(BubblePool { free });
+}
+
+/// Releases a bubble at `position` that rises for `life` seconds, reusing a
+/// spent one when the pool has any
+fn emit_bubble(
+    commands: &mut Commands,
+    pool: &mut BubblePool,
+    bubbles: &mut Query<(&mut Transform, &mut Bubble, &mut Visibility), Without<Submarine>>,
+    assets: &GameAssets,
+    position: Vec3,
+    radius: f32,
+    life: f32,
+) {
+    let transform = Transform::from_translation(position).with_scale(Vec3::splat(radius));
+    let bubble = Bubble {
+        timer: Timer::from_seconds(life, TimerMode::Once),
+        active: true,
+    };
+    if let Some(entity) = pool.free.pop() {
+        if let Ok((mut pooled_transform, mut pooled_bubble, mut visibility)) =
+            bubbles.get_mut(entity)
+        {
+            *pooled_transform = transform;
+            *pooled_bubble = bubble;
+            *visibility = Visibility::Inherited;
+            return;
+        }
+    }
+    commands.spawn((
+        Mesh3d(assets.bubble_mesh.clone()),
+        MeshMaterial3d(assets.bubble_material.clone()),
+        transform,
+        Visibility::Inherited,
+        bubble,
+        StateScoped(AppState::InGame),
+    ));
+}
+
+/// Starts a fresh dive in `mode` with the intro flyover; courses use a fixed
+/// seed so every run (and every ghost) sees the same terrain
+pub fn start_new_game(
+    commands: &mut Commands,
+    next_state: &mut NextState<AppState>,
+    mode: GameMode,
+) {
+    let seed = match mode {
+        GameMode::TimeTrial => WorldSeed(race::COURSE_SEED),
+        GameMode::Dive | GameMode::Survival | GameMode::Defense => WorldSeed::default(),
+    };
+    commands.insert_resource(seed);
+    commands.insert_resource(mode);
+    commands.insert_resource(NewGamePlus::default());
+    commands.insert_resource(Ironman::default());
+    commands.insert_resource(PendingLoad(None));
+    // Courses start against the clock, so only free dives open with the flyover
+    if mode != GameMode::TimeTrial {
+        commands.insert_resource(PlayIntro);
+    }
+    next_state.set(AppState::Loading);
+}
+
+#[allow(clippy::too_many_arguments)]
+fn setup(
+    mut commands: Commands,
+    mut window_query: Query<&mut Window, With<PrimaryWindow>>,
+    assets: Res<GameAssets>,
+    tuning: Res<Tuning>,
+    class: Res<SubmarineClass>,
+    mode: Res<GameMode>,
+    scenario: Option<Res<Scenario>>,
+) {
+    // Courses keep their own layout; the terrain and fish were built while
+    // loading
+    let scenario = scenario.filter(|_| *mode != GameMode::TimeTrial);
+
+    // Hide mouse cursor
+    if let Ok(mut window) = window_query.single_mut() {
+        window.cursor_options.visible = false;
+    }
+
+    // Lighting with softer underwater ambiance - no shadows to avoid falloff
+    commands.spawn((
+        StateScoped(AppState::InGame),
+        DirectionalLight {
+            shadows_enabled: false,
+            illuminance: 12000.0,
+            color: Color::srgb(0.7, 0.8, 0.9),
+            ..default()
+        },
+        Transform::from_xyz(4.0, 15.0, 4.4).looking_at(Vec3::ZERO, Vec3::Y),
+        DepthLighting,
+    ));
+
+    // Add underwater-appropriate ambient light
+    commands.insert_resource(AmbientLight {
+        color: Color::srgb(0.3, 0.5, 0.7),
+        brightness: 800.0,
+        affects_lightmapped_meshes: false,
+    });
+
+    // Submarine, sized by its class
+    let start = match scenario.as_ref().and_then(|scenario| scenario.start) {
+        Some((x, y, z)) => Transform::from_xyz(x, y, z),
+        None => Transform::from_xyz(0.0, -tuning.start_depth, 0.0),
+    };
+    spawn_submarine(&mut commands, &assets, &class, start);
+
+    // UI - panels are absolutely positioned and placed by the HUD layout settings
+    commands
+        .spawn((
+            StateScoped(AppState::InGame),
+            Node {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                ..default()
+            },
+            BackgroundColor(Color::NONE),
+        ))
+        .with_children(|parent| {
+            // Main HUD text block
+            parent
+                .spawn((
+                    Node {
+                        position_type: PositionType::Absolute,
+                        flex_direction: FlexDirection::Column,
+                        ..default()
+                    },
+                    BackgroundColor(Color::NONE),
+                    HudTextPanel,
+                ))
+                .with_children(|parent| {
+                    parent.spawn((
+                        Text::new("Submarine Game\n\nScore: 0\nHealth: 100.0%\nOxygen: 100.0%\nBallast: 0.0%\nCompressed Air: 100.0%\nElectricity: 100.0%\nTelegraph: Stop [Autopilot OFF]\n\nSpeed: 0.0 m/s  Noise: 0%\nDepth: 0.0 m\nPitch: 0.0°\nYaw: 0.0°\nRoll: 0.0°\n\nSonar Debug:\nSub Yaw: 0.0°\nSweep: 0.0°\nFish Angle: 0.0°\nNo fish detected\n\nWASD: Move  PgUp/PgDn: Dive Planes\nQ: Toggle Vents\nE: Toggle Air Valve\nR: Toggle Compressor\nY/U: Hold Flood/Blow  ,/.: Trim Target  I: Trim Computer\nT/G: Telegraph Ahead/Astern\nP: Autopilot  X: Select Contact  ': Intercept\nSpace: Torpedo  F: Decoy  B: Bait  K: Arm  J: Diver\nL: Floodlights  H: Repair Hull  O: Distress Buoy  ;: Strap Cargo\n\\: Control Panel  Tab: Event Log\nArrow Keys: Camera  C: Camera Style  V: View  N: Tactical Map\n-/=: HUD Scale  F2: Sonar Size  F6-F9: HUD Layout\nF5: Quicksave  F10: Quickload\nCollect fish to score points!"),
+                        TextFont {
+                            font_size: 16.0,
+                            font: assets.font.clone(),
+                            ..default()
+                        },
+                        TextColor(Color::WHITE),
+                        StatusText,
+                    ));
+                });
+
+            // Sonar panel
+            parent
+                .spawn((
+                    Node {
+                        position_type: PositionType::Absolute,
+                        width: Val::Px(200.0),
+                        height: Val::Px(200.0),
+                        ..default()
+                    },
+                    BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.5)),
+                    SonarPanel,
+                ))
+                .with_children(|sonar_parent| {
+                    // Ring, cross and center dot are laid out for the panel's size
+                    // by `sonar_scope_layout_system`
+                    for i in 0..360 {
+                        sonar_parent.spawn((
+                            Node {
+                                position_type: PositionType::Absolute,
+                                ..default()
+                            },
+                            BackgroundColor(Color::srgb(0.0, 0.5, 0.0)),
+                            ScopeMark::Ring(i as f32 * std::f32::consts::PI / 180.0),
+                        ));
+                    }
+                    for mark in [ScopeMark::VerticalLine, ScopeMark::HorizontalLine, ScopeMark::CenterDot] {
+                        sonar_parent.spawn((
+                            Node {
+                                position_type: PositionType::Absolute,
+                                ..default()
+                            },
+                            BackgroundColor(Color::srgb(0.0, 1.0, 0.0)),
+                            mark,
+                        ));
+                    }
+
+                    // Create blip entities for fish detection
+                    for _ in 0..20 {
+                        sonar_parent.spawn((
+                            Node {
+                                width: Val::Px(6.0),
+                                height: Val::Px(6.0),
+                                position_type: PositionType::Absolute,
+                                left: Val::Px(0.0),
+                                top: Val::Px(0.0),
+                                ..default()
+                            },
+                            BackgroundColor(Color::srgba(0.0, 1.0, 0.0, 0.0)), // Transparent initially
+                            SonarBlip::default(),
+                        ));
+                    }
+
+                    // Create sweep line segments for rotating sweep effect
+                    for _ in 0..20 {
+                        sonar_parent.spawn((
+                            Node {
+                                width: Val::Px(2.0),
+                                height: Val::Px(2.0),
+                                position_type: PositionType::Absolute,
+                                left: Val::Px(100.0),
+                                top: Val::Px(100.0),
+                                ..default()
+                            },
+                            BackgroundColor(Color::srgb(0.0, 1.0, 0.0)),
+                            SonarSweepLine,
+                        ));
+                    }
+                });
+        });
+}
+
+/// Spawns the boat at `transform`, its hull and collider sized by its class
+fn spawn_submarine(
+    commands: &mut Commands,
+    assets: &GameAssets,
+    class: &SubmarineClass,
+    transform: Transform,
+) -> Entity {
+    let size = class.hull;
+    // Parts are laid out for the patrol boat's hull, then scaled with it
+    let part = |transform: Transform| {
+        transform
+            .with_translation(transform.translation * size)
+            .with_scale(Vec3::splat(size))
+    };
+    let submarine_entity = commands
+        .spawn((
+            transform,
+            Visibility::default(),
+            Submarine,
+            Breaching::new(HULL_HALF_LENGTH * size),
+            RigidBody::Dynamic,
+            Collider::capsule(
+                Vec3::new(0.0, 0.0, -2.0 * size),
+                Vec3::new(0.0, 0.0, 2.0 * size),
+                0.7 * size,
+            ),
+            Velocity::default(),
+            GravityScale(0.0),
+            ActiveEvents::COLLISION_EVENTS,
+            StateScoped(AppState::InGame),
+        ))
+        .id();
+
+    // Add child entities for the submarine parts
+    commands.entity(submarine_entity).with_children(|parent| {
+        // Main hull (cylinder) - now pointing along Z-axis
+        parent.spawn((
+            Mesh3d(assets.hull_mesh.clone()),
+            MeshMaterial3d(assets.hull_material.clone()),
+            part(Transform::from_rotation(Quat::from_rotation_x(
+                std::f32::consts::FRAC_PI_2,
+            ))),
+            Hull,
+        ));
+
+        // Bow (front sphere) - at positive Z
+        parent.spawn((
+            Mesh3d(assets.hull_end_mesh.clone()),
+            MeshMaterial3d(assets.hull_material.clone()),
+            part(Transform::from_xyz(0.0, 0.0, HULL_END_OFFSET)),
+            Hull,
+        ));
+
+        // Stern (back sphere) - at negative Z
+        parent.spawn((
+            Mesh3d(assets.hull_end_mesh.clone()),
+            MeshMaterial3d(assets.hull_material.clone()),
+            part(Transform::from_xyz(0.0, 0.0, -HULL_END_OFFSET)),
+            Hull,
+        ));
+
+        // Horizontal stabilizers (wings) - at the stern
+
+        // Left wing
+        parent.spawn((
+            Mesh3d(assets.wing_mesh.clone()),
+            MeshMaterial3d(assets.wing_material.clone()),
+            part(Transform::from_xyz(-0.9, 0.0, -0.2)),
+        ));
+
+        // Right wing
+        parent.spawn((
+            Mesh3d(assets.wing_mesh.clone()),
+            MeshMaterial3d(assets.wing_material.clone()),
+            part(Transform::from_xyz(0.9, 0.0, -0.2)),
+        ));
+
+        // Vertical stabilizer (rudder) - at the stern
+        parent.spawn((
+            Mesh3d(assets.rudder_mesh.clone()),
+            MeshMaterial3d(assets.wing_material.clone()),
+            part(Transform::from_xyz(0.0, 0.7, -0.2)),
+        ));
+
+        // Floodlights, pointing the way the submarine moves (negative Z)
+        parent.spawn((
+            SpotLight {
+                color: Color::srgb(1.0, 0.95, 0.85),
+                intensity: 2_000_000.0,
+                range: 40.0,
+                outer_angle: 0.5,
+                inner_angle: 0.3,
+                ..default()
+            },
+            Transform::from_xyz(0.0, 0.0, -2.8 * size).looking_to(Vec3::NEG_Z, Vec3::Y),
+            Floodlight,
+        ));
+    });
+    submarine_entity
+}
+
+/// The sea floor and the water surface
+fn spawn_seabed(commands: &mut Commands, assets: &GameAssets) {
+    commands.spawn((
+        StateScoped(AppState::InGame),
+        Mesh3d(assets.seabed_mesh.clone()),
+        MeshMaterial3d(assets.seabed_material.clone()),
+        Transform::from_xyz(0.0, -20.5, 0.0),
+        RigidBody::Fixed,
+        Collider::cuboid(900.0, 0.1, 900.0),
+    ));
+
+    // Water surface with realistic waves - re-enabled with better lighting
+    commands.spawn((
+        StateScoped(AppState::InGame),
+        Mesh3d(assets.water_mesh.clone()),
+        MeshMaterial3d(assets.water_material.clone()),
+        Transform::from_xyz(0.0, -0.1, 0.0),
+        WaterSurface,
+    ));
+}
+
+/// A cone of terrain standing on the sea floor, drawn by scaling the shared
+/// unit cone; its collider keeps the size it is given in meters
+fn terrain_cone(
+    mesh: &Handle<Mesh>,
+    material: &Handle<StandardMaterial>,
+    x: f32,
+    z: f32,
+    radius: f32,
+    height: f32,
+) -> impl Bundle {
+    (
+        Mesh3d(mesh.clone()),
+        MeshMaterial3d(material.clone()),
+        Transform::from_xyz(x, height / 2.0 - 20.5, z) // Base below sea floor level
+            .with_scale(Vec3::new(radius, height, radius)),
+        ColliderScale::Absolute(Vec3::ONE),
+    )
+}
+
+/// The ring of mountains around the lake and its taller peaks
+fn spawn_mountains(commands: &mut Commands, assets: &GameAssets, rng: &mut StdRng) {
+    // Create circular mountain range boundary
+    let mountain_radius = 550.0;
+    let mountain_count = 36;
+
+    for i in 0..mountain_count {
+        let angle = (i as f32) * 2.0 * std::f32::consts::PI / mountain_count as f32;
+        let radius_variation = (rng.gen::<f32>() - 0.5) * 50.0;
+        let radius = mountain_radius + radius_variation;
+
+        let x = angle.cos() * radius;
+        let z = angle.sin() * radius;
+        let height = 50.0 + rng.gen::<f32>() * 40.0; // Mountains 50-90 units tall
+        let base_radius = 25.0 + rng.gen::<f32>() * 15.0; // Base radius variation
+
+        commands.spawn((
+            StateScoped(AppState::InGame),
+            terrain_cone(
+                &assets.cone_mesh,
+                &assets.mountain_material,
+                x,
+                z,
+                base_radius,
+                height,
+            ),
+            RigidBody::Fixed,
+            Collider::cylinder(height / 2.0, base_radius * 0.5),
+            Mountain,
+        ));
+    }
+
+    // Add some taller peaks for visual variety
+    for i in 0..12 {
+        let angle = (i as f32) * 2.0 * std::f32::consts::PI / 12.0;
+        let radius = mountain_radius + (rng.gen::<f32>() - 0.5) * 80.0;
+
+        let x = angle.cos() * radius;
+        let z = angle.sin() * radius;
+        let height = 100.0 + rng.gen::<f32>() * 60.0; // Tall peaks 100-160 units
+        let base_radius = 35.0 + rng.gen::<f32>() * 20.0;
+
+        commands.spawn((
+            StateScoped(AppState::InGame),
+            terrain_cone(
+                &assets.cone_mesh,
+                &assets.mountain_material,
+                x,
+                z,
+                base_radius,
+                height,
+            ),
+            RigidBody::Fixed,
+            Collider::cylinder(height / 2.0, base_radius * 0.4),
+            Mountain,
+        ));
+
+        // Add smaller satellite peaks around major peaks for clusters
+        let cluster_count = 2 + (rng.gen::<f32>() * 3.0) as i32;
+        for _ in 0..cluster_count {
+            let offset_angle = rng.gen::<f32>() * 2.0 * std::f32::consts::PI;
+            let offset_distance = 30.0 + rng.gen::<f32>() * 40.0;
+            let cluster_x = x + offset_angle.cos() * offset_distance;
+            let cluster_z = z + offset_angle.sin() * offset_distance;
+            let cluster_height = 20.0 + rng.gen::<f32>() * 40.0;
+            let cluster_radius = 15.0 + rng.gen::<f32>() * 10.0;
+
+            commands.spawn((
+                StateScoped(AppState::InGame),
+                terrain_cone(
+                    &assets.cone_mesh,
+                    &assets.mountain_material,
+                    cluster_x,
+                    cluster_z,
+                    cluster_radius,
+                    cluster_height,
+                ),
+                RigidBody::Fixed,
+                Collider::cylinder(cluster_height / 2.0, cluster_radius * 0.5),
+                Mountain,
+            ));
+        }
+    }
+}
+
+/// Foothills and, with the rocks, a natural transition from the lake floor
+/// to the mountains
+fn spawn_foothills(commands: &mut Commands, assets: &GameAssets, rng: &mut StdRng) {
+    // Inner ring of foothills (smaller cone mountains)
+    for i in 0..60 {
+        let angle = (i as f32) * 2.0 * std::f32::consts::PI / 60.0;
+        let radius = 450.0 + (rng.gen::<f32>() - 0.5) * 100.0;
+
+        let x = angle.cos() * radius;
+        let z = angle.sin() * radius;
+        let height = 15.0 + rng.gen::<f32>() * 25.0; // Foothills 15-40 units tall
+        let base_radius = 12.0 + rng.gen::<f32>() * 8.0;
+
+        commands.spawn((
+            StateScoped(AppState::InGame),
+            terrain_cone(
+                &assets.cone_mesh,
+                &assets.foothill_material,
+                x,
+                z,
+                base_radius,
+                height,
+            ),
+            RigidBody::Fixed,
+            Collider::cylinder(height / 2.0, base_radius * 0.6),
+            Foothill,
+        ));
+    }
+}
+
+fn spawn_rocks(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+    rng: &mut StdRng,
+    props: &mut PropLibrary,
+) {
+    // Underwater rocks scattered around the edges (irregular cuboid shapes)
+    for _i in 0..40 {
+        let angle = rng.gen::<f32>() * 2.0 * std::f32::consts::PI;
+        let radius = 350.0 + rng.gen::<f32>() * 150.0;
+
+        let x = angle.cos() * radius;
+        let z = angle.sin() * radius;
+        let width = 1.0 + rng.gen::<f32>() * 3.0;
+        let height = 1.0 + rng.gen::<f32>() * 4.0;
+        let depth = 1.0 + rng.gen::<f32>() * 3.0;
+
+        // Use irregular cuboids for clearly distinct rock shapes
+        let transform = Transform::from_xyz(x, -20.5 + height / 2.0, z)
+            .with_rotation(Quat::from_euler(
+                EulerRot::XYZ,
+                rng.gen::<f32>() * 0.5,
+                rng.gen::<f32>() * std::f32::consts::TAU,
+                rng.gen::<f32>() * 0.5,
+            ))
+            .with_scale(Vec3::new(width, height, depth));
+        props.spawn(commands, meshes, materials, rng, "rock", transform);
+    }
+}
+
+/// Spawns the fish numbered `indices`; false once the tables have no species
+/// left to choose from
+fn spawn_fish_batch(
+    commands: &mut Commands,
+    assets: &GameAssets,
+    rng: &mut StdRng,
+    tables: &Tables,
+    scenario: Option<&Scenario>,
+    new_game_plus: NewGamePlus,
+    indices: std::ops::Range<usize>,
+) -> bool {
+    // Fish are distributed across a much larger area
+    for i in indices {
+        // Create multiple rings of fish at different distances
+        let ring = (i / 20) as f32; // 4 rings of 20 fish each
+        let angle_in_ring = ((i % 20) as f32) * 2.0 * std::f32::consts::PI / 20.0;
+        let base_distance = 20.0 + ring * 40.0; // Rings at 20, 60, 100, 140 units
+        let distance_variation = (rng.gen::<f32>() - 0.5) * 30.0; // Add some randomness
+        let distance = base_distance + distance_variation;
+
+        let Ok(species) = tables
+            .fish
+            .choose_weighted(rng, |species| new_game_plus.species_weight(species))
+        else {
+            return false;
+        };
+        let (x, z) = match scenario.and_then(|scenario| scenario.fish_position(rng)) {
+            Some(spot) => (spot.x, spot.y),
+            None => (
+                angle_in_ring.cos() * distance,
+                angle_in_ring.sin() * distance,
+            ),
+        };
+        let depth = new_game_plus.fish_depth(species);
+        let y = -rng.gen_range(depth.0..=depth.1);
+
+        spawn_fish(commands, assets, rng, species, Vec3::new(x, y, z));
+    }
+    true
+}
+
+/// Spawns a single fish of a species with a random size, swimming direction and speed
+fn spawn_fish(
+    commands: &mut Commands,
+    assets: &GameAssets,
+    rng: &mut impl Rng,
+    species: &FishSpecies,
+    position: Vec3,
+) {
+    let Some((mesh, material)) = assets.fish.get(&species.name) else {
+        warn!("No assets for fish species {:?}", species.name);
+        return;
+    };
+    let scale = rng.gen_range(species.size.0..=species.size.1);
+    commands.spawn((
+        Mesh3d(mesh.clone()),
+        MeshMaterial3d(material.clone()),
+        Transform::from_translation(position).with_scale(Vec3::splat(scale)),
+        Fish,
+        FishSize {
+            scale,
+            undersized: scale < species.min_size,
+        },
+        Name::new(species.name.clone()),
+        RigidBody::Dynamic,
+        Collider::ball(species.radius),
+        GravityScale(0.0),
+        FishMovement {
+            direction: Vec3::new(
+                (rng.gen::<f32>() - 0.5) * 2.0,
+                (rng.gen::<f32>() - 0.5) * 0.4,
+                (rng.gen::<f32>() - 0.5) * 2.0,
+            )
+            .normalize(),
+            speed: rng.gen_range(species.speed.0..=species.speed.1),
+            change_direction_timer: 0.0,
+            change_direction_interval: 2.0 + rng.gen::<f32>() * 3.0,
+            fleeing: 0.0,
+            light: species.light,
+        },
+        StateScoped(AppState::InGame),
+    ));
+}
+
+#[allow(clippy::too_many_arguments)]
+fn submarine_movement(
+    helm_input: Res<HelmInput>,
+    mut submarine_query: Query<(&mut Velocity, &mut Transform), With<Submarine>>,
+    mut camera_state: ResMut<CameraState>,
+    ballast_state: Res<BallastState>,
+    mut helm_state: ResMut<HelmState>,
+    navigation: Res<Navigation>,
+    crew: Res<Crew>,
+    flooding: Res<Flooding>,
+    cargo: Res<CargoLoad>,
+    mut surfaced: ResMut<Surfaced>,
+    wave_time: Res<WaveTime>,
+    settings: Res<Settings>,
+    tuning: Res<Tuning>,
+    time: Res<Time>,
+) {
+    if let Ok((mut velocity, mut transform)) = submarine_query.single_mut() {
+        let camera_rotation_speed = 2.0 * settings.gameplay.camera_speed; // radians/sec
+        let camera_pitch_input = if settings.gameplay.invert_camera_pitch {
+            -helm_input.camera_pitch
+        } else {
+            helm_input.camera_pitch
+        };
+
+        // Forward/backward in facing direction
+        let mut move_direction = helm_input.throttle;
+        // Without W/S held, the engine telegraph sets a standing speed order
+        if move_direction == 0.0 {
+            move_direction = helm_state.telegraph.throttle();
+        }
+
+        // Turn left/right
+        let mut turn_direction = helm_input.turn;
+        // The autopilot steers by the compass, so it wanders inside magnetic anomalies
+        let yaw = transform.rotation.to_euler(EulerRot::YXZ).0 + navigation.compass_error;
+        if turn_direction != 0.0 {
+            // Manual steering moves the course the autopilot holds
+            helm_state.autopilot_heading = yaw;
+        } else if helm_state.autopilot {
+            let heading_error =
+                normalize_angle(helm_state.autopilot_heading - yaw + std::f32::consts::PI)
+                    - std::f32::consts::PI;
+            turn_direction = (heading_error * 2.0).clamp(-1.0, 1.0);
+        }
+        if turn_direction != 0.0 {
+            transform.rotate(Quat::from_rotation_y(
+                turn_direction
+                    * tuning.turn_speed
+                    * crew.efficiency(Station::Helm)
+                    * cargo.handling()
+                    * time.delta_secs(),
+            ));
+        }
+
+        // Camera rotation with arrow keys
+        camera_state.yaw += helm_input.camera_yaw * camera_rotation_speed * time.delta_secs();
+        camera_state.pitch += camera_pitch_input * camera_rotation_speed * time.delta_secs();
+        camera_state.pitch = camera_state.pitch.clamp(-1.0, 1.0);
+
+        // Calculate movement in local forward direction
+        let mut local_velocity = Vec3::ZERO;
+        if move_direction.abs() > 0.0 {
+            // Forward is negative Z in standard Bevy coordinates
+            local_velocity +=
+                transform.rotation * Vec3::new(0.0, 0.0, -1.0) * move_direction * tuning.speed;
+        }
+
+        if local_velocity.length() > 0.0 {
+            velocity.linvel = local_velocity;
+        } else {
+            velocity.linvel *= 0.9; // Apply some drag
+        }
+
+        // The water surface over the hull, lifted and dropped by the swell
+        let hull = *transform;
+        let surface_at = |offset: Vec3| {
+            let point = hull.translation + hull.rotation * offset;
+            wave_height(point.x, point.z, wave_time.elapsed)
+        };
+        let surface = surface_at(Vec3::ZERO);
+
+        // Prevent submarine from rising out of the water; at the surface it
+        // heaves with the swell. A hull held down by a falling swell is still in
+        // the water, so buoyancy acts on it too
+        transform.translation.y = transform.translation.y.min(surface);
+
+        // Apply realistic buoyancy force (constant upward force minus ballast weight)
+        // Apply to all underwater positions, including at the surface
+        // Constant upward buoyancy force (like real physics)
+        let upward_buoyancy = tuning.base_buoyancy_force;
+
+        // Downward force from ballast tanks (fills with water, making submarine heavier)
+        // from any water flooding the compartments through leaks, and from cargo
+        // strapped to the hull
+        let ballast_weight = (ballast_state.fill_level + flooding.weight() + cargo.weight)
+            * tuning.ballast_buoyancy_force;
+
+        let net_buoyancy_force = (upward_buoyancy - ballast_weight) * tuning.buoyancy_scale;
+        velocity.linvel.y += net_buoyancy_force * time.delta_secs();
+
+        // Stop upward velocity when hitting the surface
+        if transform.translation.y >= surface && velocity.linvel.y > 0.0 {
+            velocity.linvel.y = 0.0;
+        }
+        surfaced.0 = transform.translation.y >= surface - SURFACE_TOLERANCE;
+
+        // A surfaced hull pitches and rolls with the slope of the swell under it
+        if surfaced.0 {
+            let pitch = ((surface_at(Vec3::NEG_Z * HULL_HALF_LENGTH)
+                - surface_at(Vec3::Z * HULL_HALF_LENGTH))
+                / (2.0 * HULL_HALF_LENGTH))
+                .atan();
+            let roll = ((surface_at(Vec3::X * HULL_HALF_BEAM)
+                - surface_at(Vec3::NEG_X * HULL_HALF_BEAM))
+                / (2.0 * HULL_HALF_BEAM))
+                .atan();
+            let yaw = transform.rotation.to_euler(EulerRot::YXZ).0;
+            let trim = Quat::from_euler(EulerRot::YXZ, yaw, pitch, roll);
+            transform.rotation = transform
+                .rotation
+                .slerp(trim, (tuning.wave_trim_rate * time.delta_secs()).min(1.0));
+        } else {
+            // Submerged, the dive planes pitch the bow while held, so throttle
+            // drives the boat up or down along it; released, the hull settles
+            // back to the trim its load gives it
+            let (yaw, mut pitch, roll) = transform.rotation.to_euler(EulerRot::YXZ);
+            let level = (tuning.level_rate * time.delta_secs()).min(1.0);
+            if helm_input.planes != 0.0 {
+                pitch = (pitch + helm_input.planes * tuning.plane_rate * time.delta_secs())
+                    .clamp(-tuning.max_plane_angle, tuning.max_plane_angle);
+            } else {
+                pitch += (cargo.trim() - pitch) * level;
+            }
+            transform.rotation = Quat::from_euler(EulerRot::YXZ, yaw, pitch, roll - roll * level);
+        }
+    }
+}
+
+fn camera_follow(
+    submarine_query: Query<&Transform, With<Submarine>>,
+    mut camera_query: Query<&mut Transform, (With<CameraFollow>, Without<Submarine>)>,
+    mut camera_state: ResMut<CameraState>,
+    camera_mode: Res<State<CameraMode>>,
+    rapier_context: ReadRapierContext,
+    time: Res<Time>,
+) {
+    if let Ok(submarine_transform) = submarine_query.single() {
+        if let Ok(mut camera_transform) = camera_query.single_mut() {
+            // The periscope, bridge, tactical and cutscene views are placed by their own plugins
+            if matches!(
+                camera_mode.get(),
+                CameraMode::Periscope
+                    | CameraMode::Bridge
+                    | CameraMode::Tactical
+                    | CameraMode::Cutscene
+            ) {
+                return;
+            }
+            if *camera_mode.get() == CameraMode::ControlRoom {
+                // Eye level in the forward end of the hull, looking the way the submarine moves
+                *camera_transform = submarine_transform.mul_transform(
+                    Transform::from_xyz(0.0, 0.35, -1.6).looking_to(Vec3::NEG_Z, Vec3::Y),
+                );
+                // Leaving the control room then eases the camera back out from the hull
+                camera_state.position = camera_transform.translation;
+                camera_state.yaw = submarine_transform.rotation.to_euler(EulerRot::YXZ).0;
+                return;
+            }
+
+            // Get submarine's yaw rotation
+            let submarine_yaw = submarine_transform.rotation.to_euler(EulerRot::YXZ).0;
+
+            // Update target yaw to follow submarine rotation
+            camera_state.target_yaw = submarine_yaw;
+
+            // Smoothly interpolate camera yaw towards target yaw (rubber band effect)
+            let yaw_lerp_speed = 2.0; // Adjust this for faster/slower camera following
+            let angle_diff = (camera_state.target_yaw - camera_state.yaw + std::f32::consts::PI)
+                % (2.0 * std::f32::consts::PI)
+                - std::f32::consts::PI;
+            camera_state.yaw += angle_diff * yaw_lerp_speed * time.delta_secs();
+
+            let target_position = submarine_transform.translation + camera_state.offset();
+            let mut position = camera_state.position.lerp(target_position, 0.1);
+
+            // Stay on the submarine's side of the water surface rather than
+            // straddling it
+            let pivot = submarine_transform.translation;
+            if -pivot.y < SURFACED_CAMERA_DEPTH {
+                position.y = position.y.max(SURFACE_CLEARANCE);
+            } else {
+                position.y = position.y.min(-SURFACE_CLEARANCE);
+            }
+
+            // Spring arm: pull in at once in front of the sea floor or a
+            // mountain, then ease back out through the smoothing above
+            let arm = position - pivot;
+            if let (Ok(context), Some(direction)) = (rapier_context.single(), arm.try_normalize()) {
+                let filter = QueryFilter::only_fixed().exclude_sensors();
+                if let Some((_, toi)) =
+                    context.cast_ray(pivot, direction, arm.length(), true, filter)
+                {
+                    position = pivot + direction * (toi - CAMERA_CLEARANCE).max(CAMERA_MIN_ARM);
+                }
+            }
+
+            camera_state.position = position;
+            camera_transform.translation = camera_state.position;
+            camera_transform.look_at(submarine_transform.translation, camera_state.up());
+        }
+    }
+}
+
+/// Where the chase camera sits relative to the submarine for a given orbit.
+/// When yaw=0, pitch=0 the camera is behind the submarine (positive Z)
+fn chase_offset(distance: f32, height: f32, yaw: f32, pitch: f32) -> Vec3 {
+    Vec3::new(
+        distance * yaw.sin(),
+        distance * pitch.sin() + height,
+        distance * yaw.cos() * pitch.cos(),
+    )
+}
+
+/// Fish wander, and dart away from the submarine once it comes within a
+/// reaction radius that grows with the noise it makes. In dark water the
+/// floodlight beam draws some species in and drives others off, and every
+/// species gathers round released bait.
+#[allow(clippy::too_many_arguments)]
+fn fish_movement(
+    mut fish_query: Query<(&mut Transform, &mut FishMovement), With<Fish>>,
+    submarine_query: Query<&Transform, (With<Submarine>, Without<Fish>)>,
+    bait_query: Query<&Transform, (With<Bait>, Without<Fish>)>,
+    noise: Res<SubmarineNoise>,
+    helm_state: Res<HelmState>,
+    tuning: Res<Tuning>,
+    time: Res<Time>,
+    timings: Res<SystemTimings>,
+) {
+    let _timing = timings.scope("fish movement");
+    let submarine = submarine_query.single().ok();
+    let reaction_radius = tuning.fish_reaction_radius * noise.0;
+    for (mut fish_transform, mut fish_movement) in fish_query.iter_mut() {
+        let delta_time = time.delta_secs();
+
+        // Update direction change timer
+        fish_movement.change_direction_timer += delta_time;
+        fish_movement.fleeing = (fish_movement.fleeing - delta_time).max(0.0);
+
+        // Caught in the beam: the darker the water, the further off it is seen
+        let lamp = submarine
+            .filter(|_| helm_state.lights_on)
+            .map(|submarine| submarine.translation + submarine.forward() * 3.0);
+        let beam = lamp.zip(submarine).and_then(|(lamp, submarine)| {
+            let to_fish = fish_transform.translation - lamp;
+            let darkness = (-fish_transform.translation.y / FLOODLIGHT_DARK_DEPTH).clamp(0.0, 1.0);
+            let lit = to_fish.length() < FLOODLIGHT_REACH * darkness
+                && to_fish.angle_between(*submarine.forward()) < FLOODLIGHT_ANGLE;
+            lit.then_some(to_fish)
+        });
+        if let Some(to_fish) = beam {
+            match fish_movement.light {
+                LightResponse::Attracted => {
+                    fish_movement.direction = (-to_fish).try_normalize().unwrap_or(Vec3::X);
+                    fish_movement.change_direction_timer = 0.0;
+                }
+                LightResponse::Repelled => {
+                    fish_movement.direction = (to_fish * Vec3::new(1.0, 0.3, 1.0))
+                        .try_normalize()
+                        .unwrap_or(Vec3::X);
+                    fish_movement.fleeing = tuning.fish_flee_time;
+                    fish_movement.change_direction_timer = 0.0;
+                }
+                LightResponse::Indifferent => {}
+            }
+        }
+
+        // Smelled the bait: head for the nearest canister
+        let bait = bait_query
+            .iter()
+            .map(|bait| bait.translation - fish_transform.translation)
+            .filter(|to_bait| to_bait.length() < BAIT_RANGE)
+            .min_by(|a, b| a.length().total_cmp(&b.length()));
+        if let Some(to_bait) = bait {
+            fish_movement.direction = to_bait.try_normalize().unwrap_or(Vec3::X);
+            fish_movement.change_direction_timer = 0.0;
+        }
+
+        // Heard the submarine: turn tail, staying mostly level
+        let away = submarine
+            .map(|submarine| fish_transform.translation - submarine.translation)
+            .filter(|away| away.length() < reaction_radius);
+        if let Some(away) = away {
+            fish_movement.direction = (away * Vec3::new(1.0, 0.3, 1.0))
+                .try_normalize()
+                .unwrap_or(Vec3::X);
+            fish_movement.fleeing = tuning.fish_flee_time;
+            fish_movement.change_direction_timer = 0.0;
+        }
+
+        // Change direction when timer expires
+        if fish_movement.change_direction_timer >= fish_movement.change_direction_interval {
+            // Generate new random direction with emphasis on lateral movement
+            let random_x = (fish_movement.change_direction_timer * 0.5
+                + fish_transform.translation.x * 0.1)
+                .sin()
+                * 2.0
+                - 1.0;
+            let random_y = (fish_movement.change_direction_timer * 0.3
+                + fish_transform.translation.y * 0.2)
+                .cos()
+                * 0.5
+                - 0.25; // Reduced vertical movement
+            let random_z = (fish_movement.change_direction_timer * 0.7
+                + fish_transform.translation.z * 0.1)
+                .sin()
+                * 2.0
+                - 1.0;
+
+            fish_movement.direction = Vec3::new(random_x, random_y, random_z).normalize();
+
+            // Reset timer and set new random interval (more variation)
+            fish_movement.change_direction_timer = 0.0;
+            fish_movement.change_direction_interval = 1.5
+                + (fish_movement.change_direction_timer * 0.2
+                    + fish_transform.translation.x * 0.01)
+                    .sin()
+                    * 2.0;
+        }
+
+        // Add some lateral swaying motion
+        let sway_x =
+            (fish_movement.change_direction_timer * 2.0 + fish_transform.translation.x * 0.1).sin()
+                * 0.3;
+        let sway_z =
+            (fish_movement.change_direction_timer * 1.5 + fish_transform.translation.z * 0.1).cos()
+                * 0.3;
+
+        // Move fish in current direction with added lateral sway
+        let speed = if fish_movement.fleeing > 0.0 {
+            fish_movement.speed * tuning.fish_flee_speed
+        } else {
+            fish_movement.speed
+        };
+        let base_movement = fish_movement.direction * speed * delta_time;
+        let sway_movement = Vec3::new(sway_x, 0.0, sway_z) * delta_time;
+        fish_transform.translation += base_movement + sway_movement;
+
+        // Prevent fish from going above the surface (Y > 0)
+        if fish_transform.translation.y > 0.0 {
+            fish_transform.translation.y = 0.0;
+            // Bounce off surface by inverting Y direction
+            fish_movement.direction.y = -fish_movement.direction.y.abs();
+        }
+
+        // Keep fish within mountain boundary (lake/ocean bounds)
+        let max_distance = 400.0; // Stay well within mountain ring at ~550 units
+        let distance_from_origin = fish_transform.translation.length();
+        if distance_from_origin > max_distance {
+            // Move fish back towards center
+            let direction_to_origin = -fish_transform.translation.normalize();
+            fish_transform.translation += direction_to_origin * delta_time * 3.0;
+        }
+
+        // Also prevent fish from going too deep
+        if fish_transform.translation.y < -25.0 {
+            fish_transform.translation.y = -25.0;
+            fish_movement.direction.y = fish_movement.direction.y.abs(); // Bounce up
+        }
+    }
+}
+
+fn oxygen_system(
+    mut game_state: ResMut<GameState>,
+    tuning: Res<Tuning>,
+    surfaced: Res<Surfaced>,
+    time: Res<Time>,
+) {
+    if surfaced.0 {
+        // At the surface - increase oxygen
+        game_state.oxygen += time.delta_secs() * 5.0;
+        game_state.oxygen = game_state.oxygen.min(100.0);
+    } else {
+        // Below surface - decrease oxygen
+        game_state.oxygen -= time.delta_secs() * tuning.oxygen_rate;
+        game_state.oxygen = game_state.oxygen.max(0.0);
+    }
+
+    // If oxygen runs out, health decreases
+    if game_state.oxygen <= 0.0 {
+        game_state.health -= time.delta_secs() * 5.0;
+        game_state.health = game_state.health.max(0.0);
+    }
+}
+
+fn collect_fish(
+    mut commands: Commands,
+    submarine_query: Query<&Transform, With<Submarine>>,
+    fish_query: Query<(Entity, &Transform, Option<&FishSize>), With<Fish>>,
+    mut game_state: ResMut<GameState>,
+    mut collected: EventWriter<FishCollected>,
+    tuning: Res<Tuning>,
+) {
+    if let Ok(submarine_transform) = submarine_query.single() {
+        for (fish_entity, fish_transform, size) in fish_query.iter() {
+            let distance = submarine_transform
+                .translation
+                .distance(fish_transform.translation);
+            if distance < tuning.fish_collection_distance {
+                commands.entity(fish_entity).despawn();
+                // Points are scored by the combo system
+                collected.write(FishCollected {
+                    position: fish_transform.translation,
+                    size: size.map_or(1.0, |size| size.scale),
+                    undersized: size.is_some_and(|size| size.undersized),
+                });
+                game_state.oxygen = (game_state.oxygen + 20.0).min(100.0);
+            }
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn ui_system(
+    game_state: Res<GameState>,
+    submarine_query: Query<(&Transform, &Velocity), With<Submarine>>,
+    fish_query: Query<&Transform, With<Fish>>,
+    sonar_state: Res<SonarState>,
+    mut ui_query: Query<&mut Text, With<StatusText>>,
+    sonar_detections: Res<SonarDetections>,
+    ballast_state: Res<BallastState>,
+    helm_state: Res<HelmState>,
+    armament: Res<Armament>,
+    navigation: Res<Navigation>,
+    inventory: Res<Inventory>,
+    noise: Res<SubmarineNoise>,
+    cleanup: Res<Cleanup>,
+    trim_computer: Res<TrimComputer>,
+) {
+    if let Ok(mut text) = ui_query.single_mut() {
+        let (speed, depth, orientation) =
+            if let Ok((transform, velocity)) = submarine_query.single() {
+                let speed = velocity.linvel.length();
+                let depth = -transform.translation.y; // Negative because Y is up in world space
+                let orientation = transform.rotation.to_euler(EulerRot::YXZ);
+                (speed, depth, orientation)
+            } else {
+                (0.0, 0.0, (0.0, 0.0, 0.0))
+            };
+
+        let submarine_yaw = orientation.0.to_degrees();
+        let sweep_angle = sonar_state.sweep_angle.to_degrees();
+
+        // Calculate fish angle for debugging
+        let fish_angle_deg = if let Ok((submarine_transform, _velocity)) = submarine_query.single()
+        {
+            if let Ok(fish_transform) = fish_query.single() {
+                let rel = fish_transform.translation - submarine_transform.translation;
+                // Transform to submarine's local coordinate system
+                let local_rel = submarine_transform.rotation.inverse() * rel;
+                let fish_angle = calculate_fish_angle(local_rel);
+                fish_angle.to_degrees()
+            } else {
+                0.0
+            }
+        } else {
+            0.0
+        };
+
+        // Debug fading calculations
+        let fade_debug = if let Some(contact) = sonar_detections.contacts.first() {
+            format!(
+                "Fish detected: {:.1}° ({:.0}% confidence)",
+                contact.angle.to_degrees(),
+                contact.confidence * 100.0
+            )
+        } else {
+            "No fish detected".to_string()
+        };
+
+        // Create status indicators for valves and vents
+        let vents_status = if ballast_state.vents_open {
+            "[Vents ON]"
+        } else {
+            "[Vents OFF]"
+        };
+        let air_valve_status = if ballast_state.air_valve_open {
+            "[Valve ON]"
+        } else {
+            "[Valve OFF]"
+        };
+        let trim_status = if trim_computer.engaged {
+            " [Trim Auto]".to_string()
+        } else {
+            ballast_state
+                .target_fill
+                .map(|target| format!(" [Trim {:.0}%]", target * 100.0))
+                .unwrap_or_default()
+        };
+        let compressor_status = if ballast_state.compressor_on {
+            "[Compressor ON]"
+        } else {
+            "[Compressor OFF]"
+        };
+
+        let autopilot_status = if helm_state.autopilot {
+            if helm_state.intercept {
+                "[Autopilot INTERCEPT]"
+            } else {
+                "[Autopilot ON]"
+            }
+        } else {
+            "[Autopilot OFF]"
+        };
+
+        let anomaly_status = if navigation.interference > 0.0 {
+            " [MAGNETIC ANOMALY]"
+        } else {
+            ""
+        };
+
+        **text = format!(
+            "Submarine Game\n\nScore: {}\nHealth: {:.1}%\nOxygen: {:.1}%\nBallast: {:.1}% {}{}\nCompressed Air: {:.1}% {}\nElectricity: {:.1}% {}\nTelegraph: {} {}\nTorpedoes: {}  Decoys: {}  Bait: {}  Spare Parts: {}  Upgrade Parts: {}\nDebris Recovered: {}  Reputation: {}\n\nSpeed: {:.1} m/s  Noise: {:.0}%\nDepth: {:.1} m\nPitch: {:.1}°\nYaw: {:.1}°\nRoll: {:.1}°\nCompass: {:03.0}°{}\nDead Reckoning: {:.0}, {:.0} (±{:.0} m)\n\nSonar Debug:\nSub Yaw: {:.1}°\nSweep: {:.1}°\nFish Angle: {:.1}°\n{}\n\nWASD: Move  PgUp/PgDn: Dive Planes\nQ: Toggle Vents\nE: Toggle Air Valve\nR: Toggle Compressor\nY/U: Hold Flood/Blow  ,/.: Trim Target  I: Trim Computer\nT/G: Telegraph Ahead/Astern\nP: Autopilot  X: Select Contact  ': Intercept\nSpace: Torpedo  F: Decoy  B: Bait  K: Arm  J: Diver\nL: Floodlights  H: Repair Hull  O: Distress Buoy  ;: Strap Cargo\n\\: Control Panel  Tab: Event Log\nArrow Keys: Camera  C: Camera Style  V: View  N: Tactical Map\n-/=: HUD Scale  F2: Sonar Size  F6-F9: HUD Layout\nF5: Quicksave  F10: Quickload\nCollect fish to score points!",
+            game_state.score,
+            game_state.health,
+            game_state.oxygen,
+            ballast_state.fill_level * 100.0,
+            vents_status,
+            trim_status,
+            ballast_state.compressed_air * 100.0,
+            air_valve_status,
+            ballast_state.electricity,
+            compressor_status,
+            helm_state.telegraph.label(),
+            autopilot_status,
+            armament.torpedoes,
+            armament.decoys,
+            armament.bait,
+            inventory.spare_parts,
+            inventory.upgrade_parts,
+            cleanup.collected,
+            cleanup.reputation,
+            speed,
+            noise.0 * 100.0,
+            depth,
+            orientation.1.to_degrees(),
+            orientation.0.to_degrees(),
+            orientation.2.to_degrees(),
+            navigation.compass_heading(orientation.0),
+            anomaly_status,
+            navigation.estimate.x,
+            navigation.estimate.z,
+            navigation.drift,
+            submarine_yaw,
+            sweep_angle,
+            fish_angle_deg,
+            fade_debug
+        );
+    }
+}
+
+fn sonar_sweep_system(mut sonar_state: ResMut<SonarState>, tuning: Res<Tuning>, time: Res<Time>) {
+    sonar_state.sweep_angle -= time.delta_secs() * tuning.sweep_speed; // Counter-clockwise rotation to match angle calculations
+}
+
+/// Places the ring, cross and center dot for the scope's size, when it
+/// changes or a new panel is spawned
+fn sonar_scope_layout_system(
+    scope: Res<SonarScope>,
+    mut mark_query: Query<(&ScopeMark, &mut Node)>,
+    spawned_marks: Query<(), Added<ScopeMark>>,
+) {
+    if !scope.is_changed() && spawned_marks.is_empty() {
+        return;
+    }
+    let scale = scope.scale();
+    let (center_x, center_y) = (SONAR_CENTER_X * scale, SONAR_CENTER_Y * scale);
+    let radius = SONAR_RADIUS * scale;
+    let line = scope.line_width();
+    for (mark, mut node) in mark_query.iter_mut() {
+        let (x, y, width, height) = match *mark {
+            ScopeMark::Ring(angle) => {
+                // Large enough for neighbouring dots to meet however big the ring
+                let dot = line.max(radius * std::f32::consts::PI / 180.0);
+                (
+                    center_x + radius * angle.cos(),
+                    center_y + radius * angle.sin(),
+                    dot,
+                    dot,
+                )
+            }
+            ScopeMark::VerticalLine => (center_x, center_y, line, radius * 2.0),
+            ScopeMark::HorizontalLine => (center_x, center_y, radius * 2.0, line),
+            ScopeMark::CenterDot => (center_x, center_y, line * 3.0, line * 3.0),
+        };
+        node.left = Val::Px(x - width / 2.0);
+        node.top = Val::Px(y - height / 2.0);
+        node.width = Val::Px(width);
+        node.height = Val::Px(height);
+    }
+}
+
+fn sonar_sweep_update_system(
+    sonar_state: Res<SonarState>,
+    scope: Res<SonarScope>,
+    submarine_query: Query<&Transform, With<Submarine>>,
+    mut sweep_line_query: Query<&mut Node, With<SonarSweepLine>>,
+) {
+    let num_segments = 20;
+    let scale = scope.scale();
+    let line = scope.line_width();
+
+    // Get submarine's yaw rotation to make sweep relative to submarine orientation
+    let submarine_yaw = if let Ok(submarine_transform) = submarine_query.single() {
+        submarine_transform.rotation.to_euler(EulerRot::YXZ).0
+    } else {
+        0.0
+    };
+
+    // Position each segment along the sweep angle (clockwise)
+    // Make sweep angle relative to submarine's orientation
+    for (index, mut style) in sweep_line_query.iter_mut().enumerate() {
+        let segment_distance = (index as f32 + 1.0) * (SONAR_RADIUS / num_segments as f32);
+        let sweep_angle = sonar_state.sweep_angle + submarine_yaw;
+        let segment_x = SONAR_CENTER_X + segment_distance * sweep_angle.cos();
+        let segment_y = SONAR_CENTER_Y - segment_distance * sweep_angle.sin(); // Negative to flip Y axis
+
+        style.left = Val::Px(segment_x * scale - line / 2.0);
+        style.top = Val::Px(segment_y * scale - line / 2.0);
+        style.width = Val::Px(line);
+        style.height = Val::Px(line);
+    }
+}
+
+/// Jamming shortens the sonar's range, makes bearings wander, lowers the
+/// confidence of every contact and adds false returns; thermoclines shorten
+/// it for targets on the far side of a layer
+#[allow(clippy::too_many_arguments)]
+fn sonar_detection_system(
+    submarine_query: Query<&Transform, With<Submarine>>,
+    fish_query: Query<(Entity, &Transform, Option<&FishSize>), With<Fish>>,
+    pickup_query: Query<(Entity, &Transform), With<Pickup>>,
+    enemy_query: Query<(Entity, &Transform), With<EnemySubmarine>>,
+    encounter_query: Query<(Entity, &Transform, &Encounter)>,
+    jammer_query: Query<(&SonarJammer, &GlobalTransform)>,
+    mut sonar_detections: ResMut<SonarDetections>,
+    time: Res<Time>,
+    seed: Res<WorldSeed>,
+    crew: Res<Crew>,
+    tuning: Res<Tuning>,
+    timings: Res<SystemTimings>,
+) {
+    let _timing = timings.scope("sonar detection");
+    if let Ok(submarine_transform) = submarine_query.single() {
+        let mut contacts = Vec::new();
+        let jamming = jammer_query
+            .iter()
+            .map(|(jammer, transform)| {
+                jammer.jamming_at(transform.translation(), submarine_transform.translation)
+            })
+            .fold(0.0, f32::max)
+            .min(1.0);
+        let range = tuning.sonar_range
+            * crew.efficiency(Station::Sonar)
+            * (1.0 - tuning.sonar_jammed_range_loss * jamming);
+        let t = time.elapsed_secs();
+
+        // Detect all fish within range, big ones echoing more strongly; pickups
+        // lying on the floor give faint returns, and enemy hulls and passing
+        // wildlife strong ones
+        let targets = fish_query
+            .iter()
+            .map(|(entity, transform, size)| {
+                (entity, transform, size.map_or(1.0, |size| size.scale))
+            })
+            .chain(
+                pickup_query
+                    .iter()
+                    .map(|(entity, transform)| (entity, transform, tuning.sonar_pickup_strength)),
+            )
+            .chain(
+                enemy_query
+                    .iter()
+                    .map(|(entity, transform)| (entity, transform, ENEMY_ECHO)),
+            )
+            .chain(
+                encounter_query
+                    .iter()
+                    .map(|(entity, transform, encounter)| (entity, transform, encounter.echo)),
+            );
+        for (entity, fish_transform, strength) in targets {
+            let rel = fish_transform.translation - submarine_transform.translation;
+            let dist = rel.length();
+            // Thermoclines between the boat and the target bend the sound away
+            let layers = tuning
+                .thermoclines
+                .iter()
+                .filter(|layer| {
+                    (submarine_transform.translation.y + **layer)
+                        * (fish_transform.translation.y + **layer)
+                        < 0.0
+                })
+                .count();
+            if dist > range * (1.0 - tuning.sonar_layer_range_loss).powi(layers as i32) {
+                continue;
+            }
+
+            // Transform to submarine's local coordinate system
+            let local_rel = submarine_transform.rotation.inverse() * rel;
+
+            // Calculate angle relative to submarine's forward direction, drifting when jammed
+            let wander = (t * 1.3 + entity.index() as f32 * 1.7).sin();
+            let fish_angle = calculate_fish_angle(local_rel)
+                + wander * tuning.sonar_jammed_bearing_error * jamming;
+
+            // Convert to sonar display coordinates
+            let (blip_x, blip_y) = calculate_sonar_position(fish_angle, dist, tuning.sonar_range);
+
+            contacts.push(SonarContact {
+                x: blip_x,
+                y: blip_y,
+                angle: fish_angle,
+                confidence: (strength * (1.0 - jamming) * (1.0 - 0.5 * dist / range))
+                    .clamp(0.05, 1.0),
+                target: Some(entity),
+                depth: -fish_transform.translation.y,
+            });
+        }
+
+        // False returns hold still for half a second at a time
+        let mut rng = StdRng::seed_from_u64(seed.0 ^ (t * 2.0) as u64);
+        let false_contacts = (jamming * tuning.sonar_max_false_contacts as f32) as usize;
+        for _ in 0..false_contacts {
+            let angle = rng.gen::<f32>() * std::f32::consts::TAU;
+            let (blip_x, blip_y) =
+                calculate_sonar_position(angle, rng.gen::<f32>() * range, tuning.sonar_range);
+            contacts.push(SonarContact {
+                x: blip_x,
+                y: blip_y,
+                angle,
+                confidence: rng.gen_range(0.05..0.4),
+                target: None,
+                depth: -submarine_transform.translation.y,
+            });
+        }
+
+        sonar_detections.contacts = contacts;
+    }
+}
+
+/// Paints each contact onto a blip as the sweep line passes its bearing, the
+/// way a PPI scope glows where the beam found something, and fades the blips
+/// until the sweep comes round again. The contact selected for the depth
+/// history is drawn in amber
+#[allow(clippy::too_many_arguments)]
+fn sonar_blip_system(
+    sonar_detections: Res<SonarDetections>,
+    selected: Res<SelectedContact>,
+    mut blip_query: Query<(Entity, &mut SonarBlip, &mut Node, &mut BackgroundColor)>,
+    scope: Res<SonarScope>,
+    sonar_state: Res<SonarState>,
+    submarine_query: Query<&Transform, With<Submarine>>,
+    mut last_sweep: Local<Option<f32>>,
+    time: Res<Time>,
+) {
+    let delta = time.delta_secs();
+    for (_, mut blip, _, _) in blip_query.iter_mut() {
+        blip.age += delta;
+    }
+
+    // The sweep line is drawn turned by the boat's heading
+    let sweep = submarine_query
+        .single()
+        .ok()
+        .map(|submarine| sonar_state.sweep_angle + submarine.rotation.to_euler(EulerRot::YXZ).0);
+    let previous = std::mem::replace(&mut *last_sweep, sweep);
+    if let (Some(sweep), Some(previous)) = (sweep, previous) {
+        // The sweep turns clockwise; a turn of the boat against it covers no new ground
+        let arc = previous - sweep;
+        if arc > 0.0 && arc <= std::f32::consts::PI {
+            for contact in sonar_detections.contacts.iter() {
+                if (previous - contact.angle).rem_euclid(std::f32::consts::TAU) > arc {
+                    continue;
+                }
+                // Repaint the contact's own blip, or take the one faded longest
+                let own = blip_query
+                    .iter()
+                    .find(|(_, blip, _, _)| {
+                        contact.target.is_some()
+                            && blip
+                                .painted
+                                .is_some_and(|painted| painted.target == contact.target)
+                    })
+                    .map(|(entity, ..)| entity);
+                let faded = |blip: &SonarBlip| {
+                    if blip.painted.is_some() {
+                        blip.age
+                    } else {
+                        f32::INFINITY
+                    }
+                };
+                let free = || {
+                    blip_query
+                        .iter()
+                        .max_by(|(_, a, _, _), (_, b, _, _)| faded(a).total_cmp(&faded(b)))
+                        .map(|(entity, ..)| entity)
+                };
+                if let Some(entity) = own.or_else(free) {
+                    if let Ok((_, mut blip, _, _)) = blip_query.get_mut(entity) {
+                        blip.painted = Some(*contact);
+                        blip.age = 0.0;
+                    }
+                }
+            }
+        }
+    }
+
+    let scale = scope.scale();
+    for (_, blip, mut style, mut color) in blip_query.iter_mut() {
+        let fade = 1.0 - blip.age / BLIP_FADE_TIME;
+        let Some(contact) = blip.painted.filter(|_| fade > 0.0) else {
+            *color = BackgroundColor(Color::srgba(0.0, 1.0, 0.0, 0.0)); // Transparent
+            continue;
+        };
+        // Uncertain returns are dimmer and smeared over a larger spot
+        let size = (6.0 + 6.0 * (1.0 - contact.confidence)) * scale.sqrt();
+        style.left = Val::Px(contact.x * scale - size / 2.0);
+        style.top = Val::Px(contact.y * scale - size / 2.0);
+        style.width = Val::Px(size);
+        style.height = Val::Px(size);
+        let alpha = (0.25 + 0.75 * contact.confidence) * fade;
+        *color = BackgroundColor(
+            if contact.target.is_some() && contact.target == selected.0 {
+                Color::srgba(1.0, 0.75, 0.1, alpha)
+            } else {
+                Color::srgba(0.0, 1.0, 0.0, alpha)
+            },
+        );
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn ballast_control_system(
+    mut control_commands: EventReader<ControlCommand>,
+    mut ballast_state: ResMut<BallastState>,
+    helm_input: Res<HelmInput>,
+    surfaced: Res<Surfaced>,
+    crew: Res<Crew>,
+    class: Res<SubmarineClass>,
+    tuning: Res<Tuning>,
+    submarine_query: Query<&Transform, With<Submarine>>,
+    time: Res<Time>,
+) {
+    let delta_time = time.delta_secs();
+    // Short-handed or injured crews work the ballast and machinery slower
+    let diving = crew.efficiency(Station::Diving);
+    let engineering = crew.efficiency(Station::Engineering);
+
+    for command in control_commands.read() {
+        match command {
+            // Toggle vents (Q key) - allows water to flow into ballast tanks
+            ControlCommand::ToggleVents => {
+                ballast_state.target_fill = None;
+                ballast_state.vents_open = !ballast_state.vents_open;
+                // Close air valve when opening vents
+                if ballast_state.vents_open {
+                    ballast_state.air_valve_open = false;
+                }
+            }
+            // Toggle air valve (E key) - allows compressed air to flow into tanks
+            ControlCommand::ToggleAirValve => {
+                ballast_state.target_fill = None;
+                ballast_state.air_valve_open = !ballast_state.air_valve_open;
+                // Close vents when opening air valve
+                if ballast_state.air_valve_open {
+                    ballast_state.vents_open = false;
+                }
+            }
+            // Toggle air compressor (R key) - generates compressed air (only at surface)
+            ControlCommand::ToggleCompressor => {
+                if surfaced.0 {
+                    ballast_state.compressor_on = !ballast_state.compressor_on;
+                } else {
+                    // Turn off compressor if underwater
+                    ballast_state.compressor_on = false;
+                }
+            }
+            // Trim target (control panel slider, or ,/. keys) - the valves are
+            // worked to hold the tanks at a set fill
+            ControlCommand::SetBallastTarget(percent) => {
+                ballast_state.set_target(*percent as f32 / 100.0);
+            }
+            ControlCommand::BallastTargetUp | ControlCommand::BallastTargetDown => {
+                let step = if *command == ControlCommand::BallastTargetUp {
+                    tuning.trim_step
+                } else {
+                    -tuning.trim_step
+                };
+                // Without a target yet, the steps start from the current fill
+                let current = ballast_state.target_fill.unwrap_or(
+                    (ballast_state.fill_level / tuning.trim_step).round() * tuning.trim_step,
+                );
+                ballast_state.set_target(current + step);
+            }
+            ControlCommand::ClearBallastTarget => ballast_state.target_fill = None,
+            _ => {}
+        }
+    }
+
+    // Compressors and the air valve work against the water pressure outside
+    let depth = submarine_query
+        .single()
+        .map_or(0.0, |transform| -transform.translation.y);
+
+    // Update compressed air based on compressor (only at surface)
+    if ballast_state.compressor_on && ballast_state.electricity > 0.0 && surfaced.0 {
+        ballast_state.compressed_air += tuning.compressed_air_rate
+            * depth_factor(tuning.compressor_depth_loss, depth)
+            * engineering
+            * delta_time;
+        ballast_state.compressed_air = ballast_state.compressed_air.min(1.0);
+
+        // Drain electricity
+        ballast_state.electricity -= tuning.compressor_power_drain * delta_time;
+        ballast_state.electricity = ballast_state.electricity.max(0.0);
+    } else if !surfaced.0 {
+        // Turn off compressor if underwater
+        ballast_state.compressor_on = false;
+    }
+
+    // Recharge electricity slowly when compressor is off
+    if !ballast_state.compressor_on {
+        ballast_state.electricity += tuning.power_recharge_rate * engineering * delta_time;
+        ballast_state.electricity = ballast_state.electricity.min(100.0);
+    }
+
+    // Update ballast fill level: open vents or a held flood key let water
+    // in, the air valve or a held blow key push it out, and otherwise the
+    // valves are worked towards the trim target
+    let flood_rate = tuning.ballast_fill_rate * diving * delta_time;
+    let blow_rate = tuning.ballast_drain_rate
+        * depth_factor(tuning.blow_depth_loss, depth)
+        * diving
+        * delta_time;
+    let change = if ballast_state.vents_open {
+        flood_rate
+    } else if helm_input.ballast < 0.0 {
+        flood_rate * -helm_input.ballast
+    } else if ballast_state.air_valve_open {
+        -blow_rate
+    } else if helm_input.ballast > 0.0 {
+        -blow_rate * helm_input.ballast
+    } else if let Some(target) = ballast_state.target_fill {
+        // Metered: the valves are cracked less the closer the tanks are to the mark
+        let error = target - ballast_state.fill_level;
+        let opening = (error.abs() / tuning.trim_band).min(1.0);
+        if error > 0.0 {
+            (flood_rate * opening).min(error)
+        } else {
+            -(blow_rate * opening).min(-error)
+        }
+    } else {
+        0.0
+    };
+
+    if change > 0.0 {
+        // Water flows in through vents
+        ballast_state.fill_level = (ballast_state.fill_level + change).min(1.0);
+    } else if change < 0.0 && ballast_state.compressed_air > 0.0 {
+        // Compressed air pushes water out, and is used in step with the water
+        // blown; bigger tanks take more of it, bigger flasks hold more
+        let blown = (-change).min(ballast_state.fill_level);
+        ballast_state.fill_level -= blown;
+        ballast_state.compressed_air = (ballast_state.compressed_air
+            - blown * tuning.air_per_fill * class.ballast / class.air)
+            .max(0.0);
+
+        // Turn off air valve when ballast is empty
+        if ballast_state.fill_level <= 0.0 {
+            ballast_state.air_valve_open = false;
+        }
+    }
+}
+
+/// Height of the water surface above its rest level at a point, the sum of
+/// several overlapping wave patterns
+fn wave_height(x: f32, z: f32, elapsed: f32) -> f32 {
+    let wave_height = 0.4;
+    let wave_speed = 1.2;
+    let time_factor = elapsed * wave_speed;
+
+    // Multiple overlapping wave patterns for realistic ocean
+    let wave1 = (x * 0.02 + time_factor).sin() * wave_height * 0.4;
+    let wave2 = (z * 0.015 - time_factor * 0.7).sin() * wave_height * 0.3;
+    let wave3 = ((x + z) * 0.01 + time_factor * 1.2).sin() * wave_height * 0.2;
+    let wave4 = ((x - z) * 0.008 - time_factor * 0.5).sin() * wave_height * 0.1;
+
+    // Add some larger scale waves for ocean feel
+    let large_wave1 = (x * 0.005 + time_factor * 0.3).sin() * wave_height * 0.3;
+    let large_wave2 = (z * 0.004 - time_factor * 0.2).sin() * wave_height * 0.2;
+
+    wave1 + wave2 + wave3 + wave4 + large_wave1 + large_wave2
+}
+
+fn wave_system(
+    water_query: Query<&Mesh3d, With<WaterSurface>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut wave_time: ResMut<WaveTime>,
+    time: Res<Time>,
+    timings: Res<SystemTimings>,
+) {
+    let _timing = timings.scope("wave deformation");
+    // Update elapsed time
+    wave_time.elapsed += time.delta_secs();
+
+    if let Ok(mesh_handle) = water_query.single() {
+        if let Some(mesh) = meshes.get_mut(&mesh_handle.0) {
+            // Get mesh attributes
+            if let Some(VertexAttributeValues::Float32x3(positions)) =
+                mesh.attribute_mut(Mesh::ATTRIBUTE_POSITION)
+            {
+                for position in positions.iter_mut() {
+                    position[1] = wave_height(position[0], position[2], wave_time.elapsed);
+                }
+            }
+
+            // Update mesh normals for proper lighting
+            mesh.duplicate_vertices();
+            mesh.compute_flat_normals();
+        }
+    }
+}
+
+/// Floodlights draw power and go out when the batteries are flat
+fn floodlight_system(
+    mut helm_state: ResMut<HelmState>,
+    mut ballast_state: ResMut<BallastState>,
+    mut light_query: Query<&mut Visibility, With<Floodlight>>,
+    tuning: Res<Tuning>,
+    time: Res<Time>,
+) {
+    if helm_state.lights_on {
+        ballast_state.electricity = (ballast_state.electricity
+            - tuning.floodlight_power_drain * time.delta_secs())
+        .max(0.0);
+        if ballast_state.electricity <= 0.0 {
+            helm_state.lights_on = false;
+        }
+    }
+    for mut visibility in light_query.iter_mut() {
+        visibility.set_if_neq(if helm_state.lights_on {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        });
+    }
+}
+
+fn depth_lighting_system(
+    optics: Res<WaterOpticalProperties>,
+    mut camera_query: Query<(&mut DistanceFog, &mut Projection), With<CameraFollow>>,
+    mut light_query: Query<&mut DirectionalLight, With<DepthLighting>>,
+    mut ambient_light: ResMut<AmbientLight>,
+) {
+    if let Ok(mut directional_light) = light_query.single_mut() {
+        directional_light.illuminance = 12000.0 * optics.light;
+        directional_light.color = optics.light_color;
+    }
+
+    // Scattered light fills the water with its own colour, so the ambient
+    // follows the fog and dims more slowly than the sun
+    ambient_light.brightness = 300.0 + 800.0 * optics.light.sqrt();
+    ambient_light.color = if optics.underwater {
+        optics.fog_color
+    } else {
+        optics.light_color
+    };
+
+    if let Ok((mut fog, mut projection)) = camera_query.single_mut() {
+        fog.color = optics.fog_color;
+        fog.falloff = FogFalloff::Linear {
+            start: optics.visibility * FOG_START,
+            end: optics.visibility,
+        };
+        // Nothing past the murk needs drawing; the tactical view's
+        // orthographic camera keeps its own reach
+        if let Projection::Perspective(perspective) = projection.as_mut() {
+            let far = if optics.underwater {
+                optics.visibility * 1.2
+            } else {
+                PerspectiveProjection::default().far
+            };
+            if perspective.far != far {
+                perspective.far = far;
+            }
+        }
+    }
+}
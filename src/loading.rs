@@ -0,0 +1,299 @@
+//! The loading screen between the menu and a dive. Fonts and data files
+//! finish loading first and the shared `GameAssets` are made again from the
+//! tables as they now stand, then the
+//! terrain and fish are built a stage per frame behind a progress bar, so the
+//! world no longer appears in one frozen frame. The dive starts once
+//! everything is in place.
+
+use crate::assets::GameAssets;
+use crate::difficulty::Difficulty;
+use crate::props::PropLibrary;
+use crate::scenario::Scenario;
+use crate::tables::Tables;
+use crate::{
+    spawn_fish_batch, spawn_foothills, spawn_mountains, spawn_rocks, spawn_seabed, AppState,
+    GameMode, NewGamePlus, Tuning, WorldSeed,
+};
+use bevy::asset::LoadState;
+use bevy::prelude::*;
+use rand::{rngs::StdRng, SeedableRng};
+
+const FONTS: [&str; 2] = ["fonts/NotoSans-Regular.ttf", "fonts/DejaVuSans.ttf"];
+const DATA_FILES: [&str; 3] = ["tuning.ron", "tables.ron", "props.ron"];
+const MAX_ASSET_FRAMES: u32 = 600; // Frames to wait on assets before building the world anyway
+const FISH_PER_FRAME: usize = 20; // One ring of fish
+const BAR_WIDTH: f32 = 360.0;
+
+/// What the loader is working on, in order
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum LoadingStage {
+    Assets,
+    Seabed,
+    Mountains,
+    Foothills,
+    Rocks,
+    Fish,
+}
+
+impl LoadingStage {
+    const ALL: [LoadingStage; 6] = [
+        LoadingStage::Assets,
+        LoadingStage::Seabed,
+        LoadingStage::Mountains,
+        LoadingStage::Foothills,
+        LoadingStage::Rocks,
+        LoadingStage::Fish,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            LoadingStage::Assets => "Loading assets",
+            LoadingStage::Seabed => "Flooding the lake",
+            LoadingStage::Mountains => "Raising the mountains",
+            LoadingStage::Foothills => "Shaping the foothills",
+            LoadingStage::Rocks => "Scattering rocks",
+            LoadingStage::Fish => "Releasing the fish",
+        }
+    }
+
+    fn next(self) -> Option<LoadingStage> {
+        let index = LoadingStage::ALL.iter().position(|stage| *stage == self)?;
+        LoadingStage::ALL.get(index + 1).copied()
+    }
+}
+
+/// Progress through the loading stages. The world's random source carries
+/// over between stages, so terrain and fish come out as if built at once.
+#[derive(Resource)]
+struct Loading {
+    stage: LoadingStage,
+    frames: u32,
+    fish_spawned: usize,
+    fonts: Vec<Handle<Font>>,
+    rng: StdRng,
+}
+
+impl Loading {
+    /// Share of the work done, from 0 to 1
+    fn progress(&self, fish_count: usize) -> f32 {
+        let index = LoadingStage::ALL
+            .iter()
+            .position(|stage| *stage == self.stage)
+            .unwrap_or(0) as f32;
+        let within = match self.stage {
+            LoadingStage::Fish if fish_count > 0 => self.fish_spawned as f32 / fish_count as f32,
+            _ => 0.0,
+        };
+        (index + within) / LoadingStage::ALL.len() as f32
+    }
+}
+
+#[derive(Component)]
+struct ProgressBar;
+
+#[derive(Component)]
+struct ProgressText;
+
+pub struct LoadingPlugin;
+
+impl Plugin for LoadingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            OnEnter(AppState::Loading),
+            (start_loading, spawn_loading_screen),
+        )
+        .add_systems(OnExit(AppState::Loading), finish_loading)
+        .add_systems(
+            Update,
+            (loading_system, loading_screen_system)
+                .chain()
+                .run_if(in_state(AppState::Loading)),
+        );
+    }
+
+    fn finish(&self, app: &mut App) {
+        app.init_resource::<GameAssets>();
+    }
+}
+
+fn start_loading(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(Loading {
+        stage: LoadingStage::Assets,
+        frames: 0,
+        fish_spawned: 0,
+        fonts: FONTS.iter().map(|path| asset_server.load(*path)).collect(),
+        // Reseeded once the scenario has had its say on the seed
+        rng: StdRng::seed_from_u64(0),
+    });
+}
+
+fn finish_loading(mut commands: Commands) {
+    commands.remove_resource::<Loading>();
+}
+
+fn spawn_loading_screen(mut commands: Commands, assets: Res<GameAssets>) {
+    let font = assets.font.clone();
+    commands
+        .spawn((
+            Node {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                row_gap: Val::Px(12.0),
+                ..default()
+            },
+            BackgroundColor(Color::srgb(0.02, 0.08, 0.15)),
+            GlobalZIndex(10),
+            StateScoped(AppState::Loading),
+        ))
+        .with_children(|root| {
+            root.spawn((
+                Text::new(LoadingStage::Assets.label()),
+                TextFont {
+                    font_size: 20.0,
+                    font,
+                    ..default()
+                },
+                TextColor(Color::WHITE),
+                ProgressText,
+            ));
+            root.spawn((
+                Node {
+                    width: Val::Px(BAR_WIDTH),
+                    height: Val::Px(12.0),
+                    border: UiRect::all(Val::Px(1.0)),
+                    ..default()
+                },
+                BorderColor(Color::srgb(0.4, 0.6, 0.7)),
+                BackgroundColor(Color::srgba(0.1, 0.2, 0.3, 0.9)),
+            ))
+            .with_child((
+                Node {
+                    width: Val::Percent(0.0),
+                    height: Val::Percent(100.0),
+                    ..default()
+                },
+                BackgroundColor(Color::srgb(0.3, 0.7, 0.9)),
+                ProgressBar,
+            ));
+        });
+}
+
+/// Works through one stage a frame (the fish a ring at a time) and starts the
+/// dive once the last is done
+#[allow(clippy::too_many_arguments)]
+fn loading_system(
+    mut commands: Commands,
+    mut loading: ResMut<Loading>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut props: ResMut<PropLibrary>,
+    mut next_state: ResMut<NextState<AppState>>,
+    asset_server: Res<AssetServer>,
+    world_seed: Res<WorldSeed>,
+    tuning: Res<Tuning>,
+    difficulty: Res<Difficulty>,
+    new_game_plus: Res<NewGamePlus>,
+    tables: Res<Tables>,
+    mode: Res<GameMode>,
+    scenario: Option<Res<Scenario>>,
+    assets: Res<GameAssets>,
+) {
+    let loading = loading.as_mut();
+    loading.frames += 1;
+    // Assets made in the first stage are in place from the next frame on
+    let done = match loading.stage {
+        LoadingStage::Assets => {
+            let settled =
+                |state: LoadState| matches!(state, LoadState::Loaded | LoadState::Failed(_));
+            let fonts = loading
+                .fonts
+                .iter()
+                .all(|font| settled(asset_server.load_state(font)));
+            let data = DATA_FILES.iter().all(|path| {
+                asset_server
+                    .get_path_id(*path)
+                    .is_none_or(|id| settled(asset_server.load_state(id)))
+            });
+            let ready = (fonts && data) || loading.frames >= MAX_ASSET_FRAMES;
+            if ready {
+                commands.insert_resource(GameAssets::new(
+                    &asset_server,
+                    &mut meshes,
+                    &mut materials,
+                    &tables,
+                ));
+                // Terrain and fish placement are reproducible from the world seed
+                loading.rng = StdRng::seed_from_u64(world_seed.0);
+            }
+            ready
+        }
+        LoadingStage::Seabed => {
+            spawn_seabed(&mut commands, &assets);
+            true
+        }
+        LoadingStage::Mountains => {
+            spawn_mountains(&mut commands, &assets, &mut loading.rng);
+            true
+        }
+        LoadingStage::Foothills => {
+            spawn_foothills(&mut commands, &assets, &mut loading.rng);
+            true
+        }
+        LoadingStage::Rocks => {
+            spawn_rocks(
+                &mut commands,
+                &mut meshes,
+                &mut materials,
+                &mut loading.rng,
+                &mut props,
+            );
+            true
+        }
+        LoadingStage::Fish => {
+            // Courses keep their own layout
+            let scenario = scenario.as_deref().filter(|_| *mode != GameMode::TimeTrial);
+            let fish_count = difficulty.fish_count(tuning.fish_count);
+            let end = (loading.fish_spawned + FISH_PER_FRAME).min(fish_count);
+            let more = spawn_fish_batch(
+                &mut commands,
+                &assets,
+                &mut loading.rng,
+                &tables,
+                scenario,
+                *new_game_plus,
+                loading.fish_spawned..end,
+            );
+            loading.fish_spawned = end;
+            !more || end >= fish_count
+        }
+    };
+    if !done {
+        return;
+    }
+    match loading.stage.next() {
+        Some(stage) => loading.stage = stage,
+        None => {
+            info!("World built in {} frames", loading.frames);
+            next_state.set(AppState::InGame);
+        }
+    }
+}
+
+fn loading_screen_system(
+    loading: Res<Loading>,
+    tuning: Res<Tuning>,
+    difficulty: Res<Difficulty>,
+    mut bar_query: Query<&mut Node, With<ProgressBar>>,
+    mut text_query: Query<&mut Text, With<ProgressText>>,
+) {
+    let progress = loading.progress(difficulty.fish_count(tuning.fish_count));
+    for mut node in bar_query.iter_mut() {
+        node.width = Val::Percent(progress * 100.0);
+    }
+    for mut text in text_query.iter_mut() {
+        text.0 = format!("{}... {:.0}%", loading.stage.label(), progress * 100.0);
+    }
+}
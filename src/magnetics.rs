@@ -0,0 +1,203 @@
+use crate::failures::{Failure, Failures, GYRO_DRIFT_RATE};
+use crate::props::PropLibrary;
+use crate::{AppState, GameMode, GameSet, Submarine, WorldSeed};
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+const WRECK_COUNT: usize = 3;
+const VENT_COUNT: usize = 2;
+const SEA_FLOOR: f32 = -20.5;
+const COMPASS_SPIN: f32 = 8.0; // Radians per second the needle can wander at the heart of a zone
+const COMPASS_SETTLE: f32 = 1.0; // Radians per second the needle recovers once clear
+const DRIFT_RATE: f32 = 0.02; // Dead reckoning error per meter travelled
+const ANOMALY_DRIFT: f32 = 10.0; // Error grows up to this many times faster inside a zone
+const SURFACE_FIX_DEPTH: f32 = 0.5; // Close enough to the surface to take a position fix
+
+/// Distorts the magnetic field around it, out to `radius`
+#[derive(Component)]
+pub struct MagneticAnomaly {
+    pub radius: f32,
+}
+
+/// A sunken wreck on the sea floor; the transform is the spot where it lies
+#[derive(Component)]
+pub struct Wreck {
+    pub heading: f32, // Yaw of the hull's long axis
+}
+
+impl Wreck {
+    /// A spot on the sea floor clear of the hull, `along` meters from the middle
+    /// of the wreck towards the bow, to port (`side` < 0) or starboard
+    pub fn beside(&self, center: Vec3, along: f32, side: f32) -> Vec3 {
+        let offset =
+            Quat::from_rotation_y(self.heading) * Vec3::new(side.signum() * 6.0, 0.0, along);
+        Vec3::new(center.x + offset.x, SEA_FLOOR, center.z + offset.z)
+    }
+}
+
+/// Translucent sphere that marks an anomaly's extent
+#[derive(Component)]
+pub struct Shimmer {
+    pub material: Handle<StandardMaterial>,
+    pub phase: f32,
+}
+
+/// What the submarine's instruments believe about heading and position
+#[derive(Resource, Default)]
+pub struct Navigation {
+    pub compass_error: f32, // Radians the compass reads off the true heading
+    pub estimate: Vec3,     // Dead reckoned position
+    pub drift: f32,         // Meters the estimate may be off by
+    pub interference: f32,  // 0..1; how deep the submarine is inside an anomaly
+}
+
+impl Navigation {
+    /// Compass bearing in degrees, 0 along -Z and increasing clockwise seen from above
+    pub fn compass_heading(&self, yaw: f32) -> f32 {
+        (-(yaw + self.compass_error)).to_degrees().rem_euclid(360.0)
+    }
+}
+
+/// Random source for the needle, seeded from the world so replays steer the same
+#[derive(Resource)]
+struct CompassRng(StdRng);
+
+pub struct MagneticsPlugin;
+
+impl Plugin for MagneticsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Navigation>()
+            .insert_resource(CompassRng(StdRng::seed_from_u64(0)))
+            .add_systems(
+                OnEnter(AppState::InGame),
+                (
+                    reset_navigation,
+                    spawn_anomalies.run_if(|mode: Res<GameMode>| *mode != GameMode::TimeTrial),
+                ),
+            )
+            .add_systems(
+                Update,
+                (
+                    compass_system
+                        .before(crate::submarine_movement)
+                        .in_set(GameSet::Simulation),
+                    dead_reckoning_system
+                        .after(crate::submarine_movement)
+                        .in_set(GameSet::Simulation),
+                    shimmer_system.in_set(GameSet::Presentation),
+                ),
+            );
+    }
+}
+
+fn reset_navigation(mut commands: Commands, seed: Res<WorldSeed>) {
+    commands.insert_resource(Navigation::default());
+    commands.insert_resource(CompassRng(StdRng::seed_from_u64(seed.0 ^ 0x4D41_474E)));
+}
+
+/// Wrecks and volcanic vents on the sea floor, each inside a magnetic anomaly
+fn spawn_anomalies(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut props: ResMut<PropLibrary>,
+    seed: Res<WorldSeed>,
+) {
+    let mut rng = StdRng::seed_from_u64(seed.0 ^ 0x5752_4543);
+    for i in 0..WRECK_COUNT + VENT_COUNT {
+        let angle = rng.gen::<f32>() * std::f32::consts::TAU;
+        let distance = 80.0 + rng.gen::<f32>() * 240.0;
+        let position = Vec3::new(angle.cos() * distance, SEA_FLOOR, angle.sin() * distance);
+        let (prop, heading) = if i < WRECK_COUNT {
+            ("wreck", rng.gen::<f32>() * std::f32::consts::TAU)
+        } else {
+            ("vent", 0.0)
+        };
+        props.spawn(
+            &mut commands,
+            &mut meshes,
+            &mut materials,
+            &mut rng,
+            prop,
+            Transform::from_translation(position).with_rotation(Quat::from_rotation_y(heading)),
+        );
+    }
+}
+
+/// Inside an anomaly the needle wanders erratically; outside it settles back,
+/// unless the gyro is drifting, when it creeps steadily off
+fn compass_system(
+    time: Res<Time>,
+    mut navigation: ResMut<Navigation>,
+    mut rng: ResMut<CompassRng>,
+    failures: Res<Failures>,
+    submarine_query: Query<&Transform, With<Submarine>>,
+    anomaly_query: Query<(&MagneticAnomaly, &Transform)>,
+) {
+    let Ok(submarine) = submarine_query.single() else {
+        return;
+    };
+    let delta = time.delta_secs();
+    navigation.interference = anomaly_query
+        .iter()
+        .map(|(anomaly, transform)| {
+            1.0 - transform.translation.distance(submarine.translation) / anomaly.radius
+        })
+        .fold(0.0, f32::max);
+
+    if navigation.interference > 0.0 {
+        let kick = rng.0.gen_range(-1.0..1.0) * COMPASS_SPIN * navigation.interference * delta;
+        navigation.compass_error = (navigation.compass_error + kick + std::f32::consts::PI)
+            .rem_euclid(std::f32::consts::TAU)
+            - std::f32::consts::PI;
+    } else if failures.has(Failure::GyroDrift) {
+        navigation.compass_error =
+            (navigation.compass_error + GYRO_DRIFT_RATE * delta + std::f32::consts::PI)
+                .rem_euclid(std::f32::consts::TAU)
+                - std::f32::consts::PI;
+    } else {
+        let error = navigation.compass_error;
+        navigation.compass_error =
+            error - error.clamp(-COMPASS_SETTLE * delta, COMPASS_SETTLE * delta);
+    }
+}
+
+/// Integrates the measured speed along the compass heading; the estimate is
+/// corrected whenever the submarine surfaces
+pub fn dead_reckoning_system(
+    time: Res<Time>,
+    mut navigation: ResMut<Navigation>,
+    submarine_query: Query<(&Transform, &Velocity), With<Submarine>>,
+) {
+    let Ok((transform, velocity)) = submarine_query.single() else {
+        return;
+    };
+    if transform.translation.y > -SURFACE_FIX_DEPTH {
+        navigation.estimate = transform.translation;
+        navigation.drift = 0.0;
+        return;
+    }
+    let delta = time.delta_secs();
+    let measured = Quat::from_rotation_y(navigation.compass_error) * velocity.linvel;
+    navigation.estimate += measured * delta;
+    let travelled = velocity.linvel.length() * delta;
+    navigation.drift += travelled * DRIFT_RATE * (1.0 + ANOMALY_DRIFT * navigation.interference);
+}
+
+/// Anomalies ripple so they can be seen and avoided
+fn shimmer_system(
+    time: Res<Time>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut shimmer_query: Query<(&Shimmer, &mut Transform)>,
+) {
+    let t = time.elapsed_secs();
+    for (shimmer, mut transform) in shimmer_query.iter_mut() {
+        let wave = (t * 3.0 + shimmer.phase).sin();
+        transform.scale = Vec3::splat(1.0 + 0.02 * wave);
+        if let Some(material) = materials.get_mut(&shimmer.material) {
+            let flicker = (t * 11.0 + shimmer.phase * 2.0).sin() * 0.5 + 0.5;
+            material.base_color = Color::srgba(0.6, 0.8, 1.0, 0.03 + 0.05 * flicker);
+        }
+    }
+}
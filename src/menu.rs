@@ -0,0 +1,570 @@
+use crate::classes::SubmarineClass;
+use crate::control_panel::ControlPanelState;
+use crate::ironman::{self, Ironman};
+use crate::mods::LoadedMods;
+use crate::save::{self, SaveData};
+use crate::settings_menu::SettingsMenuState;
+use crate::survival;
+use crate::tables::Tables;
+use crate::{
+    start_new_game, AppState, CampaignProgress, GameAssets, GameMode, NewGamePlus, PauseState,
+};
+use bevy::{
+    asset::RenderAssetUsages,
+    image::{CompressedImageFormats, ImageSampler, ImageType},
+    prelude::*,
+    window::PrimaryWindow,
+};
+
+const BUTTON_IDLE: Color = Color::srgba(0.1, 0.2, 0.3, 0.9);
+const BUTTON_HOVER: Color = Color::srgba(0.15, 0.35, 0.5, 0.95);
+const MENU_BACKGROUND: Color = Color::srgb(0.02, 0.08, 0.15);
+const PAUSE_BACKGROUND: Color = Color::srgba(0.0, 0.02, 0.05, 0.7); // The frozen dive shows through
+const THUMBNAIL_WIDTH: f32 = 128.0;
+const THUMBNAIL_HEIGHT: f32 = 72.0;
+const CREDITS_HEIGHT: f32 = 420.0; // Pixels of the credits shown at once
+const CREDITS_SCROLL_SPEED: f32 = 24.0; // Pixels per second the credits roll up
+
+/// Fonts and libraries the game ships with, and their licenses
+const CREDITS_LICENSES: [(&str, &str); 5] = [
+    ("Noto Sans", "SIL Open Font License 1.1"),
+    (
+        "DejaVu Sans",
+        "Bitstream Vera License, public domain changes",
+    ),
+    ("Bevy", "MIT or Apache-2.0"),
+    ("Rapier", "Apache-2.0"),
+    ("Rhai", "MIT or Apache-2.0"),
+];
+
+/// Screens within the main menu
+#[derive(SubStates, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[source(AppState = AppState::MainMenu)]
+enum MenuPage {
+    #[default]
+    Title,
+    LoadGame,
+    Leaderboard,
+    Credits,
+}
+
+/// What a menu button does when clicked
+#[derive(Component, Clone)]
+enum MenuAction {
+    NextClass,
+    NewGame,
+    NewGamePlus(u32), // Cycle to start
+    Ironman,
+    TimeTrial,
+    Survival,
+    OpenLeaderboard,
+    OpenCredits,
+    OpenLoadGame,
+    Load(Box<SaveData>),
+    Back,
+    OpenSettings,
+    OpenEditor,
+    Resume,
+    ExitToMainMenu,
+    Quit,
+}
+
+/// The boat new games start in, with what sets it apart
+#[derive(Component)]
+struct ClassLabel;
+
+pub struct MenuPlugin;
+
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_sub_state::<MenuPage>()
+            .enable_state_scoped_entities::<MenuPage>()
+            .add_systems(OnEnter(AppState::MainMenu), show_cursor)
+            .add_systems(OnEnter(MenuPage::Title), spawn_title_screen)
+            .add_systems(OnEnter(MenuPage::LoadGame), spawn_load_screen)
+            .add_systems(OnEnter(MenuPage::Leaderboard), spawn_leaderboard_screen)
+            .add_systems(OnEnter(MenuPage::Credits), spawn_credits_screen)
+            .add_systems(
+                OnEnter(PauseState::Paused),
+                (spawn_pause_screen, pause_time, show_cursor),
+            )
+            .add_systems(OnExit(PauseState::Paused), (resume_time, restore_cursor))
+            .add_systems(
+                Update,
+                (
+                    pause_input_system.run_if(in_state(AppState::InGame)),
+                    credits_scroll_system.run_if(in_state(MenuPage::Credits)),
+                    class_label_system.run_if(in_state(MenuPage::Title)),
+                    (menu_action_system, menu_button_style_system)
+                        .run_if(in_state(AppState::MainMenu).or(in_state(PauseState::Paused))),
+                ),
+            );
+    }
+}
+
+fn show_cursor(mut window_query: Query<&mut Window, With<PrimaryWindow>>) {
+    if let Ok(mut window) = window_query.single_mut() {
+        window.cursor_options.visible = true;
+    }
+}
+
+/// Back in the dive the cursor is only wanted while the control panel is open
+fn restore_cursor(
+    panel_state: Res<ControlPanelState>,
+    mut window_query: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    if let Ok(mut window) = window_query.single_mut() {
+        window.cursor_options.visible = panel_state.open;
+    }
+}
+
+/// Freezing virtual time also stops physics, wave motion and timers
+fn pause_time(mut time: ResMut<Time<Virtual>>) {
+    time.pause();
+}
+
+fn resume_time(mut time: ResMut<Time<Virtual>>) {
+    time.unpause();
+}
+
+/// Escape toggles the pause menu; the settings screen handles Escape itself while open
+fn pause_input_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    pause_state: Res<State<PauseState>>,
+    settings_menu_state: Res<State<SettingsMenuState>>,
+    mut next_pause_state: ResMut<NextState<PauseState>>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::Escape)
+        || *settings_menu_state.get() == SettingsMenuState::Open
+    {
+        return;
+    }
+    next_pause_state.set(match pause_state.get() {
+        PauseState::Running => PauseState::Paused,
+        PauseState::Paused => PauseState::Running,
+    });
+}
+
+/// Full-screen backdrop with a centered column for menu content
+fn menu_root<S: States>(scope: S, background: Color) -> impl Bundle {
+    (
+        Node {
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            flex_direction: FlexDirection::Column,
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            row_gap: Val::Px(12.0),
+            ..default()
+        },
+        BackgroundColor(background),
+        StateScoped(scope),
+    )
+}
+
+fn menu_text(text: impl Into<String>, font: &Handle<Font>, font_size: f32) -> impl Bundle {
+    (
+        Text::new(text),
+        TextFont {
+            font_size,
+            font: font.clone(),
+            ..default()
+        },
+        TextColor(Color::WHITE),
+    )
+}
+
+fn spawn_menu_button(
+    parent: &mut ChildSpawnerCommands,
+    font: &Handle<Font>,
+    label: &str,
+    action: MenuAction,
+) {
+    parent
+        .spawn((
+            Button,
+            Node {
+                width: Val::Px(260.0),
+                height: Val::Px(48.0),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                border: UiRect::all(Val::Px(1.0)),
+                ..default()
+            },
+            BorderColor(Color::srgb(0.4, 0.6, 0.7)),
+            BackgroundColor(BUTTON_IDLE),
+            action,
+        ))
+        .with_children(|button| {
+            button.spawn(menu_text(label, font, 20.0));
+        });
+}
+
+fn spawn_title_screen(
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+    mods: Res<LoadedMods>,
+    tables: Res<Tables>,
+) {
+    let font = assets.font.clone();
+    commands
+        .spawn(menu_root(MenuPage::Title, MENU_BACKGROUND))
+        .with_children(|root| {
+            root.spawn(menu_text("Submarine", &font, 56.0));
+            // Every new game, of whichever kind, starts in the chosen boat
+            if tables.classes.len() > 1 {
+                root.spawn((menu_text("", &font, 16.0), ClassLabel));
+                spawn_menu_button(root, &font, "Change Boat", MenuAction::NextClass);
+            }
+            spawn_menu_button(root, &font, "New Game", MenuAction::NewGame);
+            let cycles = CampaignProgress::load().cycles_unlocked;
+            if cycles > 0 {
+                let label = if cycles == 1 {
+                    "New Game+".to_string()
+                } else {
+                    format!("New Game+ {}", cycles)
+                };
+                spawn_menu_button(root, &font, &label, MenuAction::NewGamePlus(cycles));
+            }
+            // Only one ironman run at a time; it has to end before another starts
+            match save::read_save(save::IRONMAN_SLOT) {
+                Some(run) => spawn_menu_button(
+                    root,
+                    &font,
+                    "Continue Ironman",
+                    MenuAction::Load(Box::new(run)),
+                ),
+                None => spawn_menu_button(root, &font, "Ironman", MenuAction::Ironman),
+            }
+            spawn_menu_button(root, &font, "Time Trial", MenuAction::TimeTrial);
+            spawn_menu_button(root, &font, "Survival", MenuAction::Survival);
+            spawn_menu_button(root, &font, "Leaderboard", MenuAction::OpenLeaderboard);
+            spawn_menu_button(root, &font, "Load Game", MenuAction::OpenLoadGame);
+            spawn_menu_button(root, &font, "Settings", MenuAction::OpenSettings);
+            spawn_menu_button(root, &font, "Scenario Editor", MenuAction::OpenEditor);
+            spawn_menu_button(root, &font, "Credits", MenuAction::OpenCredits);
+            spawn_menu_button(root, &font, "Quit", MenuAction::Quit);
+            if !mods.0.is_empty() {
+                let labels: Vec<String> = mods.0.iter().map(|m| m.label()).collect();
+                root.spawn(menu_text(
+                    format!("Mods: {}", labels.join(", ")),
+                    &font,
+                    16.0,
+                ));
+            }
+        });
+}
+
+fn spawn_pause_screen(mut commands: Commands, assets: Res<GameAssets>) {
+    let font = assets.font.clone();
+    commands
+        .spawn(menu_root(PauseState::Paused, PAUSE_BACKGROUND))
+        .with_children(|root| {
+            root.spawn(menu_text("Paused", &font, 48.0));
+            spawn_menu_button(root, &font, "Resume", MenuAction::Resume);
+            spawn_menu_button(root, &font, "Settings", MenuAction::OpenSettings);
+            spawn_menu_button(root, &font, "Main Menu", MenuAction::ExitToMainMenu);
+            spawn_menu_button(root, &font, "Quit", MenuAction::Quit);
+        });
+}
+
+/// Lists save slots newest first, each with its thumbnail, timestamp and play
+/// time; the ironman run is continued from the title screen instead
+fn spawn_load_screen(
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    let font = assets.font.clone();
+    let saves: Vec<SaveData> = save::list_saves()
+        .into_iter()
+        .filter(|save| !save.ironman)
+        .collect();
+
+    commands
+        .spawn(menu_root(MenuPage::LoadGame, MENU_BACKGROUND))
+        .with_children(|root| {
+            root.spawn(menu_text("Load Game", &font, 40.0));
+            if saves.is_empty() {
+                root.spawn(menu_text("No saved games", &font, 18.0));
+            }
+
+            for save in saves {
+                let thumbnail = save
+                    .metadata
+                    .thumbnail
+                    .as_deref()
+                    .and_then(load_thumbnail)
+                    .map(|image| images.add(image));
+                let details = format!(
+                    "{}\n{}  ·  played {}",
+                    save.metadata.name,
+                    save::format_timestamp(save.metadata.timestamp),
+                    save::format_play_time(save.metadata.play_time)
+                );
+
+                root.spawn((
+                    Button,
+                    Node {
+                        width: Val::Px(520.0),
+                        padding: UiRect::all(Val::Px(6.0)),
+                        column_gap: Val::Px(12.0),
+                        align_items: AlignItems::Center,
+                        border: UiRect::all(Val::Px(1.0)),
+                        ..default()
+                    },
+                    BorderColor(Color::srgb(0.4, 0.6, 0.7)),
+                    BackgroundColor(BUTTON_IDLE),
+                    MenuAction::Load(Box::new(save)),
+                ))
+                .with_children(|row| {
+                    let thumbnail_node = Node {
+                        width: Val::Px(THUMBNAIL_WIDTH),
+                        height: Val::Px(THUMBNAIL_HEIGHT),
+                        ..default()
+                    };
+                    match thumbnail {
+                        Some(image) => {
+                            row.spawn((ImageNode::new(image), thumbnail_node));
+                        }
+                        None => {
+                            row.spawn((thumbnail_node, BackgroundColor(Color::BLACK)));
+                        }
+                    }
+                    row.spawn(menu_text(details, &font, 16.0));
+                });
+            }
+
+            spawn_menu_button(root, &font, "Back", MenuAction::Back);
+        });
+}
+
+/// Longest survival runs, with the score collected along the way, and the
+/// best scoring ironman runs
+fn spawn_leaderboard_screen(mut commands: Commands, assets: Res<GameAssets>) {
+    let font = assets.font.clone();
+    let entries = survival::leaderboard();
+    let ironman_entries = ironman::leaderboard();
+
+    commands
+        .spawn(menu_root(MenuPage::Leaderboard, MENU_BACKGROUND))
+        .with_children(|root| {
+            root.spawn(menu_text("Survival Leaderboard", &font, 40.0));
+            if entries.is_empty() {
+                root.spawn(menu_text("No survival runs yet", &font, 18.0));
+            }
+            for (place, entry) in entries.iter().enumerate() {
+                root.spawn(menu_text(
+                    format!(
+                        "{:>2}.  {}   {} pts   {}",
+                        place + 1,
+                        save::format_play_time(entry.time),
+                        entry.score,
+                        save::format_timestamp(entry.timestamp)
+                    ),
+                    &font,
+                    18.0,
+                ));
+            }
+            root.spawn(menu_text("Ironman Leaderboard", &font, 40.0));
+            if ironman_entries.is_empty() {
+                root.spawn(menu_text("No ironman runs yet", &font, 18.0));
+            }
+            for (place, entry) in ironman_entries.iter().enumerate() {
+                root.spawn(menu_text(
+                    format!(
+                        "{:>2}.  {} pts   {}   {}",
+                        place + 1,
+                        entry.score,
+                        save::format_play_time(entry.time),
+                        save::format_timestamp(entry.timestamp)
+                    ),
+                    &font,
+                    18.0,
+                ));
+            }
+            spawn_menu_button(root, &font, "Back", MenuAction::Back);
+        });
+}
+
+/// The rolling part of the credits; its offset wraps once it has scrolled past
+#[derive(Component)]
+struct CreditsRoll {
+    offset: f32,
+}
+
+/// Contributors, asset licenses and the build, rolling slowly upwards
+fn spawn_credits_screen(mut commands: Commands, assets: Res<GameAssets>) {
+    let font = assets.font.clone();
+    let contributors = env!("GIT_CONTRIBUTORS")
+        .split(';')
+        .filter(|name| !name.is_empty());
+    let licenses = CREDITS_LICENSES
+        .iter()
+        .map(|(name, license)| format!("{name}  ·  {license}"));
+    let build = format!(
+        "Version {} ({})",
+        env!("CARGO_PKG_VERSION"),
+        env!("GIT_HASH")
+    );
+
+    commands
+        .spawn(menu_root(MenuPage::Credits, MENU_BACKGROUND))
+        .with_children(|root| {
+            root.spawn(menu_text("Credits", &font, 40.0));
+            root.spawn(Node {
+                width: Val::Px(520.0),
+                height: Val::Px(CREDITS_HEIGHT),
+                overflow: Overflow::clip(),
+                ..default()
+            })
+            .with_children(|window| {
+                window
+                    .spawn((
+                        Node {
+                            position_type: PositionType::Absolute,
+                            width: Val::Percent(100.0),
+                            top: Val::Px(CREDITS_HEIGHT),
+                            flex_direction: FlexDirection::Column,
+                            align_items: AlignItems::Center,
+                            row_gap: Val::Px(6.0),
+                            ..default()
+                        },
+                        CreditsRoll {
+                            offset: CREDITS_HEIGHT,
+                        },
+                    ))
+                    .with_children(|roll| {
+                        roll.spawn(menu_text("Contributors", &font, 24.0));
+                        for name in contributors {
+                            roll.spawn(menu_text(name, &font, 18.0));
+                        }
+                        roll.spawn(menu_text("\nAssets & Libraries", &font, 24.0));
+                        for line in licenses {
+                            roll.spawn(menu_text(line, &font, 16.0));
+                        }
+                        roll.spawn(menu_text("\nBuild", &font, 24.0));
+                        roll.spawn(menu_text(build, &font, 16.0));
+                    });
+            });
+            spawn_menu_button(root, &font, "Back", MenuAction::Back);
+        });
+}
+
+/// Rolls the credits up, starting over from the bottom once they are gone
+fn credits_scroll_system(
+    mut roll_query: Query<(&mut CreditsRoll, &mut Node, &ComputedNode)>,
+    time: Res<Time>,
+) {
+    for (mut roll, mut node, computed) in roll_query.iter_mut() {
+        roll.offset -= CREDITS_SCROLL_SPEED * time.delta_secs();
+        let height = computed.size().y * computed.inverse_scale_factor();
+        if roll.offset < -height {
+            roll.offset = CREDITS_HEIGHT;
+        }
+        node.top = Val::Px(roll.offset);
+    }
+}
+
+/// Names the chosen boat and says what it is like
+fn class_label_system(
+    class: Res<SubmarineClass>,
+    tables: Res<Tables>,
+    mut label_query: Query<&mut Text, With<ClassLabel>>,
+) {
+    let description = tables
+        .classes
+        .iter()
+        .find(|listed| listed.name == class.name)
+        .map_or(class.description.as_str(), |listed| {
+            listed.description.as_str()
+        });
+    let line = format!("Boat: {}\n{}", class.name, description);
+    for mut text in label_query.iter_mut() {
+        if **text != line {
+            **text = line.clone();
+        }
+    }
+}
+
+/// Thumbnails live next to the saves rather than under assets/, so decode them directly
+fn load_thumbnail(path: &str) -> Option<Image> {
+    let bytes = std::fs::read(path).ok()?;
+    Image::from_buffer(
+        &bytes,
+        ImageType::Extension("png"),
+        CompressedImageFormats::NONE,
+        true,
+        ImageSampler::Default,
+        RenderAssetUsages::RENDER_WORLD,
+    )
+    .ok()
+}
+
+#[allow(clippy::too_many_arguments)]
+fn menu_action_system(
+    mut commands: Commands,
+    button_query: Query<(&Interaction, &MenuAction), Changed<Interaction>>,
+    mut class: ResMut<SubmarineClass>,
+    tables: Res<Tables>,
+    mut next_app_state: ResMut<NextState<AppState>>,
+    mut next_page: ResMut<NextState<MenuPage>>,
+    mut next_pause_state: ResMut<NextState<PauseState>>,
+    mut next_settings_menu_state: ResMut<NextState<SettingsMenuState>>,
+    mut exit: EventWriter<AppExit>,
+) {
+    for (interaction, action) in button_query.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        match action {
+            MenuAction::NextClass => {
+                if let Some(next) = class.next(&tables) {
+                    *class = next;
+                }
+            }
+            MenuAction::NewGame => {
+                start_new_game(&mut commands, &mut next_app_state, GameMode::Dive)
+            }
+            MenuAction::NewGamePlus(cycle) => {
+                start_new_game(&mut commands, &mut next_app_state, GameMode::Dive);
+                commands.insert_resource(NewGamePlus(*cycle));
+            }
+            MenuAction::Ironman => {
+                start_new_game(&mut commands, &mut next_app_state, GameMode::Dive);
+                commands.insert_resource(Ironman(true));
+            }
+            MenuAction::TimeTrial => {
+                start_new_game(&mut commands, &mut next_app_state, GameMode::TimeTrial)
+            }
+            MenuAction::Survival => {
+                start_new_game(&mut commands, &mut next_app_state, GameMode::Survival)
+            }
+            MenuAction::OpenLeaderboard => next_page.set(MenuPage::Leaderboard),
+            MenuAction::OpenCredits => next_page.set(MenuPage::Credits),
+            MenuAction::OpenLoadGame => next_page.set(MenuPage::LoadGame),
+            MenuAction::Load(save) => {
+                save::start_load(&mut commands, &mut next_app_state, save.as_ref().clone())
+            }
+            MenuAction::Back => next_page.set(MenuPage::Title),
+            MenuAction::OpenSettings => next_settings_menu_state.set(SettingsMenuState::Open),
+            MenuAction::OpenEditor => next_app_state.set(AppState::Editor),
+            MenuAction::Resume => next_pause_state.set(PauseState::Running),
+            MenuAction::ExitToMainMenu => next_app_state.set(AppState::MainMenu),
+            MenuAction::Quit => {
+                exit.write(AppExit::Success);
+            }
+        }
+    }
+}
+
+fn menu_button_style_system(
+    mut button_query: Query<(&Interaction, &mut BackgroundColor), With<MenuAction>>,
+) {
+    for (interaction, mut background) in button_query.iter_mut() {
+        *background = BackgroundColor(match interaction {
+            Interaction::None => BUTTON_IDLE,
+            _ => BUTTON_HOVER,
+        });
+    }
+}
@@ -0,0 +1,178 @@
+//! Fish migration: a sea clock runs through the dive, an hour of sea time to
+//! each real minute, and fish follow daily and weekly schedules between the
+//! regions of the bay, up into the shallows at night and down into the deep
+//! holes around midday. Fish outside their scheduled region make for it and
+//! wander freely once there; outside every leg of their schedule they roam as
+//! they like. Regions and schedules are read from `assets/tables.ron`, so a
+//! player who learns the water knows where to patrol at each hour.
+
+use crate::tables::{SeaRegion, Tables};
+use crate::{AppState, Fish, FishMovement, GameAssets, GameSet};
+use bevy::prelude::*;
+use std::collections::HashMap;
+
+const HOUR_LENGTH: f32 = 60.0; // Real seconds per hour of sea time
+const START_HOUR: f32 = 8.0; // Sea time a new dive starts at
+const DAYS_PER_WEEK: u32 = 7;
+const DIVE_ANGLE: f32 = 0.3; // Meters a migrating fish climbs or dives for each meter it swims on
+
+/// Sea time since midnight on the dive's first day, in real seconds
+#[derive(Resource)]
+pub struct SeaClock {
+    pub elapsed: f32,
+}
+
+impl Default for SeaClock {
+    fn default() -> Self {
+        Self {
+            elapsed: START_HOUR * HOUR_LENGTH,
+        }
+    }
+}
+
+impl SeaClock {
+    /// Days since the dive began, the first being 0
+    pub fn days(&self) -> u32 {
+        (self.elapsed / (24.0 * HOUR_LENGTH)) as u32
+    }
+
+    /// Day of the week, the dive's first being 0
+    pub fn weekday(&self) -> u32 {
+        self.days() % DAYS_PER_WEEK
+    }
+
+    /// Hour of the day, from 0 up to 24
+    pub fn hour(&self) -> f32 {
+        (self.elapsed / HOUR_LENGTH) % 24.0
+    }
+}
+
+#[derive(Component)]
+struct ClockText;
+
+pub struct MigrationPlugin;
+
+impl Plugin for MigrationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SeaClock>()
+            .add_systems(
+                OnEnter(AppState::InGame),
+                (
+                    reset_clock.before(crate::save::apply_pending_load),
+                    spawn_clock_text,
+                ),
+            )
+            .add_systems(
+                Update,
+                (
+                    (clock_system, migration_system)
+                        .chain()
+                        .before(crate::fish_movement)
+                        .in_set(GameSet::Simulation),
+                    clock_text_system.in_set(GameSet::Presentation),
+                ),
+            );
+    }
+}
+
+fn reset_clock(mut commands: Commands) {
+    commands.insert_resource(SeaClock::default());
+}
+
+fn spawn_clock_text(mut commands: Commands, assets: Res<GameAssets>) {
+    commands.spawn((
+        Text::new(""),
+        TextFont {
+            font_size: 16.0,
+            font: assets.font.clone(),
+            ..default()
+        },
+        TextColor(Color::srgb(0.8, 0.85, 0.9)),
+        TextLayout::new_with_justify(JustifyText::Center),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(8.0),
+            width: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        ClockText,
+        StateScoped(AppState::InGame),
+    ));
+}
+
+fn clock_system(mut clock: ResMut<SeaClock>, time: Res<Time>) {
+    clock.elapsed += time.delta_secs();
+}
+
+/// Turns fish outside the region their schedule has them in towards it.
+/// Fish darting away from something are left to it, and the bait, the
+/// floodlights and the boat's noise still take them off course after this
+fn migration_system(
+    clock: Res<SeaClock>,
+    tables: Res<Tables>,
+    mut fish_query: Query<(&Name, &Transform, &mut FishMovement), With<Fish>>,
+) {
+    let (day, hour) = (clock.weekday(), clock.hour());
+    let regions: HashMap<&str, &SeaRegion> = tables
+        .regions
+        .iter()
+        .map(|region| (region.name.as_str(), region))
+        .collect();
+    let mut destinations: HashMap<&str, &SeaRegion> = HashMap::new();
+    for migration in tables.migrations.iter() {
+        if destinations.contains_key(migration.species.as_str()) {
+            continue;
+        }
+        let leg = migration.legs.iter().find(|leg| leg.runs_at(day, hour));
+        if let Some(region) = leg.and_then(|leg| regions.get(leg.region.as_str()).copied()) {
+            destinations.insert(migration.species.as_str(), region);
+        }
+    }
+    if destinations.is_empty() {
+        return;
+    }
+
+    for (name, transform, mut movement) in fish_query.iter_mut() {
+        let Some(region) = destinations.get(name.as_str()) else {
+            continue;
+        };
+        if movement.fleeing > 0.0 {
+            continue;
+        }
+        let position = transform.translation;
+        let depth = -position.y;
+        let level = Vec2::new(region.center.0 - position.x, region.center.1 - position.z);
+        if level.length() < region.radius && (region.depth.0..=region.depth.1).contains(&depth) {
+            continue;
+        }
+        // Deeper than the region's band is a positive rise, so climb
+        let rise = depth - depth.clamp(region.depth.0, region.depth.1);
+        let climb = if rise > 0.0 {
+            DIVE_ANGLE
+        } else if rise < 0.0 {
+            -DIVE_ANGLE
+        } else {
+            0.0
+        };
+        let direction = Vec3::new(level.x, 0.0, level.y).normalize_or_zero() + Vec3::Y * climb;
+        movement.direction = direction.normalize_or(Vec3::Y);
+        movement.change_direction_timer = 0.0;
+    }
+}
+
+fn clock_text_system(clock: Res<SeaClock>, mut text_query: Query<&mut Text, With<ClockText>>) {
+    let Ok(mut text) = text_query.single_mut() else {
+        return;
+    };
+    let minutes = (clock.hour() * 60.0) as u32;
+    let line = format!(
+        "Day {}  {:02}:{:02}",
+        clock.days() + 1,
+        minutes / 60,
+        minutes % 60
+    );
+    if **text != line {
+        **text = line;
+    }
+}
@@ -0,0 +1,227 @@
+//! Mods live in folders under `mods/` next to the game. Each folder may hold:
+//! - `mod.ron`: name, version, description, load priority and a default mission script
+//! - `tables.ron`: extra loot entries, fish species, submarine classes,
+//!   encounters, regions and migrations, and replacement spawn counts
+//!   (`replace_fish: true` drops the game's species first)
+//! - `props.ron`: extra or replacement materials and props, and extra placements
+//! - `assets/`: files that override the game's own assets at the same path
+//!
+//! Mods apply in order of priority, then folder name; later mods win conflicts.
+
+use crate::props::Props;
+use crate::tables::{Tables, TablesPatch};
+use bevy::prelude::*;
+use serde::Deserialize;
+
+#[cfg(not(target_arch = "wasm32"))]
+const MODS_DIR: &str = "mods";
+
+#[derive(Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ModManifest {
+    pub name: String, // Defaults to the folder name
+    pub version: String,
+    pub description: String,
+    pub priority: i32,
+    pub script: Option<String>, // Mission script run when none is given on the command line
+}
+
+pub struct Mod {
+    pub manifest: ModManifest,
+    #[cfg(not(target_arch = "wasm32"))]
+    assets: Option<std::path::PathBuf>,
+    tables: Option<TablesPatch>,
+    props: Option<Props>,
+}
+
+/// Mods found at startup, in the order they apply
+#[derive(Resource, Default)]
+pub struct LoadedMods(pub Vec<Mod>);
+
+impl LoadedMods {
+    pub fn apply_tables(&self, tables: &mut Tables) {
+        for patch in self.0.iter().filter_map(|m| m.tables.as_ref()) {
+            tables.extend(patch.clone());
+        }
+    }
+
+    pub fn apply_props(&self, props: &mut Props) {
+        for extra in self.0.iter().filter_map(|m| m.props.as_ref()) {
+            props.extend(extra.clone());
+        }
+    }
+
+    /// The highest-priority mod's default mission script
+    pub fn script(&self) -> Option<String> {
+        self.0.iter().rev().find_map(|m| m.manifest.script.clone())
+    }
+}
+
+impl Mod {
+    /// Content that doesn't come from a mods folder, such as a scenario file
+    pub fn new(manifest: ModManifest, tables: Option<TablesPatch>, props: Option<Props>) -> Self {
+        Self {
+            manifest,
+            #[cfg(not(target_arch = "wasm32"))]
+            assets: None,
+            tables,
+            props,
+        }
+    }
+
+    /// Name and version, for logs and the main menu
+    pub fn label(&self) -> String {
+        if self.manifest.version.is_empty() {
+            self.manifest.name.clone()
+        } else {
+            format!("{} {}", self.manifest.name, self.manifest.version)
+        }
+    }
+}
+
+/// Web builds have no mods folder
+#[cfg(target_arch = "wasm32")]
+pub fn discover() -> LoadedMods {
+    LoadedMods::default()
+}
+
+/// Reads every mod folder; files that fail to parse are skipped with a warning
+#[cfg(not(target_arch = "wasm32"))]
+pub fn discover() -> LoadedMods {
+    let Ok(entries) = std::fs::read_dir(MODS_DIR) else {
+        return LoadedMods::default();
+    };
+    let mut mods: Vec<Mod> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .map(|folder| {
+            let folder_name = folder
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let mut manifest: ModManifest = read_ron(&folder.join("mod.ron")).unwrap_or_default();
+            if manifest.name.is_empty() {
+                manifest.name = folder_name;
+            }
+            let assets = folder.join("assets");
+            Mod {
+                manifest,
+                assets: assets.is_dir().then_some(assets),
+                tables: read_ron(&folder.join("tables.ron")),
+                props: read_ron(&folder.join("props.ron")),
+            }
+        })
+        .collect();
+    mods.sort_by(|a, b| {
+        (a.manifest.priority, &a.manifest.name).cmp(&(b.manifest.priority, &b.manifest.name))
+    });
+    // Logging isn't set up yet this early in startup
+    for m in &mods {
+        println!("Loaded mod {}", m.label());
+    }
+    LoadedMods(mods)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn read_ron<T: serde::de::DeserializeOwned>(path: &std::path::Path) -> Option<T> {
+    let contents = std::fs::read_to_string(path).ok()?;
+    // Optional fields can be written without `Some(...)`
+    ron::Options::default()
+        .with_default_extension(ron::extensions::Extensions::IMPLICIT_SOME)
+        .from_str(&contents)
+        .map_err(|err| eprintln!("Skipping {}: {}", path.display(), err))
+        .ok()
+}
+
+/// Routes asset loads through the mods' `assets/` folders before the game's own.
+/// Has to be called before the asset plugin is added.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn register_asset_overrides(app: &mut App, mods: &LoadedMods) {
+    use bevy::asset::io::{AssetSource, AssetSourceId};
+    use std::time::Duration;
+
+    let overrides: Vec<_> = mods
+        .0
+        .iter()
+        .rev()
+        .filter_map(|m| m.assets.clone())
+        .collect();
+    if overrides.is_empty() {
+        return;
+    }
+    app.register_asset_source(
+        AssetSourceId::Default,
+        AssetSource::build()
+            .with_reader(move || {
+                Box::new(overrides::ModAssetReader {
+                    overrides: overrides.clone(),
+                    base: AssetSource::get_default_reader("assets".to_string())(),
+                })
+            })
+            .with_watcher(AssetSource::get_default_watcher(
+                "assets".to_string(),
+                Duration::from_millis(300),
+            )),
+    );
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn register_asset_overrides(_app: &mut App, _mods: &LoadedMods) {}
+
+#[cfg(not(target_arch = "wasm32"))]
+mod overrides {
+    use bevy::asset::io::{
+        AssetReader, AssetReaderError, ErasedAssetReader, PathStream, Reader, VecReader,
+    };
+    use std::path::{Path, PathBuf};
+    use std::sync::Arc;
+
+    pub struct ModAssetReader {
+        pub overrides: Vec<PathBuf>, // Mod asset folders, highest precedence first
+        pub base: Box<dyn ErasedAssetReader>,
+    }
+
+    impl ModAssetReader {
+        fn find(&self, path: &Path) -> Option<PathBuf> {
+            self.overrides
+                .iter()
+                .map(|folder| folder.join(path))
+                .find(|file| file.is_file())
+        }
+    }
+
+    impl AssetReader for ModAssetReader {
+        async fn read<'a>(
+            &'a self,
+            path: &'a Path,
+        ) -> Result<Box<dyn Reader + 'a>, AssetReaderError> {
+            match self.find(path) {
+                Some(file) => {
+                    let bytes =
+                        std::fs::read(file).map_err(|err| AssetReaderError::Io(Arc::new(err)))?;
+                    Ok(Box::new(VecReader::new(bytes)))
+                }
+                None => self.base.read(path).await,
+            }
+        }
+
+        async fn read_meta<'a>(
+            &'a self,
+            path: &'a Path,
+        ) -> Result<Box<dyn Reader + 'a>, AssetReaderError> {
+            self.base.read_meta(path).await
+        }
+
+        async fn read_directory<'a>(
+            &'a self,
+            path: &'a Path,
+        ) -> Result<Box<PathStream>, AssetReaderError> {
+            self.base.read_directory(path).await
+        }
+
+        async fn is_directory<'a>(&'a self, path: &'a Path) -> Result<bool, AssetReaderError> {
+            self.base.is_directory(path).await
+        }
+    }
+}
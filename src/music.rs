@@ -0,0 +1,225 @@
+//! Adaptive soundtrack: three synthesized stems play in step for the whole dive,
+//! and a mixer fades each one in or out with a danger score worked out from
+//! nearby predators, the oxygen left and the stress on the hull.
+
+use crate::combat::Hostile;
+use crate::settings::Settings;
+use crate::{AppState, GameSet, GameState, Submarine};
+use bevy::audio::{AddAudioSource, Decodable, Source, Volume};
+use bevy::prelude::*;
+use std::f32::consts::{PI, TAU};
+use std::time::Duration;
+
+const SAMPLE_RATE: u32 = 44_100;
+const BEAT: f32 = 0.6; // Seconds, 100 beats a minute
+const LOOP_BEATS: u32 = 16; // Every stem repeats after this many beats
+const PREDATOR_RANGE: f32 = 40.0; // Hostiles further away don't add to the danger
+const LOW_OXYGEN: f32 = 40.0; // Percent below which oxygen adds to the danger
+const STRESS_DEPTH: f32 = 12.0; // The hull starts to strain below this depth
+const STRESS_RANGE: f32 = 16.0; // Meters past STRESS_DEPTH for full depth stress
+const FADE_RATE: f32 = 0.4; // Stem gain change per second
+
+/// How much danger the submarine is in, from 0 (calm) to 1
+#[derive(Resource, Default)]
+pub struct DangerScore(pub f32);
+
+/// One layer of the soundtrack
+#[derive(Component, Clone, Copy, PartialEq, Debug)]
+enum Stem {
+    Strings,    // Calm pad that thins out as danger rises
+    Drone,      // Low tension drone under any threat
+    Percussion, // Pulse that only comes in when things get serious
+}
+
+impl Stem {
+    const ALL: [Stem; 3] = [Stem::Strings, Stem::Drone, Stem::Percussion];
+
+    /// Target gain of the stem at a danger score
+    fn gain(self, danger: f32) -> f32 {
+        let fade_in = |start: f32| ((danger - start) / (1.0 - start)).clamp(0.0, 1.0);
+        match self {
+            Stem::Strings => 0.5 * (1.0 - 0.7 * danger),
+            Stem::Drone => 0.6 * fade_in(0.15),
+            Stem::Percussion => 0.7 * fade_in(0.5),
+        }
+    }
+
+    /// The stem's waveform at `t` seconds into the loop
+    fn sample(self, t: f32) -> f32 {
+        match self {
+            Stem::Strings => {
+                // A minor and F major, a bar each, swelling in and out so the loop
+                // never cuts a chord off mid-note
+                let bar = 4.0 * BEAT;
+                let chords: [[f32; 3]; 2] = [[220.0, 261.63, 329.63], [174.61, 220.0, 261.63]];
+                let chord = chords[(t / bar) as usize % 2];
+                let local = t % bar;
+                let envelope = (PI * local / bar).sin();
+                let vibrato = 0.002 * (TAU * 5.0 * t).sin();
+                chord
+                    .iter()
+                    .map(|frequency| (TAU * frequency * (1.0 + vibrato) * local).sin())
+                    .sum::<f32>()
+                    * envelope
+                    / 3.0
+            }
+            Stem::Drone => {
+                // Two slightly detuned roots beat against each other; every
+                // frequency fits a whole number of cycles into the loop
+                let swell = 0.75 + 0.25 * (TAU * t / (LOOP_BEATS as f32 * BEAT)).sin();
+                swell
+                    * ((TAU * 55.0 * t).sin()
+                        + (TAU * 55.3125 * t).sin()
+                        + 0.4 * (TAU * 82.5 * t).sin())
+                    / 2.4
+            }
+            Stem::Percussion => {
+                // A falling kick on every beat and a short tick between them
+                let local = t % BEAT;
+                let kick_phase = TAU * (50.0 * local + 60.0 * (1.0 - (-local * 30.0).exp()) / 30.0);
+                let kick = (-local * 12.0).exp() * kick_phase.sin();
+                let offbeat = local - BEAT / 2.0;
+                let tick = if offbeat >= 0.0 {
+                    0.3 * (-offbeat * 60.0).exp() * (TAU * 1800.0 * offbeat).sin()
+                } else {
+                    0.0
+                };
+                kick + tick
+            }
+        }
+    }
+}
+
+/// Endless synthesized audio for one stem
+#[derive(Asset, TypePath, Clone, Copy)]
+struct StemSource(Stem);
+
+struct StemDecoder {
+    stem: Stem,
+    sample: u32, // Wraps at the loop length so phases stay precise in long dives
+}
+
+impl Iterator for StemDecoder {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let t = self.sample as f32 / SAMPLE_RATE as f32;
+        self.sample = (self.sample + 1) % (LOOP_BEATS as f32 * BEAT * SAMPLE_RATE as f32) as u32;
+        Some(self.stem.sample(t).clamp(-1.0, 1.0))
+    }
+}
+
+impl Source for StemDecoder {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+impl Decodable for StemSource {
+    type DecoderItem = f32;
+    type Decoder = StemDecoder;
+
+    fn decoder(&self) -> StemDecoder {
+        StemDecoder {
+            stem: self.0,
+            sample: 0,
+        }
+    }
+}
+
+/// Current gain of each stem, eased towards the target for the danger score
+#[derive(Resource, Default)]
+struct StemMixer {
+    gains: [f32; 3], // In the order of Stem::ALL
+}
+
+pub struct MusicPlugin;
+
+impl Plugin for MusicPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_audio_source::<StemSource>()
+            .init_resource::<DangerScore>()
+            .init_resource::<StemMixer>()
+            .add_systems(OnEnter(AppState::InGame), start_stems)
+            .add_systems(
+                Update,
+                (
+                    danger_score_system.in_set(GameSet::Detection),
+                    // Outside the game sets so the music volume setting applies while paused
+                    stem_mixer_system
+                        .after(danger_score_system)
+                        .run_if(in_state(AppState::InGame)),
+                ),
+            );
+    }
+}
+
+/// Starts every stem silent and in step; the mixer brings them in
+fn start_stems(mut commands: Commands, mut sources: ResMut<Assets<StemSource>>) {
+    commands.insert_resource(DangerScore::default());
+    commands.insert_resource(StemMixer::default());
+    for stem in Stem::ALL {
+        commands.spawn((
+            AudioPlayer(sources.add(StemSource(stem))),
+            PlaybackSettings::LOOP.with_volume(Volume::Linear(0.0)),
+            stem,
+            StateScoped(AppState::InGame),
+        ));
+    }
+}
+
+/// Adds up the threats to the submarine: the nearest predator, running out of
+/// oxygen, and hull damage made worse by depth
+fn danger_score_system(
+    mut danger: ResMut<DangerScore>,
+    game_state: Res<GameState>,
+    submarine_query: Query<&Transform, With<Submarine>>,
+    hostile_query: Query<&Transform, With<Hostile>>,
+) {
+    let Ok(submarine) = submarine_query.single() else {
+        return;
+    };
+    let predator = hostile_query
+        .iter()
+        .map(|hostile| 1.0 - hostile.translation.distance(submarine.translation) / PREDATOR_RANGE)
+        .fold(0.0, f32::max);
+    let oxygen = ((LOW_OXYGEN - game_state.oxygen) / LOW_OXYGEN).clamp(0.0, 1.0);
+    let depth = -submarine.translation.y;
+    let pressure = ((depth - STRESS_DEPTH) / STRESS_RANGE).clamp(0.0, 1.0);
+    let hull = ((100.0 - game_state.health) / 100.0 + 0.5 * pressure).clamp(0.0, 1.0);
+    danger.0 = (predator + oxygen + hull).min(1.0);
+}
+
+/// Eases each stem towards its gain for the danger score, so layers cross-fade
+/// instead of switching tracks
+fn stem_mixer_system(
+    danger: Res<DangerScore>,
+    settings: Res<Settings>,
+    mut mixer: ResMut<StemMixer>,
+    mut sink_query: Query<(&Stem, &mut AudioSink)>,
+    time: Res<Time>,
+) {
+    let step = FADE_RATE * time.delta_secs();
+    for (gain, stem) in mixer.gains.iter_mut().zip(Stem::ALL) {
+        let target = stem.gain(danger.0);
+        *gain += (target - *gain).clamp(-step, step);
+    }
+    for (stem, mut sink) in sink_query.iter_mut() {
+        let index = Stem::ALL.iter().position(|s| s == stem).unwrap_or(0);
+        sink.set_volume(Volume::Linear(
+            mixer.gains[index] * settings.audio.music_volume,
+        ));
+    }
+}
@@ -0,0 +1,155 @@
+//! New Game Plus: finishing the last mission of the campaign unlocks a harder
+//! run from the main menu. Upgrade parts carry over, every species keeps to
+//! deeper water, predators lead their prey and are harder to fool with
+//! decoys, and elite species join the school. Finishing the campaign again in
+//! New Game Plus unlocks the next cycle, deeper still.
+
+use crate::repair::Inventory;
+use crate::replay::ReplayPlayback;
+use crate::scripting::{MissionEnded, Missions};
+use crate::tables::FishSpecies;
+use crate::{storage, AppState, GameAssets, GameSet};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Storage key campaign progress is kept under between sessions
+const PROGRESS_PATH: &str = "campaign.ron";
+const DEPTH_STEP: f32 = 0.2; // Share of each species' depth added per cycle
+const DEEPEST_FISH: f32 = 19.5; // Meters; fish stay off the floor however deep a cycle sends them
+pub const LEAD_TIME: f32 = 2.0; // Most seconds ahead a predator aims along its prey's course
+pub const DECOY_SUSPICION: f32 = 0.5; // Share of the usual range a decoy still draws predators from
+const BANNER_TIME: f32 = 6.0;
+
+/// New Game Plus cycle the current dive is played at; 0 is the first run
+#[derive(Resource, Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct NewGamePlus(pub u32);
+
+impl NewGamePlus {
+    pub fn active(self) -> bool {
+        self.0 > 0
+    }
+
+    /// Depth range a species swims at this cycle, in meters below the surface
+    pub fn fish_depth(self, species: &FishSpecies) -> (f32, f32) {
+        let scale = 1.0 + DEPTH_STEP * self.0 as f32;
+        (
+            (species.depth.0 * scale).min(DEEPEST_FISH),
+            (species.depth.1 * scale).min(DEEPEST_FISH),
+        )
+    }
+
+    /// Relative abundance of a species; elite species only appear in New Game Plus
+    pub fn species_weight(self, species: &FishSpecies) -> u32 {
+        if species.elite && !self.active() {
+            0
+        } else {
+            species.weight
+        }
+    }
+}
+
+/// What the player has unlocked across runs of the campaign
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CampaignProgress {
+    pub cycles_unlocked: u32, // Highest New Game Plus cycle that can be started
+    pub upgrade_parts: u32,   // Carried into New Game Plus
+}
+
+impl CampaignProgress {
+    pub fn load() -> Self {
+        storage::read(PROGRESS_PATH)
+            .and_then(|contents| ron::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self) {
+        let result = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|err| err.to_string())
+            .and_then(|contents| storage::write(PROGRESS_PATH, &contents));
+        if let Err(err) = result {
+            warn!("Failed to save campaign progress: {}", err);
+        }
+    }
+}
+
+#[derive(Component)]
+struct UnlockBanner(f32); // Seconds left on screen
+
+pub struct NewGamePlusPlugin;
+
+impl Plugin for NewGamePlusPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<NewGamePlus>().add_systems(
+            Update,
+            (
+                campaign_complete_system.in_set(GameSet::Detection),
+                unlock_banner_system.in_set(GameSet::Presentation),
+            ),
+        );
+    }
+}
+
+/// Finishing the last mission unlocks the next cycle and banks the upgrade
+/// parts aboard for it
+fn campaign_complete_system(
+    mut commands: Commands,
+    mut mission_ended: EventReader<MissionEnded>,
+    missions: Res<Missions>,
+    new_game_plus: Res<NewGamePlus>,
+    inventory: Res<Inventory>,
+    assets: Res<GameAssets>,
+    playback: Option<Res<ReplayPlayback>>,
+) {
+    let finished = mission_ended
+        .read()
+        .any(|ended| ended.completed && missions.0.last() == Some(&ended.mission));
+    if !finished || playback.is_some() {
+        return;
+    }
+    let mut progress = CampaignProgress::load();
+    let cycle = new_game_plus.0 + 1;
+    progress.cycles_unlocked = progress.cycles_unlocked.max(cycle);
+    progress.upgrade_parts = inventory.upgrade_parts;
+    progress.save();
+    info!(
+        "Campaign complete, New Game Plus {} unlocked with {} upgrade parts",
+        cycle, progress.upgrade_parts
+    );
+
+    commands.spawn((
+        Text::new(format!(
+            "Campaign complete!\nNew Game+ {} unlocked from the main menu",
+            cycle
+        )),
+        TextFont {
+            font_size: 28.0,
+            font: assets.font.clone(),
+            ..default()
+        },
+        TextColor(Color::srgb(1.0, 0.85, 0.3)),
+        TextLayout::new_with_justify(JustifyText::Center),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Percent(20.0),
+            width: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        UnlockBanner(BANNER_TIME),
+        StateScoped(AppState::InGame),
+    ));
+}
+
+fn unlock_banner_system(
+    mut commands: Commands,
+    mut banner_query: Query<(Entity, &mut UnlockBanner)>,
+    time: Res<Time>,
+) {
+    for (entity, mut banner) in banner_query.iter_mut() {
+        banner.0 -= time.delta_secs();
+        if banner.0 <= 0.0 {
+            commands.entity(entity).despawn();
+        }
+    }
+}
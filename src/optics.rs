@@ -0,0 +1,154 @@
+//! Water optics: how far can be seen, what colour the distance fades to and
+//! how much daylight gets down, all worked out in one place from the depth of
+//! the camera, the weather overhead and the kind of water the dive is in. A
+//! clear arctic sound can be seen across from tens of meters away, while a
+//! silty river mouth closes in to a few meters of brown murk. Passing clouds
+//! dim the light under them. The lighting, the fog and the camera's reach all
+//! follow from `WaterOpticalProperties`.
+
+use crate::scenario::Scenario;
+use crate::{AppState, CameraFollow, GameSet, WorldSeed};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+const SURFACE_DEPTH: f32 = 0.5; // Camera depth below which it is underwater
+const CLOUD_SHADE: f32 = 0.5; // Share of the daylight full cloud cover takes away
+const CLOUD_PERIOD: f32 = 240.0; // Seconds for the weather to come round again
+const DARK_VISIBILITY: f32 = 0.4; // Share of its clear-water visibility left in the dark
+const DARK_FOG: f32 = 0.15; // Brightness of the fog where no daylight reaches
+const AIR_VISIBILITY: f32 = 1500.0; // Meters seen above the water
+const AIR_COLOR: (f32, f32, f32) = (0.7, 0.8, 0.9); // Haze and daylight above the water
+const DEEP_COLOR: (f32, f32, f32) = (0.3, 0.55, 0.95); // Daylight once the reds are gone
+
+/// The kind of water a dive takes place in
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum WaterBody {
+    #[default]
+    Coastal,
+    ClearArctic,
+    SiltyRiver,
+}
+
+impl WaterBody {
+    /// Meters seen in full daylight, the colour the distance fades to and the
+    /// share of the light lost per meter of depth
+    fn clarity(self) -> (f32, (f32, f32, f32), f32) {
+        match self {
+            WaterBody::Coastal => (35.0, (0.1, 0.3, 0.45), 0.07),
+            WaterBody::ClearArctic => (60.0, (0.15, 0.38, 0.52), 0.04),
+            WaterBody::SiltyRiver => (8.0, (0.32, 0.28, 0.15), 0.2),
+        }
+    }
+}
+
+/// `from` blended `amount` of the way to `to`, in sRGB
+fn blend(from: (f32, f32, f32), to: (f32, f32, f32), amount: f32) -> Color {
+    Color::srgb(
+        from.0 + (to.0 - from.0) * amount,
+        from.1 + (to.1 - from.1) * amount,
+        from.2 + (to.2 - from.2) * amount,
+    )
+}
+
+/// Cloud over the dive, from clear skies at 0 to overcast at 1
+#[derive(Resource, Default)]
+pub struct Weather {
+    pub cloud_cover: f32,
+}
+
+/// What the water lets through at the camera
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct WaterOpticalProperties {
+    pub water: WaterBody,
+    pub visibility: f32, // Meters at which things fade fully into the water
+    pub fog_color: Color,
+    pub light: f32, // Share of clear-sky daylight reaching the camera's depth
+    pub light_color: Color,
+    pub underwater: bool,
+}
+
+impl Default for WaterOpticalProperties {
+    fn default() -> Self {
+        Self {
+            water: WaterBody::default(),
+            visibility: AIR_VISIBILITY,
+            fog_color: Color::srgb(AIR_COLOR.0, AIR_COLOR.1, AIR_COLOR.2),
+            light: 1.0,
+            light_color: Color::srgb(AIR_COLOR.0, AIR_COLOR.1, AIR_COLOR.2),
+            underwater: false,
+        }
+    }
+}
+
+impl WaterOpticalProperties {
+    /// The water as seen from `depth` meters down under `cloud_cover`
+    pub fn at(water: WaterBody, depth: f32, cloud_cover: f32) -> Self {
+        let daylight = 1.0 - CLOUD_SHADE * cloud_cover;
+        if depth < SURFACE_DEPTH {
+            return Self {
+                water,
+                light: daylight,
+                ..default()
+            };
+        }
+        let (clear_visibility, tint, absorption) = water.clarity();
+        let light = daylight * (-absorption * depth).exp();
+        // Red goes first, so light deep down is ever bluer
+        let blue_shift = 1.0 - (-absorption * depth * 2.0).exp();
+        Self {
+            water,
+            visibility: clear_visibility * (DARK_VISIBILITY + (1.0 - DARK_VISIBILITY) * light),
+            fog_color: blend((0.0, 0.0, 0.0), tint, DARK_FOG + (1.0 - DARK_FOG) * light),
+            light,
+            light_color: blend(AIR_COLOR, DEEP_COLOR, blue_shift),
+            underwater: true,
+        }
+    }
+}
+
+pub struct OpticsPlugin;
+
+impl Plugin for OpticsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Weather>()
+            .init_resource::<WaterOpticalProperties>()
+            .add_systems(OnEnter(AppState::InGame), reset_optics)
+            .add_systems(
+                Update,
+                (
+                    weather_system.in_set(GameSet::Simulation),
+                    optics_system
+                        .after(crate::camera_follow)
+                        .before(crate::depth_lighting_system)
+                        .in_set(GameSet::Presentation),
+                ),
+            );
+    }
+}
+
+/// Scenarios may set the water; otherwise it is coastal
+fn reset_optics(mut commands: Commands, scenario: Option<Res<Scenario>>) {
+    let water = scenario.map_or_else(WaterBody::default, |scenario| scenario.water);
+    commands.insert_resource(Weather::default());
+    commands.insert_resource(WaterOpticalProperties { water, ..default() });
+}
+
+/// Clouds build and clear as the dive goes on, the same way each time for a
+/// given world so replays match
+fn weather_system(mut weather: ResMut<Weather>, seed: Res<WorldSeed>, time: Res<Time>) {
+    let phase = (seed.0 % 1000) as f32 / 1000.0 * std::f32::consts::TAU;
+    let t = time.elapsed_secs() / CLOUD_PERIOD * std::f32::consts::TAU;
+    let cover = 0.5 + 0.35 * (t + phase).sin() + 0.15 * (t * 2.7 + phase * 3.0).sin();
+    weather.cloud_cover = cover.clamp(0.0, 1.0);
+}
+
+fn optics_system(
+    mut optics: ResMut<WaterOpticalProperties>,
+    weather: Res<Weather>,
+    camera_query: Query<&Transform, With<CameraFollow>>,
+) {
+    let Ok(camera) = camera_query.single() else {
+        return;
+    };
+    *optics = WaterOpticalProperties::at(optics.water, -camera.translation.y, weather.cloud_cover);
+}
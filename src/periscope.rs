@@ -0,0 +1,450 @@
+//! The periscope: from periscope depth, look out over the surface at 1x, 4x
+//! or 8x, trained with the camera keys against a bearing ring. Merchant ships
+//! steam across the surface, and the stadimeter ranges one in the crosshair
+//! from the angle its masthead makes, writing the estimate to the contact log.
+
+use crate::controls::{Action, HelmInput, InputMap};
+use crate::event_log::{LogKind, LogMessage};
+use crate::magnetics::Navigation;
+use crate::{
+    AppState, CameraFollow, CameraMode, GameAssets, GameMode, GameSet, Submarine, ViewCameraQuery,
+    WorldSeed,
+};
+use bevy::prelude::*;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::f32::consts::{FRAC_PI_4, TAU};
+
+pub const PERISCOPE_DEPTH: f32 = 3.0; // Deeper than this the periscope head is under water
+const PERISCOPE_HEIGHT: f32 = 3.5; // Head above the hull's centre line when raised
+const ZOOMS: [f32; 3] = [1.0, 4.0, 8.0];
+const TRAIN_SPEED: f32 = 1.0; // Radians per second at 1x; slower when zoomed in
+const SHIP_COUNT: usize = 3;
+const SHIP_SPEED: f32 = 4.0;
+const TRAFFIC_RADIUS: f32 = 450.0; // Ships turn back at this distance from the middle of the map
+const STADIMETER_ERROR: f32 = 0.08; // Share the range may be off by at 1x; divided by the zoom
+const LOG_LINES: usize = 4; // Contact log entries shown in the periscope view
+const RING_STEP: usize = 10; // Degrees between labels on the bearing ring
+
+/// A ship on the surface; the stadimeter ranges it by its masthead
+#[derive(Component)]
+pub struct SurfaceShip {
+    pub name: &'static str,
+    pub mast_height: f32, // Meters from the waterline to the masthead
+    velocity: Vec3,
+}
+
+/// Where the periscope is trained and how far it is zoomed
+#[derive(Resource, Default)]
+struct Periscope {
+    bearing: f32, // Radians to port of the bow
+    zoom: usize,  // Index into ZOOMS
+}
+
+/// A range taken on a contact
+#[derive(Clone, Debug)]
+pub struct ContactReport {
+    pub name: &'static str,
+    pub bearing: f32, // Compass degrees
+    pub range: f32,   // Estimated meters
+}
+
+/// Ranges and bearings taken through the periscope, oldest first
+#[derive(Resource, Default)]
+pub struct ContactLog {
+    pub entries: Vec<ContactReport>,
+}
+
+/// Random source for stadimeter error and ship placement, seeded from the
+/// world so replays match
+#[derive(Resource)]
+struct PeriscopeRng(StdRng);
+
+#[derive(Component)]
+struct PeriscopeOverlay;
+
+#[derive(Component)]
+struct PeriscopeText;
+
+/// A bearing label on the ring, in degrees relative to the bow
+#[derive(Component)]
+struct RingLabel(usize);
+
+/// Merchant ships' hulls and masts: unit shapes scaled to each ship, made once
+#[derive(Resource)]
+struct ShipAssets {
+    hull_mesh: Handle<Mesh>,
+    mast_mesh: Handle<Mesh>,
+    hull_material: Handle<StandardMaterial>,
+    mast_material: Handle<StandardMaterial>,
+}
+
+impl FromWorld for ShipAssets {
+    fn from_world(world: &mut World) -> Self {
+        let mut meshes = world.resource_mut::<Assets<Mesh>>();
+        let hull_mesh = meshes.add(Cuboid::new(1.0, 1.0, 1.0));
+        let mast_mesh = meshes.add(Cylinder::new(1.0, 1.0));
+        let mut materials = world.resource_mut::<Assets<StandardMaterial>>();
+        Self {
+            hull_mesh,
+            mast_mesh,
+            hull_material: materials.add(StandardMaterial {
+                base_color: Color::srgb(0.25, 0.2, 0.2),
+                ..default()
+            }),
+            mast_material: materials.add(StandardMaterial {
+                base_color: Color::srgb(0.8, 0.8, 0.75),
+                ..default()
+            }),
+        }
+    }
+}
+
+pub struct PeriscopePlugin;
+
+impl Plugin for PeriscopePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Periscope>()
+            .init_resource::<ContactLog>()
+            .insert_resource(PeriscopeRng(StdRng::seed_from_u64(0)))
+            .add_systems(
+                OnEnter(AppState::InGame),
+                (
+                    reset_periscope,
+                    spawn_ships.run_if(|mode: Res<GameMode>| *mode != GameMode::TimeTrial),
+                )
+                    .chain(),
+            )
+            .add_systems(OnEnter(CameraMode::Periscope), spawn_overlay)
+            .add_systems(OnExit(CameraMode::Periscope), lower_periscope)
+            .add_systems(
+                Update,
+                (
+                    periscope_control_system
+                        .in_set(GameSet::Input)
+                        .run_if(in_state(CameraMode::Periscope)),
+                    ship_movement_system.in_set(GameSet::Simulation),
+                    (periscope_camera_system, periscope_overlay_system)
+                        .chain()
+                        .after(crate::camera_follow)
+                        .in_set(GameSet::Presentation)
+                        .run_if(in_state(CameraMode::Periscope)),
+                ),
+            );
+    }
+
+    fn finish(&self, app: &mut App) {
+        app.init_resource::<ShipAssets>();
+    }
+}
+
+fn reset_periscope(mut commands: Commands, seed: Res<WorldSeed>) {
+    commands.insert_resource(Periscope::default());
+    commands.insert_resource(ContactLog::default());
+    commands.insert_resource(PeriscopeRng(StdRng::seed_from_u64(seed.0 ^ 0x5045_5249)));
+}
+
+/// Merchant ships on straight courses across the map
+fn spawn_ships(mut commands: Commands, assets: Res<ShipAssets>, mut rng: ResMut<PeriscopeRng>) {
+    let names = ["Kestrel", "Marguerite", "Halcyon Star"];
+    for name in names.into_iter().take(SHIP_COUNT) {
+        let position = Vec3::new(
+            rng.0.gen_range(-300.0..300.0),
+            0.0,
+            rng.0.gen_range(-300.0..300.0),
+        );
+        let heading = rng.0.gen::<f32>() * TAU;
+        let length = rng.0.gen_range(20.0..40.0);
+        let mast_height = rng.0.gen_range(12.0..25.0);
+        commands
+            .spawn((
+                Transform::from_translation(position).with_rotation(Quat::from_rotation_y(heading)),
+                Visibility::default(),
+                SurfaceShip {
+                    name,
+                    mast_height,
+                    velocity: Quat::from_rotation_y(heading) * Vec3::NEG_Z * SHIP_SPEED,
+                },
+                StateScoped(AppState::InGame),
+            ))
+            .with_children(|ship| {
+                ship.spawn((
+                    Mesh3d(assets.hull_mesh.clone()),
+                    MeshMaterial3d(assets.hull_material.clone()),
+                    Transform::from_xyz(0.0, 1.0, 0.0).with_scale(Vec3::new(
+                        length * 0.15,
+                        4.0,
+                        length,
+                    )),
+                ));
+                ship.spawn((
+                    Mesh3d(assets.mast_mesh.clone()),
+                    MeshMaterial3d(assets.mast_material.clone()),
+                    Transform::from_xyz(0.0, mast_height / 2.0, 0.0).with_scale(Vec3::new(
+                        0.3,
+                        mast_height,
+                        0.3,
+                    )),
+                ));
+            });
+    }
+}
+
+/// Ships hold their course and come about at the edge of the map
+fn ship_movement_system(
+    mut ship_query: Query<(&mut SurfaceShip, &mut Transform)>,
+    time: Res<Time>,
+) {
+    for (mut ship, mut transform) in ship_query.iter_mut() {
+        transform.translation += ship.velocity * time.delta_secs();
+        if transform.translation.length() > TRAFFIC_RADIUS
+            && transform.translation.dot(ship.velocity) > 0.0
+        {
+            ship.velocity = -ship.velocity;
+            transform.rotate_y(std::f32::consts::PI);
+        }
+    }
+}
+
+fn spawn_overlay(mut commands: Commands, assets: Res<GameAssets>) {
+    let font = assets.font.clone();
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                ..default()
+            },
+            Pickable::IGNORE,
+            PeriscopeOverlay,
+            StateScoped(AppState::InGame),
+        ))
+        .with_children(|overlay| {
+            // Crosshair
+            let hair = BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.8));
+            overlay.spawn((
+                Node {
+                    position_type: PositionType::Absolute,
+                    left: Val::Percent(50.0),
+                    top: Val::Percent(20.0),
+                    width: Val::Px(1.0),
+                    height: Val::Percent(60.0),
+                    ..default()
+                },
+                hair,
+            ));
+            overlay.spawn((
+                Node {
+                    position_type: PositionType::Absolute,
+                    left: Val::Percent(20.0),
+                    top: Val::Percent(50.0),
+                    width: Val::Percent(60.0),
+                    height: Val::Px(1.0),
+                    ..default()
+                },
+                hair,
+            ));
+
+            // Bearing ring along the top edge
+            for degrees in (0..360).step_by(RING_STEP) {
+                overlay.spawn((
+                    Text::new(format!("{:03}", degrees)),
+                    TextFont {
+                        font_size: 14.0,
+                        font: font.clone(),
+                        ..default()
+                    },
+                    TextColor(Color::srgb(0.9, 0.95, 0.8)),
+                    Node {
+                        position_type: PositionType::Absolute,
+                        top: Val::Px(12.0),
+                        display: Display::None,
+                        ..default()
+                    },
+                    RingLabel(degrees),
+                ));
+            }
+
+            overlay.spawn((
+                Text::new(""),
+                TextFont {
+                    font_size: 16.0,
+                    font: font.clone(),
+                    ..default()
+                },
+                TextColor(Color::srgb(0.9, 0.95, 0.8)),
+                Node {
+                    position_type: PositionType::Absolute,
+                    bottom: Val::Px(130.0),
+                    left: Val::Px(16.0),
+                    ..default()
+                },
+                PeriscopeText,
+            ));
+        });
+}
+
+/// Lowering the periscope takes the overlay away and restores the normal field of view
+fn lower_periscope(
+    mut commands: Commands,
+    overlay_query: Query<Entity, With<PeriscopeOverlay>>,
+    mut projection_query: Query<&mut Projection, With<CameraFollow>>,
+) {
+    for overlay in overlay_query.iter() {
+        commands.entity(overlay).despawn();
+    }
+    for mut projection in projection_query.iter_mut() {
+        if let Projection::Perspective(perspective) = projection.as_mut() {
+            perspective.fov = FRAC_PI_4;
+        }
+    }
+}
+
+/// Trains and zooms the periscope and takes stadimeter ranges; the periscope
+/// comes down if the boat sinks below periscope depth
+#[allow(clippy::too_many_arguments)]
+fn periscope_control_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    input_map: Res<InputMap>,
+    helm_input: Res<HelmInput>,
+    navigation: Res<Navigation>,
+    mut periscope: ResMut<Periscope>,
+    mut log: ResMut<ContactLog>,
+    mut log_messages: EventWriter<LogMessage>,
+    mut rng: ResMut<PeriscopeRng>,
+    mut next_camera_mode: ResMut<NextState<CameraMode>>,
+    submarine_query: Query<&Transform, With<Submarine>>,
+    ship_query: Query<(&SurfaceShip, &Transform), Without<Submarine>>,
+    time: Res<Time>,
+) {
+    let Ok(submarine) = submarine_query.single() else {
+        return;
+    };
+    if -submarine.translation.y > PERISCOPE_DEPTH {
+        next_camera_mode.set(CameraMode::ControlRoom);
+        return;
+    }
+
+    let zoom = ZOOMS[periscope.zoom];
+    periscope.bearing = (periscope.bearing
+        - helm_input.camera_yaw * TRAIN_SPEED / zoom * time.delta_secs())
+    .rem_euclid(TAU);
+    if input_map.just_pressed(&keyboard_input, Action::PeriscopeZoom) {
+        periscope.zoom = (periscope.zoom + 1) % ZOOMS.len();
+    }
+    if !input_map.just_pressed(&keyboard_input, Action::Stadimeter) {
+        return;
+    }
+
+    // Range the ship closest to the crosshair within the field of view
+    let head = periscope_head(submarine);
+    let yaw = submarine.rotation.to_euler(EulerRot::YXZ).0 + periscope.bearing;
+    let half_fov = FRAC_PI_4 / zoom / 2.0;
+    let sighted = ship_query
+        .iter()
+        .filter_map(|(ship, transform)| {
+            let offset = transform.translation - head;
+            let ship_yaw = (-offset.x).atan2(-offset.z);
+            let off_axis =
+                (ship_yaw - yaw + std::f32::consts::PI).rem_euclid(TAU) - std::f32::consts::PI;
+            (off_axis.abs() <= half_fov).then_some((ship, offset, off_axis.abs()))
+        })
+        .min_by(|a, b| a.2.total_cmp(&b.2));
+    let Some((ship, offset, _)) = sighted else {
+        info!("Stadimeter: no contact in the field of view");
+        return;
+    };
+
+    // The angle the masthead makes over the waterline gives the range, as well
+    // as it can be read at this magnification
+    let distance = Vec2::new(offset.x, offset.z).length();
+    let angle = (ship.mast_height / distance).atan();
+    let error = rng.0.gen_range(-1.0..1.0) * STADIMETER_ERROR / zoom;
+    let range = ship.mast_height / (angle * (1.0 + error)).tan();
+    let report = ContactReport {
+        name: ship.name,
+        bearing: navigation.compass_heading((-offset.x).atan2(-offset.z)),
+        range,
+    };
+    info!(
+        "Stadimeter: {} bearing {:03.0}, range {:.0} m",
+        report.name, report.bearing, report.range
+    );
+    log_messages.write(LogMessage {
+        kind: LogKind::Contact,
+        text: format!(
+            "{} bearing {:03.0}, range {:.0} m",
+            report.name, report.bearing, report.range
+        ),
+    });
+    log.entries.push(report);
+}
+
+/// Where the periscope head sits when raised
+fn periscope_head(submarine: &Transform) -> Vec3 {
+    submarine.translation + submarine.rotation * Vec3::new(0.0, PERISCOPE_HEIGHT, -0.5)
+}
+
+/// Looks out from the periscope head along the trained bearing at the chosen zoom
+fn periscope_camera_system(
+    periscope: Res<Periscope>,
+    submarine_query: Query<&Transform, With<Submarine>>,
+    mut camera_query: ViewCameraQuery,
+) {
+    let Ok(submarine) = submarine_query.single() else {
+        return;
+    };
+    let yaw = submarine.rotation.to_euler(EulerRot::YXZ).0 + periscope.bearing;
+    for (mut transform, mut projection) in camera_query.iter_mut() {
+        *transform = Transform::from_translation(periscope_head(submarine))
+            .with_rotation(Quat::from_rotation_y(yaw));
+        if let Projection::Perspective(perspective) = projection.as_mut() {
+            perspective.fov = FRAC_PI_4 / ZOOMS[periscope.zoom];
+        }
+    }
+}
+
+/// Slides the bearing ring with the view and shows zoom, bearing and the
+/// latest ranges
+fn periscope_overlay_system(
+    periscope: Res<Periscope>,
+    log: Res<ContactLog>,
+    window_query: Query<&Window>,
+    mut label_query: Query<(&RingLabel, &mut Node)>,
+    mut text_query: Query<&mut Text, With<PeriscopeText>>,
+) {
+    let zoom = ZOOMS[periscope.zoom];
+    let aspect = window_query
+        .iter()
+        .next()
+        .map(|window| window.width() / window.height().max(1.0))
+        .unwrap_or(16.0 / 9.0);
+    let half_width = ((FRAC_PI_4 / zoom / 2.0).tan() * aspect).atan();
+    // Relative bearings run clockwise from the bow; the periscope's bearing runs to port
+    let centre = (-periscope.bearing).rem_euclid(TAU).to_degrees();
+    for (label, mut node) in label_query.iter_mut() {
+        let off = (label.0 as f32 - centre + 540.0).rem_euclid(360.0) - 180.0;
+        let off = off.to_radians();
+        if off.abs() < half_width {
+            node.display = Display::Flex;
+            node.left = Val::Percent(50.0 + 50.0 * off.tan() / half_width.tan());
+        } else {
+            node.display = Display::None;
+        }
+    }
+
+    for mut text in text_query.iter_mut() {
+        let mut lines = vec![format!(
+            "Periscope {:.0}x  Relative bearing {:03.0}\nZ: Zoom  M: Stadimeter  Arrows: Train",
+            zoom, centre
+        )];
+        let skip = log.entries.len().saturating_sub(LOG_LINES);
+        lines.extend(log.entries.iter().skip(skip).map(|report| {
+            format!(
+                "{}: bearing {:03.0}, range {:.0} m",
+                report.name, report.bearing, report.range
+            )
+        }));
+        text.0 = lines.join("\n");
+    }
+}
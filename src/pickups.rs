@@ -0,0 +1,274 @@
+use crate::magnetics::Wreck;
+use crate::repair::Inventory;
+use crate::tables::Tables;
+use crate::{AppState, GameMode, GameSet, GameState, Submarine, WorldSeed};
+use bevy::prelude::*;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+const SEA_FLOOR: f32 = -20.5;
+const PICKUP_DISTANCE: f32 = 2.5;
+const CANISTER_OXYGEN: f32 = 30.0;
+const RESPAWN_CLEARANCE: f32 = 40.0; // Replacements never appear this close to the submarine
+
+/// Something the submarine collects by touching it
+#[derive(Component)]
+pub struct Pickup {
+    pub kind: PickupKind,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PickupKind {
+    Oxygen,
+    SpareParts,
+}
+
+impl PickupKind {
+    const ALL: [PickupKind; 2] = [PickupKind::Oxygen, PickupKind::SpareParts];
+
+    /// How many the population manager keeps in the world
+    fn population(self, tables: &Tables) -> usize {
+        match self {
+            PickupKind::Oxygen => tables.spawns.oxygen_canisters,
+            PickupKind::SpareParts => tables.spawns.spare_parts,
+        }
+    }
+
+    /// Seconds between replacements for collected ones
+    fn respawn_interval(self) -> f32 {
+        match self {
+            PickupKind::Oxygen => 30.0,
+            PickupKind::SpareParts => 60.0,
+        }
+    }
+
+    /// Chance one is placed at a wreck rather than on the open sea floor
+    fn wreck_share(self) -> f64 {
+        match self {
+            PickupKind::Oxygen => 0.3,
+            PickupKind::SpareParts => 0.8, // Mostly salvage
+        }
+    }
+}
+
+/// Keeps the world stocked with pickups, filling it at the start of a dive and
+/// replacing collected ones over time
+#[derive(Resource, Default)]
+struct Population {
+    filled: bool,
+    respawn_timers: [f32; PickupKind::ALL.len()], // Indexed like `PickupKind::ALL`
+}
+
+/// Random source for placement, seeded from the world so replays match
+#[derive(Resource)]
+struct PickupRng(StdRng);
+
+/// Oxygen canisters and spare-parts crates, made once
+#[derive(Resource)]
+struct PickupAssets {
+    canister_mesh: Handle<Mesh>,
+    canister_material: Handle<StandardMaterial>,
+    crate_mesh: Handle<Mesh>,
+    crate_stripe_mesh: Handle<Mesh>,
+    crate_material: Handle<StandardMaterial>,
+    crate_stripe_material: Handle<StandardMaterial>,
+}
+
+impl FromWorld for PickupAssets {
+    fn from_world(world: &mut World) -> Self {
+        let mut meshes = world.resource_mut::<Assets<Mesh>>();
+        let canister_mesh = meshes.add(Cylinder::new(0.3, 1.2));
+        let crate_mesh = meshes.add(Cuboid::new(1.0, 0.8, 1.0));
+        let crate_stripe_mesh = meshes.add(Cuboid::new(1.02, 0.2, 1.02));
+        let mut materials = world.resource_mut::<Assets<StandardMaterial>>();
+        Self {
+            canister_mesh,
+            canister_material: materials.add(StandardMaterial {
+                base_color: Color::srgb(0.3, 0.8, 1.0),
+                emissive: LinearRgba::rgb(0.5, 2.0, 3.0),
+                metallic: 0.5,
+                ..default()
+            }),
+            crate_mesh,
+            crate_stripe_mesh,
+            crate_material: materials.add(StandardMaterial {
+                base_color: Color::srgb(0.55, 0.4, 0.2),
+                perceptual_roughness: 0.9,
+                ..default()
+            }),
+            crate_stripe_material: materials.add(StandardMaterial {
+                base_color: Color::srgb(1.0, 0.8, 0.1),
+                emissive: LinearRgba::rgb(1.0, 0.8, 0.1),
+                ..default()
+            }),
+        }
+    }
+}
+
+pub struct PickupsPlugin;
+
+impl Plugin for PickupsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Population>()
+            .insert_resource(PickupRng(StdRng::seed_from_u64(0)))
+            .add_systems(OnEnter(AppState::InGame), reset_population)
+            .add_systems(
+                Update,
+                (population_system, collect_pickup_system)
+                    .chain()
+                    .in_set(GameSet::Simulation)
+                    .run_if(|mode: Res<GameMode>| *mode != GameMode::TimeTrial),
+            );
+    }
+
+    fn finish(&self, app: &mut App) {
+        app.init_resource::<PickupAssets>();
+    }
+}
+
+fn reset_population(mut commands: Commands, seed: Res<WorldSeed>) {
+    commands.insert_resource(Population::default());
+    commands.insert_resource(PickupRng(StdRng::seed_from_u64(seed.0 ^ 0x4F58_5947)));
+}
+
+fn spawn_pickup(commands: &mut Commands, assets: &PickupAssets, kind: PickupKind, position: Vec3) {
+    match kind {
+        // Glowing canister that lights up the floor around it
+        PickupKind::Oxygen => {
+            commands
+                .spawn((
+                    Mesh3d(assets.canister_mesh.clone()),
+                    MeshMaterial3d(assets.canister_material.clone()),
+                    Transform::from_translation(position),
+                    Pickup { kind },
+                    StateScoped(AppState::InGame),
+                ))
+                .with_children(|parent| {
+                    parent.spawn((
+                        PointLight {
+                            color: Color::srgb(0.4, 0.8, 1.0),
+                            intensity: 20_000.0,
+                            range: 8.0,
+                            ..default()
+                        },
+                        Transform::from_xyz(0.0, 1.0, 0.0),
+                    ));
+                });
+        }
+        // Wooden crate with a painted stripe
+        PickupKind::SpareParts => {
+            commands
+                .spawn((
+                    Mesh3d(assets.crate_mesh.clone()),
+                    MeshMaterial3d(assets.crate_material.clone()),
+                    Transform::from_translation(position),
+                    Pickup { kind },
+                    StateScoped(AppState::InGame),
+                ))
+                .with_children(|parent| {
+                    parent.spawn((
+                        Mesh3d(assets.crate_stripe_mesh.clone()),
+                        MeshMaterial3d(assets.crate_stripe_material.clone()),
+                    ));
+                });
+        }
+    }
+}
+
+/// Spots on the open sea floor or among the debris beside one of the wrecks
+fn pickup_position(rng: &mut StdRng, kind: PickupKind, wrecks: &[(&Wreck, Vec3)]) -> Vec3 {
+    if !wrecks.is_empty() && rng.gen_bool(kind.wreck_share()) {
+        let (wreck, center) = wrecks[rng.gen_range(0..wrecks.len())];
+        let spot = wreck.beside(center, rng.gen_range(-12.0..12.0), rng.gen_range(-1.0..1.0));
+        return spot + Vec3::Y * 0.6;
+    }
+    let angle = rng.gen::<f32>() * std::f32::consts::TAU;
+    let distance = rng.gen_range(20.0..300.0);
+    Vec3::new(
+        angle.cos() * distance,
+        SEA_FLOOR + 0.6,
+        angle.sin() * distance,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn population_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    tables: Res<Tables>,
+    mut population: ResMut<Population>,
+    mut rng: ResMut<PickupRng>,
+    assets: Res<PickupAssets>,
+    pickup_query: Query<&Pickup>,
+    wreck_query: Query<(&Wreck, &Transform)>,
+    submarine_query: Query<&Transform, With<Submarine>>,
+) {
+    let wrecks: Vec<(&Wreck, Vec3)> = wreck_query
+        .iter()
+        .map(|(wreck, transform)| (wreck, transform.translation))
+        .collect();
+    let submarine = submarine_query.single().ok();
+    let fill = !population.filled;
+    population.filled = true;
+
+    for (index, kind) in PickupKind::ALL.into_iter().enumerate() {
+        let count = pickup_query
+            .iter()
+            .filter(|pickup| pickup.kind == kind)
+            .count();
+        if fill {
+            population.respawn_timers[index] = kind.respawn_interval();
+            for _ in count..kind.population(&tables) {
+                let position = pickup_position(&mut rng.0, kind, &wrecks);
+                spawn_pickup(&mut commands, &assets, kind, position);
+            }
+            continue;
+        }
+
+        if count >= kind.population(&tables) {
+            population.respawn_timers[index] = kind.respawn_interval();
+            continue;
+        }
+        population.respawn_timers[index] -= time.delta_secs();
+        if population.respawn_timers[index] > 0.0 {
+            continue;
+        }
+        let Some(submarine) = submarine else {
+            continue;
+        };
+        let position = pickup_position(&mut rng.0, kind, &wrecks);
+        if position.distance(submarine.translation) < RESPAWN_CLEARANCE {
+            // Try a different spot next frame
+            continue;
+        }
+        population.respawn_timers[index] = kind.respawn_interval();
+        spawn_pickup(&mut commands, &assets, kind, position);
+    }
+}
+
+fn collect_pickup_system(
+    mut commands: Commands,
+    mut game_state: ResMut<GameState>,
+    mut inventory: ResMut<Inventory>,
+    submarine_query: Query<&Transform, With<Submarine>>,
+    pickup_query: Query<(Entity, &Pickup, &Transform)>,
+) {
+    let Ok(submarine) = submarine_query.single() else {
+        return;
+    };
+    for (entity, pickup, transform) in pickup_query.iter() {
+        if transform.translation.distance(submarine.translation) >= PICKUP_DISTANCE {
+            continue;
+        }
+        commands.entity(entity).despawn();
+        match pickup.kind {
+            PickupKind::Oxygen => {
+                game_state.oxygen = (game_state.oxygen + CANISTER_OXYGEN).min(100.0);
+                info!("Collected an oxygen canister");
+            }
+            PickupKind::SpareParts => {
+                inventory.spare_parts += 1;
+                info!("Salvaged a crate of spare parts");
+            }
+        }
+    }
+}
@@ -0,0 +1,159 @@
+use crate::{GameAssets, GameSet};
+use bevy::{
+    diagnostic::{DiagnosticsStore, FrameTimeDiagnosticsPlugin},
+    log::tracing::span::EnteredSpan,
+    platform::time::Instant,
+    prelude::*,
+};
+use std::sync::Mutex;
+
+const TIMING_SMOOTHING: f32 = 0.1; // Weight of the newest sample in the moving average
+
+/// Smoothed per-system CPU timings shown on the debug overlay.
+/// Uses interior mutability so instrumented systems only need `Res` access and
+/// can still run in parallel.
+#[derive(Resource, Default)]
+pub struct SystemTimings {
+    entries: Mutex<Vec<TimingEntry>>,
+}
+
+struct TimingEntry {
+    name: &'static str,
+    average_ms: f32,
+    peak_ms: f32,
+}
+
+impl SystemTimings {
+    /// Opens a tracing span and records the elapsed time under `name` when dropped
+    pub fn scope(&self, name: &'static str) -> TimingScope<'_> {
+        TimingScope {
+            timings: self,
+            name,
+            start: Instant::now(),
+            _span: info_span!("system", name).entered(),
+        }
+    }
+
+    fn record(&self, name: &'static str, elapsed_ms: f32) {
+        let Ok(mut entries) = self.entries.lock() else {
+            return;
+        };
+        match entries.iter_mut().find(|entry| entry.name == name) {
+            Some(entry) => {
+                entry.average_ms += (elapsed_ms - entry.average_ms) * TIMING_SMOOTHING;
+                entry.peak_ms = entry.peak_ms.max(elapsed_ms);
+            }
+            None => entries.push(TimingEntry {
+                name,
+                average_ms: elapsed_ms,
+                peak_ms: elapsed_ms,
+            }),
+        }
+    }
+
+    fn report(&self) -> String {
+        let Ok(mut entries) = self.entries.lock() else {
+            return String::new();
+        };
+        entries.sort_by(|a, b| b.average_ms.total_cmp(&a.average_ms));
+        let mut report = String::new();
+        for entry in entries.iter_mut() {
+            report.push_str(&format!(
+                "\n{:<22} {:>6.3} ms (peak {:.3})",
+                entry.name, entry.average_ms, entry.peak_ms
+            ));
+            // Let peaks decay so old spikes don't stick forever
+            entry.peak_ms *= 0.99;
+        }
+        report
+    }
+}
+
+/// Guard returned by [`SystemTimings::scope`]
+pub struct TimingScope<'a> {
+    timings: &'a SystemTimings,
+    name: &'static str,
+    start: Instant,
+    _span: EnteredSpan,
+}
+
+impl Drop for TimingScope<'_> {
+    fn drop(&mut self) {
+        let elapsed_ms = self.start.elapsed().as_secs_f32() * 1000.0;
+        self.timings.record(self.name, elapsed_ms);
+    }
+}
+
+#[derive(Component)]
+struct ProfilerOverlay;
+
+pub struct ProfilingPlugin;
+
+impl Plugin for ProfilingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(FrameTimeDiagnosticsPlugin::default())
+            .init_resource::<SystemTimings>()
+            .add_systems(Startup, spawn_profiler_overlay)
+            .add_systems(
+                Update,
+                profiler_overlay_system.in_set(GameSet::Presentation),
+            );
+    }
+}
+
+fn spawn_profiler_overlay(mut commands: Commands, assets: Res<GameAssets>) {
+    commands.spawn((
+        Text::new(""),
+        TextFont {
+            font_size: 13.0,
+            font: assets.font.clone(),
+            ..default()
+        },
+        TextColor(Color::srgb(1.0, 0.9, 0.4)),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(20.0),
+            left: Val::Percent(40.0),
+            display: Display::None,
+            ..default()
+        },
+        BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.6)),
+        ProfilerOverlay,
+    ));
+}
+
+/// F3 toggles a breakdown of frame time and instrumented system costs
+fn profiler_overlay_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    timings: Res<SystemTimings>,
+    diagnostics: Res<DiagnosticsStore>,
+    mut overlay_query: Query<(&mut Text, &mut Node), With<ProfilerOverlay>>,
+) {
+    let Ok((mut text, mut node)) = overlay_query.single_mut() else {
+        return;
+    };
+    if keyboard_input.just_pressed(KeyCode::F3) {
+        node.display = match node.display {
+            Display::None => Display::Flex,
+            _ => Display::None,
+        };
+    }
+    if node.display == Display::None {
+        return;
+    }
+
+    let fps = diagnostics
+        .get(&FrameTimeDiagnosticsPlugin::FPS)
+        .and_then(|fps| fps.smoothed())
+        .unwrap_or(0.0);
+    let frame_ms = diagnostics
+        .get(&FrameTimeDiagnosticsPlugin::FRAME_TIME)
+        .and_then(|frame_time| frame_time.smoothed())
+        .unwrap_or(0.0);
+    **text = format!(
+        "Profiler (F3)\nFPS: {:.0}  Frame: {:.2} ms{}",
+        fps,
+        frame_ms,
+        timings.report()
+    );
+}
@@ -0,0 +1,481 @@
+use crate::jamming::vent_plume;
+use crate::magnetics::{MagneticAnomaly, Shimmer, Wreck};
+use crate::mods::LoadedMods;
+use crate::stations::{BuoyBeacon, ChargingBuoy};
+use crate::tables::{order_range, RonLoader};
+use crate::{AppState, UnderwaterRock, WorldSeed};
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Asset path of the prop definitions, relative to `assets/`
+const PROPS_PATH: &str = "props.ron";
+
+/// World props described in `assets/props.ron`: shared materials, named prop
+/// definitions, and hand-placed instances spawned at the start of every dive.
+/// World generation spawns the scattered rocks, wrecks, vents and buoys by name.
+#[derive(Asset, TypePath, Clone, Deserialize)]
+pub struct Props {
+    #[serde(default)]
+    pub materials: HashMap<String, MaterialDef>,
+    #[serde(default)]
+    pub props: HashMap<String, PartDef>,
+    #[serde(default)]
+    pub placements: Vec<Placement>,
+}
+
+impl Props {
+    /// Adds a mod's definitions, replacing any with the same name
+    pub fn extend(&mut self, other: Props) {
+        self.materials.extend(other.materials);
+        self.props.extend(other.props);
+        self.placements.extend(other.placements);
+    }
+
+    /// Puts reversed eruption timings the right way round. Returns a
+    /// description of each fix for logging.
+    pub fn validate(&mut self) -> Vec<String> {
+        let mut fixes = Vec::new();
+        for (name, part) in self.props.iter_mut() {
+            validate_part(part, name, &mut fixes);
+        }
+        fixes
+    }
+}
+
+fn validate_part(part: &mut PartDef, name: &str, fixes: &mut Vec<String>) {
+    for component in part.components.iter_mut() {
+        if let PropComponent::VentPlume { first_eruption } = component {
+            order_range(first_eruption, &format!("{name} first eruption"), fixes);
+        }
+    }
+    for child in part.children.iter_mut() {
+        validate_part(child, name, fixes);
+    }
+}
+
+impl Default for Props {
+    fn default() -> Self {
+        ron::from_str(include_str!("../assets/props.ron")).expect("built-in props.ron is valid")
+    }
+}
+
+#[derive(Clone, Deserialize)]
+pub struct MaterialDef {
+    pub color: (f32, f32, f32), // sRGB
+    #[serde(default = "opaque")]
+    pub alpha: f32, // Below 1.0 the material is blended
+    #[serde(default)]
+    pub emissive: (f32, f32, f32), // Linear, may exceed 1.0 to bloom
+    #[serde(default)]
+    pub metallic: f32,
+    #[serde(default = "rough")]
+    pub roughness: f32,
+    #[serde(default = "default_reflectance")]
+    pub reflectance: f32,
+    #[serde(default)]
+    pub unlit: bool,
+    #[serde(default)]
+    pub double_sided: bool,
+    #[serde(default)]
+    pub per_instance: bool, // Each prop gets its own copy, for materials animated per prop
+}
+
+fn opaque() -> f32 {
+    1.0
+}
+
+fn rough() -> f32 {
+    0.5
+}
+
+fn default_reflectance() -> f32 {
+    0.5
+}
+
+/// One piece of a prop; a prop is its root part, placed wherever it is spawned
+#[derive(Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PartDef {
+    pub mesh: Option<MeshDef>,
+    pub material: Option<String>,     // Name in `materials`
+    pub translation: (f32, f32, f32), // Relative to the parent part
+    pub rotation: (f32, f32, f32),    // Degrees about X, Y and Z, applied in that order
+    pub body: Option<BodyDef>,
+    pub collider: Option<ColliderDef>,
+    pub light: Option<LightDef>,
+    pub components: Vec<PropComponent>,
+    pub children: Vec<PartDef>,
+}
+
+#[derive(Clone, Copy, PartialEq, Deserialize)]
+pub enum MeshDef {
+    Sphere(f32),           // Radius
+    Cuboid(f32, f32, f32), // Full size
+    Cylinder(f32, f32),    // Radius, height
+    Cone(f32, f32),        // Base radius, height
+    Capsule(f32, f32),     // Radius, length of the straight section
+}
+
+#[derive(Clone, Copy, Deserialize)]
+pub enum BodyDef {
+    Fixed,
+    Kinematic, // Moved by its own systems, e.g. bobbing buoys
+}
+
+/// Arguments match the rapier `Collider` constructors
+#[derive(Clone, Copy, Deserialize)]
+pub enum ColliderDef {
+    Ball(f32),             // Radius
+    Cuboid(f32, f32, f32), // Half extents
+    Cylinder(f32, f32),    // Half height, radius
+    Cone(f32, f32),        // Half height, radius
+}
+
+#[derive(Clone, Copy, Deserialize)]
+pub struct LightDef {
+    pub color: (f32, f32, f32),
+    pub intensity: f32,
+    pub range: f32,
+}
+
+/// Gameplay components a part can carry
+#[derive(Clone, Copy, Deserialize)]
+pub enum PropComponent {
+    UnderwaterRock,
+    Wreck, // The prop's yaw is the heading of the hull
+    MagneticAnomaly { radius: f32 },
+    Shimmer,
+    VentPlume { first_eruption: (f32, f32) }, // Range of seconds until the first one
+    ChargingBuoy,
+    BuoyBeacon,
+}
+
+/// A prop placed by hand rather than by world generation
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Placement {
+    pub prop: String,
+    pub translation: (f32, f32, f32),
+    #[serde(default)]
+    pub yaw: f32, // Degrees
+    #[serde(default = "unit_scale")]
+    pub scale: (f32, f32, f32),
+}
+
+impl Placement {
+    pub fn transform(&self) -> Transform {
+        let (x, y, z) = self.translation;
+        let (sx, sy, sz) = self.scale;
+        Transform::from_xyz(x, y, z)
+            .with_rotation(Quat::from_rotation_y(self.yaw.to_radians()))
+            .with_scale(Vec3::new(sx, sy, sz))
+    }
+}
+
+fn unit_scale() -> (f32, f32, f32) {
+    (1.0, 1.0, 1.0)
+}
+
+/// The loaded definitions, with the shared materials built from them
+#[derive(Resource, Default)]
+pub struct PropLibrary {
+    props: Props,
+    materials: HashMap<String, Handle<StandardMaterial>>,
+    meshes: Vec<(MeshDef, Handle<Mesh>)>, // One per shape, shared by every part using it
+}
+
+/// Strong handle to `props.ron`; without it the definitions would be unloaded
+/// as soon as they were copied into the library
+#[derive(Resource)]
+struct PropsHandle(Handle<Props>);
+
+pub struct PropsPlugin;
+
+impl Plugin for PropsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<Props>()
+            .init_asset_loader::<RonLoader<Props>>()
+            .init_resource::<PropLibrary>()
+            .add_systems(Startup, load_props)
+            .add_systems(OnEnter(AppState::InGame), spawn_placements)
+            .add_systems(Update, apply_props_system);
+    }
+}
+
+fn load_props(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mods: Res<LoadedMods>,
+    mut library: ResMut<PropLibrary>,
+) {
+    mods.apply_props(&mut library.props);
+    warn_fixes(&mut library.props);
+    commands.insert_resource(PropsHandle(asset_server.load(PROPS_PATH)));
+}
+
+/// Swaps in the file's definitions when it loads, and whenever it is edited in
+/// builds with the `dev` feature; the next dive uses them
+fn apply_props_system(
+    mut events: EventReader<AssetEvent<Props>>,
+    handle: Res<PropsHandle>,
+    assets: Res<Assets<Props>>,
+    mods: Res<LoadedMods>,
+    mut library: ResMut<PropLibrary>,
+) {
+    for event in events.read() {
+        let (AssetEvent::LoadedWithDependencies { id } | AssetEvent::Modified { id }) = *event
+        else {
+            continue;
+        };
+        if id != handle.0.id() {
+            continue;
+        }
+        if let Some(loaded) = assets.get(id) {
+            library.props = loaded.clone();
+            mods.apply_props(&mut library.props);
+            warn_fixes(&mut library.props);
+            library.materials.clear();
+            library.meshes.clear();
+            info!("Loaded prop definitions from {}", PROPS_PATH);
+        }
+    }
+}
+
+fn warn_fixes(props: &mut Props) {
+    for fix in props.validate() {
+        warn!("{}: {}", PROPS_PATH, fix);
+    }
+}
+
+fn spawn_placements(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut library: ResMut<PropLibrary>,
+    seed: Res<WorldSeed>,
+) {
+    let mut rng = StdRng::seed_from_u64(seed.0 ^ 0x5052_4F50);
+    for placement in library.props.placements.clone() {
+        library.spawn(
+            &mut commands,
+            &mut meshes,
+            &mut materials,
+            &mut rng,
+            &placement.prop,
+            placement.transform(),
+        );
+    }
+}
+
+impl PropLibrary {
+    /// Names of every defined prop, sorted
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.props.props.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Spawns the named prop at `transform`; unknown names are logged and skipped
+    pub fn spawn(
+        &mut self,
+        commands: &mut Commands,
+        meshes: &mut Assets<Mesh>,
+        materials: &mut Assets<StandardMaterial>,
+        rng: &mut impl Rng,
+        name: &str,
+        transform: Transform,
+    ) -> Option<Entity> {
+        let Some(def) = self.props.props.get(name).cloned() else {
+            warn!("No prop named {:?} in {}", name, PROPS_PATH);
+            return None;
+        };
+        let mut context = SpawnContext {
+            library: self,
+            meshes,
+            materials,
+            rng,
+            instance: HashMap::new(),
+            yaw: transform.rotation.to_euler(EulerRot::YXZ).0,
+        };
+        let mut root = commands.spawn((transform, StateScoped(AppState::InGame)));
+        context.build(&mut root, &def);
+        Some(root.id())
+    }
+
+    fn mesh(&mut self, meshes: &mut Assets<Mesh>, def: MeshDef) -> Handle<Mesh> {
+        if let Some((_, handle)) = self.meshes.iter().find(|(shape, _)| *shape == def) {
+            return handle.clone();
+        }
+        let handle = match def {
+            MeshDef::Sphere(radius) => meshes.add(Sphere::new(radius)),
+            MeshDef::Cuboid(x, y, z) => meshes.add(Cuboid::new(x, y, z)),
+            MeshDef::Cylinder(radius, height) => meshes.add(Cylinder::new(radius, height)),
+            MeshDef::Cone(radius, height) => meshes.add(Cone::new(radius, height)),
+            MeshDef::Capsule(radius, length) => meshes.add(Capsule3d::new(radius, length)),
+        };
+        self.meshes.push((def, handle.clone()));
+        handle
+    }
+
+    fn material(
+        &mut self,
+        materials: &mut Assets<StandardMaterial>,
+        instance: &mut HashMap<String, Handle<StandardMaterial>>,
+        name: &str,
+    ) -> Option<Handle<StandardMaterial>> {
+        if let Some(handle) = self.materials.get(name).or_else(|| instance.get(name)) {
+            return Some(handle.clone());
+        }
+        let Some(def) = self.props.materials.get(name) else {
+            warn!("No material named {:?} in {}", name, PROPS_PATH);
+            return None;
+        };
+        let (red, green, blue) = def.color;
+        let (er, eg, eb) = def.emissive;
+        let handle = materials.add(StandardMaterial {
+            base_color: Color::srgba(red, green, blue, def.alpha),
+            emissive: LinearRgba::rgb(er, eg, eb),
+            metallic: def.metallic,
+            perceptual_roughness: def.roughness,
+            reflectance: def.reflectance,
+            alpha_mode: if def.alpha < 1.0 {
+                AlphaMode::Blend
+            } else {
+                AlphaMode::Opaque
+            },
+            unlit: def.unlit,
+            cull_mode: if def.double_sided {
+                None
+            } else {
+                Some(bevy::render::render_resource::Face::Back)
+            },
+            double_sided: def.double_sided,
+            ..default()
+        });
+        let cache = if def.per_instance {
+            instance
+        } else {
+            &mut self.materials
+        };
+        cache.insert(name.to_string(), handle.clone());
+        Some(handle)
+    }
+}
+
+/// State for building one prop
+struct SpawnContext<'a, R: Rng> {
+    library: &'a mut PropLibrary,
+    meshes: &'a mut Assets<Mesh>,
+    materials: &'a mut Assets<StandardMaterial>,
+    rng: &'a mut R,
+    instance: HashMap<String, Handle<StandardMaterial>>, // Per-instance materials
+    yaw: f32,
+}
+
+impl<R: Rng> SpawnContext<'_, R> {
+    fn build(&mut self, entity: &mut EntityCommands, part: &PartDef) {
+        let material = part.material.as_ref().and_then(|name| {
+            self.library
+                .material(self.materials, &mut self.instance, name)
+        });
+        if let Some(mesh) = part.mesh {
+            entity.insert(Mesh3d(self.library.mesh(self.meshes, mesh)));
+        }
+        if let Some(material) = &material {
+            entity.insert(MeshMaterial3d(material.clone()));
+        }
+        match part.body {
+            Some(BodyDef::Fixed) => {
+                entity.insert(RigidBody::Fixed);
+            }
+            Some(BodyDef::Kinematic) => {
+                entity.insert(RigidBody::KinematicPositionBased);
+            }
+            None => {}
+        }
+        if let Some(collider) = part.collider {
+            entity.insert(match collider {
+                ColliderDef::Ball(radius) => Collider::ball(radius),
+                ColliderDef::Cuboid(x, y, z) => Collider::cuboid(x, y, z),
+                ColliderDef::Cylinder(half_height, radius) => {
+                    Collider::cylinder(half_height, radius)
+                }
+                ColliderDef::Cone(half_height, radius) => Collider::cone(half_height, radius),
+            });
+        }
+        if let Some(light) = part.light {
+            let (red, green, blue) = light.color;
+            entity.insert(PointLight {
+                color: Color::srgb(red, green, blue),
+                intensity: light.intensity,
+                range: light.range,
+                ..default()
+            });
+        }
+        for component in &part.components {
+            self.insert_component(entity, *component, material.as_ref());
+        }
+        if part.mesh.is_none() && part.light.is_none() {
+            // Grouping parts still need visibility so their children render
+            entity.insert(Visibility::default());
+        }
+        entity.with_children(|parent| {
+            for child in &part.children {
+                let (x, y, z) = child.translation;
+                let (rx, ry, rz) = child.rotation;
+                let transform = Transform::from_xyz(x, y, z).with_rotation(Quat::from_euler(
+                    EulerRot::XYZ,
+                    rx.to_radians(),
+                    ry.to_radians(),
+                    rz.to_radians(),
+                ));
+                self.build(&mut parent.spawn(transform), child);
+            }
+        });
+    }
+
+    fn insert_component(
+        &mut self,
+        entity: &mut EntityCommands,
+        component: PropComponent,
+        material: Option<&Handle<StandardMaterial>>,
+    ) {
+        match component {
+            PropComponent::UnderwaterRock => {
+                entity.insert(UnderwaterRock);
+            }
+            PropComponent::Wreck => {
+                entity.insert(Wreck { heading: self.yaw });
+            }
+            PropComponent::MagneticAnomaly { radius } => {
+                entity.insert(MagneticAnomaly { radius });
+            }
+            PropComponent::Shimmer => {
+                if let Some(material) = material {
+                    entity.insert(Shimmer {
+                        material: material.clone(),
+                        phase: self.rng.gen::<f32>() * std::f32::consts::TAU,
+                    });
+                }
+            }
+            PropComponent::VentPlume { first_eruption } => {
+                let first_eruption = self.rng.gen_range(first_eruption.0..=first_eruption.1);
+                entity.with_child(vent_plume(first_eruption));
+            }
+            PropComponent::ChargingBuoy => {
+                entity.insert(ChargingBuoy {
+                    phase: self.rng.gen::<f32>() * std::f32::consts::TAU,
+                });
+            }
+            PropComponent::BuoyBeacon => {
+                if let Some(material) = material {
+                    entity.insert(BuoyBeacon {
+                        material: material.clone(),
+                    });
+                }
+            }
+        }
+    }
+}
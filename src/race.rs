@@ -0,0 +1,519 @@
+use crate::replay::{Replay, ReplayRecorder};
+use crate::{storage, AppState, GameAssets, GameMode, GameSet, Submarine};
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// World seed for the time-trial course, so terrain is identical on every run
+pub const COURSE_SEED: u64 = 0x5EA_F100D;
+const GHOST_PATH: &str = "replays/time-trial-best.ron";
+const RECORD_PATH: &str = "time-trial.ron";
+const GATE_RADIUS: f32 = 5.0; // Inner opening the submarine has to pass through
+const GATE_THICKNESS: f32 = 0.4;
+const COUNTDOWN: f32 = 3.0; // Seconds the submarine is held at the start line
+const SPLIT_DISPLAY_TIME: f32 = 3.0; // How long the latest split stays under the timer
+const SEA_FLOOR: f32 = -20.5;
+
+/// Gate centers in course order; each gate faces the direction from the previous one
+const COURSE: [Vec3; 7] = [
+    Vec3::new(0.0, -4.0, -40.0),
+    Vec3::new(30.0, -8.0, -90.0),
+    Vec3::new(80.0, -14.0, -110.0),
+    Vec3::new(120.0, -6.0, -70.0),
+    Vec3::new(110.0, -14.0, -10.0),
+    Vec3::new(60.0, -14.0, 30.0),
+    Vec3::new(10.0, -5.0, 20.0),
+];
+
+/// Rock formations built around gates as (gate index, length along the course).
+/// Long ones are caves with a roof; short ones read as arches.
+const FORMATIONS: [(usize, f32); 4] = [(2, 28.0), (3, 4.0), (4, 4.0), (5, 18.0)];
+
+const GATE_NEXT: Color = Color::srgb(0.2, 1.0, 0.4);
+const GATE_AHEAD: Color = Color::srgb(1.0, 0.8, 0.2);
+const GATE_PASSED: Color = Color::srgba(0.4, 0.4, 0.4, 0.4);
+
+/// A ring on the course, numbered in the order it has to be passed
+#[derive(Component)]
+pub struct Gate(usize);
+
+#[derive(Component)]
+struct Ghost;
+
+#[derive(Component)]
+struct RaceText;
+
+#[derive(Component)]
+struct SplitsText;
+
+/// Fastest finish on the course and the gate times it was set with
+#[derive(Clone, Default, Serialize, Deserialize)]
+struct CourseRecord {
+    time: f32,
+    splits: Vec<f32>, // Race time at each gate
+}
+
+/// Progress through the course for the current run
+#[derive(Resource, Default)]
+struct RaceState {
+    clock: f32, // Time since the dive started, countdown included
+    next_gate: usize,
+    splits: Vec<f32>,
+    finished: Option<f32>,
+    record: Option<CourseRecord>,
+    last_position: Option<Vec3>,
+}
+
+impl RaceState {
+    /// Race time, which starts when the countdown ends
+    fn elapsed(&self) -> f32 {
+        (self.clock - COUNTDOWN).max(0.0)
+    }
+
+    fn started(&self) -> bool {
+        self.clock >= COUNTDOWN
+    }
+}
+
+/// Best run so far, replayed as a ghost submarine
+#[derive(Resource, Default)]
+struct GhostRun {
+    poses: Vec<(f32, Vec3, f32)>, // (time since start, position, yaw)
+}
+
+/// Gates, rock formations and the ghost, made once
+#[derive(Resource)]
+struct CourseAssets {
+    gate_mesh: Handle<Mesh>,
+    gate_ahead_material: Handle<StandardMaterial>,
+    gate_next_material: Handle<StandardMaterial>,
+    gate_passed_material: Handle<StandardMaterial>,
+    block_mesh: Handle<Mesh>, // Unit cube
+    formation_material: Handle<StandardMaterial>,
+    ghost_mesh: Handle<Mesh>,
+    ghost_material: Handle<StandardMaterial>,
+}
+
+impl FromWorld for CourseAssets {
+    fn from_world(world: &mut World) -> Self {
+        let mut meshes = world.resource_mut::<Assets<Mesh>>();
+        let gate_mesh = meshes.add(Torus::new(GATE_RADIUS, GATE_RADIUS + GATE_THICKNESS));
+        let block_mesh = meshes.add(Cuboid::new(1.0, 1.0, 1.0));
+        let ghost_mesh = meshes.add(Capsule3d::new(0.7, 4.0));
+        let mut materials = world.resource_mut::<Assets<StandardMaterial>>();
+        let mut gate_material = |color: Color| {
+            materials.add(StandardMaterial {
+                base_color: color,
+                emissive: color.into(),
+                alpha_mode: AlphaMode::Blend,
+                ..default()
+            })
+        };
+        let gate_ahead_material = gate_material(GATE_AHEAD);
+        let gate_next_material = gate_material(GATE_NEXT);
+        let gate_passed_material = gate_material(GATE_PASSED);
+        Self {
+            gate_mesh,
+            gate_ahead_material,
+            gate_next_material,
+            gate_passed_material,
+            block_mesh,
+            formation_material: materials.add(StandardMaterial {
+                base_color: Color::srgb(0.35, 0.32, 0.28),
+                perceptual_roughness: 0.95,
+                reflectance: 0.02,
+                ..default()
+            }),
+            ghost_mesh,
+            ghost_material: materials.add(StandardMaterial {
+                base_color: Color::srgba(0.6, 0.8, 1.0, 0.35),
+                alpha_mode: AlphaMode::Blend,
+                unlit: true,
+                ..default()
+            }),
+        }
+    }
+}
+
+pub struct RacePlugin;
+
+impl Plugin for RacePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RaceState>()
+            .init_resource::<GhostRun>()
+            .add_systems(
+                OnEnter(AppState::InGame),
+                spawn_course.run_if(resource_equals(GameMode::TimeTrial)),
+            )
+            .add_systems(
+                Update,
+                (
+                    (hold_at_start_system, gate_crossing_system)
+                        .chain()
+                        .after(crate::submarine_movement)
+                        .in_set(GameSet::Simulation),
+                    (
+                        ghost_system,
+                        gate_color_system,
+                        race_text_system,
+                        splits_text_system,
+                    )
+                        .in_set(GameSet::Presentation),
+                )
+                    .run_if(resource_equals(GameMode::TimeTrial)),
+            );
+    }
+
+    fn finish(&self, app: &mut App) {
+        app.init_resource::<CourseAssets>();
+    }
+}
+
+/// Direction a gate must be crossed in: from the previous gate (or the start) towards it
+fn gate_normal(index: usize) -> Vec3 {
+    let previous = if index == 0 {
+        Vec3::ZERO
+    } else {
+        COURSE[index - 1]
+    };
+    (COURSE[index] - previous).normalize()
+}
+
+fn spawn_course(mut commands: Commands, assets: Res<GameAssets>, course: Res<CourseAssets>) {
+    commands.insert_resource(RaceState {
+        record: load_record(),
+        ..default()
+    });
+
+    for (index, center) in COURSE.iter().enumerate() {
+        commands.spawn((
+            Mesh3d(course.gate_mesh.clone()),
+            MeshMaterial3d(course.gate_ahead_material.clone()),
+            // The torus lies in the XZ plane, so its axis (Y) is turned to face along the course
+            Transform::from_translation(*center)
+                .with_rotation(Quat::from_rotation_arc(Vec3::Y, gate_normal(index))),
+            Gate(index),
+            StateScoped(AppState::InGame),
+        ));
+    }
+
+    for (gate, length) in FORMATIONS {
+        spawn_formation(&mut commands, &course, gate, length);
+    }
+
+    let poses = load_ghost().map(ghost_poses).unwrap_or_default();
+    if !poses.is_empty() {
+        commands.spawn((
+            Mesh3d(course.ghost_mesh.clone()),
+            MeshMaterial3d(course.ghost_material.clone()),
+            Transform::from_translation(poses[0].1),
+            Ghost,
+            StateScoped(AppState::InGame),
+        ));
+    }
+    commands.insert_resource(GhostRun { poses });
+
+    let font = assets.font.clone();
+    commands.spawn((
+        Text::new(""),
+        TextFont {
+            font_size: 24.0,
+            font: font.clone(),
+            ..default()
+        },
+        TextColor(Color::WHITE),
+        TextLayout::new_with_justify(JustifyText::Center),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(16.0),
+            width: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        RaceText,
+        StateScoped(AppState::InGame),
+    ));
+    commands.spawn((
+        Text::new(""),
+        TextFont {
+            font_size: 16.0,
+            font,
+            ..default()
+        },
+        TextColor(Color::WHITE),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(16.0),
+            right: Val::Px(16.0),
+            ..default()
+        },
+        SplitsText,
+        StateScoped(AppState::InGame),
+    ));
+}
+
+/// Rock walls on both sides of a gate, standing on the sea floor, with a roof
+/// over the top so the gate is only reachable by flying through the formation
+fn spawn_formation(commands: &mut Commands, assets: &CourseAssets, gate: usize, length: f32) {
+    let center = COURSE[gate];
+    let direction = gate_normal(gate).with_y(0.0).normalize();
+    // Local space: X across the course, Y up, Z along it
+    let rotation = Quat::from_rotation_arc(Vec3::Z, direction);
+    let wall_thickness = 3.0;
+    let roof_thickness = 3.0;
+    let half_width = GATE_RADIUS + 1.5;
+    let ceiling = center.y + GATE_RADIUS + 1.5;
+    let wall_height = ceiling - SEA_FLOOR;
+
+    // Unit blocks scaled to size; the collider is scaled along with them
+    let mut spawn_block = |offset: Vec3, size: Vec3| {
+        commands.spawn((
+            Mesh3d(assets.block_mesh.clone()),
+            MeshMaterial3d(assets.formation_material.clone()),
+            Transform::from_translation(center.with_y(0.0) + rotation * offset)
+                .with_rotation(rotation)
+                .with_scale(size),
+            RigidBody::Fixed,
+            Collider::cuboid(0.5, 0.5, 0.5),
+            StateScoped(AppState::InGame),
+        ));
+    };
+    for side in [-1.0, 1.0] {
+        spawn_block(
+            Vec3::new(
+                side * (half_width + wall_thickness / 2.0),
+                SEA_FLOOR + wall_height / 2.0,
+                0.0,
+            ),
+            Vec3::new(wall_thickness, wall_height, length),
+        );
+    }
+    spawn_block(
+        Vec3::new(0.0, ceiling + roof_thickness / 2.0, 0.0),
+        Vec3::new(2.0 * (half_width + wall_thickness), roof_thickness, length),
+    );
+}
+
+fn load_record() -> Option<CourseRecord> {
+    ron::from_str(&storage::read(RECORD_PATH)?).ok()
+}
+
+fn save_record(record: &CourseRecord) {
+    let result = ron::ser::to_string_pretty(record, ron::ser::PrettyConfig::default())
+        .map_err(|err| err.to_string())
+        .and_then(|contents| storage::write(RECORD_PATH, &contents));
+    if let Err(err) = result {
+        warn!("Failed to save course record: {}", err);
+    }
+}
+
+fn load_ghost() -> Option<Replay> {
+    let replay = Replay::load(GHOST_PATH).ok()?;
+    (replay.settings.mode == GameMode::TimeTrial && replay.seed == COURSE_SEED).then_some(replay)
+}
+
+/// Turns recorded frames into a timeline of poses
+fn ghost_poses(replay: Replay) -> Vec<(f32, Vec3, f32)> {
+    let mut time = 0.0;
+    replay
+        .frames
+        .iter()
+        .map(|frame| {
+            let pose = (time, Vec3::from_array(frame.position), frame.yaw);
+            time += Duration::from_nanos(frame.delta_ns).as_secs_f32();
+            pose
+        })
+        .collect()
+}
+
+/// Keeps the submarine on the start line until the countdown runs out
+fn hold_at_start_system(
+    time: Res<Time>,
+    mut race: ResMut<RaceState>,
+    mut submarine_query: Query<&mut Velocity, With<Submarine>>,
+) {
+    race.clock += time.delta_secs();
+    if race.started() {
+        return;
+    }
+    if let Ok(mut velocity) = submarine_query.single_mut() {
+        *velocity = Velocity::zero();
+    }
+}
+
+/// Counts a gate when the submarine crosses its plane inside the ring, in course order
+fn gate_crossing_system(
+    mut race: ResMut<RaceState>,
+    recorder: Res<ReplayRecorder>,
+    submarine_query: Query<&Transform, With<Submarine>>,
+) {
+    let Ok(transform) = submarine_query.single() else {
+        return;
+    };
+    let position = transform.translation;
+    let last_position = race.last_position.replace(position);
+    if race.finished.is_some() || !race.started() {
+        return;
+    }
+
+    let Some(last_position) = last_position else {
+        return;
+    };
+    let center = COURSE[race.next_gate];
+    let normal = gate_normal(race.next_gate);
+    let before = (last_position - center).dot(normal);
+    let after = (position - center).dot(normal);
+    if before >= 0.0 || after < 0.0 {
+        return;
+    }
+    // Where the path pierces the gate plane
+    let crossing = last_position.lerp(position, before / (before - after));
+    if crossing.distance(center) > GATE_RADIUS {
+        return;
+    }
+
+    let elapsed = race.elapsed();
+    race.splits.push(elapsed);
+    race.next_gate += 1;
+    if race.next_gate < COURSE.len() {
+        return;
+    }
+    race.finished = Some(elapsed);
+    info!("Course finished in {:.2} s", elapsed);
+    if race
+        .record
+        .as_ref()
+        .is_some_and(|best| best.time <= elapsed)
+    {
+        return;
+    }
+    let record = CourseRecord {
+        time: elapsed,
+        splits: race.splits.clone(),
+    };
+    save_record(&record);
+    race.record = Some(record);
+    if let Some(replay) = recorder.replay.as_ref() {
+        match replay.save(GHOST_PATH) {
+            Ok(()) => info!("New best run saved as the ghost"),
+            Err(err) => warn!("Failed to save ghost run: {}", err),
+        }
+    }
+}
+
+/// Moves the ghost along the best run, interpolating between recorded frames
+fn ghost_system(
+    race: Res<RaceState>,
+    ghost_run: Res<GhostRun>,
+    mut ghost_query: Query<(&mut Transform, &mut Visibility), With<Ghost>>,
+) {
+    let Ok((mut transform, mut visibility)) = ghost_query.single_mut() else {
+        return;
+    };
+    let poses = &ghost_run.poses;
+    // The recording covers the countdown too, so both runs share the same clock
+    let index = poses.partition_point(|(time, _, _)| *time <= race.clock);
+    if index >= poses.len() {
+        // The ghost has finished its run
+        *visibility = Visibility::Hidden;
+        return;
+    }
+    *visibility = Visibility::Inherited;
+    let (time_b, position_b, yaw_b) = poses[index];
+    let (time_a, position_a, yaw_a) = poses[index.saturating_sub(1)];
+    let t = if time_b > time_a {
+        (race.clock - time_a) / (time_b - time_a)
+    } else {
+        1.0
+    };
+    transform.translation = position_a.lerp(position_b, t.clamp(0.0, 1.0));
+    // The capsule's long axis is Y; lay it along the submarine's heading
+    transform.rotation = Quat::from_rotation_y(yaw_a + (yaw_b - yaw_a) * t)
+        * Quat::from_rotation_x(std::f32::consts::FRAC_PI_2);
+}
+
+fn gate_color_system(
+    race: Res<RaceState>,
+    assets: Res<CourseAssets>,
+    mut gate_query: Query<(&Gate, &mut MeshMaterial3d<StandardMaterial>)>,
+) {
+    if !race.is_changed() {
+        return;
+    }
+    for (gate, mut material) in gate_query.iter_mut() {
+        let wanted = match gate.0.cmp(&race.next_gate) {
+            std::cmp::Ordering::Less => &assets.gate_passed_material,
+            std::cmp::Ordering::Equal => &assets.gate_next_material,
+            std::cmp::Ordering::Greater => &assets.gate_ahead_material,
+        };
+        if material.0 != *wanted {
+            material.0 = wanted.clone();
+        }
+    }
+}
+
+/// Difference to the record's time at the same gate, e.g. "-0.42"
+fn split_delta(race: &RaceState, gate: usize, time: f32) -> Option<String> {
+    let best = race.record.as_ref()?.splits.get(gate)?;
+    Some(format!("{:+.2}", time - best))
+}
+
+fn race_text_system(race: Res<RaceState>, mut text_query: Query<&mut Text, With<RaceText>>) {
+    let Ok(mut text) = text_query.single_mut() else {
+        return;
+    };
+    let best = race
+        .record
+        .as_ref()
+        .map(|record| format!("Best {:.2} s", record.time))
+        .unwrap_or_else(|| "No best time yet".to_string());
+
+    if !race.started() {
+        **text = format!("{}\n{}", (COUNTDOWN - race.clock).ceil(), best);
+        return;
+    }
+    let mut status = match race.finished {
+        Some(time) => format!("Finished in {:.2} s   {}", time, best),
+        None => format!(
+            "Gate {}/{}   {:.2} s   {}",
+            race.next_gate + 1,
+            COURSE.len(),
+            race.elapsed(),
+            best
+        ),
+    };
+    // Flash the latest split (or GO! off the line) under the timer
+    let last_split = race
+        .splits
+        .len()
+        .checked_sub(1)
+        .map(|gate| (gate, race.splits[gate]));
+    match last_split {
+        Some((gate, time)) if race.elapsed() - time < SPLIT_DISPLAY_TIME => {
+            status += &format!("\nGate {}  {:.2} s", gate + 1, time);
+            if let Some(delta) = split_delta(&race, gate, time) {
+                status += &format!("  ({})", delta);
+            }
+        }
+        None if race.elapsed() < 1.0 => status += "\nGO!",
+        _ => {}
+    }
+    **text = status;
+}
+
+/// Every gate time so far, compared with the record's splits
+fn splits_text_system(race: Res<RaceState>, mut text_query: Query<&mut Text, With<SplitsText>>) {
+    if !race.is_changed() {
+        return;
+    }
+    let Ok(mut text) = text_query.single_mut() else {
+        return;
+    };
+    let mut lines = vec!["Splits".to_string()];
+    for (gate, time) in race.splits.iter().enumerate() {
+        let delta = split_delta(&race, gate, *time).unwrap_or_default();
+        lines.push(format!("{}  {:>7.2}  {}", gate + 1, time, delta));
+    }
+    **text = lines.join("\n");
+}
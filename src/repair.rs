@@ -0,0 +1,65 @@
+use crate::controls::ControlCommand;
+use crate::{AppState, CampaignProgress, GameSet, GameState, NewGamePlus};
+use bevy::prelude::*;
+
+const REPAIR_AMOUNT: f32 = 25.0; // Hull health restored by one set of spare parts
+const STARTING_SPARE_PARTS: u32 = 1;
+
+/// Salvaged items carried aboard
+#[derive(Resource)]
+pub struct Inventory {
+    pub spare_parts: u32,
+    pub upgrade_parts: u32,
+}
+
+impl Default for Inventory {
+    fn default() -> Self {
+        Self {
+            spare_parts: STARTING_SPARE_PARTS,
+            upgrade_parts: 0,
+        }
+    }
+}
+
+pub struct RepairPlugin;
+
+impl Plugin for RepairPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Inventory>()
+            .add_systems(OnEnter(AppState::InGame), reset_inventory)
+            .add_systems(Update, repair_command_system.in_set(GameSet::Simulation));
+    }
+}
+
+/// A fresh boat, except that New Game Plus brings the upgrade parts banked at
+/// the end of the last campaign
+fn reset_inventory(mut commands: Commands, new_game_plus: Res<NewGamePlus>) {
+    let upgrade_parts = if new_game_plus.active() {
+        CampaignProgress::load().upgrade_parts
+    } else {
+        0
+    };
+    commands.insert_resource(Inventory {
+        upgrade_parts,
+        ..default()
+    });
+}
+
+/// Spends one set of spare parts to patch up the hull
+fn repair_command_system(
+    mut control_commands: EventReader<ControlCommand>,
+    mut inventory: ResMut<Inventory>,
+    mut game_state: ResMut<GameState>,
+) {
+    for command in control_commands.read() {
+        if *command != ControlCommand::RepairHull {
+            continue;
+        }
+        if inventory.spare_parts == 0 || game_state.health >= 100.0 {
+            continue;
+        }
+        inventory.spare_parts -= 1;
+        game_state.health = (game_state.health + REPAIR_AMOUNT).min(100.0);
+        info!("Hull repaired, {} spare parts left", inventory.spare_parts);
+    }
+}
@@ -0,0 +1,376 @@
+//! Replay recording and playback.
+//!
+//! Every new dive records the helm input, control commands and frame times it
+//! was played with. Because the world is rebuilt from the seed and the tuning
+//! values, feeding the same input stream back with the same frame times
+//! reproduces the dive. The on-disk format is described in the README
+//! ("Replay Format") and is versioned independently of the crate.
+
+use crate::classes::SubmarineClass;
+use crate::controls::{ControlCommand, HelmInput};
+use crate::difficulty::Difficulty;
+use crate::save::PendingLoad;
+use crate::tuning::{Tuning, TuningOverrides};
+use crate::{storage, AppState, GameMode, GameSet, NewGamePlus, Submarine, WorldSeed};
+use bevy::{prelude::*, time::TimeUpdateStrategy};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Identifies replay files regardless of their extension
+const REPLAY_FORMAT: &str = "submarine-replay";
+/// Bumped when the file's schema changes. It does not track the simulation, so a
+/// replay from an older build can still drift on playback; that shows as a desync
+pub const REPLAY_VERSION: u32 = 1;
+const REPLAY_DIR: &str = "replays";
+const DESYNC_TOLERANCE: f32 = 1.0; // Meters of drift before playback warns about a desync
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Replay {
+    pub format: String,
+    pub version: u32,
+    pub game_version: String, // Crate version that recorded the replay, for bug reports
+    pub seed: u64,
+    pub settings: ReplaySettings,
+    pub frames: Vec<ReplayFrame>,
+}
+
+/// Gameplay tuning the dive was recorded with
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ReplaySettings {
+    pub fish_count: usize,
+    pub oxygen_rate: f32,
+    pub buoyancy_scale: f32,
+    pub start_depth: f32,
+    #[serde(default)]
+    pub mode: GameMode, // Absent in replays recorded before game modes existed
+    #[serde(default = "ReplaySettings::as_tuned")]
+    pub difficulty: f32, // Adaptive difficulty level the dive was played at
+    #[serde(default)]
+    pub new_game_plus: u32, // New Game Plus cycle; 0 for a first run
+    #[serde(default)]
+    pub class: SubmarineClass, // Replays from before there were classes used the patrol boat
+}
+
+/// Input for one simulated frame
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReplayFrame {
+    pub delta_ns: u64, // Frame time in nanoseconds
+    pub throttle: f32,
+    pub turn: f32,
+    pub camera_yaw: f32,
+    pub camera_pitch: f32,
+    pub ballast: f32,
+    pub planes: f32,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub commands: Vec<ControlCommand>,
+    pub position: [f32; 3], // Submarine position before the frame's input is applied
+    pub yaw: f32,
+}
+
+impl ReplaySettings {
+    fn new(
+        tuning: &Tuning,
+        mode: GameMode,
+        difficulty: Difficulty,
+        new_game_plus: NewGamePlus,
+        class: SubmarineClass,
+    ) -> Self {
+        Self {
+            fish_count: tuning.fish_count,
+            oxygen_rate: tuning.oxygen_rate,
+            buoyancy_scale: tuning.buoyancy_scale,
+            start_depth: tuning.start_depth,
+            mode,
+            difficulty: difficulty.0,
+            new_game_plus: new_game_plus.0,
+            class,
+        }
+    }
+
+    /// Replays recorded before adaptive difficulty played the game as tuned
+    fn as_tuned() -> f32 {
+        1.0
+    }
+}
+
+impl From<ReplaySettings> for TuningOverrides {
+    fn from(settings: ReplaySettings) -> Self {
+        Self {
+            fish_count: Some(settings.fish_count),
+            oxygen_rate: Some(settings.oxygen_rate),
+            buoyancy_scale: Some(settings.buoyancy_scale),
+            start_depth: Some(settings.start_depth),
+        }
+    }
+}
+
+impl Replay {
+    /// Reads and checks a replay file
+    pub fn load(path: &str) -> Result<Self, String> {
+        let contents = storage::read(path).ok_or_else(|| format!("cannot read {}", path))?;
+        let replay: Replay = ron::from_str(&contents).map_err(|err| err.to_string())?;
+        if replay.format != REPLAY_FORMAT {
+            return Err(format!("{} is not a replay file", path));
+        }
+        if replay.version > REPLAY_VERSION {
+            return Err(format!(
+                "{} uses replay format version {}, but this build only supports up to {}",
+                path, replay.version, REPLAY_VERSION
+            ));
+        }
+        Ok(replay)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let pretty = ron::ser::PrettyConfig::default().compact_arrays(true);
+        let contents = ron::ser::to_string_pretty(self, pretty).map_err(|err| err.to_string())?;
+        storage::write(path, &contents)
+    }
+
+    /// Seconds of play covered by the recorded frames
+    pub fn duration(&self) -> f32 {
+        self.frames
+            .iter()
+            .map(|frame| Duration::from_nanos(frame.delta_ns).as_secs_f32())
+            .sum()
+    }
+}
+
+/// The replay being recorded for the current dive, if it started from a new game
+#[derive(Resource, Default)]
+pub struct ReplayRecorder {
+    pub replay: Option<Replay>,
+}
+
+/// A replay driving the submarine instead of the player
+#[derive(Resource)]
+pub struct ReplayPlayback {
+    replay: Replay,
+    frame: usize,
+    desynced: bool,
+}
+
+/// Where to write the recording when the dive ends (`--record-replay`)
+#[derive(Resource, Default)]
+pub struct ReplayOutput(pub Option<String>);
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ReplayRecorder>()
+            .init_resource::<ReplayOutput>()
+            .add_systems(
+                OnEnter(AppState::InGame),
+                start_recording_system.before(crate::save::apply_pending_load),
+            )
+            .add_systems(OnExit(AppState::InGame), write_replay_output_system)
+            .add_systems(
+                Update,
+                (
+                    export_replay_input_system.in_set(GameSet::Input),
+                    (play_replay_system, record_replay_system)
+                        .chain()
+                        .in_set(GameSet::Replay),
+                ),
+            )
+            .add_systems(Last, write_replay_output_system.run_if(on_event::<AppExit>));
+    }
+}
+
+/// Skips the main menu and starts a dive driven by `replay`
+pub fn start_playback(app: &mut App, replay: Replay) {
+    info!(
+        "Playing replay ({} frames, {:.1} s, recorded with version {})",
+        replay.frames.len(),
+        replay.duration(),
+        replay.game_version
+    );
+    if let Some(first) = replay.frames.first() {
+        app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_nanos(
+            first.delta_ns,
+        )));
+    }
+    app.insert_resource(WorldSeed(replay.seed))
+        .insert_resource(TuningOverrides::from(replay.settings.clone()))
+        .insert_resource(replay.settings.mode)
+        .insert_resource(Difficulty(replay.settings.difficulty))
+        .insert_resource(NewGamePlus(replay.settings.new_game_plus))
+        .insert_resource(replay.settings.class.clone())
+        .insert_resource(PendingLoad(None))
+        .insert_resource(ReplayPlayback {
+            replay,
+            frame: 0,
+            desynced: false,
+        });
+    app.world_mut()
+        .resource_mut::<NextState<AppState>>()
+        .set(AppState::Loading);
+}
+
+/// New games are recorded; dives loaded from a save can't be rebuilt from the seed alone
+#[allow(clippy::too_many_arguments)]
+fn start_recording_system(
+    mut recorder: ResMut<ReplayRecorder>,
+    pending: Res<PendingLoad>,
+    playback: Option<Res<ReplayPlayback>>,
+    seed: Res<WorldSeed>,
+    tuning: Res<Tuning>,
+    mode: Res<GameMode>,
+    difficulty: Res<Difficulty>,
+    new_game_plus: Res<NewGamePlus>,
+    class: Res<SubmarineClass>,
+) {
+    recorder.replay = (pending.0.is_none() && playback.is_none()).then(|| Replay {
+        format: REPLAY_FORMAT.to_string(),
+        version: REPLAY_VERSION,
+        game_version: env!("CARGO_PKG_VERSION").to_string(),
+        seed: seed.0,
+        settings: ReplaySettings::new(&tuning, *mode, *difficulty, *new_game_plus, class.clone()),
+        frames: Vec::new(),
+    });
+}
+
+fn record_replay_system(
+    mut recorder: ResMut<ReplayRecorder>,
+    time: Res<Time>,
+    helm_input: Res<HelmInput>,
+    mut commands: EventReader<ControlCommand>,
+    submarine_query: Query<&Transform, With<Submarine>>,
+) {
+    let Some(replay) = recorder.replay.as_mut() else {
+        commands.clear();
+        return;
+    };
+    let (position, yaw) = submarine_query
+        .single()
+        .map(|transform| {
+            (
+                transform.translation.to_array(),
+                transform.rotation.to_euler(EulerRot::YXZ).0,
+            )
+        })
+        .unwrap_or_default();
+    replay.frames.push(ReplayFrame {
+        delta_ns: time.delta().as_nanos() as u64,
+        throttle: helm_input.throttle,
+        turn: helm_input.turn,
+        camera_yaw: helm_input.camera_yaw,
+        camera_pitch: helm_input.camera_pitch,
+        ballast: helm_input.ballast,
+        planes: helm_input.planes,
+        commands: commands.read().copied().collect(),
+        position,
+        yaw,
+    });
+}
+
+/// Replaces this frame's live input with the recorded frame and schedules the
+/// next frame's time step
+fn play_replay_system(
+    mut commands: Commands,
+    playback: Option<ResMut<ReplayPlayback>>,
+    mut helm_input: ResMut<HelmInput>,
+    mut control_commands: ResMut<Events<ControlCommand>>,
+    submarine_query: Query<&Transform, With<Submarine>>,
+) {
+    let Some(mut playback) = playback else {
+        return;
+    };
+    let Some(frame) = playback.replay.frames.get(playback.frame).cloned() else {
+        return;
+    };
+
+    *helm_input = HelmInput {
+        throttle: frame.throttle,
+        turn: frame.turn,
+        camera_yaw: frame.camera_yaw,
+        camera_pitch: frame.camera_pitch,
+        ballast: frame.ballast,
+        planes: frame.planes,
+    };
+    control_commands.clear();
+    for command in frame.commands {
+        control_commands.send(command);
+    }
+
+    if let Ok(transform) = submarine_query.single() {
+        let drift = transform
+            .translation
+            .distance(Vec3::from_array(frame.position));
+        if drift > DESYNC_TOLERANCE && !playback.desynced {
+            warn!(
+                "Replay desynced at frame {} ({:.1} m from the recorded position)",
+                playback.frame, drift
+            );
+            playback.desynced = true;
+        }
+    }
+
+    playback.frame += 1;
+    match playback.replay.frames.get(playback.frame) {
+        Some(next) => commands.insert_resource(TimeUpdateStrategy::ManualDuration(
+            Duration::from_nanos(next.delta_ns),
+        )),
+        None => {
+            info!("Replay finished; control returned to the player");
+            commands.insert_resource(TimeUpdateStrategy::Automatic);
+            commands.remove_resource::<ReplayPlayback>();
+        }
+    }
+}
+
+/// F12 writes the recording so far to `replays/`
+fn export_replay_input_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    recorder: Res<ReplayRecorder>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::F12) {
+        return;
+    }
+    let Some(replay) = recorder.replay.as_ref() else {
+        info!("This dive is not being recorded (loaded from a save or playing a replay)");
+        return;
+    };
+    let path = format!("{}/replay-{}.ron", REPLAY_DIR, storage::unix_time());
+    match replay.save(&path) {
+        Ok(()) => info!("Replay exported to {}", path),
+        Err(err) => warn!("Failed to export replay: {}", err),
+    }
+}
+
+fn write_replay_output_system(output: Res<ReplayOutput>, recorder: Res<ReplayRecorder>) {
+    let (Some(path), Some(replay)) = (output.0.as_ref(), recorder.replay.as_ref()) else {
+        return;
+    };
+    match replay.save(path) {
+        Ok(()) => info!("Replay written to {}", path),
+        Err(err) => warn!("Failed to write replay to {}: {}", path, err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings_from_the_first_format_get_defaults() {
+        let settings: ReplaySettings = ron::from_str(
+            "(fish_count: 12, oxygen_rate: 0.5, buoyancy_scale: 1.0, start_depth: 0.0)",
+        )
+        .expect("version 1 settings parse");
+        assert_eq!(settings.mode, GameMode::default());
+        assert_eq!(settings.difficulty, 1.0);
+        assert_eq!(settings.new_game_plus, 0);
+        assert_eq!(settings.class, SubmarineClass::default());
+    }
+
+    #[test]
+    fn frame_fields_default_when_missing() {
+        let frame: ReplayFrame = ron::from_str("(delta_ns: 16000000)").expect("frame parses");
+        assert_eq!(frame.delta_ns, 16_000_000);
+        assert!(frame.commands.is_empty());
+        assert_eq!(frame.position, [0.0; 3]);
+    }
+}
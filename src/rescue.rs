@@ -0,0 +1,280 @@
+//! Distress beacon: a boat that is disabled but still holding together, with
+//! its batteries flat or too flooded to lift itself even on empty tanks, can
+//! release a distress buoy. The buoy floats up and transmits, and rescue
+//! arrives once its timer runs out. Holding on until then ends the run with
+//! part of the score kept, instead of a plain game over; losing the hull
+//! first loses the run as usual.
+
+use crate::callouts::{CalloutKind, CrewCallout};
+use crate::controls::{key_name, Action, ControlCommand, InputMap};
+use crate::hud::spawn_game_over_screen;
+use crate::ironman::Ironman;
+use crate::leaks::Flooding;
+use crate::{
+    wave_height, AppState, BallastState, GameAssets, GameMode, GameSet, GameState, Submarine,
+    Tuning, WaveTime,
+};
+use bevy::prelude::*;
+
+const DISABLED_POWER: f32 = 1.0; // Electricity percent below which the boat counts as without power
+const RESCUE_TIME: f32 = 90.0; // Seconds from releasing the buoy to rescue
+const RESCUE_SHARE: f32 = 0.5; // Share of the score kept on rescue
+const BUOY_RISE_SPEED: f32 = 2.0; // Meters per second the buoy floats up at
+const BLINK_RATE: f32 = 2.0; // Beacon flashes per second
+
+/// The distress buoy, once released, and whether rescue has come
+#[derive(Resource, Default)]
+pub struct DistressBeacon {
+    pub remaining: Option<f32>, // Seconds until rescue, once the buoy is away
+    pub rescued: bool,
+}
+
+#[derive(Component)]
+struct DistressBuoy;
+
+/// The buoy and its beacon, lit and dark, made once; the beacon flashes by
+/// swapping between the two
+#[derive(Resource)]
+struct BuoyAssets {
+    mesh: Handle<Mesh>,
+    lit_material: Handle<StandardMaterial>,
+    dark_material: Handle<StandardMaterial>,
+}
+
+impl FromWorld for BuoyAssets {
+    fn from_world(world: &mut World) -> Self {
+        let mesh = world.resource_mut::<Assets<Mesh>>().add(Sphere::new(0.3));
+        let mut materials = world.resource_mut::<Assets<StandardMaterial>>();
+        Self {
+            mesh,
+            lit_material: materials.add(StandardMaterial {
+                base_color: Color::srgb(1.0, 0.4, 0.1),
+                emissive: LinearRgba::rgb(4.0, 1.0, 0.2),
+                ..default()
+            }),
+            dark_material: materials.add(StandardMaterial {
+                base_color: Color::srgb(1.0, 0.4, 0.1),
+                ..default()
+            }),
+        }
+    }
+}
+
+#[derive(Component)]
+struct DistressText;
+
+pub struct RescuePlugin;
+
+impl Plugin for RescuePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DistressBeacon>()
+            .add_systems(
+                OnEnter(AppState::InGame),
+                (reset_beacon, spawn_distress_text),
+            )
+            .add_systems(
+                Update,
+                (
+                    rescue_system
+                        .after(crate::oxygen_system)
+                        .in_set(GameSet::Simulation)
+                        .run_if(beacon_available),
+                    (buoy_system, distress_text_system).in_set(GameSet::Presentation),
+                ),
+            );
+    }
+
+    fn finish(&self, app: &mut App) {
+        app.init_resource::<BuoyAssets>();
+    }
+}
+
+/// Time trials have nothing to rescue, and in a defense the habitat is the point
+fn beacon_available(mode: Res<GameMode>) -> bool {
+    matches!(*mode, GameMode::Dive | GameMode::Survival)
+}
+
+/// Whether the boat is still in one piece but can't get itself home
+fn disabled(
+    game_state: &GameState,
+    ballast_state: &BallastState,
+    flooding: &Flooding,
+    tuning: &Tuning,
+) -> bool {
+    let sinking_on_empty_tanks =
+        flooding.weight() * tuning.ballast_buoyancy_force > tuning.base_buoyancy_force;
+    game_state.health > 0.0
+        && (ballast_state.electricity < DISABLED_POWER || sinking_on_empty_tanks)
+}
+
+fn reset_beacon(mut commands: Commands) {
+    commands.insert_resource(DistressBeacon::default());
+}
+
+fn spawn_distress_text(mut commands: Commands, assets: Res<GameAssets>) {
+    commands.spawn((
+        Text::new(""),
+        TextFont {
+            font_size: 22.0,
+            font: assets.font.clone(),
+            ..default()
+        },
+        TextColor(Color::srgb(1.0, 0.45, 0.2)),
+        TextLayout::new_with_justify(JustifyText::Center),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Percent(15.0),
+            width: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        DistressText,
+        StateScoped(AppState::InGame),
+    ));
+}
+
+/// Releases the buoy when ordered while disabled, counts down to rescue, and
+/// ends the run with part of the score once it comes
+#[allow(clippy::too_many_arguments)]
+fn rescue_system(
+    mut commands: Commands,
+    mut beacon: ResMut<DistressBeacon>,
+    mut game_state: ResMut<GameState>,
+    mut control_commands: EventReader<ControlCommand>,
+    mut callouts: EventWriter<CrewCallout>,
+    ballast_state: Res<BallastState>,
+    flooding: Res<Flooding>,
+    tuning: Res<Tuning>,
+    ironman: Res<Ironman>,
+    mode: Res<GameMode>,
+    submarine_query: Query<&Transform, With<Submarine>>,
+    assets: Res<GameAssets>,
+    buoy: Res<BuoyAssets>,
+    time: Res<Time>,
+) {
+    let ordered = control_commands
+        .read()
+        .any(|command| *command == ControlCommand::ReleaseDistressBuoy);
+    if beacon.rescued || game_state.health <= 0.0 {
+        return;
+    }
+
+    let Some(remaining) = beacon.remaining else {
+        if !ordered {
+            return;
+        }
+        if !disabled(&game_state, &ballast_state, &flooding, &tuning) {
+            callouts.write(CrewCallout {
+                kind: CalloutKind::Failure,
+                text: "We can still make it home, belay the distress buoy".to_string(),
+            });
+            return;
+        }
+        let Ok(submarine) = submarine_query.single() else {
+            return;
+        };
+        commands.spawn((
+            Mesh3d(buoy.mesh.clone()),
+            MeshMaterial3d(buoy.lit_material.clone()),
+            Transform::from_translation(submarine.translation + Vec3::Y),
+            DistressBuoy,
+            StateScoped(AppState::InGame),
+        ));
+        beacon.remaining = Some(RESCUE_TIME);
+        info!("Distress buoy released");
+        callouts.write(CrewCallout {
+            kind: CalloutKind::Failure,
+            text: "Distress buoy away! Hold on until rescue comes".to_string(),
+        });
+        return;
+    };
+
+    let remaining = remaining - time.delta_secs();
+    if remaining > 0.0 {
+        beacon.remaining = Some(remaining);
+        return;
+    }
+    beacon.remaining = Some(0.0);
+    beacon.rescued = true;
+    let full = game_state.score;
+    game_state.score = (full as f32 * RESCUE_SHARE) as u32;
+    info!("Rescued with {} of {} points", game_state.score, full);
+    // Survival and ironman runs end on their own screens, with their leaderboards
+    if *mode == GameMode::Dive && !ironman.0 {
+        spawn_game_over_screen(
+            &mut commands,
+            assets.font.clone(),
+            format!(
+                "Rescued\nThe crew are safe, the boat is towed home\n{} points kept of {}",
+                game_state.score, full
+            ),
+        );
+    }
+}
+
+/// Floats the buoy up to ride the waves, flashing its beacon
+fn buoy_system(
+    mut buoy_query: Query<
+        (&mut Transform, &mut MeshMaterial3d<StandardMaterial>),
+        With<DistressBuoy>,
+    >,
+    assets: Res<BuoyAssets>,
+    wave_time: Res<WaveTime>,
+    time: Res<Time>,
+) {
+    let lit = (time.elapsed_secs() * BLINK_RATE).fract() < 0.5;
+    let wanted = if lit {
+        &assets.lit_material
+    } else {
+        &assets.dark_material
+    };
+    for (mut transform, mut material) in buoy_query.iter_mut() {
+        let surface = wave_height(
+            transform.translation.x,
+            transform.translation.z,
+            wave_time.elapsed,
+        );
+        transform.translation.y =
+            (transform.translation.y + BUOY_RISE_SPEED * time.delta_secs()).min(surface);
+        if material.0 != *wanted {
+            material.0 = wanted.clone();
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn distress_text_system(
+    beacon: Res<DistressBeacon>,
+    game_state: Res<GameState>,
+    ballast_state: Res<BallastState>,
+    flooding: Res<Flooding>,
+    tuning: Res<Tuning>,
+    mode: Res<GameMode>,
+    input_map: Res<InputMap>,
+    mut text_query: Query<&mut Text, With<DistressText>>,
+) {
+    let Ok(mut text) = text_query.single_mut() else {
+        return;
+    };
+    let line = match beacon.remaining {
+        _ if beacon.rescued || game_state.health <= 0.0 => String::new(),
+        Some(remaining) => format!(
+            "DISTRESS BUOY TRANSMITTING\nRescue in {:.0} s - hold on",
+            remaining.ceil()
+        ),
+        None if beacon_available(mode)
+            && disabled(&game_state, &ballast_state, &flooding, &tuning) =>
+        {
+            let key = input_map
+                .binding(Action::DistressBuoy)
+                .primary
+                .map(key_name)
+                .unwrap_or_default();
+            format!("BOAT DISABLED\nPress {} to release the distress buoy", key)
+        }
+        None => String::new(),
+    };
+    if **text != line {
+        **text = line;
+    }
+}
@@ -0,0 +1,501 @@
+use crate::biofouling::Biofouling;
+use crate::classes::SubmarineClass;
+use crate::controls::{HelmState, Telegraph};
+use crate::ironman::Ironman;
+use crate::migration::SeaClock;
+use crate::settings::Settings;
+use crate::tables::Tables;
+use crate::{
+    spawn_fish, storage, AppState, BallastState, Fish, GameAssets, GameMode, GameSet, GameState,
+    NewGamePlus, Submarine, Surfaced, WorldSeed,
+};
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+
+const SAVE_DIR: &str = "saves";
+const SAVE_VERSION: u32 = 1;
+const QUICKSAVE_SLOT: &str = "quicksave";
+const AUTOSAVE_SLOT: &str = "autosave";
+pub const IRONMAN_SLOT: &str = "ironman"; // Every save of an ironman run goes here
+const AUTOSAVE_INTERVAL: f32 = 120.0; // Seconds between autosaves while surfaced
+
+/// Header shown on the load-game screen
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SaveMetadata {
+    pub slot: String,
+    pub name: String,
+    pub timestamp: u64, // Seconds since the Unix epoch
+    pub play_time: f32, // Seconds spent in this dive
+    pub thumbnail: Option<String>,
+}
+
+/// Everything needed to rebuild a dive: the world comes back from the seed,
+/// then the submarine, fish and resources are restored on top of it
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SaveData {
+    pub version: u32,
+    pub metadata: SaveMetadata,
+    seed: u64,
+    score: u32,
+    health: f32,
+    oxygen: f32,
+    ballast_fill: f32,
+    compressed_air: f32,
+    electricity: f32,
+    vents_open: bool,
+    air_valve_open: bool,
+    compressor_on: bool,
+    #[serde(default)]
+    ballast_target: Option<f32>,
+    telegraph: Telegraph,
+    autopilot: bool,
+    autopilot_heading: f32,
+    submarine_translation: [f32; 3],
+    submarine_rotation: [f32; 4],
+    submarine_velocity: [f32; 3],
+    fish: Vec<[f32; 3]>,
+    #[serde(default)]
+    new_game_plus: u32, // New Game Plus cycle; 0 for a first run
+    #[serde(default)]
+    pub ironman: bool,
+    #[serde(default)]
+    biofouling: f32, // Growth on the hull; clean in saves from before it grew
+    #[serde(default)]
+    class: SubmarineClass, // The patrol boat in saves from before there were classes
+    #[serde(default)]
+    clock: Option<f32>, // Sea time; saves from before the clock resume in the morning
+}
+
+/// Request to write the current dive to a slot
+#[derive(Event)]
+pub struct SaveGame {
+    pub slot: String,
+    pub name: String,
+}
+
+/// Save to restore once the world has been rebuilt; `None` starts a fresh dive
+#[derive(Resource, Default)]
+pub struct PendingLoad(pub Option<SaveData>);
+
+/// Seconds played in the current dive, carried across saves
+#[derive(Resource, Default)]
+pub struct PlayTime(pub f32);
+
+#[derive(Resource, Default)]
+struct AutosaveTimer(f32);
+
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SaveGame>()
+            .init_resource::<PendingLoad>()
+            .init_resource::<PlayTime>()
+            .init_resource::<AutosaveTimer>()
+            .add_systems(
+                Update,
+                (
+                    quicksave_input_system.in_set(GameSet::Input),
+                    (play_time_system, autosave_system).in_set(GameSet::Simulation),
+                    write_save_system.in_set(GameSet::Presentation),
+                ),
+            );
+    }
+}
+
+fn slot_key(slot: &str) -> String {
+    format!("{}/{}.ron", SAVE_DIR, slot)
+}
+
+/// Reads every save slot, newest first, skipping files that fail to parse
+pub fn list_saves() -> Vec<SaveData> {
+    let mut saves: Vec<SaveData> = storage::list(SAVE_DIR)
+        .iter()
+        .filter(|key| key.ends_with(".ron"))
+        .filter_map(|key| {
+            let contents = storage::read(key)?;
+            ron::from_str(&contents)
+                .map_err(|err| warn!("Ignoring invalid save {}: {}", key, err))
+                .ok()
+        })
+        .filter(|save: &SaveData| save.version == SAVE_VERSION)
+        .collect();
+    saves.sort_by_key(|save| std::cmp::Reverse(save.metadata.timestamp));
+    saves
+}
+
+/// Deletes a save slot and its thumbnail
+pub fn delete_save(slot: &str) {
+    let thumbnail = format!("{}/{}.png", SAVE_DIR, slot);
+    if let Err(err) = storage::remove(&slot_key(slot)).and(storage::remove(&thumbnail)) {
+        warn!("Failed to delete save {}: {}", slot, err);
+    }
+}
+
+pub fn read_save(slot: &str) -> Option<SaveData> {
+    let contents = storage::read(&slot_key(slot))?;
+    match ron::from_str::<SaveData>(&contents) {
+        Ok(save) if save.version == SAVE_VERSION => Some(save),
+        Ok(save) => {
+            warn!("Save {} has unsupported version {}", slot, save.version);
+            None
+        }
+        Err(err) => {
+            warn!("Ignoring invalid save {}: {}", slot, err);
+            None
+        }
+    }
+}
+
+/// Rebuilds the world from the save's seed and restores it on entering InGame
+pub fn start_load(commands: &mut Commands, next_state: &mut NextState<AppState>, save: SaveData) {
+    commands.insert_resource(WorldSeed(save.seed));
+    commands.insert_resource(GameMode::Dive);
+    commands.insert_resource(NewGamePlus(save.new_game_plus));
+    commands.insert_resource(Ironman(save.ironman));
+    commands.insert_resource(save.class.clone());
+    commands.insert_resource(PendingLoad(Some(save)));
+    next_state.set(AppState::Loading);
+}
+
+/// Formats a Unix timestamp as `YYYY-MM-DD HH:MM` (UTC)
+pub fn format_timestamp(timestamp: u64) -> String {
+    let days = (timestamp / 86_400) as i64;
+    let seconds_of_day = timestamp % 86_400;
+
+    // Civil-from-days conversion (proleptic Gregorian calendar)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}",
+        year,
+        month,
+        day,
+        seconds_of_day / 3_600,
+        seconds_of_day % 3_600 / 60
+    )
+}
+
+/// Formats a duration in seconds as `H:MM:SS`
+pub fn format_play_time(seconds: f32) -> String {
+    let seconds = seconds.max(0.0) as u64;
+    format!(
+        "{}:{:02}:{:02}",
+        seconds / 3_600,
+        seconds % 3_600 / 60,
+        seconds % 60
+    )
+}
+
+/// F5 quicksaves, Shift+F5 saves to a new numbered slot, F10 quickloads;
+/// an ironman run can't be reloaded
+fn quicksave_input_system(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    ironman: Res<Ironman>,
+    mut next_state: ResMut<NextState<AppState>>,
+    mut saves: EventWriter<SaveGame>,
+) {
+    if keyboard_input.just_pressed(KeyCode::F5) {
+        let shift = keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+        if shift {
+            let number = (1..)
+                .find(|number| storage::read(&slot_key(&format!("slot{}", number))).is_none())
+                .unwrap_or(1);
+            saves.write(SaveGame {
+                slot: format!("slot{}", number),
+                name: format!("Save {}", number),
+            });
+        } else {
+            saves.write(SaveGame {
+                slot: QUICKSAVE_SLOT.to_string(),
+                name: "Quicksave".to_string(),
+            });
+        }
+    }
+
+    if keyboard_input.just_pressed(KeyCode::F10) {
+        if ironman.0 {
+            info!("Ironman runs can't be reloaded");
+            return;
+        }
+        match read_save(QUICKSAVE_SLOT) {
+            Some(save) => start_load(&mut commands, &mut next_state, save),
+            None => info!("No quicksave to load"),
+        }
+    }
+}
+
+fn play_time_system(time: Res<Time>, mut play_time: ResMut<PlayTime>) {
+    play_time.0 += time.delta_secs();
+}
+
+/// Autosaves periodically, but only while surfaced where the state is stable
+fn autosave_system(
+    time: Res<Time>,
+    settings: Res<Settings>,
+    mut timer: ResMut<AutosaveTimer>,
+    surfaced: Res<Surfaced>,
+    mut saves: EventWriter<SaveGame>,
+) {
+    timer.0 += time.delta_secs();
+    if !settings.gameplay.autosave || timer.0 < AUTOSAVE_INTERVAL {
+        return;
+    }
+    if surfaced.0 {
+        timer.0 = 0.0;
+        saves.write(SaveGame {
+            slot: AUTOSAVE_SLOT.to_string(),
+            name: "Autosave".to_string(),
+        });
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn write_save_system(
+    mut commands: Commands,
+    mut requests: EventReader<SaveGame>,
+    mode: Res<GameMode>,
+    ironman: Res<Ironman>,
+    seed: Res<WorldSeed>,
+    new_game_plus: Res<NewGamePlus>,
+    play_time: Res<PlayTime>,
+    game_state: Res<GameState>,
+    ballast_state: Res<BallastState>,
+    helm_state: Res<HelmState>,
+    biofouling: Res<Biofouling>,
+    class: Res<SubmarineClass>,
+    clock: Res<SeaClock>,
+    submarine_query: Query<(&Transform, &Velocity), With<Submarine>>,
+    fish_query: Query<&Transform, With<Fish>>,
+) {
+    let Ok((transform, velocity)) = submarine_query.single() else {
+        requests.clear();
+        return;
+    };
+    // Race state isn't saved, so only free dives can be saved and resumed
+    if *mode != GameMode::Dive {
+        if !requests.is_empty() {
+            info!("Saving is only available in free dives");
+        }
+        requests.clear();
+        return;
+    }
+    // A lost ironman run stays lost
+    if ironman.0 && game_state.health <= 0.0 {
+        requests.clear();
+        return;
+    }
+
+    for request in requests.read() {
+        // Ironman runs keep to their one slot, whichever save asked
+        let (slot, name) = if ironman.0 {
+            (IRONMAN_SLOT, "Ironman")
+        } else {
+            (request.slot.as_str(), request.name.as_str())
+        };
+        let thumbnail = capture_thumbnail(&mut commands, slot);
+        let save = SaveData {
+            version: SAVE_VERSION,
+            metadata: SaveMetadata {
+                slot: slot.to_string(),
+                name: name.to_string(),
+                timestamp: storage::unix_time(),
+                play_time: play_time.0,
+                thumbnail,
+            },
+            seed: seed.0,
+            score: game_state.score,
+            health: game_state.health,
+            oxygen: game_state.oxygen,
+            ballast_fill: ballast_state.fill_level,
+            compressed_air: ballast_state.compressed_air,
+            electricity: ballast_state.electricity,
+            vents_open: ballast_state.vents_open,
+            air_valve_open: ballast_state.air_valve_open,
+            compressor_on: ballast_state.compressor_on,
+            ballast_target: ballast_state.target_fill,
+            telegraph: helm_state.telegraph,
+            autopilot: helm_state.autopilot,
+            autopilot_heading: helm_state.autopilot_heading,
+            submarine_translation: transform.translation.to_array(),
+            submarine_rotation: transform.rotation.to_array(),
+            submarine_velocity: velocity.linvel.to_array(),
+            fish: fish_query
+                .iter()
+                .map(|fish| fish.translation.to_array())
+                .collect(),
+            new_game_plus: new_game_plus.0,
+            ironman: ironman.0,
+            biofouling: biofouling.growth,
+            class: class.clone(),
+            clock: Some(clock.elapsed),
+        };
+
+        let pretty = ron::ser::PrettyConfig::default();
+        let result = ron::ser::to_string_pretty(&save, pretty)
+            .map_err(|err| err.to_string())
+            .and_then(|contents| storage::write(&slot_key(slot), &contents));
+        match result {
+            Ok(()) => info!("Saved {}", name),
+            Err(err) => warn!("Failed to save {}: {}", name, err),
+        }
+    }
+}
+
+/// Screenshots the next frame next to the save file and returns its path
+#[cfg(not(target_arch = "wasm32"))]
+fn capture_thumbnail(commands: &mut Commands, slot: &str) -> Option<String> {
+    use bevy::render::view::screenshot::{save_to_disk, Screenshot};
+
+    let path = format!("{}/{}.png", SAVE_DIR, slot);
+    commands
+        .spawn(Screenshot::primary_window())
+        .observe(save_to_disk(path.clone()));
+    Some(path)
+}
+
+/// Web builds would turn the screenshot into a browser download, so they go without
+#[cfg(target_arch = "wasm32")]
+fn capture_thumbnail(_commands: &mut Commands, _slot: &str) -> Option<String> {
+    None
+}
+
+/// Restores a pending save on top of the freshly built world, or resets the
+/// per-dive counters for a new game
+#[allow(clippy::too_many_arguments)]
+pub fn apply_pending_load(
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+    tables: Res<Tables>,
+    new_game_plus: Res<NewGamePlus>,
+    mut pending: ResMut<PendingLoad>,
+    mut play_time: ResMut<PlayTime>,
+    mut game_state: ResMut<GameState>,
+    mut ballast_state: ResMut<BallastState>,
+    mut helm_state: ResMut<HelmState>,
+    mut biofouling: ResMut<Biofouling>,
+    mut clock: ResMut<SeaClock>,
+    mut submarine_query: Query<(&mut Transform, &mut Velocity), With<Submarine>>,
+    fish_query: Query<Entity, With<Fish>>,
+) {
+    commands.insert_resource(AutosaveTimer::default());
+    let Some(save) = pending.0.take() else {
+        play_time.0 = 0.0;
+        return;
+    };
+
+    play_time.0 = save.metadata.play_time;
+    game_state.score = save.score;
+    game_state.health = save.health;
+    game_state.oxygen = save.oxygen;
+    ballast_state.fill_level = save.ballast_fill;
+    ballast_state.compressed_air = save.compressed_air;
+    ballast_state.electricity = save.electricity;
+    ballast_state.vents_open = save.vents_open;
+    ballast_state.air_valve_open = save.air_valve_open;
+    ballast_state.compressor_on = save.compressor_on;
+    ballast_state.target_fill = save.ballast_target;
+    helm_state.telegraph = save.telegraph;
+    helm_state.autopilot = save.autopilot;
+    helm_state.autopilot_heading = save.autopilot_heading;
+    biofouling.growth = save.biofouling;
+    if let Some(elapsed) = save.clock {
+        clock.elapsed = elapsed;
+    }
+
+    if let Ok((mut transform, mut velocity)) = submarine_query.single_mut() {
+        transform.translation = Vec3::from_array(save.submarine_translation);
+        transform.rotation = Quat::from_array(save.submarine_rotation).normalize();
+        velocity.linvel = Vec3::from_array(save.submarine_velocity);
+    }
+
+    // Collected fish stay collected: replace the seeded school with the saved one
+    for entity in fish_query.iter() {
+        commands.entity(entity).despawn();
+    }
+    // Species aren't saved, so the school is restocked with fresh picks
+    let mut rng = rand::thread_rng();
+    for position in save.fish {
+        let Ok(species) = tables
+            .fish
+            .choose_weighted(&mut rng, |species| new_game_plus.species_weight(species))
+        else {
+            break;
+        };
+        spawn_fish(
+            &mut commands,
+            &assets,
+            &mut rng,
+            species,
+            Vec3::from_array(position),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A save written before ballast targets, New Game Plus, ironman,
+    /// biofouling, classes and the sea clock
+    const FIRST_SAVE: &str = "(
+        version: 1,
+        metadata: (slot: \"quicksave\", name: \"Quicksave\", timestamp: 0, play_time: 60.0, thumbnail: None),
+        seed: 7,
+        score: 3,
+        health: 80.0,
+        oxygen: 90.0,
+        ballast_fill: 0.5,
+        compressed_air: 70.0,
+        electricity: 60.0,
+        vents_open: false,
+        air_valve_open: false,
+        compressor_on: true,
+        telegraph: AheadSlow,
+        autopilot: false,
+        autopilot_heading: 0.0,
+        submarine_translation: (0.0, -5.0, 0.0),
+        submarine_rotation: (0.0, 0.0, 0.0, 1.0),
+        submarine_velocity: (0.0, 0.0, 0.0),
+        fish: [],
+    )";
+
+    #[test]
+    fn first_save_format_gets_defaults() {
+        let save: SaveData = ron::from_str(FIRST_SAVE).expect("version 1 save parses");
+        assert_eq!(save.version, SAVE_VERSION);
+        assert_eq!(save.telegraph, Telegraph::AheadSlow);
+        assert_eq!(save.ballast_target, None);
+        assert_eq!(save.new_game_plus, 0);
+        assert!(!save.ironman);
+        assert_eq!(save.biofouling, 0.0);
+        assert_eq!(save.class, SubmarineClass::default());
+        assert_eq!(save.clock, None);
+    }
+
+    #[test]
+    fn save_round_trips() {
+        let save: SaveData = ron::from_str(FIRST_SAVE).expect("version 1 save parses");
+        let written = ron::to_string(&save).expect("save serializes");
+        let read: SaveData = ron::from_str(&written).expect("written save parses");
+        assert_eq!(read.seed, save.seed);
+        assert_eq!(read.submarine_translation, save.submarine_translation);
+        assert_eq!(read.class, save.class);
+    }
+}
@@ -0,0 +1,153 @@
+//! Scenario files describe a complete map: the terrain seed, hand-placed props,
+//! the fish that live there and the missions played in order. They are loaded
+//! with `--scenario <file>` and apply on top of the game's data and any mods.
+
+use crate::mods::{Mod, ModManifest};
+use crate::optics::WaterBody;
+use crate::props::{Placement, Props};
+use crate::replay::ReplayPlayback;
+use crate::save::PendingLoad;
+use crate::storage;
+use crate::tables::{FishSpecies, TablesPatch};
+use crate::{AppState, GameMode, WorldSeed};
+use bevy::prelude::*;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+const SCENARIO_FORMAT: &str = "submarine-scenario";
+
+/// The loaded scenario, present when the game was started with `--scenario`
+#[derive(Resource, Clone, Serialize, Deserialize)]
+pub struct Scenario {
+    pub format: String, // Always SCENARIO_FORMAT
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub seed: Option<u64>, // Terrain seed; a random one is rolled when missing
+    #[serde(default)]
+    pub start: Option<(f32, f32, f32)>, // Where the submarine starts a new dive
+    #[serde(default)]
+    pub fish: Vec<FishSpecies>, // Replaces the game's species when not empty
+    #[serde(default)]
+    pub fish_count: Option<usize>,
+    #[serde(default)]
+    pub fish_regions: Vec<FishRegion>, // Fish only spawn inside these when not empty
+    #[serde(default)]
+    pub placements: Vec<Placement>,
+    #[serde(default)]
+    pub missions: Vec<String>, // Mission scripts, played one after another
+    #[serde(default)]
+    pub water: WaterBody,
+}
+
+/// A circular area of the lake, seen from above, where fish spawn
+#[derive(Clone, Serialize, Deserialize)]
+pub struct FishRegion {
+    pub center: (f32, f32), // X and Z
+    pub radius: f32,
+}
+
+/// File the scenario was loaded from, and where the editor saves it
+#[derive(Resource)]
+pub struct ScenarioPath(pub String);
+
+impl Scenario {
+    /// An empty map with a random seed
+    pub fn new(name: &str) -> Self {
+        Self {
+            format: SCENARIO_FORMAT.to_string(),
+            name: name.to_string(),
+            description: String::new(),
+            seed: Some(rand::random()),
+            start: None,
+            fish: Vec::new(),
+            fish_count: None,
+            fish_regions: Vec::new(),
+            placements: Vec::new(),
+            missions: Vec::new(),
+            water: WaterBody::default(),
+        }
+    }
+
+    /// Reads and checks a scenario file
+    pub fn load(path: &str) -> Result<Self, String> {
+        let contents = storage::read(path).ok_or_else(|| format!("cannot read {}", path))?;
+        let scenario: Scenario = ron::Options::default()
+            .with_default_extension(ron::extensions::Extensions::IMPLICIT_SOME)
+            .from_str(&contents)
+            .map_err(|err| format!("{}: {}", path, err))?;
+        if scenario.format != SCENARIO_FORMAT {
+            return Err(format!("{} is not a scenario file", path));
+        }
+        Ok(scenario)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let pretty = ron::ser::PrettyConfig::default()
+            .extensions(ron::extensions::Extensions::IMPLICIT_SOME);
+        let contents = ron::ser::to_string_pretty(self, pretty).map_err(|err| err.to_string())?;
+        storage::write(path, &contents)
+    }
+
+    /// The scenario's fish and props as a content layer applied after every mod
+    pub fn content(&self) -> Mod {
+        let tables = TablesPatch {
+            replace_fish: !self.fish.is_empty(),
+            fish: self.fish.clone(),
+            ..default()
+        };
+        let props = Props {
+            materials: default(),
+            props: default(),
+            placements: self.placements.clone(),
+        };
+        Mod::new(
+            ModManifest {
+                name: format!("{} (scenario)", self.name),
+                description: self.description.clone(),
+                ..default()
+            },
+            Some(tables),
+            Some(props),
+        )
+    }
+
+    /// A random spot in one of the fish regions, or None to use the usual rings
+    pub fn fish_position(&self, rng: &mut impl Rng) -> Option<Vec2> {
+        if self.fish_regions.is_empty() {
+            return None;
+        }
+        let region = &self.fish_regions[rng.gen_range(0..self.fish_regions.len())];
+        // Square root keeps the spread even across the disc
+        let distance = region.radius * rng.gen::<f32>().sqrt();
+        let angle = rng.gen_range(0.0..std::f32::consts::TAU);
+        Some(Vec2::new(region.center.0, region.center.1) + Vec2::from_angle(angle) * distance)
+    }
+}
+
+pub struct ScenarioPlugin;
+
+impl Plugin for ScenarioPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(AppState::Loading), apply_scenario_seed);
+    }
+}
+
+/// New dives build the scenario's map. Courses keep their fixed seed, and saves
+/// and replays bring their own.
+fn apply_scenario_seed(
+    mut commands: Commands,
+    scenario: Option<Res<Scenario>>,
+    mode: Res<GameMode>,
+    pending: Res<PendingLoad>,
+    playback: Option<Res<ReplayPlayback>>,
+) {
+    let Some(seed) = scenario.and_then(|scenario| scenario.seed) else {
+        return;
+    };
+    if *mode == GameMode::TimeTrial || pending.0.is_some() || playback.is_some() {
+        return;
+    }
+    commands.insert_resource(WorldSeed(seed));
+}
@@ -0,0 +1,218 @@
+use crate::combat::Hostile;
+use crate::settings::Settings;
+use crate::{AppState, GameAssets, GameSet, GameState};
+use bevy::prelude::*;
+
+const FISH_POINTS: u32 = 10;
+const COMBO_WINDOW: f32 = 6.0; // Seconds to catch the next fish before the multiplier drops
+const MAX_MULTIPLIER: u32 = 8;
+const DEEP_DEPTH: f32 = 15.0;
+const DEEP_BONUS: u32 = 5;
+const DANGER_RANGE: f32 = 15.0; // A hostile this close makes a catch count as dangerous
+const DANGER_BONUS: u32 = 10;
+const DAMAGED_HEALTH: f32 = 50.0;
+const DAMAGED_BONUS: u32 = 5;
+const UNDERSIZED_PENALTY: u32 = 25; // Taken for each undersized catch under sustainable fishing
+const CALLOUT_TIME: f32 = 2.5; // Seconds the last catch's points stay on the meter
+
+/// Sent when the submarine collects a fish at `position`
+#[derive(Event)]
+pub struct FishCollected {
+    pub position: Vec3,
+    pub size: f32, // Multiple of the species' usual size
+    pub undersized: bool,
+}
+
+/// Catches in quick succession build up a multiplier that drops one step
+/// every time the window runs out
+#[derive(Resource)]
+struct Combo {
+    multiplier: u32,
+    timer: f32, // Seconds left in the current window
+    callout: String,
+    callout_timer: f32,
+}
+
+impl Default for Combo {
+    fn default() -> Self {
+        Self {
+            multiplier: 1,
+            timer: 0.0,
+            callout: String::new(),
+            callout_timer: 0.0,
+        }
+    }
+}
+
+#[derive(Component)]
+struct ComboText;
+
+#[derive(Component)]
+struct ComboBar;
+
+pub struct ScoringPlugin;
+
+impl Plugin for ScoringPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<FishCollected>()
+            .init_resource::<Combo>()
+            .add_systems(OnEnter(AppState::InGame), spawn_combo_meter)
+            .add_systems(
+                Update,
+                (
+                    (combo_decay_system, scoring_system)
+                        .chain()
+                        .after(crate::collect_fish)
+                        .in_set(GameSet::Simulation),
+                    combo_meter_system.in_set(GameSet::Presentation),
+                ),
+            );
+    }
+}
+
+fn spawn_combo_meter(mut commands: Commands, assets: Res<GameAssets>) {
+    commands.insert_resource(Combo::default());
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                bottom: Val::Px(16.0),
+                width: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                row_gap: Val::Px(4.0),
+                ..default()
+            },
+            Pickable::IGNORE,
+            StateScoped(AppState::InGame),
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new(""),
+                TextFont {
+                    font_size: 20.0,
+                    font: assets.font.clone(),
+                    ..default()
+                },
+                TextColor(Color::srgb(1.0, 0.85, 0.3)),
+                TextLayout::new_with_justify(JustifyText::Center),
+                ComboText,
+            ));
+            parent
+                .spawn((
+                    Node {
+                        width: Val::Px(160.0),
+                        height: Val::Px(6.0),
+                        ..default()
+                    },
+                    BackgroundColor(Color::srgba(1.0, 1.0, 1.0, 0.15)),
+                ))
+                .with_children(|bar| {
+                    bar.spawn((
+                        Node {
+                            width: Val::Percent(0.0),
+                            height: Val::Percent(100.0),
+                            ..default()
+                        },
+                        BackgroundColor(Color::srgb(1.0, 0.85, 0.3)),
+                        ComboBar,
+                    ));
+                });
+        });
+}
+
+fn combo_decay_system(time: Res<Time>, mut combo: ResMut<Combo>) {
+    let delta = time.delta_secs();
+    combo.callout_timer = (combo.callout_timer - delta).max(0.0);
+    if combo.multiplier <= 1 {
+        return;
+    }
+    combo.timer -= delta;
+    if combo.timer <= 0.0 {
+        combo.multiplier -= 1;
+        combo.timer = if combo.multiplier > 1 {
+            COMBO_WINDOW
+        } else {
+            0.0
+        };
+    }
+}
+
+/// Scores each catch by its size with the current multiplier plus bonuses for
+/// catching it deep, with a hostile close by, or with a damaged hull. Under
+/// sustainable fishing an undersized catch costs points and breaks the combo.
+fn scoring_system(
+    mut collected: EventReader<FishCollected>,
+    mut combo: ResMut<Combo>,
+    mut game_state: ResMut<GameState>,
+    settings: Res<Settings>,
+    hostile_query: Query<&Transform, With<Hostile>>,
+) {
+    for FishCollected {
+        position,
+        size,
+        undersized,
+    } in collected.read()
+    {
+        if *undersized && settings.gameplay.sustainable_fishing {
+            game_state.score = game_state.score.saturating_sub(UNDERSIZED_PENALTY);
+            combo.callout = format!("-{} UNDERSIZED", UNDERSIZED_PENALTY);
+            combo.callout_timer = CALLOUT_TIME;
+            combo.multiplier = 1;
+            combo.timer = 0.0;
+            continue;
+        }
+
+        let mut bonus = 0;
+        let mut reasons = Vec::new();
+        if -position.y > DEEP_DEPTH {
+            bonus += DEEP_BONUS;
+            reasons.push("DEEP");
+        }
+        if hostile_query
+            .iter()
+            .any(|hostile| hostile.translation.distance(*position) < DANGER_RANGE)
+        {
+            bonus += DANGER_BONUS;
+            reasons.push("DANGER");
+        }
+        if game_state.health < DAMAGED_HEALTH {
+            bonus += DAMAGED_BONUS;
+            reasons.push("DAMAGED");
+        }
+
+        let points = ((FISH_POINTS as f32 * size).round() as u32 + bonus) * combo.multiplier;
+        game_state.score += points;
+        combo.callout = if reasons.is_empty() {
+            format!("+{}", points)
+        } else {
+            format!("+{} {}", points, reasons.join(" "))
+        };
+        combo.callout_timer = CALLOUT_TIME;
+        combo.multiplier = (combo.multiplier + 1).min(MAX_MULTIPLIER);
+        combo.timer = COMBO_WINDOW;
+    }
+}
+
+fn combo_meter_system(
+    combo: Res<Combo>,
+    mut text_query: Query<&mut Text, With<ComboText>>,
+    mut bar_query: Query<&mut Node, With<ComboBar>>,
+) {
+    if let Ok(mut text) = text_query.single_mut() {
+        let mut line = if combo.multiplier > 1 {
+            format!("Combo x{}", combo.multiplier)
+        } else {
+            String::new()
+        };
+        if combo.callout_timer > 0.0 {
+            line = format!("{}\n{}", combo.callout, line);
+        }
+        if **text != line {
+            **text = line;
+        }
+    }
+    if let Ok(mut bar) = bar_query.single_mut() {
+        bar.width = Val::Percent(combo.timer / COMBO_WINDOW * 100.0);
+    }
+}
@@ -0,0 +1,542 @@
+use crate::event_log::{LogKind, LogMessage};
+use crate::props::PropLibrary;
+use crate::scoring::FishCollected;
+use crate::{AppState, GameAssets, GameSet, GameState, Submarine, WorldSeed};
+use bevy::asset::{io::Reader, AssetLoader, LoadContext};
+use bevy::prelude::*;
+use rand::{rngs::StdRng, SeedableRng};
+use rhai::{
+    module_resolvers::DummyModuleResolver, CallFnOptions, Dynamic, Engine, EvalAltResult, Map,
+    Scope, AST,
+};
+use std::sync::{Arc, Mutex};
+
+const DEFAULT_MESSAGE_TIME: f32 = 4.0;
+const MAX_OPERATIONS: u64 = 100_000; // Per call, so a runaway loop can't hang a frame
+
+/// Names of the mission scripts to run, `assets/scripts/<name>.rhai`. Each dive
+/// starts with the first; `complete_mission()` moves on to the next.
+#[derive(Resource, Default)]
+pub struct Missions(pub Vec<String>);
+
+/// Sent when a mission script completes its mission, fails it, or the hull is
+/// lost while it runs
+#[derive(Event, Clone)]
+pub struct MissionEnded {
+    pub mission: String,
+    pub completed: bool,
+    pub reason: String,          // Why the mission failed; empty when completed
+    pub objectives: Vec<String>, // In the order the script set them
+}
+
+/// Source text of a Rhai script
+#[derive(Asset, TypePath)]
+pub struct ScriptSource(String);
+
+#[derive(Default)]
+struct ScriptLoader;
+
+impl AssetLoader for ScriptLoader {
+    type Asset = ScriptSource;
+    type Settings = ();
+    type Error = Box<dyn std::error::Error + Send + Sync>;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<ScriptSource, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Ok(ScriptSource(String::from_utf8(bytes)?))
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["rhai"]
+    }
+}
+
+/// Game state a script can read, refreshed before each call
+#[derive(Default, Clone, Copy)]
+struct Snapshot {
+    position: Vec3,
+    score: u32,
+    health: f32,
+    oxygen: f32,
+}
+
+/// Something a script asked the game to do
+enum ScriptRequest {
+    Spawn { prop: String, position: Vec3 },
+    Objective(String),
+    Message(String, f32),
+    Complete,
+    Fail(String),
+}
+
+/// Shared between the engine's registered functions and the game systems
+#[derive(Default)]
+struct Bridge {
+    snapshot: Snapshot,
+    requests: Vec<ScriptRequest>,
+}
+
+/// The running mission script. Scripts define any of `on_start()`,
+/// `on_update(dt)` and `on_fish_collected()`, and keep their state in `this`.
+#[derive(Resource)]
+struct ScriptRuntime {
+    engine: Engine,
+    bridge: Arc<Mutex<Bridge>>,
+    source: Option<Handle<ScriptSource>>,
+    ast: Option<AST>,
+    scope: Scope<'static>,
+    this: Dynamic,
+    failed: bool,
+    mission: usize,          // Index into Missions
+    objectives: Vec<String>, // Set so far in the current mission
+}
+
+impl Default for ScriptRuntime {
+    fn default() -> Self {
+        let bridge = Arc::new(Mutex::new(Bridge::default()));
+        Self {
+            engine: sandboxed_engine(&bridge),
+            bridge,
+            source: None,
+            ast: None,
+            scope: Scope::new(),
+            this: Dynamic::from_map(Map::new()),
+            failed: false,
+            mission: 0,
+            objectives: Vec::new(),
+        }
+    }
+}
+
+/// An engine that can only reach the game through the functions below: no
+/// module imports, and limits on run time, recursion and data sizes
+fn sandboxed_engine(bridge: &Arc<Mutex<Bridge>>) -> Engine {
+    let mut engine = Engine::new();
+    engine
+        .set_module_resolver(DummyModuleResolver::new())
+        .set_max_operations(MAX_OPERATIONS)
+        .set_max_call_levels(32)
+        .set_max_expr_depths(64, 32)
+        .set_max_string_size(4096)
+        .set_max_array_size(1024)
+        .set_max_map_size(1024)
+        .on_print(|text| info!("[script] {}", text))
+        .on_debug(|text, _, _| debug!("[script] {}", text));
+
+    let shared = bridge.clone();
+    engine.register_fn("depth", move || {
+        -shared.lock().unwrap().snapshot.position.y as f64
+    });
+    let shared = bridge.clone();
+    engine.register_fn("position", move || {
+        let position = shared.lock().unwrap().snapshot.position;
+        let mut map = Map::new();
+        map.insert("x".into(), (position.x as f64).into());
+        map.insert("y".into(), (position.y as f64).into());
+        map.insert("z".into(), (position.z as f64).into());
+        map
+    });
+    let shared = bridge.clone();
+    engine.register_fn("score", move || {
+        shared.lock().unwrap().snapshot.score as i64
+    });
+    let shared = bridge.clone();
+    engine.register_fn("health", move || {
+        shared.lock().unwrap().snapshot.health as f64
+    });
+    let shared = bridge.clone();
+    engine.register_fn("oxygen", move || {
+        shared.lock().unwrap().snapshot.oxygen as f64
+    });
+    let shared = bridge.clone();
+    engine.register_fn("spawn_prop", move |prop: &str, x: f64, y: f64, z: f64| {
+        shared.lock().unwrap().requests.push(ScriptRequest::Spawn {
+            prop: prop.to_string(),
+            position: Vec3::new(x as f32, y as f32, z as f32),
+        });
+    });
+    let shared = bridge.clone();
+    engine.register_fn("set_objective", move |text: &str| {
+        shared
+            .lock()
+            .unwrap()
+            .requests
+            .push(ScriptRequest::Objective(text.to_string()));
+    });
+    let shared = bridge.clone();
+    engine.register_fn("show_message", move |text: &str| {
+        shared.lock().unwrap().requests.push(ScriptRequest::Message(
+            text.to_string(),
+            DEFAULT_MESSAGE_TIME,
+        ));
+    });
+    let shared = bridge.clone();
+    engine.register_fn("show_message", move |text: &str, seconds: f64| {
+        shared
+            .lock()
+            .unwrap()
+            .requests
+            .push(ScriptRequest::Message(text.to_string(), seconds as f32));
+    });
+    let shared = bridge.clone();
+    engine.register_fn("complete_mission", move || {
+        shared
+            .lock()
+            .unwrap()
+            .requests
+            .push(ScriptRequest::Complete);
+    });
+    let shared = bridge.clone();
+    engine.register_fn("fail_mission", move |reason: &str| {
+        shared
+            .lock()
+            .unwrap()
+            .requests
+            .push(ScriptRequest::Fail(reason.to_string()));
+    });
+    engine
+}
+
+impl ScriptRuntime {
+    /// Forgets the running script's code and state so it starts from the top
+    fn restart(&mut self) {
+        self.ast = None;
+        self.failed = false;
+        self.scope = Scope::new();
+        self.this = Dynamic::from_map(Map::new());
+        self.objectives.clear();
+    }
+
+    /// Calls a script function if the script defines it; errors stop the script
+    fn call(&mut self, name: &str, args: impl rhai::FuncArgs) {
+        let Some(ast) = &self.ast else {
+            return;
+        };
+        if self.failed {
+            return;
+        }
+        let mut args_vec = Vec::new();
+        args.parse(&mut args_vec);
+        if !ast
+            .iter_functions()
+            .any(|function| function.name == name && function.params.len() == args_vec.len())
+        {
+            return;
+        }
+        let options = CallFnOptions::new()
+            .eval_ast(false)
+            .bind_this_ptr(&mut self.this);
+        if let Err(err) = self.engine.call_fn_with_options::<Dynamic>(
+            options,
+            &mut self.scope,
+            ast,
+            name,
+            args_vec,
+        ) {
+            warn!("Mission script stopped in {}: {}", name, err);
+            self.failed = true;
+        }
+    }
+}
+
+/// Objective and message text shown by the script
+#[derive(Resource, Default)]
+struct ScriptDisplay {
+    objective: String,
+    message: String,
+    message_timer: f32,
+}
+
+#[derive(Component)]
+struct ObjectiveText;
+
+#[derive(Component)]
+struct ScriptMessageText;
+
+/// Random source for props spawned by scripts, seeded from the world so replays match
+#[derive(Resource)]
+struct ScriptRng(StdRng);
+
+pub struct ScriptingPlugin;
+
+impl Plugin for ScriptingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<MissionEnded>()
+            .init_asset::<ScriptSource>()
+            .init_asset_loader::<ScriptLoader>()
+            .init_resource::<Missions>()
+            .init_resource::<ScriptRuntime>()
+            .init_resource::<ScriptDisplay>()
+            .insert_resource(ScriptRng(StdRng::seed_from_u64(0)))
+            .add_systems(OnEnter(AppState::InGame), (reset_script, spawn_script_text))
+            .add_systems(
+                Update,
+                (
+                    (script_system, script_request_system)
+                        .chain()
+                        .after(crate::collect_fish)
+                        .in_set(GameSet::Simulation),
+                    script_text_system.in_set(GameSet::Presentation),
+                ),
+            );
+    }
+}
+
+/// Every dive starts the mission over, with fresh state
+fn reset_script(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    missions: Res<Missions>,
+    seed: Res<WorldSeed>,
+) {
+    commands.insert_resource(ScriptRuntime {
+        source: missions
+            .0
+            .first()
+            .map(|name| load_script(&asset_server, name)),
+        ..default()
+    });
+    commands.insert_resource(ScriptDisplay::default());
+    commands.insert_resource(ScriptRng(StdRng::seed_from_u64(seed.0 ^ 0x5343_5250)));
+}
+
+fn load_script(asset_server: &AssetServer, name: &str) -> Handle<ScriptSource> {
+    asset_server.load(format!("scripts/{}.rhai", name))
+}
+
+fn spawn_script_text(mut commands: Commands, assets: Res<GameAssets>) {
+    let font = assets.font.clone();
+    commands.spawn((
+        Text::new(""),
+        TextFont {
+            font_size: 22.0,
+            font: font.clone(),
+            ..default()
+        },
+        TextColor(Color::srgb(1.0, 0.9, 0.5)),
+        TextLayout::new_with_justify(JustifyText::Center),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(48.0),
+            width: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        ObjectiveText,
+        StateScoped(AppState::InGame),
+    ));
+    commands.spawn((
+        Text::new(""),
+        TextFont {
+            font_size: 26.0,
+            font,
+            ..default()
+        },
+        TextColor(Color::WHITE),
+        TextLayout::new_with_justify(JustifyText::Center),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Percent(15.0),
+            width: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        ScriptMessageText,
+        StateScoped(AppState::InGame),
+    ));
+}
+
+/// Starts the script once its source has loaded (and again when it's edited in
+/// `dev` builds), then feeds it game events
+fn script_system(
+    time: Res<Time>,
+    sources: Res<Assets<ScriptSource>>,
+    game_state: Res<GameState>,
+    mut runtime: ResMut<ScriptRuntime>,
+    mut asset_events: EventReader<AssetEvent<ScriptSource>>,
+    mut fish_collected: EventReader<FishCollected>,
+    submarine_query: Query<&Transform, With<Submarine>>,
+) {
+    let Some(handle) = runtime.source.clone() else {
+        fish_collected.clear();
+        return;
+    };
+    for event in asset_events.read() {
+        if let AssetEvent::Modified { id } = *event {
+            if id == handle.id() {
+                info!("Mission script changed, restarting it");
+                runtime.restart();
+            }
+        }
+    }
+
+    if let Ok(submarine) = submarine_query.single() {
+        runtime.bridge.lock().unwrap().snapshot = Snapshot {
+            position: submarine.translation,
+            score: game_state.score,
+            health: game_state.health,
+            oxygen: game_state.oxygen,
+        };
+    }
+
+    // Losing the hull ends the mission, whatever the script thinks
+    if game_state.health <= 0.0 && runtime.ast.is_some() {
+        runtime
+            .bridge
+            .lock()
+            .unwrap()
+            .requests
+            .push(ScriptRequest::Fail("The hull was lost".to_string()));
+        fish_collected.clear();
+        return;
+    }
+
+    if runtime.ast.is_none() && !runtime.failed {
+        let Some(source) = sources.get(&handle) else {
+            fish_collected.clear();
+            return;
+        };
+        let runtime = &mut *runtime;
+        // Top-level statements run once, before on_start
+        let started = runtime
+            .engine
+            .compile(&source.0)
+            .map_err(Box::<EvalAltResult>::from)
+            .and_then(|ast| {
+                runtime
+                    .engine
+                    .run_ast_with_scope(&mut runtime.scope, &ast)
+                    .map(|_| ast)
+            });
+        match started {
+            Ok(ast) => {
+                info!("Mission script started");
+                runtime.ast = Some(ast);
+                runtime.call("on_start", ());
+            }
+            Err(err) => {
+                warn!("Mission script failed to load: {}", err);
+                runtime.failed = true;
+            }
+        }
+    }
+
+    for _ in fish_collected.read() {
+        runtime.call("on_fish_collected", ());
+    }
+    runtime.call("on_update", (time.delta_secs() as f64,));
+}
+
+/// Carries out what the script asked for this frame
+#[allow(clippy::too_many_arguments)]
+fn script_request_system(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    missions: Res<Missions>,
+    mut runtime: ResMut<ScriptRuntime>,
+    mut display: ResMut<ScriptDisplay>,
+    mut mission_ended: EventWriter<MissionEnded>,
+    mut log_messages: EventWriter<LogMessage>,
+    mut rng: ResMut<ScriptRng>,
+    mut props: ResMut<PropLibrary>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let requests = std::mem::take(&mut runtime.bridge.lock().unwrap().requests);
+    for request in requests {
+        match request {
+            ScriptRequest::Spawn { prop, position } => {
+                props.spawn(
+                    &mut commands,
+                    &mut meshes,
+                    &mut materials,
+                    &mut rng.0,
+                    &prop,
+                    Transform::from_translation(position),
+                );
+            }
+            ScriptRequest::Objective(text) => {
+                if !text.is_empty() && runtime.objectives.last() != Some(&text) {
+                    runtime.objectives.push(text.clone());
+                }
+                display.objective = text;
+            }
+            ScriptRequest::Message(text, seconds) => {
+                log_messages.write(LogMessage {
+                    kind: LogKind::Radio,
+                    text: text.clone(),
+                });
+                display.message = text;
+                display.message_timer = seconds;
+            }
+            ScriptRequest::Complete => {
+                mission_ended.write(MissionEnded {
+                    mission: missions.0.get(runtime.mission).cloned().unwrap_or_default(),
+                    completed: true,
+                    reason: String::new(),
+                    objectives: runtime.objectives.clone(),
+                });
+                runtime.mission += 1;
+                runtime.restart();
+                let next = missions.0.get(runtime.mission);
+                runtime.source = next.map(|name| load_script(&asset_server, name));
+                match next {
+                    Some(name) => info!("Mission complete, starting {}", name),
+                    None => {
+                        info!("All missions complete");
+                        display.objective.clear();
+                    }
+                }
+            }
+            ScriptRequest::Fail(reason) => {
+                info!("Mission failed: {}", reason);
+                mission_ended.write(MissionEnded {
+                    mission: missions.0.get(runtime.mission).cloned().unwrap_or_default(),
+                    completed: false,
+                    reason,
+                    objectives: runtime.objectives.clone(),
+                });
+                // The dive carries on without a mission
+                runtime.restart();
+                runtime.source = None;
+                display.objective.clear();
+                break;
+            }
+        }
+    }
+}
+
+fn script_text_system(
+    time: Res<Time>,
+    mut display: ResMut<ScriptDisplay>,
+    mut objective_query: Query<&mut Text, (With<ObjectiveText>, Without<ScriptMessageText>)>,
+    mut message_query: Query<&mut Text, (With<ScriptMessageText>, Without<ObjectiveText>)>,
+) {
+    display.message_timer = (display.message_timer - time.delta_secs()).max(0.0);
+    if let Ok(mut text) = objective_query.single_mut() {
+        let line = if display.objective.is_empty() {
+            String::new()
+        } else {
+            format!("Objective: {}", display.objective)
+        };
+        if **text != line {
+            **text = line;
+        }
+    }
+    if let Ok(mut text) = message_query.single_mut() {
+        let line = if display.message_timer > 0.0 {
+            display.message.as_str()
+        } else {
+            ""
+        };
+        if **text != line {
+            **text = line.to_string();
+        }
+    }
+}
@@ -0,0 +1,325 @@
+use crate::controls::InputMap;
+use crate::{storage, DepthLighting};
+use bevy::{
+    audio::Volume,
+    prelude::*,
+    window::{MonitorSelection, PresentMode, PrimaryWindow, WindowMode},
+};
+use serde::{Deserialize, Serialize};
+
+/// Settings storage key (a file relative to the working directory on native builds)
+const SETTINGS_PATH: &str = "settings.ron";
+/// Raised whenever files saved by older builds need fixing up as they load
+const SETTINGS_VERSION: u32 = 1;
+
+/// Player preferences persisted between sessions
+#[derive(Resource, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    #[serde(default)] // Files from before versioning read as 0
+    pub version: u32,
+    pub hud: HudSettings,
+    pub accessibility: AccessibilitySettings,
+    pub controls: ControlSettings,
+    pub audio: AudioSettings,
+    pub graphics: GraphicsSettings,
+    pub gameplay: GameplaySettings,
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HudSettings {
+    pub ui_scale: f32,           // Multiplier applied to every UI node and font
+    pub sonar_corner: HudCorner, // Screen corner the sonar panel is docked to
+    pub sonar_size: SonarSize,
+    pub text_side: HudSide, // Side of the screen the status text sits on
+    pub show_text: bool,
+    pub show_sonar: bool,
+    pub fish_finder: bool, // Mark swept sonar contacts in the 3D view
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AccessibilitySettings {
+    pub tts_enabled: bool,     // Speak critical state changes aloud
+    pub camera_shake: f32,     // 0.0 disables camera shake, 1.0 is full strength
+    pub casual_buoyancy: bool, // Start every dive with the trim computer engaged
+}
+
+#[derive(Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ControlSettings {
+    pub touch_mode: TouchMode,
+    pub input_map: InputMap,
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioSettings {
+    pub master_volume: f32, // 0.0 to 1.0
+    pub music_volume: f32,
+    pub effects_volume: f32,
+    pub crew_voice: bool, // Speak crew callouts through text-to-speech as well as captioning them
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GraphicsSettings {
+    pub fullscreen: bool, // Borderless fullscreen on the current monitor
+    pub vsync: bool,
+    pub shadows: bool, // Sun shadows; noticeably more expensive over the large terrain
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GameplaySettings {
+    pub camera_speed: f32, // Multiplier for camera orbit speed
+    pub invert_camera_pitch: bool,
+    pub autosave: bool,
+    pub sustainable_fishing: bool, // Undersized catches cost points instead of scoring
+    pub adaptive_difficulty: bool, // Ease or tighten free dives to match how the last ones went
+}
+
+/// When to show the on-screen joysticks and tap targets
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum TouchMode {
+    #[default]
+    Auto, // Enabled on mobile targets or after the first touch
+    On,
+    Off,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum HudCorner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+/// How large the sonar panel is shown
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum SonarSize {
+    Small, // A widget tucked into its corner
+    #[default]
+    Medium,
+    Fullscreen, // Centered and as tall as the screen allows, for navigating by
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum HudSide {
+    Left,
+    Right,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            version: SETTINGS_VERSION,
+            hud: HudSettings::default(),
+            accessibility: AccessibilitySettings::default(),
+            controls: ControlSettings::default(),
+            audio: AudioSettings::default(),
+            graphics: GraphicsSettings::default(),
+            gameplay: GameplaySettings::default(),
+        }
+    }
+}
+
+impl Default for HudSettings {
+    fn default() -> Self {
+        Self {
+            ui_scale: 1.0,
+            sonar_corner: HudCorner::BottomRight,
+            sonar_size: SonarSize::default(),
+            text_side: HudSide::Left,
+            show_text: true,
+            show_sonar: true,
+            fish_finder: false,
+        }
+    }
+}
+
+impl Default for AccessibilitySettings {
+    fn default() -> Self {
+        Self {
+            tts_enabled: false,
+            camera_shake: 1.0,
+            casual_buoyancy: false,
+        }
+    }
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        Self {
+            master_volume: 1.0,
+            music_volume: 0.8,
+            effects_volume: 1.0,
+            crew_voice: false,
+        }
+    }
+}
+
+impl Default for GraphicsSettings {
+    fn default() -> Self {
+        Self {
+            fullscreen: false,
+            vsync: true,
+            shadows: false,
+        }
+    }
+}
+
+impl Default for GameplaySettings {
+    fn default() -> Self {
+        Self {
+            camera_speed: 1.0,
+            invert_camera_pitch: false,
+            autosave: true,
+            sustainable_fishing: false,
+            adaptive_difficulty: false,
+        }
+    }
+}
+
+impl HudCorner {
+    pub fn next(self) -> Self {
+        match self {
+            HudCorner::TopLeft => HudCorner::TopRight,
+            HudCorner::TopRight => HudCorner::BottomRight,
+            HudCorner::BottomRight => HudCorner::BottomLeft,
+            HudCorner::BottomLeft => HudCorner::TopLeft,
+        }
+    }
+}
+
+impl SonarSize {
+    pub fn next(self) -> Self {
+        match self {
+            SonarSize::Small => SonarSize::Medium,
+            SonarSize::Medium => SonarSize::Fullscreen,
+            SonarSize::Fullscreen => SonarSize::Small,
+        }
+    }
+}
+
+impl HudSide {
+    pub fn flipped(self) -> Self {
+        match self {
+            HudSide::Left => HudSide::Right,
+            HudSide::Right => HudSide::Left,
+        }
+    }
+}
+
+impl Settings {
+    /// Loads settings from disk, falling back to defaults if the file is missing or invalid
+    pub fn load() -> Self {
+        let mut settings = match storage::read(SETTINGS_PATH) {
+            Some(contents) => ron::from_str(&contents).unwrap_or_else(|err| {
+                warn!("Ignoring invalid {}: {}", SETTINGS_PATH, err);
+                Self::default()
+            }),
+            None => Self::default(),
+        };
+        settings.migrate();
+        for fix in settings.controls.input_map.validate() {
+            warn!("{}: {}", SETTINGS_PATH, fix);
+        }
+        settings
+    }
+
+    /// Brings settings saved by older builds up to the current version
+    fn migrate(&mut self) {
+        if self.version < 1 {
+            // The control panel used to open with Tab and the event log with backslash
+            self.controls.input_map.swap_panel_and_log_keys();
+        }
+        self.version = SETTINGS_VERSION;
+    }
+
+    pub fn save(&self) {
+        let pretty = ron::ser::PrettyConfig::default();
+        match ron::ser::to_string_pretty(self, pretty) {
+            Ok(contents) => {
+                if let Err(err) = storage::write(SETTINGS_PATH, &contents) {
+                    warn!("Failed to write {}: {}", SETTINGS_PATH, err);
+                }
+            }
+            Err(err) => warn!("Failed to serialize settings: {}", err),
+        }
+    }
+}
+
+pub struct SettingsPlugin;
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Settings::load())
+            .add_systems(
+                PreUpdate,
+                (
+                    apply_input_map_system,
+                    apply_audio_settings_system,
+                    apply_graphics_settings_system,
+                ),
+            )
+            .add_systems(Last, save_settings_system);
+    }
+}
+
+fn apply_input_map_system(settings: Res<Settings>, mut input_map: ResMut<InputMap>) {
+    if settings.is_changed() && *input_map != settings.controls.input_map {
+        *input_map = settings.controls.input_map.clone();
+    }
+}
+
+fn apply_audio_settings_system(settings: Res<Settings>, mut global_volume: ResMut<GlobalVolume>) {
+    if settings.is_changed() {
+        global_volume.volume = Volume::Linear(settings.audio.master_volume);
+    }
+}
+
+/// Applies window and lighting options; the sun is respawned with every dive, so
+/// newly added lights are configured too
+fn apply_graphics_settings_system(
+    settings: Res<Settings>,
+    mut window_query: Query<&mut Window, With<PrimaryWindow>>,
+    mut light_query: Query<(&mut DirectionalLight, Ref<DepthLighting>)>,
+) {
+    let graphics = &settings.graphics;
+    if settings.is_changed() {
+        if let Ok(mut window) = window_query.single_mut() {
+            let mode = if graphics.fullscreen {
+                WindowMode::BorderlessFullscreen(MonitorSelection::Current)
+            } else {
+                WindowMode::Windowed
+            };
+            if window.mode != mode {
+                window.mode = mode;
+            }
+            let present_mode = if graphics.vsync {
+                PresentMode::AutoVsync
+            } else {
+                PresentMode::AutoNoVsync
+            };
+            if window.present_mode != present_mode {
+                window.present_mode = present_mode;
+            }
+        }
+    }
+    for (mut light, marker) in light_query.iter_mut() {
+        if (settings.is_changed() || marker.is_added()) && light.shadows_enabled != graphics.shadows
+        {
+            light.shadows_enabled = graphics.shadows;
+        }
+    }
+}
+
+/// Writes settings back to disk whenever something modifies them
+fn save_settings_system(settings: Res<Settings>) {
+    if settings.is_changed() && !settings.is_added() {
+        settings.save();
+    }
+}
@@ -0,0 +1,620 @@
+use crate::controls::{key_name, Action, BindingSlot, InputMap, RESERVED_KEYS};
+use crate::settings::{Settings, TouchMode};
+use crate::GameAssets;
+use bevy::input::mouse::{MouseScrollUnit, MouseWheel};
+use bevy::{prelude::*, ui::FocusPolicy};
+
+const BUTTON_IDLE: Color = Color::srgba(0.1, 0.2, 0.3, 0.9);
+const BUTTON_HOVER: Color = Color::srgba(0.15, 0.35, 0.5, 0.95);
+const BUTTON_SELECTED: Color = Color::srgba(0.1, 0.5, 0.2, 0.9);
+const BUTTON_CONFLICT: Color = Color::srgba(0.6, 0.15, 0.1, 0.9);
+const ROW_HEIGHT: f32 = 26.0;
+const SCROLL_LINE: f32 = ROW_HEIGHT; // Pixels scrolled for each line of the mouse wheel
+
+/// Whether the settings screen is shown on top of the main or pause menu
+#[derive(States, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum SettingsMenuState {
+    #[default]
+    Closed,
+    Open,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+enum SettingsTab {
+    #[default]
+    Controls,
+    Audio,
+    Graphics,
+    Gameplay,
+}
+
+impl SettingsTab {
+    const ALL: [SettingsTab; 4] = [
+        SettingsTab::Controls,
+        SettingsTab::Audio,
+        SettingsTab::Graphics,
+        SettingsTab::Gameplay,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            SettingsTab::Controls => "Controls",
+            SettingsTab::Audio => "Audio",
+            SettingsTab::Graphics => "Graphics",
+            SettingsTab::Gameplay => "Gameplay",
+        }
+    }
+}
+
+/// Working copy edited by the menu; it only reaches `Settings` when applied
+#[derive(Resource)]
+struct SettingsDraft {
+    settings: Settings,
+    tab: SettingsTab,
+    rebinding: Option<(Action, BindingSlot)>, // Binding waiting for its next key press
+}
+
+/// Accessor for a boolean setting
+type BoolField = fn(&mut Settings) -> &mut bool;
+/// Accessor for a numeric setting
+type FloatField = fn(&mut Settings) -> &mut f32;
+
+#[derive(Component, Clone, Copy)]
+enum SettingsButton {
+    Tab(SettingsTab),
+    Toggle(BoolField),
+    Adjust {
+        field: FloatField,
+        delta: f32,
+        min: f32,
+        max: f32,
+    },
+    CycleTouchMode,
+    Rebind(Action, BindingSlot),
+    ResetBindings,
+    Apply,
+    Revert,
+    Back,
+}
+
+/// Container whose rows are rebuilt whenever the draft changes; it scrolls
+/// when a tab has more rows than the window has room for
+#[derive(Component)]
+struct SettingsContent;
+
+#[derive(Component)]
+struct SettingsStatusText;
+
+pub struct SettingsMenuPlugin;
+
+impl Plugin for SettingsMenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_state::<SettingsMenuState>()
+            .enable_state_scoped_entities::<SettingsMenuState>()
+            .add_systems(OnEnter(SettingsMenuState::Open), spawn_settings_menu)
+            .add_systems(
+                Update,
+                (
+                    settings_keyboard_system,
+                    settings_button_system,
+                    rebuild_settings_content_system,
+                    scroll_settings_content_system,
+                    settings_button_style_system,
+                    settings_status_system,
+                )
+                    .chain()
+                    .run_if(in_state(SettingsMenuState::Open)),
+            );
+    }
+}
+
+fn spawn_settings_menu(mut commands: Commands, assets: Res<GameAssets>, settings: Res<Settings>) {
+    commands.insert_resource(SettingsDraft {
+        settings: settings.clone(),
+        tab: SettingsTab::default(),
+        rebinding: None,
+    });
+
+    let font = assets.font.clone();
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                padding: UiRect::all(Val::Px(30.0)),
+                row_gap: Val::Px(12.0),
+                ..default()
+            },
+            BackgroundColor(Color::srgb(0.02, 0.08, 0.15)),
+            // Keep clicks from reaching the menu underneath
+            FocusPolicy::Block,
+            GlobalZIndex(10),
+            StateScoped(SettingsMenuState::Open),
+        ))
+        .with_children(|root| {
+            root.spawn(label_text("Settings", &font, 40.0));
+
+            root.spawn(Node {
+                column_gap: Val::Px(8.0),
+                ..default()
+            })
+            .with_children(|tabs| {
+                for tab in SettingsTab::ALL {
+                    spawn_button(tabs, &font, tab.label(), 140.0, SettingsButton::Tab(tab));
+                }
+            });
+
+            root.spawn((
+                Node {
+                    width: Val::Px(560.0),
+                    flex_direction: FlexDirection::Column,
+                    row_gap: Val::Px(2.0),
+                    flex_grow: 1.0,
+                    min_height: Val::Px(0.0),
+                    overflow: Overflow::scroll_y(),
+                    ..default()
+                },
+                ScrollPosition::default(),
+                SettingsContent,
+            ));
+
+            root.spawn((
+                label_text("", &font, 16.0),
+                TextColor(Color::srgb(0.9, 0.9, 0.7)),
+                SettingsStatusText,
+            ));
+
+            root.spawn(Node {
+                column_gap: Val::Px(8.0),
+                ..default()
+            })
+            .with_children(|actions| {
+                spawn_button(actions, &font, "Apply", 140.0, SettingsButton::Apply);
+                spawn_button(actions, &font, "Revert", 140.0, SettingsButton::Revert);
+                spawn_button(actions, &font, "Back", 140.0, SettingsButton::Back);
+            });
+        });
+}
+
+fn label_text(text: impl Into<String>, font: &Handle<Font>, font_size: f32) -> impl Bundle {
+    (
+        Text::new(text),
+        TextFont {
+            font_size,
+            font: font.clone(),
+            ..default()
+        },
+        TextColor(Color::WHITE),
+    )
+}
+
+fn spawn_button(
+    parent: &mut ChildSpawnerCommands,
+    font: &Handle<Font>,
+    label: &str,
+    width: f32,
+    button: SettingsButton,
+) {
+    parent
+        .spawn((
+            Button,
+            Node {
+                width: Val::Px(width),
+                height: Val::Px(ROW_HEIGHT),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                border: UiRect::all(Val::Px(1.0)),
+                ..default()
+            },
+            BorderColor(Color::srgb(0.4, 0.6, 0.7)),
+            BackgroundColor(BUTTON_IDLE),
+            button,
+        ))
+        .with_children(|button| {
+            button.spawn(label_text(label, font, 16.0));
+        });
+}
+
+/// One line of a settings tab: a label, the current value and the buttons that change it
+struct SettingRow {
+    label: String,
+    value: String,
+    buttons: Vec<(String, SettingsButton)>,
+}
+
+fn toggle_row(settings: &mut Settings, label: &str, field: BoolField) -> SettingRow {
+    SettingRow {
+        label: label.to_string(),
+        value: if *field(settings) { "On" } else { "Off" }.to_string(),
+        buttons: vec![("Toggle".to_string(), SettingsButton::Toggle(field))],
+    }
+}
+
+/// Slider-like row with -/+ buttons; `percent` shows 0..1 values as percentages
+fn adjust_row(
+    settings: &mut Settings,
+    label: &str,
+    field: FloatField,
+    (step, min, max): (f32, f32, f32),
+    percent: bool,
+) -> SettingRow {
+    let value = *field(settings);
+    SettingRow {
+        label: label.to_string(),
+        value: if percent {
+            format!("{:.0}%", value * 100.0)
+        } else {
+            format!("{:.2}x", value)
+        },
+        buttons: vec![
+            (
+                "-".to_string(),
+                SettingsButton::Adjust {
+                    field,
+                    delta: -step,
+                    min,
+                    max,
+                },
+            ),
+            (
+                "+".to_string(),
+                SettingsButton::Adjust {
+                    field,
+                    delta: step,
+                    min,
+                    max,
+                },
+            ),
+        ],
+    }
+}
+
+fn tab_rows(draft: &SettingsDraft) -> Vec<SettingRow> {
+    let mut settings = draft.settings.clone();
+    let settings = &mut settings;
+    match draft.tab {
+        SettingsTab::Controls => {
+            let mut rows = vec![SettingRow {
+                label: "Touch Controls".to_string(),
+                value: format!("{:?}", settings.controls.touch_mode),
+                buttons: vec![("Change".to_string(), SettingsButton::CycleTouchMode)],
+            }];
+            let input_map = &settings.controls.input_map;
+            for action in Action::ALL {
+                let binding = input_map.binding(action);
+                let conflicts = input_map.conflicts(action);
+                let value = match draft.rebinding {
+                    Some((rebinding, _)) if rebinding == action => {
+                        "Press a key (Esc cancels, Backspace clears)".to_string()
+                    }
+                    _ if !conflicts.is_empty() => format!("Also used by {}", conflicts[0].label()),
+                    _ => String::new(),
+                };
+                let buttons = [BindingSlot::Primary, BindingSlot::Secondary]
+                    .into_iter()
+                    .map(|slot| {
+                        let label = binding
+                            .get(slot)
+                            .map(key_name)
+                            .unwrap_or_else(|| "-".to_string());
+                        (label, SettingsButton::Rebind(action, slot))
+                    })
+                    .collect();
+                rows.push(SettingRow {
+                    label: action.label().to_string(),
+                    value,
+                    buttons,
+                });
+            }
+            rows.push(SettingRow {
+                label: "Key Bindings".to_string(),
+                value: String::new(),
+                buttons: vec![("Reset All".to_string(), SettingsButton::ResetBindings)],
+            });
+            rows
+        }
+        SettingsTab::Audio => vec![
+            adjust_row(
+                settings,
+                "Master Volume",
+                |s| &mut s.audio.master_volume,
+                (0.1, 0.0, 1.0),
+                true,
+            ),
+            adjust_row(
+                settings,
+                "Music Volume",
+                |s| &mut s.audio.music_volume,
+                (0.1, 0.0, 1.0),
+                true,
+            ),
+            adjust_row(
+                settings,
+                "Effects Volume",
+                |s| &mut s.audio.effects_volume,
+                (0.1, 0.0, 1.0),
+                true,
+            ),
+            toggle_row(settings, "Crew Voice", |s| &mut s.audio.crew_voice),
+        ],
+        SettingsTab::Graphics => vec![
+            toggle_row(settings, "Fullscreen", |s| &mut s.graphics.fullscreen),
+            toggle_row(settings, "VSync", |s| &mut s.graphics.vsync),
+            toggle_row(settings, "Shadows", |s| &mut s.graphics.shadows),
+            adjust_row(
+                settings,
+                "HUD Scale",
+                |s| &mut s.hud.ui_scale,
+                (0.1, 0.5, 3.0),
+                false,
+            ),
+            toggle_row(settings, "Status Text", |s| &mut s.hud.show_text),
+            toggle_row(settings, "Sonar Panel", |s| &mut s.hud.show_sonar),
+            toggle_row(settings, "Fish Finder", |s| &mut s.hud.fish_finder),
+        ],
+        SettingsTab::Gameplay => vec![
+            adjust_row(
+                settings,
+                "Camera Speed",
+                |s| &mut s.gameplay.camera_speed,
+                (0.25, 0.25, 3.0),
+                false,
+            ),
+            toggle_row(settings, "Invert Camera Pitch", |s| {
+                &mut s.gameplay.invert_camera_pitch
+            }),
+            toggle_row(settings, "Autosave", |s| &mut s.gameplay.autosave),
+            toggle_row(settings, "Sustainable Fishing", |s| {
+                &mut s.gameplay.sustainable_fishing
+            }),
+            toggle_row(settings, "Adaptive Difficulty", |s| {
+                &mut s.gameplay.adaptive_difficulty
+            }),
+            adjust_row(
+                settings,
+                "Camera Shake",
+                |s| &mut s.accessibility.camera_shake,
+                (0.1, 0.0, 1.0),
+                true,
+            ),
+            toggle_row(settings, "Spoken Announcements", |s| {
+                &mut s.accessibility.tts_enabled
+            }),
+            toggle_row(settings, "Casual Buoyancy", |s| {
+                &mut s.accessibility.casual_buoyancy
+            }),
+        ],
+    }
+}
+
+/// Rows are rebuilt in place, so a rebind keeps the list where it was; a new
+/// tab starts at its top
+fn rebuild_settings_content_system(
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+    draft: Res<SettingsDraft>,
+    mut content_query: Query<(Entity, &mut ScrollPosition), With<SettingsContent>>,
+    mut shown_tab: Local<Option<SettingsTab>>,
+) {
+    if !draft.is_changed() {
+        return;
+    }
+    let Ok((content, mut scroll)) = content_query.single_mut() else {
+        return;
+    };
+    if shown_tab.replace(draft.tab) != Some(draft.tab) {
+        scroll.offset_y = 0.0;
+    }
+    let font = assets.font.clone();
+    let rows = tab_rows(&draft);
+
+    commands
+        .entity(content)
+        .despawn_related::<Children>()
+        .with_children(|content| {
+            for row in rows {
+                content
+                    .spawn(Node {
+                        height: Val::Px(ROW_HEIGHT),
+                        flex_shrink: 0.0,
+                        align_items: AlignItems::Center,
+                        column_gap: Val::Px(8.0),
+                        ..default()
+                    })
+                    .with_children(|line| {
+                        line.spawn((
+                            label_text(row.label, &font, 16.0),
+                            Node {
+                                width: Val::Px(220.0),
+                                ..default()
+                            },
+                        ));
+                        line.spawn((
+                            label_text(row.value, &font, 16.0),
+                            TextColor(Color::srgb(0.7, 0.9, 1.0)),
+                            Node {
+                                width: Val::Px(200.0),
+                                ..default()
+                            },
+                        ));
+                        for (label, button) in row.buttons {
+                            let width = match button {
+                                SettingsButton::Adjust { .. } => 40.0,
+                                _ => 90.0,
+                            };
+                            spawn_button(line, &font, &label, width, button);
+                        }
+                    });
+            }
+        });
+}
+
+fn scroll_settings_content_system(
+    mut wheel_events: EventReader<MouseWheel>,
+    mut content_query: Query<&mut ScrollPosition, With<SettingsContent>>,
+) {
+    let pixels: f32 = wheel_events
+        .read()
+        .map(|event| match event.unit {
+            MouseScrollUnit::Line => event.y * SCROLL_LINE,
+            MouseScrollUnit::Pixel => event.y,
+        })
+        .sum();
+    if pixels == 0.0 {
+        return;
+    }
+    for mut scroll in content_query.iter_mut() {
+        // The layout keeps the offset within the rows
+        scroll.offset_y = (scroll.offset_y - pixels).max(0.0);
+    }
+}
+
+/// Captures the key for a pending rebind; otherwise Escape leaves the menu.
+/// Reserved keys are ignored so the capture keeps waiting for a usable one.
+fn settings_keyboard_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut draft: ResMut<SettingsDraft>,
+    mut next_state: ResMut<NextState<SettingsMenuState>>,
+) {
+    let Some(key) = keyboard_input.get_just_pressed().next().copied() else {
+        return;
+    };
+    match draft.rebinding {
+        Some(_) if key == KeyCode::Escape => draft.rebinding = None,
+        Some(_) if RESERVED_KEYS.contains(&key) => {}
+        Some((action, slot)) => {
+            let new_key = (key != KeyCode::Backspace).then_some(key);
+            draft
+                .settings
+                .controls
+                .input_map
+                .bind(action, slot, new_key);
+            draft.rebinding = None;
+        }
+        None if key == KeyCode::Escape => next_state.set(SettingsMenuState::Closed),
+        None => {}
+    }
+}
+
+fn settings_button_system(
+    button_query: Query<(&Interaction, &SettingsButton), Changed<Interaction>>,
+    mut draft: ResMut<SettingsDraft>,
+    mut settings: ResMut<Settings>,
+    mut next_state: ResMut<NextState<SettingsMenuState>>,
+) {
+    for (interaction, button) in button_query.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        match *button {
+            SettingsButton::Tab(tab) => {
+                draft.tab = tab;
+                draft.rebinding = None;
+            }
+            SettingsButton::Toggle(field) => {
+                let value = field(&mut draft.settings);
+                *value = !*value;
+            }
+            SettingsButton::Adjust {
+                field,
+                delta,
+                min,
+                max,
+            } => {
+                let value = field(&mut draft.settings);
+                // Round to the step grid so repeated presses don't drift
+                *value = (((*value + delta) / delta.abs()).round() * delta.abs()).clamp(min, max);
+            }
+            SettingsButton::CycleTouchMode => {
+                let touch_mode = &mut draft.settings.controls.touch_mode;
+                *touch_mode = match *touch_mode {
+                    TouchMode::Auto => TouchMode::On,
+                    TouchMode::On => TouchMode::Off,
+                    TouchMode::Off => TouchMode::Auto,
+                };
+            }
+            SettingsButton::Rebind(action, slot) => draft.rebinding = Some((action, slot)),
+            SettingsButton::ResetBindings => {
+                draft.settings.controls.input_map = InputMap::default();
+                draft.rebinding = None;
+            }
+            SettingsButton::Apply => {
+                if *settings != draft.settings {
+                    *settings = draft.settings.clone();
+                }
+            }
+            SettingsButton::Revert => {
+                draft.settings = settings.clone();
+                draft.rebinding = None;
+            }
+            SettingsButton::Back => next_state.set(SettingsMenuState::Closed),
+        }
+    }
+}
+
+/// Highlights the selected tab, the binding being captured, conflicting keys and hovered buttons
+fn settings_button_style_system(
+    draft: Res<SettingsDraft>,
+    mut button_query: Query<(&Interaction, &SettingsButton, &mut BackgroundColor)>,
+) {
+    let input_map = &draft.settings.controls.input_map;
+    for (interaction, button, mut background) in button_query.iter_mut() {
+        let selected = match *button {
+            SettingsButton::Tab(tab) => tab == draft.tab,
+            SettingsButton::Rebind(action, slot) => draft.rebinding == Some((action, slot)),
+            _ => false,
+        };
+        // A key conflicts when another action is bound to the same key
+        let conflicting = match *button {
+            SettingsButton::Rebind(action, slot) => {
+                input_map.binding(action).get(slot).is_some_and(|key| {
+                    input_map.conflicts(action).iter().any(|other| {
+                        input_map
+                            .binding(*other)
+                            .keys()
+                            .any(|other_key| other_key == key)
+                    })
+                })
+            }
+            _ => false,
+        };
+        let color = match (selected, conflicting, interaction) {
+            (true, _, _) => BUTTON_SELECTED,
+            (false, true, _) => BUTTON_CONFLICT,
+            (false, false, Interaction::None) => BUTTON_IDLE,
+            (false, false, _) => BUTTON_HOVER,
+        };
+        if background.0 != color {
+            background.0 = color;
+        }
+    }
+}
+
+fn settings_status_system(
+    draft: Res<SettingsDraft>,
+    settings: Res<Settings>,
+    mut text_query: Query<&mut Text, With<SettingsStatusText>>,
+) {
+    let has_conflicts = Action::ALL.into_iter().any(|action| {
+        !draft
+            .settings
+            .controls
+            .input_map
+            .conflicts(action)
+            .is_empty()
+    });
+    let status = match (draft.settings != *settings, has_conflicts) {
+        (true, true) => "Some keys are bound to more than one action. Apply anyway or Revert",
+        (true, false) => "Unapplied changes: Apply to keep them, Revert to discard",
+        (false, true) => "Some keys are bound to more than one action",
+        (false, false) => "",
+    };
+    for mut text in text_query.iter_mut() {
+        if **text != status {
+            **text = status.to_string();
+        }
+    }
+}
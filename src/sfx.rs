@@ -0,0 +1,174 @@
+use crate::settings::Settings;
+use bevy::audio::{AddAudioSource, Decodable, Source, Volume};
+use bevy::prelude::*;
+use std::sync::Arc;
+use std::time::Duration;
+
+const SAMPLE_RATE: u32 = 44_100;
+const ATTACK: f32 = 0.005; // Seconds to fade each note in, avoiding clicks
+
+/// One tone in a synthesized effect
+#[derive(Clone, Copy, Debug)]
+pub struct Note {
+    pub frequency: f32, // Hz
+    pub start: f32,     // Seconds from the start of the effect
+    pub duration: f32,  // Seconds until the note has decayed away
+    pub volume: f32,    // 0..1
+}
+
+/// Sound effect synthesized from decaying bell-like tones, so effects need no asset files
+#[derive(Asset, TypePath, Clone)]
+pub struct Sfx {
+    notes: Arc<[Note]>,
+}
+
+impl Sfx {
+    pub fn new(notes: Vec<Note>) -> Self {
+        Self {
+            notes: notes.into(),
+        }
+    }
+
+    fn length(&self) -> f32 {
+        self.notes
+            .iter()
+            .map(|note| note.start + note.duration)
+            .fold(0.0, f32::max)
+    }
+}
+
+pub struct SfxDecoder {
+    notes: Arc<[Note]>,
+    sample: u32,
+    samples: u32,
+}
+
+impl Iterator for SfxDecoder {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.sample >= self.samples {
+            return None;
+        }
+        let t = self.sample as f32 / SAMPLE_RATE as f32;
+        self.sample += 1;
+        let value = self
+            .notes
+            .iter()
+            .filter(|note| t >= note.start && t < note.start + note.duration)
+            .map(|note| {
+                let local = t - note.start;
+                let envelope = (local / ATTACK).min(1.0) * (-5.0 * local / note.duration).exp();
+                let phase = std::f32::consts::TAU * note.frequency * local;
+                // A quieter octave overtone gives the tone a metallic ring
+                note.volume * envelope * (phase.sin() + 0.3 * (2.0 * phase).sin())
+            })
+            .sum::<f32>();
+        Some(value.clamp(-1.0, 1.0))
+    }
+}
+
+impl Source for SfxDecoder {
+    fn current_frame_len(&self) -> Option<usize> {
+        Some((self.samples - self.sample) as usize)
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        Some(Duration::from_secs_f32(
+            self.samples as f32 / SAMPLE_RATE as f32,
+        ))
+    }
+}
+
+impl Decodable for Sfx {
+    type DecoderItem = f32;
+    type Decoder = SfxDecoder;
+
+    fn decoder(&self) -> SfxDecoder {
+        SfxDecoder {
+            notes: self.notes.clone(),
+            sample: 0,
+            samples: (self.length() * SAMPLE_RATE as f32) as u32,
+        }
+    }
+}
+
+/// Endless tone for sounds that travel with an entity, such as a torpedo's motor.
+/// The frequency is rounded to whole Hz so the tone loops every second without a click.
+#[derive(Asset, TypePath, Clone, Copy)]
+pub struct Hum {
+    pub frequency: f32, // Hz
+    pub volume: f32,    // 0..1
+}
+
+pub struct HumDecoder {
+    hum: Hum,
+    sample: u32, // Wraps every second so the phase stays precise
+}
+
+impl Iterator for HumDecoder {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let t = self.sample as f32 / SAMPLE_RATE as f32;
+        self.sample = (self.sample + 1) % SAMPLE_RATE;
+        let phase = std::f32::consts::TAU * self.hum.frequency.round() * t;
+        // Odd harmonics give it a buzzing, motor-like edge
+        let value = phase.sin() + 0.4 * (3.0 * phase).sin() + 0.2 * (5.0 * phase).sin();
+        Some((self.hum.volume * value / 1.6).clamp(-1.0, 1.0))
+    }
+}
+
+impl Source for HumDecoder {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+impl Decodable for Hum {
+    type DecoderItem = f32;
+    type Decoder = HumDecoder;
+
+    fn decoder(&self) -> HumDecoder {
+        HumDecoder {
+            hum: *self,
+            sample: 0,
+        }
+    }
+}
+
+pub struct SfxPlugin;
+
+impl Plugin for SfxPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_audio_source::<Sfx>().add_audio_source::<Hum>();
+    }
+}
+
+/// Plays an effect once at the effects volume
+pub fn play(commands: &mut Commands, sfx: Handle<Sfx>, settings: &Settings) {
+    commands.spawn((
+        AudioPlayer(sfx),
+        PlaybackSettings::DESPAWN.with_volume(Volume::Linear(settings.audio.effects_volume)),
+    ));
+}
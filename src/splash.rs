@@ -0,0 +1,134 @@
+//! Splashes: whatever breaks the water surface, a rising bubble, a torpedo
+//! broaching or the boat itself surfacing or diving, leaves a ring of foam
+//! that spreads out and fades. The rings ride the swell at the wave height
+//! under them, so they sit on the water rather than at its rest level.
+
+use crate::{wave_height, AppState, GameSet, WaveTime};
+use bevy::prelude::*;
+use std::f32::consts::FRAC_PI_2;
+
+const BREACH_DEPTH: f32 = 0.1; // Meters under the swell that still count as breaking it
+const RING_TIME: f32 = 1.6; // Seconds a ring takes to spread out and fade
+const RING_SPREAD: f32 = 2.0; // How many times its starting size a ring grows to
+const RING_OPACITY: f32 = 0.7; // Opacity of a fresh ring
+const FOAM_LIFT: f32 = 0.05; // Meters over the surface the foam is drawn, so it shows on the water
+const MAX_RINGS: usize = 96;
+
+/// Something breaking the surface at `position`, throwing a ring of foam
+/// `size` meters in radius
+#[derive(Event, Clone, Copy)]
+pub struct SurfaceBreak {
+    pub position: Vec3,
+    pub size: f32,
+}
+
+/// Marks something that throws a splash whenever it crosses the surface
+#[derive(Component)]
+pub struct Breaching {
+    pub size: f32,       // Radius of the ring it throws, in meters
+    above: Option<bool>, // Whether it was at the surface last frame
+}
+
+impl Breaching {
+    pub fn new(size: f32) -> Self {
+        Self { size, above: None }
+    }
+}
+
+/// Foam rings still spreading
+#[derive(Resource, Default)]
+struct FoamRings(Vec<Ring>);
+
+struct Ring {
+    center: Vec2, // Level position on the water
+    size: f32,
+    age: f32,
+}
+
+pub struct SplashPlugin;
+
+impl Plugin for SplashPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SurfaceBreak>()
+            .init_resource::<FoamRings>()
+            .add_systems(OnEnter(AppState::InGame), reset_foam_rings)
+            .add_systems(
+                Update,
+                (breach_system, ring_system, draw_rings_system)
+                    .chain()
+                    .in_set(GameSet::Presentation),
+            );
+    }
+}
+
+fn reset_foam_rings(mut commands: Commands) {
+    commands.insert_resource(FoamRings::default());
+}
+
+/// Watches breaching things cross the swell, going up or down
+fn breach_system(
+    mut breaks: EventWriter<SurfaceBreak>,
+    mut query: Query<(&mut Breaching, &Transform)>,
+    wave_time: Res<WaveTime>,
+) {
+    for (mut breaching, transform) in query.iter_mut() {
+        let position = transform.translation;
+        let surface = wave_height(position.x, position.z, wave_time.elapsed);
+        let above = position.y >= surface - BREACH_DEPTH;
+        if breaching
+            .above
+            .replace(above)
+            .is_some_and(|was| was != above)
+        {
+            breaks.write(SurfaceBreak {
+                position,
+                size: breaching.size,
+            });
+        }
+    }
+}
+
+/// Starts a ring for each break and ages the rest
+fn ring_system(
+    mut rings: ResMut<FoamRings>,
+    mut breaks: EventReader<SurfaceBreak>,
+    time: Res<Time>,
+) {
+    let delta = time.delta_secs();
+    rings.0.retain_mut(|ring| {
+        ring.age += delta;
+        ring.age < RING_TIME
+    });
+    for surface_break in breaks.read() {
+        if rings.0.len() >= MAX_RINGS {
+            rings.0.remove(0);
+        }
+        rings.0.push(Ring {
+            center: Vec2::new(surface_break.position.x, surface_break.position.z),
+            size: surface_break.size,
+            age: 0.0,
+        });
+    }
+}
+
+/// Draws each ring flat on the water at the height of the swell under it,
+/// with a fainter inner ring of broken water
+fn draw_rings_system(mut gizmos: Gizmos, rings: Res<FoamRings>, wave_time: Res<WaveTime>) {
+    for ring in rings.0.iter() {
+        let progress = ring.age / RING_TIME;
+        let radius = ring.size * (1.0 + (RING_SPREAD - 1.0) * progress);
+        let opacity = RING_OPACITY * (1.0 - progress);
+        let height = wave_height(ring.center.x, ring.center.y, wave_time.elapsed) + FOAM_LIFT;
+        // Gizmo circles lie in the XY plane; turn them flat onto the water
+        let flat = Isometry3d::new(
+            Vec3::new(ring.center.x, height, ring.center.y),
+            Quat::from_rotation_x(FRAC_PI_2),
+        );
+        gizmos.circle(flat, radius, Color::srgba(0.95, 0.98, 1.0, opacity));
+        gizmos.circle(
+            flat,
+            radius * 0.6,
+            Color::srgba(0.85, 0.93, 1.0, opacity * 0.5),
+        );
+    }
+}
@@ -0,0 +1,179 @@
+use crate::props::PropLibrary;
+use crate::tuning::depth_factor;
+use crate::{AppState, BallastState, GameAssets, GameMode, GameSet, Submarine, Tuning, WorldSeed};
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+const BUOY_COUNT: usize = 4;
+const BUOY_DEPTH: f32 = 8.0; // Float depth; the tether runs from here to the sea floor
+const CHARGE_RADIUS: f32 = 10.0;
+const STATION_KEEPING_SPEED: f32 = 2.0; // Slower than this counts as holding station
+const CHARGE_RATE: f32 = 1.0; // Electricity per second while holding station
+const AIR_RATE: f32 = 0.02; // Compressed air per second at the surface; a tenth of the surface compressor
+
+/// Anchored buoy that tops up power and air for a submarine holding station beside it
+#[derive(Component)]
+pub struct ChargingBuoy {
+    pub phase: f32,
+}
+
+/// Beacon lamp on top of a buoy; blinks quickly while charging
+#[derive(Component)]
+pub struct BuoyBeacon {
+    pub material: Handle<StandardMaterial>,
+}
+
+#[derive(Component)]
+struct ChargingText;
+
+/// Whether the submarine is being charged this frame
+#[derive(Resource, Default)]
+struct Charging(bool);
+
+pub struct StationsPlugin;
+
+impl Plugin for StationsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Charging>()
+            .add_systems(
+                OnEnter(AppState::InGame),
+                spawn_buoys.run_if(|mode: Res<GameMode>| *mode != GameMode::TimeTrial),
+            )
+            .add_systems(
+                Update,
+                (
+                    charging_system.in_set(GameSet::Simulation),
+                    (buoy_animation_system, charging_text_system).in_set(GameSet::Presentation),
+                ),
+            );
+    }
+}
+
+/// Buoys sit mid-map, between the start and the far edges, so they work as waypoints
+fn spawn_buoys(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut props: ResMut<PropLibrary>,
+    assets: Res<GameAssets>,
+    seed: Res<WorldSeed>,
+) {
+    commands.insert_resource(Charging::default());
+    let mut rng = StdRng::seed_from_u64(seed.0 ^ 0x4255_4F59);
+    for i in 0..BUOY_COUNT {
+        // Spread around the compass so each quarter of the map has one
+        let angle = (i as f32 + rng.gen::<f32>() * 0.6) * std::f32::consts::TAU / BUOY_COUNT as f32;
+        let distance = 100.0 + rng.gen::<f32>() * 150.0;
+        let position = Vec3::new(angle.cos() * distance, -BUOY_DEPTH, angle.sin() * distance);
+        props.spawn(
+            &mut commands,
+            &mut meshes,
+            &mut materials,
+            &mut rng,
+            "buoy",
+            Transform::from_translation(position),
+        );
+    }
+
+    commands.spawn((
+        Text::new(""),
+        TextFont {
+            font_size: 20.0,
+            font: assets.font.clone(),
+            ..default()
+        },
+        TextColor(Color::srgb(0.4, 1.0, 0.5)),
+        TextLayout::new_with_justify(JustifyText::Center),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Percent(25.0),
+            width: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        ChargingText,
+        StateScoped(AppState::InGame),
+    ));
+}
+
+/// Holding station near a buoy slowly recharges the batteries and the air flasks
+fn charging_system(
+    time: Res<Time>,
+    mut charging: ResMut<Charging>,
+    mut ballast_state: ResMut<BallastState>,
+    tuning: Res<Tuning>,
+    submarine_query: Query<(&Transform, &Velocity), With<Submarine>>,
+    buoy_query: Query<&Transform, With<ChargingBuoy>>,
+) {
+    charging.0 = false;
+    let Ok((submarine, velocity)) = submarine_query.single() else {
+        return;
+    };
+    if velocity.linvel.length() > STATION_KEEPING_SPEED
+        || !buoy_query
+            .iter()
+            .any(|buoy| buoy.translation.distance(submarine.translation) < CHARGE_RADIUS)
+    {
+        return;
+    }
+    charging.0 = true;
+    let delta = time.delta_secs();
+    ballast_state.electricity = (ballast_state.electricity + CHARGE_RATE * delta).min(100.0);
+    // The buoy's compressor has the depth's water pressure to pump against
+    let air = AIR_RATE * depth_factor(tuning.compressor_depth_loss, -submarine.translation.y);
+    ballast_state.compressed_air = (ballast_state.compressed_air + air * delta).min(1.0);
+}
+
+/// Buoys bob on their tethers; beacons blink slowly, quickly while charging
+fn buoy_animation_system(
+    time: Res<Time>,
+    charging: Res<Charging>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut buoy_query: Query<(&ChargingBuoy, &mut Transform)>,
+    beacon_query: Query<&BuoyBeacon>,
+) {
+    let t = time.elapsed_secs();
+    for (buoy, mut transform) in buoy_query.iter_mut() {
+        transform.translation.y = -BUOY_DEPTH + 0.3 * (t * 0.8 + buoy.phase).sin();
+    }
+    let rate = if charging.0 { 6.0 } else { 1.5 };
+    let emissive = if (t * rate).fract() < 0.5 {
+        LinearRgba::rgb(0.5, 4.0, 1.0)
+    } else {
+        LinearRgba::BLACK
+    };
+    for beacon in beacon_query.iter() {
+        // Only touch the material when it flips, so it isn't re-uploaded every frame
+        if materials
+            .get(&beacon.material)
+            .is_some_and(|material| material.emissive != emissive)
+        {
+            if let Some(material) = materials.get_mut(&beacon.material) {
+                material.emissive = emissive;
+            }
+        }
+    }
+}
+
+fn charging_text_system(
+    charging: Res<Charging>,
+    ballast_state: Res<BallastState>,
+    mut text_query: Query<&mut Text, With<ChargingText>>,
+) {
+    let Ok(mut text) = text_query.single_mut() else {
+        return;
+    };
+    let line = if charging.0 {
+        format!(
+            "Charging - Electricity {:.0}%  Air {:.0}%",
+            ballast_state.electricity,
+            ballast_state.compressed_air * 100.0
+        )
+    } else {
+        String::new()
+    };
+    if **text != line {
+        **text = line;
+    }
+}
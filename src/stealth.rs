@@ -0,0 +1,73 @@
+//! Stealth: how much noise the submarine puts into the water. Speed makes
+//! most of it, and the compressor, the diesels on the surface, a cavitating
+//! screw near the surface and a foul hull add more. Fish hear it from further
+//! away the louder the boat runs, so a quiet approach gets closer.
+
+use crate::biofouling::Biofouling;
+use crate::{AppState, BallastState, GameSet, Submarine, Tuning};
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+const QUIET_NOISE: f32 = 0.1; // Hull and machinery hum with the motor stopped
+const SPEED_NOISE: f32 = 0.6; // Added at full speed
+const COMPRESSOR_NOISE: f32 = 0.2;
+const DIESEL_NOISE: f32 = 0.15; // The diesels run shallower than `SURFACE_DEPTH`
+const CAVITATION_NOISE: f32 = 0.3; // Added by a screw driven flat out at the surface
+const FOULING_NOISE: f32 = 0.15; // Added at full speed by a fully fouled hull
+const SURFACE_DEPTH: f32 = 1.0;
+const CAVITATION_SPEED: f32 = 0.5; // Share of full speed above which the screw cavitates
+const CAVITATION_DEPTH: f32 = 15.0; // Water pressure suppresses cavitation below this depth
+const SETTLE_RATE: f32 = 1.0; // Change in noise per second as the boat speeds up or slows
+
+/// The submarine's radiated noise, from 0 (silent) to 1 (as loud as it gets)
+#[derive(Resource, Default)]
+pub struct SubmarineNoise(pub f32);
+
+pub struct StealthPlugin;
+
+impl Plugin for StealthPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SubmarineNoise>()
+            .add_systems(OnEnter(AppState::InGame), reset_noise)
+            .add_systems(
+                Update,
+                noise_system
+                    .before(crate::fish_movement)
+                    .in_set(GameSet::Simulation),
+            );
+    }
+}
+
+fn reset_noise(mut commands: Commands) {
+    commands.insert_resource(SubmarineNoise(QUIET_NOISE));
+}
+
+fn noise_system(
+    mut noise: ResMut<SubmarineNoise>,
+    ballast_state: Res<BallastState>,
+    biofouling: Res<Biofouling>,
+    tuning: Res<Tuning>,
+    submarine_query: Query<(&Transform, &Velocity), With<Submarine>>,
+    time: Res<Time>,
+) {
+    let Ok((transform, velocity)) = submarine_query.single() else {
+        return;
+    };
+    let depth = -transform.translation.y;
+    let speed = (velocity.linvel.length() / tuning.speed).min(1.0);
+    let cavitation = ((speed - CAVITATION_SPEED) / (1.0 - CAVITATION_SPEED)).max(0.0)
+        * (1.0 - depth / CAVITATION_DEPTH).clamp(0.0, 1.0);
+
+    let mut target = QUIET_NOISE
+        + SPEED_NOISE * speed
+        + CAVITATION_NOISE * cavitation
+        + FOULING_NOISE * biofouling.growth * speed;
+    if ballast_state.compressor_on {
+        target += COMPRESSOR_NOISE;
+    }
+    if depth < SURFACE_DEPTH {
+        target += DIESEL_NOISE;
+    }
+    let step = SETTLE_RATE * time.delta_secs();
+    noise.0 += (target.min(1.0) - noise.0).clamp(-step, step);
+}
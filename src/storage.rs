@@ -0,0 +1,89 @@
+//! Small key/value persistence layer. Native builds store each key as a file in
+//! the working directory; web builds use the browser's `localStorage`.
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn read(key: &str) -> Option<String> {
+    std::fs::read_to_string(key).ok()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn write(key: &str, contents: &str) -> Result<(), String> {
+    if let Some(parent) = std::path::Path::new(key).parent() {
+        std::fs::create_dir_all(parent).map_err(|err| err.to_string())?;
+    }
+    std::fs::write(key, contents).map_err(|err| err.to_string())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn remove(key: &str) -> Result<(), String> {
+    match std::fs::remove_file(key) {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.to_string()),
+        _ => Ok(()),
+    }
+}
+
+/// Keys stored under `dir/`, e.g. `list("saves")` returns `saves/autosave.ron`
+#[cfg(not(target_arch = "wasm32"))]
+pub fn list(dir: &str) -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().is_file())
+        .map(|entry| format!("{}/{}", dir, entry.file_name().to_string_lossy()))
+        .collect()
+}
+
+/// Seconds since the Unix epoch
+#[cfg(not(target_arch = "wasm32"))]
+pub fn unix_time() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(target_arch = "wasm32")]
+fn local_storage() -> Option<web_sys::Storage> {
+    web_sys::window()?.local_storage().ok().flatten()
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn read(key: &str) -> Option<String> {
+    local_storage()?.get_item(key).ok().flatten()
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn write(key: &str, contents: &str) -> Result<(), String> {
+    local_storage()
+        .ok_or_else(|| "localStorage is unavailable".to_string())?
+        .set_item(key, contents)
+        .map_err(|err| format!("{:?}", err))
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn remove(key: &str) -> Result<(), String> {
+    local_storage()
+        .ok_or_else(|| "localStorage is unavailable".to_string())?
+        .remove_item(key)
+        .map_err(|err| format!("{:?}", err))
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn list(dir: &str) -> Vec<String> {
+    let Some(storage) = local_storage() else {
+        return Vec::new();
+    };
+    let prefix = format!("{}/", dir);
+    let length = storage.length().unwrap_or(0);
+    (0..length)
+        .filter_map(|index| storage.key(index).ok().flatten())
+        .filter(|key| key.starts_with(&prefix))
+        .collect()
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn unix_time() -> u64 {
+    (js_sys::Date::now() / 1000.0) as u64
+}
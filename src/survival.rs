@@ -0,0 +1,290 @@
+use crate::combat::{spawn_hostile, CombatAssets, Hostile};
+use crate::hud::spawn_game_over_screen;
+use crate::rescue::DistressBeacon;
+use crate::save::format_play_time;
+use crate::{
+    storage, AppState, BallastState, Fish, GameAssets, GameMode, GameSet, GameState, Submarine,
+    WorldSeed,
+};
+use bevy::prelude::*;
+use rand::{rngs::StdRng, seq::IteratorRandom, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+const LEADERBOARD_PATH: &str = "survival-leaderboard.ron";
+const LEADERBOARD_SIZE: usize = 10;
+const RAMP_TIME: f32 = 90.0; // Seconds for the degradation rates to grow by their base amount
+const OXYGEN_DRAIN: f32 = 0.3; // Extra oxygen percent lost per second underwater, before ramping
+const BATTERY_DRAIN: f32 = 0.15; // Electricity lost per second, before ramping
+const HULL_STRESS: f32 = 0.05; // Health lost per second at the surface, before ramping
+const HULL_STRESS_DEPTH: f32 = 10.0; // Depth at which hull stress doubles
+const FISH_CULL_INTERVAL: f32 = 20.0;
+const FISH_CULL_FRACTION: f32 = 0.1;
+const PREDATOR_FIRST_SPAWN: f32 = 30.0; // Seconds between predators at the start of a run
+const PREDATOR_MIN_SPAWN: f32 = 6.0;
+const PREDATOR_SPAWN_DISTANCE: f32 = 70.0;
+const PREDATOR_BITE_DISTANCE: f32 = 2.5;
+const PREDATOR_BITE_DAMAGE: f32 = 15.0;
+const PREDATOR_HEALTH: f32 = 50.0; // One torpedo
+
+/// Grows with every minute and a half survived and scales all the pressure on the player
+fn intensity(elapsed: f32) -> f32 {
+    1.0 + elapsed / RAMP_TIME
+}
+
+#[derive(Component)]
+struct SurvivalText;
+
+/// One finished survival run
+#[derive(Clone, Serialize, Deserialize)]
+pub struct LeaderboardEntry {
+    pub time: f32,
+    pub score: u32,
+    pub timestamp: u64,
+}
+
+/// Progress of the current survival run
+#[derive(Resource, Default)]
+struct SurvivalState {
+    elapsed: f32,
+    predator_timer: f32,
+    fish_timer: f32,
+    rank: Option<Option<usize>>, // Set when the run ends: its leaderboard place, if it made the board
+}
+
+/// Random source for predators and fish losses, seeded from the world so replays match
+#[derive(Resource)]
+struct SurvivalRng(StdRng);
+
+pub struct SurvivalPlugin;
+
+impl Plugin for SurvivalPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SurvivalState>()
+            .add_systems(
+                OnEnter(AppState::InGame),
+                start_survival.run_if(resource_equals(GameMode::Survival)),
+            )
+            .add_systems(
+                Update,
+                (
+                    (
+                        degradation_system,
+                        fish_scarcity_system,
+                        predator_spawn_system,
+                        survival_end_system,
+                    )
+                        .chain()
+                        .after(crate::oxygen_system)
+                        .in_set(GameSet::Simulation),
+                    survival_text_system.in_set(GameSet::Presentation),
+                )
+                    .run_if(resource_equals(GameMode::Survival)),
+            );
+    }
+}
+
+/// Finished runs, longest first
+pub fn leaderboard() -> Vec<LeaderboardEntry> {
+    storage::read(LEADERBOARD_PATH)
+        .and_then(|contents| ron::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+/// Adds a run to the leaderboard and returns its place, if it was good enough to be kept
+fn submit_to_leaderboard(entry: LeaderboardEntry) -> Option<usize> {
+    let mut entries = leaderboard();
+    let rank = entries.partition_point(|other| other.time >= entry.time);
+    if rank >= LEADERBOARD_SIZE {
+        return None;
+    }
+    entries.insert(rank, entry);
+    entries.truncate(LEADERBOARD_SIZE);
+    let result = ron::ser::to_string_pretty(&entries, ron::ser::PrettyConfig::default())
+        .map_err(|err| err.to_string())
+        .and_then(|contents| storage::write(LEADERBOARD_PATH, &contents));
+    if let Err(err) = result {
+        warn!("Failed to save the survival leaderboard: {}", err);
+    }
+    Some(rank)
+}
+
+fn start_survival(mut commands: Commands, seed: Res<WorldSeed>, assets: Res<GameAssets>) {
+    commands.insert_resource(SurvivalState {
+        predator_timer: PREDATOR_FIRST_SPAWN,
+        fish_timer: FISH_CULL_INTERVAL,
+        ..default()
+    });
+    commands.insert_resource(SurvivalRng(StdRng::seed_from_u64(seed.0)));
+    commands.spawn((
+        Text::new(""),
+        TextFont {
+            font_size: 24.0,
+            font: assets.font.clone(),
+            ..default()
+        },
+        TextColor(Color::WHITE),
+        TextLayout::new_with_justify(JustifyText::Center),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(16.0),
+            width: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        SurvivalText,
+        StateScoped(AppState::InGame),
+    ));
+}
+
+/// Oxygen, battery and hull all wear down faster the longer the run lasts
+fn degradation_system(
+    time: Res<Time>,
+    mut survival: ResMut<SurvivalState>,
+    mut game_state: ResMut<GameState>,
+    mut ballast_state: ResMut<BallastState>,
+    submarine_query: Query<&Transform, With<Submarine>>,
+) {
+    if survival.rank.is_some() {
+        return;
+    }
+    survival.elapsed += time.delta_secs();
+    let intensity = intensity(survival.elapsed);
+    let delta = time.delta_secs() * intensity;
+    let depth = submarine_query
+        .single()
+        .map(|transform| -transform.translation.y)
+        .unwrap_or(0.0)
+        .max(0.0);
+
+    if depth > 0.0 {
+        game_state.oxygen = (game_state.oxygen - OXYGEN_DRAIN * delta).max(0.0);
+    }
+    ballast_state.electricity = (ballast_state.electricity - BATTERY_DRAIN * delta).max(0.0);
+    let stress = HULL_STRESS * (1.0 + depth / HULL_STRESS_DEPTH);
+    game_state.health = (game_state.health - stress * delta).max(0.0);
+}
+
+/// Every so often a share of the remaining fish disappears from the lake
+fn fish_scarcity_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut survival: ResMut<SurvivalState>,
+    mut rng: ResMut<SurvivalRng>,
+    fish_query: Query<Entity, With<Fish>>,
+) {
+    survival.fish_timer -= time.delta_secs();
+    if survival.fish_timer > 0.0 {
+        return;
+    }
+    survival.fish_timer += FISH_CULL_INTERVAL;
+    let count = fish_query.iter().count();
+    let cull = ((count as f32 * FISH_CULL_FRACTION).ceil() as usize).min(count);
+    for fish in fish_query.iter().choose_multiple(&mut rng.0, cull) {
+        commands.entity(fish).despawn();
+    }
+}
+
+/// Predators arrive more and more often, and faster, as the run goes on
+fn predator_spawn_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut survival: ResMut<SurvivalState>,
+    mut rng: ResMut<SurvivalRng>,
+    assets: Res<CombatAssets>,
+    submarine_query: Query<(Entity, &Transform), With<Submarine>>,
+) {
+    if survival.rank.is_some() {
+        return;
+    }
+    survival.predator_timer -= time.delta_secs();
+    if survival.predator_timer > 0.0 {
+        return;
+    }
+    let intensity = intensity(survival.elapsed);
+    survival.predator_timer += (PREDATOR_FIRST_SPAWN / intensity).max(PREDATOR_MIN_SPAWN);
+    let Ok((submarine_entity, submarine)) = submarine_query.single() else {
+        return;
+    };
+
+    let bearing = rng.0.gen::<f32>() * std::f32::consts::TAU;
+    let position = Vec3::new(
+        submarine.translation.x + bearing.cos() * PREDATOR_SPAWN_DISTANCE,
+        -3.0 - rng.0.gen::<f32>() * 15.0,
+        submarine.translation.z + bearing.sin() * PREDATOR_SPAWN_DISTANCE,
+    );
+    spawn_hostile(
+        &mut commands,
+        &assets,
+        position,
+        Hostile {
+            health: PREDATOR_HEALTH,
+            // Always slower than the submarine at full speed, so it can be outrun
+            speed: (3.0 + intensity).min(8.0),
+            damage: PREDATOR_BITE_DAMAGE,
+            prey: submarine_entity,
+            reach: PREDATOR_BITE_DISTANCE,
+        },
+    );
+}
+
+/// The run ends when the hull gives out or rescue comes; its time goes on the
+/// leaderboard
+fn survival_end_system(
+    mut commands: Commands,
+    mut survival: ResMut<SurvivalState>,
+    game_state: Res<GameState>,
+    beacon: Res<DistressBeacon>,
+    assets: Res<GameAssets>,
+) {
+    if survival.rank.is_some() || (game_state.health > 0.0 && !beacon.rescued) {
+        return;
+    }
+    let rank = submit_to_leaderboard(LeaderboardEntry {
+        time: survival.elapsed,
+        score: game_state.score,
+        timestamp: storage::unix_time(),
+    });
+    survival.rank = Some(rank);
+    info!("Survival run ended after {:.1} s", survival.elapsed);
+
+    let mut lines = vec![
+        if beacon.rescued {
+            "Rescued, with half the score"
+        } else {
+            "Hull lost"
+        }
+        .to_string(),
+        format!("Survived {}", format_play_time(survival.elapsed)),
+        String::new(),
+    ];
+    for (place, entry) in leaderboard().iter().enumerate() {
+        let marker = if rank == Some(place) { "  <" } else { "" };
+        lines.push(format!(
+            "{:>2}. {}   {} pts{}",
+            place + 1,
+            format_play_time(entry.time),
+            entry.score,
+            marker
+        ));
+    }
+
+    spawn_game_over_screen(&mut commands, assets.font.clone(), lines.join("\n"));
+}
+
+fn survival_text_system(
+    survival: Res<SurvivalState>,
+    predator_query: Query<(), With<Hostile>>,
+    fish_query: Query<(), With<Fish>>,
+    mut text_query: Query<&mut Text, With<SurvivalText>>,
+) {
+    let Ok(mut text) = text_query.single_mut() else {
+        return;
+    };
+    **text = format!(
+        "Survived {}   Threat x{:.1}   Predators {}   Fish left {}",
+        format_play_time(survival.elapsed),
+        intensity(survival.elapsed),
+        predator_query.iter().count(),
+        fish_query.iter().count()
+    );
+}
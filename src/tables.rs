@@ -0,0 +1,351 @@
+use crate::classes::SubmarineClass;
+use crate::mods::LoadedMods;
+use bevy::asset::{io::Reader, AssetLoader, LoadContext};
+use bevy::prelude::*;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::fmt::Debug;
+use std::marker::PhantomData;
+
+/// Asset path of the balancing tables, relative to `assets/`
+const TABLES_PATH: &str = "tables.ron";
+
+/// Balancing data for loot, fish and their migrations, spawn counts, submarine classes and
+/// encounters, read from `assets/tables.ron`.
+/// The copy compiled into the binary is used until the file has loaded, or if it
+/// fails to parse.
+#[derive(Asset, Resource, TypePath, Clone, Deserialize)]
+pub struct Tables {
+    pub loot: Vec<LootEntry>,
+    pub fish: Vec<FishSpecies>,
+    pub spawns: SpawnDensities,
+    #[serde(default)]
+    pub classes: Vec<SubmarineClass>, // Boats a new game can be started in
+    #[serde(default)]
+    pub encounters: Vec<EncounterSpecies>, // Rare wildlife passing the boat
+    #[serde(default)]
+    pub regions: Vec<SeaRegion>,
+    #[serde(default)]
+    pub migrations: Vec<Migration>, // Where fish go by the sea clock
+}
+
+impl Default for Tables {
+    fn default() -> Self {
+        ron::from_str(include_str!("../assets/tables.ron")).expect("built-in tables.ron is valid")
+    }
+}
+
+#[derive(Clone, Deserialize)]
+pub struct LootEntry {
+    pub weight: u32, // Relative to the other entries
+    pub loot: Loot,
+}
+
+/// Something a chest can hold
+#[derive(Clone, Deserialize)]
+pub enum Loot {
+    Score(u32),
+    UpgradeParts(u32),
+    HullPaint {
+        name: String,
+        color: (f32, f32, f32), // sRGB
+    },
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct FishSpecies {
+    pub name: String,
+    pub weight: u32, // Relative abundance
+    pub color: (f32, f32, f32),
+    pub radius: f32,
+    pub speed: (f32, f32), // Range in m/s
+    pub depth: (f32, f32), // Range in meters below the surface
+    #[serde(default)]
+    pub light: LightResponse, // How it takes to the floodlights in dark water
+    #[serde(default = "FishSpecies::full_size")]
+    pub size: (f32, f32), // Range of sizes, as multiples of `radius`
+    #[serde(default)]
+    pub min_size: f32, // Smallest catch allowed under sustainable fishing; 0 for no limit
+    #[serde(default)]
+    pub elite: bool, // Only found in New Game Plus
+}
+
+impl FishSpecies {
+    fn full_size() -> (f32, f32) {
+        (1.0, 1.0)
+    }
+}
+
+/// What a species does when caught in the floodlight beam
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum LightResponse {
+    #[default]
+    Indifferent,
+    Attracted, // Swims up the beam towards the lamp
+    Repelled,  // Darts out of the beam
+}
+
+/// Rare wildlife that passes the boat now and then, in groups
+#[derive(Clone, Deserialize)]
+pub struct EncounterSpecies {
+    pub name: String,
+    pub rarity: Rarity,
+    pub weight: u32, // Relative to the other encounters of the same rarity
+    pub group: (usize, usize),
+    pub spread: f32, // Meters the group spreads over
+    pub color: (f32, f32, f32),
+    pub size: (f32, f32, f32), // Width, height and length in meters
+    pub speed: f32,
+    pub depth: (f32, f32), // Range in meters below the surface
+    #[serde(default)]
+    pub call: Option<f32>, // Pitch in Hz of the call heard through the hull
+    pub echo: f32,         // Strength of its sonar return
+}
+
+/// How seldom an encounter comes along
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Deserialize)]
+pub enum Rarity {
+    Uncommon,
+    Rare,
+    Legendary,
+}
+
+/// An area of the bay fish gather in
+#[derive(Clone, Deserialize)]
+pub struct SeaRegion {
+    pub name: String,
+    pub center: (f32, f32), // Level position, x and z
+    pub radius: f32,
+    pub depth: (f32, f32), // Range in meters below the surface
+}
+
+/// The regions a species moves between through the day and the week
+#[derive(Clone, Deserialize)]
+pub struct Migration {
+    pub species: String,
+    pub legs: Vec<MigrationLeg>, // The first that fits the time is followed
+}
+
+#[derive(Clone, Deserialize)]
+pub struct MigrationLeg {
+    pub region: String,
+    pub hours: (f32, f32), // Past midnight if the first is later
+    #[serde(default)]
+    pub days: Vec<u32>, // Days of the week, 0 being the dive's first; every day if empty
+}
+
+impl MigrationLeg {
+    pub fn runs_at(&self, day: u32, hour: f32) -> bool {
+        let (start, end) = self.hours;
+        let in_hours = if start <= end {
+            (start..end).contains(&hour)
+        } else {
+            hour >= start || hour < end
+        };
+        in_hours && (self.days.is_empty() || self.days.contains(&day))
+    }
+}
+
+/// Additions from a mod: entries are appended, and spawn counts replace the base ones
+#[derive(Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct TablesPatch {
+    pub loot: Vec<LootEntry>,
+    pub fish: Vec<FishSpecies>,
+    pub replace_fish: bool, // Drop the species loaded so far instead of adding to them
+    pub spawns: Option<SpawnDensities>,
+    pub classes: Vec<SubmarineClass>,
+    pub encounters: Vec<EncounterSpecies>,
+    pub regions: Vec<SeaRegion>,
+    pub migrations: Vec<Migration>,
+}
+
+impl Tables {
+    pub fn extend(&mut self, patch: TablesPatch) {
+        self.loot.extend(patch.loot);
+        if patch.replace_fish {
+            self.fish.clear();
+        }
+        self.fish.extend(patch.fish);
+        if let Some(spawns) = patch.spawns {
+            self.spawns = spawns;
+        }
+        self.classes.extend(patch.classes);
+        self.encounters.extend(patch.encounters);
+        self.regions.extend(patch.regions);
+        self.migrations.extend(patch.migrations);
+    }
+
+    /// Puts reversed ranges the right way round, since picking from them would
+    /// panic. Returns a description of each fix for logging.
+    pub fn validate(&mut self) -> Vec<String> {
+        let mut fixes = Vec::new();
+        for species in self.fish.iter_mut() {
+            let name = &species.name;
+            order_range(&mut species.speed, &format!("{name} speed"), &mut fixes);
+            order_range(&mut species.depth, &format!("{name} depth"), &mut fixes);
+            order_range(&mut species.size, &format!("{name} size"), &mut fixes);
+        }
+        for species in self.encounters.iter_mut() {
+            let name = &species.name;
+            order_range(&mut species.depth, &format!("{name} depth"), &mut fixes);
+            order_range(&mut species.group, &format!("{name} group"), &mut fixes);
+        }
+        for region in self.regions.iter_mut() {
+            let name = &region.name;
+            order_range(&mut region.depth, &format!("{name} depth"), &mut fixes);
+        }
+        fixes
+    }
+}
+
+/// Swaps a (low, high) pair given high first, noting the fix
+pub fn order_range<T: PartialOrd + Debug>(range: &mut (T, T), what: &str, fixes: &mut Vec<String>) {
+    if range.0 > range.1 {
+        std::mem::swap(&mut range.0, &mut range.1);
+        fixes.push(format!("{what} was given high first; swapped to {range:?}"));
+    }
+}
+
+/// How many of each thing a dive starts with
+#[derive(Clone, Deserialize)]
+pub struct SpawnDensities {
+    pub oxygen_canisters: usize,
+    pub spare_parts: usize,
+    pub rock_chests: usize,
+    pub eel_swarms: usize,
+    pub eels_per_swarm: usize,
+    #[serde(default)]
+    pub debris: usize, // Barrels and plastic for the manipulator arm
+    #[serde(default)]
+    pub ghost_nets: usize,
+    #[serde(default)]
+    pub kelp_beds: usize,
+    #[serde(default)]
+    pub ice_fields: usize, // Patches of pack ice on the surface
+    #[serde(default)]
+    pub tide_races: usize, // Patches of fast-running water
+    #[serde(default)]
+    pub shrimp_beds: usize, // Crackling snapping shrimp on the floor
+    #[serde(default)]
+    pub croaker_schools: usize, // Drumming fish that chorus now and then
+}
+
+/// Holds `tables.ron` in memory so edits picked up by the `dev` file watcher
+/// arrive as events for this id
+#[derive(Resource)]
+struct TablesHandle(Handle<Tables>);
+
+/// Loads any deserializable asset from a RON file. Several can share the `.ron`
+/// extension; the asset server picks the one matching the requested asset type.
+pub struct RonLoader<A>(PhantomData<fn() -> A>);
+
+impl<A> Default for RonLoader<A> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<A: Asset + DeserializeOwned> AssetLoader for RonLoader<A> {
+    type Asset = A;
+    type Settings = ();
+    type Error = Box<dyn std::error::Error + Send + Sync>;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<A, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Ok(ron::de::from_bytes(&bytes)?)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["ron"]
+    }
+}
+
+pub struct TablesPlugin;
+
+impl Plugin for TablesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<Tables>()
+            .init_asset_loader::<RonLoader<Tables>>()
+            .init_resource::<Tables>()
+            .add_systems(Startup, load_tables)
+            .add_systems(Update, apply_tables_system);
+    }
+}
+
+fn load_tables(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mods: Res<LoadedMods>,
+    mut tables: ResMut<Tables>,
+) {
+    mods.apply_tables(&mut tables);
+    warn_fixes(&mut tables);
+    commands.insert_resource(TablesHandle(asset_server.load(TABLES_PATH)));
+}
+
+/// Copies the file into the `Tables` resource when it loads, and again whenever it
+/// is edited in builds with the `dev` feature (which turns on Bevy's file watcher)
+fn apply_tables_system(
+    mut events: EventReader<AssetEvent<Tables>>,
+    handle: Res<TablesHandle>,
+    assets: Res<Assets<Tables>>,
+    mods: Res<LoadedMods>,
+    mut tables: ResMut<Tables>,
+) {
+    for event in events.read() {
+        let (AssetEvent::LoadedWithDependencies { id } | AssetEvent::Modified { id }) = *event
+        else {
+            continue;
+        };
+        if id != handle.0.id() {
+            continue;
+        }
+        if let Some(loaded) = assets.get(id) {
+            *tables = loaded.clone();
+            mods.apply_tables(&mut tables);
+            warn_fixes(&mut tables);
+            info!("Loaded balancing tables from {}", TABLES_PATH);
+        }
+    }
+}
+
+fn warn_fixes(tables: &mut Tables) {
+    for fix in tables.validate() {
+        warn!("{}: {}", TABLES_PATH, fix);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn built_in_tables_are_valid() {
+        assert!(Tables::default().validate().is_empty());
+    }
+
+    #[test]
+    fn reversed_ranges_are_swapped() {
+        let mut tables = Tables::default();
+        tables.fish[0].depth = (12.0, 3.0);
+        tables.fish[0].speed = (2.0, 1.0);
+        let fixes = tables.validate();
+        assert_eq!(fixes.len(), 2);
+        assert_eq!(tables.fish[0].depth, (3.0, 12.0));
+        assert_eq!(tables.fish[0].speed, (1.0, 2.0));
+    }
+
+    #[test]
+    fn reversed_encounter_group_is_swapped() {
+        let mut tables = Tables::default();
+        tables.encounters[0].group = (5, 2);
+        tables.validate();
+        assert_eq!(tables.encounters[0].group, (2, 5));
+    }
+}
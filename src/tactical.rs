@@ -0,0 +1,319 @@
+//! The tactical view: an overhead plot looking straight down on the
+//! submarine, drawn as a schematic on its own render layer instead of the
+//! lit world. Sonar contacts, buoys, gates and the outlines of the mountains
+//! and foothills make it easy to pick a route through the gaps.
+
+use crate::controls::{Action, HelmInput, InputMap};
+use crate::ecosystem::Ecosystem;
+use crate::race::Gate;
+use crate::stations::ChargingBuoy;
+use crate::{
+    AppState, CameraFollow, CameraMode, Foothill, GameAssets, GameSet, Mountain, SonarDetections,
+    Submarine, Tuning, ViewCameraQuery, SONAR_CENTER_X, SONAR_CENTER_Y, SONAR_RADIUS,
+};
+use bevy::input::mouse::AccumulatedMouseScroll;
+use bevy::prelude::*;
+use bevy::render::camera::ClearColorConfig;
+use bevy::render::view::RenderLayers;
+use std::f32::consts::{FRAC_PI_2, FRAC_PI_4};
+
+const TACTICAL_LAYER: usize = 1; // Render layer only the schematic is drawn on
+const CAMERA_HEIGHT: f32 = 200.0; // Meters above the plot the camera looks down from
+const DEFAULT_SCALE: f32 = 0.4; // Meters per pixel
+const MIN_SCALE: f32 = 0.05;
+const MAX_SCALE: f32 = 2.0; // The whole lake fits on screen
+const ZOOM_SPEED: f32 = 1.5; // Doublings per second with the camera keys held
+const WHEEL_ZOOM: f32 = 0.15; // Share the scale changes by per wheel notch
+const MARKER_PIXELS: f32 = 12.0; // Markers keep their size on screen at any zoom
+const RING_SPACING: f32 = 50.0; // Meters between range rings around the submarine
+const RING_COUNT: usize = 4;
+
+const BACKGROUND: Color = Color::srgb(0.01, 0.05, 0.09);
+const RING_COLOR: Color = Color::srgba(0.3, 0.6, 0.7, 0.25);
+const SUBMARINE_COLOR: Color = Color::srgb(0.3, 1.0, 0.5);
+const CONTACT_COLOR: Color = Color::srgb(1.0, 0.4, 0.3);
+const WAYPOINT_COLOR: Color = Color::srgb(1.0, 0.85, 0.3);
+const MOUNTAIN_COLOR: Color = Color::srgb(0.75, 0.6, 0.45);
+const FOOTHILL_COLOR: Color = Color::srgba(0.6, 0.5, 0.35, 0.7);
+
+/// Lines of the schematic; only the tactical camera sees their layer
+#[derive(Default, Reflect, GizmoConfigGroup)]
+pub struct TacticalGizmos;
+
+/// How far the plot is zoomed out, kept between visits
+#[derive(Resource)]
+struct TacticalMap {
+    scale: f32, // Meters per pixel
+}
+
+impl Default for TacticalMap {
+    fn default() -> Self {
+        Self {
+            scale: DEFAULT_SCALE,
+        }
+    }
+}
+
+#[derive(Component)]
+struct TacticalText;
+
+pub struct TacticalPlugin;
+
+impl Plugin for TacticalPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TacticalMap>()
+            .insert_gizmo_config(
+                TacticalGizmos,
+                GizmoConfig {
+                    line: GizmoLineConfig {
+                        width: 2.0,
+                        ..default()
+                    },
+                    render_layers: RenderLayers::layer(TACTICAL_LAYER),
+                    ..default()
+                },
+            )
+            .add_systems(OnEnter(AppState::InGame), reset_map)
+            .add_systems(OnEnter(CameraMode::Tactical), enter_tactical)
+            .add_systems(OnExit(CameraMode::Tactical), leave_tactical)
+            .add_systems(
+                Update,
+                (
+                    toggle_tactical_system.in_set(GameSet::Input),
+                    zoom_system
+                        .in_set(GameSet::Input)
+                        .run_if(in_state(CameraMode::Tactical)),
+                    (tactical_camera_system, plot_system, tactical_text_system)
+                        .chain()
+                        .after(crate::camera_follow)
+                        .in_set(GameSet::Presentation)
+                        .run_if(in_state(CameraMode::Tactical)),
+                ),
+            );
+    }
+}
+
+fn reset_map(mut commands: Commands) {
+    commands.insert_resource(TacticalMap::default());
+}
+
+/// The tactical key opens the plot from any view and goes back outside from it
+fn toggle_tactical_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    input_map: Res<InputMap>,
+    camera_mode: Res<State<CameraMode>>,
+    mut next_camera_mode: ResMut<NextState<CameraMode>>,
+) {
+    if input_map.just_pressed(&keyboard_input, Action::TacticalMap) {
+        next_camera_mode.set(match camera_mode.get() {
+            CameraMode::Tactical => CameraMode::Exterior,
+            _ => CameraMode::Tactical,
+        });
+    }
+}
+
+/// Switches the camera to a flat overhead projection of the schematic layer
+fn enter_tactical(
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+    map: Res<TacticalMap>,
+    mut camera_query: Query<(Entity, &mut Camera, &mut Projection), With<CameraFollow>>,
+) {
+    for (entity, mut camera, mut projection) in camera_query.iter_mut() {
+        commands
+            .entity(entity)
+            .insert(RenderLayers::layer(TACTICAL_LAYER));
+        camera.clear_color = ClearColorConfig::Custom(BACKGROUND);
+        *projection = Projection::Orthographic(OrthographicProjection {
+            scale: map.scale,
+            far: CAMERA_HEIGHT * 2.0,
+            ..OrthographicProjection::default_3d()
+        });
+    }
+    commands.spawn((
+        Text::new(""),
+        TextFont {
+            font_size: 16.0,
+            font: assets.font.clone(),
+            ..default()
+        },
+        TextColor(Color::srgb(0.7, 0.9, 1.0)),
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(130.0),
+            left: Val::Px(16.0),
+            ..default()
+        },
+        TacticalText,
+        StateScoped(AppState::InGame),
+    ));
+}
+
+/// Puts the world back in view through the usual perspective lens
+fn leave_tactical(
+    mut commands: Commands,
+    text_query: Query<Entity, With<TacticalText>>,
+    mut camera_query: Query<(Entity, &mut Camera, &mut Projection), With<CameraFollow>>,
+) {
+    for text in text_query.iter() {
+        commands.entity(text).despawn();
+    }
+    for (entity, mut camera, mut projection) in camera_query.iter_mut() {
+        commands.entity(entity).remove::<RenderLayers>();
+        camera.clear_color = ClearColorConfig::Default;
+        *projection = Projection::Perspective(PerspectiveProjection {
+            fov: FRAC_PI_4,
+            ..default()
+        });
+    }
+}
+
+/// Zooms in with the camera-up key or the wheel and out with camera-down
+fn zoom_system(
+    helm_input: Res<HelmInput>,
+    scroll: Res<AccumulatedMouseScroll>,
+    mut map: ResMut<TacticalMap>,
+    time: Res<Time>,
+) {
+    let keys = (-helm_input.camera_pitch * ZOOM_SPEED * time.delta_secs()).exp2();
+    let wheel = (1.0 - WHEEL_ZOOM).powf(scroll.delta.y);
+    map.scale = (map.scale * keys * wheel).clamp(MIN_SCALE, MAX_SCALE);
+}
+
+/// Mountains and foothills, and which of the two each is
+type TerrainQuery<'w, 's> =
+    Query<'w, 's, (&'static Transform, Has<Mountain>), Or<(With<Mountain>, With<Foothill>)>>;
+
+/// Holds the camera over the submarine with north at the top of the screen
+fn tactical_camera_system(
+    map: Res<TacticalMap>,
+    submarine_query: Query<&Transform, With<Submarine>>,
+    mut camera_query: ViewCameraQuery,
+) {
+    let Ok(submarine) = submarine_query.single() else {
+        return;
+    };
+    let center = Vec3::new(submarine.translation.x, 0.0, submarine.translation.z);
+    for (mut transform, mut projection) in camera_query.iter_mut() {
+        *transform = Transform::from_translation(center + Vec3::Y * CAMERA_HEIGHT)
+            .looking_at(center, Vec3::NEG_Z);
+        if let Projection::Orthographic(orthographic) = projection.as_mut() {
+            orthographic.scale = map.scale;
+        }
+    }
+}
+
+/// Draws the plot flat on the water's plane: range rings, terrain outlines,
+/// waypoints, sonar contacts and the submarine itself
+#[allow(clippy::too_many_arguments)]
+fn plot_system(
+    mut gizmos: Gizmos<TacticalGizmos>,
+    map: Res<TacticalMap>,
+    tuning: Res<Tuning>,
+    detections: Res<SonarDetections>,
+    submarine_query: Query<&Transform, With<Submarine>>,
+    terrain_query: TerrainQuery,
+    buoy_query: Query<&GlobalTransform, With<ChargingBuoy>>,
+    gate_query: Query<&GlobalTransform, With<Gate>>,
+) {
+    let Ok(submarine) = submarine_query.single() else {
+        return;
+    };
+    // Gizmo circles lie in the XY plane; turn them flat onto the plot
+    let flat = |center: Vec3| Isometry3d::new(center, Quat::from_rotation_x(FRAC_PI_2));
+    let plot = |position: Vec3| Vec3::new(position.x, 0.0, position.z);
+    let marker = MARKER_PIXELS * map.scale;
+    let center = plot(submarine.translation);
+
+    for ring in 1..=RING_COUNT {
+        gizmos
+            .circle(flat(center), ring as f32 * RING_SPACING, RING_COLOR)
+            .resolution(64);
+    }
+
+    // Cones are scaled from a unit mesh, so their scale is the base radius;
+    // an inner ring at half height shows how steep each one is
+    for (transform, mountain) in terrain_query.iter() {
+        let color = if mountain {
+            MOUNTAIN_COLOR
+        } else {
+            FOOTHILL_COLOR
+        };
+        let radius = transform.scale.x;
+        gizmos
+            .circle(flat(plot(transform.translation)), radius, color)
+            .resolution(48);
+        gizmos.circle(
+            flat(plot(transform.translation)),
+            radius / 2.0,
+            color.with_alpha(0.3),
+        );
+    }
+
+    for buoy in buoy_query.iter() {
+        let position = plot(buoy.translation());
+        let corners = [Vec3::X, Vec3::Z, Vec3::NEG_X, Vec3::NEG_Z, Vec3::X]
+            .map(|corner| position + corner * marker * 0.7);
+        gizmos.linestrip(corners, WAYPOINT_COLOR);
+    }
+    for gate in gate_query.iter() {
+        gizmos.circle(flat(plot(gate.translation())), marker * 0.5, WAYPOINT_COLOR);
+    }
+
+    // Contacts are placed from what the sonar display shows, not where the
+    // fish really are, so jamming ghosts turn up here too
+    let yaw = Quat::from_rotation_y(submarine.rotation.to_euler(EulerRot::YXZ).0);
+    for contact in detections.contacts.iter() {
+        let display = Vec2::new(contact.x - SONAR_CENTER_X, SONAR_CENTER_Y - contact.y);
+        let distance = display.length() / SONAR_RADIUS * tuning.sonar_range;
+        let bearing = contact.angle - FRAC_PI_2;
+        let local = Vec3::new(-bearing.sin(), 0.0, -bearing.cos());
+        let position = center + yaw * local * distance;
+        let color = CONTACT_COLOR.with_alpha(0.3 + 0.7 * contact.confidence);
+        gizmos.line(
+            position + Vec3::new(-1.0, 0.0, -1.0) * marker * 0.4,
+            position + Vec3::new(1.0, 0.0, 1.0) * marker * 0.4,
+            color,
+        );
+        gizmos.line(
+            position + Vec3::new(-1.0, 0.0, 1.0) * marker * 0.4,
+            position + Vec3::new(1.0, 0.0, -1.0) * marker * 0.4,
+            color,
+        );
+    }
+
+    // An arrowhead pointing the way the boat is heading
+    let forward = yaw * Vec3::NEG_Z * marker;
+    let right = yaw * Vec3::X * marker * 0.5;
+    gizmos.linestrip(
+        [
+            center + forward,
+            center - forward * 0.6 + right,
+            center - forward * 0.3,
+            center - forward * 0.6 - right,
+            center + forward,
+        ],
+        SUBMARINE_COLOR,
+    );
+}
+
+fn tactical_text_system(
+    map: Res<TacticalMap>,
+    detections: Res<SonarDetections>,
+    ecosystem: Res<Ecosystem>,
+    mut text_query: Query<&mut Text, With<TacticalText>>,
+) {
+    let stock = ecosystem
+        .overall_health()
+        .map(|health| format!("  Fish stock {:.0}%", health * 100.0))
+        .unwrap_or_default();
+    for mut text in text_query.iter_mut() {
+        text.0 = format!(
+            "Tactical  {} contacts  Rings every {:.0} m  ({:.2} m per pixel){}\nUp/Down or Wheel: Zoom  N: Back",
+            detections.contacts.len(),
+            RING_SPACING,
+            map.scale,
+            stock
+        );
+    }
+}
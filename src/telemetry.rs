@@ -0,0 +1,155 @@
+//! Opt-in telemetry: samples the submarine's state at a fixed rate into a CSV or
+//! JSON Lines file (picked by the file extension), for graphing dives and for
+//! balancing. Every dive in a session goes into the same file, numbered.
+
+use crate::{AppState, BallastState, GameSet, GameState, PauseState, Submarine};
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::Velocity;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+const CSV_HEADER: &str =
+    "dive,time,depth,speed,ballast_fill,compressed_air,electricity,oxygen,x,y,z";
+
+/// Where to write samples and how often, from the command line
+#[derive(Resource, Clone)]
+pub struct TelemetrySettings {
+    pub path: String,
+    pub rate: f32, // Samples per second
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Csv,
+    JsonLines,
+}
+
+#[derive(Resource)]
+struct TelemetryRecorder {
+    writer: Option<BufWriter<File>>, // None once writing has failed
+    format: Format,
+    dive: u32,
+    elapsed: f32,     // Seconds into the current dive
+    next_sample: f32, // Dive time of the next sample
+}
+
+pub struct TelemetryPlugin(pub TelemetrySettings);
+
+impl Plugin for TelemetryPlugin {
+    fn build(&self, app: &mut App) {
+        let settings = self.0.clone();
+        let format = if settings.path.ends_with(".jsonl") || settings.path.ends_with(".json") {
+            Format::JsonLines
+        } else {
+            Format::Csv
+        };
+        let mut writer = match File::create(&settings.path) {
+            Ok(file) => BufWriter::new(file),
+            Err(err) => {
+                eprintln!("Cannot create telemetry file {}: {}", settings.path, err);
+                std::process::exit(1);
+            }
+        };
+        if format == Format::Csv {
+            // A failed header write shows up again on the first sample
+            let _ = writeln!(writer, "{}", CSV_HEADER);
+        }
+        app.insert_resource(TelemetryRecorder {
+            writer: Some(writer),
+            format,
+            dive: 0,
+            elapsed: 0.0,
+            next_sample: 0.0,
+        })
+        .insert_resource(settings)
+        .add_systems(OnEnter(AppState::InGame), start_dive_telemetry)
+        .add_systems(OnExit(AppState::InGame), flush_telemetry)
+        .add_systems(
+            Update,
+            telemetry_system
+                .after(GameSet::Simulation)
+                .run_if(in_state(PauseState::Running)),
+        )
+        .add_systems(Last, flush_telemetry.run_if(on_event::<AppExit>));
+    }
+}
+
+fn start_dive_telemetry(mut recorder: ResMut<TelemetryRecorder>) {
+    recorder.dive += 1;
+    recorder.elapsed = 0.0;
+    recorder.next_sample = 0.0;
+}
+
+fn telemetry_system(
+    mut recorder: ResMut<TelemetryRecorder>,
+    settings: Res<TelemetrySettings>,
+    submarine_query: Query<(&Transform, &Velocity), With<Submarine>>,
+    ballast_state: Res<BallastState>,
+    game_state: Res<GameState>,
+    time: Res<Time>,
+) {
+    recorder.elapsed += time.delta_secs();
+    if recorder.elapsed < recorder.next_sample {
+        return;
+    }
+    recorder.next_sample += 1.0 / settings.rate.max(0.1);
+    let Ok((transform, velocity)) = submarine_query.single() else {
+        return;
+    };
+
+    let position = transform.translation;
+    let values = [
+        ("depth", -position.y),
+        ("speed", velocity.linvel.length()),
+        ("ballast_fill", ballast_state.fill_level),
+        ("compressed_air", ballast_state.compressed_air),
+        ("electricity", ballast_state.electricity),
+        ("oxygen", game_state.oxygen),
+        ("x", position.x),
+        ("y", position.y),
+        ("z", position.z),
+    ];
+    let line = match recorder.format {
+        Format::Csv => {
+            let fields: Vec<String> = values
+                .iter()
+                .map(|(_, value)| format!("{:.3}", value))
+                .collect();
+            format!(
+                "{},{:.3},{}",
+                recorder.dive,
+                recorder.elapsed,
+                fields.join(",")
+            )
+        }
+        Format::JsonLines => {
+            let fields: Vec<String> = values
+                .iter()
+                .map(|(name, value)| format!("\"{}\":{:.3}", name, value))
+                .collect();
+            format!(
+                "{{\"dive\":{},\"time\":{:.3},{}}}",
+                recorder.dive,
+                recorder.elapsed,
+                fields.join(",")
+            )
+        }
+    };
+    let Some(writer) = recorder.writer.as_mut() else {
+        return;
+    };
+    if let Err(err) = writeln!(writer, "{}", line) {
+        error!("Telemetry stopped: {}", err);
+        recorder.writer = None;
+    }
+}
+
+/// Makes sure finished dives are on disk, even if the game is killed later
+fn flush_telemetry(mut recorder: ResMut<TelemetryRecorder>) {
+    if let Some(writer) = recorder.writer.as_mut() {
+        if let Err(err) = writer.flush() {
+            error!("Telemetry stopped: {}", err);
+            recorder.writer = None;
+        }
+    }
+}
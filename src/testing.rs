@@ -0,0 +1,134 @@
+//! Headless harness for integration tests: the gameplay plugins on top of
+//! `MinimalPlugins`, stepped by a fixed frame time so every run is identical.
+
+use crate::controls::ControlCommand;
+use crate::save::PendingLoad;
+use crate::{AppState, Cvars, GameMode, Submarine, SubmarinePlugins, WorldSeed};
+use bevy::asset::LoadState;
+use bevy::prelude::*;
+use bevy::time::TimeUpdateStrategy;
+use std::time::Duration;
+
+/// Frames per simulated second
+pub const TEST_FRAME_RATE: u32 = 60;
+
+/// Data files whose contents decide how a dive plays out
+const DATA_FILES: [&str; 3] = ["tuning.ron", "tables.ron", "props.ron"];
+const MAX_LOADING_FRAMES: usize = 600;
+
+/// The game without a window, renderer or audio output, sitting in the main
+/// menu with its data files loaded. Every run uses the same world seed; change
+/// the `Tuning` resource before calling `start_dive` to change the rules.
+pub fn test_app() -> App {
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        AssetPlugin::default(),
+        bevy::state::app::StatesPlugin,
+        bevy::input::InputPlugin,
+        TransformPlugin,
+    ))
+    // Gizmos load their shaders as they are added, renderer or not
+    .init_asset::<bevy::render::render_resource::Shader>()
+    // In place before the plugins register their cvars, so a cvars.cfg in the
+    // working directory cannot change the rules
+    .insert_resource(Cvars::without_config())
+    .add_plugins((
+        bevy::gizmos::GizmoPlugin,           // Draws nothing without a renderer
+        bevy::audio::AudioPlugin::default(), // Plays nothing without an output device
+        SubmarinePlugins,
+    ))
+    // Asset types the game creates or loads, without the plugins that render them
+    .init_asset::<Font>()
+    .init_asset::<Image>()
+    .init_asset::<Mesh>()
+    .init_asset::<StandardMaterial>()
+    .init_resource::<UiScale>()
+    .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(
+        1.0 / TEST_FRAME_RATE as f64,
+    )))
+    .insert_resource(WorldSeed(0));
+    app.finish();
+    app.cleanup();
+
+    // Data files load on background threads; wait for them so a test never
+    // races the loader and sees compiled-in values for part of a run
+    for _ in 0..MAX_LOADING_FRAMES {
+        app.update();
+        let server = app.world().resource::<AssetServer>();
+        let loaded = DATA_FILES.iter().all(|path| {
+            server.get_path_id(*path).is_some_and(|id| {
+                matches!(
+                    server.load_state(id),
+                    LoadState::Loaded | LoadState::Failed(_)
+                )
+            })
+        });
+        if loaded {
+            // One more frame copies the loaded files into their resources
+            app.update();
+            return app;
+        }
+    }
+    panic!(
+        "data files did not load within {} frames",
+        MAX_LOADING_FRAMES
+    );
+}
+
+/// Steps and state changes for apps built by `test_app`
+pub trait TestApp {
+    /// Starts a new free dive and runs until the submarine is in the water
+    fn start_dive(&mut self) -> &mut Self;
+
+    /// Issues an order as if it came from the keyboard or control panel
+    fn command(&mut self, command: ControlCommand) -> &mut Self;
+
+    /// Runs the game for `seconds` of simulated time
+    fn run_for(&mut self, seconds: f32) -> &mut Self;
+
+    /// The submarine's current transform
+    fn submarine(&mut self) -> Transform;
+
+    /// How far below the surface the submarine is, in meters
+    fn depth(&mut self) -> f32 {
+        -self.submarine().translation.y
+    }
+}
+
+impl TestApp for App {
+    fn start_dive(&mut self) -> &mut Self {
+        self.insert_resource(GameMode::Dive)
+            .insert_resource(PendingLoad(None));
+        self.world_mut()
+            .resource_mut::<NextState<AppState>>()
+            .set(AppState::Loading);
+        // Leaves any dive already under way before waiting on the new one
+        self.update();
+        while *self.world().resource::<State<AppState>>() != AppState::InGame {
+            self.update();
+        }
+        self
+    }
+
+    fn command(&mut self, command: ControlCommand) -> &mut Self {
+        self.world_mut().send_event(command);
+        self
+    }
+
+    fn run_for(&mut self, seconds: f32) -> &mut Self {
+        for _ in 0..(seconds * TEST_FRAME_RATE as f32).round() as usize {
+            self.update();
+        }
+        self
+    }
+
+    fn submarine(&mut self) -> Transform {
+        let mut query = self
+            .world_mut()
+            .query_filtered::<&Transform, With<Submarine>>();
+        *query
+            .single(self.world())
+            .expect("no submarine; call start_dive first")
+    }
+}
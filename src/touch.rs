@@ -0,0 +1,298 @@
+use crate::controls::{keyboard_helm_input_system, ControlCommand, HelmInput};
+use crate::settings::{Settings, TouchMode};
+use crate::{AppState, GameAssets, GameSet};
+use bevy::{input::touch::Touches, prelude::*};
+
+const STICK_RADIUS: f32 = 70.0; // Knob travel in logical pixels
+const STICK_KNOB_SIZE: f32 = 50.0;
+const STICK_DEAD_ZONE: f32 = 0.15;
+
+/// Which on-screen joystick a touch is steering
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Stick {
+    Helm,
+    Camera,
+}
+
+/// Tracks the finger currently driving a joystick and where it first touched down
+#[derive(Default)]
+struct StickTouch {
+    id: Option<u64>,
+    origin: Vec2,
+    offset: Vec2, // Knob offset from origin, clamped to STICK_RADIUS
+}
+
+#[derive(Resource, Default)]
+pub struct TouchControlsState {
+    pub active: bool,
+    helm: StickTouch,
+    camera: StickTouch,
+}
+
+#[derive(Component)]
+struct TouchOverlay;
+
+#[derive(Component)]
+struct StickBase(Stick);
+
+#[derive(Component)]
+struct StickKnob(Stick);
+
+/// Tap target that issues a control command
+#[derive(Component)]
+struct TouchButton(ControlCommand);
+
+pub struct TouchPlugin;
+
+impl Plugin for TouchPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TouchControlsState>()
+            .add_systems(OnEnter(AppState::InGame), spawn_touch_overlay)
+            .add_systems(
+                Update,
+                (
+                    touch_activation_system,
+                    touch_stick_system,
+                    touch_button_system,
+                    touch_overlay_layout_system,
+                )
+                    .chain()
+                    .in_set(GameSet::Input)
+                    .after(keyboard_helm_input_system),
+            );
+    }
+}
+
+fn spawn_touch_overlay(mut commands: Commands, assets: Res<GameAssets>) {
+    let font = assets.font.clone();
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                display: Display::None,
+                ..default()
+            },
+            TouchOverlay,
+            StateScoped(AppState::InGame),
+        ))
+        .with_children(|overlay| {
+            for stick in [Stick::Helm, Stick::Camera] {
+                overlay.spawn((
+                    Node {
+                        position_type: PositionType::Absolute,
+                        width: Val::Px(STICK_RADIUS * 2.0),
+                        height: Val::Px(STICK_RADIUS * 2.0),
+                        border: UiRect::all(Val::Px(2.0)),
+                        display: Display::None,
+                        ..default()
+                    },
+                    BorderRadius::MAX,
+                    BorderColor(Color::srgba(1.0, 1.0, 1.0, 0.4)),
+                    BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.2)),
+                    StickBase(stick),
+                ));
+                overlay.spawn((
+                    Node {
+                        position_type: PositionType::Absolute,
+                        width: Val::Px(STICK_KNOB_SIZE),
+                        height: Val::Px(STICK_KNOB_SIZE),
+                        display: Display::None,
+                        ..default()
+                    },
+                    BorderRadius::MAX,
+                    BackgroundColor(Color::srgba(1.0, 1.0, 1.0, 0.5)),
+                    StickKnob(stick),
+                ));
+            }
+
+            // Ballast tap targets along the right edge
+            overlay
+                .spawn(Node {
+                    position_type: PositionType::Absolute,
+                    right: Val::Px(20.0),
+                    top: Val::Percent(35.0),
+                    flex_direction: FlexDirection::Column,
+                    row_gap: Val::Px(12.0),
+                    ..default()
+                })
+                .with_children(|column| {
+                    for (command, label) in [
+                        (ControlCommand::ToggleVents, "VENT"),
+                        (ControlCommand::ToggleAirValve, "BLOW"),
+                        (ControlCommand::ToggleCompressor, "COMP"),
+                    ] {
+                        column
+                            .spawn((
+                                Button,
+                                Node {
+                                    width: Val::Px(80.0),
+                                    height: Val::Px(80.0),
+                                    justify_content: JustifyContent::Center,
+                                    align_items: AlignItems::Center,
+                                    ..default()
+                                },
+                                BorderRadius::MAX,
+                                BackgroundColor(Color::srgba(0.1, 0.3, 0.4, 0.6)),
+                                TouchButton(command),
+                            ))
+                            .with_children(|button| {
+                                button.spawn((
+                                    Text::new(label),
+                                    TextFont {
+                                        font_size: 16.0,
+                                        font: font.clone(),
+                                        ..default()
+                                    },
+                                    TextColor(Color::WHITE),
+                                ));
+                            });
+                    }
+                });
+        });
+}
+
+/// Shows touch controls when forced on, or in auto mode once a touch has been seen
+fn touch_activation_system(
+    settings: Res<Settings>,
+    touches: Res<Touches>,
+    mut touch_state: ResMut<TouchControlsState>,
+    mut overlay_query: Query<&mut Node, With<TouchOverlay>>,
+) {
+    let active = match settings.controls.touch_mode {
+        TouchMode::On => true,
+        TouchMode::Off => false,
+        TouchMode::Auto => {
+            touch_state.active
+                || cfg!(any(target_os = "android", target_os = "ios"))
+                || touches.iter().next().is_some()
+        }
+    };
+    touch_state.active = active;
+
+    let display = if active { Display::Flex } else { Display::None };
+    for mut node in overlay_query.iter_mut() {
+        if node.display != display {
+            node.display = display;
+        }
+    }
+}
+
+/// Floating joysticks: the left half of the screen steers, the right half orbits the camera
+fn touch_stick_system(
+    touches: Res<Touches>,
+    windows: Query<&Window>,
+    button_query: Query<&Interaction, With<TouchButton>>,
+    mut touch_state: ResMut<TouchControlsState>,
+    mut helm_input: ResMut<HelmInput>,
+) {
+    if !touch_state.active {
+        return;
+    }
+    let half_width = windows
+        .iter()
+        .next()
+        .map(|window| window.width() / 2.0)
+        .unwrap_or(640.0);
+    let over_button = button_query
+        .iter()
+        .any(|interaction| *interaction != Interaction::None);
+
+    let state = touch_state.as_mut();
+    for touch in touches.iter_just_pressed() {
+        if over_button {
+            continue;
+        }
+        let stick = if touch.position().x < half_width {
+            &mut state.helm
+        } else {
+            &mut state.camera
+        };
+        if stick.id.is_none() {
+            *stick = StickTouch {
+                id: Some(touch.id()),
+                origin: touch.position(),
+                offset: Vec2::ZERO,
+            };
+        }
+    }
+
+    for stick in [&mut state.helm, &mut state.camera] {
+        let Some(id) = stick.id else {
+            continue;
+        };
+        match touches.get_pressed(id) {
+            Some(touch) => {
+                stick.offset = (touch.position() - stick.origin).clamp_length_max(STICK_RADIUS)
+            }
+            None => *stick = StickTouch::default(),
+        }
+    }
+
+    // Screen Y grows downward, so pushing up is a negative offset
+    let helm = stick_axes(&state.helm);
+    let camera = stick_axes(&state.camera);
+    helm_input.throttle = (helm_input.throttle - helm.y).clamp(-1.0, 1.0);
+    helm_input.turn = (helm_input.turn - helm.x).clamp(-1.0, 1.0);
+    helm_input.camera_yaw = (helm_input.camera_yaw + camera.x).clamp(-1.0, 1.0);
+    helm_input.camera_pitch = (helm_input.camera_pitch - camera.y).clamp(-1.0, 1.0);
+}
+
+fn stick_axes(stick: &StickTouch) -> Vec2 {
+    let axes = stick.offset / STICK_RADIUS;
+    if axes.length() < STICK_DEAD_ZONE {
+        Vec2::ZERO
+    } else {
+        axes
+    }
+}
+
+fn touch_button_system(
+    button_query: Query<(&Interaction, &TouchButton), Changed<Interaction>>,
+    mut commands: EventWriter<ControlCommand>,
+) {
+    for (interaction, button) in button_query.iter() {
+        if *interaction == Interaction::Pressed {
+            commands.write(button.0);
+        }
+    }
+}
+
+/// Moves the joystick graphics under the active fingers
+fn touch_overlay_layout_system(
+    touch_state: Res<TouchControlsState>,
+    ui_scale: Res<UiScale>,
+    mut base_query: Query<(&mut Node, &StickBase), Without<StickKnob>>,
+    mut knob_query: Query<(&mut Node, &StickKnob), Without<StickBase>>,
+) {
+    let stick_touch = |stick: Stick| match stick {
+        Stick::Helm => &touch_state.helm,
+        Stick::Camera => &touch_state.camera,
+    };
+    // Touch positions are in logical pixels; UI node values are scaled by UiScale
+    let to_ui = |position: Vec2| position / ui_scale.0;
+
+    for (mut node, base) in base_query.iter_mut() {
+        let touch = stick_touch(base.0);
+        node.display = if touch.id.is_some() {
+            Display::Flex
+        } else {
+            Display::None
+        };
+        let corner = to_ui(touch.origin) - Vec2::splat(STICK_RADIUS);
+        node.left = Val::Px(corner.x);
+        node.top = Val::Px(corner.y);
+    }
+    for (mut node, knob) in knob_query.iter_mut() {
+        let touch = stick_touch(knob.0);
+        node.display = if touch.id.is_some() {
+            Display::Flex
+        } else {
+            Display::None
+        };
+        let corner = to_ui(touch.origin + touch.offset) - Vec2::splat(STICK_KNOB_SIZE / 2.0);
+        node.left = Val::Px(corner.x);
+        node.top = Val::Px(corner.y);
+    }
+}
@@ -0,0 +1,380 @@
+use crate::magnetics::Wreck;
+use crate::repair::Inventory;
+use crate::settings::Settings;
+use crate::sfx::{self, Note, Sfx};
+use crate::tables::{Loot, LootEntry, Tables};
+use crate::{
+    AppState, GameAssets, GameMode, GameSet, GameState, Hull, Submarine, UnderwaterRock, WorldSeed,
+};
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use rand::{
+    rngs::StdRng,
+    seq::{IteratorRandom, SliceRandom},
+    Rng, SeedableRng,
+};
+
+const SEA_FLOOR: f32 = -20.5;
+const UNLOCK_RANGE: f32 = 4.0;
+const UNLOCK_SPEED: f32 = 1.5; // Faster than this doesn't count as holding position
+const UNLOCK_TIME: f32 = 4.0;
+const OPEN_TIME: f32 = 1.0; // Seconds the lid takes to swing open
+const LOOT_ROLLS: usize = 2;
+const LOOT_DISPLAY_TIME: f32 = 5.0;
+
+/// A locked chest that opens after the submarine holds position beside it
+#[derive(Component)]
+struct TreasureChest {
+    progress: f32,     // Seconds spent unlocking
+    open: Option<f32>, // Seconds since the lid started opening
+    lid: Entity,
+    glow: Entity,
+}
+
+#[derive(Component)]
+struct TreasureText;
+
+#[derive(Resource, Default)]
+struct Treasure {
+    placed: bool,
+    message: String,
+    message_timer: f32,
+}
+
+/// Random source for placement and loot, seeded from the world so replays match
+#[derive(Resource)]
+struct TreasureRng(StdRng);
+
+/// Chime played as a chest opens
+#[derive(Resource)]
+struct ChestSound(Handle<Sfx>);
+
+/// The chest and its lid, clasp and bands, made once
+#[derive(Resource)]
+struct ChestAssets {
+    body_mesh: Handle<Mesh>,
+    lid_mesh: Handle<Mesh>,
+    clasp_mesh: Handle<Mesh>,
+    band_mesh: Handle<Mesh>,
+    wood_material: Handle<StandardMaterial>,
+    brass_material: Handle<StandardMaterial>,
+}
+
+impl FromWorld for ChestAssets {
+    fn from_world(world: &mut World) -> Self {
+        let mut meshes = world.resource_mut::<Assets<Mesh>>();
+        let body_mesh = meshes.add(Cuboid::new(1.4, 0.8, 0.9));
+        let lid_mesh = meshes.add(Cuboid::new(1.45, 0.25, 0.95));
+        let clasp_mesh = meshes.add(Cuboid::new(0.2, 0.3, 0.1));
+        let band_mesh = meshes.add(Cuboid::new(0.1, 0.82, 0.92));
+        let mut materials = world.resource_mut::<Assets<StandardMaterial>>();
+        Self {
+            body_mesh,
+            lid_mesh,
+            clasp_mesh,
+            band_mesh,
+            wood_material: materials.add(StandardMaterial {
+                base_color: Color::srgb(0.4, 0.25, 0.1),
+                perceptual_roughness: 0.9,
+                ..default()
+            }),
+            brass_material: materials.add(StandardMaterial {
+                base_color: Color::srgb(0.8, 0.6, 0.2),
+                metallic: 0.9,
+                perceptual_roughness: 0.3,
+                ..default()
+            }),
+        }
+    }
+}
+
+pub struct TreasurePlugin;
+
+impl Plugin for TreasurePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Treasure>()
+            .insert_resource(TreasureRng(StdRng::seed_from_u64(0)))
+            .add_systems(Startup, load_chest_sound)
+            .add_systems(
+                OnEnter(AppState::InGame),
+                (reset_treasure, spawn_treasure_text),
+            )
+            .add_systems(
+                Update,
+                (
+                    (place_chests_system, unlock_system)
+                        .chain()
+                        .in_set(GameSet::Simulation),
+                    (chest_animation_system, treasure_text_system).in_set(GameSet::Presentation),
+                )
+                    .run_if(|mode: Res<GameMode>| *mode != GameMode::TimeTrial),
+            );
+    }
+
+    fn finish(&self, app: &mut App) {
+        app.init_resource::<ChestAssets>();
+    }
+}
+
+fn load_chest_sound(mut commands: Commands, mut sounds: ResMut<Assets<Sfx>>) {
+    // Rising C major arpeggio
+    let notes = [523.25, 659.25, 783.99, 1046.5]
+        .into_iter()
+        .enumerate()
+        .map(|(i, frequency)| Note {
+            frequency,
+            start: i as f32 * 0.09,
+            duration: 0.6 + i as f32 * 0.2,
+            volume: 0.3,
+        })
+        .collect();
+    commands.insert_resource(ChestSound(sounds.add(Sfx::new(notes))));
+}
+
+fn reset_treasure(mut commands: Commands, seed: Res<WorldSeed>) {
+    commands.insert_resource(Treasure::default());
+    commands.insert_resource(TreasureRng(StdRng::seed_from_u64(seed.0 ^ 0x4C4F_4F54)));
+}
+
+fn spawn_treasure_text(mut commands: Commands, assets: Res<GameAssets>) {
+    commands.spawn((
+        Text::new(""),
+        TextFont {
+            font_size: 22.0,
+            font: assets.font.clone(),
+            ..default()
+        },
+        TextColor(Color::srgb(1.0, 0.85, 0.3)),
+        TextLayout::new_with_justify(JustifyText::Center),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Percent(60.0),
+            width: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        TreasureText,
+        StateScoped(AppState::InGame),
+    ));
+}
+
+fn spawn_chest(commands: &mut Commands, assets: &ChestAssets, position: Vec3, yaw: f32) {
+    let mut chest = commands.spawn((
+        Mesh3d(assets.body_mesh.clone()),
+        MeshMaterial3d(assets.wood_material.clone()),
+        Transform::from_translation(position).with_rotation(Quat::from_rotation_y(yaw)),
+        RigidBody::Fixed,
+        Collider::cuboid(0.7, 0.4, 0.45),
+        StateScoped(AppState::InGame),
+    ));
+    let mut lid = Entity::PLACEHOLDER;
+    let mut glow = Entity::PLACEHOLDER;
+    chest.with_children(|parent| {
+        // The lid hangs from a hinge along the back edge
+        lid = parent
+            .spawn((Transform::from_xyz(0.0, 0.4, -0.45), Visibility::default()))
+            .with_children(|hinge| {
+                hinge.spawn((
+                    Mesh3d(assets.lid_mesh.clone()),
+                    MeshMaterial3d(assets.wood_material.clone()),
+                    Transform::from_xyz(0.0, 0.125, 0.45),
+                ));
+                hinge.spawn((
+                    Mesh3d(assets.clasp_mesh.clone()),
+                    MeshMaterial3d(assets.brass_material.clone()),
+                    Transform::from_xyz(0.0, 0.0, 0.93),
+                ));
+            })
+            .id();
+        glow = parent
+            .spawn((
+                PointLight {
+                    color: Color::srgb(1.0, 0.8, 0.3),
+                    intensity: 0.0,
+                    range: 6.0,
+                    ..default()
+                },
+                Transform::from_xyz(0.0, 0.6, 0.0),
+            ))
+            .id();
+        // Brass bands
+        for x in [-0.5, 0.5] {
+            parent.spawn((
+                Mesh3d(assets.band_mesh.clone()),
+                MeshMaterial3d(assets.brass_material.clone()),
+                Transform::from_xyz(x, 0.0, 0.0),
+            ));
+        }
+    });
+    chest.insert(TreasureChest {
+        progress: 0.0,
+        open: None,
+        lid,
+        glow,
+    });
+}
+
+/// Chests go beside each wreck and under a few rocks, once the world is in place
+fn place_chests_system(
+    mut commands: Commands,
+    tables: Res<Tables>,
+    mut treasure: ResMut<Treasure>,
+    mut rng: ResMut<TreasureRng>,
+    assets: Res<ChestAssets>,
+    wreck_query: Query<(&Wreck, &Transform)>,
+    rock_query: Query<&Transform, With<UnderwaterRock>>,
+) {
+    if treasure.placed {
+        return;
+    }
+    treasure.placed = true;
+
+    let mut spots: Vec<Vec3> = wreck_query
+        .iter()
+        .map(|(wreck, transform)| wreck.beside(transform.translation, 4.0, 1.0))
+        .collect();
+    for rock in rock_query
+        .iter()
+        .choose_multiple(&mut rng.0, tables.spawns.rock_chests)
+    {
+        spots.push(rock.translation + Vec3::new(4.0, 0.0, 0.0));
+    }
+    for spot in spots {
+        let position = Vec3::new(spot.x, SEA_FLOOR + 0.4, spot.z);
+        let yaw = rng.0.gen::<f32>() * std::f32::consts::TAU;
+        spawn_chest(&mut commands, &assets, position, yaw);
+    }
+}
+
+/// Holding position beside a chest unlocks it; opening it rolls the loot
+#[allow(clippy::too_many_arguments)]
+fn unlock_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    settings: Res<Settings>,
+    tables: Res<Tables>,
+    sound: Res<ChestSound>,
+    mut treasure: ResMut<Treasure>,
+    mut rng: ResMut<TreasureRng>,
+    mut game_state: ResMut<GameState>,
+    mut inventory: ResMut<Inventory>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    submarine_query: Query<(&Transform, &Velocity), With<Submarine>>,
+    mut chest_query: Query<(&mut TreasureChest, &Transform), Without<Submarine>>,
+    hull_query: Query<&MeshMaterial3d<StandardMaterial>, With<Hull>>,
+) {
+    let delta = time.delta_secs();
+    treasure.message_timer = (treasure.message_timer - delta).max(0.0);
+    let Ok((submarine, velocity)) = submarine_query.single() else {
+        return;
+    };
+    let holding = velocity.linvel.length() < UNLOCK_SPEED;
+
+    for (mut chest, transform) in chest_query.iter_mut() {
+        if chest.open.is_some() {
+            continue;
+        }
+        let near = transform.translation.distance(submarine.translation) < UNLOCK_RANGE;
+        chest.progress = if near && holding {
+            chest.progress + delta
+        } else {
+            (chest.progress - delta).max(0.0)
+        };
+        if chest.progress < UNLOCK_TIME {
+            continue;
+        }
+
+        chest.open = Some(0.0);
+        sfx::play(&mut commands, sound.0.clone(), &settings);
+        let mut lines = vec!["Chest opened!".to_string()];
+        for _ in 0..LOOT_ROLLS {
+            let Ok(LootEntry { loot, .. }) = tables
+                .loot
+                .choose_weighted(&mut rng.0, |entry| entry.weight)
+            else {
+                continue;
+            };
+            lines.push(match loot {
+                Loot::Score(points) => {
+                    game_state.score += *points;
+                    format!("+{} points", points)
+                }
+                Loot::UpgradeParts(count) => {
+                    inventory.upgrade_parts += *count;
+                    format!(
+                        "{} upgrade part{}",
+                        count,
+                        if *count == 1 { "" } else { "s" }
+                    )
+                }
+                Loot::HullPaint { name, color } => {
+                    for material in hull_query.iter() {
+                        if let Some(material) = materials.get_mut(&material.0) {
+                            material.base_color = Color::srgb(color.0, color.1, color.2);
+                        }
+                    }
+                    format!("Hull paint: {}", name)
+                }
+            });
+        }
+        info!("{}", lines.join(", "));
+        treasure.message = lines.join("\n");
+        treasure.message_timer = LOOT_DISPLAY_TIME;
+    }
+}
+
+/// Swings lids open and lets the gold inside glow
+fn chest_animation_system(
+    time: Res<Time>,
+    mut chest_query: Query<&mut TreasureChest>,
+    mut transform_query: Query<&mut Transform>,
+    mut light_query: Query<&mut PointLight>,
+) {
+    for mut chest in chest_query.iter_mut() {
+        let Some(open) = chest.open.as_mut() else {
+            continue;
+        };
+        *open = (*open + time.delta_secs()).min(OPEN_TIME);
+        let t = *open / OPEN_TIME;
+        // Ease out so the lid slows as it falls back
+        let eased = 1.0 - (1.0 - t) * (1.0 - t);
+        if let Ok(mut lid) = transform_query.get_mut(chest.lid) {
+            lid.rotation = Quat::from_rotation_x(-1.9 * eased);
+        }
+        if let Ok(mut light) = light_query.get_mut(chest.glow) {
+            light.intensity = 60_000.0 * eased;
+        }
+    }
+}
+
+fn treasure_text_system(
+    treasure: Res<Treasure>,
+    submarine_query: Query<&Transform, With<Submarine>>,
+    chest_query: Query<(&TreasureChest, &Transform), Without<Submarine>>,
+    mut text_query: Query<&mut Text, With<TreasureText>>,
+) {
+    let Ok(mut text) = text_query.single_mut() else {
+        return;
+    };
+    let unlocking = submarine_query.single().ok().and_then(|submarine| {
+        chest_query.iter().find(|(chest, transform)| {
+            chest.open.is_none()
+                && transform.translation.distance(submarine.translation) < UNLOCK_RANGE
+        })
+    });
+    let line = if treasure.message_timer > 0.0 {
+        treasure.message.clone()
+    } else if let Some((chest, _)) = unlocking {
+        let filled = (chest.progress / UNLOCK_TIME * 20.0) as usize;
+        format!(
+            "Hold position to unlock the chest\n[{}{}]",
+            "#".repeat(filled),
+            "-".repeat(20 - filled)
+        )
+    } else {
+        String::new()
+    };
+    if **text != line {
+        **text = line;
+    }
+}
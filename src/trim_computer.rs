@@ -0,0 +1,113 @@
+//! Trim computer: an aid that works the ballast valves for the crew. Engaged,
+//! it holds the tanks near neutral buoyancy and keeps the boat at the depth it
+//! had when the computer took over, drawing a little power and blowing with the
+//! same metered air as a trim target. Opening the vents or air valve, or
+//! holding the flood or blow keys, takes manual control; once they're closed
+//! the computer holds the new depth. The casual buoyancy setting engages it at
+//! the start of every dive.
+
+use crate::cargo::CargoLoad;
+use crate::controls::{ControlCommand, HelmInput};
+use crate::leaks::Flooding;
+use crate::settings::Settings;
+use crate::{AppState, BallastState, GameSet, Submarine, Tuning};
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+const POWER_DRAW: f32 = 0.2; // Electricity percent per second while engaged
+const DEPTH_GAIN: f32 = 0.05; // Fill added per meter the boat is shallower than the held depth
+const RATE_GAIN: f32 = 0.1; // Fill added per meter per second the boat is rising
+const AUTHORITY: f32 = 0.15; // Most fill the computer strays from neutral
+
+/// Whether the trim computer is working the valves, and the depth it holds
+#[derive(Resource, Default)]
+pub struct TrimComputer {
+    pub engaged: bool,
+    pub hold_depth: Option<f32>, // None while the crew have the valves
+}
+
+pub struct TrimComputerPlugin;
+
+impl Plugin for TrimComputerPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TrimComputer>()
+            .add_systems(OnEnter(AppState::InGame), reset_trim_computer)
+            .add_systems(
+                Update,
+                trim_computer_system
+                    .before(crate::ballast_control_system)
+                    .in_set(GameSet::Simulation),
+            );
+    }
+}
+
+/// Casual buoyancy engages the computer through an order, so replays see it
+fn reset_trim_computer(
+    mut commands: Commands,
+    mut control_commands: EventWriter<ControlCommand>,
+    settings: Res<Settings>,
+) {
+    commands.insert_resource(TrimComputer::default());
+    if settings.accessibility.casual_buoyancy {
+        control_commands.write(ControlCommand::ToggleTrimComputer);
+    }
+}
+
+/// Sets the trim target to neutral buoyancy, eased towards the held depth
+#[allow(clippy::too_many_arguments)]
+fn trim_computer_system(
+    mut computer: ResMut<TrimComputer>,
+    mut ballast_state: ResMut<BallastState>,
+    mut control_commands: EventReader<ControlCommand>,
+    helm_input: Res<HelmInput>,
+    flooding: Res<Flooding>,
+    cargo: Res<CargoLoad>,
+    tuning: Res<Tuning>,
+    submarine_query: Query<(&Transform, &Velocity), With<Submarine>>,
+    time: Res<Time>,
+) {
+    for command in control_commands.read() {
+        match command {
+            ControlCommand::ToggleTrimComputer => {
+                computer.engaged = !computer.engaged;
+                computer.hold_depth = None;
+                if !computer.engaged {
+                    ballast_state.target_fill = None;
+                }
+            }
+            // Picking a trim by hand takes over from the computer
+            ControlCommand::SetBallastTarget(_)
+            | ControlCommand::BallastTargetUp
+            | ControlCommand::BallastTargetDown
+            | ControlCommand::ClearBallastTarget => computer.engaged = false,
+            _ => {}
+        }
+    }
+    if !computer.engaged {
+        return;
+    }
+    if ballast_state.electricity <= 0.0 {
+        computer.engaged = false;
+        ballast_state.target_fill = None;
+        return;
+    }
+    ballast_state.electricity =
+        (ballast_state.electricity - POWER_DRAW * time.delta_secs()).max(0.0);
+
+    let Ok((transform, velocity)) = submarine_query.single() else {
+        return;
+    };
+    if ballast_state.vents_open || ballast_state.air_valve_open || helm_input.ballast != 0.0 {
+        computer.hold_depth = None;
+        return;
+    }
+    let depth = -transform.translation.y;
+    let hold_depth = *computer.hold_depth.get_or_insert(depth);
+
+    let neutral = tuning.base_buoyancy_force / tuning.ballast_buoyancy_force
+        - flooding.weight()
+        - cargo.weight;
+    let correction = (DEPTH_GAIN * (hold_depth - depth) + RATE_GAIN * velocity.linvel.y)
+        .clamp(-AUTHORITY, AUTHORITY);
+    ballast_state.target_fill = Some((neutral + correction).clamp(0.0, 1.0));
+}
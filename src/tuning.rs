@@ -0,0 +1,160 @@
+use crate::classes::SubmarineClass;
+use crate::tables::RonLoader;
+use bevy::prelude::*;
+use serde::Deserialize;
+
+/// Asset path of the gameplay tuning, relative to `assets/`
+const TUNING_PATH: &str = "tuning.ron";
+const METERS_PER_ATMOSPHERE: f32 = 10.0; // Depth of water that adds an atmosphere of pressure
+
+/// Gameplay values read from `assets/tuning.ron`, with any command-line
+/// overrides and the submarine class applied on top. The copy compiled into
+/// the binary is used until the file has loaded, or if it fails to parse.
+#[derive(Asset, Resource, TypePath, Clone, Deserialize)]
+pub struct Tuning {
+    pub speed: f32,
+    pub turn_speed: f32,
+    pub plane_rate: f32,
+    pub max_plane_angle: f32,
+    pub wave_trim_rate: f32,
+    pub level_rate: f32,
+    pub base_buoyancy_force: f32,
+    pub ballast_buoyancy_force: f32,
+    pub buoyancy_scale: f32,
+    pub ballast_fill_rate: f32,
+    pub ballast_drain_rate: f32,
+    pub blow_depth_loss: f32,
+    pub trim_step: f32,
+    pub trim_band: f32,
+    pub air_per_fill: f32,
+    pub compressed_air_rate: f32,
+    pub compressor_depth_loss: f32,
+    pub compressor_power_drain: f32,
+    pub power_recharge_rate: f32,
+    pub floodlight_power_drain: f32,
+    pub oxygen_rate: f32,
+    pub fish_count: usize,
+    pub fish_collection_distance: f32,
+    pub fish_reaction_radius: f32,
+    pub fish_flee_speed: f32,
+    pub fish_flee_time: f32,
+    pub sonar_range: f32,
+    pub sweep_speed: f32,
+    pub sonar_jammed_range_loss: f32,
+    pub sonar_jammed_bearing_error: f32,
+    pub sonar_max_false_contacts: usize,
+    pub sonar_pickup_strength: f32,
+    pub sonar_layer_range_loss: f32,
+    pub crush_depth: f32,
+    pub crush_damage_rate: f32,
+    pub thermoclines: Vec<f32>,
+    pub bubble_rise_speed: f32,
+    pub bubble_depth_loss: f32,
+    pub start_depth: f32,
+    pub difficulty_range: (f32, f32),
+}
+
+impl Default for Tuning {
+    fn default() -> Self {
+        ron::from_str(include_str!("../assets/tuning.ron")).expect("built-in tuning.ron is valid")
+    }
+}
+
+/// Share of its surface performance something keeps at a depth, when it loses
+/// `loss` of it to each atmosphere of water pressure. The tuning's `*_depth_loss`
+/// values all work through this, so every system feels the same pressure.
+pub fn depth_factor(loss: f32, depth: f32) -> f32 {
+    1.0 / (1.0 + loss * depth.max(0.0) / METERS_PER_ATMOSPHERE)
+}
+
+/// Values fixed from the command line, a scenario or a replay, which win over
+/// the file so test setups and recordings stay as they were
+#[derive(Resource, Clone, Default)]
+pub struct TuningOverrides {
+    pub fish_count: Option<usize>,
+    pub oxygen_rate: Option<f32>,
+    pub buoyancy_scale: Option<f32>,
+    pub start_depth: Option<f32>,
+}
+
+impl TuningOverrides {
+    fn apply(&self, tuning: &mut Tuning) {
+        if let Some(fish_count) = self.fish_count {
+            tuning.fish_count = fish_count;
+        }
+        if let Some(oxygen_rate) = self.oxygen_rate {
+            tuning.oxygen_rate = oxygen_rate;
+        }
+        if let Some(buoyancy_scale) = self.buoyancy_scale {
+            tuning.buoyancy_scale = buoyancy_scale;
+        }
+        if let Some(start_depth) = self.start_depth {
+            tuning.start_depth = start_depth.max(0.0);
+        }
+    }
+}
+
+/// The loaded `tuning.ron`, read again whenever the submarine class changes
+#[derive(Resource)]
+struct TuningHandle(Handle<Tuning>);
+
+pub struct TuningPlugin;
+
+impl Plugin for TuningPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<Tuning>()
+            .init_asset_loader::<RonLoader<Tuning>>()
+            .init_resource::<Tuning>()
+            .init_resource::<TuningOverrides>()
+            .init_resource::<SubmarineClass>()
+            .add_systems(Startup, load_tuning)
+            .add_systems(Update, apply_tuning_system);
+    }
+}
+
+fn load_tuning(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    overrides: Res<TuningOverrides>,
+    class: Res<SubmarineClass>,
+    mut tuning: ResMut<Tuning>,
+) {
+    overrides.apply(&mut tuning);
+    class.apply(&mut tuning);
+    commands.insert_resource(TuningHandle(asset_server.load(TUNING_PATH)));
+}
+
+/// Copies the file into the `Tuning` resource when it loads, and again whenever
+/// it is edited in builds with the `dev` feature or another submarine class is
+/// picked
+fn apply_tuning_system(
+    mut events: EventReader<AssetEvent<Tuning>>,
+    handle: Res<TuningHandle>,
+    assets: Res<Assets<Tuning>>,
+    overrides: Res<TuningOverrides>,
+    class: Res<SubmarineClass>,
+    mut tuning: ResMut<Tuning>,
+) {
+    // Counted rather than searched so every event is read, not just up to the first
+    let reloaded = events
+        .read()
+        .filter(|event| {
+            matches!(
+                **event,
+                AssetEvent::LoadedWithDependencies { id } | AssetEvent::Modified { id }
+                    if id == handle.0.id()
+            )
+        })
+        .count()
+        > 0;
+    if !reloaded && !class.is_changed() {
+        return;
+    }
+    // Until the file has loaded, the class goes on top of the built-in copy
+    *tuning = assets.get(&handle.0).cloned().unwrap_or_default();
+    overrides.apply(&mut tuning);
+    class.apply(&mut tuning);
+    if reloaded {
+        info!("Loaded gameplay tuning from {}", TUNING_PATH);
+    }
+}
@@ -0,0 +1,237 @@
+//! Upward sonar: a strip above the depth profile that sounds the surface
+//! overhead, from astern to ahead along the heading. Under pack ice it shows
+//! how deep the floes reach and where the nearest open water is to come up
+//! in; in open water it shows the swell running over the boat.
+
+use crate::ice::{ice_draft, IceFloe};
+use crate::settings::Settings;
+use crate::{wave_height, AppState, GameAssets, GameSet, Submarine, WaveTime};
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+
+const STRIP_WIDTH: f32 = 240.0;
+const STRIP_HEIGHT: f32 = 60.0;
+const STRIP_RANGE: f32 = 40.0; // Meters astern and ahead the strip reaches
+const ABOVE_SURFACE: f32 = 1.0; // Meters above the surface at the top of the strip
+const BELOW_SURFACE: f32 = 7.0; // Meters below the surface at the bottom of the strip
+const COLUMNS: usize = 40; // Soundings along the heading, one per column
+
+const ICE_COLOR: Color = Color::srgb(0.85, 0.92, 1.0);
+const WATER_COLOR: Color = Color::srgb(0.2, 0.5, 0.7);
+
+#[derive(Component)]
+struct UpwardSonarPanel;
+
+/// One sounding, filled down from the top of the strip to the underside of
+/// the ice or the water surface
+#[derive(Component)]
+struct SurfaceColumn(usize);
+
+#[derive(Component)]
+struct UpwardSonarSubmarine;
+
+#[derive(Component)]
+struct UpwardSonarText;
+
+pub struct UpwardSonarPlugin;
+
+impl Plugin for UpwardSonarPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(AppState::InGame), spawn_upward_sonar)
+            .add_systems(Update, upward_sonar_system.in_set(GameSet::Presentation));
+    }
+}
+
+/// Pixels from the top of the strip a depth is drawn at
+fn depth_to_y(depth: f32) -> f32 {
+    ((depth + ABOVE_SURFACE) / (ABOVE_SURFACE + BELOW_SURFACE)).clamp(0.0, 1.0) * STRIP_HEIGHT
+}
+
+/// Meters along the heading a column sounds, negative astern
+fn column_offset(column: usize) -> f32 {
+    ((column as f32 + 0.5) / COLUMNS as f32 * 2.0 - 1.0) * STRIP_RANGE
+}
+
+fn spawn_upward_sonar(mut commands: Commands, assets: Res<GameAssets>) {
+    let font = assets.font.clone();
+    let column_width = STRIP_WIDTH / COLUMNS as f32;
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                bottom: Val::Percent(71.0),
+                right: Val::Px(16.0),
+                flex_direction: FlexDirection::Column,
+                row_gap: Val::Px(4.0),
+                ..default()
+            },
+            UpwardSonarPanel,
+            StateScoped(AppState::InGame),
+        ))
+        .with_children(|panel| {
+            panel.spawn((
+                Text::new(""),
+                TextFont {
+                    font_size: 12.0,
+                    font,
+                    ..default()
+                },
+                TextColor(Color::srgb(0.7, 0.9, 1.0)),
+                UpwardSonarText,
+            ));
+            panel
+                .spawn((
+                    Node {
+                        width: Val::Px(STRIP_WIDTH),
+                        height: Val::Px(STRIP_HEIGHT),
+                        border: UiRect::all(Val::Px(1.0)),
+                        overflow: Overflow::clip(),
+                        ..default()
+                    },
+                    BorderColor(Color::srgb(0.4, 0.6, 0.7)),
+                    BackgroundColor(Color::srgba(0.0, 0.05, 0.1, 0.7)),
+                ))
+                .with_children(|strip| {
+                    for index in 0..COLUMNS {
+                        strip.spawn((
+                            Node {
+                                position_type: PositionType::Absolute,
+                                left: Val::Px(index as f32 * column_width),
+                                top: Val::Px(0.0),
+                                width: Val::Px(column_width),
+                                height: Val::Px(0.0),
+                                ..default()
+                            },
+                            BackgroundColor(WATER_COLOR),
+                            SurfaceColumn(index),
+                        ));
+                    }
+                    strip.spawn((
+                        Node {
+                            position_type: PositionType::Absolute,
+                            left: Val::Px(STRIP_WIDTH / 2.0 - 5.0),
+                            top: Val::Px(0.0),
+                            width: Val::Px(10.0),
+                            height: Val::Px(4.0),
+                            ..default()
+                        },
+                        BackgroundColor(Color::srgb(0.3, 1.0, 0.5)),
+                        UpwardSonarSubmarine,
+                    ));
+                });
+        });
+}
+
+/// The submarine's marker on the strip
+type MarkerQuery<'w, 's> = Query<
+    'w,
+    's,
+    &'static mut Node,
+    (
+        With<UpwardSonarSubmarine>,
+        Without<UpwardSonarPanel>,
+        Without<SurfaceColumn>,
+    ),
+>;
+
+/// The nodes the upward sonar draws into
+#[derive(SystemParam)]
+struct StripNodes<'w, 's> {
+    panel: Query<'w, 's, &'static mut Node, With<UpwardSonarPanel>>,
+    columns: Query<
+        'w,
+        's,
+        (
+            &'static SurfaceColumn,
+            &'static mut Node,
+            &'static mut BackgroundColor,
+        ),
+        Without<UpwardSonarPanel>,
+    >,
+    submarine_marker: MarkerQuery<'w, 's>,
+    text: Query<'w, 's, &'static mut Text, With<UpwardSonarText>>,
+}
+
+/// Sounds the ice or the waves above each column and reports what is
+/// directly overhead, with the nearest open water when under the ice
+fn upward_sonar_system(
+    settings: Res<Settings>,
+    wave_time: Res<WaveTime>,
+    submarine_query: Query<&Transform, With<Submarine>>,
+    floe_query: Query<(&Transform, &IceFloe)>,
+    mut nodes: StripNodes,
+) {
+    // Shown and hidden along with the sonar panel
+    for mut node in nodes.panel.iter_mut() {
+        node.display = if settings.hud.show_sonar {
+            Display::Flex
+        } else {
+            Display::None
+        };
+    }
+    let Ok(submarine) = submarine_query.single() else {
+        return;
+    };
+
+    let heading = (submarine.rotation * Vec3::NEG_Z)
+        .with_y(0.0)
+        .try_normalize()
+        .unwrap_or(Vec3::NEG_Z);
+    // Draft of the ice over each column, and the water surface's height
+    let soundings: Vec<(f32, f32)> = (0..COLUMNS)
+        .map(|column| {
+            let point = submarine.translation + heading * column_offset(column);
+            (
+                ice_draft(floe_query.iter(), point.x, point.z),
+                wave_height(point.x, point.z, wave_time.elapsed),
+            )
+        })
+        .collect();
+    for (column, mut node, mut color) in nodes.columns.iter_mut() {
+        let (draft, wave) = soundings[column.0];
+        let (depth, fill) = if draft > 0.0 {
+            (draft, ICE_COLOR)
+        } else {
+            (-wave, WATER_COLOR)
+        };
+        node.height = Val::Px(depth_to_y(depth));
+        color.0 = fill;
+    }
+
+    let depth = -submarine.translation.y;
+    for mut node in nodes.submarine_marker.iter_mut() {
+        node.top = Val::Px(depth_to_y(depth) - 2.0);
+    }
+
+    let overhead = ice_draft(
+        floe_query.iter(),
+        submarine.translation.x,
+        submarine.translation.z,
+    );
+    let report = if overhead > 0.0 {
+        // Closest open column either way along the heading
+        let opening = (0..COLUMNS)
+            .filter(|&column| soundings[column].0 == 0.0)
+            .map(column_offset)
+            .min_by(|a, b| a.abs().total_cmp(&b.abs()));
+        let opening = match opening {
+            Some(offset) if offset >= 0.0 => format!("open water {:.0} m ahead", offset),
+            Some(offset) => format!("open water {:.0} m astern", -offset),
+            None => format!("no open water within {:.0} m", STRIP_RANGE),
+        };
+        format!("ICE overhead, {:.1} m deep - {}", overhead, opening)
+    } else {
+        let (low, high) = soundings
+            .iter()
+            .fold((f32::MAX, f32::MIN), |(low, high), &(_, wave)| {
+                (low.min(wave), high.max(wave))
+            });
+        format!("Open water overhead, swell {:.1} m", high - low)
+    };
+    for mut text in nodes.text.iter_mut() {
+        text.0 = format!(
+            "Upward Sonar, {:.0} m astern to ahead\n{}",
+            STRIP_RANGE, report
+        );
+    }
+}
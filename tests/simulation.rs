@@ -0,0 +1,141 @@
+//! Dives run headless through `test_app`, checking the core simulation against
+//! the real systems
+
+use bevy::prelude::*;
+use submarine::{
+    test_app, BallastState, ControlCommand, Fish, GameState, Submarine, Surfaced, TestApp, Tuning,
+};
+
+/// A test app whose dives start `depth` meters down
+fn app_at_depth(depth: f32) -> App {
+    let mut app = test_app();
+    app.world_mut().resource_mut::<Tuning>().start_depth = depth;
+    app
+}
+
+#[test]
+fn vents_flood_the_ballast_tanks() {
+    let mut app = test_app();
+    app.start_dive()
+        .command(ControlCommand::ToggleVents)
+        .run_for(2.0);
+    let ballast = app.world().resource::<BallastState>();
+    assert!(ballast.vents_open);
+    assert!(
+        (0.55..0.65).contains(&ballast.fill_level),
+        "tanks at {:.2} after two seconds",
+        ballast.fill_level
+    );
+}
+
+#[test]
+fn air_valve_blows_the_ballast_tanks() {
+    let mut app = test_app();
+    app.start_dive()
+        .command(ControlCommand::ToggleVents)
+        .run_for(2.0)
+        .command(ControlCommand::ToggleAirValve)
+        .run_for(1.0);
+    let ballast = app.world().resource::<BallastState>();
+    assert!(!ballast.vents_open);
+    assert!(ballast.air_valve_open);
+    assert!(
+        ballast.fill_level < 0.3,
+        "tanks still at {:.2}",
+        ballast.fill_level
+    );
+    assert!(ballast.compressed_air < 1.0);
+}
+
+#[test]
+fn flooded_submarine_sinks() {
+    let mut app = test_app();
+    app.start_dive()
+        .command(ControlCommand::ToggleVents)
+        .run_for(3.0);
+    let depth = app.depth();
+    assert!(depth > 1.0, "only reached {:.2} m", depth);
+    app.run_for(1.0);
+    assert!(app.depth() > depth, "stopped sinking at {:.2} m", depth);
+}
+
+#[test]
+fn empty_tanks_rise_and_stop_at_the_surface() {
+    let mut app = app_at_depth(10.0);
+    app.start_dive();
+    assert!((app.depth() - 10.0).abs() < 0.1);
+    app.run_for(1.0);
+    assert!(app.depth() < 10.0, "did not rise from 10 m");
+    // Water drag holds the ascent to under a meter per second
+    app.run_for(15.0);
+    // Once up it rides the swell, which lifts and drops it by well under a meter
+    assert!(
+        app.world().resource::<Surfaced>().0 && app.depth().abs() < 1.0,
+        "settled at {:.2} m instead of the surface",
+        app.depth()
+    );
+}
+
+#[test]
+fn a_second_dive_leaves_one_submarine() {
+    let mut app = test_app();
+    app.start_dive().run_for(1.0).start_dive().run_for(1.0);
+    let mut query = app.world_mut().query_filtered::<(), With<Submarine>>();
+    assert_eq!(query.iter(app.world()).count(), 1);
+}
+
+#[test]
+fn oxygen_drains_underwater() {
+    let mut app = app_at_depth(15.0);
+    app.world_mut().resource_mut::<Tuning>().oxygen_rate = 10.0;
+    app.start_dive().run_for(1.0);
+    let game_state = app.world().resource::<GameState>();
+    assert!(
+        (89.0..91.0).contains(&game_state.oxygen),
+        "oxygen at {:.1}",
+        game_state.oxygen
+    );
+    assert_eq!(game_state.health, 100.0);
+}
+
+#[test]
+fn oxygen_refills_at_the_surface() {
+    let mut app = test_app();
+    app.start_dive();
+    app.world_mut().resource_mut::<GameState>().oxygen = 50.0;
+    app.run_for(2.0);
+    let oxygen = app.world().resource::<GameState>().oxygen;
+    assert!((59.0..61.0).contains(&oxygen), "oxygen at {:.1}", oxygen);
+}
+
+#[test]
+fn nearby_fish_is_collected() {
+    let mut app = test_app();
+    app.start_dive();
+    app.world_mut().resource_mut::<GameState>().oxygen = 50.0;
+    let position = app.submarine().translation + Vec3::X;
+    let fish = app
+        .world_mut()
+        .spawn((Fish, Transform::from_translation(position)))
+        .id();
+    app.run_for(0.1);
+    assert!(
+        app.world().get_entity(fish).is_err(),
+        "fish was not collected"
+    );
+    let oxygen = app.world().resource::<GameState>().oxygen;
+    assert!(oxygen >= 70.0, "collecting gave no oxygen ({:.1})", oxygen);
+}
+
+#[test]
+fn distant_fish_is_left_alone() {
+    let mut app = test_app();
+    app.start_dive();
+    let position = app.submarine().translation + Vec3::X * 30.0;
+    let fish = app
+        .world_mut()
+        .spawn((Fish, Transform::from_translation(position)))
+        .id();
+    app.run_for(0.1);
+    assert!(app.world().get_entity(fish).is_ok());
+}